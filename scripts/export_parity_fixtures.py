@@ -0,0 +1,93 @@
+"""Export encoder parity fixtures from PyTorch for rust-transformer.
+
+Builds a stack of ``torch.nn.TransformerEncoderLayer`` modules (post-norm,
+ReLU, no dropout), maps their weights onto rust-transformer parameter names
+and records the activations after every stage. Attention projection biases
+are zeroed because the crate's attention has no bias terms.
+
+Usage:
+    python scripts/export_parity_fixtures.py --out-dir fixtures
+then load ``fixtures/weights.npz`` with ``testing::load_parameters`` and check
+``fixtures/activations.npz`` with ``testing::encoder_parity``.
+"""
+
+import argparse
+import math
+from pathlib import Path
+
+import numpy as np
+import torch
+from torch import nn
+
+
+def positional_encoding(seq_len, d_model):
+    pe = np.zeros((seq_len, d_model))
+    for pos in range(seq_len):
+        for i in range(d_model):
+            angle = pos / 10000 ** ((2 * (i // 2)) / d_model)
+            pe[pos, i] = math.sin(angle) if i % 2 == 0 else math.cos(angle)
+    return torch.tensor(pe, dtype=torch.float64)
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--vocab-size", type=int, default=50)
+    parser.add_argument("--d-model", type=int, default=16)
+    parser.add_argument("--num-heads", type=int, default=4)
+    parser.add_argument("--d-ff", type=int, default=32)
+    parser.add_argument("--num-layers", type=int, default=2)
+    parser.add_argument("--seq-len", type=int, default=7)
+    parser.add_argument("--seed", type=int, default=0)
+    parser.add_argument("--out-dir", type=Path, default=Path("fixtures"))
+    args = parser.parse_args()
+
+    torch.manual_seed(args.seed)
+    d, heads = args.d_model, args.num_heads
+
+    embedding = nn.Embedding(args.vocab_size, d).double()
+    layers = [
+        nn.TransformerEncoderLayer(
+            d, heads, args.d_ff, dropout=0.0, activation="relu", batch_first=True
+        ).double()
+        for _ in range(args.num_layers)
+    ]
+
+    weights = {"encoder.embedding": embedding.weight}
+    with torch.no_grad():
+        for i, layer in enumerate(layers):
+            layer.eval()
+            layer.self_attn.in_proj_bias.zero_()
+            layer.self_attn.out_proj.bias.zero_()
+
+            prefix = f"encoder.layers.{i}"
+            in_proj = layer.self_attn.in_proj_weight
+            for j, name in enumerate(["w_q", "w_k", "w_v"]):
+                weights[f"{prefix}.self_attention.{name}"] = in_proj[j * d : (j + 1) * d].T
+            weights[f"{prefix}.self_attention.w_o"] = layer.self_attn.out_proj.weight.T
+            weights[f"{prefix}.feed_forward.w1"] = layer.linear1.weight.T
+            weights[f"{prefix}.feed_forward.b1"] = layer.linear1.bias[None, :]
+            weights[f"{prefix}.feed_forward.w2"] = layer.linear2.weight.T
+            weights[f"{prefix}.feed_forward.b2"] = layer.linear2.bias[None, :]
+            for norm in ["norm1", "norm2"]:
+                module = getattr(layer, norm)
+                weights[f"{prefix}.{norm}.gamma"] = module.weight[None, :]
+                weights[f"{prefix}.{norm}.beta"] = module.bias[None, :]
+
+        input_ids = torch.randint(1, args.vocab_size, (args.seq_len,))
+        x = embedding(input_ids) * math.sqrt(d) + positional_encoding(args.seq_len, d)
+        activations = {"input_ids": input_ids, "encoder.embeddings": x}
+        hidden = x[None]
+        for i, layer in enumerate(layers):
+            hidden = layer(hidden)
+            activations[f"encoder.layers.{i}.output"] = hidden[0]
+        activations["encoder.output"] = hidden[0]
+
+    args.out_dir.mkdir(parents=True, exist_ok=True)
+    to_numpy = lambda tensors: {k: v.detach().numpy() for k, v in tensors.items()}
+    np.savez(args.out_dir / "weights.npz", **to_numpy(weights))
+    np.savez(args.out_dir / "activations.npz", **to_numpy(activations))
+    print(f"wrote {len(weights)} weights and {len(activations)} activations to {args.out_dir}")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,7 @@
+//! Attention mechanisms.
+
+pub mod multi_head;
+pub mod scaled_dot_product;
+
+pub use multi_head::{KvCache, MultiHeadAttention, MultiHeadAttentionCache};
+pub use scaled_dot_product::{alibi_slopes, AttentionCache, ScaledDotProductAttention};
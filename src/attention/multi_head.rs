@@ -0,0 +1,506 @@
+//! Multi-head attention.
+
+use super::scaled_dot_product::{alibi_slopes, AttentionCache, ScaledDotProductAttention};
+use crate::config::InitScheme;
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::mask::Mask;
+use crate::utils::parallel;
+use crate::utils::rng::{thread_rng, Rng};
+use crate::utils::tensor_ops::FullyMaskedRow;
+use crate::{Result, TransformerError};
+
+/// Runs `num_heads` attention heads on learned projections of the inputs and
+/// mixes their concatenated outputs with `w_o`.
+///
+/// The query, key and value projections are fused `d_model × d_model`
+/// matrices; head `h` owns columns `h·d_k .. (h + 1)·d_k`, the same layout
+/// as PyTorch's `in_proj_weight` (transposed).
+#[derive(Debug, Clone)]
+pub struct MultiHeadAttention {
+    pub d_model: usize,
+    pub num_heads: usize,
+    pub d_k: usize,
+    /// Query projection, `d_model × d_model`.
+    pub w_q: Matrix,
+    /// Key projection, `d_model × d_model`.
+    pub w_k: Matrix,
+    /// Value projection, `d_model × d_model`.
+    pub w_v: Matrix,
+    /// Output projection, `d_model × d_model`.
+    pub w_o: Matrix,
+    /// Optional `1 × d_model` biases of the query, key, value and output
+    /// projections.
+    pub b_q: Option<Matrix>,
+    pub b_k: Option<Matrix>,
+    pub b_v: Option<Matrix>,
+    pub b_o: Option<Matrix>,
+    /// One scaled dot-product attention per head; they differ only in
+    /// their ALiBi slopes.
+    heads: Vec<ScaledDotProductAttention>,
+}
+
+impl MultiHeadAttention {
+    pub fn new(d_model: usize, num_heads: usize) -> Result<Self> {
+        Self::new_with_rng(d_model, num_heads, &mut thread_rng())
+    }
+
+    /// Like [`new`](Self::new), drawing the initial weights from `rng`.
+    pub fn new_with_rng(d_model: usize, num_heads: usize, rng: &mut Rng) -> Result<Self> {
+        Self::new_with_init(d_model, num_heads, InitScheme::default(), rng)
+    }
+
+    /// Like [`new_with_rng`](Self::new_with_rng), drawing the projections
+    /// under `init`.
+    pub fn new_with_init(
+        d_model: usize,
+        num_heads: usize,
+        init: InitScheme,
+        rng: &mut Rng,
+    ) -> Result<Self> {
+        if num_heads == 0 || !d_model.is_multiple_of(num_heads) {
+            return Err(TransformerError::config(format!(
+                "d_model ({}) must be divisible by num_heads ({})",
+                d_model, num_heads
+            )));
+        }
+        let d_k = d_model / num_heads;
+        Ok(Self {
+            d_model,
+            num_heads,
+            d_k,
+            w_q: init_heads(d_model, d_k, num_heads, init, rng)?,
+            w_k: init_heads(d_model, d_k, num_heads, init, rng)?,
+            w_v: init_heads(d_model, d_k, num_heads, init, rng)?,
+            w_o: init.weight(d_model, d_model, rng),
+            b_q: None,
+            b_k: None,
+            b_v: None,
+            b_o: None,
+            heads: vec![ScaledDotProductAttention::new(d_k); num_heads],
+        })
+    }
+
+    /// Adds zero-initialized biases to all four projections, or removes them.
+    pub fn with_bias(mut self, bias: bool) -> Self {
+        let make = || bias.then(|| Matrix::zeros(1, self.d_model));
+        (self.b_q, self.b_k, self.b_v, self.b_o) = (make(), make(), make(), make());
+        self
+    }
+
+    /// Sets the attention weights of queries whose keys are all masked
+    /// (zeros by default).
+    pub fn with_fully_masked_rows(mut self, fully_masked: FullyMaskedRow) -> Self {
+        for head in &mut self.heads {
+            *head = head.clone().with_fully_masked_rows(fully_masked);
+        }
+        self
+    }
+
+    /// Gives every head its ALiBi distance penalty, with the slopes of
+    /// [`alibi_slopes`](super::alibi_slopes). Meant for
+    /// self-attention, where queries and keys share positions.
+    pub fn with_alibi(mut self) -> Self {
+        for (head, slope) in self.heads.iter_mut().zip(alibi_slopes(self.num_heads)) {
+            *head = head.clone().with_alibi_slope(slope);
+        }
+        self
+    }
+
+    /// Restricts every head to a sliding window of keys; see
+    /// [`ScaledDotProductAttention::with_window`].
+    pub fn with_window(mut self, window: usize) -> Self {
+        for head in &mut self.heads {
+            *head = head.clone().with_window(window);
+        }
+        self
+    }
+
+    /// Attends from `query` (`n × d_model`) over `key`/`value` (`m × d_model`).
+    pub fn forward(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        let (q, k, v) = self.project_inputs(query, key, value)?;
+        Ok(self.attend(&q, &k, &v, mask, false)?.0)
+    }
+
+    /// Like [`forward`](Self::forward), also returning each head's `n × m`
+    /// attention probabilities.
+    pub fn forward_with_attentions(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        let (q, k, v) = self.project_inputs(query, key, value)?;
+        self.attend(&q, &k, &v, mask, true)
+    }
+
+    fn project_inputs(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+    ) -> Result<(Matrix, Matrix, Matrix)> {
+        for (name, x) in [("query", query), ("key", key), ("value", value)] {
+            if x.cols() != self.d_model {
+                return Err(TransformerError::shape(format!(
+                    "{} has {} features, expected d_model = {}",
+                    name,
+                    x.cols(),
+                    self.d_model
+                )));
+            }
+        }
+
+        Ok((
+            project(query, &self.w_q, &self.b_q)?,
+            project(key, &self.w_k, &self.b_k)?,
+            project(value, &self.w_v, &self.b_v)?,
+        ))
+    }
+
+    /// Projects `key`/`value` (`m × d_model`) for [`forward_cached`]
+    /// without attending, e.g. the encoder output once per generated
+    /// sequence.
+    ///
+    /// [`forward_cached`]: Self::forward_cached
+    pub fn project_kv(&self, key: &Matrix, value: &Matrix) -> Result<KvCache> {
+        let mut cache = KvCache::new(self.d_model);
+        self.extend_cache(&mut cache, key, value)?;
+        Ok(cache)
+    }
+
+    /// Projects `key`/`value` and appends them to `cache`.
+    pub fn extend_cache(&self, cache: &mut KvCache, key: &Matrix, value: &Matrix) -> Result<()> {
+        let k = project(key, &self.w_k, &self.b_k)?;
+        let v = project(value, &self.w_v, &self.b_v)?;
+        cache.keys = Matrix::vstack(&[&cache.keys, &k])?;
+        cache.values = Matrix::vstack(&[&cache.values, &v])?;
+        Ok(())
+    }
+
+    /// Attends from `query` (`n × d_model`) over every key and value in
+    /// `cache`. `mask` is `n × cache.len()`. Equal to [`forward`] on the
+    /// unprojected inputs the cache was built from.
+    ///
+    /// [`forward`]: Self::forward
+    pub fn forward_cached(
+        &self,
+        query: &Matrix,
+        cache: &KvCache,
+        mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        Ok(self.attend_cached(query, cache, mask, false)?.0)
+    }
+
+    /// Like [`forward_cached`](Self::forward_cached), also returning each
+    /// head's `n × cache.len()` attention probabilities.
+    pub fn forward_cached_with_attentions(
+        &self,
+        query: &Matrix,
+        cache: &KvCache,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        self.attend_cached(query, cache, mask, true)
+    }
+
+    fn attend_cached(
+        &self,
+        query: &Matrix,
+        cache: &KvCache,
+        mask: Option<&Mask>,
+        weights: bool,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        if query.cols() != self.d_model {
+            return Err(TransformerError::shape(format!(
+                "query has {} features, expected d_model = {}",
+                query.cols(),
+                self.d_model
+            )));
+        }
+        let q = project(query, &self.w_q, &self.b_q)?;
+        self.attend(&q, &cache.keys, &cache.values, mask, weights)
+    }
+
+    /// Per-head attention over projected inputs, followed by `w_o`. The
+    /// per-head probabilities are returned only if `weights` is set.
+    fn attend(
+        &self,
+        q: &Matrix,
+        k: &Matrix,
+        v: &Matrix,
+        mask: Option<&Mask>,
+        weights: bool,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        let mut concat = Matrix::zeros(q.rows(), self.d_model);
+        let mut attentions = Vec::with_capacity(self.num_heads);
+        let outputs = self.map_heads(q.rows() * k.rows(), |h, start| {
+            let (q, k, v) = (
+                q.columns(start, self.d_k)?,
+                k.columns(start, self.d_k)?,
+                v.columns(start, self.d_k)?,
+            );
+            if weights {
+                self.heads[h]
+                    .forward_with_weights(&q, &k, &v, mask)
+                    .map(|(out, w)| (out, Some(w)))
+            } else {
+                self.heads[h]
+                    .forward(&q, &k, &v, mask)
+                    .map(|out| (out, None))
+            }
+        });
+        for (h, output) in outputs.into_iter().enumerate() {
+            let start = h * self.d_k;
+            let (head, head_weights) = output?;
+            for (i, row) in head.row_iter().enumerate() {
+                concat.row_mut(i)[start..start + self.d_k].copy_from_slice(row);
+            }
+            attentions.extend(head_weights);
+        }
+        let out = project(&concat, &self.w_o, &self.b_o)?;
+        Ok((out, attentions))
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, MultiHeadAttentionCache)> {
+        let q = project(query, &self.w_q, &self.b_q)?;
+        let k = project(key, &self.w_k, &self.b_k)?;
+        let v = project(value, &self.w_v, &self.b_v)?;
+        let mut concat = Matrix::zeros(query.rows(), self.d_model);
+        let mut heads = Vec::with_capacity(self.num_heads);
+        let outputs = self.map_heads(q.rows() * k.rows(), |h, start| {
+            self.heads[h].forward_with_cache(
+                &q.columns(start, self.d_k)?,
+                &k.columns(start, self.d_k)?,
+                &v.columns(start, self.d_k)?,
+                mask,
+            )
+        });
+        for (h, output) in outputs.into_iter().enumerate() {
+            let start = h * self.d_k;
+            let (head, cache) = output?;
+            for (i, row) in head.row_iter().enumerate() {
+                concat.row_mut(i)[start..start + self.d_k].copy_from_slice(row);
+            }
+            heads.push(cache);
+        }
+        let out = project(&concat, &self.w_o, &self.b_o)?;
+        Ok((
+            out,
+            MultiHeadAttentionCache {
+                query: query.clone(),
+                key: key.clone(),
+                value: value.clone(),
+                heads,
+                concat,
+            },
+        ))
+    }
+
+    /// `f(h, first column of head h)` for every head, in parallel with the
+    /// `parallel` feature. `scores` is the size of one head's score matrix.
+    fn map_heads<R: Send>(&self, scores: usize, f: impl Fn(usize, usize) -> R + Sync) -> Vec<R> {
+        let heads: Vec<usize> = (0..self.num_heads).collect();
+        parallel::map(&heads, scores * self.d_model, |&h| f(h, h * self.d_k))
+    }
+
+    /// Adds the gradients of every projection to `grads` and returns the
+    /// gradients with respect to the query, key and value inputs. For
+    /// self-attention the three are summed by the caller.
+    pub fn backward(
+        &self,
+        cache: &MultiHeadAttentionCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<(Matrix, Matrix, Matrix)> {
+        let d_concat = project_backward(
+            &cache.concat,
+            &self.w_o,
+            self.b_o.is_some(),
+            grad,
+            prefix,
+            "o",
+            grads,
+        )?;
+        let mut d_q = Matrix::zeros(cache.query.rows(), self.d_model);
+        let mut d_k = Matrix::zeros(cache.key.rows(), self.d_model);
+        let mut d_v = Matrix::zeros(cache.value.rows(), self.d_model);
+        for (h, head) in cache.heads.iter().enumerate() {
+            let start = h * self.d_k;
+            let (dq, dk, dv) = self.heads[h].backward(head, &d_concat.columns(start, self.d_k)?)?;
+            for (full, part) in [(&mut d_q, dq), (&mut d_k, dk), (&mut d_v, dv)] {
+                for (i, row) in part.row_iter().enumerate() {
+                    full.row_mut(i)[start..start + self.d_k].copy_from_slice(row);
+                }
+            }
+        }
+        let d_query = project_backward(
+            &cache.query,
+            &self.w_q,
+            self.b_q.is_some(),
+            &d_q,
+            prefix,
+            "q",
+            grads,
+        )?;
+        let d_key = project_backward(
+            &cache.key,
+            &self.w_k,
+            self.b_k.is_some(),
+            &d_k,
+            prefix,
+            "k",
+            grads,
+        )?;
+        let d_value = project_backward(
+            &cache.value,
+            &self.w_v,
+            self.b_v.is_some(),
+            &d_v,
+            prefix,
+            "v",
+            grads,
+        )?;
+        Ok((d_query, d_key, d_value))
+    }
+}
+
+/// Inputs and intermediate values of
+/// [`MultiHeadAttention::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct MultiHeadAttentionCache {
+    pub query: Matrix,
+    pub key: Matrix,
+    pub value: Matrix,
+    pub heads: Vec<AttentionCache>,
+    /// Concatenated head outputs, the input of `w_o`.
+    pub concat: Matrix,
+}
+
+/// Projected keys and values of the positions attended over so far, reused
+/// across the steps of incremental decoding.
+#[derive(Debug, Clone)]
+pub struct KvCache {
+    /// Projected keys, `len × d_model`.
+    pub keys: Matrix,
+    /// Projected values, `len × d_model`.
+    pub values: Matrix,
+}
+
+impl KvCache {
+    /// An empty cache for a `d_model`-wide attention layer.
+    pub fn new(d_model: usize) -> Self {
+        Self {
+            keys: Matrix::zeros(0, d_model),
+            values: Matrix::zeros(0, d_model),
+        }
+    }
+
+    /// Positions cached so far.
+    pub fn len(&self) -> usize {
+        self.keys.rows()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// `x·w`, plus `bias` if present.
+fn project(x: &Matrix, w: &Matrix, bias: &Option<Matrix>) -> Result<Matrix> {
+    let projected = x.matmul(w)?;
+    match bias {
+        Some(b) => projected.add_row_vector(b),
+        None => Ok(projected),
+    }
+}
+
+/// Backward pass of [`project`]: accumulates the gradients of `w_{name}` and,
+/// if present, `b_{name}`, and returns the gradient with respect to `x`.
+fn project_backward(
+    x: &Matrix,
+    w: &Matrix,
+    has_bias: bool,
+    grad: &Matrix,
+    prefix: &str,
+    name: &str,
+    grads: &mut Gradients,
+) -> Result<Matrix> {
+    grads.accumulate(
+        &join_name(prefix, &format!("w_{}", name)),
+        &x.transpose().matmul(grad)?,
+    )?;
+    if has_bias {
+        grads.accumulate(
+            &join_name(prefix, &format!("b_{}", name)),
+            &grad.column_sums(),
+        )?;
+    }
+    grad.matmul(&w.transpose())
+}
+
+/// A fused `d_model × d_model` projection whose per-head column blocks are
+/// initialized as independent `d_model × d_k` matrices.
+fn init_heads(
+    d_model: usize,
+    d_k: usize,
+    num_heads: usize,
+    init: InitScheme,
+    rng: &mut Rng,
+) -> Result<Matrix> {
+    let heads: Vec<Matrix> = (0..num_heads)
+        .map(|_| init.weight(d_model, d_k, rng))
+        .collect();
+    Matrix::hstack(&heads.iter().collect::<Vec<_>>())
+}
+
+impl Parameters for MultiHeadAttention {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "w_q"), &self.w_q);
+        visitor(&join_name(prefix, "w_k"), &self.w_k);
+        visitor(&join_name(prefix, "w_v"), &self.w_v);
+        visitor(&join_name(prefix, "w_o"), &self.w_o);
+        for (name, bias) in [
+            ("b_q", &self.b_q),
+            ("b_k", &self.b_k),
+            ("b_v", &self.b_v),
+            ("b_o", &self.b_o),
+        ] {
+            if let Some(b) = bias {
+                visitor(&join_name(prefix, name), b);
+            }
+        }
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "w_q"), &mut self.w_q);
+        visitor(&join_name(prefix, "w_k"), &mut self.w_k);
+        visitor(&join_name(prefix, "w_v"), &mut self.w_v);
+        visitor(&join_name(prefix, "w_o"), &mut self.w_o);
+        for (name, bias) in [
+            ("b_q", &mut self.b_q),
+            ("b_k", &mut self.b_k),
+            ("b_v", &mut self.b_v),
+            ("b_o", &mut self.b_o),
+        ] {
+            if let Some(b) = bias {
+                visitor(&join_name(prefix, name), b);
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Scaled dot-product attention.
+
+use std::ops::Range;
+
+use crate::tensor::Matrix;
+use crate::utils::mask::{ensure_mask_shape, Mask};
+use crate::utils::parallel;
+use crate::utils::tensor_ops::{masked_softmax, masked_softmax_rows, FullyMaskedRow};
+use crate::{Result, TransformerError};
+
+/// `Attention(Q, K, V) = softmax(Q·Kᵀ / √d_k)·V`, optionally with an ALiBi
+/// distance penalty `−slope · |i − j|` added to the scores and optionally
+/// restricted to a sliding window of keys around each query.
+#[derive(Debug, Clone)]
+pub struct ScaledDotProductAttention {
+    scale: f64,
+    fully_masked: FullyMaskedRow,
+    alibi_slope: Option<f64>,
+    window: Option<usize>,
+}
+
+impl ScaledDotProductAttention {
+    /// Creates attention for keys of dimension `d_k`.
+    pub fn new(d_k: usize) -> Self {
+        Self {
+            scale: 1.0 / (d_k as f64).sqrt(),
+            fully_masked: FullyMaskedRow::default(),
+            alibi_slope: None,
+            window: None,
+        }
+    }
+
+    /// Penalizes the score of query `i` and key `j` by `slope · |i − j|`
+    /// (ALiBi). With fewer queries than keys the queries are taken to be the
+    /// last positions, as in incremental decoding.
+    pub fn with_alibi_slope(mut self, slope: f64) -> Self {
+        self.alibi_slope = Some(slope);
+        self
+    }
+
+    /// Lets query `i` attend only to keys at most `window` positions away on
+    /// either side, with queries positioned as for
+    /// [`with_alibi_slope`](Self::with_alibi_slope). Scores are computed
+    /// for the band alone, so the cost grows with `n · window` rather than
+    /// `n · m`. Combine with a causal mask for a causal sliding window.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// The sliding window set by [`with_window`](Self::with_window).
+    pub fn window(&self) -> Option<usize> {
+        self.window
+    }
+
+    /// Sets the attention weights of queries whose keys are all masked
+    /// (zeros by default).
+    pub fn with_fully_masked_rows(mut self, fully_masked: FullyMaskedRow) -> Self {
+        self.fully_masked = fully_masked;
+        self
+    }
+
+    /// Attends from `query` (`n × d_k`) over `key`/`value` (`m × d_k`).
+    ///
+    /// `mask`, if given, must be `n × m`; `false` entries are excluded. A
+    /// query with no visible key gets the fully-masked row policy.
+    pub fn forward(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        if self.window.is_some() {
+            self.check_inputs(query, key, value, mask)?;
+            return Ok(banded_output(&self.banded_probs(query, key, mask), value));
+        }
+        Ok(self.forward_with_weights(query, key, value, mask)?.0)
+    }
+
+    /// Like [`forward`](Self::forward), also returning the `n × m` attention
+    /// probabilities.
+    pub fn forward_with_weights(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, Matrix)> {
+        self.check_inputs(query, key, value, mask)?;
+        if self.window.is_some() {
+            let bands = self.banded_probs(query, key, mask);
+            let mut weights = Matrix::zeros(query.rows(), key.rows());
+            for (i, (start, probs)) in bands.iter().enumerate() {
+                weights.row_mut(i)[*start..start + probs.len()].copy_from_slice(probs);
+            }
+            return Ok((banded_output(&bands, value), weights));
+        }
+        let mut scores = query.matmul(&key.transpose())?.scale(self.scale);
+        if let Some(slope) = self.alibi_slope {
+            let offset = key.rows() as f64 - query.rows() as f64;
+            for i in 0..scores.rows() {
+                let position = i as f64 + offset;
+                for (j, s) in scores.row_mut(i).iter_mut().enumerate() {
+                    *s -= slope * (position - j as f64).abs();
+                }
+            }
+        }
+        let weights = masked_softmax_rows(&scores, mask, self.fully_masked)?;
+        Ok((weights.matmul(value)?, weights))
+    }
+
+    fn check_inputs(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<()> {
+        if key.rows() != value.rows() {
+            return Err(TransformerError::shape(format!(
+                "key and value lengths differ: {} vs {}",
+                key.rows(),
+                value.rows()
+            )));
+        }
+        if query.cols() != key.cols() {
+            return Err(TransformerError::shape(format!(
+                "query and key widths differ: {} vs {}",
+                query.cols(),
+                key.cols()
+            )));
+        }
+        if let Some(mask) = mask {
+            ensure_mask_shape(mask, (query.rows(), key.rows()), "attention mask")?;
+        }
+        Ok(())
+    }
+
+    /// The keys query `i` of `n` may see among `m`: all of them, or those
+    /// inside its window.
+    fn band(&self, i: usize, n: usize, m: usize) -> Range<usize> {
+        match self.window {
+            None => 0..m,
+            Some(window) => {
+                let position = (i + m) as isize - n as isize;
+                let window = window as isize;
+                let end = (position + window + 1).clamp(0, m as isize) as usize;
+                let start = ((position - window).max(0) as usize).min(end);
+                start..end
+            }
+        }
+    }
+
+    /// Each query's first visible key and its probabilities over its band,
+    /// computing scores inside the band only.
+    fn banded_probs(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Vec<(usize, Vec<f64>)> {
+        let (n, m, d) = (query.rows(), key.rows(), query.cols());
+        let offset = m as f64 - n as f64;
+        let rows: Vec<usize> = (0..n).collect();
+        let band_width = self.window.map_or(m, |w| (2 * w + 1).min(m));
+        parallel::map(&rows, n * band_width * d, |&i| {
+            let band = self.band(i, n, m);
+            let q = query.row(i);
+            let scores: Vec<f64> = band
+                .clone()
+                .map(|j| {
+                    let dot: f64 = q.iter().zip(key.row(j)).map(|(a, b)| a * b).sum();
+                    let penalty = self
+                        .alibi_slope
+                        .map_or(0.0, |slope| slope * (i as f64 + offset - j as f64).abs());
+                    dot * self.scale - penalty
+                })
+                .collect();
+            let keep = mask.map_or(&[][..], |mask| &mask.row(i)[band.clone()]);
+            (band.start, masked_softmax(&scores, keep, self.fully_masked))
+        })
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(
+        &self,
+        query: &Matrix,
+        key: &Matrix,
+        value: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, AttentionCache)> {
+        let (out, weights) = self.forward_with_weights(query, key, value, mask)?;
+        let (n, m) = (query.rows(), key.rows());
+        let fully_masked = (0..n)
+            .map(|i| {
+                let band = self.band(i, n, m);
+                (self.window.is_some() && band.is_empty())
+                    || mask.is_some_and(|mask| mask.row(i)[band].iter().all(|&keep| !keep))
+            })
+            .collect();
+        Ok((
+            out,
+            AttentionCache {
+                query: query.clone(),
+                key: key.clone(),
+                value: value.clone(),
+                weights,
+                fully_masked,
+            },
+        ))
+    }
+
+    /// Gradients with respect to the query, key and value, given the
+    /// gradient of the output.
+    pub fn backward(
+        &self,
+        cache: &AttentionCache,
+        grad: &Matrix,
+    ) -> Result<(Matrix, Matrix, Matrix)> {
+        let weights = &cache.weights;
+        let d_value = weights.transpose().matmul(grad)?;
+        let d_weights = grad.matmul(&cache.value.transpose())?;
+        // Softmax Jacobian per row: dS = P ⊙ (dP − Σ dP·P), scaled like S.
+        // Fully masked rows do not depend on the scores.
+        let mut d_scores = Matrix::zeros(weights.rows(), weights.cols());
+        for i in 0..weights.rows() {
+            if cache.fully_masked[i] {
+                continue;
+            }
+            let p = weights.row(i);
+            let dp = d_weights.row(i);
+            let dot: f64 = p.iter().zip(dp).map(|(p, d)| p * d).sum();
+            for ((out, p), d) in d_scores.row_mut(i).iter_mut().zip(p).zip(dp) {
+                *out = p * (d - dot) * self.scale;
+            }
+        }
+        let d_query = d_scores.matmul(&cache.key)?;
+        let d_key = d_scores.transpose().matmul(&cache.query)?;
+        Ok((d_query, d_key, d_value))
+    }
+}
+
+/// `Σ_j p_ij · v_j` for the banded probabilities of
+/// [`ScaledDotProductAttention::banded_probs`].
+fn banded_output(bands: &[(usize, Vec<f64>)], value: &Matrix) -> Matrix {
+    let mut out = Matrix::zeros(bands.len(), value.cols());
+    for (i, (start, probs)) in bands.iter().enumerate() {
+        let row = out.row_mut(i);
+        for (j, &p) in (*start..).zip(probs) {
+            for (o, v) in row.iter_mut().zip(value.row(j)) {
+                *o += p * v;
+            }
+        }
+    }
+    out
+}
+
+/// ALiBi slopes of `num_heads` heads: the geometric sequence `2^(−8h/n)`,
+/// `h = 1..=n`, for a power of two `n`; other head counts take the slopes
+/// of the next lower power of two followed by every other slope of the
+/// power above, as in the reference implementation.
+pub fn alibi_slopes(num_heads: usize) -> Vec<f64> {
+    let geometric = |n: usize| -> Vec<f64> {
+        (1..=n)
+            .map(|h| 2f64.powf(-8.0 * h as f64 / n as f64))
+            .collect()
+    };
+    if num_heads == 0 || num_heads.is_power_of_two() {
+        return geometric(num_heads);
+    }
+    let lower = 1 << num_heads.ilog2();
+    let mut slopes = geometric(lower);
+    slopes.extend(
+        geometric(2 * lower)
+            .into_iter()
+            .step_by(2)
+            .take(num_heads - lower),
+    );
+    slopes
+}
+
+/// Inputs and probabilities of [`ScaledDotProductAttention::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct AttentionCache {
+    pub query: Matrix,
+    pub key: Matrix,
+    pub value: Matrix,
+    /// `n × m` attention probabilities.
+    pub weights: Matrix,
+    /// Queries whose keys were all masked.
+    pub fully_masked: Vec<bool>,
+}
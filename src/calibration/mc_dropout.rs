@@ -0,0 +1,121 @@
+//! Monte Carlo dropout (Gal & Ghahramani, 2016).
+//!
+//! Running the model several times with dropout active approximates
+//! sampling from a posterior over weights. The spread of the sampled
+//! distributions separates uncertainty the model could reduce with more
+//! data (mutual information) from noise inherent in the task (expected
+//! entropy).
+
+use crate::tensor::Matrix;
+use crate::utils::tensor_ops::softmax_rows;
+use crate::{Result, Transformer, TransformerError};
+
+/// Aggregate of several stochastic predictions. Rows are positions (tokens)
+/// or examples, columns classes or vocabulary entries.
+#[derive(Debug, Clone)]
+pub struct UncertaintyEstimate {
+    /// Mean predicted distribution.
+    pub mean: Matrix,
+    /// Per-entry variance of the probabilities across passes.
+    pub variance: Matrix,
+    /// Entropy of the mean distribution per row (total uncertainty).
+    pub predictive_entropy: Vec<f64>,
+    /// Mean entropy of the individual passes per row (aleatoric part).
+    pub expected_entropy: Vec<f64>,
+    /// `predictive_entropy − expected_entropy` per row (epistemic part).
+    pub mutual_information: Vec<f64>,
+    pub passes: usize,
+}
+
+impl UncertaintyEstimate {
+    /// Most likely class of every row under the mean distribution.
+    pub fn predictions(&self) -> Vec<usize> {
+        self.mean
+            .row_iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .fold((0, f64::NEG_INFINITY), |best, (i, &p)| {
+                        if p > best.1 {
+                            (i, p)
+                        } else {
+                            best
+                        }
+                    })
+                    .0
+            })
+            .collect()
+    }
+}
+
+/// Combines probability matrices of equal shape, one per stochastic pass.
+pub fn summarize_samples(samples: &[Matrix]) -> Result<UncertaintyEstimate> {
+    let first = samples.first().ok_or("at least one sample is required")?;
+    let (rows, cols) = first.shape();
+    let n = samples.len() as f64;
+    let mut mean = Matrix::zeros(rows, cols);
+    let mut expected_entropy = vec![0.0; rows];
+    for sample in samples {
+        mean.add_assign(sample)?;
+        for (i, row) in sample.row_iter().enumerate() {
+            expected_entropy[i] += entropy(row) / n;
+        }
+    }
+    let mean = mean.scale(1.0 / n);
+    let mut variance = Matrix::zeros(rows, cols);
+    for sample in samples {
+        for (v, (p, m)) in variance
+            .as_mut_slice()
+            .iter_mut()
+            .zip(sample.as_slice().iter().zip(mean.as_slice()))
+        {
+            *v += (p - m).powi(2) / n;
+        }
+    }
+    let predictive_entropy: Vec<f64> = mean.row_iter().map(entropy).collect();
+    let mutual_information = predictive_entropy
+        .iter()
+        .zip(&expected_entropy)
+        .map(|(total, aleatoric)| (total - aleatoric).max(0.0))
+        .collect();
+    Ok(UncertaintyEstimate {
+        mean,
+        variance,
+        predictive_entropy,
+        expected_entropy,
+        mutual_information,
+        passes: samples.len(),
+    })
+}
+
+/// Runs `passes` forward passes of `model` on `src`/`tgt` with dropout
+/// active and summarizes the next-token distributions of every target
+/// position. The model's training flag is restored afterwards.
+pub fn mc_dropout_forward(
+    model: &mut Transformer,
+    src: &[usize],
+    tgt: &[usize],
+    passes: usize,
+) -> Result<UncertaintyEstimate> {
+    if passes == 0 {
+        return Err(TransformerError::config(
+            "MC dropout needs at least one pass",
+        ));
+    }
+    let was_training = model.is_training();
+    model.set_training(true);
+    let samples = (0..passes)
+        .map(|_| model.forward(src, tgt).map(|logits| softmax_rows(&logits)))
+        .collect::<Result<Vec<_>>>();
+    model.set_training(was_training);
+    summarize_samples(&samples?)
+}
+
+/// Shannon entropy in nats.
+fn entropy(probs: &[f64]) -> f64 {
+    -probs
+        .iter()
+        .filter(|&&p| p > 0.0)
+        .map(|p| p * p.ln())
+        .sum::<f64>()
+}
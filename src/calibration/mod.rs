@@ -0,0 +1,14 @@
+//! Predictive uncertainty and probability calibration.
+//!
+//! [`mc_dropout`] estimates how uncertain a model is by sampling dropout
+//! masks at inference time; [`temperature`] rescales logits so predicted
+//! probabilities match observed accuracy.
+
+pub mod mc_dropout;
+pub mod temperature;
+
+pub use mc_dropout::{mc_dropout_forward, summarize_samples, UncertaintyEstimate};
+pub use temperature::{
+    expected_calibration_error, negative_log_likelihood, next_token_logits, CalibrationObjective,
+    TemperatureScaling,
+};
@@ -0,0 +1,218 @@
+//! Post-hoc temperature scaling (Guo et al., 2017).
+//!
+//! Dividing logits by a single temperature `T` leaves predictions unchanged
+//! but sharpens (`T < 1`) or softens (`T > 1`) the probabilities. The
+//! temperature is fitted on held-out data and stored in
+//! [`TransformerConfig::logit_temperature`](crate::TransformerConfig), so it
+//! travels with the model configuration and is applied by generation and
+//! [`Transformer::calibrated_probabilities`].
+
+use crate::data::Dataset;
+use crate::tensor::Matrix;
+use crate::utils::tensor_ops::{argmax_row, log_sum_exp, softmax, softmax_rows};
+use crate::{Result, Transformer, TransformerError};
+
+/// What [`TemperatureScaling::fit`] minimizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CalibrationObjective {
+    /// Mean negative log-likelihood.
+    #[default]
+    Nll,
+    /// Expected calibration error over the given number of bins.
+    Ece { bins: usize },
+}
+
+/// A fitted temperature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureScaling {
+    pub temperature: f64,
+}
+
+impl Default for TemperatureScaling {
+    fn default() -> Self {
+        Self { temperature: 1.0 }
+    }
+}
+
+impl TemperatureScaling {
+    /// Searches `T ∈ [0.05, 20]` for the value minimizing `objective` on
+    /// `logits` (`examples × classes`) with true `labels`.
+    pub fn fit(logits: &Matrix, labels: &[usize], objective: CalibrationObjective) -> Result<Self> {
+        check_labels(logits, labels)?;
+        let loss = |log_t: f64| match objective {
+            CalibrationObjective::Nll => negative_log_likelihood(logits, labels, log_t.exp()),
+            CalibrationObjective::Ece { bins } => expected_calibration_error(
+                &softmax_rows(&logits.scale(1.0 / log_t.exp())),
+                labels,
+                bins,
+            ),
+        };
+        let (lo, hi) = (0.05f64.ln(), 20f64.ln());
+        // A coarse grid first, since ECE is not unimodal, then golden-section
+        // refinement around the best grid point.
+        const GRID: usize = 64;
+        let step = (hi - lo) / GRID as f64;
+        let best = (0..=GRID)
+            .map(|i| lo + step * i as f64)
+            .min_by(|a, b| loss(*a).total_cmp(&loss(*b)))
+            .unwrap_or(0.0);
+        let log_t = golden_section(&loss, (best - step).max(lo), (best + step).min(hi), 60);
+        Ok(Self {
+            temperature: log_t.exp(),
+        })
+    }
+
+    /// Collects teacher-forced next-token logits of `model` over `dataset`
+    /// (`(source, target)` pairs; targets without the start token, padding
+    /// ignored) and fits a temperature to them.
+    pub fn fit_transformer<D>(
+        model: &Transformer,
+        dataset: &D,
+        objective: CalibrationObjective,
+    ) -> Result<Self>
+    where
+        D: Dataset<Item = (Vec<usize>, Vec<usize>)>,
+    {
+        let (logits, labels) = next_token_logits(model, dataset)?;
+        Self::fit(&logits, &labels, objective)
+    }
+
+    /// `logits / T`.
+    pub fn apply(&self, logits: &Matrix) -> Matrix {
+        logits.scale(1.0 / self.temperature)
+    }
+
+    /// Calibrated probabilities of every row of `logits`.
+    pub fn probabilities(&self, logits: &Matrix) -> Matrix {
+        softmax_rows(&self.apply(logits))
+    }
+
+    /// Stores the temperature in `model`'s configuration.
+    pub fn store(&self, model: &mut Transformer) {
+        model.config.logit_temperature = self.temperature;
+    }
+}
+
+/// Teacher-forced logits and labels over `(source, target)` pairs.
+pub fn next_token_logits<D>(model: &Transformer, dataset: &D) -> Result<(Matrix, Vec<usize>)>
+where
+    D: Dataset<Item = (Vec<usize>, Vec<usize>)>,
+{
+    let pad = model.config.pad_token_id;
+    let mut rows = Vec::new();
+    let mut labels = Vec::new();
+    for index in 0..dataset.len() {
+        let (source, target) = dataset
+            .get(index)
+            .ok_or_else(|| format!("dataset has no example at index {}", index))?;
+        if target.is_empty() {
+            continue;
+        }
+        let mut input = vec![model.config.bos_token_id];
+        input.extend_from_slice(&target[..target.len() - 1]);
+        let logits = model.forward(&source, &input)?;
+        for (t, &label) in target.iter().enumerate() {
+            if label != pad {
+                rows.push(logits.row(t).to_vec());
+                labels.push(label);
+            }
+        }
+    }
+    Ok((Matrix::from_rows(&rows)?, labels))
+}
+
+/// Mean negative log-likelihood of `labels` under `softmax(logits / T)`.
+pub fn negative_log_likelihood(logits: &Matrix, labels: &[usize], temperature: f64) -> f64 {
+    let n = labels.len().max(1) as f64;
+    logits
+        .row_iter()
+        .zip(labels)
+        .map(|(row, &label)| {
+            let scaled: Vec<f64> = row.iter().map(|v| v / temperature).collect();
+            log_sum_exp(&scaled) - scaled[label]
+        })
+        .sum::<f64>()
+        / n
+}
+
+/// Expected calibration error: the confidence/accuracy gap averaged over
+/// `bins` equal-width confidence bins, weighted by bin size.
+pub fn expected_calibration_error(probs: &Matrix, labels: &[usize], bins: usize) -> f64 {
+    let bins = bins.max(1);
+    let mut count = vec![0usize; bins];
+    let mut confidence = vec![0.0; bins];
+    let mut correct = vec![0.0; bins];
+    for (row, &label) in probs.row_iter().zip(labels) {
+        let Some(predicted) = argmax_row(row) else {
+            continue;
+        };
+        let p = row[predicted];
+        let bin = ((p * bins as f64) as usize).min(bins - 1);
+        count[bin] += 1;
+        confidence[bin] += p;
+        if predicted == label {
+            correct[bin] += 1.0;
+        }
+    }
+    let total = labels.len().max(1) as f64;
+    (0..bins)
+        .filter(|&b| count[b] > 0)
+        .map(|b| (confidence[b] - correct[b]).abs() / total)
+        .sum()
+}
+
+impl Transformer {
+    /// Next-token probabilities for every target position, with logits
+    /// divided by the configured `logit_temperature`.
+    pub fn calibrated_probabilities(&self, src: &[usize], tgt: &[usize]) -> Result<Matrix> {
+        let t = self.config.logit_temperature;
+        let logits = self.forward(src, tgt)?;
+        let mut out = logits.clone();
+        for i in 0..logits.rows() {
+            let row: Vec<f64> = logits.row(i).iter().map(|v| v / t).collect();
+            out.row_mut(i).copy_from_slice(&softmax(&row));
+        }
+        Ok(out)
+    }
+}
+
+fn check_labels(logits: &Matrix, labels: &[usize]) -> Result<()> {
+    if logits.rows() != labels.len() {
+        return Err(TransformerError::shape(format!(
+            "{} rows of logits but {} labels",
+            logits.rows(),
+            labels.len()
+        )));
+    }
+    if labels.is_empty() {
+        return Err("temperature scaling needs at least one example".into());
+    }
+    if let Some(&bad) = labels.iter().find(|&&l| l >= logits.cols()) {
+        return Err(format!("label {} out of range for {} classes", bad, logits.cols()).into());
+    }
+    Ok(())
+}
+
+/// Minimizes `f` on `[lo, hi]` assuming it is unimodal there.
+fn golden_section(f: &dyn Fn(f64) -> f64, mut lo: f64, mut hi: f64, iterations: usize) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let mut a = hi - ratio * (hi - lo);
+    let mut b = lo + ratio * (hi - lo);
+    let (mut fa, mut fb) = (f(a), f(b));
+    for _ in 0..iterations {
+        if fa < fb {
+            hi = b;
+            b = a;
+            fb = fa;
+            a = hi - ratio * (hi - lo);
+            fa = f(a);
+        } else {
+            lo = a;
+            a = b;
+            fa = fb;
+            b = lo + ratio * (hi - lo);
+            fb = f(b);
+        }
+    }
+    (lo + hi) / 2.0
+}
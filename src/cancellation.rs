@@ -0,0 +1,55 @@
+//! Cooperative cancellation of long-running loops.
+//!
+//! A [`CancellationToken`] is a shared flag: clones observe the same state,
+//! so one thread can hand a clone to a generation or training call and
+//! cancel it from another. Loops check the token between steps and stop
+//! cleanly, returning what they have produced so far.
+//!
+//! Honoured by:
+//!
+//! - generation through [`GenerationConfig::with_cancellation`]: the
+//!   sequence decoded so far is returned
+//! - [`DataParallelTrainer::with_cancellation`]: the epoch ends after the
+//!   current step on every rank, with
+//!   [`EpochStats::cancelled`](crate::distributed::EpochStats::cancelled) set
+//! - the `server` feature's `InferenceServer`: it stops accepting
+//!   connections and running generations return early
+//!
+//! [`GenerationConfig::with_cancellation`]: crate::generation::GenerationConfig::with_cancellation
+//! [`DataParallelTrainer::with_cancellation`]: crate::distributed::DataParallelTrainer::with_cancellation
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A cancellation flag shared by all of its clones.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation. Irreversible.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Wraps an existing flag; setting it to `true` cancels the token.
+impl From<Arc<AtomicBool>> for CancellationToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+}
+
+/// Tokens are equal when they share a flag.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
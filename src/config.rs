@@ -0,0 +1,450 @@
+//! Model configuration.
+//!
+//! A [`TransformerConfig`] round-trips through JSON
+//! ([`to_json_file`](TransformerConfig::to_json_file) /
+//! [`from_json_file`](TransformerConfig::from_json_file)), so experiment
+//! settings can live in versioned files rather than in code.
+
+use std::fs;
+use std::path::Path;
+
+use crate::layers::ActivationType;
+use crate::tensor::Matrix;
+use crate::utils::json::Json;
+use crate::utils::rng::Rng;
+use crate::{Result, TransformerError};
+
+/// How a model encodes token positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionalScheme {
+    /// A sine/cosine table of `max_seq_len` rows added to the token
+    /// embeddings.
+    #[default]
+    Sinusoidal,
+    /// ALiBi (Press et al., 2022): nothing is added to the embeddings;
+    /// every self-attention head instead penalizes its scores linearly in
+    /// the query-key distance, with a fixed per-head slope. Inputs may be
+    /// longer than `max_seq_len`.
+    Alibi,
+    /// A trained `max_seq_len × d_model` table added to the token
+    /// embeddings, as in BERT and Marian checkpoints. It is a parameter of
+    /// the model, named `positional` under the encoder and decoder.
+    Learned,
+}
+
+impl PositionalScheme {
+    /// Name used in saved configurations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PositionalScheme::Sinusoidal => "sinusoidal",
+            PositionalScheme::Alibi => "alibi",
+            PositionalScheme::Learned => "learned",
+        }
+    }
+
+    /// Inverse of [`name`](Self::name).
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "sinusoidal" => Ok(PositionalScheme::Sinusoidal),
+            "alibi" => Ok(PositionalScheme::Alibi),
+            "learned" => Ok(PositionalScheme::Learned),
+            other => Err(TransformerError::config(format!(
+                "unknown positional scheme '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+/// The normalization after every encoder and decoder sub-layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormType {
+    /// [`LayerNorm`](crate::layers::LayerNorm): centered and rescaled,
+    /// with a learned shift.
+    #[default]
+    LayerNorm,
+    /// [`RMSNorm`](crate::layers::RMSNorm): rescaled by the root mean
+    /// square only, as in LLaMA-family models.
+    RMSNorm,
+}
+
+impl NormType {
+    /// Name used in saved configurations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NormType::LayerNorm => "layer_norm",
+            NormType::RMSNorm => "rms_norm",
+        }
+    }
+
+    /// Inverse of [`name`](Self::name).
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "layer_norm" => Ok(NormType::LayerNorm),
+            "rms_norm" => Ok(NormType::RMSNorm),
+            other => Err(TransformerError::config(format!(
+                "unknown norm type '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+/// How weight matrices are drawn when a model is built. Biases and
+/// LayerNorm parameters always start at zero and one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InitScheme {
+    /// Xavier/Glorot normal, `std = sqrt(2 / (fan_in + fan_out))`.
+    #[default]
+    Xavier,
+    /// Kaiming/He normal for ReLU networks, `std = sqrt(2 / fan_in)`.
+    Kaiming,
+    /// Every weight, embeddings included, from `N(0, std_dev²)`, as in
+    /// GPT-2 and BERT (`std_dev = 0.02`).
+    Normal { std_dev: f64 },
+}
+
+impl InitScheme {
+    /// A `fan_in × fan_out` projection.
+    pub fn weight(&self, fan_in: usize, fan_out: usize, rng: &mut Rng) -> Matrix {
+        match *self {
+            InitScheme::Xavier => Matrix::xavier(fan_in, fan_out, rng),
+            InitScheme::Kaiming => {
+                Matrix::random_normal(fan_in, fan_out, (2.0 / fan_in as f64).sqrt(), rng)
+            }
+            InitScheme::Normal { std_dev } => Matrix::random_normal(fan_in, fan_out, std_dev, rng),
+        }
+    }
+
+    /// A `vocab_size × dim` embedding table: `N(0, 1/dim)` unless the scheme
+    /// is [`Normal`](InitScheme::Normal).
+    pub fn embedding(&self, vocab_size: usize, dim: usize, rng: &mut Rng) -> Matrix {
+        let std_dev = match *self {
+            InitScheme::Normal { std_dev } => std_dev,
+            _ => (dim as f64).powf(-0.5),
+        };
+        Matrix::random_normal(vocab_size, dim, std_dev, rng)
+    }
+
+    /// Name used in saved configurations: `xavier`, `kaiming` or
+    /// `normal:<std_dev>`.
+    pub fn name(&self) -> String {
+        match self {
+            InitScheme::Xavier => "xavier".to_string(),
+            InitScheme::Kaiming => "kaiming".to_string(),
+            InitScheme::Normal { std_dev } => format!("normal:{}", std_dev),
+        }
+    }
+
+    /// Inverse of [`name`](Self::name).
+    pub fn from_name(name: &str) -> Result<Self> {
+        let scheme = match name {
+            "xavier" => InitScheme::Xavier,
+            "kaiming" => InitScheme::Kaiming,
+            other => match other.strip_prefix("normal:").map(str::parse::<f64>) {
+                Some(Ok(std_dev)) => InitScheme::Normal { std_dev },
+                _ => {
+                    return Err(TransformerError::config(format!(
+                        "unknown init scheme '{}'",
+                        other
+                    )))
+                }
+            },
+        };
+        Ok(scheme)
+    }
+}
+
+/// A seed and scheme that together fix every initial weight: models built
+/// from equal configurations and equal `InitConfig`s are identical.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct InitConfig {
+    pub seed: u64,
+    pub scheme: InitScheme,
+}
+
+impl InitConfig {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            scheme: InitScheme::default(),
+        }
+    }
+
+    pub fn with_scheme(mut self, scheme: InitScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// The generator weights are drawn from.
+    pub fn rng(&self) -> Rng {
+        Rng::seed_from_u64(self.seed)
+    }
+}
+
+/// Hyper-parameters of an encoder-decoder [`Transformer`](crate::models::Transformer).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformerConfig {
+    /// Number of tokens in the vocabulary.
+    pub vocab_size: usize,
+    /// Width of every hidden representation.
+    pub d_model: usize,
+    /// Number of attention heads; must divide `d_model`.
+    pub num_heads: usize,
+    pub num_encoder_layers: usize,
+    pub num_decoder_layers: usize,
+    /// Inner width of the position-wise feed-forward networks.
+    pub d_ff: usize,
+    /// Nonlinearity inside every feed-forward network.
+    pub activation: ActivationType,
+    /// Longest sequence the positional encoding covers, and the limit
+    /// generation works within.
+    pub max_seq_len: usize,
+    /// How token positions are made visible to attention.
+    pub positional: PositionalScheme,
+    /// If set, self-attention only looks this many positions either side of
+    /// each token; see
+    /// [`ScaledDotProductAttention::with_window`](crate::attention::ScaledDotProductAttention::with_window).
+    /// Attention over the encoder output stays global.
+    pub attention_window: Option<usize>,
+    /// How weights are drawn when the model is built.
+    pub init: InitScheme,
+    /// Dropout probability applied after every sub-layer while training.
+    pub dropout: f64,
+    /// Normalization after every sub-layer.
+    pub norm: NormType,
+    /// Epsilon of every normalization, whichever [`NormType`].
+    pub layer_norm_eps: f64,
+    pub pad_token_id: usize,
+    /// Token the decoder starts generating from.
+    pub bos_token_id: usize,
+    /// Token that ends generation.
+    pub eos_token_id: usize,
+    /// Whether token embeddings are multiplied by `√d_model` before
+    /// positions are added. BERT and some Marian checkpoints leave them
+    /// unscaled.
+    pub scale_embeddings: bool,
+    /// Whether the encoder and decoder use one token embedding table.
+    pub share_embeddings: bool,
+    /// Whether attention query/key/value/output projections have biases.
+    pub attention_bias: bool,
+    /// Whether the decoder's vocabulary projection has a bias.
+    pub output_bias: bool,
+    /// Output logits are divided by this before sampling; set by
+    /// [`TemperatureScaling`](crate::calibration::TemperatureScaling).
+    pub logit_temperature: f64,
+}
+
+impl Default for TransformerConfig {
+    /// A small configuration that runs comfortably on a laptop.
+    fn default() -> Self {
+        Self {
+            vocab_size: 1000,
+            d_model: 128,
+            num_heads: 4,
+            num_encoder_layers: 2,
+            num_decoder_layers: 2,
+            d_ff: 512,
+            activation: ActivationType::ReLU,
+            max_seq_len: 128,
+            positional: PositionalScheme::Sinusoidal,
+            attention_window: None,
+            init: InitScheme::Xavier,
+            dropout: 0.1,
+            norm: NormType::LayerNorm,
+            layer_norm_eps: 1e-5,
+            pad_token_id: 0,
+            bos_token_id: 1,
+            eos_token_id: 2,
+            scale_embeddings: true,
+            share_embeddings: false,
+            attention_bias: false,
+            output_bias: false,
+            logit_temperature: 1.0,
+        }
+    }
+}
+
+impl TransformerConfig {
+    /// Checks that the configuration describes a buildable model.
+    pub fn validate(&self) -> Result<()> {
+        if self.vocab_size == 0 || self.d_model == 0 || self.d_ff == 0 || self.max_seq_len == 0 {
+            return Err(TransformerError::config(
+                "vocab_size, d_model, d_ff and max_seq_len must be non-zero",
+            ));
+        }
+        if self.num_heads == 0 || !self.d_model.is_multiple_of(self.num_heads) {
+            return Err(TransformerError::config(format!(
+                "d_model ({}) must be divisible by num_heads ({})",
+                self.d_model, self.num_heads
+            )));
+        }
+        if !(0.0..1.0).contains(&self.dropout) {
+            return Err(TransformerError::config(format!(
+                "dropout must be in [0, 1), got {}",
+                self.dropout
+            )));
+        }
+        if self.layer_norm_eps <= 0.0 {
+            return Err(TransformerError::config("layer_norm_eps must be positive"));
+        }
+        if !(self.logit_temperature > 0.0 && self.logit_temperature.is_finite()) {
+            return Err(TransformerError::config(format!(
+                "logit_temperature must be positive, got {}",
+                self.logit_temperature
+            )));
+        }
+        for (name, id) in [
+            ("pad_token_id", self.pad_token_id),
+            ("bos_token_id", self.bos_token_id),
+            ("eos_token_id", self.eos_token_id),
+        ] {
+            if id >= self.vocab_size {
+                return Err(TransformerError::config(format!(
+                    "{} ({}) must be smaller than vocab_size ({})",
+                    name, id, self.vocab_size
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Dimension of each attention head.
+    pub fn d_k(&self) -> usize {
+        self.d_model / self.num_heads
+    }
+
+    /// The configuration as a JSON object with one key per field.
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("vocab_size", Json::from(self.vocab_size)),
+            ("d_model", Json::from(self.d_model)),
+            ("num_heads", Json::from(self.num_heads)),
+            ("num_encoder_layers", Json::from(self.num_encoder_layers)),
+            ("num_decoder_layers", Json::from(self.num_decoder_layers)),
+            ("d_ff", Json::from(self.d_ff)),
+            ("activation", Json::from(self.activation.name())),
+            ("max_seq_len", Json::from(self.max_seq_len)),
+            ("positional", Json::from(self.positional.name())),
+            (
+                "attention_window",
+                self.attention_window.map_or(Json::Null, Json::from),
+            ),
+            ("init", Json::from(self.init.name())),
+            ("dropout", Json::from(self.dropout)),
+            ("norm", Json::from(self.norm.name())),
+            ("layer_norm_eps", Json::from(self.layer_norm_eps)),
+            ("pad_token_id", Json::from(self.pad_token_id)),
+            ("bos_token_id", Json::from(self.bos_token_id)),
+            ("eos_token_id", Json::from(self.eos_token_id)),
+            ("scale_embeddings", Json::from(self.scale_embeddings)),
+            ("share_embeddings", Json::from(self.share_embeddings)),
+            ("attention_bias", Json::from(self.attention_bias)),
+            ("output_bias", Json::from(self.output_bias)),
+            ("logit_temperature", Json::from(self.logit_temperature)),
+        ])
+    }
+
+    /// Reads a configuration written by [`to_json`](Self::to_json). Missing
+    /// keys keep their [`Default`] values; unknown keys are rejected so a
+    /// misspelt field does not go unnoticed. The result is validated.
+    pub fn from_json(json: &Json) -> Result<Self> {
+        let pairs = json
+            .as_object()
+            .ok_or_else(|| TransformerError::config("configuration must be a JSON object"))?;
+        let mut config = Self::default();
+        for (key, value) in pairs {
+            let usize_value = || {
+                value.as_usize().ok_or_else(|| {
+                    TransformerError::config(format!("{} must be a non-negative integer", key))
+                })
+            };
+            let f64_value = || {
+                value
+                    .as_f64()
+                    .ok_or_else(|| TransformerError::config(format!("{} must be a number", key)))
+            };
+            let bool_value = || {
+                value
+                    .as_bool()
+                    .ok_or_else(|| TransformerError::config(format!("{} must be a boolean", key)))
+            };
+            match key.as_str() {
+                "vocab_size" => config.vocab_size = usize_value()?,
+                "d_model" => config.d_model = usize_value()?,
+                "num_heads" => config.num_heads = usize_value()?,
+                "num_encoder_layers" => config.num_encoder_layers = usize_value()?,
+                "num_decoder_layers" => config.num_decoder_layers = usize_value()?,
+                "d_ff" => config.d_ff = usize_value()?,
+                "activation" => {
+                    config.activation =
+                        parse_activation(value.as_str().ok_or_else(|| {
+                            TransformerError::config("activation must be a string")
+                        })?)?
+                }
+                "max_seq_len" => config.max_seq_len = usize_value()?,
+                "positional" => {
+                    config.positional =
+                        PositionalScheme::from_name(value.as_str().ok_or_else(|| {
+                            TransformerError::config("positional must be a string")
+                        })?)?
+                }
+                "attention_window" => {
+                    config.attention_window = match value {
+                        Json::Null => None,
+                        _ => Some(usize_value()?),
+                    }
+                }
+                "init" => {
+                    config.init = InitScheme::from_name(
+                        value
+                            .as_str()
+                            .ok_or_else(|| TransformerError::config("init must be a string"))?,
+                    )?
+                }
+                "dropout" => config.dropout = f64_value()?,
+                "norm" => {
+                    config.norm = NormType::from_name(
+                        value
+                            .as_str()
+                            .ok_or_else(|| TransformerError::config("norm must be a string"))?,
+                    )?
+                }
+                "layer_norm_eps" => config.layer_norm_eps = f64_value()?,
+                "pad_token_id" => config.pad_token_id = usize_value()?,
+                "bos_token_id" => config.bos_token_id = usize_value()?,
+                "eos_token_id" => config.eos_token_id = usize_value()?,
+                "scale_embeddings" => config.scale_embeddings = bool_value()?,
+                "share_embeddings" => config.share_embeddings = bool_value()?,
+                "attention_bias" => config.attention_bias = bool_value()?,
+                "output_bias" => config.output_bias = bool_value()?,
+                "logit_temperature" => config.logit_temperature = f64_value()?,
+                other => {
+                    return Err(TransformerError::config(format!(
+                        "unknown configuration key '{}'",
+                        other
+                    )))
+                }
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Writes the configuration to `path` as pretty-printed JSON.
+    pub fn to_json_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.to_json().to_pretty_string())?;
+        Ok(())
+    }
+
+    /// Reads a configuration from a JSON file; see [`from_json`](Self::from_json).
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&Json::parse(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// [`ActivationType::from_name`], failing with a configuration error.
+pub(crate) fn parse_activation(name: &str) -> Result<ActivationType> {
+    ActivationType::from_name(name)
+        .ok_or_else(|| TransformerError::config(format!("unknown activation '{}'", name)))
+}
@@ -0,0 +1,161 @@
+//! Batching over a [`Dataset`].
+
+use super::Dataset;
+use crate::utils::rng::{mix64, Rng};
+
+/// Cuts a dataset into batches, optionally in a fresh random order every
+/// epoch. The order depends only on the seed and the epoch, so an epoch can
+/// be replayed exactly.
+#[derive(Debug, Clone)]
+pub struct DataLoader<'a, D> {
+    dataset: &'a D,
+    batch_size: usize,
+    shuffle: bool,
+    seed: u64,
+    drop_last: bool,
+}
+
+impl<'a, D: Dataset> DataLoader<'a, D> {
+    /// Batches of `batch_size` examples in dataset order.
+    pub fn new(dataset: &'a D, batch_size: usize) -> Self {
+        Self {
+            dataset,
+            batch_size: batch_size.max(1),
+            shuffle: false,
+            seed: 0,
+            drop_last: false,
+        }
+    }
+
+    /// Permutes the examples every epoch, seeded by `seed`.
+    pub fn with_shuffle(mut self, shuffle: bool, seed: u64) -> Self {
+        self.shuffle = shuffle;
+        self.seed = seed;
+        self
+    }
+
+    /// Skips the last batch when it is smaller than the batch size.
+    pub fn with_drop_last(mut self, drop_last: bool) -> Self {
+        self.drop_last = drop_last;
+        self
+    }
+
+    pub fn dataset(&self) -> &'a D {
+        self.dataset
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Number of batches in one epoch.
+    pub fn num_batches(&self) -> usize {
+        if self.drop_last {
+            self.dataset.len() / self.batch_size
+        } else {
+            self.dataset.len().div_ceil(self.batch_size)
+        }
+    }
+
+    /// The index order of `epoch`, before it is cut into batches.
+    pub fn order(&self, epoch: u64) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.dataset.len()).collect();
+        if self.shuffle {
+            Rng::seed_from_u64(mix64(self.seed ^ mix64(epoch))).shuffle(&mut order);
+        }
+        order
+    }
+
+    /// The dataset indices of every batch of `epoch`.
+    pub fn batch_indices(&self, epoch: u64) -> Vec<Vec<usize>> {
+        let mut batches: Vec<Vec<usize>> = self
+            .order(epoch)
+            .chunks(self.batch_size)
+            .map(<[usize]>::to_vec)
+            .collect();
+        batches.truncate(self.num_batches());
+        batches
+    }
+
+    /// The batches of `epoch`.
+    pub fn batches(&self, epoch: u64) -> impl Iterator<Item = Vec<D::Item>> + '_ {
+        self.batch_indices(epoch).into_iter().map(|indices| {
+            indices
+                .iter()
+                .filter_map(|&i| self.dataset.get(i))
+                .collect()
+        })
+    }
+
+    /// The batches of `epoch` as token sequences padded with `pad_token_id`
+    /// to the longest sequence of each batch.
+    pub fn padded_batches(
+        &self,
+        epoch: u64,
+        pad_token_id: usize,
+    ) -> impl Iterator<Item = PaddedBatch> + '_
+    where
+        D::Item: AsRef<[usize]>,
+    {
+        self.batches(epoch)
+            .map(move |batch| pad_batch(&batch, pad_token_id))
+    }
+}
+
+/// Token sequences padded to a common length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaddedBatch {
+    /// One row per sequence, each `max_len` tokens long.
+    pub tokens: Vec<Vec<usize>>,
+    /// Length of every sequence before padding.
+    pub lengths: Vec<usize>,
+}
+
+impl PaddedBatch {
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Length of every row.
+    pub fn max_len(&self) -> usize {
+        self.lengths.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Per row, `true` at real tokens and `false` at padding.
+    pub fn attention_mask(&self) -> Vec<Vec<bool>> {
+        let max_len = self.max_len();
+        self.lengths
+            .iter()
+            .map(|&len| (0..max_len).map(|j| j < len).collect())
+            .collect()
+    }
+
+    /// Fraction of positions that are padding.
+    pub fn padding_ratio(&self) -> f64 {
+        let total = self.len() * self.max_len();
+        if total == 0 {
+            return 0.0;
+        }
+        let real: usize = self.lengths.iter().sum();
+        (total - real) as f64 / total as f64
+    }
+}
+
+/// Pads every sequence of `batch` with `pad_token_id` to the longest one.
+pub fn pad_batch<S: AsRef<[usize]>>(batch: &[S], pad_token_id: usize) -> PaddedBatch {
+    let lengths: Vec<usize> = batch.iter().map(|s| s.as_ref().len()).collect();
+    let max_len = lengths.iter().copied().max().unwrap_or(0);
+    let tokens = batch
+        .iter()
+        .map(|s| {
+            let mut row = s.as_ref().to_vec();
+            row.resize(max_len, pad_token_id);
+            row
+        })
+        .collect();
+    PaddedBatch { tokens, lengths }
+}
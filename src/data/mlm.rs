@@ -0,0 +1,104 @@
+//! Random masking for masked language modeling.
+
+use crate::config::TransformerConfig;
+use crate::utils::rng::Rng;
+
+/// One sequence prepared for masked language modeling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaskedTokens {
+    /// The model input, with the selected positions corrupted.
+    pub input: Vec<usize>,
+    /// The original tokens, the prediction targets.
+    pub labels: Vec<usize>,
+    /// `true` at the positions selected for prediction; only these count in
+    /// the loss.
+    pub masked: Vec<bool>,
+}
+
+impl MaskedTokens {
+    /// Number of positions selected for prediction.
+    pub fn num_masked(&self) -> usize {
+        self.masked.iter().filter(|&&m| m).count()
+    }
+}
+
+/// Corrupts token sequences with BERT's masking scheme (Devlin et al.,
+/// 2019): each non-special token is selected with probability
+/// `mask_probability`; a selected token is replaced by the mask token 80% of
+/// the time, by a uniformly random token 10% of the time, and kept unchanged
+/// otherwise.
+#[derive(Debug, Clone)]
+pub struct MlmCollator {
+    pub mask_token_id: usize,
+    /// Range of the random replacement tokens.
+    pub vocab_size: usize,
+    pub mask_probability: f64,
+    /// Tokens that are never selected, such as padding and sentence markers.
+    pub special_tokens: Vec<usize>,
+    rng: Rng,
+}
+
+impl MlmCollator {
+    /// Masks 15% of the tokens, drawing from a generator seeded by `seed`.
+    pub fn new(mask_token_id: usize, vocab_size: usize, seed: u64) -> Self {
+        Self {
+            mask_token_id,
+            vocab_size,
+            mask_probability: 0.15,
+            special_tokens: vec![mask_token_id],
+            rng: Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// A collator for `config`'s vocabulary that never selects its padding,
+    /// start or end tokens.
+    pub fn for_model(config: &TransformerConfig, mask_token_id: usize, seed: u64) -> Self {
+        Self::new(mask_token_id, config.vocab_size, seed).with_special_tokens(&[
+            config.pad_token_id,
+            config.bos_token_id,
+            config.eos_token_id,
+        ])
+    }
+
+    pub fn with_mask_probability(mut self, probability: f64) -> Self {
+        self.mask_probability = probability;
+        self
+    }
+
+    /// Adds `tokens` to the tokens that are never selected.
+    pub fn with_special_tokens(mut self, tokens: &[usize]) -> Self {
+        self.special_tokens.extend_from_slice(tokens);
+        self
+    }
+
+    /// Masks one sequence.
+    pub fn mask(&mut self, tokens: &[usize]) -> MaskedTokens {
+        let mut input = tokens.to_vec();
+        let mut masked = vec![false; tokens.len()];
+        for (i, &token) in tokens.iter().enumerate() {
+            if self.special_tokens.contains(&token) || !self.rng.bernoulli(self.mask_probability) {
+                continue;
+            }
+            masked[i] = true;
+            let roll = self.rng.next_f64();
+            if roll < 0.8 {
+                input[i] = self.mask_token_id;
+            } else if roll < 0.9 {
+                input[i] = self.rng.below(self.vocab_size.max(1));
+            }
+        }
+        MaskedTokens {
+            input,
+            labels: tokens.to_vec(),
+            masked,
+        }
+    }
+
+    /// Masks every sequence of `batch`.
+    pub fn mask_batch<S: AsRef<[usize]>>(&mut self, batch: &[S]) -> Vec<MaskedTokens> {
+        batch
+            .iter()
+            .map(|tokens| self.mask(tokens.as_ref()))
+            .collect()
+    }
+}
@@ -0,0 +1,128 @@
+//! Dataset abstractions.
+//!
+//! A [`Dataset`] is an indexable collection of examples. Splits and other
+//! views are expressed as [`Subset`]s, which borrow the underlying dataset and
+//! only store indices, so no examples are copied until they are requested.
+//! A [`DataLoader`] cuts a dataset into shuffled batches and pads token
+//! sequences to the longest one of each batch; corpora too large for memory
+//! are read, tokenized and batched lazily through a [`CorpusReader`] and a
+//! [`StreamLoader`]. An [`MlmCollator`] masks token sequences for masked
+//! language modeling.
+
+mod loader;
+mod mlm;
+mod split;
+mod stream;
+mod text;
+
+pub use loader::{pad_batch, DataLoader, PaddedBatch};
+pub use mlm::{MaskedTokens, MlmCollator};
+pub use split::{DatasetSplit, SplitRatios};
+pub use stream::{CorpusReader, DocumentSplit, StreamLoader, TokenStream};
+pub use text::TextDataset;
+
+use crate::{Result, TransformerError};
+
+/// An indexable collection of training examples.
+pub trait Dataset {
+    /// The type of a single example.
+    type Item;
+
+    /// Number of examples in the dataset.
+    fn len(&self) -> usize;
+
+    /// Returns the example at `index`, or `None` if it is out of range.
+    fn get(&self, index: usize) -> Option<Self::Item>;
+
+    /// Returns `true` if the dataset contains no examples.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Randomly partitions the dataset into train/validation/test subsets.
+    ///
+    /// The permutation depends only on `seed`, so the same seed always yields
+    /// the same split for a dataset of the same length. The train and
+    /// validation sizes are rounded down; the test subset takes the
+    /// remainder. Fails if `ratios` does not [validate](SplitRatios::validate).
+    fn split(&self, ratios: SplitRatios, seed: u64) -> Result<DatasetSplit<Subset<'_, Self>>>
+    where
+        Self: Sized,
+    {
+        split::split_by_fraction(self, ratios, seed)
+    }
+
+    /// Partitions the dataset by hashing a key extracted from each example.
+    ///
+    /// An example always lands in the same subset regardless of dataset order
+    /// or size, which keeps splits stable when examples are added later.
+    /// `seed` salts the hash so different experiments can use different
+    /// assignments. Fails if `ratios` does not
+    /// [validate](SplitRatios::validate).
+    fn split_by_key<K, F>(
+        &self,
+        ratios: SplitRatios,
+        seed: u64,
+        key: F,
+    ) -> Result<DatasetSplit<Subset<'_, Self>>>
+    where
+        Self: Sized,
+        K: AsRef<[u8]>,
+        F: Fn(&Self::Item) -> K,
+    {
+        split::split_by_key(self, ratios, seed, key)
+    }
+}
+
+impl<T: Clone> Dataset for Vec<T> {
+    type Item = T;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn get(&self, index: usize) -> Option<T> {
+        self.as_slice().get(index).cloned()
+    }
+}
+
+/// A view over a subset of another dataset's examples.
+#[derive(Debug, Clone)]
+pub struct Subset<'a, D> {
+    dataset: &'a D,
+    indices: Vec<usize>,
+}
+
+impl<'a, D: Dataset> Subset<'a, D> {
+    /// Creates a subset from indices into `dataset`.
+    pub fn new(dataset: &'a D, indices: Vec<usize>) -> Result<Self> {
+        if let Some(&bad) = indices.iter().find(|&&i| i >= dataset.len()) {
+            return Err(TransformerError::shape(format!(
+                "subset index {} out of range for dataset of length {}",
+                bad,
+                dataset.len()
+            )));
+        }
+        Ok(Self { dataset, indices })
+    }
+
+    /// Indices of the selected examples in the parent dataset.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+impl<D: Dataset> Dataset for Subset<'_, D> {
+    type Item = D::Item;
+
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn get(&self, index: usize) -> Option<D::Item> {
+        self.indices
+            .as_slice()
+            .get(index)
+            .and_then(|&i| self.dataset.get(i))
+    }
+}
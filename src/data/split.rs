@@ -0,0 +1,142 @@
+//! Reproducible train/validation/test splitting.
+
+use super::{Dataset, Subset};
+use crate::utils::rng::{fnv1a64, mix64, Rng};
+use crate::{Result, TransformerError};
+
+/// Fractions of a dataset assigned to each split. They must sum to one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitRatios {
+    pub train: f64,
+    pub validation: f64,
+    pub test: f64,
+}
+
+impl SplitRatios {
+    /// Creates split ratios, checking that they are non-negative and sum to one.
+    pub fn new(train: f64, validation: f64, test: f64) -> Result<Self> {
+        let ratios = Self {
+            train,
+            validation,
+            test,
+        };
+        ratios.validate()?;
+        Ok(ratios)
+    }
+
+    /// Checks that the ratios are non-negative, finite and sum to one.
+    pub fn validate(&self) -> Result<()> {
+        let parts = [self.train, self.validation, self.test];
+        if parts.iter().any(|p| !p.is_finite() || *p < 0.0) {
+            return Err(TransformerError::config(format!(
+                "split ratios must be non-negative, got {:?}",
+                parts
+            )));
+        }
+        let sum: f64 = parts.iter().sum();
+        if (sum - 1.0).abs() > 1e-6 {
+            return Err(TransformerError::config(format!(
+                "split ratios must sum to 1, got {}",
+                sum
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SplitRatios {
+    /// The common 80/10/10 split.
+    fn default() -> Self {
+        Self {
+            train: 0.8,
+            validation: 0.1,
+            test: 0.1,
+        }
+    }
+}
+
+/// The three partitions produced by a split.
+#[derive(Debug, Clone)]
+pub struct DatasetSplit<D> {
+    pub train: D,
+    pub validation: D,
+    pub test: D,
+}
+
+pub(super) fn split_by_fraction<D: Dataset>(
+    dataset: &D,
+    ratios: SplitRatios,
+    seed: u64,
+) -> Result<DatasetSplit<Subset<'_, D>>> {
+    ratios.validate()?;
+    let n = dataset.len();
+    let mut indices: Vec<usize> = (0..n).collect();
+    Rng::seed_from_u64(seed).shuffle(&mut indices);
+
+    let n_train = ((n as f64 * ratios.train).floor() as usize).min(n);
+    let n_validation = ((n as f64 * ratios.validation).floor() as usize).min(n - n_train);
+
+    let test = indices.split_off(n_train + n_validation);
+    let validation = indices.split_off(n_train);
+    Ok(DatasetSplit {
+        train: Subset { dataset, indices },
+        validation: Subset {
+            dataset,
+            indices: validation,
+        },
+        test: Subset {
+            dataset,
+            indices: test,
+        },
+    })
+}
+
+pub(super) fn split_by_key<D, K, F>(
+    dataset: &D,
+    ratios: SplitRatios,
+    seed: u64,
+    key: F,
+) -> Result<DatasetSplit<Subset<'_, D>>>
+where
+    D: Dataset,
+    K: AsRef<[u8]>,
+    F: Fn(&D::Item) -> K,
+{
+    ratios.validate()?;
+    let mut train = Vec::new();
+    let mut validation = Vec::new();
+    let mut test = Vec::new();
+
+    for index in 0..dataset.len() {
+        let Some(item) = dataset.get(index) else {
+            continue;
+        };
+        let mut bytes = seed.to_le_bytes().to_vec();
+        bytes.extend_from_slice(key(&item).as_ref());
+        // Map the top 53 bits of the hash onto [0, 1).
+        let unit = (mix64(fnv1a64(&bytes)) >> 11) as f64 / (1u64 << 53) as f64;
+
+        if unit < ratios.train {
+            train.push(index);
+        } else if unit < ratios.train + ratios.validation {
+            validation.push(index);
+        } else {
+            test.push(index);
+        }
+    }
+
+    Ok(DatasetSplit {
+        train: Subset {
+            dataset,
+            indices: train,
+        },
+        validation: Subset {
+            dataset,
+            indices: validation,
+        },
+        test: Subset {
+            dataset,
+            indices: test,
+        },
+    })
+}
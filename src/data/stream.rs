@@ -0,0 +1,231 @@
+//! Corpora read lazily from disk.
+//!
+//! A [`CorpusReader`] yields one document at a time from a plain or
+//! gzip-compressed file, [`CorpusReader::tokenize`] turns the documents into
+//! token sequences as they are pulled, and a [`StreamLoader`] batches and
+//! pads them like a [`DataLoader`](super::DataLoader). Only the current
+//! document and the shuffle buffer are held in memory.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use super::loader::{pad_batch, PaddedBatch};
+use crate::tokenizer::Tokenizer;
+use crate::utils::gzip::{is_gzip, GzipReader};
+use crate::utils::rng::Rng;
+use crate::Result;
+
+/// How a corpus file is cut into documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocumentSplit {
+    /// Every non-empty line is a document.
+    #[default]
+    Line,
+    /// Documents are runs of non-empty lines separated by blank lines; the
+    /// lines of a document are joined with `\n`.
+    BlankLine,
+}
+
+/// An iterator over the documents of a text stream.
+pub struct CorpusReader {
+    reader: Box<dyn BufRead + Send>,
+    split: DocumentSplit,
+    line: String,
+    done: bool,
+}
+
+impl CorpusReader {
+    /// Reads documents from `reader`, one per non-empty line.
+    pub fn new(reader: impl BufRead + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(reader),
+            split: DocumentSplit::Line,
+            line: String::new(),
+            done: false,
+        }
+    }
+
+    /// Opens the file at `path`, decompressing it on the fly if it starts
+    /// with the gzip magic number.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        if is_gzip(reader.fill_buf()?) {
+            Ok(Self::new(GzipReader::new(reader)))
+        } else {
+            Ok(Self::new(reader))
+        }
+    }
+
+    pub fn with_split(mut self, split: DocumentSplit) -> Self {
+        self.split = split;
+        self
+    }
+
+    /// Tokenizes each document as it is read, keeping at most `max_len`
+    /// tokens. Documents that tokenize to nothing are skipped, as in
+    /// [`TextDataset::from_texts`](super::TextDataset::from_texts).
+    pub fn tokenize(self, tokenizer: &dyn Tokenizer, max_len: Option<usize>) -> TokenStream<'_> {
+        TokenStream {
+            documents: self,
+            tokenizer,
+            max_len,
+        }
+    }
+
+    /// The next line without its terminator, or `None` at the end.
+    fn next_line(&mut self) -> Result<Option<&str>> {
+        self.line.clear();
+        if self.reader.read_line(&mut self.line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.line.trim_end_matches(['\n', '\r'])))
+    }
+
+    fn next_document(&mut self) -> Result<Option<String>> {
+        let split = self.split;
+        let mut document = String::new();
+        while let Some(line) = self.next_line()? {
+            if line.trim().is_empty() {
+                if !document.is_empty() {
+                    return Ok(Some(document));
+                }
+                continue;
+            }
+            match split {
+                DocumentSplit::Line => return Ok(Some(line.to_string())),
+                DocumentSplit::BlankLine => {
+                    if !document.is_empty() {
+                        document.push('\n');
+                    }
+                    document.push_str(line);
+                }
+            }
+        }
+        Ok((!document.is_empty()).then_some(document))
+    }
+}
+
+impl Iterator for CorpusReader {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Result<String>> {
+        if self.done {
+            return None;
+        }
+        let document = self.next_document().transpose();
+        // A read error leaves the stream in an unknown position, so stop.
+        if !matches!(document, Some(Ok(_))) {
+            self.done = true;
+        }
+        document
+    }
+}
+
+/// Token sequences tokenized lazily from a [`CorpusReader`].
+pub struct TokenStream<'t> {
+    documents: CorpusReader,
+    tokenizer: &'t dyn Tokenizer,
+    max_len: Option<usize>,
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = Result<Vec<usize>>;
+
+    fn next(&mut self) -> Option<Result<Vec<usize>>> {
+        for document in self.documents.by_ref() {
+            let mut ids = match document.and_then(|text| self.tokenizer.encode(&text)) {
+                Ok(ids) => ids,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some(max_len) = self.max_len {
+                ids.truncate(max_len);
+            }
+            if !ids.is_empty() {
+                return Some(Ok(ids));
+            }
+        }
+        None
+    }
+}
+
+/// Padded batches cut from a stream of token sequences.
+///
+/// A stream cannot be permuted as a whole, so shuffling draws each example
+/// at random from a buffer of upcoming sequences. Larger buffers mix more
+/// thoroughly at the cost of memory.
+pub struct StreamLoader<I> {
+    sequences: I,
+    batch_size: usize,
+    pad_token_id: usize,
+    buffer: Vec<Vec<usize>>,
+    buffer_size: usize,
+    rng: Option<Rng>,
+    drop_last: bool,
+    exhausted: bool,
+}
+
+impl<I: Iterator<Item = Result<Vec<usize>>>> StreamLoader<I> {
+    /// Batches of `batch_size` sequences in stream order, padded with
+    /// `pad_token_id`.
+    pub fn new(sequences: I, batch_size: usize, pad_token_id: usize) -> Self {
+        Self {
+            sequences,
+            batch_size: batch_size.max(1),
+            pad_token_id,
+            buffer: Vec::new(),
+            buffer_size: 1,
+            rng: None,
+            drop_last: false,
+            exhausted: false,
+        }
+    }
+
+    /// Draws examples at random from a buffer of `buffer_size` sequences,
+    /// seeded by `seed`.
+    pub fn with_shuffle_buffer(mut self, buffer_size: usize, seed: u64) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self.rng = Some(Rng::seed_from_u64(seed));
+        self
+    }
+
+    /// Skips the last batch when it is smaller than the batch size.
+    pub fn with_drop_last(mut self, drop_last: bool) -> Self {
+        self.drop_last = drop_last;
+        self
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+}
+
+impl<I: Iterator<Item = Result<Vec<usize>>>> Iterator for StreamLoader<I> {
+    type Item = Result<PaddedBatch>;
+
+    fn next(&mut self) -> Option<Result<PaddedBatch>> {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        while batch.len() < self.batch_size {
+            while !self.exhausted && self.buffer.len() < self.buffer_size {
+                match self.sequences.next() {
+                    Some(Ok(sequence)) => self.buffer.push(sequence),
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => self.exhausted = true,
+                }
+            }
+            if self.buffer.is_empty() {
+                break;
+            }
+            let index = match &mut self.rng {
+                Some(rng) => rng.below(self.buffer.len()),
+                None => 0,
+            };
+            batch.push(self.buffer.swap_remove(index));
+        }
+
+        if batch.is_empty() || (self.drop_last && batch.len() < self.batch_size) {
+            return None;
+        }
+        Some(Ok(pad_batch(&batch, self.pad_token_id)))
+    }
+}
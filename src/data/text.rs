@@ -0,0 +1,71 @@
+//! Tokenized text held in memory.
+
+use super::Dataset;
+use crate::tokenizer::Tokenizer;
+use crate::Result;
+
+/// Token sequences, one per document, each truncated to an optional
+/// maximum length.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextDataset {
+    sequences: Vec<Vec<usize>>,
+}
+
+impl TextDataset {
+    /// Wraps already tokenized sequences.
+    pub fn new(sequences: Vec<Vec<usize>>) -> Self {
+        Self { sequences }
+    }
+
+    /// Tokenizes every text of `texts` with `tokenizer`, keeping at most
+    /// `max_len` tokens of each. Empty documents are dropped.
+    pub fn from_texts<S: AsRef<str>>(
+        tokenizer: &dyn Tokenizer,
+        texts: &[S],
+        max_len: Option<usize>,
+    ) -> Result<Self> {
+        let mut sequences = Vec::with_capacity(texts.len());
+        for text in texts {
+            let mut ids = tokenizer.encode(text.as_ref())?;
+            if let Some(max_len) = max_len {
+                ids.truncate(max_len);
+            }
+            if !ids.is_empty() {
+                sequences.push(ids);
+            }
+        }
+        Ok(Self { sequences })
+    }
+
+    /// Like [`from_texts`](Self::from_texts), with one document per
+    /// non-empty line of `text`.
+    pub fn from_lines(
+        tokenizer: &dyn Tokenizer,
+        text: &str,
+        max_len: Option<usize>,
+    ) -> Result<Self> {
+        let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+        Self::from_texts(tokenizer, &lines, max_len)
+    }
+
+    pub fn sequences(&self) -> &[Vec<usize>] {
+        &self.sequences
+    }
+
+    /// Total number of tokens over all sequences.
+    pub fn num_tokens(&self) -> usize {
+        self.sequences.iter().map(Vec::len).sum()
+    }
+}
+
+impl Dataset for TextDataset {
+    type Item = Vec<usize>;
+
+    fn len(&self) -> usize {
+        self.sequences.len()
+    }
+
+    fn get(&self, index: usize) -> Option<Vec<usize>> {
+        self.sequences.as_slice().get(index).cloned()
+    }
+}
@@ -0,0 +1,234 @@
+//! Collective operations over a group of model replicas.
+//!
+//! [`Collective`] abstracts how replicas communicate: across processes with
+//! [`TcpProcessGroup`](super::TcpProcessGroup) or across threads of one
+//! process with [`ThreadGroup`]. The helpers on top average gradients or
+//! weights, which is all that synchronous data parallelism, local SGD and
+//! model soups need.
+
+use std::sync::{Arc, Condvar, Mutex};
+
+use super::{flatten_gradients, unflatten_gradients};
+use crate::params::{Gradients, Parameters};
+use crate::{Result, TransformerError};
+
+/// Communication between the replicas of a group.
+pub trait Collective {
+    /// This replica's index in `0..world_size`.
+    fn rank(&self) -> usize;
+
+    fn world_size(&self) -> usize;
+
+    /// Replaces `values` on every rank with their element-wise sum.
+    fn all_reduce_sum(&mut self, values: &mut [f64]) -> Result<()>;
+
+    /// Copies rank 0's `values` to every other rank.
+    fn broadcast(&mut self, values: &mut [f64]) -> Result<()>;
+
+    /// Blocks until every rank has reached the barrier.
+    fn barrier(&mut self) -> Result<()> {
+        self.all_reduce_sum(&mut [])
+    }
+}
+
+/// Replaces `values` on every rank with their element-wise mean.
+pub fn all_reduce_mean<C: Collective + ?Sized>(group: &mut C, values: &mut [f64]) -> Result<()> {
+    group.all_reduce_sum(values)?;
+    let world = group.world_size() as f64;
+    for v in values {
+        *v /= world;
+    }
+    Ok(())
+}
+
+/// Averages `grads` across ranks in place. Weights of `model` without a
+/// gradient on some rank count as zero there.
+pub fn average_gradients<C: Collective + ?Sized>(
+    group: &mut C,
+    model: &dyn Parameters,
+    grads: &mut Gradients,
+) -> Result<()> {
+    let mut flat = flatten_gradients(model, grads)?;
+    all_reduce_mean(group, &mut flat)?;
+    *grads = unflatten_gradients(model, &flat)?;
+    Ok(())
+}
+
+/// Replaces every weight of `model` with its mean across ranks (the
+/// synchronization step of local SGD).
+pub fn average_parameters<C: Collective + ?Sized>(
+    group: &mut C,
+    model: &mut dyn Parameters,
+) -> Result<()> {
+    let mut flat = flatten_parameters(model);
+    all_reduce_mean(group, &mut flat)?;
+    load_flat_parameters(model, &flat)
+}
+
+/// Overwrites every rank's weights with those of rank 0.
+pub fn broadcast_parameters<C: Collective + ?Sized>(
+    group: &mut C,
+    model: &mut dyn Parameters,
+) -> Result<()> {
+    let mut flat = flatten_parameters(model);
+    group.broadcast(&mut flat)?;
+    load_flat_parameters(model, &flat)
+}
+
+/// Element-wise mean of several gradient sets, e.g. from replicas trained
+/// sequentially in one thread. Names missing from a set count as zero.
+pub fn mean_gradients(sets: &[Gradients]) -> Result<Gradients> {
+    let mut mean = Gradients::new();
+    for grads in sets {
+        for (name, grad) in grads.iter() {
+            mean.accumulate(name, grad)?;
+        }
+    }
+    if !sets.is_empty() {
+        mean.scale(1.0 / sets.len() as f64);
+    }
+    Ok(mean)
+}
+
+/// Sets the weights of all `replicas` to their element-wise mean.
+pub fn average_replicas<M: Parameters>(replicas: &mut [M]) -> Result<()> {
+    let Some(first) = replicas.first() else {
+        return Ok(());
+    };
+    let mut mean = vec![0.0; first.num_parameters()];
+    for replica in replicas.iter() {
+        let flat = flatten_parameters(replica);
+        if flat.len() != mean.len() {
+            return Err(TransformerError::shape(
+                "replicas have different parameter counts",
+            ));
+        }
+        for (m, v) in mean.iter_mut().zip(&flat) {
+            *m += v;
+        }
+    }
+    let count = replicas.len() as f64;
+    for m in &mut mean {
+        *m /= count;
+    }
+    for replica in replicas.iter_mut() {
+        load_flat_parameters(replica, &mean)?;
+    }
+    Ok(())
+}
+
+/// Every weight of `model`, in visiting order, as one vector.
+pub fn flatten_parameters(model: &dyn Parameters) -> Vec<f64> {
+    let mut flat = Vec::with_capacity(model.num_parameters());
+    model.visit_parameters("", &mut |_, p| flat.extend_from_slice(p.as_slice()));
+    flat
+}
+
+/// Inverse of [`flatten_parameters`].
+pub fn load_flat_parameters(model: &mut dyn Parameters, flat: &[f64]) -> Result<()> {
+    let expected = model.num_parameters();
+    if flat.len() != expected {
+        return Err(TransformerError::shape(format!(
+            "flat parameters have {} values, model has {} weights",
+            flat.len(),
+            expected
+        )));
+    }
+    let mut offset = 0;
+    model.visit_parameters_mut("", &mut |_, p| {
+        let len = p.len();
+        p.as_mut_slice()
+            .copy_from_slice(&flat[offset..offset + len]);
+        offset += len;
+    });
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct Rendezvous {
+    arrived: usize,
+    generation: u64,
+    sum: Vec<f64>,
+    result: Vec<f64>,
+    error: Option<String>,
+}
+
+/// An in-process group whose ranks run on different threads.
+#[derive(Debug, Clone)]
+pub struct ThreadGroup {
+    rank: usize,
+    world_size: usize,
+    shared: Arc<(Mutex<Rendezvous>, Condvar)>,
+}
+
+impl ThreadGroup {
+    /// Creates one handle per rank; move each into its worker thread.
+    pub fn new(world_size: usize) -> Vec<ThreadGroup> {
+        let shared = Arc::new((Mutex::new(Rendezvous::default()), Condvar::new()));
+        (0..world_size)
+            .map(|rank| ThreadGroup {
+                rank,
+                world_size,
+                shared: shared.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Collective for ThreadGroup {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn world_size(&self) -> usize {
+        self.world_size
+    }
+
+    fn all_reduce_sum(&mut self, values: &mut [f64]) -> Result<()> {
+        if self.world_size <= 1 {
+            return Ok(());
+        }
+        let (lock, ready) = &*self.shared;
+        let mut state = lock.lock().map_err(|_| "thread group poisoned")?;
+        if state.arrived == 0 {
+            state.sum.clear();
+            state.sum.extend_from_slice(values);
+            state.error = None;
+        } else if state.sum.len() != values.len() {
+            state.error = Some(format!(
+                "collective size mismatch: rank {} sent {} values, expected {}",
+                self.rank,
+                values.len(),
+                state.sum.len()
+            ));
+        } else {
+            for (s, v) in state.sum.iter_mut().zip(values.iter()) {
+                *s += v;
+            }
+        }
+        state.arrived += 1;
+        let generation = state.generation;
+        if state.arrived == self.world_size {
+            state.result = std::mem::take(&mut state.sum);
+            state.arrived = 0;
+            state.generation += 1;
+            ready.notify_all();
+        } else {
+            while state.generation == generation {
+                state = ready.wait(state).map_err(|_| "thread group poisoned")?;
+            }
+        }
+        if let Some(error) = &state.error {
+            return Err(error.clone().into());
+        }
+        values.copy_from_slice(&state.result);
+        Ok(())
+    }
+
+    fn broadcast(&mut self, values: &mut [f64]) -> Result<()> {
+        if self.rank != 0 {
+            values.fill(0.0);
+        }
+        self.all_reduce_sum(values)
+    }
+}
@@ -0,0 +1,261 @@
+//! Data-parallel training across processes.
+//!
+//! Each of `world_size` workers trains on its own shard of the data (see
+//! [`shard_indices`]), computes gradients locally and averages them with
+//! the other workers before every optimizer step, so all replicas stay
+//! identical. Workers communicate through a [`Collective`]: a
+//! [`TcpProcessGroup`] across processes or a [`ThreadGroup`] within one.
+
+pub mod collective;
+pub mod tcp;
+
+pub use collective::{
+    average_gradients, average_parameters, average_replicas, broadcast_parameters, mean_gradients,
+    Collective, ThreadGroup,
+};
+pub use tcp::{DistributedConfig, TcpProcessGroup};
+
+use crate::cancellation::CancellationToken;
+use crate::data::{Dataset, Subset};
+use crate::optim::Optimizer;
+use crate::params::{Gradients, Parameters};
+use crate::progress::{NoProgress, Progress, ProgressHandler, Stage};
+use crate::tensor::Matrix;
+use crate::utils::rng::{mix64, Rng};
+use crate::{Result, TransformerError};
+
+/// Indices of the examples `rank` trains on in `epoch`.
+///
+/// All ranks shuffle `0..len` with the same permutation (derived from `seed`
+/// and `epoch`), pad it by wrapping around to a multiple of `world_size` and
+/// take every `world_size`-th index starting at `rank`. Shards are therefore
+/// disjoint up to the padding and equally long, which keeps the
+/// collectives of all workers in lockstep.
+pub fn shard_indices(
+    len: usize,
+    rank: usize,
+    world_size: usize,
+    seed: u64,
+    epoch: u64,
+) -> Vec<usize> {
+    if len == 0 || world_size == 0 {
+        return Vec::new();
+    }
+    let mut order: Vec<usize> = (0..len).collect();
+    Rng::seed_from_u64(mix64(seed ^ mix64(epoch))).shuffle(&mut order);
+    let padded = len.div_ceil(world_size) * world_size;
+    (rank..padded)
+        .step_by(world_size)
+        .map(|i| order[i % len])
+        .collect()
+}
+
+/// The shard of `dataset` assigned to `rank` (see [`shard_indices`]).
+pub fn shard<D: Dataset>(
+    dataset: &D,
+    rank: usize,
+    world_size: usize,
+    seed: u64,
+    epoch: u64,
+) -> Result<Subset<'_, D>> {
+    Subset::new(
+        dataset,
+        shard_indices(dataset.len(), rank, world_size, seed, epoch),
+    )
+}
+
+/// Gradients of every weight of `model`, in visiting order, as one vector.
+/// Weights without a gradient contribute zeros.
+pub fn flatten_gradients(model: &dyn Parameters, grads: &Gradients) -> Result<Vec<f64>> {
+    let mut flat = Vec::new();
+    let mut result: Result<()> = Ok(());
+    model.visit_parameters("", &mut |name, param| match grads.get(name) {
+        Some(grad) => {
+            if let Err(e) = grad.ensure_shape(param.shape(), name) {
+                result = Err(e);
+            }
+            flat.extend_from_slice(grad.as_slice());
+        }
+        None => flat.resize(flat.len() + param.len(), 0.0),
+    });
+    result.map(|_| flat)
+}
+
+/// Inverse of [`flatten_gradients`].
+pub fn unflatten_gradients(model: &dyn Parameters, flat: &[f64]) -> Result<Gradients> {
+    let mut grads = Gradients::new();
+    let mut offset = 0;
+    let mut result: Result<()> = Ok(());
+    model.visit_parameters("", &mut |name, param| {
+        let end = offset + param.len();
+        match flat.get(offset..end) {
+            Some(values) => grads.insert(
+                name,
+                Matrix::from_vec(param.rows(), param.cols(), values.to_vec())
+                    .expect("slice length matches the parameter shape"),
+            ),
+            None => {
+                result = Err(format!("flat gradient too short for {}", name).into());
+            }
+        }
+        offset = end;
+    });
+    result?;
+    if offset != flat.len() {
+        return Err(TransformerError::shape(format!(
+            "flat gradient has {} values, model has {} weights",
+            flat.len(),
+            offset
+        )));
+    }
+    Ok(grads)
+}
+
+/// Settings of [`DataParallelTrainer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataParallelConfig {
+    /// Examples per worker and step; the effective batch is
+    /// `batch_size × world_size`.
+    pub batch_size: usize,
+    /// Seed of the per-epoch shard permutation; must match on all ranks.
+    pub seed: u64,
+}
+
+impl Default for DataParallelConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            seed: 0,
+        }
+    }
+}
+
+/// Loss statistics of one epoch, averaged over all workers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EpochStats {
+    pub epoch: u64,
+    pub steps: usize,
+    pub mean_loss: f64,
+    /// The epoch was cut short by a cancellation token; `steps` and
+    /// `mean_loss` cover the steps that ran.
+    pub cancelled: bool,
+}
+
+/// Synchronous data-parallel training loop.
+#[derive(Debug)]
+pub struct DataParallelTrainer<C = TcpProcessGroup> {
+    pub group: C,
+    pub config: DataParallelConfig,
+    /// Stops the running epoch when cancelled on any rank.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl<C: Collective> DataParallelTrainer<C> {
+    pub fn new(group: C, config: DataParallelConfig) -> Self {
+        Self {
+            group,
+            config,
+            cancellation: None,
+        }
+    }
+
+    /// Lets `token` end epochs early. The flag travels with the loss
+    /// reduction, so every rank stops after the same step, at most one step
+    /// after cancellation.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Overwrites every rank's weights with those of rank 0, so replicas
+    /// start from the same initialization.
+    pub fn sync_parameters(&mut self, model: &mut dyn Parameters) -> Result<()> {
+        broadcast_parameters(&mut self.group, model)
+    }
+
+    /// Trains one epoch on this rank's shard. `compute` returns the mean
+    /// loss and the gradients of a batch; gradients are averaged across
+    /// ranks before `optimizer` steps.
+    pub fn train_epoch<M, D, O, F>(
+        &mut self,
+        model: &mut M,
+        optimizer: &mut O,
+        dataset: &D,
+        epoch: u64,
+        compute: F,
+    ) -> Result<EpochStats>
+    where
+        M: Parameters,
+        D: Dataset,
+        O: Optimizer,
+        F: FnMut(&M, &[D::Item]) -> Result<(f64, Gradients)>,
+    {
+        self.train_epoch_with_progress(model, optimizer, dataset, epoch, compute, &NoProgress)
+    }
+
+    /// [`train_epoch`](Self::train_epoch), reporting [`Stage::Training`]
+    /// after every step.
+    pub fn train_epoch_with_progress<M, D, O, F>(
+        &mut self,
+        model: &mut M,
+        optimizer: &mut O,
+        dataset: &D,
+        epoch: u64,
+        mut compute: F,
+        progress: &dyn ProgressHandler,
+    ) -> Result<EpochStats>
+    where
+        M: Parameters,
+        D: Dataset,
+        O: Optimizer,
+        F: FnMut(&M, &[D::Item]) -> Result<(f64, Gradients)>,
+    {
+        let batch_size = self.config.batch_size.max(1);
+        let shard = shard(
+            dataset,
+            self.group.rank(),
+            self.group.world_size(),
+            self.config.seed,
+            epoch,
+        )?;
+        let total_steps = shard.len().div_ceil(batch_size);
+        let mut total_loss = 0.0;
+        let mut steps = 0;
+        let mut cancelled = false;
+        for start in (0..shard.len()).step_by(batch_size) {
+            let batch: Vec<D::Item> = (start..(start + batch_size).min(shard.len()))
+                .filter_map(|i| shard.get(i))
+                .collect();
+            let (loss, mut grads) = compute(model, &batch)?;
+            average_gradients(&mut self.group, model, &mut grads)?;
+            let stop = self
+                .cancellation
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled);
+            let mut reduced = [loss, if stop { 1.0 } else { 0.0 }];
+            self.group.all_reduce_sum(&mut reduced)?;
+            total_loss += reduced[0] / self.group.world_size() as f64;
+            optimizer.step(model, &grads)?;
+            steps += 1;
+            progress.on_progress(&Progress::new(
+                Stage::Training { epoch },
+                steps,
+                total_steps,
+            ));
+            if reduced[1] > 0.0 {
+                cancelled = true;
+                break;
+            }
+        }
+        Ok(EpochStats {
+            epoch,
+            steps,
+            mean_loss: if steps > 0 {
+                total_loss / steps as f64
+            } else {
+                0.0
+            },
+            cancelled,
+        })
+    }
+}
@@ -0,0 +1,241 @@
+//! A process group connected over TCP.
+//!
+//! Rank 0 listens on the configured address and every other rank connects
+//! to it. Collectives are star-shaped: rank 0 gathers, reduces and sends the
+//! result back. That keeps the protocol trivial and is adequate for the small
+//! worker counts this crate targets.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::Collective;
+use crate::Result;
+
+const MAGIC: &[u8; 4] = b"RTPG";
+
+/// Rank, world size and rendezvous address of one worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistributedConfig {
+    /// This worker's index in `0..world_size`.
+    pub rank: usize,
+    pub world_size: usize,
+    /// Address rank 0 listens on, e.g. `127.0.0.1:29500`.
+    pub master_addr: String,
+    /// How long to wait for all workers to connect.
+    pub connect_timeout: Duration,
+}
+
+impl DistributedConfig {
+    pub fn new(rank: usize, world_size: usize, master_addr: impl Into<String>) -> Self {
+        Self {
+            rank,
+            world_size,
+            master_addr: master_addr.into(),
+            connect_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Reads `RANK`, `WORLD_SIZE` and `MASTER_ADDR` (default
+    /// `127.0.0.1:29500`) from the environment.
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).map_err(|_| format!("{} is not set", name));
+        let rank = var("RANK")?.parse()?;
+        let world_size = var("WORLD_SIZE")?.parse()?;
+        let master = var("MASTER_ADDR").unwrap_or_else(|_| "127.0.0.1:29500".to_string());
+        let config = Self::new(rank, world_size, master);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.world_size == 0 || self.rank >= self.world_size {
+            return Err(format!(
+                "rank {} is out of range for world size {}",
+                self.rank, self.world_size
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+struct Peer {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    fn send(&mut self, values: &[f64]) -> Result<()> {
+        self.writer
+            .write_all(&(values.len() as u64).to_le_bytes())?;
+        for v in values {
+            self.writer.write_all(&v.to_le_bytes())?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn recv_into(&mut self, out: &mut [f64]) -> Result<()> {
+        let mut word = [0u8; 8];
+        self.reader.read_exact(&mut word)?;
+        let len = u64::from_le_bytes(word) as usize;
+        if len != out.len() {
+            return Err(format!(
+                "collective size mismatch: expected {} values, peer sent {}",
+                out.len(),
+                len
+            )
+            .into());
+        }
+        for v in out.iter_mut() {
+            self.reader.read_exact(&mut word)?;
+            *v = f64::from_le_bytes(word);
+        }
+        Ok(())
+    }
+}
+
+/// Connections of one worker to the rest of the group.
+pub struct TcpProcessGroup {
+    rank: usize,
+    world_size: usize,
+    /// On rank 0 the workers `1..world_size` in rank order; elsewhere the
+    /// single connection to rank 0.
+    peers: Vec<Peer>,
+}
+
+impl std::fmt::Debug for TcpProcessGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpProcessGroup")
+            .field("rank", &self.rank)
+            .field("world_size", &self.world_size)
+            .finish()
+    }
+}
+
+impl TcpProcessGroup {
+    /// Joins the group, blocking until every worker has connected.
+    pub fn connect(config: &DistributedConfig) -> Result<Self> {
+        config.validate()?;
+        let mut group = Self {
+            rank: config.rank,
+            world_size: config.world_size,
+            peers: Vec::new(),
+        };
+        if config.world_size == 1 {
+            return Ok(group);
+        }
+        if config.rank == 0 {
+            let listener = TcpListener::bind(&config.master_addr)?;
+            let mut slots: Vec<Option<Peer>> = (1..config.world_size).map(|_| None).collect();
+            for _ in 1..config.world_size {
+                let (mut stream, _) = listener.accept()?;
+                let (rank, world_size) = read_handshake(&mut stream)?;
+                if world_size != config.world_size || rank == 0 || rank >= world_size {
+                    return Err(format!(
+                        "worker announced rank {} of {}, expected a world size of {}",
+                        rank, world_size, config.world_size
+                    )
+                    .into());
+                }
+                if slots[rank - 1].is_some() {
+                    return Err(format!("rank {} connected twice", rank).into());
+                }
+                slots[rank - 1] = Some(Peer::new(stream)?);
+            }
+            group.peers = slots.into_iter().flatten().collect();
+        } else {
+            let mut stream = connect_with_retry(&config.master_addr, config.connect_timeout)?;
+            stream.write_all(MAGIC)?;
+            stream.write_all(&(config.rank as u32).to_le_bytes())?;
+            stream.write_all(&(config.world_size as u32).to_le_bytes())?;
+            group.peers.push(Peer::new(stream)?);
+        }
+        Ok(group)
+    }
+}
+
+impl Collective for TcpProcessGroup {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn world_size(&self) -> usize {
+        self.world_size
+    }
+
+    /// Replaces `values` on every rank with their element-wise sum across ranks.
+    fn all_reduce_sum(&mut self, values: &mut [f64]) -> Result<()> {
+        if self.world_size == 1 {
+            return Ok(());
+        }
+        if self.rank == 0 {
+            let mut incoming = vec![0.0; values.len()];
+            for peer in &mut self.peers {
+                peer.recv_into(&mut incoming)?;
+                for (v, x) in values.iter_mut().zip(&incoming) {
+                    *v += x;
+                }
+            }
+            for peer in &mut self.peers {
+                peer.send(values)?;
+            }
+        } else {
+            let master = &mut self.peers[0];
+            master.send(values)?;
+            master.recv_into(values)?;
+        }
+        Ok(())
+    }
+
+    /// Copies rank 0's `values` to every other rank.
+    fn broadcast(&mut self, values: &mut [f64]) -> Result<()> {
+        if self.world_size == 1 {
+            return Ok(());
+        }
+        if self.rank == 0 {
+            for peer in &mut self.peers {
+                peer.send(values)?;
+            }
+        } else {
+            self.peers[0].recv_into(values)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_handshake(stream: &mut TcpStream) -> Result<(usize, usize)> {
+    let mut header = [0u8; 12];
+    stream.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err("unexpected handshake from worker".into());
+    }
+    let rank = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let world_size = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    Ok((rank, world_size))
+}
+
+/// Connects to `addr`, retrying while rank 0 is not yet listening.
+fn connect_with_retry(addr: &str, timeout: Duration) -> Result<TcpStream> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let attempt = TcpStream::connect(addr);
+        match attempt {
+            Ok(stream) => return Ok(stream),
+            Err(e) if Instant::now() >= deadline => {
+                return Err(format!("could not reach rank 0 at {}: {}", addr, e).into())
+            }
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
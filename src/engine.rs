@@ -0,0 +1,296 @@
+//! A pool of inference threads sharing one model.
+//!
+//! [`InferenceEngine`] keeps the weights behind an `Arc`, starts
+//! [`EngineConfig::num_workers`] threads and feeds them from a bounded job
+//! queue. Each submission returns a [`JobHandle`] that yields the job's
+//! result once a worker has finished it, so concurrent callers share the
+//! pool without managing threads themselves. When the queue is full,
+//! [`submit`](InferenceEngine::submit) blocks until a worker frees a slot
+//! and [`try_submit`](InferenceEngine::try_submit) fails instead;
+//! [`try_submit_all`](InferenceEngine::try_submit_all) queues a batch of
+//! jobs only if there is room for all of them.
+//!
+//! Dropping the engine stops accepting jobs, lets the workers drain the
+//! queue and joins them.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::generation::{GenerationConfig, GenerationOutput};
+use crate::models::Transformer;
+use crate::tensor::Matrix;
+use crate::{Result, TransformerError};
+
+/// Settings of [`InferenceEngine`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Worker threads; defaults to the available parallelism.
+    pub num_workers: usize,
+    /// Jobs that may wait for a worker before submission blocks.
+    pub queue_capacity: usize,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            num_workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            queue_capacity: 64,
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce(&Transformer) + Send>;
+
+/// The pending result of a submitted job.
+#[derive(Debug)]
+pub struct JobHandle<T> {
+    result: Receiver<Result<T>>,
+}
+
+impl<T> JobHandle<T> {
+    /// Blocks until the job has run.
+    pub fn wait(self) -> Result<T> {
+        self.result
+            .recv()
+            .map_err(|_| "inference worker exited before finishing the job")?
+    }
+
+    /// Waits at most `timeout`; `None` if the job has not finished by then.
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<Result<T>> {
+        match self.result.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Err(
+                "inference worker exited before finishing the job".into(),
+            )),
+        }
+    }
+
+    /// The result if the job has already finished.
+    pub fn try_get(&self) -> Option<Result<T>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(
+                "inference worker exited before finishing the job".into(),
+            )),
+        }
+    }
+}
+
+/// Worker threads processing encode and generate jobs against shared
+/// weights.
+pub struct InferenceEngine {
+    model: Arc<Transformer>,
+    queue: Option<SyncSender<Job>>,
+    queue_capacity: usize,
+    /// Jobs sent or being sent that no worker has taken yet; never less than
+    /// the length of the queue.
+    queued: Arc<AtomicUsize>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl InferenceEngine {
+    pub fn new(model: Transformer, config: EngineConfig) -> Result<Self> {
+        Self::from_shared(Arc::new(model), config)
+    }
+
+    /// Starts the pool on weights that other owners may keep using.
+    pub fn from_shared(model: Arc<Transformer>, config: EngineConfig) -> Result<Self> {
+        if config.num_workers == 0 {
+            return Err(TransformerError::config(
+                "inference engine needs at least one worker",
+            ));
+        }
+        let (queue, jobs) = mpsc::sync_channel::<Job>(config.queue_capacity);
+        let jobs = Arc::new(Mutex::new(jobs));
+        let queued = Arc::new(AtomicUsize::new(0));
+        let workers = (0..config.num_workers)
+            .map(|i| {
+                let model = Arc::clone(&model);
+                let jobs = Arc::clone(&jobs);
+                let queued = Arc::clone(&queued);
+                thread::Builder::new()
+                    .name(format!("inference-{}", i))
+                    .spawn(move || loop {
+                        // The lock is released before the job runs.
+                        let job = match jobs.lock() {
+                            Ok(jobs) => jobs.recv(),
+                            Err(_) => return,
+                        };
+                        match job {
+                            // A panicking job fails its own handle only.
+                            Ok(job) => {
+                                queued.fetch_sub(1, Ordering::SeqCst);
+                                let _ = panic::catch_unwind(AssertUnwindSafe(|| job(&model)));
+                            }
+                            Err(_) => return,
+                        }
+                    })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Self {
+            model,
+            queue: Some(queue),
+            queue_capacity: config.queue_capacity,
+            queued,
+            workers,
+        })
+    }
+
+    pub fn model(&self) -> &Arc<Transformer> {
+        &self.model
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queues `job`, blocking while the queue is full.
+    pub fn submit<T, F>(&self, job: F) -> Result<JobHandle<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Transformer) -> Result<T> + Send + 'static,
+    {
+        let (job, handle) = wrap_job(job);
+        let queue = self.queue()?;
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        queue.send(job).map_err(|_| {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            no_workers()
+        })?;
+        Ok(handle)
+    }
+
+    /// Like [`submit`](Self::submit), failing with
+    /// [`TransformerError::Unavailable`] instead of blocking when the queue
+    /// is full.
+    pub fn try_submit<T, F>(&self, job: F) -> Result<JobHandle<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Transformer) -> Result<T> + Send + 'static,
+    {
+        let (job, handle) = wrap_job(job);
+        let queue = self.queue()?;
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        queue.try_send(job).map_err(|e| {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            match e {
+                TrySendError::Full(_) => {
+                    TransformerError::Unavailable("inference queue is full".to_string())
+                }
+                TrySendError::Disconnected(_) => no_workers(),
+            }
+        })?;
+        Ok(handle)
+    }
+
+    /// Queues every job of `jobs`, or none of them: fails with
+    /// [`TransformerError::Unavailable`] if the queue lacks room for the
+    /// whole batch, so a rejected batch leaves no jobs behind.
+    pub fn try_submit_all<T, F>(
+        &self,
+        jobs: impl IntoIterator<Item = F>,
+    ) -> Result<Vec<JobHandle<T>>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Transformer) -> Result<T> + Send + 'static,
+    {
+        let jobs: Vec<F> = jobs.into_iter().collect();
+        let queue = self.queue()?;
+        let capacity = self.queue_capacity;
+        self.queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                Some(queued + jobs.len()).filter(|&total| total <= capacity)
+            })
+            .map_err(|_| {
+                TransformerError::Unavailable(format!(
+                    "inference queue has no room for {} jobs",
+                    jobs.len()
+                ))
+            })?;
+        let mut reserved = jobs.len();
+        let mut handles = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            let (job, handle) = wrap_job(job);
+            // The reservation keeps the queue below capacity, so this does
+            // not block.
+            if queue.send(job).is_err() {
+                self.queued.fetch_sub(reserved, Ordering::SeqCst);
+                return Err(no_workers());
+            }
+            reserved -= 1;
+            handles.push(handle);
+        }
+        Ok(handles)
+    }
+
+    fn queue(&self) -> Result<&SyncSender<Job>> {
+        self.queue.as_ref().ok_or_else(|| {
+            TransformerError::Unavailable("inference engine is shut down".to_string())
+        })
+    }
+
+    /// Queues [`Transformer::encode`] of `src`.
+    pub fn encode(&self, src: Vec<usize>) -> Result<JobHandle<Matrix>> {
+        self.submit(move |model| model.encode(&src))
+    }
+
+    /// Queues [`Transformer::generate_output`] of `src`.
+    pub fn generate(
+        &self,
+        src: Vec<usize>,
+        generation: GenerationConfig,
+    ) -> Result<JobHandle<GenerationOutput>> {
+        self.submit(move |model| model.generate_output(&src, &generation))
+    }
+
+    /// Stops accepting jobs, finishes the queued ones and joins the workers.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.queue = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A queueable job that reports `job`'s result to the returned handle.
+fn wrap_job<T, F>(job: F) -> (Job, JobHandle<T>)
+where
+    T: Send + 'static,
+    F: FnOnce(&Transformer) -> Result<T> + Send + 'static,
+{
+    let (done, result) = mpsc::channel();
+    let job: Job = Box::new(move |model| {
+        // The caller may have dropped its handle; nothing to report then.
+        let _ = done.send(job(model));
+    });
+    (job, JobHandle { result })
+}
+
+fn no_workers() -> TransformerError {
+    TransformerError::Unavailable("inference engine has no running workers".to_string())
+}
+
+impl Drop for InferenceEngine {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl fmt::Debug for InferenceEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InferenceEngine")
+            .field("num_workers", &self.workers.len())
+            .finish_non_exhaustive()
+    }
+}
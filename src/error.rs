@@ -0,0 +1,118 @@
+//! The crate-wide error type.
+//!
+//! Failures callers commonly need to tell apart get their own
+//! [`TransformerError`] variant; everything else is
+//! [`Other`](TransformerError::Other) with a message. String errors convert
+//! into `Other`, so `Err("...".into())` and `Err(format!(...).into())` keep
+//! working for one-off failures.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Why an operation failed.
+#[derive(Debug)]
+pub enum TransformerError {
+    /// Operand shapes do not fit the operation, as in
+    /// `matmul shape mismatch: (2, 3) x (4, 5)`.
+    ShapeMismatch(String),
+    /// A token id at or beyond the vocabulary size.
+    VocabOverflow {
+        token: usize,
+        vocab_size: usize,
+    },
+    /// A configuration that fails validation.
+    InvalidConfig(String),
+    /// A mask of the wrong size for the scores it is applied to.
+    MaskError(String),
+    /// Reading or writing a file or socket failed.
+    IoError(io::Error),
+    /// Malformed text: invalid JSON, UTF-8 or numbers.
+    Parse(String),
+    /// A service cannot take work right now, e.g. an
+    /// [`InferenceEngine`](crate::engine::InferenceEngine) that is shut down
+    /// or whose queue is full.
+    Unavailable(String),
+    Other(String),
+}
+
+impl TransformerError {
+    pub fn shape(message: impl Into<String>) -> Self {
+        TransformerError::ShapeMismatch(message.into())
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        TransformerError::InvalidConfig(message.into())
+    }
+
+    pub fn mask(message: impl Into<String>) -> Self {
+        TransformerError::MaskError(message.into())
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        TransformerError::Parse(message.into())
+    }
+}
+
+impl fmt::Display for TransformerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformerError::ShapeMismatch(message)
+            | TransformerError::InvalidConfig(message)
+            | TransformerError::MaskError(message)
+            | TransformerError::Parse(message)
+            | TransformerError::Unavailable(message)
+            | TransformerError::Other(message) => f.write_str(message),
+            TransformerError::VocabOverflow { token, vocab_size } => write!(
+                f,
+                "token id {} out of range for vocabulary of {}",
+                token, vocab_size
+            ),
+            TransformerError::IoError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for TransformerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TransformerError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TransformerError {
+    fn from(e: io::Error) -> Self {
+        TransformerError::IoError(e)
+    }
+}
+
+impl From<String> for TransformerError {
+    fn from(message: String) -> Self {
+        TransformerError::Other(message)
+    }
+}
+
+impl From<&str> for TransformerError {
+    fn from(message: &str) -> Self {
+        TransformerError::Other(message.to_string())
+    }
+}
+
+macro_rules! parse_error_from {
+    ($($t:ty),*) => {
+        $(impl From<$t> for TransformerError {
+            fn from(e: $t) -> Self {
+                TransformerError::Parse(e.to_string())
+            }
+        })*
+    };
+}
+
+parse_error_from!(
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+    std::num::ParseIntError,
+    std::num::ParseFloatError
+);
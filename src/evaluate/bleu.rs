@@ -0,0 +1,159 @@
+//! Corpus-level BLEU (Papineni et al., 2002) over pre-tokenized sequences.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::ngram_counts;
+use crate::{Result, TransformerError};
+
+/// Smoothing applied to n-gram precisions with zero matches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Plain BLEU: any zero precision makes the score zero.
+    None,
+    /// Replaces a zero match count with `epsilon` (Chen & Cherry method 1).
+    Epsilon(f64),
+    /// Adds one to the match and total counts for orders above one
+    /// (Lin & Och, 2004). Useful for sentence-level scores.
+    AddOne,
+}
+
+/// BLEU settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BleuConfig {
+    /// Highest n-gram order, 4 in the standard metric.
+    pub max_order: usize,
+    pub smoothing: Smoothing,
+}
+
+impl Default for BleuConfig {
+    fn default() -> Self {
+        Self {
+            max_order: 4,
+            smoothing: Smoothing::None,
+        }
+    }
+}
+
+/// BLEU score with the statistics it was computed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BleuScore {
+    /// Final score in `[0, 1]`.
+    pub score: f64,
+    /// Modified n-gram precision for each order `1..=max_order`.
+    pub precisions: Vec<f64>,
+    pub brevity_penalty: f64,
+    /// Candidate length divided by effective reference length.
+    pub length_ratio: f64,
+    pub candidate_length: usize,
+    pub reference_length: usize,
+}
+
+/// Computes corpus BLEU for `candidates`, each scored against one or more
+/// references.
+pub fn corpus_bleu<T: Eq + Hash>(
+    candidates: &[Vec<T>],
+    references: &[Vec<Vec<T>>],
+    config: BleuConfig,
+) -> Result<BleuScore> {
+    if candidates.len() != references.len() {
+        return Err(TransformerError::shape(format!(
+            "got {} candidates but {} reference sets",
+            candidates.len(),
+            references.len()
+        )));
+    }
+    if config.max_order == 0 {
+        return Err(TransformerError::config(
+            "BLEU max_order must be at least 1",
+        ));
+    }
+
+    let mut matches = vec![0usize; config.max_order];
+    let mut totals = vec![0usize; config.max_order];
+    let mut candidate_length = 0;
+    let mut reference_length = 0;
+
+    for (candidate, refs) in candidates.iter().zip(references) {
+        if refs.is_empty() {
+            return Err("every candidate needs at least one reference".into());
+        }
+        candidate_length += candidate.len();
+        reference_length += closest_reference_length(candidate.len(), refs);
+
+        for n in 1..=config.max_order {
+            let cand_counts = ngram_counts(candidate, n);
+            // Clip each n-gram by the maximum count seen in any single reference.
+            let mut max_ref_counts: HashMap<&[T], usize> = HashMap::new();
+            for reference in refs {
+                for (gram, count) in ngram_counts(reference, n) {
+                    let entry = max_ref_counts.entry(gram).or_insert(0);
+                    *entry = (*entry).max(count);
+                }
+            }
+            for (gram, count) in &cand_counts {
+                matches[n - 1] += (*count).min(max_ref_counts.get(gram).copied().unwrap_or(0));
+            }
+            totals[n - 1] += candidate.len().saturating_sub(n - 1);
+        }
+    }
+
+    let precisions: Vec<f64> = (0..config.max_order)
+        .map(|i| smoothed_precision(matches[i], totals[i], i + 1, config.smoothing))
+        .collect();
+
+    let brevity_penalty = if candidate_length == 0 {
+        0.0
+    } else if candidate_length >= reference_length {
+        1.0
+    } else {
+        (1.0 - reference_length as f64 / candidate_length as f64).exp()
+    };
+
+    let score = if precisions.iter().any(|&p| p <= 0.0) {
+        0.0
+    } else {
+        let log_mean = precisions.iter().map(|p| p.ln()).sum::<f64>() / config.max_order as f64;
+        brevity_penalty * log_mean.exp()
+    };
+
+    Ok(BleuScore {
+        score,
+        precisions,
+        brevity_penalty,
+        length_ratio: if reference_length == 0 {
+            0.0
+        } else {
+            candidate_length as f64 / reference_length as f64
+        },
+        candidate_length,
+        reference_length,
+    })
+}
+
+/// BLEU for a single candidate against a single reference.
+pub fn sentence_bleu<T: Eq + Hash + Clone>(
+    candidate: &[T],
+    reference: &[T],
+    config: BleuConfig,
+) -> Result<BleuScore> {
+    corpus_bleu(&[candidate.to_vec()], &[vec![reference.to_vec()]], config)
+}
+
+fn smoothed_precision(matches: usize, total: usize, order: usize, smoothing: Smoothing) -> f64 {
+    match smoothing {
+        Smoothing::AddOne if order > 1 => (matches as f64 + 1.0) / (total as f64 + 1.0),
+        _ if total == 0 => 0.0,
+        Smoothing::Epsilon(epsilon) if matches == 0 => epsilon / total as f64,
+        _ => matches as f64 / total as f64,
+    }
+}
+
+/// Length of the reference closest to the candidate, preferring the shorter
+/// one on ties.
+fn closest_reference_length<T>(candidate_len: usize, refs: &[Vec<T>]) -> usize {
+    refs.iter()
+        .map(|r| r.len())
+        .min_by_key(|&len| (len.abs_diff(candidate_len), len))
+        .unwrap_or(0)
+}
@@ -0,0 +1,98 @@
+//! Evaluation metrics for sequence-to-sequence outputs.
+//!
+//! All metrics work on pre-tokenized sequences of any comparable token type,
+//! so they can score token ids straight out of generation or whitespace-split
+//! words alike.
+
+pub mod bleu;
+pub mod rouge;
+
+pub use bleu::{corpus_bleu, sentence_bleu, BleuConfig, BleuScore, Smoothing};
+pub use rouge::{rouge_l, rouge_n, RougeScore};
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::data::Dataset;
+use crate::Result;
+
+/// Scores of a generation run over a dataset.
+#[derive(Debug, Clone)]
+pub struct GenerationReport {
+    pub bleu: BleuScore,
+    /// ROUGE scores averaged over examples.
+    pub rouge1: RougeScore,
+    pub rouge2: RougeScore,
+    pub rouge_l: RougeScore,
+    /// The generated sequence for every example, in dataset order.
+    pub hypotheses: Vec<Vec<usize>>,
+}
+
+/// Runs `generate` on the source side of every `(source, reference)` example
+/// in `dataset` and scores the outputs against the references.
+///
+/// Hypotheses are scored exactly as `generate` returns them, so it must
+/// strip whatever the references leave out: a decoder's start and end tokens
+/// would otherwise count as unmatched n-grams. The `eval` command of the
+/// binary drops both from generated output and the end token from targets.
+pub fn evaluate_generation<D, F>(
+    dataset: &D,
+    mut generate: F,
+    bleu_config: BleuConfig,
+) -> Result<GenerationReport>
+where
+    D: Dataset<Item = (Vec<usize>, Vec<usize>)>,
+    F: FnMut(&[usize]) -> Result<Vec<usize>>,
+{
+    let mut hypotheses = Vec::with_capacity(dataset.len());
+    let mut references = Vec::with_capacity(dataset.len());
+    for index in 0..dataset.len() {
+        let (source, reference) = dataset
+            .get(index)
+            .ok_or_else(|| format!("dataset has no example at index {}", index))?;
+        hypotheses.push(generate(&source)?);
+        references.push(vec![reference]);
+    }
+
+    let bleu = corpus_bleu(&hypotheses, &references, bleu_config)?;
+    let mut rouge1 = Vec::with_capacity(hypotheses.len());
+    let mut rouge2 = Vec::with_capacity(hypotheses.len());
+    let mut rouge_ls = Vec::with_capacity(hypotheses.len());
+    for (hypothesis, refs) in hypotheses.iter().zip(&references) {
+        rouge1.push(rouge_n(hypothesis, &refs[0], 1));
+        rouge2.push(rouge_n(hypothesis, &refs[0], 2));
+        rouge_ls.push(rouge_l(hypothesis, &refs[0]));
+    }
+
+    Ok(GenerationReport {
+        bleu,
+        rouge1: mean_rouge(&rouge1),
+        rouge2: mean_rouge(&rouge2),
+        rouge_l: mean_rouge(&rouge_ls),
+        hypotheses,
+    })
+}
+
+/// Counts every n-gram of length `n` in `tokens`.
+pub(crate) fn ngram_counts<T: Eq + Hash>(tokens: &[T], n: usize) -> HashMap<&[T], usize> {
+    let mut counts = HashMap::new();
+    if n == 0 {
+        return counts;
+    }
+    for gram in tokens.windows(n) {
+        *counts.entry(gram).or_insert(0) += 1;
+    }
+    counts
+}
+
+fn mean_rouge(scores: &[RougeScore]) -> RougeScore {
+    if scores.is_empty() {
+        return RougeScore::default();
+    }
+    let n = scores.len() as f64;
+    RougeScore {
+        precision: scores.iter().map(|s| s.precision).sum::<f64>() / n,
+        recall: scores.iter().map(|s| s.recall).sum::<f64>() / n,
+        f1: scores.iter().map(|s| s.f1).sum::<f64>() / n,
+    }
+}
@@ -0,0 +1,79 @@
+//! ROUGE-N and ROUGE-L (Lin, 2004) over pre-tokenized sequences.
+
+use std::hash::Hash;
+
+use super::ngram_counts;
+
+/// Precision, recall and F1 of a ROUGE variant.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RougeScore {
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+impl RougeScore {
+    fn from_counts(overlap: usize, candidate_total: usize, reference_total: usize) -> Self {
+        let precision = ratio(overlap, candidate_total);
+        let recall = ratio(overlap, reference_total);
+        let f1 = if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        };
+        Self {
+            precision,
+            recall,
+            f1,
+        }
+    }
+}
+
+/// ROUGE-N: clipped n-gram overlap between candidate and reference.
+pub fn rouge_n<T: Eq + Hash>(candidate: &[T], reference: &[T], n: usize) -> RougeScore {
+    if n == 0 {
+        return RougeScore::default();
+    }
+    let cand_counts = ngram_counts(candidate, n);
+    let ref_counts = ngram_counts(reference, n);
+    let overlap = cand_counts
+        .iter()
+        .map(|(gram, &count)| count.min(ref_counts.get(gram).copied().unwrap_or(0)))
+        .sum();
+    RougeScore::from_counts(
+        overlap,
+        candidate.len().saturating_sub(n - 1),
+        reference.len().saturating_sub(n - 1),
+    )
+}
+
+/// ROUGE-L: longest-common-subsequence based precision and recall.
+pub fn rouge_l<T: Eq>(candidate: &[T], reference: &[T]) -> RougeScore {
+    let lcs = lcs_length(candidate, reference);
+    RougeScore::from_counts(lcs, candidate.len(), reference.len())
+}
+
+fn lcs_length<T: Eq>(a: &[T], b: &[T]) -> usize {
+    // Two-row dynamic program: O(|a|·|b|) time, O(|b|) memory.
+    let mut prev = vec![0usize; b.len() + 1];
+    let mut curr = vec![0usize; b.len() + 1];
+    for x in a {
+        for (j, y) in b.iter().enumerate() {
+            curr[j + 1] = if x == y {
+                prev[j] + 1
+            } else {
+                prev[j + 1].max(curr[j])
+            };
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+fn ratio(num: usize, den: usize) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        num as f64 / den as f64
+    }
+}
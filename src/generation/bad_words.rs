@@ -0,0 +1,110 @@
+//! Banning phrases from generated text.
+//!
+//! Phrases are matched on decoded text rather than token ids, so a word is
+//! caught however the tokenizer splits it. Before each step the filter
+//! decodes the tail of the output and hides every candidate token whose text
+//! would complete a banned phrase, including phrases spread over several
+//! tokens.
+
+use crate::tokenizer::Tokenizer;
+use crate::Result;
+
+/// Phrases that must not appear in generated text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadWords {
+    phrases: Vec<String>,
+    /// Decoded text of every token id.
+    token_text: Vec<String>,
+    /// Longest phrase in bytes; only this much of the tail can matter.
+    max_phrase_len: usize,
+}
+
+impl BadWords {
+    /// Precomputes the text of every token of `tokenizer`. Empty phrases are
+    /// ignored.
+    pub fn new<S: AsRef<str>>(
+        tokenizer: &dyn Tokenizer,
+        phrases: impl IntoIterator<Item = S>,
+    ) -> Result<Self> {
+        let phrases: Vec<String> = phrases
+            .into_iter()
+            .map(|p| p.as_ref().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        let token_text = (0..tokenizer.vocab_size())
+            .map(|id| tokenizer.decode(&[id]))
+            .collect::<Result<Vec<_>>>()?;
+        let max_phrase_len = phrases.iter().map(String::len).max().unwrap_or(0);
+        Ok(Self {
+            phrases,
+            token_text,
+            max_phrase_len,
+        })
+    }
+
+    pub fn phrases(&self) -> &[String] {
+        &self.phrases
+    }
+
+    /// Whether any banned phrase occurs in `text`.
+    pub fn contains_banned(&self, text: &str) -> bool {
+        self.phrases.iter().any(|p| text.contains(p.as_str()))
+    }
+
+    /// Text of `generated` (no start token), as the filter sees it.
+    pub fn decode(&self, generated: &[usize]) -> String {
+        generated
+            .iter()
+            .filter_map(|&id| self.token_text.get(id))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Token ids that would complete a banned phrase if appended to text
+    /// ending in `tail`.
+    pub fn banned_tokens(&self, tail: &str) -> Vec<usize> {
+        if self.phrases.is_empty() {
+            return Vec::new();
+        }
+        let tail = suffix(tail, self.max_phrase_len.saturating_sub(1));
+        let mut candidate = String::with_capacity(tail.len() + 16);
+        self.token_text
+            .iter()
+            .enumerate()
+            .filter(|(_, text)| !text.is_empty())
+            .filter_map(|(id, text)| {
+                candidate.clear();
+                candidate.push_str(tail);
+                candidate.push_str(text);
+                // A match must end inside the new token; matches lying wholly
+                // in the tail are already part of the output.
+                let completes = self.phrases.iter().any(|p| {
+                    candidate
+                        .match_indices(p.as_str())
+                        .any(|(start, _)| start + p.len() > tail.len())
+                });
+                completes.then_some(id)
+            })
+            .collect()
+    }
+
+    /// Sets the logits of [`banned_tokens`](Self::banned_tokens) for the
+    /// output `generated` to `-inf`.
+    pub fn mask_logits(&self, generated: &[usize], logits: &mut [f64]) {
+        let tail = self.decode(generated);
+        for id in self.banned_tokens(&tail) {
+            if let Some(logit) = logits.get_mut(id) {
+                *logit = f64::NEG_INFINITY;
+            }
+        }
+    }
+}
+
+/// The last `max_bytes` bytes of `text`, widened to a char boundary.
+fn suffix(text: &str, max_bytes: usize) -> &str {
+    let mut start = text.len().saturating_sub(max_bytes);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    &text[start..]
+}
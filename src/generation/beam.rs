@@ -0,0 +1,179 @@
+//! Beam search decoding.
+//!
+//! Every step extends each live hypothesis by its `beam_width` most likely
+//! tokens and keeps the `beam_width` best extensions by summed
+//! log-probability. Each beam carries its own decoder key/value cache, so a
+//! step only runs the newest token of every beam. Hypotheses that emit the
+//! end token are set aside and ranked with [`BeamScoring`]; the search stops
+//! once `beam_width` of them are finished or the length limit is reached.
+//! When the scoring has a coverage penalty, every beam also accumulates the
+//! cross-attention of its decoder steps, averaged over layers and heads.
+
+use super::{mean_cross_attention, BeamScoring, FinishReason, GenerationConfig};
+use crate::models::{DecoderKvCache, Transformer};
+use crate::tensor::Matrix;
+use crate::utils::tensor_ops::{log_softmax, top_k};
+use crate::{Result, TransformerError};
+
+/// A finished beam.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeamHypothesis {
+    /// The start token followed by every generated token.
+    pub tokens: Vec<usize>,
+    /// Summed log-probability of the generated tokens.
+    pub log_prob: f64,
+    /// `log_prob` after the length penalty, the value beams are ranked by.
+    pub score: f64,
+    /// [`FinishReason::Stop`] after the end token, otherwise
+    /// [`FinishReason::Length`].
+    pub finish_reason: FinishReason,
+}
+
+/// A live hypothesis and its decoder state.
+struct Beam {
+    tokens: Vec<usize>,
+    log_prob: f64,
+    /// `None` when hooks are registered and every step decodes the whole
+    /// sequence.
+    cache: Option<DecoderKvCache>,
+    /// Mean cross-attention of every decoder step so far,
+    /// `tokens.len() - 1 × src_len`; only kept for the coverage penalty.
+    attention: Option<Matrix>,
+}
+
+impl Transformer {
+    /// Beam search from the start token, returning up to `beam_width`
+    /// hypotheses of at most `max_len` tokens (start token included), best
+    /// first. `length_penalty` is the GNMT exponent `alpha` of
+    /// [`BeamScoring::with_length_penalty`]; 0 ranks by raw log-probability.
+    /// Longer sources are left-truncated.
+    pub fn generate_beam(
+        &self,
+        src: &[usize],
+        beam_width: usize,
+        length_penalty: f64,
+        max_len: usize,
+    ) -> Result<Vec<BeamHypothesis>> {
+        if !length_penalty.is_finite() {
+            return Err(TransformerError::config(format!(
+                "length_penalty must be finite, got {}",
+                length_penalty
+            )));
+        }
+        let scoring = BeamScoring::new().with_length_penalty(length_penalty);
+        self.generate_beam_with_scoring(src, beam_width, &scoring, max_len)
+    }
+
+    /// Like [`generate_beam`](Self::generate_beam), ranking hypotheses with
+    /// `scoring`, including its coverage penalty.
+    pub fn generate_beam_with_scoring(
+        &self,
+        src: &[usize],
+        beam_width: usize,
+        scoring: &BeamScoring,
+        max_len: usize,
+    ) -> Result<Vec<BeamHypothesis>> {
+        if beam_width == 0 {
+            return Err(TransformerError::config("beam_width must be at least 1"));
+        }
+        let coverage = scoring.uses_coverage();
+        let src = GenerationConfig::new().truncate(src, self.config.max_seq_len)?;
+        let memory = self.encode(src)?;
+        let max_len = max_len.min(self.config.max_seq_len);
+        let temperature = self.config.logit_temperature;
+        let finish =
+            |tokens: Vec<usize>, log_prob: f64, attention: Option<&Matrix>, finish_reason| {
+                BeamHypothesis {
+                    score: scoring.score(log_prob, tokens.len() - 1, attention),
+                    tokens,
+                    log_prob,
+                    finish_reason,
+                }
+            };
+
+        let mut beams = vec![Beam {
+            tokens: vec![self.config.bos_token_id],
+            log_prob: 0.0,
+            cache: if self.decoder.hooks.is_empty() {
+                Some(self.decoder.start_kv_cache(&memory)?)
+            } else {
+                None
+            },
+            attention: None,
+        }];
+        let mut finished = Vec::new();
+        while !beams.is_empty() && beams[0].tokens.len() < max_len && finished.len() < beam_width {
+            // (beam, token, log-probability of the extended hypothesis)
+            let mut candidates = Vec::with_capacity(beams.len() * beam_width);
+            for (b, beam) in beams.iter_mut().enumerate() {
+                let logits = match (&mut beam.cache, coverage) {
+                    (Some(cache), false) => self.decode_incremental(&beam.tokens, src, cache)?,
+                    (None, false) => self.decode(&beam.tokens, src, &memory)?,
+                    (Some(cache), true) => {
+                        let (logits, layers) =
+                            self.decode_incremental_with_attentions(&beam.tokens, src, cache)?;
+                        let step = mean_cross_attention(&layers)?;
+                        beam.attention = Some(match &beam.attention {
+                            Some(previous) => Matrix::vstack(&[previous, &step])?,
+                            None => step,
+                        });
+                        logits
+                    }
+                    (None, true) => {
+                        let (logits, layers) =
+                            self.decode_with_attentions(&beam.tokens, src, &memory)?;
+                        beam.attention = Some(mean_cross_attention(&layers)?);
+                        logits
+                    }
+                };
+                let last: Vec<f64> = logits
+                    .row(logits.rows() - 1)
+                    .iter()
+                    .map(|v| v / temperature)
+                    .collect();
+                for (token, lp) in top_k(&log_softmax(&last), beam_width) {
+                    candidates.push((b, token, beam.log_prob + lp));
+                }
+            }
+            candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+            let mut next = Vec::with_capacity(beam_width);
+            for (b, token, log_prob) in candidates {
+                if next.len() == beam_width || finished.len() == beam_width {
+                    break;
+                }
+                let mut tokens = beams[b].tokens.clone();
+                tokens.push(token);
+                if token == self.config.eos_token_id {
+                    let attention = beams[b].attention.as_ref();
+                    finished.push(finish(tokens, log_prob, attention, FinishReason::Stop));
+                } else {
+                    next.push(Beam {
+                        tokens,
+                        log_prob,
+                        cache: beams[b].cache.clone(),
+                        attention: beams[b].attention.clone(),
+                    });
+                }
+            }
+            beams = next;
+        }
+        if finished.len() < beam_width {
+            finished.extend(
+                beams
+                    .into_iter()
+                    .take(beam_width - finished.len())
+                    .map(|beam| {
+                        finish(
+                            beam.tokens,
+                            beam.log_prob,
+                            beam.attention.as_ref(),
+                            FinishReason::Length,
+                        )
+                    }),
+            );
+        }
+        finished.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(finished)
+    }
+}
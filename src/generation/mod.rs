@@ -0,0 +1,35 @@
+//! Decoding strategies and the options that control them.
+//!
+//! [`GenerationConfig`] bounds how many tokens are produced, for how long
+//! and down to which log-probability decoding may run, and how over-long
+//! prompts are truncated, and can carry [`BadWords`] to keep phrases out of
+//! the output; [`GenerationOutput`] reports why decoding stopped.
+//! [`SamplingConfig`] reshapes the distribution tokens are sampled from with
+//! a temperature and top-k or top-p filtering.
+//! [`Transformer::generate_beam`](crate::Transformer::generate_beam) runs a
+//! beam search, keeping a decoder key/value cache per beam.
+//! [`InferenceSession`] holds the state of one decoding (the encoded
+//! source, the key/value cache and the tokens so far) apart from the
+//! shared model, for callers that drive decoding step by step.
+//! [`scoring`] turns the log-probability of a finished hypothesis into the
+//! score beams are ranked by, with the length and coverage penalties of Wu
+//! et al. (2016), and [`rerank`] orders an N-best list of candidates by
+//! model score or an external scorer.
+
+pub mod bad_words;
+pub mod beam;
+pub mod options;
+pub mod output;
+pub mod rerank;
+pub mod sampling;
+pub mod scoring;
+pub mod session;
+
+pub use bad_words::BadWords;
+pub use beam::BeamHypothesis;
+pub use options::{GenerationConfig, Truncation};
+pub use output::{FinishReason, GenerationOutput};
+pub use rerank::{rerank, RankedHypothesis, RerankBy, ScoreFn};
+pub use sampling::SamplingConfig;
+pub use scoring::{mean_cross_attention, BeamScoring, LengthNormalization};
+pub use session::InferenceSession;
@@ -0,0 +1,204 @@
+//! Length, time and probability budgets and prompt handling for generation.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{BadWords, SamplingConfig};
+use crate::cancellation::CancellationToken;
+use crate::{Result, TransformerError};
+
+/// What to do with a source sequence longer than the model's `max_seq_len`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Truncation {
+    /// Drop the oldest tokens, keeping the end of the prompt.
+    #[default]
+    Left,
+    /// Drop tokens from the end, keeping the beginning.
+    Right,
+    /// Refuse over-long prompts.
+    Error,
+}
+
+/// Options shared by the `generate_with_config` methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationConfig {
+    /// Most tokens to produce after the decoder prefix.
+    pub max_new_tokens: usize,
+    /// Cap on the whole decoder sequence, prefix included. The model's
+    /// `max_seq_len` always applies as well.
+    pub max_length: Option<usize>,
+    /// Tokens decoding continues from, such as a start token followed by a
+    /// target-language tag or the beginning of an answer. `None` starts from
+    /// the model's start token alone. Returned sequences begin with the
+    /// prefix.
+    pub decoder_prefix: Option<Vec<usize>>,
+    /// Handling of sources longer than `max_seq_len`.
+    pub truncation: Truncation,
+    /// Sample from the output distribution; `false` decodes greedily.
+    pub do_sample: bool,
+    /// Temperature, top-k and top-p filtering applied when sampling.
+    pub sampling: SamplingConfig,
+    /// Phrases the output must not contain.
+    pub bad_words: Option<Arc<BadWords>>,
+    /// Checked before every decoding step; once cancelled, generation stops
+    /// and returns the tokens produced so far.
+    pub cancellation: Option<CancellationToken>,
+    /// Wall-clock budget per call, checked before every decoding step.
+    pub time_limit: Option<Duration>,
+    /// Stop once the summed log-probability of the generated tokens falls
+    /// below `-max_logprob_drop`. The token crossing the limit is kept.
+    pub max_logprob_drop: Option<f64>,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            max_new_tokens: 32,
+            max_length: None,
+            decoder_prefix: None,
+            truncation: Truncation::Left,
+            do_sample: true,
+            sampling: SamplingConfig::default(),
+            bad_words: None,
+            cancellation: None,
+            time_limit: None,
+            max_logprob_drop: None,
+        }
+    }
+}
+
+impl GenerationConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Greedy decoding with the default limits.
+    pub fn greedy() -> Self {
+        Self {
+            do_sample: false,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_max_new_tokens(mut self, max_new_tokens: usize) -> Self {
+        self.max_new_tokens = max_new_tokens;
+        self
+    }
+
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    pub fn with_decoder_prefix(mut self, prefix: Vec<usize>) -> Self {
+        self.decoder_prefix = Some(prefix);
+        self
+    }
+
+    /// The decoder prefix, defaulting to `[bos_token_id]`. Fails if the
+    /// prefix is empty, leaves no room in `max_seq_len`, or holds ids outside
+    /// the vocabulary.
+    pub fn prefix(
+        &self,
+        bos_token_id: usize,
+        vocab_size: usize,
+        max_seq_len: usize,
+    ) -> Result<Vec<usize>> {
+        let prefix = match &self.decoder_prefix {
+            Some(prefix) => prefix.clone(),
+            None => return Ok(vec![bos_token_id]),
+        };
+        if prefix.is_empty() {
+            return Err(TransformerError::config(
+                "decoder prefix must hold at least one token",
+            ));
+        }
+        if prefix.len() > max_seq_len {
+            return Err(TransformerError::shape(format!(
+                "decoder prefix of {} tokens exceeds max_seq_len {}",
+                prefix.len(),
+                max_seq_len
+            )));
+        }
+        if let Some(&id) = prefix.iter().find(|&&id| id >= vocab_size) {
+            return Err(TransformerError::VocabOverflow {
+                token: id,
+                vocab_size,
+            });
+        }
+        Ok(prefix)
+    }
+
+    pub fn with_truncation(mut self, truncation: Truncation) -> Self {
+        self.truncation = truncation;
+        self
+    }
+
+    pub fn with_sampling(mut self, do_sample: bool) -> Self {
+        self.do_sample = do_sample;
+        self
+    }
+
+    /// Samples under `sampling`; a greedy `sampling` also turns sampling off.
+    pub fn with_sampling_config(mut self, sampling: SamplingConfig) -> Self {
+        self.do_sample = !sampling.greedy;
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn with_bad_words(mut self, bad_words: BadWords) -> Self {
+        self.bad_words = Some(Arc::new(bad_words));
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn with_max_logprob_drop(mut self, max_drop: f64) -> Self {
+        self.max_logprob_drop = Some(max_drop);
+        self
+    }
+
+    /// Whether the cancellation token, if any, has fired.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Applies [`truncation`](Self::truncation) so `src` fits in
+    /// `max_seq_len` tokens.
+    pub fn truncate<'a>(&self, src: &'a [usize], max_seq_len: usize) -> Result<&'a [usize]> {
+        if src.len() <= max_seq_len {
+            return Ok(src);
+        }
+        match self.truncation {
+            Truncation::Left => Ok(&src[src.len() - max_seq_len..]),
+            Truncation::Right => Ok(&src[..max_seq_len]),
+            Truncation::Error => Err(TransformerError::shape(format!(
+                "source length {} exceeds max_seq_len {}",
+                src.len(),
+                max_seq_len
+            ))),
+        }
+    }
+
+    /// Longest decoder sequence allowed when decoding starts from
+    /// `prefix_len` tokens.
+    pub fn length_limit(&self, prefix_len: usize, max_seq_len: usize) -> usize {
+        let mut limit = prefix_len
+            .saturating_add(self.max_new_tokens)
+            .min(max_seq_len);
+        if let Some(max_length) = self.max_length {
+            limit = limit.min(max_length);
+        }
+        limit
+    }
+}
@@ -0,0 +1,43 @@
+//! What a generation call produced and why it stopped.
+
+use std::fmt;
+
+/// Why decoding ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinishReason {
+    /// The model emitted its end token.
+    Stop,
+    /// The token or sequence length limit was reached.
+    Length,
+    /// The wall-clock budget ran out.
+    Deadline,
+    /// The cancellation token fired.
+    Cancelled,
+    /// The cumulative log-probability fell below the allowed drop.
+    LogProbBudget,
+}
+
+impl fmt::Display for FinishReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FinishReason::Stop => "stop",
+            FinishReason::Length => "length",
+            FinishReason::Deadline => "deadline",
+            FinishReason::Cancelled => "cancelled",
+            FinishReason::LogProbBudget => "logprob_budget",
+        })
+    }
+}
+
+/// A generated sequence with its finish reason.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationOutput {
+    /// The decoder prefix followed by every generated token.
+    pub tokens: Vec<usize>,
+    pub finish_reason: FinishReason,
+    /// Sum of the log-probabilities of the generated tokens (prefix
+    /// excluded) under the temperature-scaled distribution.
+    pub log_prob: f64,
+    /// Number of generated tokens.
+    pub new_tokens: usize,
+}
@@ -0,0 +1,108 @@
+//! Reranking candidate outputs.
+//!
+//! Candidates may come from repeated sampling or from the beams of a beam
+//! search. Each is rescored by the model with teacher forcing, then ordered
+//! by the chosen [`RerankBy`] criterion; the model scores are kept on every
+//! result so different criteria can be compared.
+
+use super::LengthNormalization;
+use crate::models::Transformer;
+use crate::utils::tensor_ops::log_softmax;
+use crate::{Result, TransformerError};
+
+/// External scoring function, called with the source and a candidate.
+pub type ScoreFn<'a> = &'a dyn Fn(&[usize], &[usize]) -> Result<f64>;
+
+/// Criterion candidates are ordered by, highest first.
+#[derive(Clone, Copy)]
+pub enum RerankBy<'a> {
+    /// Summed log-probability under the model.
+    LogProb,
+    /// Log-probability divided by a length term.
+    Normalized(LengthNormalization),
+    /// An arbitrary score such as a reward model's.
+    Custom(ScoreFn<'a>),
+}
+
+/// A candidate with its scores.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedHypothesis {
+    pub tokens: Vec<usize>,
+    /// Position of the candidate in the input.
+    pub index: usize,
+    /// Score the candidates were ordered by.
+    pub score: f64,
+    /// Summed log-probability of every token after the first.
+    pub log_prob: f64,
+    /// `log_prob` divided by the number of scored tokens.
+    pub mean_log_prob: f64,
+    /// Per-token log-probabilities, one for each token after the first.
+    pub token_log_probs: Vec<f64>,
+}
+
+impl Transformer {
+    /// Log-probability of each token of `tgt` after the first given the
+    /// tokens before it and `src`, with the model's logit temperature. `tgt`
+    /// normally starts with the start token, as [`generate`](Self::generate)
+    /// output does.
+    pub fn token_log_probs(&self, src: &[usize], tgt: &[usize]) -> Result<Vec<f64>> {
+        if tgt.len() < 2 {
+            return Ok(Vec::new());
+        }
+        let memory = self.encode(src)?;
+        let logits = self.decode(&tgt[..tgt.len() - 1], src, &memory)?;
+        let temperature = self.config().logit_temperature;
+        Ok(tgt[1..]
+            .iter()
+            .enumerate()
+            .map(|(i, &token)| {
+                let row: Vec<f64> = logits.row(i).iter().map(|v| v / temperature).collect();
+                log_softmax(&row)[token]
+            })
+            .collect())
+    }
+}
+
+/// Scores every candidate for `src` and returns them best first. Ties keep
+/// input order and NaN scores sort last.
+pub fn rerank(
+    model: &Transformer,
+    src: &[usize],
+    candidates: &[Vec<usize>],
+    by: RerankBy<'_>,
+) -> Result<Vec<RankedHypothesis>> {
+    let vocab_size = model.config().vocab_size;
+    let mut ranked = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, tokens)| {
+            if let Some(&bad) = tokens.iter().find(|&&t| t >= vocab_size) {
+                return Err(TransformerError::VocabOverflow {
+                    token: bad,
+                    vocab_size,
+                });
+            }
+            let token_log_probs = model.token_log_probs(src, tokens)?;
+            let log_prob: f64 = token_log_probs.iter().sum();
+            let len = token_log_probs.len();
+            let score = match by {
+                RerankBy::LogProb => log_prob,
+                RerankBy::Normalized(normalization) => log_prob / normalization.divisor(len),
+                RerankBy::Custom(score) => score(src, tokens)?,
+            };
+            Ok(RankedHypothesis {
+                tokens: tokens.clone(),
+                index,
+                score,
+                log_prob,
+                mean_log_prob: if len == 0 { 0.0 } else { log_prob / len as f64 },
+                token_log_probs,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ranked.sort_by(|a, b| match (a.score.is_nan(), b.score.is_nan()) {
+        (false, false) => b.score.total_cmp(&a.score),
+        (nan_a, nan_b) => nan_a.cmp(&nan_b),
+    });
+    Ok(ranked)
+}
@@ -0,0 +1,148 @@
+//! Temperature, top-k and nucleus (top-p) sampling.
+//!
+//! [`SamplingConfig::filter_logits`] rescales the next-token logits by the
+//! temperature, then hides everything outside the `top_k` most likely tokens
+//! and outside the smallest set whose probability reaches `top_p` (Holtzman
+//! et al., 2020), in that order. [`SamplingConfig::pick`] draws the next
+//! token from what is left, or takes the argmax when decoding greedily.
+
+use crate::utils::rng::Rng;
+use crate::utils::tensor_ops::{argmax_row, softmax, top_k};
+use crate::{Result, TransformerError};
+
+/// How the next token is chosen from the decoder's output distribution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingConfig {
+    /// Divides the logits before sampling: below 1 sharpens the
+    /// distribution, above 1 flattens it. `0` decodes greedily.
+    pub temperature: f64,
+    /// Sample only among the `k` most likely tokens.
+    pub top_k: Option<usize>,
+    /// Sample only among the most likely tokens whose probabilities sum to
+    /// at least `p`.
+    pub top_p: Option<f64>,
+    /// Always take the most likely token.
+    pub greedy: bool,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 1.0,
+            top_k: None,
+            top_p: None,
+            greedy: false,
+        }
+    }
+}
+
+impl SamplingConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Argmax decoding.
+    pub fn greedy() -> Self {
+        Self {
+            greedy: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_top_k(mut self, k: usize) -> Self {
+        self.top_k = Some(k);
+        self
+    }
+
+    pub fn with_top_p(mut self, p: f64) -> Self {
+        self.top_p = Some(p);
+        self
+    }
+
+    /// Checks that the temperature is finite and non-negative, `top_k` is
+    /// at least 1 and `top_p` lies in `(0, 1]`.
+    pub fn validate(&self) -> Result<()> {
+        if !(self.temperature >= 0.0 && self.temperature.is_finite()) {
+            return Err(TransformerError::config(format!(
+                "sampling temperature must be non-negative, got {}",
+                self.temperature
+            )));
+        }
+        if self.top_k == Some(0) {
+            return Err(TransformerError::config("top_k must be at least 1"));
+        }
+        if let Some(p) = self.top_p {
+            if !(p > 0.0 && p <= 1.0) {
+                return Err(TransformerError::config(format!(
+                    "top_p must be in (0, 1], got {}",
+                    p
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether [`pick`](Self::pick) takes the argmax.
+    pub fn is_greedy(&self) -> bool {
+        self.greedy || self.temperature == 0.0
+    }
+
+    /// Applies the temperature and sets every logit outside the top-k and
+    /// top-p sets to `-inf`. At least one token always survives.
+    pub fn filter_logits(&self, logits: &mut [f64]) {
+        if self.temperature > 0.0 && self.temperature != 1.0 {
+            for v in logits.iter_mut() {
+                *v /= self.temperature;
+            }
+        }
+        if let Some(k) = self.top_k.filter(|&k| k > 0 && k < logits.len()) {
+            let mut keep = vec![false; logits.len()];
+            for (i, _) in top_k(logits, k) {
+                keep[i] = true;
+            }
+            hide(logits, &keep);
+        }
+        if let Some(p) = self.top_p.filter(|&p| p < 1.0) {
+            let probs = softmax(logits);
+            let mut order: Vec<usize> = (0..logits.len()).filter(|&i| probs[i] > 0.0).collect();
+            order.sort_by(|&a, &b| probs[b].total_cmp(&probs[a]).then(a.cmp(&b)));
+            let mut keep = vec![false; logits.len()];
+            let mut cumulative = 0.0;
+            for i in order {
+                keep[i] = true;
+                cumulative += probs[i];
+                if cumulative >= p {
+                    break;
+                }
+            }
+            hide(logits, &keep);
+        }
+    }
+
+    /// Chooses the next token from `logits`: the argmax when greedy,
+    /// otherwise a draw from the filtered distribution. `None` if every
+    /// logit is NaN.
+    pub fn pick(&self, logits: &[f64], rng: &mut Rng) -> Option<usize> {
+        if self.is_greedy() {
+            return argmax_row(logits);
+        }
+        let mut filtered = logits.to_vec();
+        self.filter_logits(&mut filtered);
+        argmax_row(&filtered)?;
+        Some(rng.categorical(&softmax(&filtered)))
+    }
+}
+
+/// Sets the logits whose `keep` flag is unset to `-inf`.
+fn hide(logits: &mut [f64], keep: &[bool]) {
+    for (v, &k) in logits.iter_mut().zip(keep) {
+        if !k {
+            *v = f64::NEG_INFINITY;
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Hypothesis scoring for beam search.
+//!
+//! Summed log-probabilities favour short outputs, since every extra token
+//! can only lower the total. [`BeamScoring`] corrects for this by dividing
+//! by a length term and optionally rewarding hypotheses whose cross-attention
+//! covers the whole source:
+//!
+//! ```text
+//! score = log P(y | x) / lp(|y|) + β · Σ_i ln min(Σ_j a_ji, 1)
+//! lp(n) = ((5 + n) / 6)^α
+//! ```
+
+use crate::models::DecoderLayerAttentions;
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// How a hypothesis' summed log-probability is normalized by its length.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LengthNormalization {
+    /// Raw summed log-probability.
+    #[default]
+    None,
+    /// Mean log-probability per token.
+    Average,
+    /// The GNMT length penalty `((5 + n) / 6)^alpha`. `alpha = 0` is the raw
+    /// sum and `alpha = 1` is close to the average; values around 0.6–1.0
+    /// work well for translation.
+    Wu { alpha: f64 },
+}
+
+impl LengthNormalization {
+    /// Divisor applied to the log-probability of an `n`-token hypothesis.
+    pub fn divisor(&self, len: usize) -> f64 {
+        match *self {
+            LengthNormalization::None => 1.0,
+            LengthNormalization::Average => len.max(1) as f64,
+            LengthNormalization::Wu { alpha } => ((5.0 + len as f64) / 6.0).powf(alpha),
+        }
+    }
+}
+
+/// Ranks finished beam hypotheses.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BeamScoring {
+    pub normalization: LengthNormalization,
+    /// Weight `β` of the coverage penalty; 0 disables it.
+    pub coverage_penalty: f64,
+}
+
+impl BeamScoring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the GNMT length penalty with exponent `alpha`.
+    pub fn with_length_penalty(mut self, alpha: f64) -> Self {
+        self.normalization = LengthNormalization::Wu { alpha };
+        self
+    }
+
+    pub fn with_normalization(mut self, normalization: LengthNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn with_coverage_penalty(mut self, beta: f64) -> Self {
+        self.coverage_penalty = beta;
+        self
+    }
+
+    /// Whether [`score`](Self::score) needs cross-attention probabilities.
+    pub fn uses_coverage(&self) -> bool {
+        self.coverage_penalty != 0.0
+    }
+
+    /// Coverage term `β · Σ_i ln min(Σ_j a_ji, 1)` for a `tgt_len × src_len`
+    /// attention matrix. It is zero once every source position has received
+    /// a total attention of at least one and negative otherwise.
+    pub fn coverage(&self, attention: &Matrix) -> f64 {
+        if !self.uses_coverage() {
+            return 0.0;
+        }
+        let total: f64 = (0..attention.cols())
+            .map(|i| {
+                let received: f64 = (0..attention.rows()).map(|j| attention[(j, i)]).sum();
+                // Keep a source position nothing attended to from scoring -inf.
+                received.clamp(1e-10, 1.0).ln()
+            })
+            .sum();
+        self.coverage_penalty * total
+    }
+
+    /// Final score of a hypothesis with summed log-probability `log_prob`
+    /// over `len` generated tokens. `attention` is only read when the
+    /// coverage penalty is enabled.
+    pub fn score(&self, log_prob: f64, len: usize, attention: Option<&Matrix>) -> f64 {
+        let normalized = log_prob / self.normalization.divisor(len);
+        match attention {
+            Some(attention) => normalized + self.coverage(attention),
+            None => normalized,
+        }
+    }
+}
+
+/// Cross-attention averaged over every layer and head, `tgt_len × src_len`,
+/// as used for the coverage penalty.
+pub fn mean_cross_attention(layers: &[DecoderLayerAttentions]) -> Result<Matrix> {
+    let mut heads = layers.iter().flat_map(|l| &l.cross_attention);
+    let first = heads
+        .next()
+        .ok_or("no cross-attention maps to average")?
+        .clone();
+    let mut count = 1.0;
+    let mut sum = first;
+    for head in heads {
+        sum.add_assign(head)?;
+        count += 1.0;
+    }
+    Ok(sum.scale(1.0 / count))
+}
@@ -0,0 +1,202 @@
+//! Per-request decoding state over a shared model.
+//!
+//! An [`InferenceSession`] owns everything that changes while one output is
+//! decoded: the encoded source, the decoder key/value cache, the tokens so
+//! far and the sampling generator. The model is only borrowed, so any number
+//! of sessions can decode against one `Transformer` (or one behind an
+//! `Arc`) at the same time, each on its own thread.
+
+use crate::generation::SamplingConfig;
+use crate::models::{DecoderKvCache, Transformer};
+use crate::tensor::Matrix;
+use crate::utils::rng::Rng;
+use crate::utils::tensor_ops::argmax_row;
+use crate::{Result, TransformerError};
+
+/// Incremental decoding of one output sequence.
+#[derive(Debug, Clone)]
+pub struct InferenceSession<'a> {
+    model: &'a Transformer,
+    src: Vec<usize>,
+    memory: Matrix,
+    prefix_len: usize,
+    tokens: Vec<usize>,
+    /// `None` while the decoder has hooks, which need whole sequences.
+    kv_cache: Option<DecoderKvCache>,
+    /// Logits of the position after `tokens`, once computed.
+    next_logits: Option<Vec<f64>>,
+    /// Forked from the model's generator on the first draw unless one was
+    /// supplied with [`with_rng`](Self::with_rng).
+    rng: Option<Rng>,
+}
+
+impl<'a> InferenceSession<'a> {
+    /// Encodes `src` and starts the decoder from the start token. Samples
+    /// are drawn from a fork of the model's [`rng`](Transformer::rng).
+    pub fn new(model: &'a Transformer, src: &[usize]) -> Result<Self> {
+        Self::with_prefix(model, src, vec![model.config.bos_token_id])
+    }
+
+    /// Like [`new`](Self::new), starting the decoder from `prefix` instead.
+    pub fn with_prefix(model: &'a Transformer, src: &[usize], prefix: Vec<usize>) -> Result<Self> {
+        if prefix.is_empty() {
+            return Err(TransformerError::config(
+                "decoder prefix must hold at least one token",
+            ));
+        }
+        let config = &model.config;
+        if let Some(limit) = Self::position_limit(model).filter(|&l| prefix.len() > l) {
+            return Err(TransformerError::shape(format!(
+                "decoder prefix of {} tokens exceeds max_seq_len {}",
+                prefix.len(),
+                limit
+            )));
+        }
+        if let Some(&token) = prefix.iter().find(|&&t| t >= config.vocab_size) {
+            return Err(TransformerError::VocabOverflow {
+                token,
+                vocab_size: config.vocab_size,
+            });
+        }
+        let memory = model.encode(src)?;
+        let kv_cache = if model.decoder.hooks.is_empty() {
+            Some(model.decoder.start_kv_cache(&memory)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            model,
+            src: src.to_vec(),
+            memory,
+            prefix_len: prefix.len(),
+            tokens: prefix,
+            kv_cache,
+            next_logits: None,
+            rng: None,
+        })
+    }
+
+    /// Draws samples from `rng` rather than from the model's generator,
+    /// which is then left untouched.
+    pub fn with_rng(mut self, rng: Rng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
+    /// The longest decoder sequence the model's position table covers, or
+    /// `None` when it has none (ALiBi) and sequences may grow freely.
+    fn position_limit(model: &Transformer) -> Option<usize> {
+        let positional = &model.decoder.positional;
+        (!positional.is_none()).then(|| positional.max_seq_len())
+    }
+
+    pub fn model(&self) -> &'a Transformer {
+        self.model
+    }
+
+    pub fn source(&self) -> &[usize] {
+        &self.src
+    }
+
+    /// The encoder output cross-attention reads.
+    pub fn memory(&self) -> &Matrix {
+        &self.memory
+    }
+
+    /// The decoder sequence so far, starting with the prefix.
+    pub fn tokens(&self) -> &[usize] {
+        &self.tokens
+    }
+
+    /// The tokens added after the prefix.
+    pub fn new_tokens(&self) -> &[usize] {
+        &self.tokens[self.prefix_len..]
+    }
+
+    /// Whether the sequence ended with the end token or fills the model's
+    /// position table. Under ALiBi only the end token finishes it.
+    pub fn is_finished(&self) -> bool {
+        let config = &self.model.config;
+        Self::position_limit(self.model).is_some_and(|limit| self.tokens.len() >= limit)
+            || (self.tokens.len() > self.prefix_len
+                && self.tokens.last() == Some(&config.eos_token_id))
+    }
+
+    /// Logits of the next token, divided by the model's
+    /// [`logit_temperature`](crate::TransformerConfig::logit_temperature).
+    /// Only positions not yet in the key/value cache are decoded; repeated
+    /// calls without a [`push`](Self::push) reuse the result.
+    pub fn next_logits(&mut self) -> Result<&[f64]> {
+        if self.next_logits.is_none() {
+            let logits = match &mut self.kv_cache {
+                Some(cache) => self
+                    .model
+                    .decode_incremental(&self.tokens, &self.src, cache)?,
+                None => self.model.decode(&self.tokens, &self.src, &self.memory)?,
+            };
+            let temperature = self.model.config.logit_temperature;
+            let last = logits.row(logits.rows() - 1);
+            self.next_logits = Some(last.iter().map(|v| v / temperature).collect());
+        }
+        Ok(self.next_logits.as_deref().unwrap_or_default())
+    }
+
+    /// Appends `token`, whether chosen by the caller or by
+    /// [`sample`](Self::sample).
+    pub fn push(&mut self, token: usize) -> Result<()> {
+        let config = &self.model.config;
+        if token >= config.vocab_size {
+            return Err(TransformerError::VocabOverflow {
+                token,
+                vocab_size: config.vocab_size,
+            });
+        }
+        if let Some(limit) = Self::position_limit(self.model).filter(|&l| self.tokens.len() >= l) {
+            return Err(TransformerError::shape(format!(
+                "decoder sequence already holds max_seq_len = {} tokens",
+                limit
+            )));
+        }
+        self.tokens.push(token);
+        self.next_logits = None;
+        Ok(())
+    }
+
+    /// Chooses the next token under `sampling`, appends it and returns it.
+    pub fn sample(&mut self, sampling: &SamplingConfig) -> Result<usize> {
+        sampling.validate()?;
+        self.next_logits()?;
+        let logits = self.next_logits.as_deref().unwrap_or_default();
+        let model = self.model;
+        let rng = self.rng.get_or_insert_with(|| model.rng.fork());
+        let next = sampling
+            .pick(logits, rng)
+            .ok_or("decoder produced no finite logits")?;
+        self.push(next)?;
+        Ok(next)
+    }
+
+    /// Appends the most likely next token and returns it.
+    pub fn greedy(&mut self) -> Result<usize> {
+        let next = argmax_row(self.next_logits()?).ok_or("decoder produced no finite logits")?;
+        self.push(next)?;
+        Ok(next)
+    }
+
+    /// Drops everything after the prefix, keeping the encoded source.
+    pub fn reset(&mut self) -> Result<()> {
+        self.tokens.truncate(self.prefix_len);
+        self.next_logits = None;
+        if self.kv_cache.is_some() {
+            self.kv_cache = Some(self.model.decoder.start_kv_cache(&self.memory)?);
+        }
+        Ok(())
+    }
+}
+
+impl Transformer {
+    /// Starts an [`InferenceSession`] decoding from `src`.
+    pub fn start_session(&self, src: &[usize]) -> Result<InferenceSession<'_>> {
+        InferenceSession::new(self, src)
+    }
+}
@@ -0,0 +1,692 @@
+//! Reading and writing GGUF model files.
+//!
+//! GGUF (version 3) is the single-file format of llama.cpp: a header of
+//! typed key/value metadata, a table of tensor descriptions and the aligned
+//! tensor data. [`export_gguf`] writes a [`Transformer`] with its
+//! configuration as metadata and 2-D weights optionally quantized to
+//! [`GgmlType::Q8_0`] or [`GgmlType::Q4_0`]; [`load_gguf`] reads such a file
+//! back into a model.
+//!
+//! Tensors keep the crate's parameter names (`encoder.layers.0.norm1.gamma`,
+//! ...). Dimensions are listed innermost first as GGUF requires, so a
+//! `rows × cols` matrix is stored with shape `[cols, rows]`.
+//!
+//! The model's [`ModelMetadata`] is kept under `general.license` and
+//! `provenance.*` keys (`provenance.eval.{metric}`, `provenance.extra.{key}`
+//! for the maps).
+
+use std::fs;
+use std::path::Path;
+
+use crate::config::{parse_activation, InitScheme, NormType, PositionalScheme, TransformerConfig};
+use crate::models::{ModelMetadata, Transformer};
+use crate::params::Parameters;
+use crate::progress::{NoProgress, Progress, ProgressHandler, Stage};
+use crate::quantization::GgmlType;
+use crate::tensor::Matrix;
+use crate::testing::{load_parameters_with_progress, Fixtures};
+use crate::Result;
+
+const MAGIC: &[u8; 4] = b"GGUF";
+const VERSION: u32 = 3;
+const DEFAULT_ALIGNMENT: usize = 32;
+/// Value of `general.architecture` in exported files.
+pub const ARCHITECTURE: &str = "transformer";
+
+/// A typed metadata value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+    /// Elements must all have the same type.
+    Array(Vec<GgufValue>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl GgufValue {
+    fn type_id(&self) -> u32 {
+        match self {
+            GgufValue::U8(_) => 0,
+            GgufValue::I8(_) => 1,
+            GgufValue::U16(_) => 2,
+            GgufValue::I16(_) => 3,
+            GgufValue::U32(_) => 4,
+            GgufValue::I32(_) => 5,
+            GgufValue::F32(_) => 6,
+            GgufValue::Bool(_) => 7,
+            GgufValue::String(_) => 8,
+            GgufValue::Array(_) => 9,
+            GgufValue::U64(_) => 10,
+            GgufValue::I64(_) => 11,
+            GgufValue::F64(_) => 12,
+        }
+    }
+
+    /// Any non-negative integer value as `usize`.
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            GgufValue::U8(v) => Some(v as usize),
+            GgufValue::U16(v) => Some(v as usize),
+            GgufValue::U32(v) => Some(v as usize),
+            GgufValue::U64(v) => usize::try_from(v).ok(),
+            GgufValue::I8(v) => usize::try_from(v).ok(),
+            GgufValue::I16(v) => usize::try_from(v).ok(),
+            GgufValue::I32(v) => usize::try_from(v).ok(),
+            GgufValue::I64(v) => usize::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Any floating-point value as `f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            GgufValue::F32(v) => Some(v as f64),
+            GgufValue::F64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            GgufValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) -> Result<()> {
+        match self {
+            GgufValue::U8(v) => out.push(*v),
+            GgufValue::I8(v) => out.push(*v as u8),
+            GgufValue::U16(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GgufValue::I16(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GgufValue::U32(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GgufValue::I32(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GgufValue::F32(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GgufValue::Bool(v) => out.push(*v as u8),
+            GgufValue::String(s) => write_string(out, s),
+            GgufValue::Array(items) => {
+                let elem_type = items
+                    .first()
+                    .map_or(GgufValue::U8(0).type_id(), |i| i.type_id());
+                if items.iter().any(|i| i.type_id() != elem_type) {
+                    return Err("GGUF arrays must hold values of a single type".into());
+                }
+                out.extend_from_slice(&elem_type.to_le_bytes());
+                out.extend_from_slice(&(items.len() as u64).to_le_bytes());
+                for item in items {
+                    item.write(out)?;
+                }
+            }
+            GgufValue::U64(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GgufValue::I64(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GgufValue::F64(v) => out.extend_from_slice(&v.to_le_bytes()),
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut Reader<'_>, type_id: u32) -> Result<Self> {
+        Ok(match type_id {
+            0 => GgufValue::U8(reader.u8()?),
+            1 => GgufValue::I8(reader.u8()? as i8),
+            2 => GgufValue::U16(u16::from_le_bytes(reader.array()?)),
+            3 => GgufValue::I16(i16::from_le_bytes(reader.array()?)),
+            4 => GgufValue::U32(reader.u32()?),
+            5 => GgufValue::I32(i32::from_le_bytes(reader.array()?)),
+            6 => GgufValue::F32(f32::from_le_bytes(reader.array()?)),
+            7 => GgufValue::Bool(reader.u8()? != 0),
+            8 => GgufValue::String(reader.string()?),
+            9 => {
+                let elem_type = reader.u32()?;
+                let len = reader.len()?;
+                let mut items = Vec::with_capacity(len.min(1 << 16));
+                for _ in 0..len {
+                    items.push(GgufValue::read(reader, elem_type)?);
+                }
+                GgufValue::Array(items)
+            }
+            10 => GgufValue::U64(reader.u64()?),
+            11 => GgufValue::I64(i64::from_le_bytes(reader.array()?)),
+            12 => GgufValue::F64(f64::from_le_bytes(reader.array()?)),
+            other => return Err(format!("unknown GGUF value type {}", other).into()),
+        })
+    }
+}
+
+impl From<usize> for GgufValue {
+    fn from(v: usize) -> Self {
+        match u32::try_from(v) {
+            Ok(v) => GgufValue::U32(v),
+            Err(_) => GgufValue::U64(v as u64),
+        }
+    }
+}
+
+impl From<f64> for GgufValue {
+    fn from(v: f64) -> Self {
+        GgufValue::F64(v)
+    }
+}
+
+impl From<bool> for GgufValue {
+    fn from(v: bool) -> Self {
+        GgufValue::Bool(v)
+    }
+}
+
+impl From<&str> for GgufValue {
+    fn from(s: &str) -> Self {
+        GgufValue::String(s.to_string())
+    }
+}
+
+impl From<String> for GgufValue {
+    fn from(s: String) -> Self {
+        GgufValue::String(s)
+    }
+}
+
+/// One stored tensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GgufTensor {
+    pub name: String,
+    /// Dimensions, innermost first.
+    pub dims: Vec<usize>,
+    pub ggml_type: GgmlType,
+    /// Encoded values.
+    pub data: Vec<u8>,
+}
+
+impl GgufTensor {
+    /// Encodes `matrix` as `ggml_type`, storing `1 × n` matrices as 1-D.
+    pub fn from_matrix(
+        name: impl Into<String>,
+        matrix: &Matrix,
+        ggml_type: GgmlType,
+    ) -> Result<Self> {
+        let values: Vec<f32> = matrix.as_slice().iter().map(|&v| v as f32).collect();
+        let dims = if matrix.rows() == 1 {
+            vec![matrix.cols()]
+        } else {
+            vec![matrix.cols(), matrix.rows()]
+        };
+        Ok(Self {
+            name: name.into(),
+            dims,
+            ggml_type,
+            data: ggml_type.encode(&values)?,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.dims.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decodes the tensor as a matrix whose columns are the innermost
+    /// dimension; 1-D tensors become a single row.
+    pub fn to_matrix(&self) -> Result<Matrix> {
+        let cols = self.dims.first().copied().unwrap_or(1);
+        let rows = self.dims.iter().skip(1).product();
+        let values = self.ggml_type.decode(&self.data, self.len())?;
+        Matrix::from_vec(rows, cols, values.into_iter().map(f64::from).collect())
+    }
+}
+
+/// Contents of a GGUF file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GgufFile {
+    pub version: u32,
+    /// Key/value metadata in file order.
+    pub metadata: Vec<(String, GgufValue)>,
+    pub tensors: Vec<GgufTensor>,
+}
+
+impl Default for GgufFile {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            metadata: Vec::new(),
+            tensors: Vec::new(),
+        }
+    }
+}
+
+impl GgufFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `key`, replacing an existing value.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<GgufValue>) {
+        let key = key.into();
+        let value = value.into();
+        match self.metadata.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.metadata.push((key, value)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn tensor(&self, name: &str) -> Option<&GgufTensor> {
+        self.tensors.iter().find(|t| t.name == name)
+    }
+
+    /// Alignment of tensor data (`general.alignment`, 32 by default).
+    pub fn alignment(&self) -> usize {
+        self.get("general.alignment")
+            .and_then(GgufValue::as_usize)
+            .filter(|&a| a > 0)
+            .unwrap_or(DEFAULT_ALIGNMENT)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Decodes a GGUF file of version 2 or 3.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err("not a GGUF file (bad magic)".into());
+        }
+        let version = reader.u32()?;
+        if !(2..=3).contains(&version) {
+            return Err(format!("unsupported GGUF version {}", version).into());
+        }
+        let tensor_count = reader.len()?;
+        let metadata_count = reader.len()?;
+
+        let mut file = GgufFile {
+            version,
+            ..Self::default()
+        };
+        for _ in 0..metadata_count {
+            let key = reader.string()?;
+            let type_id = reader.u32()?;
+            let value = GgufValue::read(&mut reader, type_id)?;
+            file.metadata.push((key, value));
+        }
+
+        let mut infos = Vec::with_capacity(tensor_count.min(1 << 16));
+        for _ in 0..tensor_count {
+            let name = reader.string()?;
+            let n_dims = reader.u32()? as usize;
+            let dims = (0..n_dims)
+                .map(|_| reader.len())
+                .collect::<Result<Vec<_>>>()?;
+            let ggml_type = GgmlType::from_id(reader.u32()?)?;
+            let offset = reader.len()?;
+            infos.push((name, dims, ggml_type, offset));
+        }
+
+        let data_start = reader.pos.next_multiple_of(file.alignment());
+        for (name, dims, ggml_type, offset) in infos {
+            let len = dims.iter().product();
+            let size = ggml_type.size_of(len)?;
+            let start = data_start
+                .checked_add(offset)
+                .ok_or("GGUF tensor offset overflows")?;
+            let data = bytes
+                .get(start..start.saturating_add(size))
+                .ok_or_else(|| format!("truncated data for tensor '{}'", name))?;
+            file.tensors.push(GgufTensor {
+                name,
+                dims,
+                ggml_type,
+                data: data.to_vec(),
+            });
+        }
+        Ok(file)
+    }
+
+    /// Encodes the file, aligning every tensor to [`alignment`](Self::alignment).
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let alignment = self.alignment();
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&(self.tensors.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(self.metadata.len() as u64).to_le_bytes());
+        for (key, value) in &self.metadata {
+            write_string(&mut out, key);
+            out.extend_from_slice(&value.type_id().to_le_bytes());
+            value.write(&mut out)?;
+        }
+
+        let mut offset = 0usize;
+        for tensor in &self.tensors {
+            let size = tensor.ggml_type.size_of(tensor.len())?;
+            if tensor.data.len() != size {
+                return Err(format!(
+                    "tensor '{}' holds {} bytes, its shape needs {}",
+                    tensor.name,
+                    tensor.data.len(),
+                    size
+                )
+                .into());
+            }
+            write_string(&mut out, &tensor.name);
+            out.extend_from_slice(&(tensor.dims.len() as u32).to_le_bytes());
+            for &d in &tensor.dims {
+                out.extend_from_slice(&(d as u64).to_le_bytes());
+            }
+            out.extend_from_slice(&tensor.ggml_type.id().to_le_bytes());
+            out.extend_from_slice(&(offset as u64).to_le_bytes());
+            offset = (offset + size).next_multiple_of(alignment);
+        }
+
+        out.resize(out.len().next_multiple_of(alignment), 0);
+        for tensor in &self.tensors {
+            out.extend_from_slice(&tensor.data);
+            out.resize(out.len().next_multiple_of(alignment), 0);
+        }
+        Ok(out)
+    }
+
+    /// Dequantizes every tensor into fixtures keyed by tensor name.
+    pub fn to_fixtures(&self) -> Result<Fixtures> {
+        let mut fixtures = Fixtures::new();
+        for tensor in &self.tensors {
+            fixtures.insert(tensor.name.clone(), tensor.to_matrix()?);
+        }
+        Ok(fixtures)
+    }
+
+    /// Reads back the model card stored by [`to_gguf`]. Missing keys leave
+    /// fields unset.
+    pub fn model_metadata(&self) -> ModelMetadata {
+        let string_at = |key: &str| self.get(key).and_then(GgufValue::as_str).map(String::from);
+        let mut metadata = ModelMetadata {
+            training_data: string_at("provenance.training_data"),
+            license: string_at("general.license"),
+            git_commit: string_at("provenance.git_commit"),
+            config_hash: string_at("provenance.config_hash"),
+            ..ModelMetadata::default()
+        };
+        for (key, value) in &self.metadata {
+            if let Some(metric) = key.strip_prefix("provenance.eval.") {
+                if let Some(score) = value.as_f64() {
+                    metadata.eval_scores.insert(metric.to_string(), score);
+                }
+            } else if let Some(name) = key.strip_prefix("provenance.extra.") {
+                if let Some(value) = value.as_str() {
+                    metadata.extra.insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+        metadata
+    }
+
+    /// Rebuilds the configuration stored by [`export_gguf`].
+    pub fn transformer_config(&self) -> Result<TransformerConfig> {
+        if let Some(arch) = self.get("general.architecture").and_then(GgufValue::as_str) {
+            if arch != ARCHITECTURE {
+                return Err(format!(
+                    "GGUF architecture is '{}', expected '{}'",
+                    arch, ARCHITECTURE
+                )
+                .into());
+            }
+        }
+        let key = |name: &str| format!("{}.{}", ARCHITECTURE, name);
+        let usize_at = |name: &str| -> Result<usize> {
+            self.get(name)
+                .and_then(GgufValue::as_usize)
+                .ok_or_else(|| format!("GGUF metadata lacks integer '{}'", name).into())
+        };
+        let f64_or = |name: &str, default: f64| {
+            self.get(name)
+                .and_then(GgufValue::as_f64)
+                .unwrap_or(default)
+        };
+        let bool_or = |name: &str| self.get(name).and_then(GgufValue::as_bool).unwrap_or(false);
+        let defaults = TransformerConfig::default();
+        let config = TransformerConfig {
+            vocab_size: usize_at(&key("vocab_size"))?,
+            d_model: usize_at(&key("embedding_length"))?,
+            num_heads: usize_at(&key("attention.head_count"))?,
+            num_encoder_layers: usize_at(&key("encoder.block_count"))?,
+            num_decoder_layers: usize_at(&key("decoder.block_count"))?,
+            d_ff: usize_at(&key("feed_forward_length"))?,
+            activation: match self.get(&key("activation")).and_then(GgufValue::as_str) {
+                Some(name) => parse_activation(name)?,
+                None => defaults.activation,
+            },
+            max_seq_len: usize_at(&key("context_length"))?,
+            positional: match self.get(&key("positional")).and_then(GgufValue::as_str) {
+                Some(name) => PositionalScheme::from_name(name)?,
+                None => defaults.positional,
+            },
+            attention_window: self
+                .get(&key("attention.sliding_window"))
+                .and_then(GgufValue::as_usize),
+            init: match self.get(&key("init")).and_then(GgufValue::as_str) {
+                Some(name) => InitScheme::from_name(name)?,
+                None => defaults.init,
+            },
+            dropout: f64_or(&key("dropout"), defaults.dropout),
+            norm: match self.get(&key("norm")).and_then(GgufValue::as_str) {
+                Some(name) => NormType::from_name(name)?,
+                None => defaults.norm,
+            },
+            layer_norm_eps: f64_or(
+                &key("attention.layer_norm_epsilon"),
+                defaults.layer_norm_eps,
+            ),
+            pad_token_id: usize_at("tokenizer.ggml.padding_token_id")?,
+            bos_token_id: usize_at("tokenizer.ggml.bos_token_id")?,
+            eos_token_id: usize_at("tokenizer.ggml.eos_token_id")?,
+            scale_embeddings: self
+                .get(&key("scale_embeddings"))
+                .and_then(GgufValue::as_bool)
+                .unwrap_or(defaults.scale_embeddings),
+            share_embeddings: bool_or(&key("share_embeddings")),
+            attention_bias: bool_or(&key("attention.bias")),
+            output_bias: bool_or(&key("output_bias")),
+            logit_temperature: f64_or(&key("logit_temperature"), defaults.logit_temperature),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// `general.file_type` code for a file whose large weights are `ggml_type`.
+fn file_type(ggml_type: GgmlType) -> u32 {
+    match ggml_type {
+        GgmlType::F32 => 0,
+        GgmlType::F16 => 1,
+        GgmlType::Q4_0 => 2,
+        GgmlType::Q8_0 => 7,
+    }
+}
+
+/// Builds a GGUF file holding `model`'s configuration and weights. 2-D
+/// weights whose rows fill whole blocks are stored as `ggml_type`; vectors
+/// (norms, biases) and other matrices stay `F32`, as llama.cpp does.
+pub fn to_gguf(model: &Transformer, name: &str, ggml_type: GgmlType) -> Result<GgufFile> {
+    to_gguf_with_progress(model, name, ggml_type, &NoProgress)
+}
+
+/// [`to_gguf`], reporting [`Stage::Quantizing`] after every tensor.
+pub fn to_gguf_with_progress(
+    model: &Transformer,
+    name: &str,
+    ggml_type: GgmlType,
+    progress: &dyn ProgressHandler,
+) -> Result<GgufFile> {
+    let config = model.config();
+    let mut file = GgufFile::new();
+    file.set("general.architecture", ARCHITECTURE);
+    file.set("general.name", name);
+    file.set("general.alignment", DEFAULT_ALIGNMENT);
+    file.set("general.file_type", GgufValue::U32(file_type(ggml_type)));
+    file.set("general.quantization_version", GgufValue::U32(2));
+    let key = |name: &str| format!("{}.{}", ARCHITECTURE, name);
+    file.set(key("vocab_size"), config.vocab_size);
+    file.set(key("context_length"), config.max_seq_len);
+    file.set(key("positional"), config.positional.name());
+    file.set(key("init"), config.init.name());
+    file.set(key("activation"), config.activation.name());
+    file.set(key("norm"), config.norm.name());
+    file.set(key("embedding_length"), config.d_model);
+    file.set(key("feed_forward_length"), config.d_ff);
+    file.set(key("encoder.block_count"), config.num_encoder_layers);
+    file.set(key("decoder.block_count"), config.num_decoder_layers);
+    file.set(key("attention.head_count"), config.num_heads);
+    file.set(key("attention.layer_norm_epsilon"), config.layer_norm_eps);
+    file.set(key("attention.bias"), config.attention_bias);
+    if let Some(window) = config.attention_window {
+        file.set(key("attention.sliding_window"), window);
+    }
+    file.set(key("dropout"), config.dropout);
+    file.set(key("output_bias"), config.output_bias);
+    file.set(key("scale_embeddings"), config.scale_embeddings);
+    file.set(key("share_embeddings"), config.share_embeddings);
+    file.set(key("logit_temperature"), config.logit_temperature);
+    file.set("tokenizer.ggml.bos_token_id", config.bos_token_id);
+    file.set("tokenizer.ggml.eos_token_id", config.eos_token_id);
+    file.set("tokenizer.ggml.padding_token_id", config.pad_token_id);
+
+    let metadata = model.metadata();
+    let fields = [
+        ("general.license", &metadata.license),
+        ("provenance.training_data", &metadata.training_data),
+        ("provenance.git_commit", &metadata.git_commit),
+        ("provenance.config_hash", &metadata.config_hash),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            file.set(key, value.as_str());
+        }
+    }
+    for (metric, &score) in &metadata.eval_scores {
+        file.set(format!("provenance.eval.{}", metric), score);
+    }
+    for (name, value) in &metadata.extra {
+        file.set(format!("provenance.extra.{}", name), value.as_str());
+    }
+
+    let mut total = 0;
+    model.visit_parameters("", &mut |_, _| total += 1);
+    let mut result = Ok(());
+    model.visit_parameters("", &mut |name, param| {
+        if result.is_err() {
+            return;
+        }
+        let quantize = param.rows() > 1 && param.cols().is_multiple_of(ggml_type.block_len());
+        let ty = if quantize { ggml_type } else { GgmlType::F32 };
+        match GgufTensor::from_matrix(name, param, ty) {
+            Ok(tensor) => file.tensors.push(tensor),
+            Err(e) => result = Err(e),
+        }
+        let done = Progress::new(Stage::Quantizing, file.tensors.len(), total);
+        progress.on_progress(&done);
+    });
+    result?;
+    Ok(file)
+}
+
+/// Writes `model` to `path` as GGUF; see [`to_gguf`].
+pub fn export_gguf(model: &Transformer, path: impl AsRef<Path>, ggml_type: GgmlType) -> Result<()> {
+    let path = path.as_ref();
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("model");
+    to_gguf(model, name, ggml_type)?.write(path)
+}
+
+/// Builds a model from a file written by [`export_gguf`], dequantizing
+/// quantized weights. The model card is available from
+/// [`Transformer::metadata`].
+pub fn load_gguf(path: impl AsRef<Path>) -> Result<Transformer> {
+    load_gguf_with_progress(path, &NoProgress)
+}
+
+/// [`load_gguf`], reporting [`Stage::LoadingWeights`] after every parameter.
+pub fn load_gguf_with_progress(
+    path: impl AsRef<Path>,
+    progress: &dyn ProgressHandler,
+) -> Result<Transformer> {
+    let file = GgufFile::read(path)?;
+    let mut model =
+        Transformer::new(file.transformer_config()?)?.with_metadata(file.model_metadata());
+    load_parameters_with_progress(&mut model, "", &file.to_fixtures()?, progress)?;
+    Ok(model)
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Bounds-checked little-endian cursor.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.bytes.len());
+        let end = end.ok_or("truncated GGUF file")?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// A `u64` count or size that must fit in memory.
+    fn len(&mut self) -> Result<usize> {
+        let v = self.u64()?;
+        usize::try_from(v)
+            .ok()
+            .filter(|&v| v <= self.bytes.len().saturating_mul(8).max(1 << 20))
+            .ok_or_else(|| format!("implausible GGUF length {}", v).into())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.len()?;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_string())
+    }
+}
@@ -0,0 +1,124 @@
+//! Callbacks fired at named points of a forward pass.
+//!
+//! A hook is registered against a dotted module name such as
+//! `encoder.layers.0.self_attention`, using the same naming scheme as
+//! [`Parameters`](crate::Parameters). A `*` segment matches any single
+//! segment, so `decoder.layers.*.cross_attention` selects every layer.
+//!
+//! Hooks receive the module input and a mutable reference to its output.
+//! Reading the output is enough for logging activations; writing it changes
+//! what the rest of the model sees, which allows ablations and other
+//! interventions.
+//!
+//! Hook points of [`Encoder`](crate::models::Encoder) and
+//! [`Decoder`](crate::models::Decoder), relative to the stack:
+//!
+//! - `layers.{i}`: the whole layer
+//! - `layers.{i}.self_attention`, `layers.{i}.cross_attention` (decoder only)
+//! - `layers.{i}.feed_forward`
+//! - `layers.{i}.norm1` … `layers.{i}.norm3`: input is the residual sum
+//! - `output_projection` (decoder only): input is the final hidden state,
+//!   output the logits
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::tensor::Matrix;
+
+/// Signature of a forward hook: module name, module input and module output.
+pub type ForwardHook = Arc<dyn Fn(&str, &Matrix, &mut Matrix) + Send + Sync>;
+
+/// Identifies a registered hook so it can be removed again. Handles are
+/// unique within the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookHandle(usize);
+
+impl HookHandle {
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        HookHandle(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A set of forward hooks keyed by module name patterns.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    forward: Vec<(HookHandle, String, ForwardHook)>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `hook` for every module whose name matches `pattern`.
+    pub fn register_forward<F>(&mut self, pattern: &str, hook: F) -> HookHandle
+    where
+        F: Fn(&str, &Matrix, &mut Matrix) + Send + Sync + 'static,
+    {
+        let handle = HookHandle::next();
+        self.insert(handle, pattern, Arc::new(hook));
+        handle
+    }
+
+    /// Registers an already shared hook under an existing handle.
+    pub(crate) fn insert(&mut self, handle: HookHandle, pattern: &str, hook: ForwardHook) {
+        self.forward.push((handle, pattern.to_string(), hook));
+    }
+
+    /// Removes a hook. Returns `false` if it was not registered here.
+    pub fn remove(&mut self, handle: HookHandle) -> bool {
+        let before = self.forward.len();
+        self.forward.retain(|(h, _, _)| *h != handle);
+        self.forward.len() != before
+    }
+
+    /// Removes every hook.
+    pub fn clear(&mut self) {
+        self.forward.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    /// Runs the hooks matching the module produced by `name`. The name is
+    /// only built when at least one hook is registered.
+    pub(crate) fn fire(&self, name: impl FnOnce() -> String, input: &Matrix, output: &mut Matrix) {
+        if self.forward.is_empty() {
+            return;
+        }
+        let name = name();
+        for (_, pattern, hook) in &self.forward {
+            if matches_pattern(pattern, &name) {
+                hook(&name, input, output);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.forward.iter().map(|(_, pattern, _)| pattern))
+            .finish()
+    }
+}
+
+/// Whether dotted `name` matches `pattern`, where `*` matches one segment.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut pattern = pattern.split('.');
+    let mut name = name.split('.');
+    loop {
+        match (pattern.next(), name.next()) {
+            (None, None) => return true,
+            (Some(p), Some(n)) if p == "*" || p == n => {}
+            _ => return false,
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! Loading pretrained Hugging Face checkpoints.
+//!
+//! A checkpoint directory holds a `config.json` and the weights as
+//! `model.safetensors` (or shards listed in `model.safetensors.index.json`).
+//! PyTorch `.bin` pickles are not read; convert them to safetensors first.
+//! Two architectures map onto the crate's post-norm layers exactly:
+//!
+//! - BERT (`model_type: "bert"`) as an [`Encoder`] with learned positions
+//!   and token-type embeddings and an embedding layer norm ([`load_bert`]),
+//!   or wrapped as an [`EncoderOnlyTransformer`] ([`load_bert_model`]).
+//! - Marian (`"marian"`) as a full [`Transformer`] ([`load_marian`]).
+//!
+//! PyTorch `nn.Linear` weights are `out × in`; they are transposed into the
+//! crate's `in × out` layout. Every parameter of the built model must be
+//! found in the checkpoint, otherwise loading fails listing what is missing;
+//! tensors the crate has no use for (poolers, heads) are ignored.
+//!
+//! The checkpoint's `tokenizer.json` loads as an [`HfTokenizer`]
+//! ([`load_tokenizer`]), so text is split exactly as the weights expect.
+
+pub mod safetensors;
+pub mod tokenizer;
+
+pub use safetensors::{parse_safetensors, read_safetensors, to_safetensors, write_safetensors};
+pub use tokenizer::HfTokenizer;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{InitScheme, NormType, PositionalScheme, TransformerConfig};
+use crate::layers::{ActivationType, Embedding, LayerNorm};
+use crate::models::{Encoder, EncoderOnlyTransformer, Transformer};
+use crate::tensor::Matrix;
+use crate::testing::{load_parameters, Fixtures};
+use crate::utils::json::Json;
+use crate::Result;
+
+/// The configuration and weights of a checkpoint directory.
+#[derive(Debug, Clone)]
+pub struct HfCheckpoint {
+    /// Parsed `config.json`.
+    pub config: Json,
+    /// Every tensor under its Hugging Face name.
+    pub tensors: BTreeMap<String, Matrix>,
+}
+
+impl HfCheckpoint {
+    /// Reads `config.json` and the safetensors weights in `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let config = Json::parse(&fs::read_to_string(dir.join("config.json"))?)
+            .map_err(|e| format!("{}: {}", dir.join("config.json").display(), e))?;
+        let mut tensors = BTreeMap::new();
+        for file in weight_files(dir)? {
+            tensors.extend(read_safetensors(file)?);
+        }
+        Ok(Self { config, tensors })
+    }
+
+    /// The `model_type` field of the configuration.
+    pub fn model_type(&self) -> Option<&str> {
+        self.config.get("model_type").and_then(Json::as_str)
+    }
+
+    fn usize_at(&self, key: &str) -> Result<usize> {
+        self.config
+            .get(key)
+            .and_then(Json::as_usize)
+            .ok_or_else(|| format!("config.json lacks integer '{}'", key).into())
+    }
+
+    fn usize_or(&self, key: &str, default: usize) -> usize {
+        self.config
+            .get(key)
+            .and_then(Json::as_usize)
+            .unwrap_or(default)
+    }
+
+    fn f64_or(&self, key: &str, default: f64) -> f64 {
+        self.config
+            .get(key)
+            .and_then(Json::as_f64)
+            .unwrap_or(default)
+    }
+
+    fn activation(&self, key: &str, default: &str) -> Result<ActivationType> {
+        let name = self
+            .config
+            .get(key)
+            .and_then(Json::as_str)
+            .unwrap_or(default);
+        ActivationType::from_name(name)
+            .ok_or_else(|| format!("unsupported activation '{}'", name).into())
+    }
+
+    /// The first of `names` present in the checkpoint.
+    fn tensor(&self, names: &[String]) -> Result<&Matrix> {
+        names
+            .iter()
+            .find_map(|name| self.tensors.get(name))
+            .ok_or_else(|| format!("checkpoint lacks tensor '{}'", names[0]).into())
+    }
+
+    /// The crate configuration of a BERT encoder. BERT has no start or end
+    /// token, so both are set to the padding id.
+    pub fn bert_config(&self) -> Result<TransformerConfig> {
+        let pad_token_id = self.usize_or("pad_token_id", 0);
+        Ok(TransformerConfig {
+            vocab_size: self.usize_at("vocab_size")?,
+            d_model: self.usize_at("hidden_size")?,
+            num_heads: self.usize_at("num_attention_heads")?,
+            num_encoder_layers: self.usize_at("num_hidden_layers")?,
+            num_decoder_layers: 0,
+            d_ff: self.usize_at("intermediate_size")?,
+            activation: self.activation("hidden_act", "gelu")?,
+            max_seq_len: self.usize_at("max_position_embeddings")?,
+            positional: PositionalScheme::Learned,
+            attention_window: None,
+            init: InitScheme::Normal {
+                std_dev: self.f64_or("initializer_range", 0.02),
+            },
+            dropout: self.f64_or("hidden_dropout_prob", 0.1),
+            norm: NormType::LayerNorm,
+            layer_norm_eps: self.f64_or("layer_norm_eps", 1e-12),
+            pad_token_id,
+            bos_token_id: pad_token_id,
+            eos_token_id: pad_token_id,
+            scale_embeddings: false,
+            share_embeddings: false,
+            attention_bias: true,
+            output_bias: false,
+            logit_temperature: 1.0,
+        })
+    }
+
+    /// Builds the BERT encoder (`bert.` prefixes are optional). Dropout
+    /// starts in inference mode.
+    pub fn bert_encoder(&self) -> Result<(TransformerConfig, Encoder)> {
+        if let Some(model_type) = self.model_type().filter(|&t| t != "bert") {
+            return Err(format!(
+                "expected a BERT checkpoint, got model_type '{}'",
+                model_type
+            )
+            .into());
+        }
+        let config = self.bert_config()?;
+        let name = |n: &str| vec![format!("bert.{}", n), n.to_string()];
+        // Checkpoints converted from TensorFlow name the norm parameters
+        // gamma/beta instead of weight/bias.
+        let norm_names = |n: &str, param: &str, legacy: &str| {
+            let mut names = name(&format!("{}.{}", n, param));
+            names.extend(name(&format!("{}.{}", n, legacy)));
+            names
+        };
+
+        let mut encoder = Encoder::new(&config)?;
+        encoder.set_training(false);
+        encoder.embedding_norm = Some(LayerNorm::new(config.d_model, config.layer_norm_eps));
+
+        let mut fixtures = Fixtures::new();
+        fixtures.insert(
+            "positional",
+            self.tensor(&name("embeddings.position_embeddings.weight"))?
+                .clone(),
+        );
+        if let Ok(token_types) = self.tensor(&name("embeddings.token_type_embeddings.weight")) {
+            encoder.token_type_embedding = Some(Embedding::from_weight(token_types.clone()));
+            fixtures.insert("token_type_embedding", token_types.clone());
+        }
+        fixtures.insert(
+            "embedding",
+            self.tensor(&name("embeddings.word_embeddings.weight"))?
+                .clone(),
+        );
+        let norm = |fixtures: &mut Fixtures, ours: &str, theirs: &str| -> Result<()> {
+            fixtures.insert(
+                format!("{}.gamma", ours),
+                self.tensor(&norm_names(theirs, "weight", "gamma"))?.clone(),
+            );
+            fixtures.insert(
+                format!("{}.beta", ours),
+                self.tensor(&norm_names(theirs, "bias", "beta"))?.clone(),
+            );
+            Ok(())
+        };
+        norm(&mut fixtures, "embedding_norm", "embeddings.LayerNorm")?;
+        for i in 0..config.num_encoder_layers {
+            let ours = |n: &str| format!("layers.{}.{}", i, n);
+            let theirs = |n: &str| format!("encoder.layer.{}.{}", i, n);
+            for (w, b, linear) in [
+                (
+                    "self_attention.w_q",
+                    "self_attention.b_q",
+                    "attention.self.query",
+                ),
+                (
+                    "self_attention.w_k",
+                    "self_attention.b_k",
+                    "attention.self.key",
+                ),
+                (
+                    "self_attention.w_v",
+                    "self_attention.b_v",
+                    "attention.self.value",
+                ),
+                (
+                    "self_attention.w_o",
+                    "self_attention.b_o",
+                    "attention.output.dense",
+                ),
+                ("feed_forward.w1", "feed_forward.b1", "intermediate.dense"),
+                ("feed_forward.w2", "feed_forward.b2", "output.dense"),
+            ] {
+                self.insert_linear(&mut fixtures, &ours(w), &ours(b), &name(&theirs(linear)))?;
+            }
+            norm(
+                &mut fixtures,
+                &ours("norm1"),
+                &theirs("attention.output.LayerNorm"),
+            )?;
+            norm(&mut fixtures, &ours("norm2"), &theirs("output.LayerNorm"))?;
+        }
+        load_parameters(&mut encoder, "", &fixtures)?;
+        Ok((config, encoder))
+    }
+
+    /// The crate configuration of a Marian translation model. Generation
+    /// starts from `decoder_start_token_id`, as in Hugging Face.
+    pub fn marian_config(&self) -> Result<TransformerConfig> {
+        let num_heads = self.usize_at("encoder_attention_heads")?;
+        let d_ff = self.usize_at("encoder_ffn_dim")?;
+        if self.usize_or("decoder_attention_heads", num_heads) != num_heads
+            || self.usize_or("decoder_ffn_dim", d_ff) != d_ff
+        {
+            return Err("encoder and decoder must share head count and FFN width".into());
+        }
+        if self.config.get("share_encoder_decoder_embeddings") == Some(&Json::Bool(false)) {
+            return Err("Marian checkpoints with separate vocabularies are not supported".into());
+        }
+        let pad_token_id = self.usize_at("pad_token_id")?;
+        Ok(TransformerConfig {
+            vocab_size: self.usize_at("vocab_size")?,
+            d_model: self.usize_at("d_model")?,
+            num_heads,
+            num_encoder_layers: self.usize_at("encoder_layers")?,
+            num_decoder_layers: self.usize_at("decoder_layers")?,
+            d_ff,
+            activation: self.activation("activation_function", "swish")?,
+            max_seq_len: self.usize_at("max_position_embeddings")?,
+            positional: PositionalScheme::Learned,
+            attention_window: None,
+            init: InitScheme::Normal {
+                std_dev: self.f64_or("init_std", 0.02),
+            },
+            dropout: self.f64_or("dropout", 0.1),
+            norm: NormType::LayerNorm,
+            layer_norm_eps: 1e-5,
+            pad_token_id,
+            bos_token_id: self.usize_or("decoder_start_token_id", pad_token_id),
+            eos_token_id: self.usize_at("eos_token_id")?,
+            scale_embeddings: self.config.get("scale_embedding") != Some(&Json::Bool(false)),
+            share_embeddings: true,
+            attention_bias: true,
+            output_bias: true,
+            logit_temperature: 1.0,
+        })
+    }
+
+    /// Builds the Marian model (`model.` prefixes are optional). Dropout
+    /// starts in inference mode.
+    pub fn marian_transformer(&self) -> Result<Transformer> {
+        if let Some(model_type) = self.model_type().filter(|&t| t != "marian") {
+            return Err(format!(
+                "expected a Marian checkpoint, got model_type '{}'",
+                model_type
+            )
+            .into());
+        }
+        let config = self.marian_config()?;
+        let name = |n: &str| vec![format!("model.{}", n), n.to_string()];
+
+        let mut model = Transformer::new(config.clone())?;
+        model.set_training(false);
+        let positions =
+            |stack: &str| match self.tensor(&name(&format!("{}.embed_positions.weight", stack))) {
+                Ok(table) => table.clone(),
+                Err(_) => marian_positions(config.max_seq_len, config.d_model),
+            };
+
+        let shared = self.tensor(&[
+            "model.shared.weight".to_string(),
+            "shared.weight".to_string(),
+            "model.encoder.embed_tokens.weight".to_string(),
+        ])?;
+        let mut fixtures = Fixtures::new();
+        fixtures.insert("encoder.embedding", shared.clone());
+        fixtures.insert("encoder.positional", positions("encoder"));
+        fixtures.insert("decoder.positional", positions("decoder"));
+        let lm_head = self
+            .tensor(&["lm_head.weight".to_string()])
+            .unwrap_or(shared);
+        fixtures.insert("decoder.output_projection", lm_head.transpose());
+        let bias = self
+            .tensor(&name("final_logits_bias"))
+            .cloned()
+            .unwrap_or_else(|_| Matrix::zeros(1, config.vocab_size));
+        fixtures.insert("decoder.output_bias", bias);
+
+        let norm = |fixtures: &mut Fixtures, ours: &str, theirs: &str| -> Result<()> {
+            fixtures.insert(
+                format!("{}.gamma", ours),
+                self.tensor(&name(&format!("{}.weight", theirs)))?.clone(),
+            );
+            fixtures.insert(
+                format!("{}.beta", ours),
+                self.tensor(&name(&format!("{}.bias", theirs)))?.clone(),
+            );
+            Ok(())
+        };
+        let attention = |fixtures: &mut Fixtures, ours: &str, theirs: &str| -> Result<()> {
+            for (w, b, proj) in [
+                ("w_q", "b_q", "q_proj"),
+                ("w_k", "b_k", "k_proj"),
+                ("w_v", "b_v", "v_proj"),
+                ("w_o", "b_o", "out_proj"),
+            ] {
+                self.insert_linear(
+                    fixtures,
+                    &format!("{}.{}", ours, w),
+                    &format!("{}.{}", ours, b),
+                    &name(&format!("{}.{}", theirs, proj)),
+                )?;
+            }
+            Ok(())
+        };
+        let feed_forward = |fixtures: &mut Fixtures, ours: &str, theirs: &str| -> Result<()> {
+            for (w, b, fc) in [("w1", "b1", "fc1"), ("w2", "b2", "fc2")] {
+                self.insert_linear(
+                    fixtures,
+                    &format!("{}.feed_forward.{}", ours, w),
+                    &format!("{}.feed_forward.{}", ours, b),
+                    &name(&format!("{}.{}", theirs, fc)),
+                )?;
+            }
+            Ok(())
+        };
+        for i in 0..config.num_encoder_layers {
+            let ours = format!("encoder.layers.{}", i);
+            let theirs = format!("encoder.layers.{}", i);
+            attention(
+                &mut fixtures,
+                &format!("{}.self_attention", ours),
+                &format!("{}.self_attn", theirs),
+            )?;
+            norm(
+                &mut fixtures,
+                &format!("{}.norm1", ours),
+                &format!("{}.self_attn_layer_norm", theirs),
+            )?;
+            feed_forward(&mut fixtures, &ours, &theirs)?;
+            norm(
+                &mut fixtures,
+                &format!("{}.norm2", ours),
+                &format!("{}.final_layer_norm", theirs),
+            )?;
+        }
+        for i in 0..config.num_decoder_layers {
+            let ours = format!("decoder.layers.{}", i);
+            let theirs = format!("decoder.layers.{}", i);
+            attention(
+                &mut fixtures,
+                &format!("{}.self_attention", ours),
+                &format!("{}.self_attn", theirs),
+            )?;
+            norm(
+                &mut fixtures,
+                &format!("{}.norm1", ours),
+                &format!("{}.self_attn_layer_norm", theirs),
+            )?;
+            attention(
+                &mut fixtures,
+                &format!("{}.cross_attention", ours),
+                &format!("{}.encoder_attn", theirs),
+            )?;
+            norm(
+                &mut fixtures,
+                &format!("{}.norm2", ours),
+                &format!("{}.encoder_attn_layer_norm", theirs),
+            )?;
+            feed_forward(&mut fixtures, &ours, &theirs)?;
+            norm(
+                &mut fixtures,
+                &format!("{}.norm3", ours),
+                &format!("{}.final_layer_norm", theirs),
+            )?;
+        }
+        load_parameters(&mut model, "", &fixtures)?;
+        Ok(model)
+    }
+
+    /// Records the transposed weight and the bias of the `nn.Linear` found
+    /// under the first of `linear`.
+    fn insert_linear(
+        &self,
+        fixtures: &mut Fixtures,
+        weight: &str,
+        bias: &str,
+        linear: &[String],
+    ) -> Result<()> {
+        let with = |suffix: &str| -> Vec<String> {
+            linear.iter().map(|n| format!("{}.{}", n, suffix)).collect()
+        };
+        fixtures.insert(weight, self.tensor(&with("weight"))?.transpose());
+        fixtures.insert(bias, self.tensor(&with("bias"))?.clone());
+        Ok(())
+    }
+}
+
+/// Loads a BERT checkpoint directory as an encoder; see
+/// [`HfCheckpoint::bert_encoder`].
+pub fn load_bert(dir: impl AsRef<Path>) -> Result<(TransformerConfig, Encoder)> {
+    HfCheckpoint::load(dir)?.bert_encoder()
+}
+
+/// Loads a BERT checkpoint directory as an encoder-only model with
+/// `[CLS]` pooling; see [`HfCheckpoint::bert_encoder`].
+pub fn load_bert_model(dir: impl AsRef<Path>) -> Result<EncoderOnlyTransformer> {
+    let (config, encoder) = load_bert(dir)?;
+    Ok(EncoderOnlyTransformer::from_encoder(config, encoder))
+}
+
+/// Loads a Marian checkpoint directory; see
+/// [`HfCheckpoint::marian_transformer`].
+pub fn load_marian(dir: impl AsRef<Path>) -> Result<Transformer> {
+    HfCheckpoint::load(dir)?.marian_transformer()
+}
+
+/// Loads the `tokenizer.json` of a checkpoint directory.
+pub fn load_tokenizer(dir: impl AsRef<Path>) -> Result<HfTokenizer> {
+    HfTokenizer::from_file(dir.as_ref().join("tokenizer.json"))
+}
+
+/// The safetensors files of a checkpoint directory: the shards named by
+/// `model.safetensors.index.json`, or `model.safetensors`.
+fn weight_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let index = dir.join("model.safetensors.index.json");
+    if index.exists() {
+        let index = Json::parse(&fs::read_to_string(&index)?)?;
+        let mut files: Vec<PathBuf> = index
+            .get("weight_map")
+            .and_then(Json::as_object)
+            .ok_or("model.safetensors.index.json has no weight_map")?
+            .iter()
+            .filter_map(|(_, file)| file.as_str().map(|f| dir.join(f)))
+            .collect();
+        files.sort();
+        files.dedup();
+        return Ok(files);
+    }
+    let single = dir.join("model.safetensors");
+    if single.exists() {
+        return Ok(vec![single]);
+    }
+    if dir.join("pytorch_model.bin").exists() {
+        return Err(format!(
+            "{} only has pytorch_model.bin; convert it to model.safetensors",
+            dir.display()
+        )
+        .into());
+    }
+    Err(format!("no safetensors weights in {}", dir.display()).into())
+}
+
+/// Marian's sinusoidal table: sines in the first half of the columns,
+/// cosines in the second, rather than interleaved.
+fn marian_positions(max_seq_len: usize, d_model: usize) -> Matrix {
+    let half = d_model.div_ceil(2);
+    Matrix::from_fn(max_seq_len, d_model, |pos, i| {
+        let k = if i < half { i } else { i - half };
+        let angle = pos as f64 / 10000f64.powf((2 * k) as f64 / d_model as f64);
+        if i < half {
+            angle.sin()
+        } else {
+            angle.cos()
+        }
+    })
+}
@@ -0,0 +1,148 @@
+//! Reading and writing `.safetensors` weight files.
+//!
+//! A safetensors file is a little-endian `u64` header length, a JSON header
+//! mapping every tensor name to its `dtype`, `shape` and `data_offsets`
+//! (relative to the end of the header), and the raw tensor bytes.
+//! `F64`, `F32`, `F16` and `BF16` tensors of rank 0 to 2 are read; vectors
+//! become `1 × n` matrices. Written files hold `F64` matrices of rank 2, so
+//! they read back unchanged.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::quantization::f16_to_f32;
+use crate::tensor::Matrix;
+use crate::utils::json::Json;
+use crate::Result;
+
+/// Reads every tensor of the safetensors file at `path`.
+pub fn read_safetensors(path: impl AsRef<Path>) -> Result<BTreeMap<String, Matrix>> {
+    let path = path.as_ref();
+    parse_safetensors(&fs::read(path)?).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Writes `tensors` to a safetensors file at `path`; see
+/// [`to_safetensors`].
+pub fn write_safetensors(path: impl AsRef<Path>, tensors: &BTreeMap<String, Matrix>) -> Result<()> {
+    fs::write(path, to_safetensors(tensors))?;
+    Ok(())
+}
+
+/// The bytes of a safetensors file holding `tensors` as `F64` values of
+/// shape `[rows, cols]`, in name order.
+pub fn to_safetensors(tensors: &BTreeMap<String, Matrix>) -> Vec<u8> {
+    let mut entries = Vec::with_capacity(tensors.len());
+    let mut data = Vec::new();
+    for (name, matrix) in tensors {
+        let start = data.len();
+        for v in matrix.as_slice() {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        entries.push((
+            name.clone(),
+            Json::object([
+                ("dtype", Json::from("F64")),
+                ("shape", Json::from(vec![matrix.rows(), matrix.cols()])),
+                ("data_offsets", Json::from(vec![start, data.len()])),
+            ]),
+        ));
+    }
+    let mut header = Json::Object(entries).to_string().into_bytes();
+    // Pads the header with spaces so the data starts 8-byte aligned.
+    header.resize(header.len().next_multiple_of(8), b' ');
+
+    let mut bytes = Vec::with_capacity(8 + header.len() + data.len());
+    bytes.extend_from_slice(&(header.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&header);
+    bytes.extend_from_slice(&data);
+    bytes
+}
+
+/// Parses the bytes of a safetensors file.
+pub fn parse_safetensors(bytes: &[u8]) -> Result<BTreeMap<String, Matrix>> {
+    let header_len = bytes
+        .get(..8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()))
+        .ok_or("truncated safetensors header")?;
+    let data_start = usize::try_from(header_len)
+        .ok()
+        .and_then(|n| n.checked_add(8))
+        .filter(|&end| end <= bytes.len())
+        .ok_or("safetensors header extends past the end of the file")?;
+    let header = Json::parse(std::str::from_utf8(&bytes[8..data_start])?)?;
+    let data = &bytes[data_start..];
+
+    let mut tensors = BTreeMap::new();
+    for (name, info) in header
+        .as_object()
+        .ok_or("safetensors header is not an object")?
+    {
+        if name == "__metadata__" {
+            continue;
+        }
+        let dtype = info
+            .get("dtype")
+            .and_then(Json::as_str)
+            .ok_or_else(|| format!("tensor '{}' has no dtype", name))?;
+        let shape = info
+            .get("shape")
+            .and_then(Json::as_array)
+            .and_then(|dims| dims.iter().map(Json::as_usize).collect::<Option<Vec<_>>>())
+            .ok_or_else(|| format!("tensor '{}' has no valid shape", name))?;
+        let offsets = info
+            .get("data_offsets")
+            .and_then(Json::as_array)
+            .and_then(|o| o.iter().map(Json::as_usize).collect::<Option<Vec<_>>>())
+            .filter(|o| o.len() == 2 && o[0] <= o[1] && o[1] <= data.len())
+            .ok_or_else(|| format!("tensor '{}' has invalid data offsets", name))?;
+        let raw = &data[offsets[0]..offsets[1]];
+
+        let (rows, cols) = match shape[..] {
+            [] => (1, 1),
+            [n] => (1, n),
+            [rows, cols] => (rows, cols),
+            _ => {
+                return Err(format!(
+                    "tensor '{}' has rank {}, only up to 2 is supported",
+                    name,
+                    shape.len()
+                )
+                .into())
+            }
+        };
+        let values: Vec<f64> = match dtype {
+            "F64" => raw
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap_or_default()))
+                .collect(),
+            "F32" => raw
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap_or_default()) as f64)
+                .collect(),
+            "F16" => raw
+                .chunks_exact(2)
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])) as f64)
+                .collect(),
+            "BF16" => raw
+                .chunks_exact(2)
+                .map(|b| f32::from_bits((u16::from_le_bytes([b[0], b[1]]) as u32) << 16) as f64)
+                .collect(),
+            other => {
+                return Err(format!("tensor '{}' has unsupported dtype {}", name, other).into())
+            }
+        };
+        if values.len() != rows * cols {
+            return Err(format!(
+                "tensor '{}' holds {} values, shape {:?} needs {}",
+                name,
+                values.len(),
+                shape,
+                rows * cols
+            )
+            .into());
+        }
+        tensors.insert(name.clone(), Matrix::from_vec(rows, cols, values)?);
+    }
+    Ok(tensors)
+}
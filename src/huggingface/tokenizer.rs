@@ -0,0 +1,1517 @@
+//! `tokenizer.json` files of the Hugging Face `tokenizers` library.
+//!
+//! [`HfTokenizer`] runs the pipeline such a file describes: added tokens are
+//! cut out of the text first, every other piece is normalized,
+//! pre-tokenized into words and split into subwords by the model, and the
+//! post-processor adds special tokens around the result. Decoding maps ids
+//! back to strings and joins them with the file's decoder. The components
+//! BERT, GPT-2, RoBERTa, Llama and SentencePiece-style Unigram tokenizers
+//! are built from are supported:
+//!
+//! - models: `WordPiece`, `BPE` and `Unigram`, including byte fallback
+//! - normalizers: `BertNormalizer`, `Lowercase`, `StripAccents`, `NFD`,
+//!   `NFC`, `Replace`, `Prepend`, `Strip` and `Sequence`
+//! - pre-tokenizers: `BertPreTokenizer`, `Whitespace`, `WhitespaceSplit`,
+//!   `ByteLevel`, `Metaspace` and `Sequence`
+//! - post-processors: `TemplateProcessing`, `BertProcessing`,
+//!   `RobertaProcessing`, `ByteLevel` and `Sequence`
+//! - decoders: `WordPiece`, `ByteLevel`, `Metaspace`, `BPEDecoder`,
+//!   `Replace`, `ByteFallback`, `Fuse`, `Strip` and `Sequence`
+//!
+//! Files using anything else, such as regex patterns or the `Precompiled`
+//! normalizer of T5, are rejected when loading rather than tokenized differently. Unicode
+//! classes come from the standard library (`char::is_alphabetic` for
+//! `\p{L}`, `char::is_numeric` for `\p{N}`), decomposition (`NFD`, accent
+//! stripping) covers the precomposed Latin letters, and `NFC` assumes its
+//! input is already composed. Within those limits ids match the reference
+//! implementation.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::tokenizer::Tokenizer;
+use crate::utils::json::Json;
+use crate::{Result, TransformerError};
+
+/// A tokenizer loaded from a `tokenizer.json` file.
+#[derive(Debug, Clone)]
+pub struct HfTokenizer {
+    vocab: HashMap<String, usize>,
+    /// Token string of every id, `""` for ids nothing maps to.
+    tokens: Vec<String>,
+    model: Model,
+    normalizers: Vec<Normalizer>,
+    pre_tokenizers: Vec<PreTokenizer>,
+    /// Longest first, so overlapping added tokens match greedily.
+    added: Vec<AddedToken>,
+    special: HashSet<usize>,
+    single: Vec<Piece>,
+    pair: Vec<Piece>,
+    decoders: Vec<Decoder>,
+}
+
+#[derive(Debug, Clone)]
+enum Model {
+    WordPiece {
+        unk_token: String,
+        prefix: String,
+        max_input_chars_per_word: usize,
+    },
+    Bpe {
+        /// Rank of every mergeable pair.
+        merges: HashMap<(String, String), usize>,
+        unk_token: Option<String>,
+        prefix: String,
+        suffix: String,
+        fuse_unk: bool,
+        byte_fallback: bool,
+        ignore_merges: bool,
+    },
+    /// Picks the segmentation of each word with the highest total score.
+    Unigram {
+        /// Log probability of every piece, by id.
+        scores: Vec<f64>,
+        unk_id: Option<usize>,
+        byte_fallback: bool,
+    },
+}
+
+/// How far below the lowest piece score an unknown character scores in
+/// [`Model::Unigram`], as in `tokenizers`.
+const UNIGRAM_UNK_PENALTY: f64 = 10.0;
+
+#[derive(Debug, Clone)]
+enum Normalizer {
+    Bert {
+        clean_text: bool,
+        handle_chinese_chars: bool,
+        strip_accents: bool,
+        lowercase: bool,
+    },
+    Lowercase,
+    StripAccents,
+    Nfd,
+    Replace {
+        pattern: String,
+        content: String,
+    },
+    Prepend(String),
+    Strip {
+        left: bool,
+        right: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum PreTokenizer {
+    Bert,
+    Whitespace,
+    WhitespaceSplit,
+    ByteLevel {
+        add_prefix_space: bool,
+        use_regex: bool,
+    },
+    Metaspace {
+        replacement: char,
+        prepend: Prepend,
+        split: bool,
+    },
+}
+
+/// When Metaspace puts its replacement character in front of the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prepend {
+    Always,
+    First,
+    Never,
+}
+
+#[derive(Debug, Clone)]
+struct AddedToken {
+    id: usize,
+    content: String,
+    single_word: bool,
+    lstrip: bool,
+    rstrip: bool,
+}
+
+/// One element of a post-processing template.
+#[derive(Debug, Clone)]
+enum Piece {
+    Special {
+        ids: Vec<usize>,
+        type_id: usize,
+    },
+    /// The first (`A`) or second (`B`) input sequence.
+    Sequence {
+        second: bool,
+        type_id: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Decoder {
+    WordPiece {
+        prefix: String,
+        cleanup: bool,
+    },
+    ByteLevel,
+    Metaspace {
+        replacement: char,
+        prepend: Prepend,
+    },
+    Bpe {
+        suffix: String,
+    },
+    Replace {
+        pattern: String,
+        content: String,
+    },
+    ByteFallback,
+    Fuse,
+    Strip {
+        content: char,
+        start: usize,
+        stop: usize,
+    },
+}
+
+/// A piece of input text: either ordinary text or an added token.
+enum Segment<'a> {
+    Text(&'a str),
+    Added(usize),
+}
+
+impl HfTokenizer {
+    /// Parses the contents of a `tokenizer.json` file.
+    pub fn from_json(json: &Json) -> Result<Self> {
+        let model_json = json
+            .get("model")
+            .ok_or_else(|| parse_error("tokenizer.json lacks a model"))?;
+        let model = parse_model(model_json)?;
+        let mut vocab = HashMap::new();
+        match model_json.get("vocab") {
+            Some(Json::Object(entries)) => {
+                for (token, id) in entries {
+                    let id = id.as_usize().ok_or_else(|| {
+                        parse_error(format!("vocab entry {:?} has no integer id", token))
+                    })?;
+                    vocab.insert(token.clone(), id);
+                }
+            }
+            // Unigram lists `[piece, score]` pairs; the id is the position.
+            Some(Json::Array(entries)) => {
+                for (id, entry) in entries.iter().enumerate() {
+                    let token = entry
+                        .as_array()
+                        .and_then(|pair| pair.first())
+                        .and_then(Json::as_str)
+                        .ok_or_else(|| parse_error(format!("malformed vocab entry {}", entry)))?;
+                    vocab.entry(token.to_string()).or_insert(id);
+                }
+            }
+            _ => return Err(parse_error("tokenizer model lacks a vocab")),
+        }
+
+        let mut added = Vec::new();
+        let mut special = HashSet::new();
+        for token in json
+            .get("added_tokens")
+            .and_then(Json::as_array)
+            .unwrap_or(&[])
+        {
+            let id = token
+                .get("id")
+                .and_then(Json::as_usize)
+                .ok_or_else(|| parse_error("added token lacks an id"))?;
+            let content = str_field(token, "content")?.to_string();
+            if bool_field(token, "special", false) {
+                special.insert(id);
+            }
+            vocab.entry(content.clone()).or_insert(id);
+            added.push(AddedToken {
+                id,
+                content,
+                single_word: bool_field(token, "single_word", false),
+                lstrip: bool_field(token, "lstrip", false),
+                rstrip: bool_field(token, "rstrip", false),
+            });
+        }
+        added.retain(|token| !token.content.is_empty());
+        added.sort_by_key(|token| std::cmp::Reverse(token.content.len()));
+
+        let size = vocab.values().map(|&id| id + 1).max().unwrap_or(0);
+        let mut tokens = vec![String::new(); size];
+        for (token, &id) in &vocab {
+            tokens[id] = token.clone();
+        }
+
+        let mut normalizers = Vec::new();
+        if let Some(normalizer) = json.get("normalizer").filter(|n| **n != Json::Null) {
+            parse_normalizer(normalizer, &mut normalizers)?;
+        }
+        let mut pre_tokenizers = Vec::new();
+        if let Some(pre) = json.get("pre_tokenizer").filter(|p| **p != Json::Null) {
+            parse_pre_tokenizer(pre, &mut pre_tokenizers)?;
+        }
+        let mut single = vec![Piece::Sequence {
+            second: false,
+            type_id: 0,
+        }];
+        let mut pair = vec![
+            single[0].clone(),
+            Piece::Sequence {
+                second: true,
+                type_id: 1,
+            },
+        ];
+        if let Some(post) = json.get("post_processor").filter(|p| **p != Json::Null) {
+            parse_post_processor(post, &vocab, &mut single, &mut pair)?;
+        }
+        let mut decoders = Vec::new();
+        if let Some(decoder) = json.get("decoder").filter(|d| **d != Json::Null) {
+            parse_decoder(decoder, &mut decoders)?;
+        }
+
+        Ok(Self {
+            vocab,
+            tokens,
+            model,
+            normalizers,
+            pre_tokenizers,
+            added,
+            special,
+            single,
+            pair,
+            decoders,
+        })
+    }
+
+    /// Reads a `tokenizer.json` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = Json::parse(&fs::read_to_string(path)?)
+            .map_err(|e| parse_error(format!("{}: {}", path.display(), e)))?;
+        Self::from_json(&json)
+    }
+
+    /// The string of a single token, or `None` if nothing maps to `id`.
+    pub fn token(&self, id: usize) -> Option<&str> {
+        self.tokens
+            .get(id)
+            .map(String::as_str)
+            .filter(|t| !t.is_empty())
+    }
+
+    pub fn token_id(&self, token: &str) -> Option<usize> {
+        self.vocab.get(token).copied()
+    }
+
+    /// Whether `id` is a special token: skipped by
+    /// [`decode`](Tokenizer::decode).
+    pub fn is_special(&self, id: usize) -> bool {
+        self.special.contains(&id)
+    }
+
+    /// Tokenizes `text` without the special tokens the post-processor adds.
+    pub fn encode_without_special_tokens(&self, text: &str) -> Result<Vec<usize>> {
+        let mut ids = Vec::new();
+        for (i, segment) in self.split_added(text).iter().enumerate() {
+            match segment {
+                Segment::Added(id) => ids.push(*id),
+                Segment::Text(text) => {
+                    let normalized = self.normalize(text);
+                    for word in self.pre_tokenize(&normalized, i == 0) {
+                        self.model_encode(&word, &mut ids)?;
+                    }
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Tokenizes a sentence pair as the model expects it, for example
+    /// `[CLS] first [SEP] second [SEP]` for BERT, returning the ids and the
+    /// token type of each.
+    pub fn encode_pair(&self, first: &str, second: &str) -> Result<(Vec<usize>, Vec<usize>)> {
+        let first = self.encode_without_special_tokens(first)?;
+        let second = self.encode_without_special_tokens(second)?;
+        Ok(apply_template(&self.pair, &first, &second))
+    }
+
+    /// Cuts the added tokens out of `text`.
+    fn split_added<'a>(&self, text: &'a str) -> Vec<Segment<'a>> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut pos = 0;
+        while pos < text.len() {
+            let found = self
+                .added
+                .iter()
+                .find(|t| text[pos..].starts_with(&t.content) && self.is_word(text, pos, t));
+            let Some(token) = found else {
+                pos += text[pos..].chars().next().map_or(1, char::len_utf8);
+                continue;
+            };
+            let mut before = &text[start..pos];
+            if token.lstrip {
+                before = before.trim_end();
+            }
+            if !before.is_empty() {
+                segments.push(Segment::Text(before));
+            }
+            segments.push(Segment::Added(token.id));
+            pos += token.content.len();
+            if token.rstrip {
+                pos = text.len() - text[pos..].trim_start().len();
+            }
+            start = pos;
+        }
+        if start < text.len() {
+            segments.push(Segment::Text(&text[start..]));
+        }
+        segments
+    }
+
+    /// Whether a `single_word` token at `pos` is not part of a longer word.
+    fn is_word(&self, text: &str, pos: usize, token: &AddedToken) -> bool {
+        if !token.single_word {
+            return true;
+        }
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        let before = text[..pos].chars().next_back();
+        let after = text[pos + token.content.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    }
+
+    fn normalize(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for normalizer in &self.normalizers {
+            text = normalizer.apply(&text);
+        }
+        text
+    }
+
+    /// Splits normalized text into the words the model sees. `first` is
+    /// whether the text starts the input.
+    fn pre_tokenize(&self, text: &str, first: bool) -> Vec<String> {
+        let mut words = vec![text.to_string()];
+        for pre in &self.pre_tokenizers {
+            words = words
+                .iter()
+                .flat_map(|word| pre.split(word, first))
+                .collect();
+        }
+        words.retain(|w| !w.is_empty());
+        words
+    }
+
+    fn model_encode(&self, word: &str, ids: &mut Vec<usize>) -> Result<()> {
+        match &self.model {
+            Model::WordPiece {
+                unk_token,
+                prefix,
+                max_input_chars_per_word,
+            } => {
+                let unk = || {
+                    self.token_id(unk_token).ok_or_else(|| {
+                        parse_error(format!("unk token {:?} is not in the vocab", unk_token))
+                    })
+                };
+                if word.chars().count() > *max_input_chars_per_word {
+                    ids.push(unk()?);
+                    return Ok(());
+                }
+                let mut pieces = Vec::new();
+                let mut start = 0;
+                while start < word.len() {
+                    let found = word[start..]
+                        .char_indices()
+                        .map(|(i, c)| start + i + c.len_utf8())
+                        .rev()
+                        .find_map(|end| {
+                            let piece = &word[start..end];
+                            let id = if start > 0 {
+                                self.token_id(&format!("{}{}", prefix, piece))
+                            } else {
+                                self.token_id(piece)
+                            };
+                            id.map(|id| (id, end))
+                        });
+                    match found {
+                        Some((id, end)) => {
+                            pieces.push(id);
+                            start = end;
+                        }
+                        None => {
+                            ids.push(unk()?);
+                            return Ok(());
+                        }
+                    }
+                }
+                ids.extend(pieces);
+            }
+            Model::Bpe {
+                merges,
+                unk_token,
+                prefix,
+                suffix,
+                fuse_unk,
+                byte_fallback,
+                ignore_merges,
+            } => {
+                if *ignore_merges {
+                    if let Some(id) = self.token_id(word) {
+                        ids.push(id);
+                        return Ok(());
+                    }
+                }
+                let count = word.chars().count();
+                let mut parts: Vec<String> = word
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let mut part = if i > 0 { prefix.clone() } else { String::new() };
+                        part.push(c);
+                        if i + 1 == count {
+                            part.push_str(suffix);
+                        }
+                        part
+                    })
+                    .collect();
+                while let Some((rank, _)) = (0..parts.len().saturating_sub(1))
+                    .filter_map(|i| {
+                        merges
+                            .get(&(parts[i].clone(), parts[i + 1].clone()))
+                            .map(|&rank| (rank, i))
+                    })
+                    .min()
+                {
+                    let mut merged = Vec::with_capacity(parts.len());
+                    let mut i = 0;
+                    while i < parts.len() {
+                        if i + 1 < parts.len()
+                            && merges.get(&(parts[i].clone(), parts[i + 1].clone())) == Some(&rank)
+                        {
+                            let right = parts[i + 1]
+                                .strip_prefix(prefix.as_str())
+                                .unwrap_or(&parts[i + 1]);
+                            merged.push(format!("{}{}", parts[i], right));
+                            i += 2;
+                        } else {
+                            merged.push(parts[i].clone());
+                            i += 1;
+                        }
+                    }
+                    parts = merged;
+                }
+                let mut last_unk = false;
+                for part in &parts {
+                    if let Some(id) = self.token_id(part) {
+                        ids.push(id);
+                        last_unk = false;
+                        continue;
+                    }
+                    if *byte_fallback {
+                        let bytes: Option<Vec<usize>> = part
+                            .bytes()
+                            .map(|b| self.token_id(&format!("<0x{:02X}>", b)))
+                            .collect();
+                        if let Some(bytes) = bytes {
+                            ids.extend(bytes);
+                            last_unk = false;
+                            continue;
+                        }
+                    }
+                    // Without an unk token, uncovered characters are dropped.
+                    let Some(unk_token) = unk_token else {
+                        continue;
+                    };
+                    let unk = self.token_id(unk_token).ok_or_else(|| {
+                        parse_error(format!("unk token {:?} is not in the vocab", unk_token))
+                    })?;
+                    if !(*fuse_unk && last_unk) {
+                        ids.push(unk);
+                    }
+                    last_unk = true;
+                }
+            }
+            Model::Unigram {
+                scores,
+                unk_id,
+                byte_fallback,
+            } => {
+                for piece in unigram_segments(word, scores, |piece| self.token_id(piece)) {
+                    if let Some(id) = piece.id {
+                        ids.push(id);
+                        continue;
+                    }
+                    let text = &word[piece.start..piece.end];
+                    if *byte_fallback {
+                        let bytes: Option<Vec<usize>> = text
+                            .bytes()
+                            .map(|b| self.token_id(&format!("<0x{:02X}>", b)))
+                            .collect();
+                        if let Some(bytes) = bytes {
+                            ids.extend(bytes);
+                            continue;
+                        }
+                    }
+                    ids.push(unk_id.ok_or_else(|| {
+                        parse_error(format!("{:?} is unknown and the model has no unk_id", text))
+                    })?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Tokenizer for HfTokenizer {
+    fn vocab_size(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Tokenizes `text` with the special tokens of the post-processor, as
+    /// `tokenizers` does by default.
+    fn encode(&self, text: &str) -> Result<Vec<usize>> {
+        let ids = self.encode_without_special_tokens(text)?;
+        Ok(apply_template(&self.single, &ids, &[]).0)
+    }
+
+    fn decode(&self, ids: &[usize]) -> Result<String> {
+        let mut tokens = Vec::with_capacity(ids.len());
+        for &id in ids {
+            if self.special.contains(&id) {
+                continue;
+            }
+            let token = self.token(id).ok_or(TransformerError::VocabOverflow {
+                token: id,
+                vocab_size: self.tokens.len(),
+            })?;
+            tokens.push(token.to_string());
+        }
+        if self.decoders.is_empty() {
+            return Ok(tokens.join(" "));
+        }
+        for decoder in &self.decoders {
+            tokens = decoder.apply(tokens);
+        }
+        Ok(tokens.concat())
+    }
+}
+
+/// A span of a word in a Unigram segmentation; `id` is `None` for a run of
+/// characters no piece covers.
+struct UnigramPiece {
+    start: usize,
+    end: usize,
+    id: Option<usize>,
+}
+
+/// The segmentation of `word` into pieces with the highest total score
+/// (Viterbi). A character no piece starts with becomes an unknown piece
+/// scoring [`UNIGRAM_UNK_PENALTY`] below the lowest score, and adjacent
+/// unknown pieces are fused into one.
+fn unigram_segments(
+    word: &str,
+    scores: &[f64],
+    token_id: impl Fn(&str) -> Option<usize>,
+) -> Vec<UnigramPiece> {
+    let unk_score = scores.iter().copied().fold(f64::INFINITY, f64::min) - UNIGRAM_UNK_PENALTY;
+    let bounds: Vec<usize> = word
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain([word.len()])
+        .collect();
+    // best[e]: score of the best segmentation of the first e characters,
+    // with the index its last piece starts at and the piece's id.
+    let mut best: Vec<Option<(f64, usize, Option<usize>)>> = vec![None; bounds.len()];
+    best[0] = Some((0.0, 0, None));
+    for start in 0..bounds.len() - 1 {
+        let Some((base, _, _)) = best[start] else {
+            continue;
+        };
+        let mut offer = |end: usize, score: f64, id: Option<usize>| {
+            if best[end].is_none_or(|(current, _, _)| base + score > current) {
+                best[end] = Some((base + score, start, id));
+            }
+        };
+        let mut covered = false;
+        for end in start + 1..bounds.len() {
+            let piece = &word[bounds[start]..bounds[end]];
+            if let Some(id) = token_id(piece).filter(|&id| id < scores.len()) {
+                covered |= end == start + 1;
+                offer(end, scores[id], Some(id));
+            }
+        }
+        if !covered {
+            offer(start + 1, unk_score, None);
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut end = bounds.len() - 1;
+    while end > 0 {
+        let (_, start, id) = best[end].expect("every prefix has a segmentation");
+        match pieces.last_mut() {
+            Some(UnigramPiece {
+                start: next_start,
+                id: None,
+                ..
+            }) if id.is_none() => *next_start = bounds[start],
+            _ => pieces.push(UnigramPiece {
+                start: bounds[start],
+                end: bounds[end],
+                id,
+            }),
+        }
+        end = start;
+    }
+    pieces.reverse();
+    pieces
+}
+
+/// Fills `template` with `first` and `second`, returning ids and token
+/// types.
+fn apply_template(
+    template: &[Piece],
+    first: &[usize],
+    second: &[usize],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut ids = Vec::new();
+    let mut type_ids = Vec::new();
+    for piece in template {
+        let (part, type_id) = match piece {
+            Piece::Special { ids, type_id } => (ids.as_slice(), *type_id),
+            Piece::Sequence {
+                second: false,
+                type_id,
+            } => (first, *type_id),
+            Piece::Sequence {
+                second: true,
+                type_id,
+            } => (second, *type_id),
+        };
+        ids.extend_from_slice(part);
+        type_ids.extend(std::iter::repeat_n(type_id, part.len()));
+    }
+    (ids, type_ids)
+}
+
+impl Normalizer {
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Normalizer::Bert {
+                clean_text,
+                handle_chinese_chars,
+                strip_accents,
+                lowercase,
+            } => {
+                let mut out = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if *clean_text {
+                        let is_space = matches!(c, '\t' | '\n' | '\r') || c.is_whitespace();
+                        if c == '\0'
+                            || c == '\u{fffd}'
+                            || (c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+                        {
+                            continue;
+                        }
+                        if is_space {
+                            out.push(' ');
+                            continue;
+                        }
+                    }
+                    if *handle_chinese_chars && is_chinese_char(c) {
+                        out.push(' ');
+                        out.push(c);
+                        out.push(' ');
+                    } else {
+                        out.push(c);
+                    }
+                }
+                if *strip_accents {
+                    out = strip_marks(&decompose(&out));
+                }
+                if *lowercase {
+                    out = out.to_lowercase();
+                }
+                out
+            }
+            Normalizer::Lowercase => text.to_lowercase(),
+            Normalizer::StripAccents => strip_marks(text),
+            Normalizer::Nfd => decompose(text),
+            Normalizer::Replace { pattern, content } => text.replace(pattern.as_str(), content),
+            Normalizer::Prepend(prefix) if !text.is_empty() => format!("{}{}", prefix, text),
+            Normalizer::Prepend(_) => String::new(),
+            Normalizer::Strip { left, right } => {
+                let text = if *left { text.trim_start() } else { text };
+                let text = if *right { text.trim_end() } else { text };
+                text.to_string()
+            }
+        }
+    }
+}
+
+impl PreTokenizer {
+    fn split(&self, text: &str, first: bool) -> Vec<String> {
+        match self {
+            PreTokenizer::Bert => {
+                let mut words = Vec::new();
+                let mut word = String::new();
+                for c in text.chars() {
+                    if c.is_whitespace() || is_punctuation(c) {
+                        if !word.is_empty() {
+                            words.push(std::mem::take(&mut word));
+                        }
+                        if !c.is_whitespace() {
+                            words.push(c.to_string());
+                        }
+                    } else {
+                        word.push(c);
+                    }
+                }
+                words.push(word);
+                words
+            }
+            PreTokenizer::Whitespace => {
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                let mut words: Vec<String> = Vec::new();
+                let mut last: Option<bool> = None;
+                for c in text.chars() {
+                    if c.is_whitespace() {
+                        last = None;
+                        continue;
+                    }
+                    let class = is_word(c);
+                    match words.last_mut() {
+                        Some(word) if last == Some(class) => word.push(c),
+                        _ => words.push(c.to_string()),
+                    }
+                    last = Some(class);
+                }
+                words
+            }
+            PreTokenizer::WhitespaceSplit => text.split_whitespace().map(str::to_string).collect(),
+            PreTokenizer::ByteLevel {
+                add_prefix_space,
+                use_regex,
+            } => {
+                let mut text = text.to_string();
+                if *add_prefix_space && !text.starts_with(' ') {
+                    text.insert(0, ' ');
+                }
+                let words = if *use_regex {
+                    gpt2_split(&text)
+                } else {
+                    vec![text.as_str()]
+                };
+                let map = byte_to_char();
+                words
+                    .into_iter()
+                    .map(|w| w.bytes().map(|b| map[b as usize]).collect())
+                    .collect()
+            }
+            PreTokenizer::Metaspace {
+                replacement,
+                prepend,
+                split,
+            } => {
+                let mut text: String = text
+                    .chars()
+                    .map(|c| if c == ' ' { *replacement } else { c })
+                    .collect();
+                let prepend = match prepend {
+                    Prepend::Always => true,
+                    Prepend::First => first,
+                    Prepend::Never => false,
+                };
+                if prepend && !text.starts_with(*replacement) {
+                    text.insert(0, *replacement);
+                }
+                if !split {
+                    return vec![text];
+                }
+                let mut words: Vec<String> = Vec::new();
+                for c in text.chars() {
+                    match words.last_mut() {
+                        Some(word) if c != *replacement => word.push(c),
+                        _ => words.push(c.to_string()),
+                    }
+                }
+                words
+            }
+        }
+    }
+}
+
+impl Decoder {
+    fn apply(&self, tokens: Vec<String>) -> Vec<String> {
+        match self {
+            Decoder::WordPiece { prefix, cleanup } => tokens
+                .into_iter()
+                .enumerate()
+                .map(|(i, token)| {
+                    let token = if i == 0 {
+                        token
+                    } else if let Some(rest) = token.strip_prefix(prefix.as_str()) {
+                        rest.to_string()
+                    } else {
+                        format!(" {}", token)
+                    };
+                    if *cleanup {
+                        clean_up_tokenization(&token)
+                    } else {
+                        token
+                    }
+                })
+                .collect(),
+            Decoder::ByteLevel => {
+                let map = char_to_byte();
+                let mut bytes = Vec::new();
+                for token in &tokens {
+                    match token
+                        .chars()
+                        .map(|c| map.get(&c).copied())
+                        .collect::<Option<Vec<u8>>>()
+                    {
+                        Some(decoded) => bytes.extend(decoded),
+                        None => bytes.extend_from_slice(token.as_bytes()),
+                    }
+                }
+                vec![String::from_utf8_lossy(&bytes).into_owned()]
+            }
+            Decoder::Metaspace {
+                replacement,
+                prepend,
+            } => tokens
+                .into_iter()
+                .enumerate()
+                .map(|(i, token)| {
+                    let token: String = token
+                        .chars()
+                        .map(|c| if c == *replacement { ' ' } else { c })
+                        .collect();
+                    match token.strip_prefix(' ') {
+                        Some(rest) if i == 0 && *prepend != Prepend::Never => rest.to_string(),
+                        _ => token,
+                    }
+                })
+                .collect(),
+            Decoder::Bpe { suffix } => {
+                let last = tokens.len().saturating_sub(1);
+                tokens
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, token)| {
+                        token.replace(suffix.as_str(), if i == last { "" } else { " " })
+                    })
+                    .collect()
+            }
+            Decoder::Replace { pattern, content } => tokens
+                .into_iter()
+                .map(|token| token.replace(pattern.as_str(), content))
+                .collect(),
+            Decoder::ByteFallback => {
+                let mut out = Vec::with_capacity(tokens.len());
+                let mut bytes = Vec::new();
+                let flush = |bytes: &mut Vec<u8>, out: &mut Vec<String>| {
+                    if bytes.is_empty() {
+                        return;
+                    }
+                    match String::from_utf8(std::mem::take(bytes)) {
+                        Ok(text) => out.push(text),
+                        Err(e) => out.extend(std::iter::repeat_n(
+                            "\u{fffd}".to_string(),
+                            e.as_bytes().len(),
+                        )),
+                    }
+                };
+                for token in tokens {
+                    match parse_byte_token(&token) {
+                        Some(byte) => bytes.push(byte),
+                        None => {
+                            flush(&mut bytes, &mut out);
+                            out.push(token);
+                        }
+                    }
+                }
+                flush(&mut bytes, &mut out);
+                out
+            }
+            Decoder::Fuse => vec![tokens.concat()],
+            Decoder::Strip {
+                content,
+                start,
+                stop,
+            } => tokens
+                .into_iter()
+                .map(|token| {
+                    let chars: Vec<char> = token.chars().collect();
+                    let lead = chars
+                        .iter()
+                        .take(*start)
+                        .take_while(|&&c| c == *content)
+                        .count();
+                    let trail = chars[lead..]
+                        .iter()
+                        .rev()
+                        .take(*stop)
+                        .take_while(|&&c| c == *content)
+                        .count();
+                    chars[lead..chars.len() - trail].iter().collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+fn parse_model(json: &Json) -> Result<Model> {
+    match str_field(json, "type")? {
+        "WordPiece" => Ok(Model::WordPiece {
+            unk_token: str_field_or(json, "unk_token", "[UNK]").to_string(),
+            prefix: str_field_or(json, "continuing_subword_prefix", "##").to_string(),
+            max_input_chars_per_word: json
+                .get("max_input_chars_per_word")
+                .and_then(Json::as_usize)
+                .unwrap_or(100),
+        }),
+        "BPE" => {
+            if json.get("dropout").is_some_and(|d| *d != Json::Null) {
+                return Err(parse_error("BPE dropout is not supported"));
+            }
+            let mut merges = HashMap::new();
+            for (rank, merge) in json
+                .get("merges")
+                .and_then(Json::as_array)
+                .unwrap_or(&[])
+                .iter()
+                .enumerate()
+            {
+                let pair = match merge {
+                    Json::String(merge) => merge
+                        .split_once(' ')
+                        .map(|(a, b)| (a.to_string(), b.to_string())),
+                    Json::Array(parts) => match parts.as_slice() {
+                        [Json::String(a), Json::String(b)] => Some((a.clone(), b.clone())),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let pair = pair.ok_or_else(|| parse_error(format!("malformed merge {}", merge)))?;
+                merges.entry(pair).or_insert(rank);
+            }
+            Ok(Model::Bpe {
+                merges,
+                unk_token: json
+                    .get("unk_token")
+                    .and_then(Json::as_str)
+                    .map(str::to_string),
+                prefix: str_field_or(json, "continuing_subword_prefix", "").to_string(),
+                suffix: str_field_or(json, "end_of_word_suffix", "").to_string(),
+                fuse_unk: bool_field(json, "fuse_unk", false),
+                byte_fallback: bool_field(json, "byte_fallback", false),
+                ignore_merges: bool_field(json, "ignore_merges", false),
+            })
+        }
+        "Unigram" => {
+            let scores = array_field(json, "vocab")?
+                .iter()
+                .map(|entry| {
+                    entry
+                        .as_array()
+                        .and_then(|pair| pair.get(1))
+                        .and_then(Json::as_f64)
+                        .ok_or_else(|| parse_error(format!("malformed vocab entry {}", entry)))
+                })
+                .collect::<Result<_>>()?;
+            Ok(Model::Unigram {
+                scores,
+                unk_id: json.get("unk_id").and_then(Json::as_usize),
+                byte_fallback: bool_field(json, "byte_fallback", false),
+            })
+        }
+        other => Err(parse_error(format!(
+            "unsupported tokenizer model '{}'",
+            other
+        ))),
+    }
+}
+
+fn parse_normalizer(json: &Json, out: &mut Vec<Normalizer>) -> Result<()> {
+    let normalizer = match str_field(json, "type")? {
+        "Sequence" => {
+            for part in array_field(json, "normalizers")? {
+                parse_normalizer(part, out)?;
+            }
+            return Ok(());
+        }
+        "BertNormalizer" => {
+            let lowercase = bool_field(json, "lowercase", true);
+            Normalizer::Bert {
+                clean_text: bool_field(json, "clean_text", true),
+                handle_chinese_chars: bool_field(json, "handle_chinese_chars", true),
+                strip_accents: json
+                    .get("strip_accents")
+                    .and_then(Json::as_bool)
+                    .unwrap_or(lowercase),
+                lowercase,
+            }
+        }
+        "Lowercase" => Normalizer::Lowercase,
+        "StripAccents" => Normalizer::StripAccents,
+        "NFD" => Normalizer::Nfd,
+        "NFC" => return Ok(()),
+        "Replace" => Normalizer::Replace {
+            pattern: string_pattern(json)?,
+            content: str_field(json, "content")?.to_string(),
+        },
+        "Prepend" => Normalizer::Prepend(str_field(json, "prepend")?.to_string()),
+        "Strip" => Normalizer::Strip {
+            left: bool_field(json, "strip_left", true),
+            right: bool_field(json, "strip_right", true),
+        },
+        other => return Err(parse_error(format!("unsupported normalizer '{}'", other))),
+    };
+    out.push(normalizer);
+    Ok(())
+}
+
+fn parse_pre_tokenizer(json: &Json, out: &mut Vec<PreTokenizer>) -> Result<()> {
+    let pre = match str_field(json, "type")? {
+        "Sequence" => {
+            for part in array_field(json, "pretokenizers")? {
+                parse_pre_tokenizer(part, out)?;
+            }
+            return Ok(());
+        }
+        "BertPreTokenizer" => PreTokenizer::Bert,
+        "Whitespace" => PreTokenizer::Whitespace,
+        "WhitespaceSplit" => PreTokenizer::WhitespaceSplit,
+        "ByteLevel" => PreTokenizer::ByteLevel {
+            add_prefix_space: bool_field(json, "add_prefix_space", true),
+            use_regex: bool_field(json, "use_regex", true),
+        },
+        "Metaspace" => PreTokenizer::Metaspace {
+            replacement: replacement_char(json)?,
+            prepend: prepend_scheme(json)?,
+            split: bool_field(json, "split", true),
+        },
+        other => {
+            return Err(parse_error(format!(
+                "unsupported pre-tokenizer '{}'",
+                other
+            )))
+        }
+    };
+    out.push(pre);
+    Ok(())
+}
+
+fn parse_post_processor(
+    json: &Json,
+    vocab: &HashMap<String, usize>,
+    single: &mut Vec<Piece>,
+    pair: &mut Vec<Piece>,
+) -> Result<()> {
+    let special = |name: &str, value: Option<&Json>| -> Result<Vec<usize>> {
+        let id = value
+            .and_then(Json::as_array)
+            .and_then(|entry| entry.get(1))
+            .and_then(Json::as_usize)
+            .or_else(|| vocab.get(name).copied())
+            .ok_or_else(|| parse_error(format!("post-processor lacks the {} token", name)))?;
+        Ok(vec![id])
+    };
+    let a = |type_id| Piece::Sequence {
+        second: false,
+        type_id,
+    };
+    let b = |type_id| Piece::Sequence {
+        second: true,
+        type_id,
+    };
+    match str_field(json, "type")? {
+        "Sequence" => {
+            for part in array_field(json, "processors")? {
+                parse_post_processor(part, vocab, single, pair)?;
+            }
+        }
+        "ByteLevel" => {}
+        kind @ ("BertProcessing" | "RobertaProcessing") => {
+            let cls = special("cls", json.get("cls"))?;
+            let sep = special("sep", json.get("sep"))?;
+            let special = |ids: &Vec<usize>, type_id| Piece::Special {
+                ids: ids.clone(),
+                type_id,
+            };
+            *single = vec![special(&cls, 0), a(0), special(&sep, 0)];
+            *pair = if kind == "BertProcessing" {
+                vec![
+                    special(&cls, 0),
+                    a(0),
+                    special(&sep, 0),
+                    b(1),
+                    special(&sep, 1),
+                ]
+            } else {
+                vec![
+                    special(&cls, 0),
+                    a(0),
+                    special(&sep, 0),
+                    special(&sep, 0),
+                    b(0),
+                    special(&sep, 0),
+                ]
+            };
+        }
+        "TemplateProcessing" => {
+            let specials = json.get("special_tokens");
+            let template = |key: &str| -> Result<Vec<Piece>> {
+                let mut pieces = Vec::new();
+                for item in array_field(json, key)? {
+                    if let Some(token) = item.get("SpecialToken") {
+                        let name = str_field(token, "id")?;
+                        let ids = match specials.and_then(|s| s.get(name)) {
+                            Some(entry) => array_field(entry, "ids")?
+                                .iter()
+                                .map(|id| {
+                                    id.as_usize().ok_or_else(|| {
+                                        parse_error("special token id must be an integer")
+                                    })
+                                })
+                                .collect::<Result<Vec<_>>>()?,
+                            None => special(name, None)?,
+                        };
+                        pieces.push(Piece::Special {
+                            ids,
+                            type_id: type_id(token),
+                        });
+                    } else if let Some(sequence) = item.get("Sequence") {
+                        let second = str_field(sequence, "id")? == "B";
+                        pieces.push(Piece::Sequence {
+                            second,
+                            type_id: type_id(sequence),
+                        });
+                    } else {
+                        return Err(parse_error(format!("malformed template item {}", item)));
+                    }
+                }
+                Ok(pieces)
+            };
+            *single = template("single")?;
+            *pair = template("pair")?;
+        }
+        other => {
+            return Err(parse_error(format!(
+                "unsupported post-processor '{}'",
+                other
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn parse_decoder(json: &Json, out: &mut Vec<Decoder>) -> Result<()> {
+    let decoder = match str_field(json, "type")? {
+        "Sequence" => {
+            for part in array_field(json, "decoders")? {
+                parse_decoder(part, out)?;
+            }
+            return Ok(());
+        }
+        "WordPiece" => Decoder::WordPiece {
+            prefix: str_field_or(json, "prefix", "##").to_string(),
+            cleanup: bool_field(json, "cleanup", true),
+        },
+        "ByteLevel" => Decoder::ByteLevel,
+        "Metaspace" => Decoder::Metaspace {
+            replacement: replacement_char(json)?,
+            prepend: prepend_scheme(json)?,
+        },
+        "BPEDecoder" => Decoder::Bpe {
+            suffix: str_field_or(json, "suffix", "</w>").to_string(),
+        },
+        "Replace" => Decoder::Replace {
+            pattern: string_pattern(json)?,
+            content: str_field(json, "content")?.to_string(),
+        },
+        "ByteFallback" => Decoder::ByteFallback,
+        "Fuse" => Decoder::Fuse,
+        "Strip" => Decoder::Strip {
+            content: str_field(json, "content")?
+                .chars()
+                .next()
+                .ok_or_else(|| parse_error("Strip decoder has empty content"))?,
+            start: json.get("start").and_then(Json::as_usize).unwrap_or(0),
+            stop: json.get("stop").and_then(Json::as_usize).unwrap_or(0),
+        },
+        other => return Err(parse_error(format!("unsupported decoder '{}'", other))),
+    };
+    out.push(decoder);
+    Ok(())
+}
+
+fn parse_error(message: impl Into<String>) -> TransformerError {
+    TransformerError::parse(message)
+}
+
+fn str_field<'a>(json: &'a Json, key: &str) -> Result<&'a str> {
+    json.get(key)
+        .and_then(Json::as_str)
+        .ok_or_else(|| parse_error(format!("tokenizer component lacks string '{}'", key)))
+}
+
+fn str_field_or<'a>(json: &'a Json, key: &str, default: &'a str) -> &'a str {
+    json.get(key).and_then(Json::as_str).unwrap_or(default)
+}
+
+fn bool_field(json: &Json, key: &str, default: bool) -> bool {
+    json.get(key).and_then(Json::as_bool).unwrap_or(default)
+}
+
+fn array_field<'a>(json: &'a Json, key: &str) -> Result<&'a [Json]> {
+    json.get(key)
+        .and_then(Json::as_array)
+        .ok_or_else(|| parse_error(format!("tokenizer component lacks array '{}'", key)))
+}
+
+fn type_id(json: &Json) -> usize {
+    json.get("type_id").and_then(Json::as_usize).unwrap_or(0)
+}
+
+/// The literal of a `{"String": ...}` pattern; regex patterns are rejected.
+fn string_pattern(json: &Json) -> Result<String> {
+    json.get("pattern")
+        .and_then(|p| p.get("String"))
+        .and_then(Json::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| parse_error("only string patterns are supported"))
+}
+
+fn replacement_char(json: &Json) -> Result<char> {
+    str_field_or(json, "replacement", "\u{2581}")
+        .chars()
+        .next()
+        .ok_or_else(|| parse_error("Metaspace replacement is empty"))
+}
+
+/// `prepend_scheme`, or the older `add_prefix_space` flag.
+fn prepend_scheme(json: &Json) -> Result<Prepend> {
+    match json.get("prepend_scheme").and_then(Json::as_str) {
+        Some("always") => Ok(Prepend::Always),
+        Some("first") => Ok(Prepend::First),
+        Some("never") => Ok(Prepend::Never),
+        Some(other) => Err(parse_error(format!("unknown prepend_scheme '{}'", other))),
+        None if bool_field(json, "add_prefix_space", true) => Ok(Prepend::Always),
+        None => Ok(Prepend::Never),
+    }
+}
+
+/// Splits like GPT-2's pattern
+/// `'s|'t|'re|'ve|'m|'ll|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(?!\S)|\s+`.
+fn gpt2_split(text: &str) -> Vec<&str> {
+    #[derive(PartialEq)]
+    enum Class {
+        Letter,
+        Number,
+        Other,
+        Space,
+    }
+    let class = |c: char| {
+        if c.is_whitespace() {
+            Class::Space
+        } else if c.is_alphabetic() {
+            Class::Letter
+        } else if c.is_numeric() {
+            Class::Number
+        } else {
+            Class::Other
+        }
+    };
+    let mut words = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let contraction = ["'s", "'t", "'re", "'ve", "'m", "'ll", "'d"]
+            .iter()
+            .find(|c| rest.starts_with(*c));
+        let len = if let Some(c) = contraction {
+            c.len()
+        } else {
+            let mut chars = rest.chars();
+            let first = chars.next().expect("rest is non-empty");
+            // An optional leading space joins a following non-space run.
+            let (start, run_class) = match chars.next() {
+                Some(next) if first == ' ' && class(next) != Class::Space => (1, class(next)),
+                _ => (0, class(first)),
+            };
+            if run_class == Class::Space {
+                let run: Vec<(usize, char)> = rest
+                    .char_indices()
+                    .take_while(|&(_, c)| c.is_whitespace())
+                    .collect();
+                let end = run.last().map_or(0, |&(i, c)| i + c.len_utf8());
+                if end == rest.len() || run.len() == 1 {
+                    end
+                } else {
+                    // Leave the last space to prefix the next word.
+                    run[run.len() - 1].0
+                }
+            } else {
+                rest[start..]
+                    .char_indices()
+                    .find(|&(_, c)| class(c) != run_class)
+                    .map_or(rest.len(), |(i, _)| start + i)
+            }
+        };
+        words.push(&text[pos..pos + len]);
+        pos += len;
+    }
+    words
+}
+
+/// GPT-2's reversible mapping of bytes to printable characters.
+fn byte_to_char() -> &'static [char; 256] {
+    static MAP: OnceLock<[char; 256]> = OnceLock::new();
+    MAP.get_or_init(|| {
+        let mut map = ['\0'; 256];
+        let mut next = 256;
+        for b in 0..256u32 {
+            let printable =
+                (33..=126).contains(&b) || (161..=172).contains(&b) || (174..=255).contains(&b);
+            map[b as usize] = if printable {
+                char::from_u32(b).expect("latin-1 is valid")
+            } else {
+                next += 1;
+                char::from_u32(next - 1).expect("below the surrogates")
+            };
+        }
+        map
+    })
+}
+
+fn char_to_byte() -> &'static HashMap<char, u8> {
+    static MAP: OnceLock<HashMap<char, u8>> = OnceLock::new();
+    MAP.get_or_init(|| {
+        byte_to_char()
+            .iter()
+            .enumerate()
+            .map(|(b, &c)| (c, b as u8))
+            .collect()
+    })
+}
+
+/// The byte of a `<0xAB>` fallback token.
+fn parse_byte_token(token: &str) -> Option<u8> {
+    let hex = token.strip_prefix("<0x")?.strip_suffix('>')?;
+    if hex.len() != 2 {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Precomposed Latin letters by combining mark, as base/composed pairs.
+const DECOMPOSITIONS: &[(char, &str)] = &[
+    ('\u{300}', "AÀEÈIÌOÒUÙaàeèiìoòuù"),
+    (
+        '\u{301}',
+        "AÁEÉIÍOÓUÚYÝaáeéiíoóuúyýCĆcćLĹlĺNŃnńRŔrŕSŚsśZŹzź",
+    ),
+    (
+        '\u{302}',
+        "AÂEÊIÎOÔUÛaâeêiîoôuûCĈcĉGĜgĝHĤhĥJĴjĵSŜsŝWŴwŵYŶyŷ",
+    ),
+    ('\u{303}', "AÃNÑOÕaãnñoõIĨiĩUŨuũ"),
+    ('\u{304}', "AĀaāEĒeēIĪiīOŌoōUŪuū"),
+    ('\u{306}', "AĂaăEĔeĕGĞgğIĬiĭOŎoŏUŬuŭ"),
+    ('\u{307}', "CĊcċEĖeėGĠgġIİZŻzż"),
+    ('\u{308}', "AÄEËIÏOÖUÜaäeëiïoöuüyÿYŸ"),
+    ('\u{30a}', "AÅaåUŮuů"),
+    ('\u{30b}', "OŐoőUŰuű"),
+    ('\u{30c}', "CČcčDĎdďEĚeěLĽlľNŇnňRŘrřSŠsšTŤtťZŽzž"),
+    ('\u{327}', "CÇcçGĢgģKĶkķLĻlļNŅnņRŖrŗSŞsşTŢtţ"),
+    ('\u{328}', "AĄaąEĘeęIĮiįUŲuų"),
+];
+
+/// Canonical decomposition of the precomposed Latin letters.
+fn decompose(text: &str) -> String {
+    static TABLE: OnceLock<HashMap<char, (char, char)>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = HashMap::new();
+        for &(mark, pairs) in DECOMPOSITIONS {
+            let chars: Vec<char> = pairs.chars().collect();
+            for pair in chars.chunks(2) {
+                table.insert(pair[1], (pair[0], mark));
+            }
+        }
+        table
+    });
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match table.get(&c) {
+            Some(&(base, mark)) => {
+                out.push(base);
+                out.push(mark);
+            }
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// Drops combining diacritical marks.
+fn strip_marks(text: &str) -> String {
+    text.chars()
+        .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+        .collect()
+}
+
+/// CJK ideographs, which BERT surrounds with spaces.
+fn is_chinese_char(c: char) -> bool {
+    matches!(c as u32,
+        0x4E00..=0x9FFF
+        | 0x3400..=0x4DBF
+        | 0x20000..=0x2A6DF
+        | 0x2A700..=0x2B73F
+        | 0x2B740..=0x2B81F
+        | 0x2B820..=0x2CEAF
+        | 0xF900..=0xFAFF
+        | 0x2F800..=0x2FA1F)
+}
+
+/// BERT's punctuation: ASCII symbols and the common Unicode punctuation
+/// blocks.
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(c as u32,
+            0xA1 | 0xA7 | 0xAB | 0xB6 | 0xB7 | 0xBB | 0xBF
+            | 0x2010..=0x2027
+            | 0x2030..=0x205E
+            | 0x3001..=0x3003
+            | 0x3008..=0x3011
+            | 0x3014..=0x301F
+            | 0xFF01..=0xFF0F
+            | 0xFF1A..=0xFF20
+            | 0xFF3B..=0xFF40
+            | 0xFF5B..=0xFF65)
+}
+
+/// The `tokenizers` WordPiece decoder's clean-up of spaces before
+/// punctuation and contractions.
+fn clean_up_tokenization(text: &str) -> String {
+    text.replace(" .", ".")
+        .replace(" ?", "?")
+        .replace(" !", "!")
+        .replace(" ,", ",")
+        .replace(" ' ", "'")
+        .replace(" n't", "n't")
+        .replace(" 'm", "'m")
+        .replace(" do not", " don't")
+        .replace(" 's", "'s")
+        .replace(" 've", "'ve")
+        .replace(" 're", "'re")
+}
@@ -0,0 +1,271 @@
+//! Element-wise activation functions.
+
+use crate::tensor::{Float, Matrix};
+use crate::Result;
+
+/// An element-wise non-linearity.
+pub trait Activation {
+    /// Applies the function to a single value.
+    fn apply(&self, x: f64) -> f64;
+
+    /// Applies the function to every element of `x`, in either precision.
+    /// Each value is computed in `f64` and rounded back.
+    fn forward<T: Float>(&self, x: &Matrix<T>) -> Matrix<T>
+    where
+        Self: Sized,
+    {
+        x.map(|&v| T::from_f64(self.apply(v.to_f64())))
+    }
+
+    /// Derivative at `x`. Defaults to a central difference; the built-in
+    /// activations override it with the exact form.
+    fn derivative(&self, x: f64) -> f64 {
+        let h = 1e-6 * x.abs().max(1.0);
+        (self.apply(x + h) - self.apply(x - h)) / (2.0 * h)
+    }
+
+    /// Gradient with respect to the input `x`, given the gradient `grad` of
+    /// the output.
+    fn backward(&self, x: &Matrix, grad: &Matrix) -> Result<Matrix> {
+        grad.hadamard(&x.map(|&v| self.derivative(v)))
+    }
+}
+
+/// Rectified linear unit, `max(0, x)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReLU;
+
+impl Activation for ReLU {
+    fn apply(&self, x: f64) -> f64 {
+        x.max(0.0)
+    }
+
+    fn derivative(&self, x: f64) -> f64 {
+        if x > 0.0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Gaussian error linear unit (tanh approximation, as in BERT and GPT-2).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GELU;
+
+impl Activation for GELU {
+    fn apply(&self, x: f64) -> f64 {
+        0.5 * x * (1.0 + (SQRT_2_OVER_PI * (x + 0.044_715 * x * x * x)).tanh())
+    }
+
+    fn derivative(&self, x: f64) -> f64 {
+        let t = (SQRT_2_OVER_PI * (x + 0.044_715 * x * x * x)).tanh();
+        let du = SQRT_2_OVER_PI * (1.0 + 3.0 * 0.044_715 * x * x);
+        0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * du
+    }
+}
+
+const SQRT_2_OVER_PI: f64 = 0.797_884_560_802_865_4;
+
+/// Gaussian error linear unit, exact form `x·Φ(x)` using the error function
+/// (PyTorch's default `nn.GELU`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GELUExact;
+
+impl Activation for GELUExact {
+    fn apply(&self, x: f64) -> f64 {
+        0.5 * x * (1.0 + erf(x / std::f64::consts::SQRT_2))
+    }
+
+    /// `Φ(x) + x·φ(x)`.
+    fn derivative(&self, x: f64) -> f64 {
+        let cdf = 0.5 * (1.0 + erf(x / std::f64::consts::SQRT_2));
+        let pdf = (-0.5 * x * x).exp() * 0.5 * std::f64::consts::FRAC_2_SQRT_PI
+            / std::f64::consts::SQRT_2;
+        cdf + x * pdf
+    }
+}
+
+/// Sigmoid linear unit (also called swish), `x·σ(x)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SiLU;
+
+impl Activation for SiLU {
+    fn apply(&self, x: f64) -> f64 {
+        x / (1.0 + (-x).exp())
+    }
+
+    fn derivative(&self, x: f64) -> f64 {
+        let sigmoid = 1.0 / (1.0 + (-x).exp());
+        sigmoid * (1.0 + x * (1.0 - sigmoid))
+    }
+}
+
+/// Mish, `x·tanh(softplus(x))` (Misra, 2019).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mish;
+
+impl Activation for Mish {
+    fn apply(&self, x: f64) -> f64 {
+        // softplus(x) = ln(1 + eˣ), written to avoid overflow for large x.
+        let softplus = x.max(0.0) + (-x.abs()).exp().ln_1p();
+        x * softplus.tanh()
+    }
+
+    fn derivative(&self, x: f64) -> f64 {
+        let softplus = x.max(0.0) + (-x.abs()).exp().ln_1p();
+        let t = softplus.tanh();
+        let sigmoid = 1.0 / (1.0 + (-x).exp());
+        t + x * (1.0 - t * t) * sigmoid
+    }
+}
+
+/// Leaky rectified linear unit, `x` for positive inputs and
+/// `negative_slope·x` otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct LeakyReLU {
+    pub negative_slope: f64,
+}
+
+impl Default for LeakyReLU {
+    /// Slope 0.01, as in PyTorch.
+    fn default() -> Self {
+        Self {
+            negative_slope: 0.01,
+        }
+    }
+}
+
+impl Activation for LeakyReLU {
+    fn apply(&self, x: f64) -> f64 {
+        if x >= 0.0 {
+            x
+        } else {
+            self.negative_slope * x
+        }
+    }
+
+    fn derivative(&self, x: f64) -> f64 {
+        if x >= 0.0 {
+            1.0
+        } else {
+            self.negative_slope
+        }
+    }
+}
+
+/// Selects one of the built-in activations.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ActivationType {
+    #[default]
+    ReLU,
+    /// GELU, tanh approximation.
+    GELU,
+    /// GELU, exact erf form.
+    GELUExact,
+    SiLU,
+    Mish,
+    LeakyReLU {
+        negative_slope: f64,
+    },
+}
+
+impl ActivationType {
+    /// Parses the activation names used by common checkpoint configs
+    /// (`hidden_act` in Hugging Face, `activation` in PyTorch).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "relu" => ActivationType::ReLU,
+            "gelu_new" | "gelu_pytorch_tanh" | "gelu_tanh" | "gelu_fast" => ActivationType::GELU,
+            "gelu" | "gelu_exact" => ActivationType::GELUExact,
+            "silu" | "swish" => ActivationType::SiLU,
+            "mish" => ActivationType::Mish,
+            "leaky_relu" | "leakyrelu" => ActivationType::LeakyReLU {
+                negative_slope: LeakyReLU::default().negative_slope,
+            },
+            other => {
+                let slope = other.strip_prefix("leaky_relu:")?.parse().ok()?;
+                ActivationType::LeakyReLU {
+                    negative_slope: slope,
+                }
+            }
+        })
+    }
+
+    /// Canonical name, accepted by [`from_name`](Self::from_name). Leaky
+    /// ReLU carries its slope, as in `leaky_relu:0.01`.
+    pub fn name(&self) -> String {
+        match self {
+            ActivationType::ReLU => "relu".to_string(),
+            ActivationType::GELU => "gelu_tanh".to_string(),
+            ActivationType::GELUExact => "gelu".to_string(),
+            ActivationType::SiLU => "silu".to_string(),
+            ActivationType::Mish => "mish".to_string(),
+            ActivationType::LeakyReLU { negative_slope } => {
+                format!("leaky_relu:{}", negative_slope)
+            }
+        }
+    }
+}
+
+impl Activation for ActivationType {
+    fn apply(&self, x: f64) -> f64 {
+        match *self {
+            ActivationType::ReLU => ReLU.apply(x),
+            ActivationType::GELU => GELU.apply(x),
+            ActivationType::GELUExact => GELUExact.apply(x),
+            ActivationType::SiLU => SiLU.apply(x),
+            ActivationType::Mish => Mish.apply(x),
+            ActivationType::LeakyReLU { negative_slope } => LeakyReLU { negative_slope }.apply(x),
+        }
+    }
+
+    fn derivative(&self, x: f64) -> f64 {
+        match *self {
+            ActivationType::ReLU => ReLU.derivative(x),
+            ActivationType::GELU => GELU.derivative(x),
+            ActivationType::GELUExact => GELUExact.derivative(x),
+            ActivationType::SiLU => SiLU.derivative(x),
+            ActivationType::Mish => Mish.derivative(x),
+            ActivationType::LeakyReLU { negative_slope } => {
+                LeakyReLU { negative_slope }.derivative(x)
+            }
+        }
+    }
+}
+
+/// The error function, accurate to about 1e-14.
+///
+/// Uses the Maclaurin series for small arguments and the continued fraction
+/// of `erfc` beyond, where the series would lose precision.
+pub fn erf(x: f64) -> f64 {
+    const TWO_OVER_SQRT_PI: f64 = std::f64::consts::FRAC_2_SQRT_PI;
+    if x.is_nan() {
+        return x;
+    }
+    let a = x.abs();
+    let value = if a < 3.0 {
+        let (mut term, mut sum) = (a, a);
+        let x2 = a * a;
+        for n in 1..100 {
+            term *= -x2 / n as f64;
+            let contribution = term / (2 * n + 1) as f64;
+            sum += contribution;
+            if contribution.abs() < 1e-17 * sum.abs() {
+                break;
+            }
+        }
+        TWO_OVER_SQRT_PI * sum
+    } else if a < 6.0 {
+        // erfc(a) = e^(-a²)/√π · 1/(a + (1/2)/(a + 1/(a + (3/2)/(a + …)))),
+        // evaluated bottom-up.
+        let mut fraction = a;
+        for k in (1..60).rev() {
+            fraction = a + (k as f64 / 2.0) / fraction;
+        }
+        1.0 - (-a * a).exp() / (fraction * std::f64::consts::PI.sqrt())
+    } else {
+        1.0
+    };
+    value.copysign(x)
+}
@@ -0,0 +1,71 @@
+//! Inverted dropout.
+
+use crate::tensor::{Float, Matrix};
+use crate::utils::rng::SharedRng;
+use crate::Result;
+
+/// Randomly zeroes elements with probability `rate` while training and scales
+/// the survivors by `1 / (1 - rate)`. Acts as the identity in evaluation mode.
+#[derive(Debug, Clone)]
+pub struct Dropout {
+    pub rate: f64,
+    pub training: bool,
+    /// Source of the dropout masks.
+    pub rng: SharedRng,
+}
+
+impl Dropout {
+    /// Creates a dropout layer in evaluation mode with its own
+    /// entropy-seeded generator.
+    pub fn new(rate: f64) -> Self {
+        Self::with_rng(rate, SharedRng::from_entropy())
+    }
+
+    /// Creates a dropout layer in evaluation mode drawing from `rng`.
+    pub fn with_rng(rate: f64, rng: SharedRng) -> Self {
+        Self {
+            rate,
+            training: false,
+            rng,
+        }
+    }
+
+    pub fn forward<T: Float>(&self, x: &Matrix<T>) -> Matrix<T> {
+        if !self.training || self.rate <= 0.0 {
+            return x.clone();
+        }
+        let keep = 1.0 - self.rate;
+        let divisor = T::from_f64(keep);
+        self.rng.with(|rng| {
+            x.map(|&v| {
+                if rng.bernoulli(keep) {
+                    v / divisor
+                } else {
+                    T::ZERO
+                }
+            })
+        })
+    }
+
+    /// Like [`forward`](Self::forward), also returning the applied mask
+    /// (`0` or `1 / (1 - rate)` per element), or `None` when the layer is
+    /// the identity. Draws the same random numbers as `forward`.
+    pub fn forward_with_mask(&self, x: &Matrix) -> Result<(Matrix, Option<Matrix>)> {
+        if !self.training || self.rate <= 0.0 {
+            return Ok((x.clone(), None));
+        }
+        let keep = 1.0 - self.rate;
+        let mask = self
+            .rng
+            .with(|rng| x.map(|_| if rng.bernoulli(keep) { 1.0 / keep } else { 0.0 }));
+        Ok((x.hadamard(&mask)?, Some(mask)))
+    }
+
+    /// Gradient through a dropout that applied `mask`.
+    pub fn backward(mask: Option<&Matrix>, grad: &Matrix) -> Result<Matrix> {
+        match mask {
+            Some(mask) => grad.hadamard(mask),
+            None => Ok(grad.clone()),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Token embedding lookup.
+
+use std::sync::Arc;
+
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::{Float, Matrix};
+use crate::utils::rng::Rng;
+use crate::{Result, TransformerError};
+
+/// Maps token ids to rows of a learned `vocab_size × dim` table, optionally
+/// multiplied by a constant (`√d_model` in the original Transformer).
+///
+/// The table is reference counted so several modules can share it; writes
+/// through [`weight_mut`](Self::weight_mut) copy it first if it is shared.
+/// Lookups work in either precision; training is `f64` only.
+#[derive(Debug, Clone)]
+pub struct Embedding<T = f64> {
+    pub weight: Arc<Matrix<T>>,
+    pub scale: f64,
+}
+
+impl Embedding {
+    /// Creates a table with entries drawn from `N(0, 1/dim)` and scale one.
+    pub fn new(vocab_size: usize, dim: usize, rng: &mut Rng) -> Self {
+        Self::from_weight(Matrix::random_normal(
+            vocab_size,
+            dim,
+            (dim as f64).powf(-0.5),
+            rng,
+        ))
+    }
+
+    /// Adds the gradient of the table, given the gradient `grad` of the
+    /// looked-up rows of `tokens`, to `grads` under `name`.
+    pub fn backward(
+        &self,
+        tokens: &[usize],
+        grad: &Matrix,
+        name: &str,
+        grads: &mut Gradients,
+    ) -> Result<()> {
+        grad.ensure_shape((tokens.len(), self.dim()), "embedding gradient")?;
+        let mut d_weight = Matrix::zeros(self.vocab_size(), self.dim());
+        for (i, &token) in tokens.iter().enumerate() {
+            for (acc, g) in d_weight.row_mut(token).iter_mut().zip(grad.row(i)) {
+                *acc += self.scale * g;
+            }
+        }
+        grads.accumulate(name, &d_weight)
+    }
+
+    /// Adds the table's gradient through [`attend`](Self::attend), given the
+    /// gradient `d_logits` of its output, to `grads` under `name` and
+    /// returns the gradient with respect to `hidden`.
+    pub fn attend_backward(
+        &self,
+        hidden: &Matrix,
+        d_logits: &Matrix,
+        name: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        d_logits.ensure_shape((hidden.rows(), self.vocab_size()), "logit gradient")?;
+        grads.accumulate(name, &d_logits.transpose().matmul(hidden)?)?;
+        d_logits.matmul(&self.weight)
+    }
+}
+
+impl<T: Float> Embedding<T> {
+    /// Wraps an existing table.
+    pub fn from_weight(weight: Matrix<T>) -> Self {
+        Self {
+            weight: Arc::new(weight),
+            scale: 1.0,
+        }
+    }
+
+    /// Mutable access to the table, copying it first if it is shared.
+    pub fn weight_mut(&mut self) -> &mut Matrix<T> {
+        Arc::make_mut(&mut self.weight)
+    }
+
+    /// Makes this embedding use `other`'s table.
+    pub fn share_weight(&mut self, other: &Embedding<T>) {
+        self.weight = Arc::clone(&other.weight);
+    }
+
+    /// Whether both embeddings use the same table.
+    pub fn shares_weight_with(&self, other: &Embedding<T>) -> bool {
+        Arc::ptr_eq(&self.weight, &other.weight)
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn vocab_size(&self) -> usize {
+        self.weight.rows()
+    }
+
+    pub fn dim(&self) -> usize {
+        self.weight.cols()
+    }
+
+    /// Looks up `tokens`, returning `tokens.len() × dim` scaled rows.
+    pub fn forward(&self, tokens: &[usize]) -> Result<Matrix<T>> {
+        if let Some(&token) = tokens.iter().find(|&&t| t >= self.vocab_size()) {
+            return Err(TransformerError::VocabOverflow {
+                token,
+                vocab_size: self.vocab_size(),
+            });
+        }
+        let rows = self.weight.select_rows(tokens)?;
+        Ok(if self.scale == 1.0 {
+            rows
+        } else {
+            rows.scale(T::from_f64(self.scale))
+        })
+    }
+
+    /// Projects `seq_len × dim` hidden states back onto the vocabulary with
+    /// the transposed table, giving `seq_len × vocab_size` logits.
+    pub fn attend(&self, hidden: &Matrix<T>) -> Result<Matrix<T>> {
+        hidden.matmul(&self.weight.transpose())
+    }
+
+    /// A copy with the table converted to another precision.
+    pub fn cast<U: Float>(&self) -> Embedding<U> {
+        Embedding {
+            weight: Arc::new(self.weight.cast()),
+            scale: self.scale,
+        }
+    }
+}
+
+impl Parameters for Embedding {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "weight"), &self.weight);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "weight"), self.weight_mut());
+    }
+}
@@ -0,0 +1,164 @@
+//! Position-wise feed-forward network.
+
+use super::activation::{Activation, ActivationType};
+use super::dropout::Dropout;
+use crate::config::InitScheme;
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::{Float, Matrix};
+use crate::utils::rng::{thread_rng, Rng};
+use crate::Result;
+
+/// `FFN(x) = act(x·W1 + b1)·W2 + b2`, applied to every position independently.
+/// The forward pass works in either precision; training is `f64` only.
+#[derive(Debug, Clone)]
+pub struct FeedForward<T = f64> {
+    pub w1: Matrix<T>,
+    pub b1: Matrix<T>,
+    pub w2: Matrix<T>,
+    pub b2: Matrix<T>,
+    pub activation: ActivationType,
+    pub dropout: Dropout,
+}
+
+impl FeedForward {
+    /// Creates a randomly initialized `d_model → d_ff → d_model` network.
+    pub fn new(d_model: usize, d_ff: usize, activation: ActivationType, dropout: f64) -> Self {
+        Self::new_with_rng(
+            d_model,
+            d_ff,
+            activation,
+            Dropout::new(dropout),
+            &mut thread_rng(),
+        )
+    }
+
+    /// Like [`new`](Self::new), drawing the initial weights from `rng` and
+    /// using the given dropout layer.
+    pub fn new_with_rng(
+        d_model: usize,
+        d_ff: usize,
+        activation: ActivationType,
+        dropout: Dropout,
+        rng: &mut Rng,
+    ) -> Self {
+        Self::new_with_init(
+            d_model,
+            d_ff,
+            activation,
+            dropout,
+            InitScheme::default(),
+            rng,
+        )
+    }
+
+    /// Like [`new_with_rng`](Self::new_with_rng), drawing both weights under
+    /// `init`.
+    pub fn new_with_init(
+        d_model: usize,
+        d_ff: usize,
+        activation: ActivationType,
+        dropout: Dropout,
+        init: InitScheme,
+        rng: &mut Rng,
+    ) -> Self {
+        Self {
+            w1: init.weight(d_model, d_ff, rng),
+            b1: Matrix::zeros(1, d_ff),
+            w2: init.weight(d_ff, d_model, rng),
+            b2: Matrix::zeros(1, d_model),
+            activation,
+            dropout,
+        }
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(&self, x: &Matrix) -> Result<(Matrix, FeedForwardCache)> {
+        let pre_activation = x.matmul(&self.w1)?.add_row_vector(&self.b1)?;
+        let (hidden, dropout_mask) = self
+            .dropout
+            .forward_with_mask(&self.activation.forward(&pre_activation))?;
+        let out = hidden.matmul(&self.w2)?.add_row_vector(&self.b2)?;
+        Ok((
+            out,
+            FeedForwardCache {
+                input: x.clone(),
+                pre_activation,
+                dropout_mask,
+                hidden,
+            },
+        ))
+    }
+
+    /// Adds the gradients of all four weights to `grads` and returns the
+    /// gradient with respect to the input.
+    pub fn backward(
+        &self,
+        cache: &FeedForwardCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        grads.accumulate(
+            &join_name(prefix, "w2"),
+            &cache.hidden.transpose().matmul(grad)?,
+        )?;
+        grads.accumulate(&join_name(prefix, "b2"), &grad.column_sums())?;
+        let d_hidden = grad.matmul(&self.w2.transpose())?;
+        let d_hidden = Dropout::backward(cache.dropout_mask.as_ref(), &d_hidden)?;
+        let d_pre = self.activation.backward(&cache.pre_activation, &d_hidden)?;
+        grads.accumulate(
+            &join_name(prefix, "w1"),
+            &cache.input.transpose().matmul(&d_pre)?,
+        )?;
+        grads.accumulate(&join_name(prefix, "b1"), &d_pre.column_sums())?;
+        d_pre.matmul(&self.w1.transpose())
+    }
+}
+
+impl<T: Float> FeedForward<T> {
+    pub fn forward(&self, x: &Matrix<T>) -> Result<Matrix<T>> {
+        let hidden = x.matmul(&self.w1)?.add_row_vector(&self.b1)?;
+        let hidden = self.dropout.forward(&self.activation.forward(&hidden));
+        hidden.matmul(&self.w2)?.add_row_vector(&self.b2)
+    }
+
+    /// A copy with the weights converted to another precision.
+    pub fn cast<U: Float>(&self) -> FeedForward<U> {
+        FeedForward {
+            w1: self.w1.cast(),
+            b1: self.b1.cast(),
+            w2: self.w2.cast(),
+            b2: self.b2.cast(),
+            activation: self.activation,
+            dropout: self.dropout.clone(),
+        }
+    }
+}
+
+/// Intermediate values of [`FeedForward::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct FeedForwardCache {
+    pub input: Matrix,
+    /// `x·W1 + b1`.
+    pub pre_activation: Matrix,
+    pub dropout_mask: Option<Matrix>,
+    /// Activated, dropped-out hidden layer.
+    pub hidden: Matrix,
+}
+
+impl Parameters for FeedForward {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "w1"), &self.w1);
+        visitor(&join_name(prefix, "b1"), &self.b1);
+        visitor(&join_name(prefix, "w2"), &self.w2);
+        visitor(&join_name(prefix, "b2"), &self.b2);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "w1"), &mut self.w1);
+        visitor(&join_name(prefix, "b1"), &mut self.b1);
+        visitor(&join_name(prefix, "w2"), &mut self.w2);
+        visitor(&join_name(prefix, "b2"), &mut self.b2);
+    }
+}
@@ -0,0 +1,149 @@
+//! Layer normalization (Ba et al., 2016).
+
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::{Float, Matrix, Tensor3};
+use crate::utils::parallel;
+use crate::{Result, TransformerError};
+
+/// Normalizes each row to zero mean and unit variance, then applies a learned
+/// per-feature scale (`gamma`) and shift (`beta`). The forward pass works in
+/// either precision; training is `f64` only.
+#[derive(Debug, Clone)]
+pub struct LayerNorm<T = f64> {
+    pub gamma: Matrix<T>,
+    pub beta: Matrix<T>,
+    pub eps: f64,
+}
+
+impl LayerNorm {
+    /// Creates a layer norm over `d_model` features with `gamma = 1`, `beta = 0`.
+    pub fn new(d_model: usize, eps: f64) -> Self {
+        Self {
+            gamma: Matrix::ones(1, d_model),
+            beta: Matrix::zeros(1, d_model),
+            eps,
+        }
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(&self, x: &Matrix) -> Result<(Matrix, LayerNormCache)> {
+        let out = self.forward(x)?;
+        let n = x.cols() as f64;
+        let mut normalized = x.clone();
+        let mut inv_std = Vec::with_capacity(x.rows());
+        for i in 0..x.rows() {
+            let row = normalized.row_mut(i);
+            let mean = row.iter().sum::<f64>() / n;
+            let var = row.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / n;
+            let inv = 1.0 / (var + self.eps).sqrt();
+            for v in row.iter_mut() {
+                *v = (*v - mean) * inv;
+            }
+            inv_std.push(inv);
+        }
+        Ok((
+            out,
+            LayerNormCache {
+                normalized,
+                inv_std,
+            },
+        ))
+    }
+
+    /// Adds the gradients of `gamma` and `beta` to `grads` and returns the
+    /// gradient with respect to the input.
+    pub fn backward(
+        &self,
+        cache: &LayerNormCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        let x_hat = &cache.normalized;
+        grad.ensure_shape(x_hat.shape(), "LayerNorm gradient")?;
+        grads.accumulate(
+            &join_name(prefix, "gamma"),
+            &grad.hadamard(x_hat)?.column_sums(),
+        )?;
+        grads.accumulate(&join_name(prefix, "beta"), &grad.column_sums())?;
+
+        // dx = inv_std / n · (n·dx̂ − Σdx̂ − x̂·Σ(dx̂·x̂)), with dx̂ = grad·γ.
+        let n = x_hat.cols() as f64;
+        let gamma = self.gamma.as_slice();
+        let mut dx = Matrix::zeros(x_hat.rows(), x_hat.cols());
+        for i in 0..x_hat.rows() {
+            let d_hat: Vec<f64> = grad.row(i).iter().zip(gamma).map(|(g, w)| g * w).collect();
+            let sum: f64 = d_hat.iter().sum();
+            let dot: f64 = d_hat.iter().zip(x_hat.row(i)).map(|(d, x)| d * x).sum();
+            let inv = cache.inv_std[i];
+            for ((out, d), x) in dx.row_mut(i).iter_mut().zip(&d_hat).zip(x_hat.row(i)) {
+                *out = inv / n * (n * d - sum - x * dot);
+            }
+        }
+        Ok(dx)
+    }
+
+    /// Normalizes every row of every batch entry of `x`.
+    pub fn forward_batch(&self, x: &Tensor3) -> Result<Tensor3> {
+        x.layer_norm(&self.gamma, &self.beta, self.eps)
+    }
+}
+
+impl<T: Float> LayerNorm<T> {
+    pub fn d_model(&self) -> usize {
+        self.gamma.cols()
+    }
+
+    /// Normalizes every row of `x` (`seq_len × d_model`).
+    pub fn forward(&self, x: &Matrix<T>) -> Result<Matrix<T>> {
+        if x.cols() != self.d_model() {
+            return Err(TransformerError::shape(format!(
+                "LayerNorm expects {} features, got {}",
+                self.d_model(),
+                x.cols()
+            )));
+        }
+        let n = T::from_f64(x.cols() as f64);
+        let eps = T::from_f64(self.eps);
+        let mut out = x.clone();
+        parallel::for_each_row_mut(out.as_mut_slice(), x.cols(), |_, row| {
+            let mean = row.iter().copied().sum::<T>() / n;
+            let var = row.iter().map(|&v| (v - mean) * (v - mean)).sum::<T>() / n;
+            let inv_std = T::ONE / (var + eps).sqrt();
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (*v - mean) * inv_std * self.gamma.as_slice()[j] + self.beta.as_slice()[j];
+            }
+        });
+        Ok(out)
+    }
+
+    /// A copy with `gamma` and `beta` converted to another precision.
+    pub fn cast<U: Float>(&self) -> LayerNorm<U> {
+        LayerNorm {
+            gamma: self.gamma.cast(),
+            beta: self.beta.cast(),
+            eps: self.eps,
+        }
+    }
+}
+
+/// Normalized inputs and inverse standard deviations from
+/// [`LayerNorm::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct LayerNormCache {
+    pub normalized: Matrix,
+    pub inv_std: Vec<f64>,
+}
+
+impl Parameters for LayerNorm {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "gamma"), &self.gamma);
+        visitor(&join_name(prefix, "beta"), &self.beta);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "gamma"), &mut self.gamma);
+        visitor(&join_name(prefix, "beta"), &mut self.beta);
+    }
+}
@@ -0,0 +1,19 @@
+//! Building blocks shared by the encoder and decoder stacks.
+
+pub mod activation;
+pub mod dropout;
+pub mod embedding;
+pub mod feed_forward;
+pub mod layer_norm;
+pub mod norm;
+pub mod positional;
+pub mod rms_norm;
+
+pub use activation::{Activation, ActivationType, GELUExact, LeakyReLU, Mish, ReLU, SiLU, GELU};
+pub use dropout::Dropout;
+pub use embedding::Embedding;
+pub use feed_forward::{FeedForward, FeedForwardCache};
+pub use layer_norm::{LayerNorm, LayerNormCache};
+pub use norm::{Norm, NormCache};
+pub use positional::PositionalEncoding;
+pub use rms_norm::{RMSNorm, RMSNormCache};
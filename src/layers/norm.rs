@@ -0,0 +1,127 @@
+//! The normalization of encoder and decoder sub-layers, chosen by
+//! [`TransformerConfig::norm`].
+
+use super::layer_norm::{LayerNorm, LayerNormCache};
+use super::rms_norm::{RMSNorm, RMSNormCache};
+use crate::config::{NormType, TransformerConfig};
+use crate::params::{Gradients, Parameters};
+use crate::tensor::{Float, Matrix, Tensor3};
+use crate::Result;
+
+/// A [`LayerNorm`] or an [`RMSNorm`].
+#[derive(Debug, Clone)]
+pub enum Norm<T = f64> {
+    Layer(LayerNorm<T>),
+    Rms(RMSNorm<T>),
+}
+
+/// What [`Norm::backward`] needs, from [`Norm::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub enum NormCache {
+    Layer(LayerNormCache),
+    Rms(RMSNormCache),
+}
+
+impl Norm {
+    /// A freshly initialized norm of the given type over `d_model` features.
+    pub fn new(norm: NormType, d_model: usize, eps: f64) -> Self {
+        match norm {
+            NormType::LayerNorm => Norm::Layer(LayerNorm::new(d_model, eps)),
+            NormType::RMSNorm => Norm::Rms(RMSNorm::new(d_model, eps)),
+        }
+    }
+
+    /// The norm `config` asks for, over `d_model` features.
+    pub fn for_config(config: &TransformerConfig) -> Self {
+        Self::new(config.norm, config.d_model, config.layer_norm_eps)
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(&self, x: &Matrix) -> Result<(Matrix, NormCache)> {
+        Ok(match self {
+            Norm::Layer(norm) => {
+                let (out, cache) = norm.forward_with_cache(x)?;
+                (out, NormCache::Layer(cache))
+            }
+            Norm::Rms(norm) => {
+                let (out, cache) = norm.forward_with_cache(x)?;
+                (out, NormCache::Rms(cache))
+            }
+        })
+    }
+
+    /// Adds the parameter gradients to `grads` and returns the gradient with
+    /// respect to the input.
+    pub fn backward(
+        &self,
+        cache: &NormCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        match (self, cache) {
+            (Norm::Layer(norm), NormCache::Layer(cache)) => {
+                norm.backward(cache, grad, prefix, grads)
+            }
+            (Norm::Rms(norm), NormCache::Rms(cache)) => norm.backward(cache, grad, prefix, grads),
+            _ => Err("norm cache does not match the norm type".into()),
+        }
+    }
+
+    /// Normalizes every row of every batch entry of `x`.
+    pub fn forward_batch(&self, x: &Tensor3) -> Result<Tensor3> {
+        match self {
+            Norm::Layer(norm) => norm.forward_batch(x),
+            Norm::Rms(norm) => norm.forward_batch(x),
+        }
+    }
+}
+
+impl<T: Float> Norm<T> {
+    pub fn norm_type(&self) -> NormType {
+        match self {
+            Norm::Layer(_) => NormType::LayerNorm,
+            Norm::Rms(_) => NormType::RMSNorm,
+        }
+    }
+
+    pub fn d_model(&self) -> usize {
+        match self {
+            Norm::Layer(norm) => norm.d_model(),
+            Norm::Rms(norm) => norm.d_model(),
+        }
+    }
+
+    /// Normalizes every row of `x` (`seq_len × d_model`).
+    pub fn forward(&self, x: &Matrix<T>) -> Result<Matrix<T>> {
+        match self {
+            Norm::Layer(norm) => norm.forward(x),
+            Norm::Rms(norm) => norm.forward(x),
+        }
+    }
+
+    /// A copy with the parameters converted to another precision.
+    pub fn cast<U: Float>(&self) -> Norm<U> {
+        match self {
+            Norm::Layer(norm) => Norm::Layer(norm.cast()),
+            Norm::Rms(norm) => Norm::Rms(norm.cast()),
+        }
+    }
+}
+
+impl Parameters for Norm {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        match self {
+            Norm::Layer(norm) => norm.visit_parameters(prefix, visitor),
+            Norm::Rms(norm) => norm.visit_parameters(prefix, visitor),
+        }
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        match self {
+            Norm::Layer(norm) => norm.visit_parameters_mut(prefix, visitor),
+            Norm::Rms(norm) => norm.visit_parameters_mut(prefix, visitor),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Sinusoidal (Vaswani et al., 2017) and learned positional encodings.
+
+use crate::config::{PositionalScheme, TransformerConfig};
+use crate::params::Gradients;
+use crate::tensor::Matrix;
+use crate::utils::rng::Rng;
+use crate::{Result, TransformerError};
+
+/// Position table added to token embeddings: fixed sine/cosine values, or
+/// a trained table that is a parameter of the model.
+#[derive(Debug, Clone)]
+pub struct PositionalEncoding {
+    table: Matrix,
+    learned: bool,
+}
+
+impl PositionalEncoding {
+    /// Precomputes encodings for positions `0..max_seq_len`.
+    pub fn new(max_seq_len: usize, d_model: usize) -> Self {
+        let table = Matrix::from_fn(max_seq_len, d_model, |pos, i| {
+            let exponent = (2 * (i / 2)) as f64 / d_model as f64;
+            let angle = pos as f64 / 10000f64.powf(exponent);
+            if i % 2 == 0 {
+                angle.sin()
+            } else {
+                angle.cos()
+            }
+        });
+        Self {
+            table,
+            learned: false,
+        }
+    }
+
+    /// An encoding that adds nothing and accepts any length, for models
+    /// whose attention sees positions itself (ALiBi).
+    pub fn none() -> Self {
+        Self {
+            table: Matrix::zeros(0, 0),
+            learned: false,
+        }
+    }
+
+    /// Whether this is [`none`](Self::none).
+    pub fn is_none(&self) -> bool {
+        self.table.cols() == 0
+    }
+
+    /// Builds the encoding `config` asks for, drawing a learned table from
+    /// `rng` like a token embedding.
+    pub fn for_config(config: &TransformerConfig, rng: &mut Rng) -> Self {
+        match config.positional {
+            PositionalScheme::Sinusoidal => Self::new(config.max_seq_len, config.d_model),
+            PositionalScheme::Alibi => Self::none(),
+            PositionalScheme::Learned => Self::learned(config.init.embedding(
+                config.max_seq_len,
+                config.d_model,
+                rng,
+            )),
+        }
+    }
+
+    /// Uses `table` (`max_seq_len × d_model`) as fixed encodings.
+    pub fn from_table(table: Matrix) -> Self {
+        Self {
+            table,
+            learned: false,
+        }
+    }
+
+    /// Uses `table` (`max_seq_len × d_model`) as trainable encodings, e.g.
+    /// the position embeddings of a pretrained checkpoint.
+    pub fn learned(table: Matrix) -> Self {
+        Self {
+            table,
+            learned: true,
+        }
+    }
+
+    /// Whether the table is a trainable parameter.
+    pub fn is_learned(&self) -> bool {
+        self.learned
+    }
+
+    pub fn table(&self) -> &Matrix {
+        &self.table
+    }
+
+    pub fn table_mut(&mut self) -> &mut Matrix {
+        &mut self.table
+    }
+
+    pub fn max_seq_len(&self) -> usize {
+        self.table.rows()
+    }
+
+    /// Adds the encodings for positions `0..x.rows()` to `x`.
+    pub fn forward(&self, x: &Matrix) -> Result<Matrix> {
+        self.forward_at(x, 0)
+    }
+
+    /// Adds the encodings for positions `offset..offset + x.rows()` to `x`,
+    /// for rows that continue an earlier sequence.
+    pub fn forward_at(&self, x: &Matrix, offset: usize) -> Result<Matrix> {
+        if self.is_none() {
+            return Ok(x.clone());
+        }
+        if offset + x.rows() > self.max_seq_len() {
+            return Err(TransformerError::shape(format!(
+                "sequence length {} exceeds max_seq_len {}",
+                offset + x.rows(),
+                self.max_seq_len()
+            )));
+        }
+        x.add(&self.table.rows_range(offset, x.rows())?)
+    }
+
+    /// Adds the gradient of a learned table, named `name`, given the
+    /// gradient `grad` of the output of [`forward`](Self::forward). Fixed
+    /// tables have no gradient.
+    pub fn backward(&self, grad: &Matrix, name: &str, grads: &mut Gradients) -> Result<()> {
+        if !self.learned {
+            return Ok(());
+        }
+        let mut d_table = Matrix::zeros(self.table.rows(), self.table.cols());
+        for i in 0..grad.rows() {
+            d_table.row_mut(i).copy_from_slice(grad.row(i));
+        }
+        grads.accumulate(name, &d_table)
+    }
+}
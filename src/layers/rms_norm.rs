@@ -0,0 +1,156 @@
+//! Root-mean-square layer normalization (Zhang & Sennrich, 2019).
+
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::{Float, Matrix, Tensor3};
+use crate::utils::parallel;
+use crate::{Result, TransformerError};
+
+/// Divides each row by its root mean square and applies a learned
+/// per-feature scale (`gamma`). Unlike [`LayerNorm`](super::LayerNorm) it
+/// neither centers the row nor adds a shift, as in LLaMA-family models. The
+/// forward pass works in either precision; training is `f64` only.
+#[derive(Debug, Clone)]
+pub struct RMSNorm<T = f64> {
+    pub gamma: Matrix<T>,
+    pub eps: f64,
+}
+
+impl RMSNorm {
+    /// Creates an RMS norm over `d_model` features with `gamma = 1`.
+    pub fn new(d_model: usize, eps: f64) -> Self {
+        Self {
+            gamma: Matrix::ones(1, d_model),
+            eps,
+        }
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(&self, x: &Matrix) -> Result<(Matrix, RMSNormCache)> {
+        let out = self.forward(x)?;
+        let mut normalized = x.clone();
+        let mut inv_rms = Vec::with_capacity(x.rows());
+        for i in 0..x.rows() {
+            let row = normalized.row_mut(i);
+            let inv = self.inv_rms(row);
+            for v in row.iter_mut() {
+                *v *= inv;
+            }
+            inv_rms.push(inv);
+        }
+        Ok((
+            out,
+            RMSNormCache {
+                normalized,
+                inv_rms,
+            },
+        ))
+    }
+
+    /// Adds the gradient of `gamma` to `grads` and returns the gradient with
+    /// respect to the input.
+    pub fn backward(
+        &self,
+        cache: &RMSNormCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        let x_hat = &cache.normalized;
+        grad.ensure_shape(x_hat.shape(), "RMSNorm gradient")?;
+        grads.accumulate(
+            &join_name(prefix, "gamma"),
+            &grad.hadamard(x_hat)?.column_sums(),
+        )?;
+
+        // dx = inv_rms · (dx̂ − x̂·mean(dx̂·x̂)), with dx̂ = grad·γ.
+        let n = x_hat.cols() as f64;
+        let gamma = self.gamma.as_slice();
+        let mut dx = Matrix::zeros(x_hat.rows(), x_hat.cols());
+        for i in 0..x_hat.rows() {
+            let d_hat: Vec<f64> = grad.row(i).iter().zip(gamma).map(|(g, w)| g * w).collect();
+            let mean_dot = d_hat
+                .iter()
+                .zip(x_hat.row(i))
+                .map(|(d, x)| d * x)
+                .sum::<f64>()
+                / n;
+            let inv = cache.inv_rms[i];
+            for ((out, d), x) in dx.row_mut(i).iter_mut().zip(&d_hat).zip(x_hat.row(i)) {
+                *out = inv * (d - x * mean_dot);
+            }
+        }
+        Ok(dx)
+    }
+
+    /// Normalizes every row of every batch entry of `x`.
+    pub fn forward_batch(&self, x: &Tensor3) -> Result<Tensor3> {
+        let normalized = x
+            .to_matrices()
+            .iter()
+            .map(|m| self.forward(m))
+            .collect::<Result<Vec<_>>>()?;
+        Tensor3::from_matrices(&normalized)
+    }
+}
+
+impl<T: Float> RMSNorm<T> {
+    pub fn d_model(&self) -> usize {
+        self.gamma.cols()
+    }
+
+    /// Normalizes every row of `x` (`seq_len × d_model`).
+    pub fn forward(&self, x: &Matrix<T>) -> Result<Matrix<T>> {
+        self.check(x)?;
+        let mut out = x.clone();
+        parallel::for_each_row_mut(out.as_mut_slice(), x.cols(), |_, row| {
+            let inv = self.inv_rms(row);
+            for (v, &g) in row.iter_mut().zip(self.gamma.as_slice()) {
+                *v *= inv * g;
+            }
+        });
+        Ok(out)
+    }
+
+    /// A copy with `gamma` converted to another precision.
+    pub fn cast<U: Float>(&self) -> RMSNorm<U> {
+        RMSNorm {
+            gamma: self.gamma.cast(),
+            eps: self.eps,
+        }
+    }
+
+    fn check(&self, x: &Matrix<T>) -> Result<()> {
+        if x.cols() != self.d_model() {
+            return Err(TransformerError::shape(format!(
+                "RMSNorm expects {} features, got {}",
+                self.d_model(),
+                x.cols()
+            )));
+        }
+        Ok(())
+    }
+
+    fn inv_rms(&self, row: &[T]) -> T {
+        let mean_square = row.iter().map(|&v| v * v).sum::<T>() / T::from_f64(row.len() as f64);
+        T::ONE / (mean_square + T::from_f64(self.eps)).sqrt()
+    }
+}
+
+/// Normalized inputs and inverse root mean squares from
+/// [`RMSNorm::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct RMSNormCache {
+    pub normalized: Matrix,
+    pub inv_rms: Vec<f64>,
+}
+
+impl Parameters for RMSNorm {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "gamma"), &self.gamma);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "gamma"), &mut self.gamma);
+    }
+}
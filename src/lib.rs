@@ -0,0 +1,82 @@
+//! # rust-transformer
+//!
+//! A Transformer implementation in Rust for learning and experimentation.
+//!
+//! The crate is dependency-free and favours readable implementations over
+//! raw speed. Modules are added as the project grows:
+//!
+//! - [`tensor`]: the dense [`Matrix`] type every layer computes with
+//! - [`layers`]: layer and RMS normalization, feed-forward networks, activations
+//! - [`attention`]: scaled dot-product and multi-head attention, with optional ALiBi
+//!   distance biases and sliding-window (local) attention
+//! - [`models`]: encoder and decoder stacks, the encoder-only model and the full
+//!   [`Transformer`], and classification heads for fine-tuning
+//! - [`engine`]: a worker-thread pool serving encode and generate jobs
+//! - [`generation`]: decoding options, sampling filters and beam search
+//! - [`gguf`]: GGUF model export and import with quantized weights
+//! - [`hooks`]: callbacks observing or editing intermediate activations
+//! - [`huggingface`]: pretrained BERT and Marian checkpoints from safetensors
+//! - [`cancellation`]: tokens that stop generation and training early
+//! - [`calibration`]: uncertainty estimates and calibrated probabilities
+//! - [`data`]: datasets, reproducible splitting and padded batching
+//! - [`evaluate`]: BLEU and ROUGE scoring for generated sequences
+//! - [`onnx`]: running external ONNX encoders with the crate's kernels
+//! - [`optim`]: optimizers, parameter groups and learning-rate schedules
+//! - [`pipeline`]: string-in, string-out generation with a tokenizer
+//! - [`progress`]: progress callbacks for loading, quantizing and training
+//! - [`quantization`]: Q8_0 / Q4_0 block formats
+//! - [`rlhf`]: PPO fine-tuning against a reward model
+//! - [`distributed`]: data-parallel training over TCP
+//! - [`training`]: losses and optimization helpers such as gradient-flow
+//!   statistics
+//! - [`tokenizer`]: text to token id conversion
+//! - [`testing`]: numerical parity checks against reference fixtures
+//! - [`visualize`]: attention map export as JSON and PNG heatmaps
+//! - `server` (feature `server`): an HTTP/JSON front end to the engine with
+//!   `/encode` and `/generate` endpoints
+//! - `wasm` (feature `wasm`): `wasm-bindgen` exports for encoding and
+//!   generation in the browser
+//! - [`utils`]: masks, softmax, JSON reading, JSON/PNG writing, gzip reading, the
+//!   seedable RNG, and the thread splitting behind the `parallel` feature
+
+pub mod attention;
+pub mod calibration;
+pub mod cancellation;
+pub mod config;
+pub mod data;
+pub mod distributed;
+pub mod engine;
+pub mod error;
+pub mod evaluate;
+pub mod generation;
+pub mod gguf;
+pub mod hooks;
+pub mod huggingface;
+pub mod layers;
+pub mod models;
+pub mod onnx;
+pub mod optim;
+pub mod params;
+pub mod pipeline;
+pub mod progress;
+pub mod quantization;
+pub mod rlhf;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod tensor;
+pub mod testing;
+pub mod tokenizer;
+pub mod training;
+pub mod utils;
+pub mod visualize;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use config::{InitConfig, InitScheme, NormType, PositionalScheme, TransformerConfig};
+pub use error::TransformerError;
+pub use models::Transformer;
+pub use params::Parameters;
+pub use tensor::Matrix;
+
+/// Crate-wide result type.
+pub type Result<T> = std::result::Result<T, TransformerError>;
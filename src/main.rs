@@ -1,3 +1,590 @@
-fn main() {
-    println!("Hello, world!");
+//! Command-line front end: train a sequence-to-sequence model on a text
+//! corpus, sample from a checkpoint, score one on held-out data or chat
+//! with it.
+//!
+//! ```text
+//! rust-transformer train --data corpus.txt [--config cfg.json] [--model model.bin]
+//!                        [--epochs N] [--batch-size N] [--lr X] [--seed N]
+//! rust-transformer generate --model model.bin --prompt "..." [--max-length N]
+//!                           [--seed N] [--greedy]
+//! rust-transformer eval --model model.bin --data test.txt
+//! rust-transformer chat --model model.bin [--history chat.txt] [--seed N] [--greedy]
+//! ```
+//!
+//! A corpus holds one example per line, `source<TAB>target`; a line without
+//! a tab is its own target. Text is tokenized per character with a
+//! vocabulary built from the training corpus, which `train` saves next to
+//! the model as `<model>.vocab.json`.
+//!
+//! `chat` answers lines read from standard input. Each reply is generated
+//! from the end of the conversation so far, as much as fits in
+//! `max_seq_len`, so earlier turns inform later ones. With `--history` the
+//! conversation is appended to a file and picked up again by the next
+//! session.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use rust_transformer::evaluate::{evaluate_generation, BleuConfig};
+use rust_transformer::optim::{Adam, AdamConfig};
+use rust_transformer::tokenizer::{Tokenizer, VocabTokenizer};
+use rust_transformer::training::{
+    cross_entropy, seq2seq_loss, CrossEntropyConfig, Trainer, TrainerConfig,
+};
+use rust_transformer::utils::json::Json;
+use rust_transformer::{Parameters, Result, Transformer, TransformerConfig};
+
+const USAGE: &str = "usage:
+  rust-transformer train --data corpus.txt [--config cfg.json] [--model model.bin]
+                         [--epochs N] [--batch-size N] [--lr X] [--seed N]
+  rust-transformer generate --model model.bin --prompt TEXT [--max-length N]
+                            [--seed N] [--greedy]
+  rust-transformer eval --model model.bin --data test.txt
+  rust-transformer chat --model model.bin [--history chat.txt] [--seed N] [--greedy]";
+
+/// The options each command accepts, flags included.
+const TRAIN_OPTIONS: &[&str] = &[
+    "data",
+    "config",
+    "model",
+    "epochs",
+    "batch-size",
+    "lr",
+    "seed",
+];
+const GENERATE_OPTIONS: &[&str] = &["model", "prompt", "max-length", "seed", "greedy"];
+const EVAL_OPTIONS: &[&str] = &["model", "data"];
+const CHAT_OPTIONS: &[&str] = &["model", "history", "seed", "greedy"];
+
+/// Ids of the special tokens at the start of every vocabulary, matching
+/// the [`TransformerConfig`] defaults.
+const PAD: usize = 0;
+const BOS: usize = 1;
+const EOS: usize = 2;
+const UNK: usize = 3;
+const NUM_SPECIAL: usize = 4;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    let result = match command.as_str() {
+        "train" => Options::parse(rest, TRAIN_OPTIONS).and_then(|options| train(&options)),
+        "generate" => Options::parse(rest, GENERATE_OPTIONS).and_then(|options| generate(&options)),
+        "eval" => Options::parse(rest, EVAL_OPTIONS).and_then(|options| eval(&options)),
+        "chat" => Options::parse(rest, CHAT_OPTIONS).and_then(|options| chat(&options)),
+        _ => Err(format!("unknown command {:?}", command).into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `--name value` options and bare `--flag`s.
+struct Options {
+    values: HashMap<String, String>,
+    flags: Vec<String>,
+}
+
+impl Options {
+    /// Options that take no value.
+    const FLAGS: &'static [&'static str] = &["greedy"];
+
+    /// Parses `args`, failing on any option not in `allowed`.
+    fn parse(args: &[String], allowed: &[&str]) -> Result<Self> {
+        let mut values = HashMap::new();
+        let mut flags = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument {:?}", arg))?;
+            if !allowed.contains(&name) {
+                return Err(format!("unknown option --{}", name).into());
+            }
+            if Self::FLAGS.contains(&name) {
+                flags.push(name.to_string());
+            } else {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("--{} needs a value", name))?;
+                values.insert(name.to_string(), value.clone());
+            }
+        }
+        Ok(Self { values, flags })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    fn require(&self, name: &str) -> Result<&str> {
+        self.get(name)
+            .ok_or_else(|| format!("missing --{}", name).into())
+    }
+
+    fn parsed<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("invalid --{} {:?}", name, value).into()),
+            None => Ok(default),
+        }
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|f| f == name)
+    }
+
+    fn model_path(&self) -> PathBuf {
+        PathBuf::from(self.get("model").unwrap_or("model.bin"))
+    }
+}
+
+fn train(options: &Options) -> Result<()> {
+    let lines = read_lines(options.require("data")?)?;
+    let pairs: Vec<(&str, &str)> = lines.iter().map(|line| split_example(line)).collect();
+    let vocabulary = build_vocabulary(&pairs);
+    let tokenizer = tokenizer(vocabulary.clone())?;
+
+    let mut config = match options.get("config") {
+        Some(path) => TransformerConfig::from_json_file(path)?,
+        None => TransformerConfig {
+            d_model: 32,
+            num_heads: 4,
+            d_ff: 64,
+            max_seq_len: 64,
+            ..TransformerConfig::default()
+        },
+    };
+    config.vocab_size = tokenizer.vocab_size();
+    config.pad_token_id = PAD;
+    config.bos_token_id = BOS;
+    config.eos_token_id = EOS;
+    let seed = options.parsed("seed", 0)?;
+    let model = Transformer::with_seed(config, seed)?;
+    println!(
+        "Training a {}-parameter model on {} examples",
+        model.num_parameters(),
+        pairs.len()
+    );
+
+    let examples = encode_examples(&tokenizer, &pairs, model.config.max_seq_len)?;
+    let trainer_config = TrainerConfig::new()
+        .with_epochs(options.parsed("epochs", 10)?)
+        .with_batch_size(options.parsed("batch-size", 16)?)
+        .with_shuffle(true, seed);
+    let optimizer = Adam::new(AdamConfig {
+        lr: options.parsed("lr", 1e-3)?,
+        ..AdamConfig::default()
+    });
+    let mut trainer = Trainer::new(model, optimizer, trainer_config);
+    let loss = CrossEntropyConfig::for_model(&trainer.model.config);
+    for epoch in 0..trainer.config.epochs {
+        let stats = trainer.train_epoch(&examples, epoch, |model, batch| {
+            seq2seq_loss(model, batch, &loss)
+        })?;
+        println!("epoch {}: loss {:.4}", epoch + 1, stats.mean_loss);
+    }
+
+    let path = options.model_path();
+    trainer.into_model().save(&path)?;
+    save_vocabulary(&vocabulary, &vocabulary_path(&path))?;
+    println!("Saved {}", path.display());
+    Ok(())
+}
+
+fn generate(options: &Options) -> Result<()> {
+    let (mut model, tokenizer) = load(&options.model_path())?;
+    if options.get("seed").is_some() {
+        model.set_seed(options.parsed("seed", 0)?);
+    }
+    let mut src = tokenizer.encode(options.require("prompt")?)?;
+    src.truncate(model.config.max_seq_len);
+    let max_length = options.parsed("max-length", model.config.max_seq_len)?;
+    let output = if options.flag("greedy") {
+        model.generate_greedy(&src, max_length)?
+    } else {
+        model.generate(&src, max_length)?
+    };
+    println!("{}", tokenizer.decode(&output)?);
+    Ok(())
+}
+
+fn eval(options: &Options) -> Result<()> {
+    let (model, tokenizer) = load(&options.model_path())?;
+    let lines = read_lines(options.require("data")?)?;
+    let pairs: Vec<(&str, &str)> = lines.iter().map(|line| split_example(line)).collect();
+    let examples = encode_examples(&tokenizer, &pairs, model.config.max_seq_len)?;
+
+    // Pad positions are neither scored nor counted as tokens.
+    let loss = CrossEntropyConfig::for_model(&model.config);
+    let mut total_loss = 0.0;
+    let mut total_tokens = 0;
+    let mut correct = 0.0;
+    for (src, tgt) in &examples {
+        let mut decoder_input = vec![model.config.bos_token_id];
+        decoder_input.extend_from_slice(&tgt[..tgt.len() - 1]);
+        let logits = model.forward(src, &decoder_input)?;
+        let scored = cross_entropy(&logits, tgt, &loss)?;
+        total_loss += scored.loss * scored.tokens as f64;
+        correct += scored.accuracy * scored.tokens as f64;
+        total_tokens += scored.tokens;
+    }
+    let mean_loss = total_loss / total_tokens.max(1) as f64;
+
+    // BLEU compares the generated and reference tokens without the end token.
+    let references: Vec<(Vec<usize>, Vec<usize>)> = examples
+        .iter()
+        .map(|(src, tgt)| (src.clone(), tgt[..tgt.len() - 1].to_vec()))
+        .collect();
+    let report = evaluate_generation(
+        &references,
+        |src| {
+            let mut output = model.generate_greedy(src, model.config.max_seq_len)?;
+            output.retain(|&t| t != model.config.bos_token_id && t != model.config.eos_token_id);
+            Ok(output)
+        },
+        BleuConfig::default(),
+    )?;
+
+    println!("examples:   {}", examples.len());
+    println!("loss:       {:.4}", mean_loss);
+    println!("perplexity: {:.4}", mean_loss.exp());
+    println!("accuracy:   {:.4}", correct / total_tokens.max(1) as f64);
+    println!("bleu:       {:.4}", report.bleu.score);
+    Ok(())
+}
+
+fn chat(options: &Options) -> Result<()> {
+    let (mut model, tokenizer) = load(&options.model_path())?;
+    if options.get("seed").is_some() {
+        model.set_seed(options.parsed("seed", 0)?);
+    }
+    let history_path = options.get("history").map(PathBuf::from);
+    let mut turns = match &history_path {
+        Some(path) if path.exists() => read_turns(path)?,
+        _ => Vec::new(),
+    };
+    if !turns.is_empty() {
+        println!("Resuming a conversation of {} turns", turns.len());
+    }
+    println!("Type a message, /reset to forget the conversation, /quit to leave.");
+
+    let stdin = std::io::stdin();
+    let mut input = String::new();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        input.clear();
+        if stdin.lock().read_line(&mut input)? == 0 {
+            break;
+        }
+        let message = input.trim();
+        match message {
+            "" => continue,
+            "/quit" => break,
+            "/reset" => {
+                turns.clear();
+                if let Some(path) = &history_path {
+                    std::fs::write(path, "")?;
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        turns.push(Turn::new("user", message));
+        let context = turns
+            .iter()
+            .map(|turn| turn.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut src = tokenizer.encode(&context)?;
+        let max_seq_len = model.config.max_seq_len;
+        src.drain(..src.len().saturating_sub(max_seq_len));
+        let output = if options.flag("greedy") {
+            model.generate_greedy(&src, max_seq_len)?
+        } else {
+            model.generate(&src, max_seq_len)?
+        };
+        let reply = tokenizer.decode(&output)?;
+        println!("{}", reply);
+        turns.push(Turn::new("model", &reply));
+        if let Some(path) = &history_path {
+            append_turns(path, &turns[turns.len() - 2..])?;
+        }
+    }
+    Ok(())
+}
+
+/// One message of a chat, stored as a `speaker<TAB>text` line.
+struct Turn {
+    speaker: String,
+    text: String,
+}
+
+impl Turn {
+    fn new(speaker: &str, text: &str) -> Self {
+        // Tabs and line breaks would split the stored line.
+        let text = text.replace(['\t', '\n', '\r'], " ");
+        Self {
+            speaker: speaker.to_string(),
+            text,
+        }
+    }
+}
+
+fn read_turns(path: &Path) -> Result<Vec<Turn>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (speaker, text) = line
+                .split_once('\t')
+                .ok_or_else(|| format!("malformed line {:?} in {}", line, path.display()))?;
+            Ok(Turn::new(speaker, text))
+        })
+        .collect()
+}
+
+fn append_turns(path: &Path, turns: &[Turn]) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    for turn in turns {
+        writeln!(file, "{}\t{}", turn.speaker, turn.text)?;
+    }
+    Ok(())
+}
+
+fn read_lines(path: &str) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)?;
+    let lines: Vec<String> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect();
+    if lines.is_empty() {
+        return Err(format!("{} holds no examples", path).into());
+    }
+    Ok(lines)
+}
+
+/// `(source, target)` of a corpus line.
+fn split_example(line: &str) -> (&str, &str) {
+    line.split_once('\t').unwrap_or((line, line))
+}
+
+/// The special tokens followed by every character of the corpus, in order.
+fn build_vocabulary(pairs: &[(&str, &str)]) -> Vec<String> {
+    let chars: BTreeSet<char> = pairs
+        .iter()
+        .flat_map(|(src, tgt)| src.chars().chain(tgt.chars()))
+        .collect();
+    let mut tokens = vec![String::new(); NUM_SPECIAL];
+    tokens.extend(chars.into_iter().map(String::from));
+    tokens
+}
+
+fn tokenizer(tokens: Vec<String>) -> Result<VocabTokenizer> {
+    Ok(VocabTokenizer::new(tokens)?
+        .with_unk_token(UNK)
+        .with_special_tokens([PAD, BOS, EOS]))
+}
+
+/// Token ids of every example, truncated to `max_seq_len` with the end
+/// token closing each target.
+fn encode_examples(
+    tokenizer: &VocabTokenizer,
+    pairs: &[(&str, &str)],
+    max_seq_len: usize,
+) -> Result<Vec<(Vec<usize>, Vec<usize>)>> {
+    pairs
+        .iter()
+        .map(|(src, tgt)| {
+            let mut src = tokenizer.encode(src)?;
+            src.truncate(max_seq_len);
+            let mut tgt = tokenizer.encode(tgt)?;
+            tgt.truncate(max_seq_len.saturating_sub(1));
+            tgt.push(EOS);
+            Ok((src, tgt))
+        })
+        .collect()
+}
+
+fn vocabulary_path(model: &Path) -> PathBuf {
+    let mut name = model.as_os_str().to_owned();
+    name.push(".vocab.json");
+    PathBuf::from(name)
+}
+
+fn save_vocabulary(tokens: &[String], path: &Path) -> Result<()> {
+    std::fs::write(path, Json::from(tokens.to_vec()).to_string())?;
+    Ok(())
+}
+
+fn load_vocabulary(path: &Path) -> Result<Vec<String>> {
+    let json = Json::parse(&std::fs::read_to_string(path)?)?;
+    json.as_array()
+        .ok_or_else(|| format!("{} is not a JSON array", path.display()))?
+        .iter()
+        .map(|t| t.as_str().map(str::to_string))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("{} holds a non-string token", path.display()).into())
+}
+
+/// The model at `path` and the tokenizer saved next to it.
+fn load(path: &Path) -> Result<(Transformer, VocabTokenizer)> {
+    let mut model = Transformer::load(path)?;
+    model.set_training(false);
+    let tokenizer = tokenizer(load_vocabulary(&vocabulary_path(path))?)?;
+    if tokenizer.vocab_size() != model.config.vocab_size {
+        return Err(format!(
+            "vocabulary of {} tokens does not match the model's {}",
+            tokenizer.vocab_size(),
+            model.config.vocab_size
+        )
+        .into());
+    }
+    Ok((model, tokenizer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn options_take_values_and_flags() {
+        let options = Options::parse(
+            &args(&["--model", "m.bin", "--greedy", "--seed", "7", "--lr", "0.5"]),
+            &["model", "greedy", "seed", "lr", "epochs", "data"],
+        )
+        .unwrap();
+        assert_eq!(options.get("model"), Some("m.bin"));
+        assert_eq!(options.model_path(), PathBuf::from("m.bin"));
+        assert!(options.flag("greedy"));
+        assert_eq!(options.parsed("seed", 0u64).unwrap(), 7);
+        assert_eq!(options.parsed("lr", 0.0f64).unwrap(), 0.5);
+        assert_eq!(options.parsed("epochs", 3usize).unwrap(), 3);
+        assert!(options.require("data").is_err());
+
+        let defaults = Options::parse(&[], CHAT_OPTIONS).unwrap();
+        assert_eq!(defaults.model_path(), PathBuf::from("model.bin"));
+        assert!(!defaults.flag("greedy"));
+    }
+
+    #[test]
+    fn options_reject_malformed_arguments() {
+        let err = |a: &[&str]| {
+            Options::parse(&args(a), TRAIN_OPTIONS)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert!(err(&["model.bin"]).contains("unexpected argument"));
+        assert!(err(&["--model"]).contains("--model needs a value"));
+        assert!(err(&["--seed", "1", "--data"]).contains("--data needs a value"));
+
+        let options = Options::parse(&args(&["--seed", "many"]), TRAIN_OPTIONS).unwrap();
+        let err = options.parsed("seed", 0u64).unwrap_err().to_string();
+        assert!(err.contains("invalid --seed"), "{}", err);
+    }
+
+    #[test]
+    fn options_outside_the_command_are_rejected() {
+        let err = |a: &[&str], allowed: &[&str]| {
+            Options::parse(&args(a), allowed)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert_eq!(
+            err(&["--epoch", "5"], TRAIN_OPTIONS).as_deref(),
+            Some("unknown option --epoch")
+        );
+        assert_eq!(
+            err(&["--lr", "0.1", "--lr-schedule", "cosine"], TRAIN_OPTIONS).as_deref(),
+            Some("unknown option --lr-schedule")
+        );
+        // Each command takes only its own options.
+        assert_eq!(
+            err(&["--greedy"], TRAIN_OPTIONS).as_deref(),
+            Some("unknown option --greedy")
+        );
+        assert_eq!(
+            err(&["--prompt", "hi"], EVAL_OPTIONS).as_deref(),
+            Some("unknown option --prompt")
+        );
+        assert_eq!(err(&["--prompt", "hi", "--greedy"], GENERATE_OPTIONS), None);
+        assert_eq!(err(&["--history", "chat.txt"], CHAT_OPTIONS), None);
+    }
+
+    #[test]
+    fn examples_split_at_the_first_tab() {
+        assert_eq!(split_example("hi\tthere"), ("hi", "there"));
+        assert_eq!(split_example("a\tb\tc"), ("a", "b\tc"));
+        assert_eq!(split_example("echo"), ("echo", "echo"));
+    }
+
+    #[test]
+    fn vocabulary_lists_special_tokens_then_sorted_characters() {
+        let vocabulary = build_vocabulary(&[("ba", "cab"), ("é", "a")]);
+        assert_eq!(vocabulary.len(), NUM_SPECIAL + 4);
+        assert!(vocabulary[..NUM_SPECIAL].iter().all(String::is_empty));
+        assert_eq!(&vocabulary[NUM_SPECIAL..], ["a", "b", "c", "é"]);
+    }
+
+    #[test]
+    fn examples_are_truncated_and_closed_with_eos() {
+        let pairs = [("abc", "cba"), ("a", "")];
+        let tokenizer = tokenizer(build_vocabulary(&pairs)).unwrap();
+        let (a, b, c) = (NUM_SPECIAL, NUM_SPECIAL + 1, NUM_SPECIAL + 2);
+
+        let encoded = encode_examples(&tokenizer, &pairs, 2).unwrap();
+        assert_eq!(encoded[0], (vec![a, b], vec![c, EOS]));
+        assert_eq!(encoded[1], (vec![a], vec![EOS]));
+
+        let encoded = encode_examples(&tokenizer, &[("a", "z")], 4).unwrap();
+        assert_eq!(encoded[0], (vec![a], vec![UNK, EOS]));
+    }
+
+    #[test]
+    fn vocabulary_round_trips_through_its_file() {
+        let pairs = [("héllo", "wörld"), ("\"quoted\"", "back\\slash")];
+        let tokens = build_vocabulary(&pairs);
+        let path = std::env::temp_dir().join(format!(
+            "rust-transformer-cli-{}.vocab.json",
+            std::process::id()
+        ));
+        save_vocabulary(&tokens, &path).unwrap();
+        let loaded = load_vocabulary(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), tokens);
+
+        let tokenizer = tokenizer(tokens).unwrap();
+        for (src, tgt) in pairs {
+            let (src_ids, tgt_ids) = encode_examples(&tokenizer, &[(src, tgt)], 64)
+                .unwrap()
+                .remove(0);
+            assert_eq!(tokenizer.decode(&src_ids).unwrap(), src);
+            assert_eq!(tokenizer.decode(&tgt_ids).unwrap(), tgt);
+        }
+        assert_eq!(
+            vocabulary_path(Path::new("out/model.bin")),
+            PathBuf::from("out/model.bin.vocab.json")
+        );
+    }
 }
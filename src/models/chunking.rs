@@ -0,0 +1,133 @@
+//! Encoding inputs longer than the model's context.
+//!
+//! [`Transformer::encode_chunked`] splits the source into overlapping
+//! windows of at most `max_seq_len` tokens, encodes every window on its own
+//! and merges the results according to [`ChunkMerge`]. Each window gets the
+//! full attention context of its neighbours within the overlap, so tokens
+//! near a chunk edge see more of their surroundings than with disjoint
+//! chunks.
+
+use super::Transformer;
+use crate::tensor::Matrix;
+use crate::{Result, TransformerError};
+
+/// How window encodings are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkMerge {
+    /// One row per source token (`src_len × d_model`); tokens covered by
+    /// several windows get the mean of their encodings.
+    #[default]
+    Concatenate,
+    /// A single `1 × d_model` row: the mean of the windows' mean-pooled
+    /// encodings.
+    Mean,
+}
+
+/// Window layout of [`Transformer::encode_chunked`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkingConfig {
+    /// Tokens per window; `None` uses the model's `max_seq_len`.
+    pub window: Option<usize>,
+    /// Tokens shared by consecutive windows. Must be below the window size.
+    pub overlap: usize,
+    pub merge: ChunkMerge,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            window: None,
+            overlap: 32,
+            merge: ChunkMerge::Concatenate,
+        }
+    }
+}
+
+impl ChunkingConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    pub fn with_merge(mut self, merge: ChunkMerge) -> Self {
+        self.merge = merge;
+        self
+    }
+
+    /// `(start, end)` token ranges covering `len` tokens. Windows advance by
+    /// `window - overlap`; the last one is aligned to the end of the input so
+    /// every window is full.
+    pub fn windows(&self, len: usize, max_seq_len: usize) -> Result<Vec<(usize, usize)>> {
+        let window = self.window.unwrap_or(max_seq_len);
+        if window == 0 || window > max_seq_len {
+            return Err(TransformerError::config(format!(
+                "chunk window {} must be between 1 and max_seq_len {}",
+                window, max_seq_len
+            )));
+        }
+        if self.overlap >= window {
+            return Err(TransformerError::config(format!(
+                "chunk overlap {} must be smaller than the window {}",
+                self.overlap, window
+            )));
+        }
+        if len <= window {
+            return Ok(vec![(0, len)]);
+        }
+        let step = window - self.overlap;
+        let mut windows = Vec::new();
+        let mut start = 0;
+        while start + window < len {
+            windows.push((start, start + window));
+            start += step;
+        }
+        windows.push((len - window, len));
+        Ok(windows)
+    }
+}
+
+impl Transformer {
+    /// Encodes a source of any length in overlapping windows; see
+    /// [`ChunkingConfig`].
+    pub fn encode_chunked(&self, src: &[usize], chunking: &ChunkingConfig) -> Result<Matrix> {
+        let windows = chunking.windows(src.len(), self.config.max_seq_len)?;
+        match chunking.merge {
+            ChunkMerge::Concatenate => {
+                let d_model = self.config.d_model;
+                let mut sum = Matrix::zeros(src.len(), d_model);
+                let mut counts = vec![0usize; src.len()];
+                for &(start, end) in &windows {
+                    let encoded = self.encode(&src[start..end])?;
+                    for (offset, i) in (start..end).enumerate() {
+                        for (acc, v) in sum.row_mut(i).iter_mut().zip(encoded.row(offset)) {
+                            *acc += v;
+                        }
+                        counts[i] += 1;
+                    }
+                }
+                for (i, &count) in counts.iter().enumerate() {
+                    for v in sum.row_mut(i) {
+                        *v /= count as f64;
+                    }
+                }
+                Ok(sum)
+            }
+            ChunkMerge::Mean => {
+                let pooled = windows
+                    .iter()
+                    .map(|&(start, end)| self.mean_pooled_encoding(&src[start..end]))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Matrix::vstack(&pooled.iter().collect::<Vec<_>>())?.column_means())
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Classification heads on top of the encoder-only model.
+//!
+//! A [`SequenceClassifier`] pools the encoder states of an input into one
+//! vector and maps it to label logits with a [`ClassificationHead`], for
+//! sentiment, intent and similar sentence-level tasks. A [`TokenClassifier`]
+//! applies the head to every encoder state instead, producing one label per
+//! token for tagging tasks such as NER and part-of-speech tagging. Both the
+//! head and the encoder are trainable: `backward` returns gradients for every
+//! weight, named as by [`Parameters`].
+
+use super::encoder::EncoderCache;
+use super::encoder_only::EncoderOnlyTransformer;
+use crate::config::TransformerConfig;
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::rng::Rng;
+use crate::utils::tensor_ops::{argmax_rows, softmax};
+use crate::{Result, TransformerError};
+
+/// A linear map `x·W + b` from hidden states to label logits.
+#[derive(Debug, Clone)]
+pub struct ClassificationHead {
+    /// `d_model × num_labels`.
+    pub weight: Matrix,
+    /// `1 × num_labels`.
+    pub bias: Matrix,
+}
+
+impl ClassificationHead {
+    /// A head with weights drawn under `config.init` and zero bias.
+    pub fn new_with_rng(
+        config: &TransformerConfig,
+        num_labels: usize,
+        rng: &mut Rng,
+    ) -> Result<Self> {
+        if num_labels == 0 {
+            return Err(TransformerError::config(
+                "a classification head needs at least one label",
+            ));
+        }
+        Ok(Self {
+            weight: config.init.weight(config.d_model, num_labels, rng),
+            bias: Matrix::zeros(1, num_labels),
+        })
+    }
+
+    pub fn num_labels(&self) -> usize {
+        self.weight.cols()
+    }
+
+    /// Logits for every row of `x` (`rows × d_model`), `rows × num_labels`.
+    pub fn forward(&self, x: &Matrix) -> Result<Matrix> {
+        x.matmul(&self.weight)?.add_row_vector(&self.bias)
+    }
+
+    /// Adds the weight gradients, named under `prefix`, to `grads` and
+    /// returns the gradient with respect to `x`.
+    pub fn backward(
+        &self,
+        x: &Matrix,
+        d_logits: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        grads.accumulate(
+            &join_name(prefix, "weight"),
+            &x.transpose().matmul(d_logits)?,
+        )?;
+        grads.accumulate(&join_name(prefix, "bias"), &d_logits.column_sums())?;
+        d_logits.matmul(&self.weight.transpose())
+    }
+}
+
+impl Parameters for ClassificationHead {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "weight"), &self.weight);
+        visitor(&join_name(prefix, "bias"), &self.bias);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "weight"), &mut self.weight);
+        visitor(&join_name(prefix, "bias"), &mut self.bias);
+    }
+}
+
+/// What [`SequenceClassifier::backward`] needs from a forward pass.
+#[derive(Debug, Clone)]
+pub struct SequenceClassifierCache {
+    pub encoder: EncoderCache,
+    /// The `seq_len × d_model` encoder states that were pooled.
+    pub hidden: Matrix,
+    /// The pooled `1 × d_model` state the head read.
+    pub pooled: Matrix,
+}
+
+/// An encoder-only model with a pooled classification head.
+#[derive(Debug, Clone)]
+pub struct SequenceClassifier {
+    /// Encoder and pooling; [`EncoderOnlyTransformer::pooling`] picks the
+    /// state the head reads.
+    pub backbone: EncoderOnlyTransformer,
+    pub head: ClassificationHead,
+}
+
+impl SequenceClassifier {
+    /// Puts a freshly initialized head for `num_labels` classes on
+    /// `backbone`, drawing its weights from the backbone's rng.
+    pub fn new(backbone: EncoderOnlyTransformer, num_labels: usize) -> Result<Self> {
+        let head = ClassificationHead::new_with_rng(
+            &backbone.config,
+            num_labels,
+            &mut backbone.rng.fork(),
+        )?;
+        Ok(Self { backbone, head })
+    }
+
+    /// Builds a randomly initialized encoder and head whose weights are
+    /// determined by `seed`.
+    pub fn with_seed(config: TransformerConfig, num_labels: usize, seed: u64) -> Result<Self> {
+        let mut rng = Rng::seed_from_u64(seed);
+        let backbone = EncoderOnlyTransformer::new_with_rng(config, &mut rng)?;
+        let head = ClassificationHead::new_with_rng(&backbone.config, num_labels, &mut rng)?;
+        Ok(Self { backbone, head })
+    }
+
+    pub fn num_labels(&self) -> usize {
+        self.head.num_labels()
+    }
+
+    /// Label logits of `tokens`, `1 × num_labels`.
+    pub fn logits(&self, tokens: &[usize]) -> Result<Matrix> {
+        self.head.forward(&self.backbone.embed(tokens)?)
+    }
+
+    /// Label probabilities of `tokens`.
+    pub fn probabilities(&self, tokens: &[usize]) -> Result<Vec<f64>> {
+        Ok(softmax(self.logits(tokens)?.row(0)))
+    }
+
+    /// The most likely label of `tokens`.
+    pub fn predict(&self, tokens: &[usize]) -> Result<usize> {
+        Ok(argmax_rows(&self.logits(tokens)?)[0])
+    }
+
+    /// Like [`logits`](Self::logits), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(
+        &self,
+        tokens: &[usize],
+    ) -> Result<(Matrix, SequenceClassifierCache)> {
+        let (hidden, encoder) = self.backbone.forward_with_cache(tokens, None)?;
+        let pooled =
+            self.backbone
+                .pooling
+                .pool(&hidden, tokens, self.backbone.config.pad_token_id)?;
+        let logits = self.head.forward(&pooled)?;
+        Ok((
+            logits,
+            SequenceClassifierCache {
+                encoder,
+                hidden,
+                pooled,
+            },
+        ))
+    }
+
+    /// Gradients of every weight given the gradient `d_logits`
+    /// (`1 × num_labels`) of the logits.
+    pub fn backward(
+        &self,
+        cache: &SequenceClassifierCache,
+        d_logits: &Matrix,
+    ) -> Result<Gradients> {
+        let mut grads = Gradients::new();
+        self.accumulate_gradients(cache, d_logits, &mut grads)?;
+        Ok(grads)
+    }
+
+    /// Like [`backward`](Self::backward), adding into `grads` so gradients
+    /// of several examples can be summed.
+    pub fn accumulate_gradients(
+        &self,
+        cache: &SequenceClassifierCache,
+        d_logits: &Matrix,
+        grads: &mut Gradients,
+    ) -> Result<()> {
+        d_logits.ensure_shape((1, self.num_labels()), "classifier logit gradient")?;
+        let d_pooled = self
+            .head
+            .backward(&cache.pooled, d_logits, "classifier", grads)?;
+        let d_hidden = self.backbone.pooling.backward(
+            &d_pooled,
+            &cache.hidden,
+            &cache.encoder.tokens,
+            self.backbone.config.pad_token_id,
+        )?;
+        self.backbone
+            .encoder
+            .backward(&cache.encoder, &d_hidden, "encoder", grads)
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.backbone.set_training(training);
+    }
+}
+
+impl Parameters for SequenceClassifier {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.backbone.visit_parameters(prefix, visitor);
+        self.head
+            .visit_parameters(&join_name(prefix, "classifier"), visitor);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        self.backbone.visit_parameters_mut(prefix, visitor);
+        self.head
+            .visit_parameters_mut(&join_name(prefix, "classifier"), visitor);
+    }
+}
+
+/// What [`TokenClassifier::backward`] needs from a forward pass.
+#[derive(Debug, Clone)]
+pub struct TokenClassifierCache {
+    pub encoder: EncoderCache,
+    /// The `seq_len × d_model` encoder states the head read.
+    pub hidden: Matrix,
+}
+
+/// An encoder-only model with a classification head on every position.
+#[derive(Debug, Clone)]
+pub struct TokenClassifier {
+    pub backbone: EncoderOnlyTransformer,
+    pub head: ClassificationHead,
+}
+
+impl TokenClassifier {
+    /// Puts a freshly initialized head for `num_labels` tags on `backbone`,
+    /// drawing its weights from the backbone's rng.
+    pub fn new(backbone: EncoderOnlyTransformer, num_labels: usize) -> Result<Self> {
+        let head = ClassificationHead::new_with_rng(
+            &backbone.config,
+            num_labels,
+            &mut backbone.rng.fork(),
+        )?;
+        Ok(Self { backbone, head })
+    }
+
+    /// Builds a randomly initialized encoder and head whose weights are
+    /// determined by `seed`.
+    pub fn with_seed(config: TransformerConfig, num_labels: usize, seed: u64) -> Result<Self> {
+        let mut rng = Rng::seed_from_u64(seed);
+        let backbone = EncoderOnlyTransformer::new_with_rng(config, &mut rng)?;
+        let head = ClassificationHead::new_with_rng(&backbone.config, num_labels, &mut rng)?;
+        Ok(Self { backbone, head })
+    }
+
+    pub fn num_labels(&self) -> usize {
+        self.head.num_labels()
+    }
+
+    /// Label logits of every token, `seq_len × num_labels`.
+    pub fn logits(&self, tokens: &[usize]) -> Result<Matrix> {
+        self.head.forward(&self.backbone.encode(tokens)?)
+    }
+
+    /// The most likely label of every token. Padding positions get a label
+    /// too; callers drop them with the input's padding mask.
+    pub fn predict(&self, tokens: &[usize]) -> Result<Vec<usize>> {
+        Ok(argmax_rows(&self.logits(tokens)?))
+    }
+
+    /// `true` at the positions a loss should count: every non-padding token.
+    pub fn loss_mask(&self, tokens: &[usize]) -> Vec<bool> {
+        let pad = self.backbone.config.pad_token_id;
+        tokens.iter().map(|&t| t != pad).collect()
+    }
+
+    /// Like [`logits`](Self::logits), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(&self, tokens: &[usize]) -> Result<(Matrix, TokenClassifierCache)> {
+        let (hidden, encoder) = self.backbone.forward_with_cache(tokens, None)?;
+        let logits = self.head.forward(&hidden)?;
+        Ok((logits, TokenClassifierCache { encoder, hidden }))
+    }
+
+    /// Gradients of every weight given the gradient `d_logits`
+    /// (`seq_len × num_labels`) of the logits.
+    pub fn backward(&self, cache: &TokenClassifierCache, d_logits: &Matrix) -> Result<Gradients> {
+        let mut grads = Gradients::new();
+        self.accumulate_gradients(cache, d_logits, &mut grads)?;
+        Ok(grads)
+    }
+
+    /// Like [`backward`](Self::backward), adding into `grads` so gradients
+    /// of several examples can be summed.
+    pub fn accumulate_gradients(
+        &self,
+        cache: &TokenClassifierCache,
+        d_logits: &Matrix,
+        grads: &mut Gradients,
+    ) -> Result<()> {
+        d_logits.ensure_shape(
+            (cache.hidden.rows(), self.num_labels()),
+            "classifier logit gradient",
+        )?;
+        let d_hidden = self
+            .head
+            .backward(&cache.hidden, d_logits, "classifier", grads)?;
+        self.backbone
+            .encoder
+            .backward(&cache.encoder, &d_hidden, "encoder", grads)
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.backbone.set_training(training);
+    }
+}
+
+impl Parameters for TokenClassifier {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.backbone.visit_parameters(prefix, visitor);
+        self.head
+            .visit_parameters(&join_name(prefix, "classifier"), visitor);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        self.backbone.visit_parameters_mut(prefix, visitor);
+        self.head
+            .visit_parameters_mut(&join_name(prefix, "classifier"), visitor);
+    }
+}
@@ -0,0 +1,768 @@
+//! Transformer decoder stack.
+
+use crate::attention::{KvCache, MultiHeadAttention, MultiHeadAttentionCache};
+use crate::config::{PositionalScheme, TransformerConfig};
+use crate::hooks::Hooks;
+use crate::layers::{
+    Dropout, Embedding, FeedForward, FeedForwardCache, Norm, NormCache, PositionalEncoding,
+};
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::mask::Mask;
+use crate::utils::rng::{thread_rng, Rng, SharedRng};
+use crate::{Result, TransformerError};
+
+/// Attention probabilities of one decoder layer, one matrix per head.
+#[derive(Debug, Clone)]
+pub struct DecoderLayerAttentions {
+    /// Masked self-attention, each `tgt_len × tgt_len`.
+    pub self_attention: Vec<Matrix>,
+    /// Attention over the encoder output, each `tgt_len × src_len`.
+    pub cross_attention: Vec<Matrix>,
+}
+
+/// One decoder block: masked self-attention, cross-attention over the encoder
+/// output and a feed-forward network, each with a residual connection and
+/// post-layer normalization.
+#[derive(Debug, Clone)]
+pub struct DecoderLayer {
+    pub self_attention: MultiHeadAttention,
+    pub cross_attention: MultiHeadAttention,
+    pub feed_forward: FeedForward,
+    pub norm1: Norm,
+    pub norm2: Norm,
+    pub norm3: Norm,
+    pub dropout: Dropout,
+}
+
+impl DecoderLayer {
+    pub fn new(config: &TransformerConfig) -> Result<Self> {
+        Self::new_with_rng(config, &mut thread_rng(), &SharedRng::from_entropy())
+    }
+
+    /// Like [`new`](Self::new), drawing the initial weights from `rng` and
+    /// dropout masks from `dropout_rng`.
+    pub fn new_with_rng(
+        config: &TransformerConfig,
+        rng: &mut Rng,
+        dropout_rng: &SharedRng,
+    ) -> Result<Self> {
+        let dropout = || Dropout::with_rng(config.dropout, dropout_rng.clone());
+        let mut self_attention =
+            MultiHeadAttention::new_with_init(config.d_model, config.num_heads, config.init, rng)?
+                .with_bias(config.attention_bias);
+        if config.positional == PositionalScheme::Alibi {
+            self_attention = self_attention.with_alibi();
+        }
+        if let Some(window) = config.attention_window {
+            self_attention = self_attention.with_window(window);
+        }
+        Ok(Self {
+            self_attention,
+            cross_attention: MultiHeadAttention::new_with_init(
+                config.d_model,
+                config.num_heads,
+                config.init,
+                rng,
+            )?
+            .with_bias(config.attention_bias),
+            feed_forward: FeedForward::new_with_init(
+                config.d_model,
+                config.d_ff,
+                config.activation,
+                dropout(),
+                config.init,
+                rng,
+            ),
+            norm1: Norm::for_config(config),
+            norm2: Norm::for_config(config),
+            norm3: Norm::for_config(config),
+            dropout: dropout(),
+        })
+    }
+
+    /// Transforms `x` (`tgt_len × d_model`) attending over `memory`
+    /// (`src_len × d_model`). `tgt_mask` is `tgt_len × tgt_len` and
+    /// `memory_mask` is `tgt_len × src_len`.
+    pub fn forward(
+        &self,
+        x: &Matrix,
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        Ok(self
+            .forward_with_attentions(x, memory, tgt_mask, memory_mask)?
+            .0)
+    }
+
+    /// Like [`forward`](Self::forward), also returning the per-head self- and
+    /// cross-attention probabilities.
+    pub fn forward_with_attentions(
+        &self,
+        x: &Matrix,
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<(Matrix, DecoderLayerAttentions)> {
+        self.forward_hooked(x, memory, tgt_mask, memory_mask, &Hooks::new(), 0)
+    }
+
+    /// Forward pass firing `hooks` at the points named `layers.{index}.*`.
+    pub(crate) fn forward_hooked(
+        &self,
+        x: &Matrix,
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+        hooks: &Hooks,
+        index: usize,
+    ) -> Result<(Matrix, DecoderLayerAttentions)> {
+        let name = |part: &str| join_name(&format!("layers.{}", index), part);
+        let (mut attended, self_attention) = self
+            .self_attention
+            .forward_with_attentions(x, x, x, tgt_mask)?;
+        hooks.fire(|| name("self_attention"), x, &mut attended);
+        let residual = x.add(&self.dropout.forward(&attended))?;
+        let mut h = self.norm1.forward(&residual)?;
+        hooks.fire(|| name("norm1"), &residual, &mut h);
+        let (mut crossed, cross_attention) =
+            self.cross_attention
+                .forward_with_attentions(&h, memory, memory, memory_mask)?;
+        hooks.fire(|| name("cross_attention"), &h, &mut crossed);
+        let residual = h.add(&self.dropout.forward(&crossed))?;
+        let mut h = self.norm2.forward(&residual)?;
+        hooks.fire(|| name("norm2"), &residual, &mut h);
+        let mut ff = self.feed_forward.forward(&h)?;
+        hooks.fire(|| name("feed_forward"), &h, &mut ff);
+        let residual = h.add(&self.dropout.forward(&ff))?;
+        let mut out = self.norm3.forward(&residual)?;
+        hooks.fire(|| name("norm3"), &residual, &mut out);
+        hooks.fire(|| name(""), x, &mut out);
+        Ok((
+            out,
+            DecoderLayerAttentions {
+                self_attention,
+                cross_attention,
+            },
+        ))
+    }
+
+    /// Projects `memory` for cross-attention and starts an empty
+    /// self-attention cache.
+    pub fn start_kv_cache(&self, memory: &Matrix) -> Result<DecoderLayerKvCache> {
+        Ok(DecoderLayerKvCache {
+            self_attention: KvCache::new(self.self_attention.d_model),
+            cross_attention: self.cross_attention.project_kv(memory, memory)?,
+        })
+    }
+
+    /// Transforms `x`, the rows following the positions already in `cache`,
+    /// and adds them to the cache. `tgt_mask` is `x.rows()` × all positions
+    /// including the new ones and `memory_mask` is `x.rows() × src_len`.
+    /// Matches the last rows of [`forward`](Self::forward) on the whole
+    /// sequence. Hooks are not fired.
+    pub fn forward_incremental(
+        &self,
+        x: &Matrix,
+        cache: &mut DecoderLayerKvCache,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        Ok(self.incremental(x, cache, tgt_mask, memory_mask, false)?.0)
+    }
+
+    /// Like [`forward_incremental`](Self::forward_incremental), also
+    /// returning the attention probabilities of the new rows: self-attention
+    /// over every cached position and cross-attention over the memory.
+    pub fn forward_incremental_with_attentions(
+        &self,
+        x: &Matrix,
+        cache: &mut DecoderLayerKvCache,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<(Matrix, DecoderLayerAttentions)> {
+        self.incremental(x, cache, tgt_mask, memory_mask, true)
+    }
+
+    fn incremental(
+        &self,
+        x: &Matrix,
+        cache: &mut DecoderLayerKvCache,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+        weights: bool,
+    ) -> Result<(Matrix, DecoderLayerAttentions)> {
+        let attend = |attention: &MultiHeadAttention, x: &Matrix, kv: &KvCache, mask| {
+            if weights {
+                attention.forward_cached_with_attentions(x, kv, mask)
+            } else {
+                Ok((attention.forward_cached(x, kv, mask)?, Vec::new()))
+            }
+        };
+        self.self_attention
+            .extend_cache(&mut cache.self_attention, x, x)?;
+        let (attended, self_attention) =
+            attend(&self.self_attention, x, &cache.self_attention, tgt_mask)?;
+        let h = self
+            .norm1
+            .forward(&x.add(&self.dropout.forward(&attended))?)?;
+        let (crossed, cross_attention) = attend(
+            &self.cross_attention,
+            &h,
+            &cache.cross_attention,
+            memory_mask,
+        )?;
+        let h = self
+            .norm2
+            .forward(&h.add(&self.dropout.forward(&crossed))?)?;
+        let ff = self.feed_forward.forward(&h)?;
+        let out = self.norm3.forward(&h.add(&self.dropout.forward(&ff))?)?;
+        Ok((
+            out,
+            DecoderLayerAttentions {
+                self_attention,
+                cross_attention,
+            },
+        ))
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs. Hooks are not fired.
+    pub fn forward_with_cache(
+        &self,
+        x: &Matrix,
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<(Matrix, DecoderLayerCache)> {
+        let (attended, self_attention) =
+            self.self_attention.forward_with_cache(x, x, x, tgt_mask)?;
+        let (attended, self_attention_dropout) = self.dropout.forward_with_mask(&attended)?;
+        let (h, norm1) = self.norm1.forward_with_cache(&x.add(&attended)?)?;
+        let (crossed, cross_attention) =
+            self.cross_attention
+                .forward_with_cache(&h, memory, memory, memory_mask)?;
+        let (crossed, cross_attention_dropout) = self.dropout.forward_with_mask(&crossed)?;
+        let (h, norm2) = self.norm2.forward_with_cache(&h.add(&crossed)?)?;
+        let (ff, feed_forward) = self.feed_forward.forward_with_cache(&h)?;
+        let (ff, feed_forward_dropout) = self.dropout.forward_with_mask(&ff)?;
+        let (out, norm3) = self.norm3.forward_with_cache(&h.add(&ff)?)?;
+        Ok((
+            out,
+            DecoderLayerCache {
+                self_attention,
+                self_attention_dropout,
+                norm1,
+                cross_attention,
+                cross_attention_dropout,
+                norm2,
+                feed_forward,
+                feed_forward_dropout,
+                norm3,
+            },
+        ))
+    }
+
+    /// Adds the gradients of every weight, named under `prefix`, to `grads`
+    /// and returns the gradients with respect to the layer input and to
+    /// `memory`.
+    pub fn backward(
+        &self,
+        cache: &DecoderLayerCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<(Matrix, Matrix)> {
+        let d_residual =
+            self.norm3
+                .backward(&cache.norm3, grad, &join_name(prefix, "norm3"), grads)?;
+        let d_ff = Dropout::backward(cache.feed_forward_dropout.as_ref(), &d_residual)?;
+        let d_h = d_residual.add(&self.feed_forward.backward(
+            &cache.feed_forward,
+            &d_ff,
+            &join_name(prefix, "feed_forward"),
+            grads,
+        )?)?;
+        let d_residual =
+            self.norm2
+                .backward(&cache.norm2, &d_h, &join_name(prefix, "norm2"), grads)?;
+        let d_crossed = Dropout::backward(cache.cross_attention_dropout.as_ref(), &d_residual)?;
+        let (d_q, d_k, d_v) = self.cross_attention.backward(
+            &cache.cross_attention,
+            &d_crossed,
+            &join_name(prefix, "cross_attention"),
+            grads,
+        )?;
+        let d_memory = d_k.add(&d_v)?;
+        let d_h = d_residual.add(&d_q)?;
+        let d_residual =
+            self.norm1
+                .backward(&cache.norm1, &d_h, &join_name(prefix, "norm1"), grads)?;
+        let d_attended = Dropout::backward(cache.self_attention_dropout.as_ref(), &d_residual)?;
+        let (d_q, d_k, d_v) = self.self_attention.backward(
+            &cache.self_attention,
+            &d_attended,
+            &join_name(prefix, "self_attention"),
+            grads,
+        )?;
+        let d_x = d_residual.add(&d_q)?.add(&d_k)?.add(&d_v)?;
+        Ok((d_x, d_memory))
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.dropout.training = training;
+        self.feed_forward.dropout.training = training;
+    }
+
+    /// Makes every dropout layer draw from `rng`.
+    pub fn set_rng(&mut self, rng: &SharedRng) {
+        self.dropout.rng = rng.clone();
+        self.feed_forward.dropout.rng = rng.clone();
+    }
+}
+
+impl Parameters for DecoderLayer {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.self_attention
+            .visit_parameters(&join_name(prefix, "self_attention"), visitor);
+        self.cross_attention
+            .visit_parameters(&join_name(prefix, "cross_attention"), visitor);
+        self.feed_forward
+            .visit_parameters(&join_name(prefix, "feed_forward"), visitor);
+        self.norm1
+            .visit_parameters(&join_name(prefix, "norm1"), visitor);
+        self.norm2
+            .visit_parameters(&join_name(prefix, "norm2"), visitor);
+        self.norm3
+            .visit_parameters(&join_name(prefix, "norm3"), visitor);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        self.self_attention
+            .visit_parameters_mut(&join_name(prefix, "self_attention"), visitor);
+        self.cross_attention
+            .visit_parameters_mut(&join_name(prefix, "cross_attention"), visitor);
+        self.feed_forward
+            .visit_parameters_mut(&join_name(prefix, "feed_forward"), visitor);
+        self.norm1
+            .visit_parameters_mut(&join_name(prefix, "norm1"), visitor);
+        self.norm2
+            .visit_parameters_mut(&join_name(prefix, "norm2"), visitor);
+        self.norm3
+            .visit_parameters_mut(&join_name(prefix, "norm3"), visitor);
+    }
+}
+
+/// Intermediate values of [`DecoderLayer::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct DecoderLayerCache {
+    pub self_attention: MultiHeadAttentionCache,
+    pub self_attention_dropout: Option<Matrix>,
+    pub norm1: NormCache,
+    pub cross_attention: MultiHeadAttentionCache,
+    pub cross_attention_dropout: Option<Matrix>,
+    pub norm2: NormCache,
+    pub feed_forward: FeedForwardCache,
+    pub feed_forward_dropout: Option<Matrix>,
+    pub norm3: NormCache,
+}
+
+/// Projected keys and values a [`DecoderLayer`] keeps between the steps of
+/// incremental decoding.
+#[derive(Debug, Clone)]
+pub struct DecoderLayerKvCache {
+    /// Self-attention over the positions decoded so far; grows every step.
+    pub self_attention: KvCache,
+    /// Cross-attention over the encoder output, projected once.
+    pub cross_attention: KvCache,
+}
+
+/// Key/value caches of every layer of a [`Decoder`], from
+/// [`Decoder::start_kv_cache`].
+#[derive(Debug, Clone)]
+pub struct DecoderKvCache {
+    pub layers: Vec<DecoderLayerKvCache>,
+    len: usize,
+}
+
+impl DecoderKvCache {
+    /// Target positions decoded so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Intermediate values of [`Decoder::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct DecoderCache {
+    pub tokens: Vec<usize>,
+    pub embedding_dropout: Option<Matrix>,
+    pub layers: Vec<DecoderLayerCache>,
+    /// Final hidden states, the input of the output projection.
+    pub hidden: Matrix,
+    /// Rows of the memory attended over.
+    pub memory_len: usize,
+}
+
+/// Token embedding, positional encoding, a stack of [`DecoderLayer`]s and the
+/// projection onto vocabulary logits.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    /// Token embedding table, `vocab_size × d_model`, scaled by `√d_model`
+    /// unless [`scale_embeddings`](TransformerConfig::scale_embeddings) is off.
+    pub embedding: Embedding,
+    pub positional: PositionalEncoding,
+    pub layers: Vec<DecoderLayer>,
+    /// Projection from hidden states to logits, `d_model × vocab_size`.
+    pub output_projection: Matrix,
+    /// Optional `1 × vocab_size` bias added to the logits.
+    pub output_bias: Option<Matrix>,
+    pub dropout: Dropout,
+    /// Forward hooks, addressed relative to the decoder (`layers.0.feed_forward`).
+    pub hooks: Hooks,
+    d_model: usize,
+}
+
+impl Decoder {
+    pub fn new(config: &TransformerConfig) -> Result<Self> {
+        Self::new_with_rng(config, &mut thread_rng(), &SharedRng::from_entropy())
+    }
+
+    /// Like [`new`](Self::new), drawing the initial weights from `rng` and
+    /// dropout masks from `dropout_rng`.
+    pub fn new_with_rng(
+        config: &TransformerConfig,
+        rng: &mut Rng,
+        dropout_rng: &SharedRng,
+    ) -> Result<Self> {
+        let embedding = Embedding::from_weight(config.init.embedding(
+            config.vocab_size,
+            config.d_model,
+            rng,
+        ))
+        .with_scale(if config.scale_embeddings {
+            (config.d_model as f64).sqrt()
+        } else {
+            1.0
+        });
+        let layers = (0..config.num_decoder_layers)
+            .map(|_| DecoderLayer::new_with_rng(config, rng, dropout_rng))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            embedding,
+            positional: PositionalEncoding::for_config(config, rng),
+            layers,
+            output_projection: config.init.weight(config.d_model, config.vocab_size, rng),
+            output_bias: config
+                .output_bias
+                .then(|| Matrix::zeros(1, config.vocab_size)),
+            dropout: Dropout::with_rng(config.dropout, dropout_rng.clone()),
+            hooks: Hooks::new(),
+            d_model: config.d_model,
+        })
+    }
+
+    pub fn d_model(&self) -> usize {
+        self.d_model
+    }
+
+    pub fn vocab_size(&self) -> usize {
+        self.embedding.vocab_size()
+    }
+
+    /// Embeds `tokens`, scales by `√d_model` and adds positional encodings.
+    pub fn embed(&self, tokens: &[usize]) -> Result<Matrix> {
+        let x = self.positional.forward(&self.embedding.forward(tokens)?)?;
+        Ok(self.dropout.forward(&x))
+    }
+
+    /// Runs the decoder stack and returns final hidden states
+    /// (`tgt_len × d_model`).
+    pub fn forward_hidden(
+        &self,
+        tokens: &[usize],
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        let mut x = self.embed(tokens)?;
+        for (i, layer) in self.layers.iter().enumerate() {
+            x = layer
+                .forward_hooked(&x, memory, tgt_mask, memory_mask, &self.hooks, i)?
+                .0;
+        }
+        Ok(x)
+    }
+
+    /// Runs the decoder and returns vocabulary logits (`tgt_len × vocab_size`).
+    pub fn forward(
+        &self,
+        tokens: &[usize],
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        self.project(&self.forward_hidden(tokens, memory, tgt_mask, memory_mask)?)
+    }
+
+    /// Like [`forward`](Self::forward), also returning every layer's
+    /// attention probabilities.
+    pub fn forward_with_attentions(
+        &self,
+        tokens: &[usize],
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<DecoderLayerAttentions>)> {
+        let mut x = self.embed(tokens)?;
+        let mut attentions = Vec::with_capacity(self.layers.len());
+        for (i, layer) in self.layers.iter().enumerate() {
+            let (out, layer_attentions) =
+                layer.forward_hooked(&x, memory, tgt_mask, memory_mask, &self.hooks, i)?;
+            x = out;
+            attentions.push(layer_attentions);
+        }
+        Ok((self.project(&x)?, attentions))
+    }
+
+    /// Like [`forward`](Self::forward), also returning the hidden states
+    /// entering and leaving every layer: index 0 is the embedding output and
+    /// index `i + 1` the output of layer `i` (before the output projection).
+    pub fn forward_with_hidden_states(
+        &self,
+        tokens: &[usize],
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        let mut x = self.embed(tokens)?;
+        let mut hidden_states = Vec::with_capacity(self.layers.len() + 1);
+        for (i, layer) in self.layers.iter().enumerate() {
+            let out = layer
+                .forward_hooked(&x, memory, tgt_mask, memory_mask, &self.hooks, i)?
+                .0;
+            hidden_states.push(x);
+            x = out;
+        }
+        let logits = self.project(&x)?;
+        hidden_states.push(x);
+        Ok((logits, hidden_states))
+    }
+
+    /// Starts incremental decoding against `memory`
+    /// (`src_len × d_model`), projecting it once for every layer's
+    /// cross-attention.
+    pub fn start_kv_cache(&self, memory: &Matrix) -> Result<DecoderKvCache> {
+        Ok(DecoderKvCache {
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| layer.start_kv_cache(memory))
+                .collect::<Result<_>>()?,
+            len: 0,
+        })
+    }
+
+    /// Decodes `tokens`, the positions following those already in `cache`,
+    /// and returns their logits (`tokens.len() × vocab_size`). Only the new
+    /// positions are embedded and projected; earlier ones are read from the
+    /// cache, which is extended. `tgt_mask` is `tokens.len()` × all
+    /// positions and `memory_mask` is `tokens.len() × src_len`. Hooks are
+    /// not fired.
+    pub fn forward_incremental(
+        &self,
+        tokens: &[usize],
+        cache: &mut DecoderKvCache,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        Ok(self
+            .incremental(tokens, cache, tgt_mask, memory_mask, false)?
+            .0)
+    }
+
+    /// Like [`forward_incremental`](Self::forward_incremental), also
+    /// returning every layer's attention probabilities for the new
+    /// positions.
+    pub fn forward_incremental_with_attentions(
+        &self,
+        tokens: &[usize],
+        cache: &mut DecoderKvCache,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<DecoderLayerAttentions>)> {
+        self.incremental(tokens, cache, tgt_mask, memory_mask, true)
+    }
+
+    pub(crate) fn incremental(
+        &self,
+        tokens: &[usize],
+        cache: &mut DecoderKvCache,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+        weights: bool,
+    ) -> Result<(Matrix, Vec<DecoderLayerAttentions>)> {
+        if cache.layers.len() != self.layers.len() {
+            return Err(TransformerError::shape(format!(
+                "key/value cache has {} layers, decoder has {}",
+                cache.layers.len(),
+                self.layers.len()
+            )));
+        }
+        let x = self
+            .positional
+            .forward_at(&self.embedding.forward(tokens)?, cache.len())?;
+        let mut x = self.dropout.forward(&x);
+        let mut attentions = Vec::with_capacity(if weights { self.layers.len() } else { 0 });
+        for (layer, layer_cache) in self.layers.iter().zip(&mut cache.layers) {
+            let (out, layer_attentions) =
+                layer.incremental(&x, layer_cache, tgt_mask, memory_mask, weights)?;
+            x = out;
+            if weights {
+                attentions.push(layer_attentions);
+            }
+        }
+        cache.len += tokens.len();
+        Ok((self.output_logits(&x)?, attentions))
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs. Hooks are not fired.
+    pub fn forward_with_cache(
+        &self,
+        tokens: &[usize],
+        memory: &Matrix,
+        tgt_mask: Option<&Mask>,
+        memory_mask: Option<&Mask>,
+    ) -> Result<(Matrix, DecoderCache)> {
+        let x = self.positional.forward(&self.embedding.forward(tokens)?)?;
+        let (mut x, embedding_dropout) = self.dropout.forward_with_mask(&x)?;
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            let (out, cache) = layer.forward_with_cache(&x, memory, tgt_mask, memory_mask)?;
+            x = out;
+            layers.push(cache);
+        }
+        let logits = self.output_logits(&x)?;
+        Ok((
+            logits,
+            DecoderCache {
+                tokens: tokens.to_vec(),
+                embedding_dropout,
+                layers,
+                hidden: x,
+                memory_len: memory.rows(),
+            },
+        ))
+    }
+
+    /// Backpropagates `grad`, the gradient of the logits, adding the
+    /// gradient of every weight (named under `prefix`) to `grads`. Returns
+    /// the gradient with respect to `memory`, summed over all layers.
+    pub fn backward(
+        &self,
+        cache: &DecoderCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        grads.accumulate(
+            &join_name(prefix, "output_projection"),
+            &cache.hidden.transpose().matmul(grad)?,
+        )?;
+        if self.output_bias.is_some() {
+            grads.accumulate(&join_name(prefix, "output_bias"), &grad.column_sums())?;
+        }
+        let mut grad = grad.matmul(&self.output_projection.transpose())?;
+        let mut d_memory = Matrix::zeros(cache.memory_len, self.d_model);
+        for (i, (layer, layer_cache)) in self.layers.iter().zip(&cache.layers).enumerate().rev() {
+            let name = join_name(prefix, &format!("layers.{}", i));
+            let (d_x, d_mem) = layer.backward(layer_cache, &grad, &name, grads)?;
+            grad = d_x;
+            d_memory.add_assign(&d_mem)?;
+        }
+        let grad = Dropout::backward(cache.embedding_dropout.as_ref(), &grad)?;
+        self.positional
+            .backward(&grad, &join_name(prefix, "positional"), grads)?;
+        self.embedding
+            .backward(&cache.tokens, &grad, &join_name(prefix, "embedding"), grads)?;
+        Ok(d_memory)
+    }
+
+    /// Projects hidden states (`len × d_model`) onto vocabulary logits.
+    pub fn output_logits(&self, hidden: &Matrix) -> Result<Matrix> {
+        let logits = hidden.matmul(&self.output_projection)?;
+        match &self.output_bias {
+            Some(bias) => logits.add_row_vector(bias),
+            None => Ok(logits),
+        }
+    }
+
+    /// [`output_logits`](Self::output_logits), firing the
+    /// `output_projection` hook.
+    fn project(&self, hidden: &Matrix) -> Result<Matrix> {
+        let mut logits = self.output_logits(hidden)?;
+        self.hooks
+            .fire(|| "output_projection".to_string(), hidden, &mut logits);
+        Ok(logits)
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.dropout.training = training;
+        for layer in &mut self.layers {
+            layer.set_training(training);
+        }
+    }
+
+    /// Makes every dropout layer draw from `rng`.
+    pub fn set_rng(&mut self, rng: &SharedRng) {
+        self.dropout.rng = rng.clone();
+        for layer in &mut self.layers {
+            layer.set_rng(rng);
+        }
+    }
+}
+
+impl Parameters for Decoder {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "embedding"), &self.embedding.weight);
+        if self.positional.is_learned() {
+            visitor(&join_name(prefix, "positional"), self.positional.table());
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.visit_parameters(&join_name(prefix, &format!("layers.{}", i)), visitor);
+        }
+        visitor(
+            &join_name(prefix, "output_projection"),
+            &self.output_projection,
+        );
+        if let Some(bias) = &self.output_bias {
+            visitor(&join_name(prefix, "output_bias"), bias);
+        }
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "embedding"), self.embedding.weight_mut());
+        if self.positional.is_learned() {
+            visitor(
+                &join_name(prefix, "positional"),
+                self.positional.table_mut(),
+            );
+        }
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            layer.visit_parameters_mut(&join_name(prefix, &format!("layers.{}", i)), visitor);
+        }
+        visitor(
+            &join_name(prefix, "output_projection"),
+            &mut self.output_projection,
+        );
+        if let Some(bias) = &mut self.output_bias {
+            visitor(&join_name(prefix, "output_bias"), bias);
+        }
+    }
+}
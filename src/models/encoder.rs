@@ -0,0 +1,589 @@
+//! Transformer encoder stack.
+
+use crate::attention::{MultiHeadAttention, MultiHeadAttentionCache};
+use crate::config::{PositionalScheme, TransformerConfig};
+use crate::hooks::Hooks;
+use crate::layers::{
+    Dropout, Embedding, FeedForward, FeedForwardCache, LayerNorm, LayerNormCache, Norm, NormCache,
+    PositionalEncoding,
+};
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::mask::{AttentionMask, Mask};
+use crate::utils::rng::{thread_rng, Rng, SharedRng};
+use crate::utils::tensor_ops::log_softmax;
+use crate::{Result, TransformerError};
+
+/// One encoder block: self-attention and a feed-forward network, each wrapped
+/// in a residual connection followed by layer normalization (post-norm).
+#[derive(Debug, Clone)]
+pub struct EncoderLayer {
+    pub self_attention: MultiHeadAttention,
+    pub feed_forward: FeedForward,
+    pub norm1: Norm,
+    pub norm2: Norm,
+    pub dropout: Dropout,
+}
+
+impl EncoderLayer {
+    pub fn new(config: &TransformerConfig) -> Result<Self> {
+        Self::new_with_rng(config, &mut thread_rng(), &SharedRng::from_entropy())
+    }
+
+    /// Like [`new`](Self::new), drawing the initial weights from `rng` and
+    /// dropout masks from `dropout_rng`.
+    pub fn new_with_rng(
+        config: &TransformerConfig,
+        rng: &mut Rng,
+        dropout_rng: &SharedRng,
+    ) -> Result<Self> {
+        let dropout = || Dropout::with_rng(config.dropout, dropout_rng.clone());
+        let mut self_attention =
+            MultiHeadAttention::new_with_init(config.d_model, config.num_heads, config.init, rng)?
+                .with_bias(config.attention_bias);
+        if config.positional == PositionalScheme::Alibi {
+            self_attention = self_attention.with_alibi();
+        }
+        if let Some(window) = config.attention_window {
+            self_attention = self_attention.with_window(window);
+        }
+        Ok(Self {
+            self_attention,
+            feed_forward: FeedForward::new_with_init(
+                config.d_model,
+                config.d_ff,
+                config.activation,
+                dropout(),
+                config.init,
+                rng,
+            ),
+            norm1: Norm::for_config(config),
+            norm2: Norm::for_config(config),
+            dropout: dropout(),
+        })
+    }
+
+    /// Transforms `x` (`seq_len × d_model`); `mask` is `seq_len × seq_len`.
+    pub fn forward(&self, x: &Matrix, mask: Option<&Mask>) -> Result<Matrix> {
+        Ok(self.forward_with_attentions(x, mask)?.0)
+    }
+
+    /// Like [`forward`](Self::forward), also returning the per-head
+    /// self-attention probabilities.
+    pub fn forward_with_attentions(
+        &self,
+        x: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        self.forward_hooked(x, mask, &Hooks::new(), 0)
+    }
+
+    /// Forward pass firing `hooks` at the points named `layers.{index}.*`.
+    pub(crate) fn forward_hooked(
+        &self,
+        x: &Matrix,
+        mask: Option<&Mask>,
+        hooks: &Hooks,
+        index: usize,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        let name = |part: &str| join_name(&format!("layers.{}", index), part);
+        let (mut attended, attentions) =
+            self.self_attention.forward_with_attentions(x, x, x, mask)?;
+        hooks.fire(|| name("self_attention"), x, &mut attended);
+        let residual = x.add(&self.dropout.forward(&attended))?;
+        let mut h = self.norm1.forward(&residual)?;
+        hooks.fire(|| name("norm1"), &residual, &mut h);
+        let mut ff = self.feed_forward.forward(&h)?;
+        hooks.fire(|| name("feed_forward"), &h, &mut ff);
+        let residual = h.add(&self.dropout.forward(&ff))?;
+        let mut out = self.norm2.forward(&residual)?;
+        hooks.fire(|| name("norm2"), &residual, &mut out);
+        hooks.fire(|| name(""), x, &mut out);
+        Ok((out, attentions))
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs. Hooks are not fired.
+    pub fn forward_with_cache(
+        &self,
+        x: &Matrix,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, EncoderLayerCache)> {
+        let (attended, attention) = self.self_attention.forward_with_cache(x, x, x, mask)?;
+        let (attended, attention_dropout) = self.dropout.forward_with_mask(&attended)?;
+        let (h, norm1) = self.norm1.forward_with_cache(&x.add(&attended)?)?;
+        let (ff, feed_forward) = self.feed_forward.forward_with_cache(&h)?;
+        let (ff, feed_forward_dropout) = self.dropout.forward_with_mask(&ff)?;
+        let (out, norm2) = self.norm2.forward_with_cache(&h.add(&ff)?)?;
+        Ok((
+            out,
+            EncoderLayerCache {
+                attention,
+                attention_dropout,
+                norm1,
+                feed_forward,
+                feed_forward_dropout,
+                norm2,
+            },
+        ))
+    }
+
+    /// Adds the gradients of every weight, named under `prefix`, to `grads`
+    /// and returns the gradient with respect to the layer input.
+    pub fn backward(
+        &self,
+        cache: &EncoderLayerCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<Matrix> {
+        let d_residual =
+            self.norm2
+                .backward(&cache.norm2, grad, &join_name(prefix, "norm2"), grads)?;
+        let d_ff = Dropout::backward(cache.feed_forward_dropout.as_ref(), &d_residual)?;
+        let d_h = d_residual.add(&self.feed_forward.backward(
+            &cache.feed_forward,
+            &d_ff,
+            &join_name(prefix, "feed_forward"),
+            grads,
+        )?)?;
+        let d_residual =
+            self.norm1
+                .backward(&cache.norm1, &d_h, &join_name(prefix, "norm1"), grads)?;
+        let d_attended = Dropout::backward(cache.attention_dropout.as_ref(), &d_residual)?;
+        let (d_q, d_k, d_v) = self.self_attention.backward(
+            &cache.attention,
+            &d_attended,
+            &join_name(prefix, "self_attention"),
+            grads,
+        )?;
+        d_residual.add(&d_q)?.add(&d_k)?.add(&d_v)
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.dropout.training = training;
+        self.feed_forward.dropout.training = training;
+    }
+
+    /// Makes every dropout layer draw from `rng`.
+    pub fn set_rng(&mut self, rng: &SharedRng) {
+        self.dropout.rng = rng.clone();
+        self.feed_forward.dropout.rng = rng.clone();
+    }
+}
+
+impl Parameters for EncoderLayer {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.self_attention
+            .visit_parameters(&join_name(prefix, "self_attention"), visitor);
+        self.feed_forward
+            .visit_parameters(&join_name(prefix, "feed_forward"), visitor);
+        self.norm1
+            .visit_parameters(&join_name(prefix, "norm1"), visitor);
+        self.norm2
+            .visit_parameters(&join_name(prefix, "norm2"), visitor);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        self.self_attention
+            .visit_parameters_mut(&join_name(prefix, "self_attention"), visitor);
+        self.feed_forward
+            .visit_parameters_mut(&join_name(prefix, "feed_forward"), visitor);
+        self.norm1
+            .visit_parameters_mut(&join_name(prefix, "norm1"), visitor);
+        self.norm2
+            .visit_parameters_mut(&join_name(prefix, "norm2"), visitor);
+    }
+}
+
+/// Intermediate values of [`EncoderLayer::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct EncoderLayerCache {
+    pub attention: MultiHeadAttentionCache,
+    pub attention_dropout: Option<Matrix>,
+    pub norm1: NormCache,
+    pub feed_forward: FeedForwardCache,
+    pub feed_forward_dropout: Option<Matrix>,
+    pub norm2: NormCache,
+}
+
+/// Intermediate values of [`Encoder::forward_with_cache`].
+#[derive(Debug, Clone)]
+pub struct EncoderCache {
+    pub tokens: Vec<usize>,
+    /// Segment of every token; empty without token-type embeddings.
+    pub token_types: Vec<usize>,
+    pub embedding_norm: Option<LayerNormCache>,
+    pub embedding_dropout: Option<Matrix>,
+    pub layers: Vec<EncoderLayerCache>,
+}
+
+/// Token embedding, positional encoding and a stack of [`EncoderLayer`]s.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    /// Token embedding table, `vocab_size × d_model`, scaled by `√d_model`
+    /// unless [`scale_embeddings`](TransformerConfig::scale_embeddings) is off.
+    pub embedding: Embedding,
+    pub positional: PositionalEncoding,
+    /// Segment embeddings added to the token embeddings,
+    /// `num_token_types × d_model`, as in BERT; `None` in the original
+    /// architecture.
+    pub token_type_embedding: Option<Embedding>,
+    /// Normalization of the summed embeddings before the first layer, as in
+    /// BERT; `None` in the original architecture.
+    pub embedding_norm: Option<LayerNorm>,
+    pub layers: Vec<EncoderLayer>,
+    pub dropout: Dropout,
+    /// Forward hooks, addressed relative to the encoder (`layers.0.feed_forward`).
+    pub hooks: Hooks,
+    d_model: usize,
+}
+
+impl Encoder {
+    pub fn new(config: &TransformerConfig) -> Result<Self> {
+        Self::new_with_rng(config, &mut thread_rng(), &SharedRng::from_entropy())
+    }
+
+    /// Like [`new`](Self::new), drawing the initial weights from `rng` and
+    /// dropout masks from `dropout_rng`.
+    pub fn new_with_rng(
+        config: &TransformerConfig,
+        rng: &mut Rng,
+        dropout_rng: &SharedRng,
+    ) -> Result<Self> {
+        let embedding = Embedding::from_weight(config.init.embedding(
+            config.vocab_size,
+            config.d_model,
+            rng,
+        ))
+        .with_scale(if config.scale_embeddings {
+            (config.d_model as f64).sqrt()
+        } else {
+            1.0
+        });
+        let layers = (0..config.num_encoder_layers)
+            .map(|_| EncoderLayer::new_with_rng(config, rng, dropout_rng))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            embedding,
+            positional: PositionalEncoding::for_config(config, rng),
+            token_type_embedding: None,
+            embedding_norm: None,
+            layers,
+            dropout: Dropout::with_rng(config.dropout, dropout_rng.clone()),
+            hooks: Hooks::new(),
+            d_model: config.d_model,
+        })
+    }
+
+    pub fn d_model(&self) -> usize {
+        self.d_model
+    }
+
+    pub fn vocab_size(&self) -> usize {
+        self.embedding.vocab_size()
+    }
+
+    /// Embeds `tokens`, scales by `√d_model`, adds positional encodings and
+    /// applies the [`embedding_norm`](Self::embedding_norm) if there is one.
+    /// With token-type embeddings every token is in segment 0.
+    pub fn embed(&self, tokens: &[usize]) -> Result<Matrix> {
+        self.embed_with_token_types(tokens, None)
+    }
+
+    /// Like [`embed`](Self::embed), adding the embedding of each token's
+    /// segment in `token_types` (segment 0 when `None`). Token types are
+    /// ignored without a [`token_type_embedding`](Self::token_type_embedding).
+    pub fn embed_with_token_types(
+        &self,
+        tokens: &[usize],
+        token_types: Option<&[usize]>,
+    ) -> Result<Matrix> {
+        let x = self.sum_embeddings(tokens, &self.resolve_token_types(tokens, token_types)?)?;
+        let x = match &self.embedding_norm {
+            Some(norm) => norm.forward(&x)?,
+            None => x,
+        };
+        Ok(self.dropout.forward(&x))
+    }
+
+    /// Token, position and segment embeddings before normalization.
+    fn sum_embeddings(&self, tokens: &[usize], token_types: &[usize]) -> Result<Matrix> {
+        let x = self.positional.forward(&self.embedding.forward(tokens)?)?;
+        match &self.token_type_embedding {
+            Some(segments) => x.add(&segments.forward(token_types)?),
+            None => Ok(x),
+        }
+    }
+
+    /// The segment of every token: `token_types` checked against `tokens`,
+    /// zeros when it is `None`, and empty without token-type embeddings.
+    fn resolve_token_types(
+        &self,
+        tokens: &[usize],
+        token_types: Option<&[usize]>,
+    ) -> Result<Vec<usize>> {
+        if self.token_type_embedding.is_none() {
+            return Ok(Vec::new());
+        }
+        match token_types {
+            Some(types) if types.len() != tokens.len() => Err(TransformerError::shape(format!(
+                "{} token types for {} tokens",
+                types.len(),
+                tokens.len()
+            ))),
+            Some(types) => Ok(types.to_vec()),
+            None => Ok(vec![0; tokens.len()]),
+        }
+    }
+
+    /// Encodes `tokens` into a `seq_len × d_model` matrix.
+    pub fn forward(&self, tokens: &[usize], mask: Option<&Mask>) -> Result<Matrix> {
+        self.forward_with_token_types(tokens, None, mask)
+    }
+
+    /// Like [`forward`](Self::forward), placing each token in the segment
+    /// given by `token_types`.
+    pub fn forward_with_token_types(
+        &self,
+        tokens: &[usize],
+        token_types: Option<&[usize]>,
+        mask: Option<&Mask>,
+    ) -> Result<Matrix> {
+        let mut x = self.embed_with_token_types(tokens, token_types)?;
+        for (i, layer) in self.layers.iter().enumerate() {
+            x = layer.forward_hooked(&x, mask, &self.hooks, i)?.0;
+        }
+        Ok(x)
+    }
+
+    /// Like [`forward`](Self::forward), also returning the self-attention
+    /// probabilities of every layer, indexed `[layer][head]`.
+    pub fn forward_with_attentions(
+        &self,
+        tokens: &[usize],
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<Vec<Matrix>>)> {
+        let mut x = self.embed(tokens)?;
+        let mut attentions = Vec::with_capacity(self.layers.len());
+        for (i, layer) in self.layers.iter().enumerate() {
+            let (out, layer_attentions) = layer.forward_hooked(&x, mask, &self.hooks, i)?;
+            x = out;
+            attentions.push(layer_attentions);
+        }
+        Ok((x, attentions))
+    }
+
+    /// Like [`forward`](Self::forward), also returning the hidden states
+    /// entering and leaving every layer: index 0 is the embedding output and
+    /// index `i + 1` the output of layer `i`, so the last entry equals the
+    /// returned encoding.
+    pub fn forward_with_hidden_states(
+        &self,
+        tokens: &[usize],
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, Vec<Matrix>)> {
+        let mut x = self.embed(tokens)?;
+        let mut hidden_states = Vec::with_capacity(self.layers.len() + 1);
+        for (i, layer) in self.layers.iter().enumerate() {
+            let out = layer.forward_hooked(&x, mask, &self.hooks, i)?.0;
+            hidden_states.push(x);
+            x = out;
+        }
+        hidden_states.push(x.clone());
+        Ok((x, hidden_states))
+    }
+
+    /// Like [`forward`](Self::forward), also returning what
+    /// [`backward`](Self::backward) needs. Hooks are not fired.
+    pub fn forward_with_cache(
+        &self,
+        tokens: &[usize],
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, EncoderCache)> {
+        self.forward_with_token_types_and_cache(tokens, None, mask)
+    }
+
+    /// [`forward_with_cache`](Self::forward_with_cache) with the segments of
+    /// [`forward_with_token_types`](Self::forward_with_token_types).
+    pub fn forward_with_token_types_and_cache(
+        &self,
+        tokens: &[usize],
+        token_types: Option<&[usize]>,
+        mask: Option<&Mask>,
+    ) -> Result<(Matrix, EncoderCache)> {
+        let token_types = self.resolve_token_types(tokens, token_types)?;
+        let mut x = self.sum_embeddings(tokens, &token_types)?;
+        let embedding_norm = match &self.embedding_norm {
+            Some(norm) => {
+                let (normalized, cache) = norm.forward_with_cache(&x)?;
+                x = normalized;
+                Some(cache)
+            }
+            None => None,
+        };
+        let (mut x, embedding_dropout) = self.dropout.forward_with_mask(&x)?;
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            let (out, cache) = layer.forward_with_cache(&x, mask)?;
+            x = out;
+            layers.push(cache);
+        }
+        Ok((
+            x,
+            EncoderCache {
+                tokens: tokens.to_vec(),
+                token_types,
+                embedding_norm,
+                embedding_dropout,
+                layers,
+            },
+        ))
+    }
+
+    /// Backpropagates `grad`, the gradient of the encoder output, adding the
+    /// gradient of every weight (named under `prefix`) to `grads`.
+    pub fn backward(
+        &self,
+        cache: &EncoderCache,
+        grad: &Matrix,
+        prefix: &str,
+        grads: &mut Gradients,
+    ) -> Result<()> {
+        let mut grad = grad.clone();
+        for (i, (layer, layer_cache)) in self.layers.iter().zip(&cache.layers).enumerate().rev() {
+            let name = join_name(prefix, &format!("layers.{}", i));
+            grad = layer.backward(layer_cache, &grad, &name, grads)?;
+        }
+        let mut grad = Dropout::backward(cache.embedding_dropout.as_ref(), &grad)?;
+        if let (Some(norm), Some(norm_cache)) = (&self.embedding_norm, &cache.embedding_norm) {
+            grad = norm.backward(
+                norm_cache,
+                &grad,
+                &join_name(prefix, "embedding_norm"),
+                grads,
+            )?;
+        }
+        if let Some(segments) = &self.token_type_embedding {
+            segments.backward(
+                &cache.token_types,
+                &grad,
+                &join_name(prefix, "token_type_embedding"),
+                grads,
+            )?;
+        }
+        self.positional
+            .backward(&grad, &join_name(prefix, "positional"), grads)?;
+        self.embedding
+            .backward(&cache.tokens, &grad, &join_name(prefix, "embedding"), grads)
+    }
+
+    /// Vocabulary logits for encoder outputs, `seq_len × vocab_size`. The
+    /// projection is tied to the token embedding, so no extra parameters are
+    /// needed for masked-token prediction.
+    pub fn lm_logits(&self, hidden: &Matrix) -> Result<Matrix> {
+        self.embedding.attend(hidden)
+    }
+
+    /// Encodes `tokens`, hiding `pad_token` positions, and returns
+    /// [`lm_logits`](Self::lm_logits) for every position.
+    pub fn masked_lm_logits(&self, tokens: &[usize], pad_token: usize) -> Result<Matrix> {
+        let mask =
+            AttentionMask::padding(tokens, pad_token).materialize(tokens.len(), tokens.len())?;
+        self.lm_logits(&self.forward(tokens, mask.as_deref())?)
+    }
+
+    /// Pseudo-log-likelihood (Salazar et al., 2020): the sum over non-pad
+    /// positions of `ln p(token)` when that position alone is replaced by
+    /// `mask_token`. Runs one forward pass per scored position.
+    pub fn pseudo_log_likelihood(
+        &self,
+        tokens: &[usize],
+        mask_token: usize,
+        pad_token: usize,
+    ) -> Result<f64> {
+        let mut total = 0.0;
+        let mut masked = tokens.to_vec();
+        for (i, &token) in tokens.iter().enumerate() {
+            if token == pad_token {
+                continue;
+            }
+            masked[i] = mask_token;
+            let logits = self.masked_lm_logits(&masked, pad_token)?;
+            masked[i] = token;
+            total += log_softmax(logits.row(i))[token];
+        }
+        Ok(total)
+    }
+
+    /// `exp(-PLL / n)` over the `n` non-pad positions of `tokens`; lower is
+    /// better. Returns an error if every position is padding.
+    pub fn pseudo_perplexity(
+        &self,
+        tokens: &[usize],
+        mask_token: usize,
+        pad_token: usize,
+    ) -> Result<f64> {
+        let scored = tokens.iter().filter(|&&t| t != pad_token).count();
+        if scored == 0 {
+            return Err("pseudo-perplexity needs at least one non-pad token".into());
+        }
+        let pll = self.pseudo_log_likelihood(tokens, mask_token, pad_token)?;
+        Ok((-pll / scored as f64).exp())
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.dropout.training = training;
+        for layer in &mut self.layers {
+            layer.set_training(training);
+        }
+    }
+
+    /// Makes every dropout layer draw from `rng`.
+    pub fn set_rng(&mut self, rng: &SharedRng) {
+        self.dropout.rng = rng.clone();
+        for layer in &mut self.layers {
+            layer.set_rng(rng);
+        }
+    }
+}
+
+impl Parameters for Encoder {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "embedding"), &self.embedding.weight);
+        if self.positional.is_learned() {
+            visitor(&join_name(prefix, "positional"), self.positional.table());
+        }
+        if let Some(segments) = &self.token_type_embedding {
+            visitor(&join_name(prefix, "token_type_embedding"), &segments.weight);
+        }
+        if let Some(norm) = &self.embedding_norm {
+            norm.visit_parameters(&join_name(prefix, "embedding_norm"), visitor);
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.visit_parameters(&join_name(prefix, &format!("layers.{}", i)), visitor);
+        }
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "embedding"), self.embedding.weight_mut());
+        if self.positional.is_learned() {
+            visitor(
+                &join_name(prefix, "positional"),
+                self.positional.table_mut(),
+            );
+        }
+        if let Some(segments) = &mut self.token_type_embedding {
+            visitor(
+                &join_name(prefix, "token_type_embedding"),
+                segments.weight_mut(),
+            );
+        }
+        if let Some(norm) = &mut self.embedding_norm {
+            norm.visit_parameters_mut(&join_name(prefix, "embedding_norm"), visitor);
+        }
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            layer.visit_parameters_mut(&join_name(prefix, &format!("layers.{}", i)), visitor);
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Encoder-only (BERT-style) model.
+//!
+//! [`EncoderOnlyTransformer`] is an [`Encoder`] with padding masks, optional
+//! token-type (segment) embeddings for sentence pairs, and a [`Pooling`]
+//! that turns the per-token states into one sentence embedding, for
+//! classification and retrieval workloads. `num_decoder_layers` in its
+//! configuration is ignored.
+
+use std::sync::Arc;
+
+use super::encoder::{Encoder, EncoderCache};
+use crate::config::{InitConfig, TransformerConfig};
+use crate::hooks::HookHandle;
+use crate::layers::Embedding;
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::mask::{AttentionMask, Mask};
+use crate::utils::rng::{thread_rng, Rng, SharedRng};
+use crate::{Result, TransformerError};
+
+/// How per-token encoder states are reduced to one sentence embedding.
+///
+/// Mean, max and last-token pooling read only non-padding positions; an
+/// all-padding input falls back to every position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pooling {
+    /// The state of the first token, the `[CLS]` position in BERT.
+    #[default]
+    Cls,
+    /// The mean over non-padding positions.
+    Mean,
+    /// The element-wise maximum over non-padding positions.
+    Max,
+    /// The state of the last non-padding token, which has seen the whole
+    /// input in causal models.
+    LastToken,
+}
+
+impl Pooling {
+    /// Pools `hidden` (`seq_len × d_model`), the encoding of `tokens`, into
+    /// a `1 × d_model` row.
+    pub fn pool(&self, hidden: &Matrix, tokens: &[usize], pad_token: usize) -> Result<Matrix> {
+        if hidden.rows() == 0 {
+            return Err("cannot pool an empty sequence".into());
+        }
+        let rows = pooled_rows(hidden.rows(), tokens, pad_token);
+        match self {
+            Pooling::Cls => hidden.rows_range(0, 1),
+            Pooling::Mean => Ok(hidden.select_rows(&rows)?.column_means()),
+            Pooling::Max => Ok(Matrix::row_vector(
+                max_rows(hidden, &rows)
+                    .iter()
+                    .enumerate()
+                    .map(|(j, &i)| hidden[(i, j)])
+                    .collect(),
+            )),
+            Pooling::LastToken => hidden.rows_range(rows[rows.len() - 1], 1),
+        }
+    }
+
+    /// Gradient with respect to the `seq_len × d_model` states pooled by
+    /// [`pool`](Self::pool), given the gradient `d_pooled` (`1 × d_model`)
+    /// of its output. `hidden` is the pooled input.
+    pub fn backward(
+        &self,
+        d_pooled: &Matrix,
+        hidden: &Matrix,
+        tokens: &[usize],
+        pad_token: usize,
+    ) -> Result<Matrix> {
+        if hidden.rows() == 0 {
+            return Err("cannot pool an empty sequence".into());
+        }
+        d_pooled.ensure_shape((1, hidden.cols()), "pooled gradient")?;
+        let mut grad = Matrix::zeros(hidden.rows(), hidden.cols());
+        let rows = pooled_rows(hidden.rows(), tokens, pad_token);
+        match self {
+            Pooling::Cls => grad.row_mut(0).copy_from_slice(d_pooled.row(0)),
+            Pooling::Mean => {
+                let share = d_pooled.scale(1.0 / rows.len() as f64);
+                for &i in &rows {
+                    grad.row_mut(i).copy_from_slice(share.row(0));
+                }
+            }
+            Pooling::Max => {
+                for (j, &i) in max_rows(hidden, &rows).iter().enumerate() {
+                    grad[(i, j)] = d_pooled[(0, j)];
+                }
+            }
+            Pooling::LastToken => grad
+                .row_mut(rows[rows.len() - 1])
+                .copy_from_slice(d_pooled.row(0)),
+        }
+        Ok(grad)
+    }
+}
+
+/// Non-padding positions of a `seq_len`-row encoding, or every position if
+/// all are padding.
+fn pooled_rows(seq_len: usize, tokens: &[usize], pad_token: usize) -> Vec<usize> {
+    let keep: Vec<usize> = (0..tokens.len().min(seq_len))
+        .filter(|&i| tokens[i] != pad_token)
+        .collect();
+    if keep.is_empty() {
+        (0..seq_len).collect()
+    } else {
+        keep
+    }
+}
+
+/// For every column of `hidden`, the first of `rows` holding its maximum.
+fn max_rows(hidden: &Matrix, rows: &[usize]) -> Vec<usize> {
+    (0..hidden.cols())
+        .map(|j| {
+            rows.iter().copied().fold(rows[0], |best, i| {
+                if hidden[(i, j)] > hidden[(best, j)] {
+                    i
+                } else {
+                    best
+                }
+            })
+        })
+        .collect()
+}
+
+/// Settings of [`Transformer::embed_with_config`](super::Transformer::embed_with_config).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbedConfig {
+    pub pooling: Pooling,
+    /// Scale the embedding to unit L2 length, so that dot products are
+    /// cosine similarities.
+    pub normalize: bool,
+}
+
+impl Default for EmbedConfig {
+    /// Mean pooling without normalization, as
+    /// [`mean_pooled_encoding`](super::Transformer::mean_pooled_encoding).
+    fn default() -> Self {
+        Self {
+            pooling: Pooling::Mean,
+            normalize: false,
+        }
+    }
+}
+
+impl EmbedConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pooling(mut self, pooling: Pooling) -> Self {
+        self.pooling = pooling;
+        self
+    }
+
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+}
+
+/// A stack of encoder layers used on its own.
+#[derive(Debug, Clone)]
+pub struct EncoderOnlyTransformer {
+    pub config: TransformerConfig,
+    pub encoder: Encoder,
+    /// Reduction used by [`embed`](Self::embed).
+    pub pooling: Pooling,
+    /// Generator behind dropout, shared by every dropout layer.
+    pub rng: SharedRng,
+}
+
+impl EncoderOnlyTransformer {
+    /// Builds a randomly initialized model from `config`.
+    pub fn new(config: TransformerConfig) -> Result<Self> {
+        Self::new_with_rng(config, &mut thread_rng())
+    }
+
+    /// Builds a model whose weights and dropout masks are determined by
+    /// `seed`.
+    pub fn with_seed(config: TransformerConfig, seed: u64) -> Result<Self> {
+        Self::new_with_rng(config, &mut Rng::seed_from_u64(seed))
+    }
+
+    /// Builds a model whose weights are fixed by `init`: its scheme replaces
+    /// `config.init` and its seed acts as in [`with_seed`](Self::with_seed).
+    pub fn with_init(mut config: TransformerConfig, init: InitConfig) -> Result<Self> {
+        config.init = init.scheme;
+        Self::new_with_rng(config, &mut init.rng())
+    }
+
+    /// Builds a model initialized from `rng`; the dropout stream is seeded
+    /// from it as well.
+    pub fn new_with_rng(config: TransformerConfig, rng: &mut Rng) -> Result<Self> {
+        config.validate()?;
+        let shared = SharedRng::seed_from_u64(rng.next_u64());
+        let encoder = Encoder::new_with_rng(&config, rng, &shared)?;
+        Ok(Self {
+            config,
+            encoder,
+            pooling: Pooling::default(),
+            rng: shared,
+        })
+    }
+
+    /// Wraps an existing encoder, such as
+    /// [`load_bert`](crate::huggingface::load_bert)'s, sharing the rng of
+    /// its dropout layers.
+    pub fn from_encoder(config: TransformerConfig, encoder: Encoder) -> Self {
+        Self {
+            rng: encoder.dropout.rng.clone(),
+            config,
+            encoder,
+            pooling: Pooling::default(),
+        }
+    }
+
+    pub fn config(&self) -> &TransformerConfig {
+        &self.config
+    }
+
+    pub fn with_pooling(mut self, pooling: Pooling) -> Self {
+        self.pooling = pooling;
+        self
+    }
+
+    /// Adds randomly initialized embeddings for `num_token_types` segments.
+    pub fn with_token_types(mut self, num_token_types: usize) -> Self {
+        self.encoder.token_type_embedding = Some(Embedding::from_weight(
+            self.config
+                .init
+                .embedding(num_token_types, self.config.d_model, &mut self.rng.fork()),
+        ));
+        self
+    }
+
+    /// Encodes `tokens` into `seq_len × d_model` states, hiding padding
+    /// from self-attention.
+    pub fn encode(&self, tokens: &[usize]) -> Result<Matrix> {
+        self.encode_with_token_types(tokens, None)
+    }
+
+    /// Like [`encode`](Self::encode), placing each token in the segment
+    /// given by `token_types` (segment 0 when `None`).
+    pub fn encode_with_token_types(
+        &self,
+        tokens: &[usize],
+        token_types: Option<&[usize]>,
+    ) -> Result<Matrix> {
+        self.encoder.forward_with_token_types(
+            tokens,
+            token_types,
+            self.padding_mask(tokens)?.as_deref(),
+        )
+    }
+
+    /// Like [`encode`](Self::encode), also returning the self-attention
+    /// probabilities of every layer, indexed `[layer][head]`.
+    pub fn encode_with_attentions(&self, tokens: &[usize]) -> Result<(Matrix, Vec<Vec<Matrix>>)> {
+        self.encoder
+            .forward_with_attentions(tokens, self.padding_mask(tokens)?.as_deref())
+    }
+
+    /// Like [`encode`](Self::encode), also returning the hidden state
+    /// entering every layer followed by the final one; see
+    /// [`Encoder::forward_with_hidden_states`].
+    pub fn encode_with_hidden_states(&self, tokens: &[usize]) -> Result<(Matrix, Vec<Matrix>)> {
+        self.encoder
+            .forward_with_hidden_states(tokens, self.padding_mask(tokens)?.as_deref())
+    }
+
+    /// Sentence embedding of `tokens` under [`pooling`](Self::pooling),
+    /// `1 × d_model`.
+    pub fn embed(&self, tokens: &[usize]) -> Result<Matrix> {
+        self.embed_with_token_types(tokens, None)
+    }
+
+    /// Like [`embed`](Self::embed), with per-token segments.
+    pub fn embed_with_token_types(
+        &self,
+        tokens: &[usize],
+        token_types: Option<&[usize]>,
+    ) -> Result<Matrix> {
+        let hidden = self.encode_with_token_types(tokens, token_types)?;
+        self.pooling.pool(&hidden, tokens, self.config.pad_token_id)
+    }
+
+    /// Sentence embeddings of many inputs, one row per input
+    /// (`inputs.len() × d_model`).
+    pub fn embed_batch(&self, inputs: &[Vec<usize>]) -> Result<Matrix> {
+        if inputs.is_empty() {
+            return Ok(Matrix::zeros(0, self.config.d_model));
+        }
+        let rows = inputs
+            .iter()
+            .map(|tokens| self.embed(tokens))
+            .collect::<Result<Vec<_>>>()?;
+        Matrix::vstack(&rows.iter().collect::<Vec<_>>())
+    }
+
+    /// Like [`encode_with_token_types`](Self::encode_with_token_types),
+    /// also returning what [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(
+        &self,
+        tokens: &[usize],
+        token_types: Option<&[usize]>,
+    ) -> Result<(Matrix, EncoderCache)> {
+        self.encoder.forward_with_token_types_and_cache(
+            tokens,
+            token_types,
+            self.padding_mask(tokens)?.as_deref(),
+        )
+    }
+
+    /// Gradients of every weight, named as by [`Parameters`], given the
+    /// gradient `d_hidden` of the encoder states.
+    pub fn backward(&self, cache: &EncoderCache, d_hidden: &Matrix) -> Result<Gradients> {
+        let mut grads = Gradients::new();
+        self.encoder
+            .backward(cache, d_hidden, "encoder", &mut grads)?;
+        Ok(grads)
+    }
+
+    /// Registers a forward hook. `pattern` is a full module name starting
+    /// with `encoder`, as in [`Parameters`], for example
+    /// `encoder.layers.*.feed_forward`. See [`crate::hooks`] for the
+    /// available hook points.
+    pub fn register_forward_hook<F>(&mut self, pattern: &str, hook: F) -> Result<HookHandle>
+    where
+        F: Fn(&str, &Matrix, &mut Matrix) + Send + Sync + 'static,
+    {
+        let rest = match pattern.split_once('.') {
+            Some(("encoder" | "*", rest)) if !rest.is_empty() => rest,
+            _ => {
+                return Err(TransformerError::config(format!(
+                    "hook pattern '{}' must start with 'encoder.' or '*.'",
+                    pattern
+                )))
+            }
+        };
+        let handle = HookHandle::next();
+        // The encoder fires with relative names; report the full one.
+        self.encoder.hooks.insert(
+            handle,
+            rest,
+            Arc::new(move |local: &str, input: &Matrix, output: &mut Matrix| {
+                hook(&join_name("encoder", local), input, output)
+            }),
+        );
+        Ok(handle)
+    }
+
+    /// Removes a hook registered with
+    /// [`register_forward_hook`](Self::register_forward_hook).
+    pub fn remove_hook(&mut self, handle: HookHandle) -> bool {
+        self.encoder.hooks.remove(handle)
+    }
+
+    /// Removes every hook.
+    pub fn clear_hooks(&mut self) {
+        self.encoder.hooks.clear();
+    }
+
+    /// Self-attention mask hiding padding tokens.
+    fn padding_mask(&self, tokens: &[usize]) -> Result<Option<Arc<Mask>>> {
+        AttentionMask::padding(tokens, self.config.pad_token_id)
+            .materialize(tokens.len(), tokens.len())
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.encoder.set_training(training);
+    }
+
+    /// Reseeds the dropout stream.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = SharedRng::seed_from_u64(seed);
+        self.encoder.set_rng(&self.rng);
+    }
+}
+
+impl Parameters for EncoderOnlyTransformer {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.encoder
+            .visit_parameters(&join_name(prefix, "encoder"), visitor);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        self.encoder
+            .visit_parameters_mut(&join_name(prefix, "encoder"), visitor);
+    }
+}
@@ -0,0 +1,247 @@
+//! Ensembles of independently trained models.
+//!
+//! Members share a vocabulary and special tokens but may differ in every
+//! other hyper-parameter. Each member's logits are first divided by its own
+//! `logit_temperature`, so calibrated checkpoints combine sensibly.
+
+use std::path::Path;
+
+use super::transformer::Transformer;
+use crate::config::TransformerConfig;
+use crate::generation::GenerationConfig;
+use crate::tensor::Matrix;
+use crate::testing::{load_parameters, Fixtures};
+use crate::utils::rng::Rng;
+use crate::utils::tensor_ops::{
+    argmax_row, argmax_rows, log_softmax_rows, log_sum_exp, softmax, softmax_rows,
+};
+use crate::{Result, TransformerError};
+
+/// How member outputs are combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnsembleStrategy {
+    /// Weighted mean of the members' logits.
+    #[default]
+    MeanLogits,
+    /// Weighted mean of the members' probabilities.
+    MeanProbabilities,
+}
+
+/// Several models whose predictions are averaged.
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    pub members: Vec<Transformer>,
+    /// Non-negative member weights, normalized to sum to one.
+    pub weights: Vec<f64>,
+    pub strategy: EnsembleStrategy,
+}
+
+impl Ensemble {
+    /// Builds an equally weighted ensemble. Fails if `members` is empty or
+    /// the members disagree on vocabulary size or special tokens.
+    pub fn new(members: Vec<Transformer>) -> Result<Self> {
+        let first = members
+            .first()
+            .ok_or("an ensemble needs at least one member")?
+            .config
+            .clone();
+        for (i, member) in members.iter().enumerate().skip(1) {
+            let c = &member.config;
+            if c.vocab_size != first.vocab_size
+                || c.pad_token_id != first.pad_token_id
+                || c.bos_token_id != first.bos_token_id
+                || c.eos_token_id != first.eos_token_id
+            {
+                return Err(TransformerError::config(format!(
+                    "ensemble member {} does not share the vocabulary of member 0",
+                    i
+                )));
+            }
+        }
+        let weights = vec![1.0 / members.len() as f64; members.len()];
+        Ok(Self {
+            members,
+            weights,
+            strategy: EnsembleStrategy::default(),
+        })
+    }
+
+    /// Loads one member per checkpoint (`.npz` archive or `.npy` directory
+    /// of parameters, as written by [`Fixtures::save`]), all using `config`.
+    pub fn from_checkpoints<P: AsRef<Path>>(
+        config: &TransformerConfig,
+        paths: &[P],
+    ) -> Result<Self> {
+        let members = paths
+            .iter()
+            .map(|path| {
+                let mut model = Transformer::new(config.clone())?;
+                load_parameters(&mut model, "", &Fixtures::load(path)?)?;
+                Ok(model)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(members)
+    }
+
+    /// Sets member weights. They must be non-negative with a positive sum and
+    /// are normalized.
+    pub fn with_weights(mut self, weights: &[f64]) -> Result<Self> {
+        if weights.len() != self.members.len() {
+            return Err(TransformerError::config(format!(
+                "{} weights for {} ensemble members",
+                weights.len(),
+                self.members.len()
+            )));
+        }
+        let total: f64 = weights.iter().sum();
+        if weights.iter().any(|w| w.is_nan() || *w < 0.0) || total.is_nan() || total <= 0.0 {
+            return Err(TransformerError::config(
+                "ensemble weights must be non-negative with a positive sum",
+            ));
+        }
+        self.weights = weights.iter().map(|w| w / total).collect();
+        Ok(self)
+    }
+
+    pub fn with_strategy(mut self, strategy: EnsembleStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Combined scores for every target position (`tgt_len × vocab_size`):
+    /// mean logits, or log mean probabilities, depending on the strategy.
+    /// Either way `softmax` of a row gives the ensemble's distribution.
+    pub fn forward(&self, src: &[usize], tgt: &[usize]) -> Result<Matrix> {
+        let logits = self
+            .members
+            .iter()
+            .map(|m| m.forward(src, tgt))
+            .collect::<Result<Vec<_>>>()?;
+        self.combine(&logits)
+    }
+
+    /// Ensemble next-token probabilities for every target position.
+    pub fn probabilities(&self, src: &[usize], tgt: &[usize]) -> Result<Matrix> {
+        Ok(softmax_rows(&self.forward(src, tgt)?))
+    }
+
+    /// Most likely token at every target position.
+    pub fn predict(&self, src: &[usize], tgt: &[usize]) -> Result<Vec<usize>> {
+        Ok(argmax_rows(&self.forward(src, tgt)?))
+    }
+
+    /// Generates up to `max_length` tokens (including the start token) by
+    /// sampling from the combined distribution, drawing from the first
+    /// member's generator. Every member encodes `src` once.
+    pub fn generate(&self, src: &[usize], max_length: usize) -> Result<Vec<usize>> {
+        self.generate_with_rng(src, max_length, &mut self.members[0].rng.fork())
+    }
+
+    /// Like [`generate`](Self::generate), sampling from `rng`.
+    pub fn generate_with_rng(
+        &self,
+        src: &[usize],
+        max_length: usize,
+        rng: &mut Rng,
+    ) -> Result<Vec<usize>> {
+        self.decode_loop(src, max_length, |scores| {
+            Ok(rng.categorical(&softmax(scores)))
+        })
+    }
+
+    /// Generates up to `max_length` tokens by always choosing the token with
+    /// the highest combined score. Uses no randomness.
+    pub fn generate_greedy(&self, src: &[usize], max_length: usize) -> Result<Vec<usize>> {
+        self.decode_loop(src, max_length, |scores| {
+            argmax_row(scores).ok_or_else(|| "ensemble produced no finite scores".into())
+        })
+    }
+
+    /// Autoregressive decoding over the combined scores; see
+    /// [`Transformer::generate`]. Lengths are bounded by the smallest member
+    /// `max_seq_len`.
+    fn decode_loop(
+        &self,
+        src: &[usize],
+        max_length: usize,
+        mut pick: impl FnMut(&[f64]) -> Result<usize>,
+    ) -> Result<Vec<usize>> {
+        let config = &self.members[0].config;
+        let max_seq_len = self
+            .members
+            .iter()
+            .map(|m| m.config.max_seq_len)
+            .min()
+            .unwrap_or(0);
+        let src = GenerationConfig::new().truncate(src, max_seq_len)?;
+        let memories = self
+            .members
+            .iter()
+            .map(|m| m.encode(src))
+            .collect::<Result<Vec<_>>>()?;
+        let mut output = vec![config.bos_token_id];
+        let max_length = max_length.min(max_seq_len);
+
+        while output.len() < max_length {
+            let last = self
+                .members
+                .iter()
+                .zip(&memories)
+                .map(|(m, memory)| {
+                    let logits = m.decode(&output, src, memory)?;
+                    Matrix::from_rows(&[logits.row(logits.rows() - 1).to_vec()])
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let next = pick(self.combine(&last)?.row(0))?;
+            output.push(next);
+            if next == config.eos_token_id {
+                break;
+            }
+        }
+        Ok(output)
+    }
+
+    /// Combines per-member logits of identical shape.
+    fn combine(&self, logits: &[Matrix]) -> Result<Matrix> {
+        let scaled: Vec<Matrix> = self
+            .members
+            .iter()
+            .zip(logits)
+            .map(|(member, l)| l.scale(1.0 / member.config.logit_temperature))
+            .collect();
+        match self.strategy {
+            EnsembleStrategy::MeanLogits => {
+                let mut out = Matrix::zeros(scaled[0].rows(), scaled[0].cols());
+                for (weight, member_logits) in self.weights.iter().zip(&scaled) {
+                    out.add_assign(&member_logits.scale(*weight))?;
+                }
+                Ok(out)
+            }
+            // ln Σ w·p computed in log space: log-sum-exp of ln w + ln p.
+            EnsembleStrategy::MeanProbabilities => {
+                let log_probs: Vec<Matrix> = scaled.iter().map(log_softmax_rows).collect();
+                let mut out = log_probs[0].clone();
+                for i in 0..out.rows() {
+                    for j in 0..out.cols() {
+                        let terms: Vec<f64> = self
+                            .weights
+                            .iter()
+                            .zip(&log_probs)
+                            .map(|(w, lp)| w.ln() + lp[(i, j)])
+                            .collect();
+                        out[(i, j)] = log_sum_exp(&terms);
+                    }
+                }
+                Ok(out)
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Masked language modeling (BERT-style pretraining).
+//!
+//! A [`MaskedLanguageModel`] predicts the original token at every position
+//! of an encoder-only model's input. Its head reuses the token embedding
+//! transposed, as [`Encoder::lm_logits`](super::Encoder::lm_logits) does,
+//! plus a per-token output bias. Training inputs come from an
+//! [`MlmCollator`](crate::data::MlmCollator), and the loss counts only the
+//! positions it masked.
+
+use super::encoder::EncoderCache;
+use super::encoder_only::EncoderOnlyTransformer;
+use crate::config::TransformerConfig;
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::tensor_ops::argmax_rows;
+use crate::Result;
+
+/// What [`MaskedLanguageModel::backward`] needs from a forward pass.
+#[derive(Debug, Clone)]
+pub struct MaskedLanguageModelCache {
+    pub encoder: EncoderCache,
+    /// The `seq_len × d_model` encoder states the head read.
+    pub hidden: Matrix,
+}
+
+/// An encoder-only model with a vocabulary head tied to its token
+/// embedding.
+#[derive(Debug, Clone)]
+pub struct MaskedLanguageModel {
+    pub backbone: EncoderOnlyTransformer,
+    /// Output bias, `1 × vocab_size`.
+    pub bias: Matrix,
+}
+
+impl MaskedLanguageModel {
+    /// Adds a zero output bias to `backbone`.
+    pub fn new(backbone: EncoderOnlyTransformer) -> Self {
+        Self {
+            bias: Matrix::zeros(1, backbone.config.vocab_size),
+            backbone,
+        }
+    }
+
+    /// Builds a randomly initialized model whose weights are determined by
+    /// `seed`.
+    pub fn with_seed(config: TransformerConfig, seed: u64) -> Result<Self> {
+        Ok(Self::new(EncoderOnlyTransformer::with_seed(config, seed)?))
+    }
+
+    /// Vocabulary logits for every position of `tokens`,
+    /// `seq_len × vocab_size`.
+    pub fn logits(&self, tokens: &[usize]) -> Result<Matrix> {
+        self.head(&self.backbone.encode(tokens)?)
+    }
+
+    /// The most likely token at every position, for filling in masks.
+    pub fn predict(&self, tokens: &[usize]) -> Result<Vec<usize>> {
+        Ok(argmax_rows(&self.logits(tokens)?))
+    }
+
+    /// Like [`logits`](Self::logits), also returning what
+    /// [`backward`](Self::backward) needs.
+    pub fn forward_with_cache(
+        &self,
+        tokens: &[usize],
+    ) -> Result<(Matrix, MaskedLanguageModelCache)> {
+        let (hidden, encoder) = self.backbone.forward_with_cache(tokens, None)?;
+        let logits = self.head(&hidden)?;
+        Ok((logits, MaskedLanguageModelCache { encoder, hidden }))
+    }
+
+    /// Gradients of every weight given the gradient `d_logits`
+    /// (`seq_len × vocab_size`) of the logits.
+    pub fn backward(
+        &self,
+        cache: &MaskedLanguageModelCache,
+        d_logits: &Matrix,
+    ) -> Result<Gradients> {
+        let mut grads = Gradients::new();
+        self.accumulate_gradients(cache, d_logits, &mut grads)?;
+        Ok(grads)
+    }
+
+    /// Like [`backward`](Self::backward), adding into `grads` so gradients
+    /// of several examples can be summed. The tied head's gradient is added
+    /// to `encoder.embedding`.
+    pub fn accumulate_gradients(
+        &self,
+        cache: &MaskedLanguageModelCache,
+        d_logits: &Matrix,
+        grads: &mut Gradients,
+    ) -> Result<()> {
+        let encoder = &self.backbone.encoder;
+        let d_hidden = encoder.embedding.attend_backward(
+            &cache.hidden,
+            d_logits,
+            "encoder.embedding",
+            grads,
+        )?;
+        grads.accumulate("mlm_head.bias", &d_logits.column_sums())?;
+        encoder.backward(&cache.encoder, &d_hidden, "encoder", grads)
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.backbone.set_training(training);
+    }
+
+    fn head(&self, hidden: &Matrix) -> Result<Matrix> {
+        self.backbone
+            .encoder
+            .lm_logits(hidden)?
+            .add_row_vector(&self.bias)
+    }
+}
+
+impl Parameters for MaskedLanguageModel {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.backbone.visit_parameters(prefix, visitor);
+        visitor(&join_name(prefix, "mlm_head.bias"), &self.bias);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        self.backbone.visit_parameters_mut(prefix, visitor);
+        visitor(&join_name(prefix, "mlm_head.bias"), &mut self.bias);
+    }
+}
@@ -0,0 +1,122 @@
+//! Model card and provenance information carried with a checkpoint.
+//!
+//! A [`ModelMetadata`] travels with a [`Transformer`](super::Transformer)
+//! and is written to and read back from GGUF files (see [`crate::gguf`])
+//! and saved models (see [`Transformer::save`](super::Transformer::save)),
+//! so whoever loads a model can check where it came from: what it was
+//! trained on, under which license, which commit produced it and how it
+//! scored.
+
+use std::collections::BTreeMap;
+
+use crate::config::TransformerConfig;
+use crate::utils::json::Json;
+use crate::utils::rng::fnv1a64;
+
+/// Structured, optional facts about a trained model.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelMetadata {
+    /// Free-form description of the training data.
+    pub training_data: Option<String>,
+    /// License identifier, preferably SPDX (`Apache-2.0`, `MIT`, ...).
+    pub license: Option<String>,
+    /// Commit of the code that trained the model.
+    pub git_commit: Option<String>,
+    /// [`config_hash`] of the configuration the weights were trained with.
+    pub config_hash: Option<String>,
+    /// Evaluation results by metric name, e.g. `"bleu"` or `"val_loss"`.
+    pub eval_scores: BTreeMap<String, f64>,
+    /// Any further key/value pairs.
+    pub extra: BTreeMap<String, String>,
+}
+
+impl ModelMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Metadata whose `config_hash` identifies `config`.
+    pub fn for_config(config: &TransformerConfig) -> Self {
+        Self::new().with_config_hash(config)
+    }
+
+    pub fn with_training_data(mut self, description: impl Into<String>) -> Self {
+        self.training_data = Some(description.into());
+        self
+    }
+
+    pub fn with_license(mut self, license: impl Into<String>) -> Self {
+        self.license = Some(license.into());
+        self
+    }
+
+    pub fn with_git_commit(mut self, commit: impl Into<String>) -> Self {
+        self.git_commit = Some(commit.into());
+        self
+    }
+
+    pub fn with_config_hash(mut self, config: &TransformerConfig) -> Self {
+        self.config_hash = Some(config_hash(config));
+        self
+    }
+
+    pub fn with_eval_score(mut self, metric: impl Into<String>, score: f64) -> Self {
+        self.eval_scores.insert(metric.into(), score);
+        self
+    }
+
+    pub fn with_extra(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra.insert(key.into(), value.into());
+        self
+    }
+
+    /// Whether no field is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the recorded config hash matches `config`; `None` if no hash
+    /// was recorded.
+    pub fn matches_config(&self, config: &TransformerConfig) -> Option<bool> {
+        self.config_hash
+            .as_ref()
+            .map(|hash| *hash == config_hash(config))
+    }
+
+    /// The model card as a JSON object, leaving out unset fields.
+    pub fn to_json(&self) -> Json {
+        let mut pairs = Vec::new();
+        let fields = [
+            ("training_data", &self.training_data),
+            ("license", &self.license),
+            ("git_commit", &self.git_commit),
+            ("config_hash", &self.config_hash),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                pairs.push((key.to_string(), Json::from(value.as_str())));
+            }
+        }
+        if !self.eval_scores.is_empty() {
+            let scores = self
+                .eval_scores
+                .iter()
+                .map(|(k, &v)| (k.clone(), Json::from(v)));
+            pairs.push(("eval_scores".to_string(), Json::object(scores)));
+        }
+        if !self.extra.is_empty() {
+            let extra = self
+                .extra
+                .iter()
+                .map(|(k, v)| (k.clone(), Json::from(v.as_str())));
+            pairs.push(("extra".to_string(), Json::object(extra)));
+        }
+        Json::Object(pairs)
+    }
+}
+
+/// Stable fingerprint of a configuration: 16 hex digits of the FNV-1a hash
+/// of its `Debug` representation.
+pub fn config_hash(config: &TransformerConfig) -> String {
+    format!("{:016x}", fnv1a64(format!("{:?}", config).as_bytes()))
+}
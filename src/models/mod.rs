@@ -0,0 +1,32 @@
+//! Encoder, decoder, encoder-only and full model definitions, and
+//! classification and masked-language-modeling heads on the encoder-only
+//! model.
+
+pub mod chunking;
+pub mod classifier;
+pub mod decoder;
+pub mod encoder;
+pub mod encoder_only;
+pub mod ensemble;
+pub mod masked_lm;
+pub mod metadata;
+pub mod serialization;
+pub mod transformer;
+
+pub use chunking::{ChunkMerge, ChunkingConfig};
+pub use classifier::{
+    ClassificationHead, SequenceClassifier, SequenceClassifierCache, TokenClassifier,
+    TokenClassifierCache,
+};
+pub use decoder::{
+    Decoder, DecoderCache, DecoderKvCache, DecoderLayer, DecoderLayerAttentions, DecoderLayerCache,
+    DecoderLayerKvCache,
+};
+pub use encoder::{Encoder, EncoderCache, EncoderLayer, EncoderLayerCache};
+pub use encoder_only::{EmbedConfig, EncoderOnlyTransformer, Pooling};
+pub use ensemble::{Ensemble, EnsembleStrategy};
+pub use masked_lm::{MaskedLanguageModel, MaskedLanguageModelCache};
+pub use metadata::ModelMetadata;
+pub use transformer::{
+    Transformer, TransformerAttentions, TransformerCache, TransformerHiddenStates,
+};
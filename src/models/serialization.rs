@@ -0,0 +1,372 @@
+//! Lossless on-disk format for [`Transformer`] weights.
+//!
+//! [`Transformer::save`] writes every parameter at full `f64` precision
+//! together with the configuration and [`ModelMetadata`], so
+//! [`Transformer::load`] can rebuild the model without any other input.
+//! Unlike GGUF export, nothing is rounded or quantized.
+//!
+//! Layout, all integers little-endian:
+//!
+//! - magic `RTWT` and a `u32` format version (currently 1)
+//! - `u64` count of header entries, each a string key, a `u8` type tag
+//!   (0 `u64`, 1 `f64`, 2 `bool`, 3 string) and the value; the
+//!   configuration uses its field names as keys, metadata lives under
+//!   `metadata.*`
+//! - `u64` count of tensors, each a string name (the parameter name), `u64`
+//!   rows, `u64` columns and `rows × cols` row-major `f64`s
+//! - a CRC-32 of everything before it
+//!
+//! Strings are a `u64` byte length followed by UTF-8. Readers skip header
+//! keys they do not know and fall back to defaults for optional keys they
+//! do not find, so adding configuration fields does not require a new
+//! version.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::{ModelMetadata, Transformer};
+use crate::config::{parse_activation, InitScheme, NormType, PositionalScheme, TransformerConfig};
+use crate::layers::{Embedding, LayerNorm};
+use crate::params::Parameters;
+use crate::tensor::Matrix;
+use crate::testing::{load_parameters, Fixtures};
+use crate::utils::checksum::crc32;
+use crate::Result;
+
+const MAGIC: &[u8; 4] = b"RTWT";
+/// Format version written by [`Transformer::save`].
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl Transformer {
+    /// Writes the model to `path`; see the [module docs](self) for the
+    /// format.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a model written by [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// The bytes [`save`](Self::save) writes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+        let header = header_entries(&self.config, &self.metadata);
+        out.extend_from_slice(&(header.len() as u64).to_le_bytes());
+        for (key, value) in &header {
+            write_string(&mut out, key);
+            match value {
+                Value::U64(v) => {
+                    out.push(0);
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+                Value::F64(v) => {
+                    out.push(1);
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+                Value::Bool(v) => out.extend_from_slice(&[2, *v as u8]),
+                Value::Str(v) => {
+                    out.push(3);
+                    write_string(&mut out, v);
+                }
+            }
+        }
+
+        let mut tensors = Vec::new();
+        self.visit_parameters("", &mut |name, m| {
+            tensors.push((name.to_string(), m.clone()))
+        });
+        out.extend_from_slice(&(tensors.len() as u64).to_le_bytes());
+        for (name, m) in &tensors {
+            write_string(&mut out, name);
+            out.extend_from_slice(&(m.rows() as u64).to_le_bytes());
+            out.extend_from_slice(&(m.cols() as u64).to_le_bytes());
+            for v in m.as_slice() {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        let crc = crc32(&out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// Parses the bytes of a saved model, verifying the checksum and that
+    /// every parameter is present with the right shape.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < MAGIC.len() + 8 || &bytes[..4] != MAGIC {
+            return Err("not a saved transformer (bad magic)".into());
+        }
+        let (body, stored) = bytes.split_at(bytes.len() - 4);
+        let stored = u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]]);
+        if crc32(body) != stored {
+            return Err("saved transformer is corrupt (checksum mismatch)".into());
+        }
+        let mut reader = Reader {
+            bytes: body,
+            pos: 4,
+        };
+        let version = u32::from_le_bytes(reader.array()?);
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(format!(
+                "unsupported model format version {} (this build reads up to {})",
+                version, FORMAT_VERSION
+            )
+            .into());
+        }
+
+        let mut header = BTreeMap::new();
+        for _ in 0..reader.len()? {
+            let key = reader.string()?;
+            let value = match reader.u8()? {
+                0 => Value::U64(reader.u64()?),
+                1 => Value::F64(f64::from_le_bytes(reader.array()?)),
+                2 => Value::Bool(reader.u8()? != 0),
+                3 => Value::Str(reader.string()?),
+                tag => return Err(format!("unknown value type {} for '{}'", tag, key).into()),
+            };
+            header.insert(key, value);
+        }
+
+        let mut fixtures = Fixtures::new();
+        for _ in 0..reader.len()? {
+            let name = reader.string()?;
+            let rows = reader.len()?;
+            let cols = reader.len()?;
+            let count = rows
+                .checked_mul(cols)
+                .filter(|&n| n <= (body.len() - reader.pos) / 8)
+                .ok_or_else(|| format!("tensor '{}' extends past the end of the file", name))?;
+            let data = (0..count)
+                .map(|_| reader.array().map(f64::from_le_bytes))
+                .collect::<Result<Vec<_>>>()?;
+            fixtures.insert(name, Matrix::from_vec(rows, cols, data)?);
+        }
+        if reader.pos != body.len() {
+            return Err(format!(
+                "{} unexpected trailing bytes in saved transformer",
+                body.len() - reader.pos
+            )
+            .into());
+        }
+
+        let mut model = Transformer::new(config_from_header(&header)?)?
+            .with_metadata(metadata_from_header(&header));
+        if fixtures.contains("encoder.embedding_norm.gamma") {
+            model.encoder.embedding_norm = Some(LayerNorm::new(
+                model.config.d_model,
+                model.config.layer_norm_eps,
+            ));
+        }
+        if let Ok(segments) = fixtures.get("encoder.token_type_embedding") {
+            model.encoder.token_type_embedding = Some(Embedding::from_weight(Matrix::zeros(
+                segments.rows(),
+                model.config.d_model,
+            )));
+        }
+        load_parameters(&mut model, "", &fixtures)?;
+        Ok(model)
+    }
+}
+
+fn header_entries(config: &TransformerConfig, metadata: &ModelMetadata) -> Vec<(String, Value)> {
+    let mut entries = vec![
+        ("vocab_size", Value::U64(config.vocab_size as u64)),
+        ("d_model", Value::U64(config.d_model as u64)),
+        ("num_heads", Value::U64(config.num_heads as u64)),
+        (
+            "num_encoder_layers",
+            Value::U64(config.num_encoder_layers as u64),
+        ),
+        (
+            "num_decoder_layers",
+            Value::U64(config.num_decoder_layers as u64),
+        ),
+        ("d_ff", Value::U64(config.d_ff as u64)),
+        ("max_seq_len", Value::U64(config.max_seq_len as u64)),
+        (
+            "positional",
+            Value::Str(config.positional.name().to_string()),
+        ),
+        ("init", Value::Str(config.init.name())),
+        ("activation", Value::Str(config.activation.name())),
+        ("dropout", Value::F64(config.dropout)),
+        ("norm", Value::Str(config.norm.name().to_string())),
+        ("layer_norm_eps", Value::F64(config.layer_norm_eps)),
+        ("pad_token_id", Value::U64(config.pad_token_id as u64)),
+        ("bos_token_id", Value::U64(config.bos_token_id as u64)),
+        ("eos_token_id", Value::U64(config.eos_token_id as u64)),
+        ("scale_embeddings", Value::Bool(config.scale_embeddings)),
+        ("share_embeddings", Value::Bool(config.share_embeddings)),
+        ("attention_bias", Value::Bool(config.attention_bias)),
+        ("output_bias", Value::Bool(config.output_bias)),
+        ("logit_temperature", Value::F64(config.logit_temperature)),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect::<Vec<_>>();
+    if let Some(window) = config.attention_window {
+        entries.push(("attention_window".to_string(), Value::U64(window as u64)));
+    }
+
+    let strings = [
+        ("metadata.training_data", &metadata.training_data),
+        ("metadata.license", &metadata.license),
+        ("metadata.git_commit", &metadata.git_commit),
+        ("metadata.config_hash", &metadata.config_hash),
+    ];
+    for (key, value) in strings {
+        if let Some(value) = value {
+            entries.push((key.to_string(), Value::Str(value.clone())));
+        }
+    }
+    for (metric, score) in &metadata.eval_scores {
+        entries.push((format!("metadata.eval.{}", metric), Value::F64(*score)));
+    }
+    for (key, value) in &metadata.extra {
+        entries.push((format!("metadata.extra.{}", key), Value::Str(value.clone())));
+    }
+    entries
+}
+
+fn config_from_header(header: &BTreeMap<String, Value>) -> Result<TransformerConfig> {
+    let usize_at = |key: &str| -> Result<usize> {
+        match header.get(key) {
+            Some(Value::U64(v)) => usize::try_from(*v)
+                .map_err(|_| format!("'{}' = {} does not fit in usize", key, v).into()),
+            _ => Err(format!("saved transformer lacks integer '{}'", key).into()),
+        }
+    };
+    let f64_or = |key: &str, default: f64| match header.get(key) {
+        Some(Value::F64(v)) => *v,
+        _ => default,
+    };
+    let bool_or = |key: &str, default: bool| match header.get(key) {
+        Some(Value::Bool(v)) => *v,
+        _ => default,
+    };
+    let defaults = TransformerConfig::default();
+    Ok(TransformerConfig {
+        vocab_size: usize_at("vocab_size")?,
+        d_model: usize_at("d_model")?,
+        num_heads: usize_at("num_heads")?,
+        num_encoder_layers: usize_at("num_encoder_layers")?,
+        num_decoder_layers: usize_at("num_decoder_layers")?,
+        d_ff: usize_at("d_ff")?,
+        activation: match header.get("activation") {
+            Some(Value::Str(name)) => parse_activation(name)?,
+            _ => defaults.activation,
+        },
+        max_seq_len: usize_at("max_seq_len")?,
+        positional: match header.get("positional") {
+            Some(Value::Str(name)) => PositionalScheme::from_name(name)?,
+            _ => defaults.positional,
+        },
+        attention_window: match header.get("attention_window") {
+            Some(Value::U64(_)) => Some(usize_at("attention_window")?),
+            _ => None,
+        },
+        init: match header.get("init") {
+            Some(Value::Str(name)) => InitScheme::from_name(name)?,
+            _ => defaults.init,
+        },
+        dropout: f64_or("dropout", defaults.dropout),
+        norm: match header.get("norm") {
+            Some(Value::Str(name)) => NormType::from_name(name)?,
+            _ => defaults.norm,
+        },
+        layer_norm_eps: f64_or("layer_norm_eps", defaults.layer_norm_eps),
+        pad_token_id: usize_at("pad_token_id")?,
+        bos_token_id: usize_at("bos_token_id")?,
+        eos_token_id: usize_at("eos_token_id")?,
+        scale_embeddings: bool_or("scale_embeddings", defaults.scale_embeddings),
+        share_embeddings: bool_or("share_embeddings", defaults.share_embeddings),
+        attention_bias: bool_or("attention_bias", defaults.attention_bias),
+        output_bias: bool_or("output_bias", defaults.output_bias),
+        logit_temperature: f64_or("logit_temperature", defaults.logit_temperature),
+    })
+}
+
+fn metadata_from_header(header: &BTreeMap<String, Value>) -> ModelMetadata {
+    let string_at = |key: &str| match header.get(key) {
+        Some(Value::Str(v)) => Some(v.clone()),
+        _ => None,
+    };
+    let mut metadata = ModelMetadata {
+        training_data: string_at("metadata.training_data"),
+        license: string_at("metadata.license"),
+        git_commit: string_at("metadata.git_commit"),
+        config_hash: string_at("metadata.config_hash"),
+        ..ModelMetadata::default()
+    };
+    for (key, value) in header {
+        if let (Some(metric), Value::F64(score)) = (key.strip_prefix("metadata.eval."), value) {
+            metadata.eval_scores.insert(metric.to_string(), *score);
+        } else if let (Some(name), Value::Str(v)) = (key.strip_prefix("metadata.extra."), value) {
+            metadata.extra.insert(name.to_string(), v.clone());
+        }
+    }
+    metadata
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Bounds-checked little-endian cursor.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.bytes.len());
+        let end = end.ok_or("truncated saved transformer")?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// A `u64` count or size no larger than the file.
+    fn len(&mut self) -> Result<usize> {
+        let v = self.u64()?;
+        usize::try_from(v)
+            .ok()
+            .filter(|&v| v <= self.bytes.len())
+            .ok_or_else(|| format!("implausible length {} in saved transformer", v).into())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.len()?;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_string())
+    }
+}
@@ -0,0 +1,671 @@
+//! Encoder-decoder Transformer (Vaswani et al., 2017).
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::decoder::{Decoder, DecoderCache, DecoderKvCache, DecoderLayerAttentions};
+use super::encoder::{Encoder, EncoderCache};
+use super::encoder_only::EmbedConfig;
+use super::metadata::ModelMetadata;
+use crate::config::{InitConfig, TransformerConfig};
+use crate::generation::{
+    FinishReason, GenerationConfig, GenerationOutput, InferenceSession, SamplingConfig,
+};
+use crate::hooks::{ForwardHook, HookHandle};
+use crate::layers::Embedding;
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::mask::{AttentionMask, Mask};
+use crate::utils::rng::{thread_rng, Rng, SharedRng};
+use crate::utils::similarity::normalize;
+use crate::utils::tensor_ops::{argmax_row, softmax};
+use crate::{Result, TransformerError};
+
+/// Attention probabilities collected from a full forward pass.
+#[derive(Debug, Clone)]
+pub struct TransformerAttentions {
+    /// Encoder self-attention, indexed `[layer][head]`, each `src_len × src_len`.
+    pub encoder: Vec<Vec<Matrix>>,
+    /// Decoder self- and cross-attention for every layer.
+    pub decoder: Vec<DecoderLayerAttentions>,
+}
+
+/// Hidden states collected from a full forward pass. Each stack contributes
+/// its embedding output followed by the output of every layer.
+#[derive(Debug, Clone)]
+pub struct TransformerHiddenStates {
+    /// Encoder states, `num_encoder_layers + 1` matrices of `src_len × d_model`.
+    pub encoder: Vec<Matrix>,
+    /// Decoder states, `num_decoder_layers + 1` matrices of `tgt_len × d_model`.
+    pub decoder: Vec<Matrix>,
+}
+
+/// Intermediate values of a [`Transformer::forward_with_cache`] pass.
+#[derive(Debug, Clone)]
+pub struct TransformerCache {
+    pub encoder: EncoderCache,
+    pub decoder: DecoderCache,
+}
+
+/// The full sequence-to-sequence model.
+#[derive(Debug, Clone)]
+pub struct Transformer {
+    pub config: TransformerConfig,
+    pub encoder: Encoder,
+    pub decoder: Decoder,
+    /// Generator behind dropout and [`generate`](Self::generate), shared by
+    /// every dropout layer. Clones of the model share it too; use
+    /// [`set_seed`](Self::set_seed) to give a clone its own stream.
+    pub rng: SharedRng,
+    /// Model card and provenance, saved with checkpoints.
+    pub metadata: ModelMetadata,
+}
+
+impl Transformer {
+    /// Builds a randomly initialized model from `config`.
+    pub fn new(config: TransformerConfig) -> Result<Self> {
+        Self::new_with_rng(config, &mut thread_rng())
+    }
+
+    /// Builds a model whose weights, dropout masks and samples are all
+    /// determined by `seed`.
+    pub fn with_seed(config: TransformerConfig, seed: u64) -> Result<Self> {
+        Self::new_with_rng(config, &mut Rng::seed_from_u64(seed))
+    }
+
+    /// Builds a model whose weights are fixed by `init`: its scheme replaces
+    /// `config.init` and its seed acts as in [`with_seed`](Self::with_seed).
+    pub fn with_init(mut config: TransformerConfig, init: InitConfig) -> Result<Self> {
+        config.init = init.scheme;
+        Self::new_with_rng(config, &mut init.rng())
+    }
+
+    /// Builds a model initialized from `rng`; the dropout and sampling stream
+    /// is seeded from it as well.
+    pub fn new_with_rng(config: TransformerConfig, rng: &mut Rng) -> Result<Self> {
+        config.validate()?;
+        let shared = SharedRng::seed_from_u64(rng.next_u64());
+        let encoder = Encoder::new_with_rng(&config, rng, &shared)?;
+        let mut decoder = Decoder::new_with_rng(&config, rng, &shared)?;
+        if config.share_embeddings {
+            decoder.embedding.share_weight(&encoder.embedding);
+        }
+        Ok(Self {
+            encoder,
+            decoder,
+            config,
+            rng: shared,
+            metadata: ModelMetadata::default(),
+        })
+    }
+
+    pub fn config(&self) -> &TransformerConfig {
+        &self.config
+    }
+
+    /// Provenance attached to the model, or loaded with its checkpoint.
+    pub fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    pub fn with_metadata(mut self, metadata: ModelMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Encodes `src` into memory for the decoder (`src_len × d_model`).
+    /// Padding tokens are masked out of self-attention.
+    pub fn encode(&self, src: &[usize]) -> Result<Matrix> {
+        self.encoder.forward(src, self.source_mask(src)?.as_deref())
+    }
+
+    /// Mean of the encoder outputs over the non-padding positions of `src`
+    /// (`1 × d_model`). An all-padding input averages every position.
+    pub fn mean_pooled_encoding(&self, src: &[usize]) -> Result<Matrix> {
+        let encoded = self.encode(src)?;
+        let pad = self.config.pad_token_id;
+        let keep: Vec<usize> = (0..src.len()).filter(|&i| src[i] != pad).collect();
+        if keep.is_empty() {
+            return Ok(encoded.column_means());
+        }
+        Ok(encoded.select_rows(&keep)?.column_means())
+    }
+
+    /// Sentence embedding of `src`: the mean of its non-padding encoder
+    /// outputs, `d_model` values. See
+    /// [`embed_with_config`](Self::embed_with_config) for other poolings
+    /// and unit-length output.
+    pub fn embed(&self, src: &[usize]) -> Result<Vec<f64>> {
+        self.embed_with_config(src, &EmbedConfig::default())
+    }
+
+    /// Sentence embedding of `src` pooled and optionally L2-normalized as
+    /// set by `config`, for use as a search or retrieval vector.
+    pub fn embed_with_config(&self, src: &[usize], config: &EmbedConfig) -> Result<Vec<f64>> {
+        let pooled = config
+            .pooling
+            .pool(&self.encode(src)?, src, self.config.pad_token_id)?
+            .into_vec();
+        Ok(if config.normalize {
+            normalize(&pooled)
+        } else {
+            pooled
+        })
+    }
+
+    /// Sentence embeddings of many inputs, one mean-pooled row per input
+    /// (`inputs.len() × d_model`). Inputs may differ in length and contain
+    /// padding, which is masked out of attention and pooling.
+    pub fn embed_batch(&self, inputs: &[Vec<usize>]) -> Result<Matrix> {
+        if inputs.is_empty() {
+            return Ok(Matrix::zeros(0, self.config.d_model));
+        }
+        let rows = inputs
+            .iter()
+            .map(|src| self.mean_pooled_encoding(src))
+            .collect::<Result<Vec<_>>>()?;
+        Matrix::vstack(&rows.iter().collect::<Vec<_>>())
+    }
+
+    /// Encoder-only vocabulary logits for `src` (`src_len × vocab_size`),
+    /// projected through the transposed source embedding. See
+    /// [`Encoder::lm_logits`].
+    pub fn encoder_lm_logits(&self, src: &[usize]) -> Result<Matrix> {
+        self.encoder.masked_lm_logits(src, self.config.pad_token_id)
+    }
+
+    /// Encoder-only pseudo-perplexity of `src`, masking one position at a
+    /// time with `mask_token`. See [`Encoder::pseudo_perplexity`].
+    pub fn pseudo_perplexity(&self, src: &[usize], mask_token: usize) -> Result<f64> {
+        self.encoder
+            .pseudo_perplexity(src, mask_token, self.config.pad_token_id)
+    }
+
+    /// Decodes `tgt` against `memory`, the encoding of `src`, returning
+    /// logits (`tgt_len × vocab_size`). Each position only sees earlier
+    /// positions, and padding in `src` is hidden from cross-attention.
+    pub fn decode(&self, tgt: &[usize], src: &[usize], memory: &Matrix) -> Result<Matrix> {
+        self.decoder.forward(
+            tgt,
+            memory,
+            self.target_mask(tgt)?.as_deref(),
+            self.memory_mask(tgt, src)?.as_deref(),
+        )
+    }
+
+    /// Like [`decode`](Self::decode), also returning every decoder layer's
+    /// attention probabilities.
+    pub fn decode_with_attentions(
+        &self,
+        tgt: &[usize],
+        src: &[usize],
+        memory: &Matrix,
+    ) -> Result<(Matrix, Vec<DecoderLayerAttentions>)> {
+        self.decoder.forward_with_attentions(
+            tgt,
+            memory,
+            self.target_mask(tgt)?.as_deref(),
+            self.memory_mask(tgt, src)?.as_deref(),
+        )
+    }
+
+    /// Incremental [`decode`](Self::decode): `tgt` is the whole target so
+    /// far, of which only the positions after the `cache.len()` already
+    /// decoded are run through the decoder, returning their logits. Start
+    /// with [`Decoder::start_kv_cache`] on `memory`, the encoding of `src`.
+    pub fn decode_incremental(
+        &self,
+        tgt: &[usize],
+        src: &[usize],
+        cache: &mut DecoderKvCache,
+    ) -> Result<Matrix> {
+        Ok(self.decode_incremental_inner(tgt, src, cache, false)?.0)
+    }
+
+    /// Like [`decode_incremental`](Self::decode_incremental), also returning
+    /// every decoder layer's attention probabilities for the new positions.
+    pub fn decode_incremental_with_attentions(
+        &self,
+        tgt: &[usize],
+        src: &[usize],
+        cache: &mut DecoderKvCache,
+    ) -> Result<(Matrix, Vec<DecoderLayerAttentions>)> {
+        self.decode_incremental_inner(tgt, src, cache, true)
+    }
+
+    fn decode_incremental_inner(
+        &self,
+        tgt: &[usize],
+        src: &[usize],
+        cache: &mut DecoderKvCache,
+        weights: bool,
+    ) -> Result<(Matrix, Vec<DecoderLayerAttentions>)> {
+        let start = cache.len();
+        if start > tgt.len() {
+            return Err(TransformerError::shape(format!(
+                "key/value cache holds {} positions, target has {}",
+                start,
+                tgt.len()
+            )));
+        }
+        let new = &tgt[start..];
+        let tgt_mask = self.target_mask_from(tgt, start)?;
+        self.decoder.incremental(
+            new,
+            cache,
+            tgt_mask.as_deref(),
+            self.memory_mask(new, src)?.as_deref(),
+            weights,
+        )
+    }
+
+    /// Runs the encoder on `src` and the decoder on `tgt`, returning logits.
+    pub fn forward(&self, src: &[usize], tgt: &[usize]) -> Result<Matrix> {
+        let memory = self.encode(src)?;
+        self.decode(tgt, src, &memory)
+    }
+
+    /// Like [`forward`](Self::forward), also returning the attention
+    /// probabilities of every layer and head.
+    pub fn forward_with_attentions(
+        &self,
+        src: &[usize],
+        tgt: &[usize],
+    ) -> Result<(Matrix, TransformerAttentions)> {
+        let (memory, encoder) = self
+            .encoder
+            .forward_with_attentions(src, self.source_mask(src)?.as_deref())?;
+        let (logits, decoder) = self.decoder.forward_with_attentions(
+            tgt,
+            &memory,
+            self.target_mask(tgt)?.as_deref(),
+            self.memory_mask(tgt, src)?.as_deref(),
+        )?;
+        Ok((logits, TransformerAttentions { encoder, decoder }))
+    }
+
+    /// Like [`forward`](Self::forward), also returning the hidden state
+    /// before and after every encoder and decoder layer.
+    pub fn forward_with_hidden_states(
+        &self,
+        src: &[usize],
+        tgt: &[usize],
+    ) -> Result<(Matrix, TransformerHiddenStates)> {
+        let (memory, encoder) = self
+            .encoder
+            .forward_with_hidden_states(src, self.source_mask(src)?.as_deref())?;
+        let (logits, decoder) = self.decoder.forward_with_hidden_states(
+            tgt,
+            &memory,
+            self.target_mask(tgt)?.as_deref(),
+            self.memory_mask(tgt, src)?.as_deref(),
+        )?;
+        Ok((logits, TransformerHiddenStates { encoder, decoder }))
+    }
+
+    /// Like [`forward`](Self::forward), also returning the intermediate
+    /// values [`backward`](Self::backward) needs. Dropout masks are drawn as
+    /// in `forward` and recorded; hooks are not fired.
+    pub fn forward_with_cache(
+        &self,
+        src: &[usize],
+        tgt: &[usize],
+    ) -> Result<(Matrix, TransformerCache)> {
+        let (memory, encoder) = self
+            .encoder
+            .forward_with_cache(src, self.source_mask(src)?.as_deref())?;
+        let (logits, decoder) = self.decoder.forward_with_cache(
+            tgt,
+            &memory,
+            self.target_mask(tgt)?.as_deref(),
+            self.memory_mask(tgt, src)?.as_deref(),
+        )?;
+        Ok((logits, TransformerCache { encoder, decoder }))
+    }
+
+    /// Gradients of every weight, keyed by parameter name, given the
+    /// gradient `d_logits` of a loss with respect to the logits of the pass
+    /// that produced `cache`.
+    pub fn backward(&self, cache: &TransformerCache, d_logits: &Matrix) -> Result<Gradients> {
+        let mut grads = Gradients::new();
+        self.accumulate_gradients(cache, d_logits, &mut grads)?;
+        Ok(grads)
+    }
+
+    /// Like [`backward`](Self::backward), adding into `grads` so gradients
+    /// of several examples can be summed. With `share_embeddings` the
+    /// decoder's embedding gradient is added to `encoder.embedding`.
+    pub fn accumulate_gradients(
+        &self,
+        cache: &TransformerCache,
+        d_logits: &Matrix,
+        grads: &mut Gradients,
+    ) -> Result<()> {
+        let tgt_len = cache.decoder.tokens.len();
+        d_logits.ensure_shape((tgt_len, self.config.vocab_size), "logit gradient")?;
+        let mut decoder_grads = Gradients::new();
+        let d_memory =
+            self.decoder
+                .backward(&cache.decoder, d_logits, "decoder", &mut decoder_grads)?;
+        if self.config.share_embeddings {
+            if let Some(tied) = decoder_grads.remove("decoder.embedding") {
+                decoder_grads.accumulate("encoder.embedding", &tied)?;
+            }
+        }
+        for (name, grad) in decoder_grads.iter() {
+            grads.accumulate(name, grad)?;
+        }
+        self.encoder
+            .backward(&cache.encoder, &d_memory, "encoder", grads)
+    }
+
+    /// Generates up to `max_length` tokens (including the start token) by
+    /// sampling from the decoder's output distribution, drawing from the
+    /// model's [`rng`](Self::rng). Output never exceeds `max_seq_len`, and
+    /// longer sources are left-truncated.
+    pub fn generate(&self, src: &[usize], max_length: usize) -> Result<Vec<usize>> {
+        self.generate_with_rng(src, max_length, &mut self.rng.fork())
+    }
+
+    /// Like [`generate`](Self::generate), sampling from `rng`.
+    pub fn generate_with_rng(
+        &self,
+        src: &[usize],
+        max_length: usize,
+        rng: &mut Rng,
+    ) -> Result<Vec<usize>> {
+        self.generate_with_config_rng(src, &Self::length_config(max_length), rng)
+    }
+
+    /// Like [`generate`](Self::generate), choosing each token under
+    /// `sampling`'s temperature, top-k and top-p settings.
+    pub fn generate_with_sampling(
+        &self,
+        src: &[usize],
+        max_length: usize,
+        sampling: &SamplingConfig,
+    ) -> Result<Vec<usize>> {
+        self.generate_with_config(
+            src,
+            &Self::length_config(max_length).with_sampling_config(sampling.clone()),
+        )
+    }
+
+    /// Generates up to `max_length` tokens (including the start token) by
+    /// always choosing the most likely next token. Uses no randomness, so
+    /// the same source always yields the same output.
+    pub fn generate_greedy(&self, src: &[usize], max_length: usize) -> Result<Vec<usize>> {
+        self.generate_with_config(src, &Self::length_config(max_length).with_sampling(false))
+    }
+
+    /// Generates from `src` under the limits of `generation`, drawing any
+    /// samples from the model's [`rng`](Self::rng). The returned sequence
+    /// starts with the decoder prefix (by default the start token).
+    pub fn generate_with_config(
+        &self,
+        src: &[usize],
+        generation: &GenerationConfig,
+    ) -> Result<Vec<usize>> {
+        self.generate_with_config_rng(src, generation, &mut self.rng.fork())
+    }
+
+    /// Like [`generate_with_config`](Self::generate_with_config), sampling
+    /// from `rng`.
+    pub fn generate_with_config_rng(
+        &self,
+        src: &[usize],
+        generation: &GenerationConfig,
+        rng: &mut Rng,
+    ) -> Result<Vec<usize>> {
+        Ok(self.generate_output_rng(src, generation, rng)?.tokens)
+    }
+
+    /// Like [`generate_with_config`](Self::generate_with_config), also
+    /// reporting why decoding stopped and the log-probability of the output.
+    pub fn generate_output(
+        &self,
+        src: &[usize],
+        generation: &GenerationConfig,
+    ) -> Result<GenerationOutput> {
+        self.generate_output_rng(src, generation, &mut self.rng.fork())
+    }
+
+    /// Like [`generate_output`](Self::generate_output), sampling from `rng`.
+    pub fn generate_output_rng(
+        &self,
+        src: &[usize],
+        generation: &GenerationConfig,
+        rng: &mut Rng,
+    ) -> Result<GenerationOutput> {
+        generation.sampling.validate()?;
+        self.decode_loop(src, generation, |probs| {
+            let next = if generation.do_sample {
+                let logits: Vec<f64> = probs.iter().map(|p| p.ln()).collect();
+                generation.sampling.pick(&logits, rng)
+            } else {
+                argmax_row(probs)
+            };
+            next.ok_or_else(|| "decoder produced no finite logits".into())
+        })
+    }
+
+    /// Limits matching the `max_length` argument of [`generate`](Self::generate).
+    fn length_config(max_length: usize) -> GenerationConfig {
+        GenerationConfig::new()
+            .with_max_new_tokens(max_length.saturating_sub(1))
+            .with_max_length(max_length)
+    }
+
+    /// Autoregressive decoding from the decoder prefix, letting `pick` choose
+    /// each next token from the temperature-scaled distribution over the
+    /// vocabulary. Stops after the end token or when a limit or budget of
+    /// `generation` is reached.
+    fn decode_loop(
+        &self,
+        src: &[usize],
+        generation: &GenerationConfig,
+        mut pick: impl FnMut(&[f64]) -> Result<usize>,
+    ) -> Result<GenerationOutput> {
+        // Only read the clock for a deadline; wasm32-unknown-unknown has none.
+        let start = generation.time_limit.map(|_| Instant::now());
+        let src = generation.truncate(src, self.config.max_seq_len)?;
+        let prefix = generation.prefix(
+            self.config.bos_token_id,
+            self.config.vocab_size,
+            self.config.max_seq_len,
+        )?;
+        let prefix_len = prefix.len();
+        let mut session = InferenceSession::with_prefix(self, src, prefix)?;
+        let max_length = generation.length_limit(prefix_len, self.config.max_seq_len);
+        let mut log_prob = 0.0;
+
+        let finish_reason = loop {
+            if generation.is_cancelled() {
+                break FinishReason::Cancelled;
+            }
+            if generation
+                .time_limit
+                .zip(start)
+                .is_some_and(|(limit, start)| start.elapsed() >= limit)
+            {
+                break FinishReason::Deadline;
+            }
+            if session.tokens().len() >= max_length {
+                break FinishReason::Length;
+            }
+            let mut last = session.next_logits()?.to_vec();
+            if let Some(bad_words) = &generation.bad_words {
+                bad_words.mask_logits(session.new_tokens(), &mut last);
+            }
+            let probs = softmax(&last);
+            let next = pick(&probs)?;
+            log_prob += probs.get(next).map_or(f64::NEG_INFINITY, |p| p.ln());
+
+            session.push(next)?;
+            if next == self.config.eos_token_id {
+                break FinishReason::Stop;
+            }
+            if generation
+                .max_logprob_drop
+                .is_some_and(|drop| log_prob < -drop)
+            {
+                break FinishReason::LogProbBudget;
+            }
+        };
+        Ok(GenerationOutput {
+            new_tokens: session.new_tokens().len(),
+            tokens: session.tokens().to_vec(),
+            finish_reason,
+            log_prob,
+        })
+    }
+
+    /// Encoder self-attention mask hiding padding tokens.
+    fn source_mask(&self, src: &[usize]) -> Result<Option<Arc<Mask>>> {
+        AttentionMask::padding(src, self.config.pad_token_id).materialize(src.len(), src.len())
+    }
+
+    /// Cross-attention mask hiding source padding from every target position.
+    fn memory_mask(&self, tgt: &[usize], src: &[usize]) -> Result<Option<Arc<Mask>>> {
+        AttentionMask::padding(src, self.config.pad_token_id).materialize(tgt.len(), src.len())
+    }
+
+    /// Decoder self-attention mask hiding future positions and padding. The
+    /// first position holds the start token and is never hidden, even when
+    /// it shares the padding id (as in Marian).
+    fn target_mask(&self, tgt: &[usize]) -> Result<Option<Arc<Mask>>> {
+        self.target_mask_from(tgt, 0)
+    }
+
+    /// The rows of [`target_mask`](Self::target_mask) for positions
+    /// `start..tgt.len()`.
+    fn target_mask_from(&self, tgt: &[usize], start: usize) -> Result<Option<Arc<Mask>>> {
+        let keep = tgt
+            .iter()
+            .enumerate()
+            .map(|(i, &t)| i == 0 || t != self.config.pad_token_id)
+            .collect();
+        let mask = AttentionMask::Causal.and(AttentionMask::Padding(keep));
+        if start == 0 {
+            return mask.materialize(tgt.len(), tgt.len());
+        }
+        let rows = Matrix::from_fn(tgt.len() - start, tgt.len(), |i, j| {
+            mask.allows(start + i, j)
+        });
+        Ok((!rows.as_slice().iter().all(|&k| k)).then(|| Arc::new(rows)))
+    }
+
+    /// Registers a forward hook. `pattern` is a full module name starting
+    /// with `encoder`, `decoder` or `*` (both stacks), for example
+    /// `decoder.layers.*.cross_attention`. See [`crate::hooks`] for the
+    /// available hook points.
+    pub fn register_forward_hook<F>(&mut self, pattern: &str, hook: F) -> Result<HookHandle>
+    where
+        F: Fn(&str, &Matrix, &mut Matrix) + Send + Sync + 'static,
+    {
+        let (stack, rest) = pattern.split_once('.').unwrap_or((pattern, ""));
+        if rest.is_empty() || !matches!(stack, "encoder" | "decoder" | "*") {
+            return Err(TransformerError::config(format!(
+                "hook pattern '{}' must start with 'encoder.', 'decoder.' or '*.'",
+                pattern
+            )));
+        }
+        let hook: ForwardHook = Arc::new(hook);
+        let handle = HookHandle::next();
+        for (name, hooks) in [
+            ("encoder", &mut self.encoder.hooks),
+            ("decoder", &mut self.decoder.hooks),
+        ] {
+            if stack == name || stack == "*" {
+                // Stacks fire with relative names; report the full one.
+                let hook = hook.clone();
+                hooks.insert(
+                    handle,
+                    rest,
+                    Arc::new(move |local: &str, input: &Matrix, output: &mut Matrix| {
+                        hook(&join_name(name, local), input, output)
+                    }),
+                );
+            }
+        }
+        Ok(handle)
+    }
+
+    /// Removes a hook registered with
+    /// [`register_forward_hook`](Self::register_forward_hook).
+    pub fn remove_hook(&mut self, handle: HookHandle) -> bool {
+        let encoder = self.encoder.hooks.remove(handle);
+        self.decoder.hooks.remove(handle) || encoder
+    }
+
+    /// Removes every hook from both stacks.
+    pub fn clear_hooks(&mut self) {
+        self.encoder.hooks.clear();
+        self.decoder.hooks.clear();
+    }
+
+    /// Switches dropout on (`true`) or off (`false`) throughout the model.
+    pub fn set_training(&mut self, training: bool) {
+        self.encoder.set_training(training);
+        self.decoder.set_training(training);
+    }
+
+    /// Makes dropout and sampling draw from `rng`.
+    pub fn set_rng(&mut self, rng: SharedRng) {
+        self.encoder.set_rng(&rng);
+        self.decoder.set_rng(&rng);
+        self.rng = rng;
+    }
+
+    /// Gives the model a fresh stream seeded with `seed`, detached from any
+    /// clones, so dropout and sampling can be replayed exactly.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.set_rng(SharedRng::seed_from_u64(seed));
+    }
+
+    /// Whether dropout is currently active.
+    pub fn is_training(&self) -> bool {
+        self.encoder.dropout.training
+    }
+}
+
+/// With `share_embeddings` the table is visited once, as
+/// `encoder.embedding`; the decoder's `decoder.embedding` entry is skipped.
+impl Parameters for Transformer {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.encoder
+            .visit_parameters(&join_name(prefix, "encoder"), visitor);
+        let tied = join_name(prefix, "decoder.embedding");
+        let shared = self.config.share_embeddings;
+        self.decoder
+            .visit_parameters(&join_name(prefix, "decoder"), &mut |name, m| {
+                if !(shared && name == tied) {
+                    visitor(name, m);
+                }
+            });
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        if !self.config.share_embeddings {
+            self.encoder
+                .visit_parameters_mut(&join_name(prefix, "encoder"), visitor);
+            self.decoder
+                .visit_parameters_mut(&join_name(prefix, "decoder"), visitor);
+            return;
+        }
+        // Detach the decoder's handle so the encoder's table is updated in
+        // place rather than copied, then share it again.
+        let placeholder = Embedding::from_weight(Matrix::zeros(0, 0));
+        self.decoder.embedding.share_weight(&placeholder);
+        self.encoder
+            .visit_parameters_mut(&join_name(prefix, "encoder"), visitor);
+        let tied = join_name(prefix, "decoder.embedding");
+        self.decoder
+            .visit_parameters_mut(&join_name(prefix, "decoder"), &mut |name, m| {
+                if name != tied {
+                    visitor(name, m);
+                }
+            });
+        self.decoder.embedding.share_weight(&self.encoder.embedding);
+    }
+}
@@ -0,0 +1,252 @@
+//! Running externally trained models from ONNX files.
+//!
+//! [`OnnxModel`] parses a serialized `ModelProto` and interprets its graph
+//! with the crate's own kernels: `MatMul` and `Gemm` use [`Matrix::matmul`],
+//! `Softmax` uses [`softmax_rows`], `LayerNormalization` uses [`LayerNorm`]
+//! and `Erf` the error function behind [`GELUExact`]. The supported operator
+//! set is deliberately small, covering what transformer encoders exported
+//! with static shapes consist of:
+//!
+//! - `MatMul`, `Gemm`, `Add`, `Sub`, `Mul`, `Div`, `Pow` (NumPy broadcasting)
+//! - `Softmax`, `LayerNormalization`, `ReduceMean` over the last axis
+//! - `Gather` along axis 0 (embedding lookup)
+//! - `Erf`, `Tanh`, `Relu`, `Sigmoid`, `Sqrt`, `Exp`, `Neg`
+//! - `Reshape`, `Transpose`, `Unsqueeze`, `Squeeze`, `Identity`, `Cast`,
+//!   `Constant`
+//!
+//! Any other operator is reported when the model is loaded, not halfway
+//! through a run. All values are held as `f64`, integer tensors included.
+//!
+//! [`softmax_rows`]: crate::utils::tensor_ops::softmax_rows
+//! [`LayerNorm`]: crate::layers::LayerNorm
+//! [`GELUExact`]: crate::layers::GELUExact
+
+mod ops;
+mod proto;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::tensor::Matrix;
+use crate::Result;
+use proto::Graph;
+
+/// Operators [`OnnxModel`] can execute.
+pub const SUPPORTED_OPS: &[&str] = &[
+    "Add",
+    "Cast",
+    "Constant",
+    "Div",
+    "Erf",
+    "Exp",
+    "Gather",
+    "Gemm",
+    "Identity",
+    "LayerNormalization",
+    "MatMul",
+    "Mul",
+    "Neg",
+    "Pow",
+    "ReduceMean",
+    "Relu",
+    "Reshape",
+    "Sigmoid",
+    "Softmax",
+    "Sqrt",
+    "Squeeze",
+    "Sub",
+    "Tanh",
+    "Transpose",
+    "Unsqueeze",
+];
+
+/// A dense tensor of any rank, stored row-major.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnnxTensor {
+    pub shape: Vec<usize>,
+    pub data: Vec<f64>,
+}
+
+impl OnnxTensor {
+    pub fn new(shape: Vec<usize>, data: Vec<f64>) -> Result<Self> {
+        let len: usize = shape.iter().product();
+        if len != data.len() {
+            return Err(
+                format!("shape {:?} needs {} values, got {}", shape, len, data.len()).into(),
+            );
+        }
+        Ok(Self { shape, data })
+    }
+
+    pub fn scalar(value: f64) -> Self {
+        Self {
+            shape: Vec::new(),
+            data: vec![value],
+        }
+    }
+
+    /// A `[1, n]` tensor of token ids, the usual encoder input.
+    pub fn from_tokens(tokens: &[usize]) -> Self {
+        Self {
+            shape: vec![1, tokens.len()],
+            data: tokens.iter().map(|&t| t as f64).collect(),
+        }
+    }
+
+    pub fn from_matrix(matrix: &Matrix) -> Self {
+        Self {
+            shape: vec![matrix.rows(), matrix.cols()],
+            data: matrix.as_slice().to_vec(),
+        }
+    }
+
+    pub fn rank(&self) -> usize {
+        self.shape.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Views the tensor as a matrix with the last axis as columns and every
+    /// other axis folded into rows.
+    pub fn to_matrix(&self) -> Result<Matrix> {
+        let cols = self.shape.last().copied().unwrap_or(1);
+        let rows = self.len().checked_div(cols).unwrap_or(0);
+        Matrix::from_vec(rows, cols, self.data.clone())
+    }
+}
+
+/// An ONNX graph ready to run.
+#[derive(Debug, Clone)]
+pub struct OnnxModel {
+    graph: Graph,
+    initializers: HashMap<String, OnnxTensor>,
+}
+
+impl OnnxModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    /// Decodes a serialized `ModelProto`, failing if the graph uses an
+    /// operator outside [`SUPPORTED_OPS`].
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let graph = proto::parse_model(bytes)?;
+        let mut unsupported: Vec<&str> = graph
+            .nodes
+            .iter()
+            .filter(|n| {
+                !(n.domain.is_empty() || n.domain == "ai.onnx")
+                    || !SUPPORTED_OPS.contains(&n.op_type.as_str())
+            })
+            .map(|n| n.op_type.as_str())
+            .collect();
+        if !unsupported.is_empty() {
+            unsupported.sort_unstable();
+            unsupported.dedup();
+            return Err(format!("unsupported ONNX operators: {}", unsupported.join(", ")).into());
+        }
+        let initializers = graph.initializers.iter().cloned().collect();
+        Ok(Self {
+            graph,
+            initializers,
+        })
+    }
+
+    /// Names of the inputs a caller must provide. Older exporters list
+    /// initializers as inputs too; those are left out.
+    pub fn inputs(&self) -> Vec<&str> {
+        self.graph
+            .inputs
+            .iter()
+            .filter(|name| !self.initializers.contains_key(*name))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<&str> {
+        self.graph.outputs.iter().map(String::as_str).collect()
+    }
+
+    /// Weight tensors stored in the model, by name.
+    pub fn initializers(&self) -> &HashMap<String, OnnxTensor> {
+        &self.initializers
+    }
+
+    /// Executes the graph and returns every graph output by name.
+    pub fn run(&self, inputs: &[(&str, OnnxTensor)]) -> Result<HashMap<String, OnnxTensor>> {
+        let mut values: HashMap<&str, OnnxTensor> = inputs
+            .iter()
+            .map(|(name, tensor)| (*name, tensor.clone()))
+            .collect();
+        for name in self.inputs() {
+            if !values.contains_key(name) {
+                return Err(format!("missing ONNX input '{}'", name).into());
+            }
+        }
+        for node in &self.graph.nodes {
+            let args = node
+                .inputs
+                .iter()
+                .map(|name| {
+                    if name.is_empty() {
+                        // Omitted optional input.
+                        return Ok(None);
+                    }
+                    values
+                        .get(name.as_str())
+                        .or_else(|| self.initializers.get(name))
+                        .map(Some)
+                        .ok_or_else(|| {
+                            format!("node '{}' reads undefined value '{}'", node.name, name)
+                        })
+                })
+                .collect::<std::result::Result<Vec<_>, String>>()?;
+            let outputs = ops::execute(node, &args)
+                .map_err(|e| format!("{} node '{}': {}", node.op_type, node.name, e))?;
+            for (name, tensor) in node.outputs.iter().zip(outputs) {
+                values.insert(name.as_str(), tensor);
+            }
+        }
+        self.graph
+            .outputs
+            .iter()
+            .map(|name| {
+                let tensor = values
+                    .get(name.as_str())
+                    .or_else(|| self.initializers.get(name))
+                    .ok_or_else(|| format!("graph output '{}' was never computed", name))?;
+                Ok((name.clone(), tensor.clone()))
+            })
+            .collect()
+    }
+
+    /// Runs a single-input encoder on `tokens` (fed as a `[1, n]` tensor)
+    /// and returns its first output as `n × hidden`.
+    pub fn encode(&self, tokens: &[usize]) -> Result<Matrix> {
+        let inputs = self.inputs();
+        let [input] = inputs.as_slice() else {
+            return Err(format!(
+                "encode needs a model with one input, this one has {}",
+                inputs.len()
+            )
+            .into());
+        };
+        let output = self
+            .graph
+            .outputs
+            .first()
+            .ok_or("ONNX model has no outputs")?;
+        let mut outputs = self.run(&[(input, OnnxTensor::from_tokens(tokens))])?;
+        outputs
+            .remove(output)
+            .ok_or("ONNX model produced no output")?
+            .to_matrix()
+    }
+}
@@ -0,0 +1,480 @@
+//! Kernels of the supported ONNX operators.
+
+use super::proto::{Attribute, Node};
+use super::OnnxTensor;
+use crate::layers::activation::erf;
+use crate::layers::LayerNorm;
+use crate::tensor::Matrix;
+use crate::utils::tensor_ops::softmax_rows;
+use crate::Result;
+
+/// Runs `node` on its (possibly omitted) inputs.
+pub(super) fn execute(node: &Node, args: &[Option<&OnnxTensor>]) -> Result<Vec<OnnxTensor>> {
+    let arg = |i: usize| -> Result<&OnnxTensor> {
+        args.get(i)
+            .copied()
+            .flatten()
+            .ok_or_else(|| format!("missing input {}", i).into())
+    };
+    let out = match node.op_type.as_str() {
+        "Add" => binary(arg(0)?, arg(1)?, |a, b| a + b)?,
+        "Sub" => binary(arg(0)?, arg(1)?, |a, b| a - b)?,
+        "Mul" => binary(arg(0)?, arg(1)?, |a, b| a * b)?,
+        "Div" => binary(arg(0)?, arg(1)?, |a, b| a / b)?,
+        "Pow" => binary(arg(0)?, arg(1)?, f64::powf)?,
+        "Erf" => unary(arg(0)?, erf),
+        "Tanh" => unary(arg(0)?, f64::tanh),
+        "Relu" => unary(arg(0)?, |v| v.max(0.0)),
+        "Sigmoid" => unary(arg(0)?, |v| 1.0 / (1.0 + (-v).exp())),
+        "Sqrt" => unary(arg(0)?, f64::sqrt),
+        "Exp" => unary(arg(0)?, f64::exp),
+        "Neg" => unary(arg(0)?, |v| -v),
+        "Identity" => arg(0)?.clone(),
+        "Cast" => cast(arg(0)?, node.int("to", 1)),
+        "Constant" => constant(node)?,
+        "MatMul" => matmul(arg(0)?, arg(1)?)?,
+        "Gemm" => gemm(node, arg(0)?, arg(1)?, args.get(2).copied().flatten())?,
+        "Softmax" => softmax(arg(0)?, node.int("axis", -1))?,
+        "LayerNormalization" => layer_norm(node, arg(0)?, arg(1)?, args.get(2).copied().flatten())?,
+        "ReduceMean" => {
+            let axes = match args.get(1).copied().flatten() {
+                Some(axes) => Some(to_ints(axes)),
+                None => node.ints("axes").map(<[i64]>::to_vec),
+            };
+            reduce_mean(arg(0)?, axes.as_deref(), node.int("keepdims", 1) != 0)?
+        }
+        "Gather" => gather(arg(0)?, arg(1)?, node.int("axis", 0))?,
+        "Reshape" => reshape(arg(0)?, &to_ints(arg(1)?), node.int("allowzero", 0) != 0)?,
+        "Transpose" => transpose(arg(0)?, node.ints("perm"))?,
+        "Unsqueeze" => {
+            let axes = match args.get(1).copied().flatten() {
+                Some(axes) => to_ints(axes),
+                None => node.ints("axes").ok_or("missing axes")?.to_vec(),
+            };
+            unsqueeze(arg(0)?, &axes)?
+        }
+        "Squeeze" => {
+            let axes = match args.get(1).copied().flatten() {
+                Some(axes) => Some(to_ints(axes)),
+                None => node.ints("axes").map(<[i64]>::to_vec),
+            };
+            squeeze(arg(0)?, axes.as_deref())?
+        }
+        other => return Err(format!("operator {} is not supported", other).into()),
+    };
+    Ok(vec![out])
+}
+
+fn to_ints(t: &OnnxTensor) -> Vec<i64> {
+    t.data.iter().map(|&v| v as i64).collect()
+}
+
+/// Resolves a possibly negative axis against `rank`.
+fn axis(axis: i64, rank: usize) -> Result<usize> {
+    let resolved = if axis < 0 { axis + rank as i64 } else { axis };
+    if resolved < 0 || resolved as usize >= rank.max(1) {
+        return Err(format!("axis {} out of range for rank {}", axis, rank).into());
+    }
+    Ok(resolved as usize)
+}
+
+fn strides(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for i in (0..shape.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    strides
+}
+
+fn unary(x: &OnnxTensor, f: impl Fn(f64) -> f64) -> OnnxTensor {
+    OnnxTensor {
+        shape: x.shape.clone(),
+        data: x.data.iter().map(|&v| f(v)).collect(),
+    }
+}
+
+/// NumPy-style broadcast of two shapes.
+fn broadcast_shape(a: &[usize], b: &[usize]) -> Result<Vec<usize>> {
+    let rank = a.len().max(b.len());
+    (0..rank)
+        .map(|i| {
+            let da = if i + a.len() >= rank {
+                a[i + a.len() - rank]
+            } else {
+                1
+            };
+            let db = if i + b.len() >= rank {
+                b[i + b.len() - rank]
+            } else {
+                1
+            };
+            match (da, db) {
+                (x, y) if x == y => Ok(x),
+                (1, y) => Ok(y),
+                (x, 1) => Ok(x),
+                _ => Err(format!("shapes {:?} and {:?} do not broadcast", a, b).into()),
+            }
+        })
+        .collect()
+}
+
+/// Strides that read `shape` as if broadcast to `target` (0 on expanded axes).
+fn broadcast_strides(shape: &[usize], target: &[usize]) -> Vec<usize> {
+    let own = strides(shape);
+    let offset = target.len() - shape.len();
+    (0..target.len())
+        .map(|i| {
+            if i < offset || shape[i - offset] == 1 {
+                0
+            } else {
+                own[i - offset]
+            }
+        })
+        .collect()
+}
+
+/// Calls `f` with the flat source offsets of every element of `target`.
+fn for_each_broadcast(target: &[usize], sources: &[&[usize]], mut f: impl FnMut(&[usize])) {
+    let len: usize = target.iter().product();
+    let strides: Vec<Vec<usize>> = sources
+        .iter()
+        .map(|s| broadcast_strides(s, target))
+        .collect();
+    let mut index = vec![0usize; target.len()];
+    let mut offsets = vec![0usize; sources.len()];
+    for _ in 0..len {
+        f(&offsets);
+        for d in (0..target.len()).rev() {
+            index[d] += 1;
+            for (o, s) in offsets.iter_mut().zip(&strides) {
+                *o += s[d];
+            }
+            if index[d] < target[d] {
+                break;
+            }
+            for (o, s) in offsets.iter_mut().zip(&strides) {
+                *o -= s[d] * index[d];
+            }
+            index[d] = 0;
+        }
+    }
+}
+
+fn binary(a: &OnnxTensor, b: &OnnxTensor, f: impl Fn(f64, f64) -> f64) -> Result<OnnxTensor> {
+    if a.shape == b.shape {
+        let data = a.data.iter().zip(&b.data).map(|(&x, &y)| f(x, y)).collect();
+        return Ok(OnnxTensor {
+            shape: a.shape.clone(),
+            data,
+        });
+    }
+    let shape = broadcast_shape(&a.shape, &b.shape)?;
+    let mut data = Vec::with_capacity(shape.iter().product());
+    for_each_broadcast(&shape, &[&a.shape, &b.shape], |o| {
+        data.push(f(a.data[o[0]], b.data[o[1]]))
+    });
+    Ok(OnnxTensor { shape, data })
+}
+
+fn cast(x: &OnnxTensor, to: i64) -> OnnxTensor {
+    // TensorProto.DataType: integers and bool truncate, floats keep values.
+    match to {
+        2..=7 | 12 | 13 => unary(x, f64::trunc),
+        9 => unary(x, |v| (v != 0.0) as u8 as f64),
+        _ => x.clone(),
+    }
+}
+
+fn constant(node: &Node) -> Result<OnnxTensor> {
+    let value = |name| node.attribute(name);
+    Ok(
+        match (value("value"), value("value_float"), value("value_floats")) {
+            (Some(Attribute::Tensor(t)), _, _) => t.clone(),
+            (_, Some(Attribute::Float(v)), _) => OnnxTensor::scalar(*v),
+            (_, _, Some(Attribute::Floats(v))) => OnnxTensor::new(vec![v.len()], v.clone())?,
+            _ => match (value("value_int"), value("value_ints")) {
+                (Some(Attribute::Int(v)), _) => OnnxTensor::scalar(*v as f64),
+                (_, Some(Attribute::Ints(v))) => {
+                    OnnxTensor::new(vec![v.len()], v.iter().map(|&i| i as f64).collect())?
+                }
+                _ => return Err("Constant needs a value attribute".into()),
+            },
+        },
+    )
+}
+
+/// Batched matrix product with NumPy semantics for 1-D operands and
+/// broadcast batch dimensions.
+fn matmul(a: &OnnxTensor, b: &OnnxTensor) -> Result<OnnxTensor> {
+    if a.rank() == 0 || b.rank() == 0 {
+        return Err("MatMul operands must have at least one dimension".into());
+    }
+    let a_shape = if a.rank() == 1 {
+        vec![1, a.shape[0]]
+    } else {
+        a.shape.clone()
+    };
+    let b_shape = if b.rank() == 1 {
+        vec![b.shape[0], 1]
+    } else {
+        b.shape.clone()
+    };
+    let (m, k) = (a_shape[a_shape.len() - 2], a_shape[a_shape.len() - 1]);
+    let (k2, n) = (b_shape[b_shape.len() - 2], b_shape[b_shape.len() - 1]);
+    if k != k2 {
+        return Err(format!("cannot multiply {:?} by {:?}", a.shape, b.shape).into());
+    }
+    let a_batch = &a_shape[..a_shape.len() - 2];
+    let b_batch = &b_shape[..b_shape.len() - 2];
+    let batch = broadcast_shape(a_batch, b_batch)?;
+
+    let mut data = Vec::with_capacity(batch.iter().product::<usize>() * m * n);
+    let mut result = Ok(());
+    for_each_broadcast(&batch, &[a_batch, b_batch], |o| {
+        if result.is_err() {
+            return;
+        }
+        let lhs = Matrix::from_vec(m, k, a.data[o[0] * m * k..(o[0] + 1) * m * k].to_vec());
+        let rhs = Matrix::from_vec(k, n, b.data[o[1] * k * n..(o[1] + 1) * k * n].to_vec());
+        match lhs.and_then(|l| l.matmul(&rhs?)) {
+            Ok(product) => data.extend_from_slice(product.as_slice()),
+            Err(e) => result = Err(e),
+        }
+    });
+    result?;
+
+    let mut shape = batch;
+    if a.rank() > 1 {
+        shape.push(m);
+    }
+    if b.rank() > 1 {
+        shape.push(n);
+    }
+    OnnxTensor::new(shape, data)
+}
+
+fn gemm(node: &Node, a: &OnnxTensor, b: &OnnxTensor, c: Option<&OnnxTensor>) -> Result<OnnxTensor> {
+    if a.rank() != 2 || b.rank() != 2 {
+        return Err("Gemm operands must be matrices".into());
+    }
+    let mut lhs = a.to_matrix()?;
+    let mut rhs = b.to_matrix()?;
+    if node.int("transA", 0) != 0 {
+        lhs = lhs.transpose();
+    }
+    if node.int("transB", 0) != 0 {
+        rhs = rhs.transpose();
+    }
+    let product = lhs.matmul(&rhs)?.scale(node.float("alpha", 1.0));
+    let out = OnnxTensor::from_matrix(&product);
+    match c {
+        Some(c) => {
+            let beta = node.float("beta", 1.0);
+            binary(&out, c, |x, y| x + beta * y)
+        }
+        None => Ok(out),
+    }
+}
+
+fn require_last_axis(op: &str, requested: i64, rank: usize) -> Result<()> {
+    if axis(requested, rank)? + 1 != rank {
+        return Err(format!("{} is only supported over the last axis", op).into());
+    }
+    Ok(())
+}
+
+fn softmax(x: &OnnxTensor, requested: i64) -> Result<OnnxTensor> {
+    require_last_axis("Softmax", requested, x.rank())?;
+    Ok(OnnxTensor {
+        shape: x.shape.clone(),
+        data: softmax_rows(&x.to_matrix()?).as_slice().to_vec(),
+    })
+}
+
+fn layer_norm(
+    node: &Node,
+    x: &OnnxTensor,
+    scale: &OnnxTensor,
+    bias: Option<&OnnxTensor>,
+) -> Result<OnnxTensor> {
+    require_last_axis("LayerNormalization", node.int("axis", -1), x.rank())?;
+    let d = x.shape.last().copied().unwrap_or(1);
+    let row = |t: &OnnxTensor| Matrix::from_vec(1, d, t.data.clone());
+    let norm = LayerNorm {
+        gamma: row(scale)?,
+        beta: match bias {
+            Some(bias) => row(bias)?,
+            None => Matrix::zeros(1, d),
+        },
+        eps: node.float("epsilon", 1e-5),
+    };
+    Ok(OnnxTensor {
+        shape: x.shape.clone(),
+        data: norm.forward(&x.to_matrix()?)?.as_slice().to_vec(),
+    })
+}
+
+fn reduce_mean(x: &OnnxTensor, axes: Option<&[i64]>, keepdims: bool) -> Result<OnnxTensor> {
+    let rank = x.rank();
+    let mut reduced = vec![false; rank];
+    match axes {
+        Some(axes) if !axes.is_empty() => {
+            for &a in axes {
+                reduced[axis(a, rank)?] = true;
+            }
+        }
+        _ => reduced.iter_mut().for_each(|r| *r = true),
+    }
+    let kept: Vec<usize> = x
+        .shape
+        .iter()
+        .zip(&reduced)
+        .map(|(&d, &r)| if r { 1 } else { d })
+        .collect();
+    let out_len: usize = kept.iter().product();
+    let mut sums = vec![0.0; out_len];
+    // Broadcasting the kept shape over the input maps every input element
+    // to the output it is averaged into.
+    let mut values = x.data.iter();
+    for_each_broadcast(&x.shape, &[&kept], |o| {
+        sums[o[0]] += values.next().copied().unwrap_or(0.0)
+    });
+    let count = (x.len() / out_len.max(1)) as f64;
+    let data = sums.into_iter().map(|s| s / count).collect();
+    let shape = if keepdims {
+        kept
+    } else {
+        x.shape
+            .iter()
+            .zip(&reduced)
+            .filter(|(_, &r)| !r)
+            .map(|(&d, _)| d)
+            .collect()
+    };
+    OnnxTensor::new(shape, data)
+}
+
+fn gather(data: &OnnxTensor, indices: &OnnxTensor, requested: i64) -> Result<OnnxTensor> {
+    if axis(requested, data.rank())? != 0 {
+        return Err("Gather is only supported along axis 0".into());
+    }
+    let rows = *data
+        .shape
+        .first()
+        .ok_or("Gather needs a non-scalar input")?;
+    let inner: usize = data.shape[1..].iter().product();
+    let mut out = Vec::with_capacity(indices.len() * inner);
+    for &index in &indices.data {
+        let i = index as i64;
+        let i = if i < 0 { i + rows as i64 } else { i };
+        if i < 0 || i as usize >= rows {
+            return Err(format!("index {} out of range for {} rows", index, rows).into());
+        }
+        let i = i as usize;
+        out.extend_from_slice(&data.data[i * inner..(i + 1) * inner]);
+    }
+    let mut shape = indices.shape.clone();
+    shape.extend_from_slice(&data.shape[1..]);
+    OnnxTensor::new(shape, out)
+}
+
+fn reshape(x: &OnnxTensor, target: &[i64], allow_zero: bool) -> Result<OnnxTensor> {
+    let mut shape = Vec::with_capacity(target.len());
+    let mut infer = None;
+    for (i, &d) in target.iter().enumerate() {
+        match d {
+            -1 if infer.is_none() => {
+                infer = Some(i);
+                shape.push(1);
+            }
+            0 if !allow_zero => {
+                shape.push(*x.shape.get(i).ok_or("Reshape copies a missing dimension")?)
+            }
+            d if d >= 0 => shape.push(d as usize),
+            _ => return Err(format!("invalid Reshape target {:?}", target).into()),
+        }
+    }
+    if let Some(i) = infer {
+        let known: usize = shape.iter().product();
+        if known == 0 || !x.len().is_multiple_of(known) {
+            return Err(format!("cannot reshape {:?} to {:?}", x.shape, target).into());
+        }
+        shape[i] = x.len() / known;
+    }
+    OnnxTensor::new(shape, x.data.clone())
+}
+
+fn transpose(x: &OnnxTensor, perm: Option<&[i64]>) -> Result<OnnxTensor> {
+    let rank = x.rank();
+    let perm: Vec<usize> = match perm {
+        Some(p) => p.iter().map(|&a| axis(a, rank)).collect::<Result<_>>()?,
+        None => (0..rank).rev().collect(),
+    };
+    let mut seen = vec![false; rank];
+    if perm.len() != rank || perm.iter().any(|&p| std::mem::replace(&mut seen[p], true)) {
+        return Err(format!("invalid permutation {:?} for rank {}", perm, rank).into());
+    }
+    let shape: Vec<usize> = perm.iter().map(|&p| x.shape[p]).collect();
+    let source = strides(&x.shape);
+    let permuted: Vec<usize> = perm.iter().map(|&p| source[p]).collect();
+    // Walk the output in order, reading the input through permuted strides.
+    let mut data = Vec::with_capacity(x.len());
+    let mut index = vec![0usize; rank];
+    let mut offset = 0;
+    for _ in 0..x.len() {
+        data.push(x.data[offset]);
+        for d in (0..rank).rev() {
+            index[d] += 1;
+            offset += permuted[d];
+            if index[d] < shape[d] {
+                break;
+            }
+            offset -= permuted[d] * index[d];
+            index[d] = 0;
+        }
+    }
+    OnnxTensor::new(shape, data)
+}
+
+fn unsqueeze(x: &OnnxTensor, axes: &[i64]) -> Result<OnnxTensor> {
+    let rank = x.rank() + axes.len();
+    let mut inserted = vec![false; rank];
+    for &a in axes {
+        inserted[axis(a, rank)?] = true;
+    }
+    let mut dims = x.shape.iter();
+    let shape = inserted
+        .iter()
+        .map(|&new| if new { 1 } else { *dims.next().unwrap_or(&1) })
+        .collect();
+    OnnxTensor::new(shape, x.data.clone())
+}
+
+fn squeeze(x: &OnnxTensor, axes: Option<&[i64]>) -> Result<OnnxTensor> {
+    let rank = x.rank();
+    let mut removed = vec![false; rank];
+    match axes {
+        Some(axes) => {
+            for &a in axes {
+                let a = axis(a, rank)?;
+                if x.shape[a] != 1 {
+                    return Err(format!("cannot squeeze axis {} of size {}", a, x.shape[a]).into());
+                }
+                removed[a] = true;
+            }
+        }
+        None => {
+            for (r, &d) in removed.iter_mut().zip(&x.shape) {
+                *r = d == 1;
+            }
+        }
+    }
+    let shape = x
+        .shape
+        .iter()
+        .zip(&removed)
+        .filter(|(_, &r)| !r)
+        .map(|(&d, _)| d)
+        .collect();
+    OnnxTensor::new(shape, x.data.clone())
+}
@@ -0,0 +1,379 @@
+//! Decoding of the ONNX protobuf messages the importer needs.
+//!
+//! Only the fields used for inference are read; everything else, including
+//! unknown fields, is skipped according to its wire type.
+
+use super::OnnxTensor;
+use crate::quantization::f16_to_f32;
+use crate::Result;
+
+/// A graph in topological order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Graph {
+    pub nodes: Vec<Node>,
+    pub initializers: Vec<(String, OnnxTensor)>,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Node {
+    pub name: String,
+    pub op_type: String,
+    pub domain: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub attributes: Vec<(String, Attribute)>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Attribute {
+    Float(f64),
+    Int(i64),
+    /// Strings and graphs, which no supported operator reads.
+    Other,
+    Tensor(OnnxTensor),
+    Floats(Vec<f64>),
+    Ints(Vec<i64>),
+}
+
+impl Node {
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, a)| a)
+    }
+
+    pub fn int(&self, name: &str, default: i64) -> i64 {
+        match self.attribute(name) {
+            Some(Attribute::Int(v)) => *v,
+            _ => default,
+        }
+    }
+
+    pub fn float(&self, name: &str, default: f64) -> f64 {
+        match self.attribute(name) {
+            Some(Attribute::Float(v)) => *v,
+            _ => default,
+        }
+    }
+
+    pub fn ints(&self, name: &str) -> Option<&[i64]> {
+        match self.attribute(name) {
+            Some(Attribute::Ints(v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the graph of a serialized `ModelProto`.
+pub(crate) fn parse_model(bytes: &[u8]) -> Result<Graph> {
+    let mut graph = None;
+    for field in Fields::new(bytes) {
+        let (number, value) = field?;
+        if number == 7 {
+            graph = Some(parse_graph(value.bytes()?)?);
+        }
+    }
+    graph.ok_or_else(|| "ONNX model has no graph".into())
+}
+
+fn parse_graph(bytes: &[u8]) -> Result<Graph> {
+    let mut graph = Graph::default();
+    for field in Fields::new(bytes) {
+        let (number, value) = field?;
+        match number {
+            1 => graph.nodes.push(parse_node(value.bytes()?)?),
+            5 => graph.initializers.push(parse_tensor(value.bytes()?)?),
+            11 => graph.inputs.push(parse_value_info(value.bytes()?)?),
+            12 => graph.outputs.push(parse_value_info(value.bytes()?)?),
+            _ => {}
+        }
+    }
+    Ok(graph)
+}
+
+fn parse_value_info(bytes: &[u8]) -> Result<String> {
+    for field in Fields::new(bytes) {
+        let (number, value) = field?;
+        if number == 1 {
+            return value.string();
+        }
+    }
+    Ok(String::new())
+}
+
+fn parse_node(bytes: &[u8]) -> Result<Node> {
+    let mut node = Node::default();
+    for field in Fields::new(bytes) {
+        let (number, value) = field?;
+        match number {
+            1 => node.inputs.push(value.string()?),
+            2 => node.outputs.push(value.string()?),
+            3 => node.name = value.string()?,
+            4 => node.op_type = value.string()?,
+            5 => node.attributes.push(parse_attribute(value.bytes()?)?),
+            7 => node.domain = value.string()?,
+            _ => {}
+        }
+    }
+    Ok(node)
+}
+
+fn parse_attribute(bytes: &[u8]) -> Result<(String, Attribute)> {
+    let mut name = String::new();
+    let mut scalar = None;
+    let mut floats = Vec::new();
+    let mut ints = Vec::new();
+    let mut kind = 0;
+    for field in Fields::new(bytes) {
+        let (number, value) = field?;
+        match number {
+            1 => name = value.string()?,
+            2 => scalar = Some(Attribute::Float(value.fixed32()? as f64)),
+            3 => scalar = Some(Attribute::Int(value.varint()? as i64)),
+            4 => scalar = Some(Attribute::Other),
+            5 => scalar = Some(Attribute::Tensor(parse_tensor(value.bytes()?)?.1)),
+            7 => value.repeated_fixed32(&mut floats)?,
+            8 => value.repeated_varint(&mut ints)?,
+            20 => kind = value.varint()?,
+            _ => {}
+        }
+    }
+    // AttributeProto.type: FLOATS = 6, INTS = 7.
+    let attribute = match (kind, scalar) {
+        (6, _) => Attribute::Floats(floats.into_iter().map(f64::from).collect()),
+        (7, _) => Attribute::Ints(ints.into_iter().map(|v| v as i64).collect()),
+        (_, Some(scalar)) => scalar,
+        _ if !floats.is_empty() => Attribute::Floats(floats.into_iter().map(f64::from).collect()),
+        _ => Attribute::Ints(ints.into_iter().map(|v| v as i64).collect()),
+    };
+    Ok((name, attribute))
+}
+
+/// Element types of `TensorProto.data_type` the importer reads.
+const FLOAT: u64 = 1;
+const UINT8: u64 = 2;
+const INT8: u64 = 3;
+const INT32: u64 = 6;
+const INT64: u64 = 7;
+const BOOL: u64 = 9;
+const FLOAT16: u64 = 10;
+const DOUBLE: u64 = 11;
+
+fn parse_tensor(bytes: &[u8]) -> Result<(String, OnnxTensor)> {
+    let mut name = String::new();
+    let mut dims = Vec::new();
+    let mut data_type = 0;
+    let mut raw: Option<&[u8]> = None;
+    let mut floats = Vec::new();
+    let mut doubles = Vec::new();
+    let mut ints = Vec::new();
+    for field in Fields::new(bytes) {
+        let (number, value) = field?;
+        match number {
+            1 => value.repeated_varint(&mut dims)?,
+            2 => data_type = value.varint()?,
+            4 => value.repeated_fixed32(&mut floats)?,
+            5 | 7 => value.repeated_varint(&mut ints)?,
+            8 => name = value.string()?,
+            9 => raw = Some(value.bytes()?),
+            10 => value.repeated_fixed64(&mut doubles)?,
+            _ => {}
+        }
+    }
+    let shape: Vec<usize> = dims.iter().map(|&d| d as usize).collect();
+    let data: Vec<f64> = match raw {
+        Some(raw) => decode_raw(raw, data_type).map_err(|e| format!("tensor '{}': {}", name, e))?,
+        None => match data_type {
+            FLOAT | FLOAT16 => floats.into_iter().map(f64::from).collect(),
+            DOUBLE => doubles,
+            // int32_data holds INT32, INT8, UINT8 and BOOL as varints; int64
+            // values are sign-extended two's complement.
+            _ => ints.into_iter().map(|v| v as i64 as f64).collect(),
+        },
+    };
+    let tensor = OnnxTensor::new(shape, data).map_err(|e| format!("tensor '{}': {}", name, e))?;
+    Ok((name, tensor))
+}
+
+fn decode_raw(raw: &[u8], data_type: u64) -> Result<Vec<f64>> {
+    fn chunks<const N: usize>(raw: &[u8]) -> impl Iterator<Item = [u8; N]> + '_ {
+        raw.chunks_exact(N).map(|c| {
+            let mut out = [0u8; N];
+            out.copy_from_slice(c);
+            out
+        })
+    }
+    Ok(match data_type {
+        FLOAT => chunks::<4>(raw)
+            .map(|b| f32::from_le_bytes(b) as f64)
+            .collect(),
+        DOUBLE => chunks::<8>(raw).map(f64::from_le_bytes).collect(),
+        FLOAT16 => chunks::<2>(raw)
+            .map(|b| f16_to_f32(u16::from_le_bytes(b)) as f64)
+            .collect(),
+        INT64 => chunks::<8>(raw)
+            .map(|b| i64::from_le_bytes(b) as f64)
+            .collect(),
+        INT32 => chunks::<4>(raw)
+            .map(|b| i32::from_le_bytes(b) as f64)
+            .collect(),
+        INT8 => raw.iter().map(|&b| b as i8 as f64).collect(),
+        UINT8 | BOOL => raw.iter().map(|&b| b as f64).collect(),
+        other => return Err(format!("unsupported tensor data type {}", other).into()),
+    })
+}
+
+/// A field value, borrowed from the message.
+enum Value<'a> {
+    Varint(u64),
+    Fixed64([u8; 8]),
+    Bytes(&'a [u8]),
+    Fixed32([u8; 4]),
+}
+
+impl<'a> Value<'a> {
+    fn varint(&self) -> Result<u64> {
+        match self {
+            Value::Varint(v) => Ok(*v),
+            _ => Err("expected a varint field".into()),
+        }
+    }
+
+    fn fixed32(&self) -> Result<f32> {
+        match self {
+            Value::Fixed32(b) => Ok(f32::from_le_bytes(*b)),
+            _ => Err("expected a 32-bit field".into()),
+        }
+    }
+
+    fn bytes(&self) -> Result<&'a [u8]> {
+        match self {
+            Value::Bytes(b) => Ok(b),
+            _ => Err("expected a length-delimited field".into()),
+        }
+    }
+
+    fn string(&self) -> Result<String> {
+        Ok(std::str::from_utf8(self.bytes()?)?.to_string())
+    }
+
+    /// Appends a packed or single varint value.
+    fn repeated_varint(&self, out: &mut Vec<u64>) -> Result<()> {
+        match self {
+            Value::Varint(v) => out.push(*v),
+            Value::Bytes(b) => {
+                let mut pos = 0;
+                while pos < b.len() {
+                    out.push(read_varint(b, &mut pos)?);
+                }
+            }
+            _ => return Err("expected varint values".into()),
+        }
+        Ok(())
+    }
+
+    /// Appends packed or single `float` values.
+    fn repeated_fixed32(&self, out: &mut Vec<f32>) -> Result<()> {
+        match self {
+            Value::Fixed32(b) => out.push(f32::from_le_bytes(*b)),
+            Value::Bytes(b) if b.len() % 4 == 0 => out.extend(
+                b.chunks_exact(4)
+                    .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])),
+            ),
+            _ => return Err("expected float values".into()),
+        }
+        Ok(())
+    }
+
+    /// Appends packed or single `double` values.
+    fn repeated_fixed64(&self, out: &mut Vec<f64>) -> Result<()> {
+        match self {
+            Value::Fixed64(b) => out.push(f64::from_le_bytes(*b)),
+            Value::Bytes(b) if b.len() % 8 == 0 => out.extend(b.chunks_exact(8).map(|c| {
+                let mut a = [0u8; 8];
+                a.copy_from_slice(c);
+                f64::from_le_bytes(a)
+            })),
+            _ => return Err("expected double values".into()),
+        }
+        Ok(())
+    }
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or("truncated protobuf varint")?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("protobuf varint is too long".into())
+}
+
+/// Iterator over the `(field number, value)` pairs of a message.
+struct Fields<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&e| e <= self.bytes.len())
+            .ok_or("truncated protobuf message")?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn next_field(&mut self) -> Result<(u64, Value<'a>)> {
+        let key = read_varint(self.bytes, &mut self.pos)?;
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(self.bytes, &mut self.pos)?),
+            1 => {
+                let mut b = [0u8; 8];
+                b.copy_from_slice(self.take(8)?);
+                Value::Fixed64(b)
+            }
+            2 => {
+                let len = read_varint(self.bytes, &mut self.pos)? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                let mut b = [0u8; 4];
+                b.copy_from_slice(self.take(4)?);
+                Value::Fixed32(b)
+            }
+            other => return Err(format!("unsupported protobuf wire type {}", other).into()),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Value<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.bytes.len() {
+            return None;
+        }
+        let field = self.next_field();
+        if field.is_err() {
+            // Stop after the first error instead of reading garbage.
+            self.pos = self.bytes.len();
+        }
+        Some(field)
+    }
+}
@@ -0,0 +1,224 @@
+//! Adafactor (Shazeer & Stern, 2018).
+//!
+//! Second moments of a matrix are stored factored as one row and one column
+//! accumulator, so optimizer memory grows with `rows + cols` instead of
+//! `rows × cols`. Vectors (`1 × n` or `n × 1`) keep a full accumulator.
+
+use std::collections::HashMap;
+
+use super::{Optimizer, ParamGroups, UpdateSettings};
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// Hyperparameters of [`Adafactor`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdafactorConfig {
+    /// Learning rate, or with `relative_step` the cap on the `1/√t` schedule.
+    pub lr: f64,
+    /// Use the step size `min(lr, 1/√t)` instead of a constant rate.
+    pub relative_step: bool,
+    /// Multiply the step by the weight RMS (at least `eps2`).
+    pub scale_parameter: bool,
+    /// Regularizer added to squared gradients.
+    pub eps1: f64,
+    /// Lower bound on the weight RMS used by `scale_parameter`.
+    pub eps2: f64,
+    /// Updates are rescaled so their RMS does not exceed this value.
+    pub clip_threshold: f64,
+    /// Second-moment decay `β₂ₜ = 1 − t^decay_rate`.
+    pub decay_rate: f64,
+    /// First-moment coefficient; `None` keeps no momentum (the default).
+    pub beta1: Option<f64>,
+    /// Decoupled weight decay.
+    pub weight_decay: f64,
+}
+
+impl Default for AdafactorConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1e-2,
+            relative_step: true,
+            scale_parameter: true,
+            eps1: 1e-30,
+            eps2: 1e-3,
+            clip_threshold: 1.0,
+            decay_rate: -0.8,
+            beta1: None,
+            weight_decay: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SecondMoment {
+    Factored { row: Vec<f64>, col: Vec<f64> },
+    Full(Vec<f64>),
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    second: SecondMoment,
+    momentum: Option<Vec<f64>>,
+}
+
+/// The Adafactor optimizer.
+#[derive(Debug, Clone)]
+pub struct Adafactor {
+    pub config: AdafactorConfig,
+    pub groups: ParamGroups,
+    step: u64,
+    state: HashMap<String, State>,
+}
+
+impl Adafactor {
+    pub fn new(config: AdafactorConfig) -> Self {
+        Self {
+            config,
+            groups: ParamGroups::new(),
+            step: 0,
+            state: HashMap::new(),
+        }
+    }
+
+    pub fn with_param_groups(mut self, groups: ParamGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Number of steps taken so far.
+    pub fn steps(&self) -> u64 {
+        self.step
+    }
+
+    /// Scalars of optimizer state currently held, for memory accounting.
+    pub fn state_len(&self) -> usize {
+        self.state
+            .values()
+            .map(|s| {
+                let second = match &s.second {
+                    SecondMoment::Factored { row, col } => row.len() + col.len(),
+                    SecondMoment::Full(v) => v.len(),
+                };
+                second + s.momentum.as_ref().map_or(0, Vec::len)
+            })
+            .sum()
+    }
+}
+
+fn rms(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    (values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64).sqrt()
+}
+
+impl Optimizer for Adafactor {
+    fn learning_rate(&self) -> f64 {
+        self.config.lr
+    }
+
+    fn set_learning_rate(&mut self, lr: f64) {
+        self.config.lr = lr;
+    }
+
+    fn weight_decay(&self) -> f64 {
+        self.config.weight_decay
+    }
+
+    fn param_groups(&self) -> &ParamGroups {
+        &self.groups
+    }
+
+    fn param_groups_mut(&mut self) -> &mut ParamGroups {
+        &mut self.groups
+    }
+
+    fn begin_step(&mut self) {
+        self.step += 1;
+    }
+
+    fn update(
+        &mut self,
+        name: &str,
+        param: &mut Matrix,
+        grad: &Matrix,
+        settings: UpdateSettings,
+    ) -> Result<()> {
+        let config = self.config;
+        let t = self.step.max(1) as f64;
+        let (rows, cols) = grad.shape();
+        let factored = rows > 1 && cols > 1;
+        let state = self.state.entry(name.to_string()).or_insert_with(|| State {
+            second: if factored {
+                SecondMoment::Factored {
+                    row: vec![0.0; rows],
+                    col: vec![0.0; cols],
+                }
+            } else {
+                SecondMoment::Full(vec![0.0; grad.len()])
+            },
+            momentum: config.beta1.map(|_| vec![0.0; grad.len()]),
+        });
+
+        let beta2 = 1.0 - t.powf(config.decay_rate);
+        let squared: Vec<f64> = grad
+            .as_slice()
+            .iter()
+            .map(|g| g * g + config.eps1)
+            .collect();
+
+        // u = g / √v̂, with v̂ reconstructed from the factors when factored.
+        let mut update: Vec<f64> = match &mut state.second {
+            SecondMoment::Factored { row, col } => {
+                for (i, r) in row.iter_mut().enumerate() {
+                    let mean = squared[i * cols..(i + 1) * cols].iter().sum::<f64>() / cols as f64;
+                    *r = beta2 * *r + (1.0 - beta2) * mean;
+                }
+                for (j, c) in col.iter_mut().enumerate() {
+                    let mean = (0..rows).map(|i| squared[i * cols + j]).sum::<f64>() / rows as f64;
+                    *c = beta2 * *c + (1.0 - beta2) * mean;
+                }
+                let row_mean = row.iter().sum::<f64>() / rows as f64;
+                grad.as_slice()
+                    .iter()
+                    .enumerate()
+                    .map(|(k, g)| {
+                        let v = row[k / cols] * col[k % cols] / row_mean;
+                        g / v.sqrt()
+                    })
+                    .collect()
+            }
+            SecondMoment::Full(v) => v
+                .iter_mut()
+                .zip(grad.as_slice().iter().zip(&squared))
+                .map(|(v, (g, sq))| {
+                    *v = beta2 * *v + (1.0 - beta2) * sq;
+                    g / v.sqrt()
+                })
+                .collect(),
+        };
+
+        let clip = (rms(&update) / config.clip_threshold).max(1.0);
+        for u in &mut update {
+            *u /= clip;
+        }
+        if let (Some(beta1), Some(m)) = (config.beta1, state.momentum.as_mut()) {
+            for (m, u) in m.iter_mut().zip(&mut update) {
+                *m = beta1 * *m + (1.0 - beta1) * *u;
+                *u = *m;
+            }
+        }
+
+        let mut step_size = settings.lr;
+        if config.relative_step && config.lr > 0.0 {
+            step_size *= (1.0 / (config.lr * t.sqrt())).min(1.0);
+        }
+        if config.scale_parameter {
+            step_size *= rms(param.as_slice()).max(config.eps2);
+        }
+        for (w, u) in param.as_mut_slice().iter_mut().zip(&update) {
+            *w -= step_size * (u + settings.weight_decay * *w);
+        }
+        Ok(())
+    }
+}
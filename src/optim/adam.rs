@@ -0,0 +1,204 @@
+//! Adam (Kingma & Ba, 2015) and AdamW (Loshchilov & Hutter, 2019).
+
+use std::collections::HashMap;
+
+use super::quantized::QuantizedBuffer;
+use super::{Optimizer, ParamGroups, UpdateSettings};
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// How Adam stores its first and second moments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatePrecision {
+    /// One `f64` per moment entry.
+    #[default]
+    Full,
+    /// Block-wise quantized 8-bit codes with one scale per `block_size`
+    /// entries, cutting optimizer memory by roughly 8× relative to `f64`
+    /// (4× relative to `f32`).
+    Int8 { block_size: usize },
+}
+
+/// Hyperparameters of [`Adam`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdamConfig {
+    pub lr: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+    pub eps: f64,
+    pub weight_decay: f64,
+    /// Apply weight decay directly to the weights (AdamW) instead of adding
+    /// it to the gradient (Adam with L2 regularization).
+    pub decoupled_weight_decay: bool,
+    pub state_precision: StatePrecision,
+}
+
+impl Default for AdamConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1e-3,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            weight_decay: 0.0,
+            decoupled_weight_decay: false,
+            state_precision: StatePrecision::Full,
+        }
+    }
+}
+
+impl AdamConfig {
+    /// AdamW defaults: decoupled weight decay of `0.01`.
+    pub fn adamw() -> Self {
+        Self {
+            weight_decay: 0.01,
+            decoupled_weight_decay: true,
+            ..Self::default()
+        }
+    }
+
+    /// The same configuration with 8-bit moments in blocks of `block_size`.
+    pub fn with_8bit_state(mut self, block_size: usize) -> Self {
+        self.state_precision = StatePrecision::Int8 { block_size };
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Moments {
+    Full {
+        m: Vec<f64>,
+        v: Vec<f64>,
+    },
+    Int8 {
+        m: QuantizedBuffer,
+        v: QuantizedBuffer,
+    },
+}
+
+impl Moments {
+    fn size_in_bytes(&self) -> usize {
+        match self {
+            Moments::Full { m, v } => 8 * (m.len() + v.len()),
+            Moments::Int8 { m, v } => m.size_in_bytes() + v.size_in_bytes(),
+        }
+    }
+}
+
+/// The Adam optimizer, optionally with decoupled weight decay (AdamW).
+#[derive(Debug, Clone)]
+pub struct Adam {
+    pub config: AdamConfig,
+    pub groups: ParamGroups,
+    step: u64,
+    state: HashMap<String, Moments>,
+}
+
+impl Adam {
+    pub fn new(config: AdamConfig) -> Self {
+        Self {
+            config,
+            groups: ParamGroups::new(),
+            step: 0,
+            state: HashMap::new(),
+        }
+    }
+
+    pub fn with_param_groups(mut self, groups: ParamGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.step
+    }
+
+    /// Bytes held by the moment buffers.
+    pub fn state_size_in_bytes(&self) -> usize {
+        self.state.values().map(Moments::size_in_bytes).sum()
+    }
+}
+
+impl Optimizer for Adam {
+    fn learning_rate(&self) -> f64 {
+        self.config.lr
+    }
+
+    fn set_learning_rate(&mut self, lr: f64) {
+        self.config.lr = lr;
+    }
+
+    fn weight_decay(&self) -> f64 {
+        self.config.weight_decay
+    }
+
+    fn param_groups(&self) -> &ParamGroups {
+        &self.groups
+    }
+
+    fn param_groups_mut(&mut self) -> &mut ParamGroups {
+        &mut self.groups
+    }
+
+    fn begin_step(&mut self) {
+        self.step += 1;
+    }
+
+    fn update(
+        &mut self,
+        name: &str,
+        param: &mut Matrix,
+        grad: &Matrix,
+        settings: UpdateSettings,
+    ) -> Result<()> {
+        let config = self.config;
+        let len = grad.len();
+        let moments =
+            self.state
+                .entry(name.to_string())
+                .or_insert_with(|| match config.state_precision {
+                    StatePrecision::Full => Moments::Full {
+                        m: vec![0.0; len],
+                        v: vec![0.0; len],
+                    },
+                    StatePrecision::Int8 { block_size } => Moments::Int8 {
+                        m: QuantizedBuffer::zeros(len, block_size, true),
+                        v: QuantizedBuffer::zeros(len, block_size, false),
+                    },
+                });
+
+        let t = self.step.max(1) as i32;
+        let c1 = 1.0 - config.beta1.powi(t);
+        let c2 = 1.0 - config.beta2.powi(t);
+        let mut apply = |m: &mut [f64], v: &mut [f64]| {
+            for (((w, &g), m), v) in param
+                .as_mut_slice()
+                .iter_mut()
+                .zip(grad.as_slice())
+                .zip(m.iter_mut())
+                .zip(v.iter_mut())
+            {
+                let g = if config.decoupled_weight_decay {
+                    *w -= settings.lr * settings.weight_decay * *w;
+                    g
+                } else {
+                    g + settings.weight_decay * *w
+                };
+                *m = config.beta1 * *m + (1.0 - config.beta1) * g;
+                *v = config.beta2 * *v + (1.0 - config.beta2) * g * g;
+                *w -= settings.lr * (*m / c1) / ((*v / c2).sqrt() + config.eps);
+            }
+        };
+
+        match moments {
+            Moments::Full { m, v } => apply(m, v),
+            Moments::Int8 { m, v } => {
+                let (mut m_full, mut v_full) = (m.dequantize(), v.dequantize());
+                apply(&mut m_full, &mut v_full);
+                m.quantize_from(&m_full);
+                v.quantize_from(&v_full);
+            }
+        }
+        Ok(())
+    }
+}
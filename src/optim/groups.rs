@@ -0,0 +1,118 @@
+//! Parameter groups: per-weight learning-rate scales and weight decay.
+
+use crate::hooks::matches_pattern;
+
+/// Hyperparameter overrides for the weights matching any of `patterns`.
+///
+/// A pattern matches a weight if it matches the leading segments of its
+/// dotted name, with `*` standing for any single segment: `encoder.layers.0`
+/// covers every weight of the first encoder layer and `*.*.*.norm1` every
+/// first layer norm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamGroup {
+    pub name: String,
+    pub patterns: Vec<String>,
+    /// Multiplier applied to the optimizer's learning rate.
+    pub lr_scale: f64,
+    /// Replaces the optimizer's weight decay when set.
+    pub weight_decay: Option<f64>,
+}
+
+impl ParamGroup {
+    pub fn new(name: &str, patterns: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            lr_scale: 1.0,
+            weight_decay: None,
+        }
+    }
+
+    pub fn with_lr_scale(mut self, lr_scale: f64) -> Self {
+        self.lr_scale = lr_scale;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.weight_decay = Some(weight_decay);
+        self
+    }
+
+    /// Whether the weight called `name` belongs to this group.
+    pub fn matches(&self, name: &str) -> bool {
+        self.patterns.iter().any(|pattern| {
+            let depth = pattern.split('.').count();
+            let end = name
+                .match_indices('.')
+                .nth(depth - 1)
+                .map_or(name.len(), |(i, _)| i);
+            matches_pattern(pattern, &name[..end])
+        })
+    }
+}
+
+/// An ordered list of [`ParamGroup`]s. A weight belongs to the first group
+/// that matches it; unmatched weights use the optimizer defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParamGroups {
+    pub groups: Vec<ParamGroup>,
+}
+
+impl ParamGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, group: ParamGroup) {
+        self.groups.push(group);
+    }
+
+    pub fn with(mut self, group: ParamGroup) -> Self {
+        self.push(group);
+        self
+    }
+
+    /// The group the weight called `name` belongs to.
+    pub fn find(&self, name: &str) -> Option<&ParamGroup> {
+        self.groups.iter().find(|g| g.matches(name))
+    }
+
+    /// Layer-wise learning-rate decay (LLRD) for the stack under `stack`
+    /// (e.g. `encoder`) with `num_layers` layers.
+    ///
+    /// The top layer trains at the full rate and every layer below it at
+    /// `decay` times the rate of the layer above; the stack's embeddings sit
+    /// below layer 0, so layer `i` is scaled by `decay^(num_layers - 1 - i)`
+    /// and the token, learned position and segment embeddings and the
+    /// embedding norm by `decay^num_layers`. Weights outside the stack, and
+    /// the stack's output projection, are unaffected.
+    pub fn layer_wise_decay(stack: &str, num_layers: usize, decay: f64) -> Self {
+        let mut groups = Self::new();
+        for i in (0..num_layers).rev() {
+            let prefix = format!("{}.layers.{}", stack, i);
+            groups.push(
+                ParamGroup::new(&prefix, &[&prefix])
+                    .with_lr_scale(decay.powi((num_layers - 1 - i) as i32)),
+            );
+        }
+        let embeddings: Vec<String> = [
+            "embedding",
+            "positional",
+            "token_type_embedding",
+            "embedding_norm",
+        ]
+        .iter()
+        .map(|name| format!("{}.{}", stack, name))
+        .collect();
+        let patterns: Vec<&str> = embeddings.iter().map(String::as_str).collect();
+        groups.push(
+            ParamGroup::new(&embeddings[0], &patterns).with_lr_scale(decay.powi(num_layers as i32)),
+        );
+        groups
+    }
+
+    /// Appends the groups of `other`, which take lower precedence.
+    pub fn extend(&mut self, other: ParamGroups) {
+        self.groups.extend(other.groups);
+    }
+}
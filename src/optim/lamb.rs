@@ -0,0 +1,163 @@
+//! LAMB (You et al., 2019): Adam with a per-weight trust ratio.
+//!
+//! The Adam direction of every weight matrix is rescaled by
+//! `‖w‖ / ‖update‖`, so each layer moves by a step proportional to its own
+//! magnitude. This keeps training stable at the large batch sizes reached
+//! with gradient accumulation.
+
+use std::collections::HashMap;
+
+use super::{Optimizer, ParamGroups, UpdateSettings};
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// Hyperparameters of [`Lamb`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LambConfig {
+    pub lr: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+    pub eps: f64,
+    /// Decoupled weight decay, added to the direction before the trust ratio.
+    pub weight_decay: f64,
+    /// Upper bound on the trust ratio; `None` leaves it unbounded.
+    pub max_trust_ratio: Option<f64>,
+    /// Apply Adam bias correction to the moments.
+    pub bias_correction: bool,
+}
+
+impl Default for LambConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1e-3,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-6,
+            weight_decay: 0.01,
+            max_trust_ratio: Some(10.0),
+            bias_correction: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Moments {
+    m: Vec<f64>,
+    v: Vec<f64>,
+}
+
+/// The LAMB optimizer.
+#[derive(Debug, Clone)]
+pub struct Lamb {
+    pub config: LambConfig,
+    pub groups: ParamGroups,
+    step: u64,
+    state: HashMap<String, Moments>,
+    trust_ratios: HashMap<String, f64>,
+}
+
+impl Lamb {
+    pub fn new(config: LambConfig) -> Self {
+        Self {
+            config,
+            groups: ParamGroups::new(),
+            step: 0,
+            state: HashMap::new(),
+            trust_ratios: HashMap::new(),
+        }
+    }
+
+    pub fn with_param_groups(mut self, groups: ParamGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.step
+    }
+
+    /// Trust ratio applied to `name` in the most recent step.
+    pub fn trust_ratio(&self, name: &str) -> Option<f64> {
+        self.trust_ratios.get(name).copied()
+    }
+}
+
+impl Optimizer for Lamb {
+    fn learning_rate(&self) -> f64 {
+        self.config.lr
+    }
+
+    fn set_learning_rate(&mut self, lr: f64) {
+        self.config.lr = lr;
+    }
+
+    fn weight_decay(&self) -> f64 {
+        self.config.weight_decay
+    }
+
+    fn param_groups(&self) -> &ParamGroups {
+        &self.groups
+    }
+
+    fn param_groups_mut(&mut self) -> &mut ParamGroups {
+        &mut self.groups
+    }
+
+    fn begin_step(&mut self) {
+        self.step += 1;
+    }
+
+    fn update(
+        &mut self,
+        name: &str,
+        param: &mut Matrix,
+        grad: &Matrix,
+        settings: UpdateSettings,
+    ) -> Result<()> {
+        let config = self.config;
+        let t = self.step.max(1) as i32;
+        let (c1, c2) = if config.bias_correction {
+            (1.0 - config.beta1.powi(t), 1.0 - config.beta2.powi(t))
+        } else {
+            (1.0, 1.0)
+        };
+        let moments = self
+            .state
+            .entry(name.to_string())
+            .or_insert_with(|| Moments {
+                m: vec![0.0; grad.len()],
+                v: vec![0.0; grad.len()],
+            });
+
+        let mut direction = Vec::with_capacity(grad.len());
+        for (((m, v), &g), &w) in moments
+            .m
+            .iter_mut()
+            .zip(&mut moments.v)
+            .zip(grad.as_slice())
+            .zip(param.as_slice())
+        {
+            *m = config.beta1 * *m + (1.0 - config.beta1) * g;
+            *v = config.beta2 * *v + (1.0 - config.beta2) * g * g;
+            let adam = (*m / c1) / ((*v / c2).sqrt() + config.eps);
+            direction.push(adam + settings.weight_decay * w);
+        }
+
+        let weight_norm = param.norm();
+        let direction_norm = direction.iter().map(|d| d * d).sum::<f64>().sqrt();
+        let mut trust = if weight_norm > 0.0 && direction_norm > 0.0 {
+            weight_norm / direction_norm
+        } else {
+            1.0
+        };
+        if let Some(max) = config.max_trust_ratio {
+            trust = trust.min(max);
+        }
+        self.trust_ratios.insert(name.to_string(), trust);
+
+        for (w, d) in param.as_mut_slice().iter_mut().zip(&direction) {
+            *w -= settings.lr * trust * d;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! Optimizers that update [`Parameters`] from [`Gradients`].
+//!
+//! Every optimizer works on named weights, so any model exposing its
+//! weights through [`Parameters`] can be trained. Per-parameter learning
+//! rate scales and weight decay come from [`ParamGroups`], which is also how
+//! layer-wise learning-rate decay is configured; [`LrScheduler`]s vary the
+//! base rate over the course of training.
+//!
+//! A training step pairs [`Transformer::backward`] with [`Optimizer::step`]:
+//! the gradients it returns are keyed by the same names the model's
+//! [`Parameters`] visitor reports, so every sub-layer is updated. AdamW is
+//! [`Adam`] with [`AdamConfig::adamw`].
+//!
+//! [`Transformer::backward`]: crate::models::Transformer::backward
+
+pub mod adafactor;
+pub mod adam;
+pub mod groups;
+pub mod lamb;
+pub mod quantized;
+pub mod schedule;
+pub mod sgd;
+
+pub use adafactor::{Adafactor, AdafactorConfig};
+pub use adam::{Adam, AdamConfig, StatePrecision};
+pub use groups::{ParamGroup, ParamGroups};
+pub use lamb::{Lamb, LambConfig};
+pub use schedule::{ConstantLr, InverseSqrtLr, LrScheduler, WarmupCosineLr};
+pub use sgd::{Sgd, SgdConfig};
+
+use crate::params::{Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// Hyperparameters resolved for a single weight in the current step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpdateSettings {
+    /// Effective learning rate (base rate times the group scale).
+    pub lr: f64,
+    pub weight_decay: f64,
+}
+
+/// A gradient-based parameter update rule.
+pub trait Optimizer {
+    /// Base learning rate, before group scaling.
+    fn learning_rate(&self) -> f64;
+
+    fn set_learning_rate(&mut self, lr: f64);
+
+    /// Weight decay for parameters whose group does not override it.
+    fn weight_decay(&self) -> f64;
+
+    fn param_groups(&self) -> &ParamGroups;
+
+    fn param_groups_mut(&mut self) -> &mut ParamGroups;
+
+    /// Called once at the start of every [`step`](Self::step), e.g. to
+    /// advance bias-correction counters.
+    fn begin_step(&mut self) {}
+
+    /// Updates one weight in place.
+    fn update(
+        &mut self,
+        name: &str,
+        param: &mut Matrix,
+        grad: &Matrix,
+        settings: UpdateSettings,
+    ) -> Result<()>;
+
+    /// Resolves the hyperparameters of the weight called `name`.
+    fn settings_for(&self, name: &str) -> UpdateSettings {
+        let group = self.param_groups().find(name);
+        UpdateSettings {
+            lr: self.learning_rate() * group.map_or(1.0, |g| g.lr_scale),
+            weight_decay: group
+                .and_then(|g| g.weight_decay)
+                .unwrap_or_else(|| self.weight_decay()),
+        }
+    }
+
+    /// Applies one update to every weight of `model` that has a gradient.
+    fn step(&mut self, model: &mut dyn Parameters, grads: &Gradients) -> Result<()> {
+        self.begin_step();
+        let mut result = Ok(());
+        model.visit_parameters_mut("", &mut |name, param| {
+            if result.is_err() {
+                return;
+            }
+            if let Some(grad) = grads.get(name) {
+                let settings = self.settings_for(name);
+                result = grad
+                    .ensure_shape(param.shape(), name)
+                    .and_then(|_| self.update(name, param, grad, settings));
+            }
+        });
+        result
+    }
+}
@@ -0,0 +1,138 @@
+//! Block-wise 8-bit storage for optimizer state.
+//!
+//! Values are split into blocks, each normalized by its absolute maximum and
+//! mapped to the nearest entry of a 256-value dynamic code. The code spends
+//! few entries per decade close to zero and many close to ±1, so both the
+//! large and the tiny moments of a block keep a useful relative precision
+//! (Dettmers et al., 2022).
+
+use std::sync::OnceLock;
+
+/// A buffer of `f64` values stored as 8-bit codes plus one scale per block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedBuffer {
+    codes: Vec<u8>,
+    scales: Vec<f32>,
+    block_size: usize,
+    signed: bool,
+}
+
+impl QuantizedBuffer {
+    /// Zeros of length `len`. `signed` selects the code for values of either
+    /// sign (first moments) or non-negative values (second moments).
+    pub fn zeros(len: usize, block_size: usize, signed: bool) -> Self {
+        let block_size = block_size.max(1);
+        let zero = code_index(codebook(signed), 0.0);
+        Self {
+            codes: vec![zero; len],
+            scales: vec![0.0; len.div_ceil(block_size)],
+            block_size,
+            signed,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Bytes used by codes and scales.
+    pub fn size_in_bytes(&self) -> usize {
+        self.codes.len() + 4 * self.scales.len()
+    }
+
+    /// Decodes the buffer into `out`, which must have [`len`](Self::len) entries.
+    pub fn dequantize_into(&self, out: &mut [f64]) {
+        let code = codebook(self.signed);
+        for ((block, values), &scale) in self
+            .codes
+            .chunks(self.block_size)
+            .zip(out.chunks_mut(self.block_size))
+            .zip(&self.scales)
+        {
+            for (value, &c) in values.iter_mut().zip(block) {
+                *value = code[c as usize] * scale as f64;
+            }
+        }
+    }
+
+    pub fn dequantize(&self) -> Vec<f64> {
+        let mut out = vec![0.0; self.len()];
+        self.dequantize_into(&mut out);
+        out
+    }
+
+    /// Re-encodes the buffer from `values`, which must have [`len`](Self::len) entries.
+    pub fn quantize_from(&mut self, values: &[f64]) {
+        let code = codebook(self.signed);
+        for ((block, values), scale) in self
+            .codes
+            .chunks_mut(self.block_size)
+            .zip(values.chunks(self.block_size))
+            .zip(&mut self.scales)
+        {
+            let absmax = values.iter().fold(0.0f64, |m, v| m.max(v.abs())) as f32;
+            *scale = absmax;
+            for (c, &v) in block.iter_mut().zip(values) {
+                let normalized = if absmax > 0.0 { v / absmax as f64 } else { 0.0 };
+                *c = code_index(code, normalized);
+            }
+        }
+    }
+}
+
+/// The sorted 256-entry code for signed or non-negative values in `[-1, 1]`.
+fn codebook(signed: bool) -> &'static [f64; 256] {
+    static SIGNED: OnceLock<[f64; 256]> = OnceLock::new();
+    static UNSIGNED: OnceLock<[f64; 256]> = OnceLock::new();
+    let cell = if signed { &SIGNED } else { &UNSIGNED };
+    cell.get_or_init(|| build_codebook(signed))
+}
+
+/// Seven decades `10^-6 … 10^0`; decade `i` holds `2^i` (signed, per sign)
+/// or `2^(i+1)` (unsigned) evenly spaced values in `(0.1, 1] · 10^(i-6)`.
+/// With `0` and `1` the unsigned code has exactly 256 entries. The signed
+/// code is symmetric: it holds `±1` and drops one value per sign from the
+/// top decade, and since a symmetric code around zero has an odd number of
+/// entries, the spare 256th slot repeats `0`.
+fn build_codebook(signed: bool) -> [f64; 256] {
+    let mut values = if signed {
+        vec![0.0, 0.0, 1.0, -1.0]
+    } else {
+        vec![0.0, 1.0]
+    };
+    for i in 0..7 {
+        let count = match (signed, i) {
+            (true, 6) => (1 << i) - 1,
+            (true, _) => 1 << i,
+            (false, _) => 1 << (i + 1),
+        };
+        let scale = 10f64.powi(i - 6);
+        for k in 0..count {
+            let lo = 0.1 + 0.9 * k as f64 / count as f64;
+            let hi = 0.1 + 0.9 * (k + 1) as f64 / count as f64;
+            let mid = scale * (lo + hi) / 2.0;
+            values.push(mid);
+            if signed {
+                values.push(-mid);
+            }
+        }
+    }
+    values.sort_by(f64::total_cmp);
+    let mut code = [0.0; 256];
+    code.copy_from_slice(&values);
+    code
+}
+
+/// Index of the code entry nearest to `value`.
+fn code_index(code: &[f64; 256], value: f64) -> u8 {
+    let upper = code.partition_point(|&c| c < value).min(255);
+    if upper > 0 && (value - code[upper - 1]).abs() <= (code[upper] - value).abs() {
+        (upper - 1) as u8
+    } else {
+        upper as u8
+    }
+}
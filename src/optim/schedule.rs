@@ -0,0 +1,112 @@
+//! Learning-rate schedules.
+//!
+//! An [`LrScheduler`] maps the 1-based optimizer step to a base learning
+//! rate; [`apply`](LrScheduler::apply) writes it into an [`Optimizer`]
+//! before the step runs, and [`Trainer::with_scheduler`] does so for every
+//! batch. Parameter-group scales still multiply the scheduled rate.
+//!
+//! [`Trainer::with_scheduler`]: crate::training::Trainer::with_scheduler
+
+use std::f64::consts::PI;
+
+use super::Optimizer;
+
+/// Learning rate as a function of the optimizer step.
+pub trait LrScheduler {
+    /// Learning rate of step `step`, counting from 1.
+    fn lr_at(&self, step: usize) -> f64;
+
+    /// Sets `optimizer`'s learning rate for `step` and returns it.
+    fn apply(&self, optimizer: &mut dyn Optimizer, step: usize) -> f64 {
+        let lr = self.lr_at(step);
+        optimizer.set_learning_rate(lr);
+        lr
+    }
+}
+
+/// The same rate at every step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstantLr(pub f64);
+
+impl LrScheduler for ConstantLr {
+    fn lr_at(&self, _step: usize) -> f64 {
+        self.0
+    }
+}
+
+/// The schedule of Vaswani et al. (2017):
+/// `scale · d_model^−½ · min(t^−½, t · warmup^−3⁄2)`, rising linearly for
+/// `warmup_steps` and decaying with the inverse square root of the step
+/// afterwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InverseSqrtLr {
+    pub d_model: usize,
+    pub warmup_steps: usize,
+    /// Overall multiplier; the paper uses 1.
+    pub scale: f64,
+}
+
+impl InverseSqrtLr {
+    pub fn new(d_model: usize, warmup_steps: usize) -> Self {
+        Self {
+            d_model,
+            warmup_steps,
+            scale: 1.0,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl LrScheduler for InverseSqrtLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        let t = step.max(1) as f64;
+        let warmup = self.warmup_steps.max(1) as f64;
+        self.scale
+            * (self.d_model.max(1) as f64).powf(-0.5)
+            * t.powf(-0.5).min(t * warmup.powf(-1.5))
+    }
+}
+
+/// Linear warmup to `peak_lr` over `warmup_steps`, then half a cosine down
+/// to `min_lr` at `total_steps`; `min_lr` afterwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarmupCosineLr {
+    pub peak_lr: f64,
+    pub min_lr: f64,
+    pub warmup_steps: usize,
+    pub total_steps: usize,
+}
+
+impl WarmupCosineLr {
+    pub fn new(peak_lr: f64, warmup_steps: usize, total_steps: usize) -> Self {
+        Self {
+            peak_lr,
+            min_lr: 0.0,
+            warmup_steps,
+            total_steps,
+        }
+    }
+
+    pub fn with_min_lr(mut self, min_lr: f64) -> Self {
+        self.min_lr = min_lr;
+        self
+    }
+}
+
+impl LrScheduler for WarmupCosineLr {
+    fn lr_at(&self, step: usize) -> f64 {
+        if step < self.warmup_steps {
+            return self.peak_lr * step as f64 / self.warmup_steps as f64;
+        }
+        if step >= self.total_steps {
+            return self.min_lr;
+        }
+        let decay_steps = self.total_steps - self.warmup_steps;
+        let progress = (step - self.warmup_steps) as f64 / decay_steps as f64;
+        self.min_lr + 0.5 * (self.peak_lr - self.min_lr) * (1.0 + (PI * progress).cos())
+    }
+}
@@ -0,0 +1,99 @@
+//! Stochastic gradient descent with optional momentum.
+
+use std::collections::HashMap;
+
+use super::{Optimizer, ParamGroups, UpdateSettings};
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// Hyperparameters of [`Sgd`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SgdConfig {
+    pub lr: f64,
+    /// Heavy-ball momentum; `0.0` disables the velocity buffers.
+    pub momentum: f64,
+    /// L2 penalty added to the gradient.
+    pub weight_decay: f64,
+}
+
+impl Default for SgdConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1e-2,
+            momentum: 0.0,
+            weight_decay: 0.0,
+        }
+    }
+}
+
+/// `v ← μv + g + λw;  w ← w − lr·v`.
+#[derive(Debug, Clone)]
+pub struct Sgd {
+    pub config: SgdConfig,
+    pub groups: ParamGroups,
+    velocity: HashMap<String, Matrix>,
+}
+
+impl Sgd {
+    pub fn new(config: SgdConfig) -> Self {
+        Self {
+            config,
+            groups: ParamGroups::new(),
+            velocity: HashMap::new(),
+        }
+    }
+
+    pub fn with_param_groups(mut self, groups: ParamGroups) -> Self {
+        self.groups = groups;
+        self
+    }
+}
+
+impl Optimizer for Sgd {
+    fn learning_rate(&self) -> f64 {
+        self.config.lr
+    }
+
+    fn set_learning_rate(&mut self, lr: f64) {
+        self.config.lr = lr;
+    }
+
+    fn weight_decay(&self) -> f64 {
+        self.config.weight_decay
+    }
+
+    fn param_groups(&self) -> &ParamGroups {
+        &self.groups
+    }
+
+    fn param_groups_mut(&mut self) -> &mut ParamGroups {
+        &mut self.groups
+    }
+
+    fn update(
+        &mut self,
+        name: &str,
+        param: &mut Matrix,
+        grad: &Matrix,
+        settings: UpdateSettings,
+    ) -> Result<()> {
+        let UpdateSettings { lr, weight_decay } = settings;
+        let momentum = self.config.momentum;
+        let weights = param.as_mut_slice().iter_mut().zip(grad.as_slice());
+        if momentum == 0.0 {
+            for (w, &g) in weights {
+                *w -= lr * (g + weight_decay * *w);
+            }
+        } else {
+            let velocity = self
+                .velocity
+                .entry(name.to_string())
+                .or_insert_with(|| Matrix::zeros(grad.rows(), grad.cols()));
+            for ((w, &g), v) in weights.zip(velocity.as_mut_slice()) {
+                *v = momentum * *v + g + weight_decay * *w;
+                *w -= lr * *v;
+            }
+        }
+        Ok(())
+    }
+}
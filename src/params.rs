@@ -0,0 +1,137 @@
+//! Named access to model weights.
+//!
+//! Every layer implements [`Parameters`], exposing its weight matrices under
+//! stable dotted names such as `encoder.layers.0.self_attention.w_o`. The
+//! names are the contract used by fixture loading and other tooling that
+//! needs to address individual tensors.
+
+use std::collections::BTreeMap;
+
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// A module whose weights can be visited by name.
+pub trait Parameters {
+    /// Calls `visitor` with the full name and value of every weight.
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix));
+
+    /// Calls `visitor` with the full name and a mutable reference to every weight.
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix));
+
+    /// Total number of scalar weights.
+    fn num_parameters(&self) -> usize {
+        let mut total = 0;
+        self.visit_parameters("", &mut |_, m| total += m.len());
+        total
+    }
+
+    /// Names of every weight, in visiting order.
+    fn parameter_names(&self, prefix: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        self.visit_parameters(prefix, &mut |name, _| names.push(name.to_string()));
+        names
+    }
+}
+
+/// Joins a parameter prefix and a local name with a dot.
+pub fn join_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+/// Gradients (or any other per-weight tensors) keyed by parameter name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Gradients {
+    tensors: BTreeMap<String, Matrix>,
+}
+
+impl Gradients {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Zero gradients for every weight of `model`.
+    pub fn zeros_like(model: &dyn Parameters) -> Self {
+        let mut grads = Self::new();
+        model.visit_parameters("", &mut |name, m| {
+            grads.insert(name, Matrix::zeros(m.rows(), m.cols()));
+        });
+        grads
+    }
+
+    pub fn insert(&mut self, name: &str, grad: Matrix) {
+        self.tensors.insert(name.to_string(), grad);
+    }
+
+    /// Adds `grad` to the gradient stored under `name`, inserting it if absent.
+    pub fn accumulate(&mut self, name: &str, grad: &Matrix) -> Result<()> {
+        match self.tensors.get_mut(name) {
+            Some(existing) => existing.add_assign(grad),
+            None => {
+                self.insert(name, grad.clone());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Matrix> {
+        self.tensors.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Matrix> {
+        self.tensors.remove(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Matrix> {
+        self.tensors.get_mut(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tensors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tensors.is_empty()
+    }
+
+    /// Iterates over `(name, gradient)` in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Matrix)> {
+        self.tensors.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Matrix)> {
+        self.tensors.iter_mut().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Multiplies every gradient by `factor`.
+    pub fn scale(&mut self, factor: f64) {
+        for grad in self.tensors.values_mut() {
+            for v in grad.as_mut_slice() {
+                *v *= factor;
+            }
+        }
+    }
+
+    /// L2 norm over all gradients.
+    pub fn global_norm(&self) -> f64 {
+        self.tensors
+            .values()
+            .flat_map(|g| g.as_slice())
+            .map(|v| v * v)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Rescales all gradients so their global norm is at most `max_norm`.
+    /// Returns the norm before clipping.
+    pub fn clip_global_norm(&mut self, max_norm: f64) -> f64 {
+        let norm = self.global_norm();
+        if norm > max_norm && norm > 0.0 {
+            self.scale(max_norm / norm);
+        }
+        norm
+    }
+}
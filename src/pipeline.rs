@@ -0,0 +1,131 @@
+//! One-call text-to-text generation.
+//!
+//! A [`Pipeline`] owns a model, the tokenizer its vocabulary came from and
+//! the [`GenerationConfig`] to decode with, so applications can go from a
+//! prompt string to a reply string without handling token ids, masks or
+//! decoder prefixes themselves.
+
+use crate::generation::GenerationConfig;
+use crate::models::Transformer;
+use crate::tokenizer::Tokenizer;
+use crate::{Result, TransformerError};
+
+/// Speaker of a chat message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+}
+
+/// Renders `messages` as `role: content` lines followed by `assistant: `,
+/// the prompt [`Pipeline::chat`] encodes by default.
+pub fn format_chat(messages: &[ChatMessage]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        prompt.push_str(message.role.as_str());
+        prompt.push_str(": ");
+        prompt.push_str(&message.content);
+        prompt.push('\n');
+    }
+    prompt.push_str(Role::Assistant.as_str());
+    prompt.push_str(": ");
+    prompt
+}
+
+/// Tokenizer, model and decoding options behind a string API.
+#[derive(Debug, Clone)]
+pub struct Pipeline<T> {
+    pub model: Transformer,
+    pub tokenizer: T,
+    pub generation: GenerationConfig,
+    /// Builds the source text from a conversation.
+    pub chat_template: fn(&[ChatMessage]) -> String,
+}
+
+impl<T: Tokenizer> Pipeline<T> {
+    /// Fails if the tokenizer produces ids the model cannot embed.
+    pub fn new(model: Transformer, tokenizer: T) -> Result<Self> {
+        if tokenizer.vocab_size() > model.config().vocab_size {
+            return Err(TransformerError::config(format!(
+                "tokenizer vocabulary of {} does not fit the model's {}",
+                tokenizer.vocab_size(),
+                model.config().vocab_size
+            )));
+        }
+        Ok(Self {
+            model,
+            tokenizer,
+            generation: GenerationConfig::default(),
+            chat_template: format_chat,
+        })
+    }
+
+    pub fn with_generation(mut self, generation: GenerationConfig) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub fn with_chat_template(mut self, template: fn(&[ChatMessage]) -> String) -> Self {
+        self.chat_template = template;
+        self
+    }
+
+    /// Generates a reply to `input`. The decoder prefix is not part of
+    /// the returned text and special tokens are dropped by the tokenizer.
+    pub fn run(&self, input: &str) -> Result<String> {
+        let src = self.tokenizer.encode(input)?;
+        let config = self.model.config();
+        let prefix_len = self
+            .generation
+            .prefix(config.bos_token_id, config.vocab_size, config.max_seq_len)?
+            .len();
+        let output = self.model.generate_with_config(&src, &self.generation)?;
+        self.tokenizer.decode(&output[prefix_len..])
+    }
+
+    /// Generates the assistant's next message for a conversation, encoding
+    /// it with [`chat_template`](Self::chat_template). Over-long histories
+    /// are truncated by the generation config, which drops the oldest turns
+    /// first by default.
+    pub fn chat(&self, messages: &[ChatMessage]) -> Result<String> {
+        self.run(&(self.chat_template)(messages))
+    }
+}
@@ -0,0 +1,113 @@
+//! Progress reporting for long-running operations.
+//!
+//! Functions taking a [`ProgressHandler`] call it after every unit of work
+//! with the current [`Stage`] and how much of it is done, so command-line
+//! tools and GUIs can draw progress bars. Any `Fn(&Progress)` closure is a
+//! handler; [`NoProgress`] ignores every report.
+//!
+//! Reporting operations:
+//!
+//! - [`load_parameters_with_progress`] and
+//!   [`load_gguf_with_progress`]: one step per parameter
+//! - [`to_gguf_with_progress`]: one step per tensor written or quantized
+//! - [`encode_corpus`]: one step per document
+//! - [`DataParallelTrainer::train_epoch_with_progress`]: one step per batch
+//!
+//! [`load_parameters_with_progress`]: crate::testing::load_parameters_with_progress
+//! [`load_gguf_with_progress`]: crate::gguf::load_gguf_with_progress
+//! [`to_gguf_with_progress`]: crate::gguf::to_gguf_with_progress
+//! [`encode_corpus`]: crate::tokenizer::encode_corpus
+//! [`DataParallelTrainer::train_epoch_with_progress`]: crate::distributed::DataParallelTrainer::train_epoch_with_progress
+
+use std::fmt;
+
+/// The operation a [`Progress`] report belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    LoadingWeights,
+    Quantizing,
+    Tokenizing,
+    /// Batches of the given training epoch.
+    Training {
+        epoch: u64,
+    },
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::LoadingWeights => f.write_str("loading weights"),
+            Stage::Quantizing => f.write_str("quantizing"),
+            Stage::Tokenizing => f.write_str("tokenizing"),
+            Stage::Training { epoch } => write!(f, "training epoch {}", epoch),
+        }
+    }
+}
+
+/// How far an operation has got: `completed` of `total` steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub stage: Stage,
+    pub completed: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn new(stage: Stage, completed: usize, total: usize) -> Self {
+        Self {
+            stage,
+            completed,
+            total,
+        }
+    }
+
+    /// Completed share of the work in `[0, 1]`; an empty operation counts
+    /// as done.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.completed as f64 / self.total as f64).min(1.0)
+        }
+    }
+
+    pub fn percent(&self) -> f64 {
+        100.0 * self.fraction()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.completed >= self.total
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}/{} ({:.1}%)",
+            self.stage,
+            self.completed,
+            self.total,
+            self.percent()
+        )
+    }
+}
+
+/// Receives progress reports. Handlers may be called from worker threads.
+pub trait ProgressHandler: Send + Sync {
+    fn on_progress(&self, progress: &Progress);
+}
+
+impl<F: Fn(&Progress) + Send + Sync> ProgressHandler for F {
+    fn on_progress(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// A handler that discards every report.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressHandler for NoProgress {
+    fn on_progress(&self, _: &Progress) {}
+}
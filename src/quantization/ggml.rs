@@ -0,0 +1,231 @@
+//! GGML tensor types and the Q8_0 / Q4_0 block codecs.
+
+use crate::Result;
+
+/// Values per quantization block.
+pub const BLOCK_SIZE: usize = 32;
+
+/// Element types of GGML tensors supported by the crate. Discriminants are
+/// the on-disk type ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GgmlType {
+    F32 = 0,
+    F16 = 1,
+    Q4_0 = 2,
+    Q8_0 = 8,
+}
+
+impl GgmlType {
+    pub fn from_id(id: u32) -> Result<Self> {
+        match id {
+            0 => Ok(GgmlType::F32),
+            1 => Ok(GgmlType::F16),
+            2 => Ok(GgmlType::Q4_0),
+            8 => Ok(GgmlType::Q8_0),
+            other => Err(format!("unsupported GGML tensor type {}", other).into()),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GgmlType::F32 => "F32",
+            GgmlType::F16 => "F16",
+            GgmlType::Q4_0 => "Q4_0",
+            GgmlType::Q8_0 => "Q8_0",
+        }
+    }
+
+    /// Values covered by one storage unit: 1 for floats, a block otherwise.
+    pub fn block_len(&self) -> usize {
+        match self {
+            GgmlType::F32 | GgmlType::F16 => 1,
+            GgmlType::Q4_0 | GgmlType::Q8_0 => BLOCK_SIZE,
+        }
+    }
+
+    /// Bytes of one storage unit.
+    pub fn block_bytes(&self) -> usize {
+        match self {
+            GgmlType::F32 => 4,
+            GgmlType::F16 => 2,
+            GgmlType::Q4_0 => 2 + BLOCK_SIZE / 2,
+            GgmlType::Q8_0 => 2 + BLOCK_SIZE,
+        }
+    }
+
+    /// Bytes needed for `len` values, or an error if `len` does not fill
+    /// whole blocks.
+    pub fn size_of(&self, len: usize) -> Result<usize> {
+        if !len.is_multiple_of(self.block_len()) {
+            return Err(format!(
+                "{} values do not fill whole {} blocks of {}",
+                len,
+                self.name(),
+                self.block_len()
+            )
+            .into());
+        }
+        Ok(len / self.block_len() * self.block_bytes())
+    }
+
+    /// Encodes `values` in this type. Quantized types need a multiple of
+    /// [`BLOCK_SIZE`] values.
+    pub fn encode(&self, values: &[f32]) -> Result<Vec<u8>> {
+        self.size_of(values.len())?;
+        Ok(match self {
+            GgmlType::F32 => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            GgmlType::F16 => values
+                .iter()
+                .flat_map(|&v| f32_to_f16(v).to_le_bytes())
+                .collect(),
+            GgmlType::Q4_0 => quantize_q4_0(values),
+            GgmlType::Q8_0 => quantize_q8_0(values),
+        })
+    }
+
+    /// Decodes `len` values from `bytes`.
+    pub fn decode(&self, bytes: &[u8], len: usize) -> Result<Vec<f32>> {
+        let size = self.size_of(len)?;
+        if bytes.len() < size {
+            return Err(format!(
+                "{} values of type {} need {} bytes, got {}",
+                len,
+                self.name(),
+                size,
+                bytes.len()
+            )
+            .into());
+        }
+        let bytes = &bytes[..size];
+        Ok(match self {
+            GgmlType::F32 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            GgmlType::F16 => bytes
+                .chunks_exact(2)
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            GgmlType::Q4_0 => dequantize_q4_0(bytes),
+            GgmlType::Q8_0 => dequantize_q8_0(bytes),
+        })
+    }
+}
+
+/// Quantizes to Q8_0 blocks: `d = max|x| / 127`, `q = round(x / d)`.
+/// `values.len()` must be a multiple of [`BLOCK_SIZE`]; a trailing partial
+/// block is ignored.
+pub fn quantize_q8_0(values: &[f32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(values.len() / BLOCK_SIZE * (2 + BLOCK_SIZE));
+    for block in values.chunks_exact(BLOCK_SIZE) {
+        let amax = block.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+        let d = amax / 127.0;
+        let id = if d != 0.0 { 1.0 / d } else { 0.0 };
+        out.extend_from_slice(&f32_to_f16(d).to_le_bytes());
+        out.extend(block.iter().map(|&v| (v * id).round() as i8 as u8));
+    }
+    out
+}
+
+pub fn dequantize_q8_0(bytes: &[u8]) -> Vec<f32> {
+    let mut out = Vec::with_capacity(bytes.len() / (2 + BLOCK_SIZE) * BLOCK_SIZE);
+    for block in bytes.chunks_exact(2 + BLOCK_SIZE) {
+        let d = f16_to_f32(u16::from_le_bytes([block[0], block[1]]));
+        out.extend(block[2..].iter().map(|&q| q as i8 as f32 * d));
+    }
+    out
+}
+
+/// Quantizes to Q4_0 blocks. The scale maps the value of largest magnitude
+/// to -8, so it is represented exactly; value `j` and value `j + 16` of a
+/// block share byte `j` (low and high nibble).
+pub fn quantize_q4_0(values: &[f32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(values.len() / BLOCK_SIZE * (2 + BLOCK_SIZE / 2));
+    for block in values.chunks_exact(BLOCK_SIZE) {
+        let max = block
+            .iter()
+            .fold(0.0f32, |m, &v| if v.abs() > m.abs() { v } else { m });
+        let d = max / -8.0;
+        let id = if d != 0.0 { 1.0 / d } else { 0.0 };
+        out.extend_from_slice(&f32_to_f16(d).to_le_bytes());
+        let level = |v: f32| ((v * id + 8.5) as u8).min(15);
+        for j in 0..BLOCK_SIZE / 2 {
+            out.push(level(block[j]) | (level(block[j + BLOCK_SIZE / 2]) << 4));
+        }
+    }
+    out
+}
+
+pub fn dequantize_q4_0(bytes: &[u8]) -> Vec<f32> {
+    let half = BLOCK_SIZE / 2;
+    let mut out = Vec::with_capacity(bytes.len() / (2 + half) * BLOCK_SIZE);
+    for block in bytes.chunks_exact(2 + half) {
+        let d = f16_to_f32(u16::from_le_bytes([block[0], block[1]]));
+        let qs = &block[2..];
+        out.extend(qs.iter().map(|&q| ((q & 0x0F) as i32 - 8) as f32 * d));
+        out.extend(qs.iter().map(|&q| ((q >> 4) as i32 - 8) as f32 * d));
+    }
+    out
+}
+
+/// IEEE binary16 bits of `value`, rounding to nearest even.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+    if exp == 0xFF {
+        // Infinity, or NaN with a quiet bit set so it stays NaN.
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+    let exp = exp - 127 + 15;
+    if exp >= 0x1F {
+        return sign | 0x7C00;
+    }
+    if exp <= 0 {
+        if exp < -10 {
+            return sign;
+        }
+        // Subnormal: shift the mantissa, implicit bit included, into place.
+        let m = mantissa | 0x80_0000;
+        let shift = (14 - exp) as u32;
+        let half = 1 << (shift - 1);
+        let rest = m & ((1 << shift) - 1);
+        let mut out = m >> shift;
+        if rest > half || (rest == half && out & 1 == 1) {
+            out += 1;
+        }
+        return sign | out as u16;
+    }
+    let mut out = ((exp as u32) << 10) | (mantissa >> 13);
+    let rest = mantissa & 0x1FFF;
+    if rest > 0x1000 || (rest == 0x1000 && out & 1 == 1) {
+        // May carry into the exponent, which correctly rounds up to infinity.
+        out += 1;
+    }
+    sign | out as u16
+}
+
+/// `f32` value of IEEE binary16 bits.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exp = ((bits >> 10) & 0x1F) as u32;
+    let mantissa = (bits & 0x3FF) as u32;
+    let out = match (exp, mantissa) {
+        (0, 0) => sign,
+        (0, m) => {
+            // Subnormal: normalize the mantissa.
+            let shift = m.leading_zeros() - 21;
+            let m = (m << shift) & 0x3FF;
+            sign | ((127 - 15 + 1 - shift) << 23) | (m << 13)
+        }
+        (0x1F, m) => sign | 0x7F80_0000 | (m << 13),
+        (e, m) => sign | ((e + 127 - 15) << 23) | (m << 13),
+    };
+    f32::from_bits(out)
+}
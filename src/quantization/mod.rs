@@ -0,0 +1,21 @@
+//! Block quantization formats shared with the GGML ecosystem.
+//!
+//! Rows are cut into blocks of [`BLOCK_SIZE`] values, each stored as one
+//! half-precision scale followed by small integers:
+//!
+//! - [`GgmlType::Q8_0`]: `x ≈ d · q` with `q ∈ [-127, 127]`, 34 bytes per block
+//! - [`GgmlType::Q4_0`]: `x ≈ d · (q - 8)` with `q ∈ [0, 15]`, 18 bytes per block
+//!
+//! The byte layouts match llama.cpp, so quantized rows can be written to and
+//! read from GGUF files unchanged. [`QuantizedMatrix`] keeps a weight
+//! matrix in either format and multiplies by it, decoding one row at a
+//! time.
+
+mod ggml;
+mod matrix;
+
+pub use ggml::{
+    dequantize_q4_0, dequantize_q8_0, f16_to_f32, f32_to_f16, quantize_q4_0, quantize_q8_0,
+    GgmlType, BLOCK_SIZE,
+};
+pub use matrix::QuantizedMatrix;
@@ -0,0 +1,58 @@
+//! Scalar output heads on top of hidden states.
+
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// A linear map `h·w + b` from hidden states to one scalar per position,
+/// used as PPO value head and as reward head.
+#[derive(Debug, Clone)]
+pub struct ScalarHead {
+    /// `d_model × 1`.
+    pub weight: Matrix,
+    /// `1 × 1`.
+    pub bias: Matrix,
+}
+
+impl ScalarHead {
+    /// A zero-initialized head, predicting 0 everywhere.
+    pub fn new(d_model: usize) -> Self {
+        Self {
+            weight: Matrix::zeros(d_model, 1),
+            bias: Matrix::zeros(1, 1),
+        }
+    }
+
+    /// One value per row of `hidden` (`seq_len × d_model`).
+    pub fn forward(&self, hidden: &Matrix) -> Result<Vec<f64>> {
+        let b = self.bias[(0, 0)];
+        Ok(hidden
+            .matmul(&self.weight)?
+            .into_vec()
+            .into_iter()
+            .map(|v| v + b)
+            .collect())
+    }
+
+    /// Gradients of a loss given its derivative `d_values` with respect to
+    /// [`forward`](Self::forward)'s output.
+    pub fn backward(&self, hidden: &Matrix, d_values: &[f64]) -> Result<Gradients> {
+        let d = Matrix::from_vec(d_values.len(), 1, d_values.to_vec())?;
+        let mut grads = Gradients::new();
+        grads.insert("weight", hidden.transpose().matmul(&d)?);
+        grads.insert("bias", Matrix::from_element(1, 1, d_values.iter().sum()));
+        Ok(grads)
+    }
+}
+
+impl Parameters for ScalarHead {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "weight"), &self.weight);
+        visitor(&join_name(prefix, "bias"), &self.bias);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "weight"), &mut self.weight);
+        visitor(&join_name(prefix, "bias"), &mut self.bias);
+    }
+}
@@ -0,0 +1,32 @@
+//! Reinforcement learning from human feedback.
+//!
+//! [`reward`] trains reward models from pairwise preferences and [`ppo`]
+//! fine-tunes a policy against a [`RewardModel`] with proximal policy
+//! optimization, keeping it close to a frozen reference model.
+
+pub mod head;
+pub mod ppo;
+pub mod reward;
+
+pub use head::ScalarHead;
+pub use ppo::{generalized_advantages, PolicyLoss, PpoConfig, PpoStats, PpoTrainer, Rollout};
+pub use reward::{
+    pairwise_loss, PairwiseStats, PreferencePair, RewardInput, RewardTrainer,
+    TransformerRewardModel,
+};
+
+use crate::Result;
+
+/// Scores a generated `response` to `prompt`; higher is better.
+pub trait RewardModel {
+    fn score(&self, prompt: &[usize], response: &[usize]) -> Result<f64>;
+}
+
+impl<F> RewardModel for F
+where
+    F: Fn(&[usize], &[usize]) -> Result<f64>,
+{
+    fn score(&self, prompt: &[usize], response: &[usize]) -> Result<f64> {
+        self(prompt, response)
+    }
+}
@@ -0,0 +1,408 @@
+//! Proximal policy optimization (Schulman et al., 2017) for sequence models,
+//! following the InstructGPT recipe (Ouyang et al., 2022).
+//!
+//! One [`PpoTrainer::step`]:
+//!
+//! 1. samples a response to every prompt with the policy ([`Rollout`]),
+//!    recording the log-probabilities of the policy and of the frozen
+//!    reference model and the value head estimates;
+//! 2. scores each response with the reward model and turns the score into
+//!    per-token rewards with a KL penalty `-β·(log π − log π_ref)`;
+//! 3. computes advantages with generalized advantage estimation;
+//! 4. for several epochs, evaluates the clipped surrogate objective and
+//!    hands its gradient with respect to the policy logits to an update
+//!    callback, while fitting the value head to the returns.
+//!
+//! The value head is linear in the final decoder hidden state, so its
+//! gradient is computed here directly. Updating the policy itself requires
+//! backpropagating the logit gradient through the model, which the callback
+//! is responsible for, typically with
+//! [`Transformer::backward`](crate::Transformer::backward).
+
+use super::{RewardModel, ScalarHead};
+use crate::optim::{Adam, AdamConfig, Optimizer};
+use crate::tensor::Matrix;
+use crate::utils::rng::Rng;
+use crate::utils::tensor_ops::{log_softmax, softmax};
+use crate::{Result, Transformer};
+
+/// Hyperparameters of [`PpoTrainer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PpoConfig {
+    /// Longest response sampled per prompt.
+    pub max_new_tokens: usize,
+    /// Sampling temperature, also used when re-evaluating log-probabilities.
+    pub temperature: f64,
+    /// Policy ratio clipping `ε`.
+    pub clip_range: f64,
+    /// Weight `β` of the per-token KL penalty against the reference model.
+    pub kl_coef: f64,
+    /// Discount factor.
+    pub gamma: f64,
+    /// GAE smoothing `λ`.
+    pub lambda: f64,
+    /// Optimization passes over every batch of rollouts.
+    pub ppo_epochs: usize,
+    /// Normalize advantages to zero mean and unit variance per batch.
+    pub whiten_advantages: bool,
+    /// Learning rate of the value head.
+    pub value_lr: f64,
+    /// Seed of the response sampler.
+    pub seed: u64,
+}
+
+impl Default for PpoConfig {
+    fn default() -> Self {
+        Self {
+            max_new_tokens: 32,
+            temperature: 1.0,
+            clip_range: 0.2,
+            kl_coef: 0.05,
+            gamma: 1.0,
+            lambda: 0.95,
+            ppo_epochs: 4,
+            whiten_advantages: true,
+            value_lr: 1e-3,
+            seed: 0,
+        }
+    }
+}
+
+/// One sampled response and everything PPO derives from it. Per-token
+/// vectors are aligned with `response`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rollout {
+    pub prompt: Vec<usize>,
+    /// Sampled tokens, ending with EOS unless the length limit was hit.
+    pub response: Vec<usize>,
+    /// Log-probabilities of `response` under the policy at sampling time.
+    pub logprobs: Vec<f64>,
+    /// Log-probabilities of `response` under the reference model.
+    pub ref_logprobs: Vec<f64>,
+    /// Value estimates before each token.
+    pub values: Vec<f64>,
+    /// Reward model score of the whole response.
+    pub score: f64,
+    /// KL-penalized per-token rewards; the score is added to the last token.
+    pub rewards: Vec<f64>,
+    pub advantages: Vec<f64>,
+    pub returns: Vec<f64>,
+}
+
+impl Rollout {
+    /// Decoder input that reproduces `response` with teacher forcing.
+    pub fn decoder_input(&self, bos_token_id: usize) -> Vec<usize> {
+        let mut input = vec![bos_token_id];
+        input.extend_from_slice(&self.response[..self.response.len().saturating_sub(1)]);
+        input
+    }
+
+    /// Summed per-token KL estimate `log π − log π_ref`.
+    pub fn kl(&self) -> f64 {
+        self.logprobs
+            .iter()
+            .zip(&self.ref_logprobs)
+            .map(|(p, r)| p - r)
+            .sum()
+    }
+}
+
+/// Clipped surrogate loss of one rollout under the current policy.
+#[derive(Debug, Clone)]
+pub struct PolicyLoss {
+    /// Mean clipped surrogate loss over the response tokens.
+    pub loss: f64,
+    /// `∂loss/∂logits`, `response_len × vocab_size`, aligned with the logits
+    /// of the teacher-forced decoder pass over [`Rollout::decoder_input`].
+    pub logit_grads: Matrix,
+    /// Mean of `log π_old − log π`, an estimate of the policy drift.
+    pub approx_kl: f64,
+    /// Fraction of tokens whose ratio was clipped.
+    pub clip_fraction: f64,
+}
+
+/// Averages of one [`PpoTrainer::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PpoStats {
+    pub mean_score: f64,
+    /// Mean per-response KL to the reference model at sampling time.
+    pub mean_kl: f64,
+    pub policy_loss: f64,
+    pub value_loss: f64,
+    pub approx_kl: f64,
+    pub clip_fraction: f64,
+    pub mean_response_len: f64,
+}
+
+/// PPO fine-tuning state: the frozen reference, the value head and its
+/// optimizer.
+#[derive(Debug, Clone)]
+pub struct PpoTrainer {
+    pub config: PpoConfig,
+    pub reference: Transformer,
+    pub value_head: ScalarHead,
+    pub value_optimizer: Adam,
+    rng: Rng,
+}
+
+impl PpoTrainer {
+    /// Freezes a copy of `policy` as the reference model.
+    pub fn new(policy: &Transformer, config: PpoConfig) -> Self {
+        let mut reference = policy.clone();
+        reference.set_training(false);
+        Self {
+            value_head: ScalarHead::new(policy.config.d_model),
+            value_optimizer: Adam::new(AdamConfig {
+                lr: config.value_lr,
+                ..AdamConfig::default()
+            }),
+            rng: Rng::seed_from_u64(config.seed),
+            reference,
+            config,
+        }
+    }
+
+    /// Samples a response to `prompt` and scores it. Rewards and advantages
+    /// are filled in by [`prepare`](Self::prepare).
+    pub fn rollout(
+        &mut self,
+        policy: &Transformer,
+        prompt: &[usize],
+        reward: &dyn RewardModel,
+    ) -> Result<Rollout> {
+        let eos = policy.config.eos_token_id;
+        let memory = policy.encode(prompt)?;
+        let mut input = vec![policy.config.bos_token_id];
+        let mut response = Vec::new();
+        while response.len() < self.config.max_new_tokens {
+            let logits = policy.decode(&input, prompt, &memory)?;
+            let probs = softmax(&self.scaled(logits.row(logits.rows() - 1)));
+            let token = self.rng.categorical(&probs);
+            response.push(token);
+            if token == eos {
+                break;
+            }
+            input.push(token);
+        }
+
+        let mut rollout = Rollout {
+            prompt: prompt.to_vec(),
+            score: reward.score(prompt, &response)?,
+            response,
+            logprobs: Vec::new(),
+            ref_logprobs: Vec::new(),
+            values: Vec::new(),
+            rewards: Vec::new(),
+            advantages: Vec::new(),
+            returns: Vec::new(),
+        };
+        if rollout.response.is_empty() {
+            return Ok(rollout);
+        }
+        let (logits, hidden) = self.evaluate(policy, &rollout)?;
+        rollout.logprobs = self.token_logprobs(&logits, &rollout.response);
+        rollout.values = self.value_head.forward(&hidden)?;
+        let (ref_logits, _) = self.evaluate(&self.reference, &rollout)?;
+        rollout.ref_logprobs = self.token_logprobs(&ref_logits, &rollout.response);
+        Ok(rollout)
+    }
+
+    /// Computes KL-penalized rewards, advantages and returns for a batch,
+    /// whitening advantages across it if configured.
+    pub fn prepare(&self, rollouts: &mut [Rollout]) {
+        for rollout in rollouts.iter_mut() {
+            let mut rewards: Vec<f64> = rollout
+                .logprobs
+                .iter()
+                .zip(&rollout.ref_logprobs)
+                .map(|(p, r)| -self.config.kl_coef * (p - r))
+                .collect();
+            if let Some(last) = rewards.last_mut() {
+                *last += rollout.score;
+            }
+            let (advantages, returns) = generalized_advantages(
+                &rewards,
+                &rollout.values,
+                self.config.gamma,
+                self.config.lambda,
+            );
+            rollout.rewards = rewards;
+            rollout.advantages = advantages;
+            rollout.returns = returns;
+        }
+
+        if self.config.whiten_advantages {
+            let all: Vec<f64> = rollouts
+                .iter()
+                .flat_map(|r| r.advantages.iter().copied())
+                .collect();
+            if all.len() > 1 {
+                let mean = all.iter().sum::<f64>() / all.len() as f64;
+                let var = all.iter().map(|a| (a - mean).powi(2)).sum::<f64>() / all.len() as f64;
+                let std = var.sqrt().max(1e-8);
+                for a in rollouts.iter_mut().flat_map(|r| r.advantages.iter_mut()) {
+                    *a = (*a - mean) / std;
+                }
+            }
+        }
+    }
+
+    /// Clipped surrogate loss of `rollout` under the current `policy` and its
+    /// gradient with respect to the policy logits.
+    pub fn policy_loss(&self, policy: &Transformer, rollout: &Rollout) -> Result<PolicyLoss> {
+        let (logits, _) = self.evaluate(policy, rollout)?;
+        Ok(self.surrogate(&logits, rollout))
+    }
+
+    /// Runs one PPO iteration over `prompts`. `update_policy` receives the
+    /// policy, a prepared rollout and the gradient of its loss with respect
+    /// to the policy logits, and is expected to apply a gradient step.
+    pub fn step<F>(
+        &mut self,
+        policy: &mut Transformer,
+        prompts: &[Vec<usize>],
+        reward: &dyn RewardModel,
+        mut update_policy: F,
+    ) -> Result<PpoStats>
+    where
+        F: FnMut(&mut Transformer, &Rollout, &Matrix) -> Result<()>,
+    {
+        let mut rollouts = prompts
+            .iter()
+            .map(|prompt| self.rollout(policy, prompt, reward))
+            .collect::<Result<Vec<_>>>()?;
+        self.prepare(&mut rollouts);
+
+        let mut stats = PpoStats::default();
+        let count = rollouts.len().max(1) as f64;
+        for rollout in &rollouts {
+            stats.mean_score += rollout.score / count;
+            stats.mean_kl += rollout.kl() / count;
+            stats.mean_response_len += rollout.response.len() as f64 / count;
+        }
+
+        let mut evaluations = 0.0;
+        for _ in 0..self.config.ppo_epochs {
+            for rollout in rollouts.iter().filter(|r| !r.response.is_empty()) {
+                let (logits, hidden) = self.evaluate(policy, rollout)?;
+                let loss = self.surrogate(&logits, rollout);
+                stats.value_loss += self.update_value_head(&hidden, &rollout.returns)?;
+                stats.policy_loss += loss.loss;
+                stats.approx_kl += loss.approx_kl;
+                stats.clip_fraction += loss.clip_fraction;
+                evaluations += 1.0;
+                update_policy(policy, rollout, &loss.logit_grads)?;
+            }
+        }
+        if evaluations > 0.0 {
+            stats.policy_loss /= evaluations;
+            stats.value_loss /= evaluations;
+            stats.approx_kl /= evaluations;
+            stats.clip_fraction /= evaluations;
+        }
+        Ok(stats)
+    }
+
+    /// Teacher-forced logits and final hidden states over the response.
+    fn evaluate(&self, model: &Transformer, rollout: &Rollout) -> Result<(Matrix, Matrix)> {
+        let input = rollout.decoder_input(model.config.bos_token_id);
+        let (logits, hidden) = model.forward_with_hidden_states(&rollout.prompt, &input)?;
+        let last = hidden
+            .decoder
+            .last()
+            .cloned()
+            .ok_or("model has no decoder hidden states")?;
+        Ok((logits, last))
+    }
+
+    fn scaled(&self, logits: &[f64]) -> Vec<f64> {
+        let t = self.config.temperature.max(1e-6);
+        logits.iter().map(|v| v / t).collect()
+    }
+
+    fn token_logprobs(&self, logits: &Matrix, tokens: &[usize]) -> Vec<f64> {
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(t, &token)| log_softmax(&self.scaled(logits.row(t)))[token])
+            .collect()
+    }
+
+    fn surrogate(&self, logits: &Matrix, rollout: &Rollout) -> PolicyLoss {
+        let n = rollout.response.len();
+        let temperature = self.config.temperature.max(1e-6);
+        let eps = self.config.clip_range;
+        let mut logit_grads = Matrix::zeros(n, logits.cols());
+        let (mut loss, mut approx_kl, mut clipped) = (0.0, 0.0, 0);
+        for (t, &token) in rollout.response.iter().enumerate() {
+            let log_probs = log_softmax(&self.scaled(logits.row(t)));
+            let logp = log_probs[token];
+            let probs: Vec<f64> = log_probs.iter().map(|l| l.exp()).collect();
+            let ratio = (logp - rollout.logprobs[t]).exp();
+            let advantage = rollout.advantages[t];
+            let unclipped = -advantage * ratio;
+            let clipped_term = -advantage * ratio.clamp(1.0 - eps, 1.0 + eps);
+            loss += unclipped.max(clipped_term);
+            approx_kl += rollout.logprobs[t] - logp;
+            if (ratio - 1.0).abs() > eps {
+                clipped += 1;
+            }
+            // The clipped branch is constant in the logits, so only the
+            // unclipped branch contributes a gradient.
+            if unclipped >= clipped_term {
+                let d_logp = -advantage * ratio / n as f64;
+                for (j, g) in logit_grads.row_mut(t).iter_mut().enumerate() {
+                    let onehot = if j == token { 1.0 } else { 0.0 };
+                    *g = d_logp * (onehot - probs[j]) / temperature;
+                }
+            }
+        }
+        PolicyLoss {
+            loss: loss / n as f64,
+            logit_grads,
+            approx_kl: approx_kl / n as f64,
+            clip_fraction: clipped as f64 / n as f64,
+        }
+    }
+
+    /// One optimizer step on the squared error to `returns`; returns the loss.
+    fn update_value_head(&mut self, hidden: &Matrix, returns: &[f64]) -> Result<f64> {
+        let values = self.value_head.forward(hidden)?;
+        let n = values.len() as f64;
+        let d_values: Vec<f64> = values
+            .iter()
+            .zip(returns)
+            .map(|(v, r)| 2.0 * (v - r) / n)
+            .collect();
+        let loss = values
+            .iter()
+            .zip(returns)
+            .map(|(v, r)| (v - r).powi(2))
+            .sum::<f64>()
+            / n;
+        let grads = self.value_head.backward(hidden, &d_values)?;
+        self.value_optimizer.step(&mut self.value_head, &grads)?;
+        Ok(loss)
+    }
+}
+
+/// Generalized advantage estimation (Schulman et al., 2016). The value after
+/// the last token is taken as zero.
+pub fn generalized_advantages(
+    rewards: &[f64],
+    values: &[f64],
+    gamma: f64,
+    lambda: f64,
+) -> (Vec<f64>, Vec<f64>) {
+    let n = rewards.len();
+    let mut advantages = vec![0.0; n];
+    let mut running = 0.0;
+    for t in (0..n).rev() {
+        let next_value = if t + 1 < n { values[t + 1] } else { 0.0 };
+        let delta = rewards[t] + gamma * next_value - values[t];
+        running = delta + gamma * lambda * running;
+        advantages[t] = running;
+    }
+    let returns = advantages.iter().zip(values).map(|(a, v)| a + v).collect();
+    (advantages, returns)
+}
@@ -0,0 +1,198 @@
+//! Reward models trained from pairwise preferences.
+//!
+//! A [`TransformerRewardModel`] puts a [`ScalarHead`] on a pooled hidden
+//! state of a Transformer and is trained with the Bradley–Terry loss
+//! `−log σ(r_chosen − r_rejected)` on [`PreferencePair`]s. The head is
+//! trained on features of the (frozen) backbone.
+
+use super::{RewardModel, ScalarHead};
+use crate::optim::{Adam, AdamConfig, Optimizer};
+use crate::params::{join_name, Parameters};
+use crate::tensor::Matrix;
+use crate::{Result, Transformer};
+
+/// Which hidden state the reward is read from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RewardInput {
+    /// Prompt through the encoder, response through the decoder; the final
+    /// decoder state of the last response token.
+    #[default]
+    Decoder,
+    /// `prompt ++ response` through the encoder, mean-pooled.
+    Encoder,
+}
+
+/// A Transformer backbone with a scalar reward head.
+#[derive(Debug, Clone)]
+pub struct TransformerRewardModel {
+    pub backbone: Transformer,
+    pub head: ScalarHead,
+    pub input: RewardInput,
+}
+
+impl TransformerRewardModel {
+    pub fn new(backbone: Transformer, input: RewardInput) -> Self {
+        Self {
+            head: ScalarHead::new(backbone.config.d_model),
+            backbone,
+            input,
+        }
+    }
+
+    /// The pooled `1 × d_model` feature the head scores.
+    pub fn features(&self, prompt: &[usize], response: &[usize]) -> Result<Matrix> {
+        match self.input {
+            RewardInput::Decoder => {
+                let mut tgt = vec![self.backbone.config.bos_token_id];
+                tgt.extend_from_slice(response);
+                let (_, hidden) = self.backbone.forward_with_hidden_states(prompt, &tgt)?;
+                let last = hidden
+                    .decoder
+                    .last()
+                    .ok_or("model has no decoder hidden states")?;
+                last.rows_range(last.rows() - 1, 1)
+            }
+            RewardInput::Encoder => {
+                let mut tokens = prompt.to_vec();
+                tokens.extend_from_slice(response);
+                Ok(self.backbone.encode(&tokens)?.column_means())
+            }
+        }
+    }
+
+    pub fn reward(&self, prompt: &[usize], response: &[usize]) -> Result<f64> {
+        Ok(self.head.forward(&self.features(prompt, response)?)?[0])
+    }
+}
+
+impl RewardModel for TransformerRewardModel {
+    fn score(&self, prompt: &[usize], response: &[usize]) -> Result<f64> {
+        self.reward(prompt, response)
+    }
+}
+
+impl Parameters for TransformerRewardModel {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        self.backbone.visit_parameters(prefix, visitor);
+        self.head
+            .visit_parameters(&join_name(prefix, "reward_head"), visitor);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        self.backbone.visit_parameters_mut(prefix, visitor);
+        self.head
+            .visit_parameters_mut(&join_name(prefix, "reward_head"), visitor);
+    }
+}
+
+/// A prompt with a preferred and a dispreferred response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreferencePair {
+    pub prompt: Vec<usize>,
+    pub chosen: Vec<usize>,
+    pub rejected: Vec<usize>,
+}
+
+/// Bradley–Terry loss `−log σ(chosen − rejected)` and its derivatives with
+/// respect to both rewards.
+pub fn pairwise_loss(chosen: f64, rejected: f64) -> (f64, f64, f64) {
+    let margin = chosen - rejected;
+    // softplus(-m), written to stay finite for large |m|.
+    let loss = (-margin).max(0.0) + (-margin.abs()).exp().ln_1p();
+    let sigmoid_neg = 1.0 / (1.0 + margin.exp());
+    (loss, -sigmoid_neg, sigmoid_neg)
+}
+
+/// Averages over a batch of preference pairs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PairwiseStats {
+    pub loss: f64,
+    /// Fraction of pairs where the chosen response scored higher.
+    pub accuracy: f64,
+    /// Mean `r_chosen − r_rejected`.
+    pub margin: f64,
+}
+
+/// Trains the head of a [`TransformerRewardModel`] on preference pairs.
+#[derive(Debug, Clone)]
+pub struct RewardTrainer<O = Adam> {
+    pub optimizer: O,
+}
+
+impl Default for RewardTrainer<Adam> {
+    fn default() -> Self {
+        Self::new(Adam::new(AdamConfig::default()))
+    }
+}
+
+impl<O: Optimizer> RewardTrainer<O> {
+    pub fn new(optimizer: O) -> Self {
+        Self { optimizer }
+    }
+
+    /// One optimizer step on the mean pairwise loss of `pairs`.
+    pub fn train_batch(
+        &mut self,
+        model: &mut TransformerRewardModel,
+        pairs: &[PreferencePair],
+    ) -> Result<PairwiseStats> {
+        if pairs.is_empty() {
+            return Ok(PairwiseStats::default());
+        }
+        let mut features = Vec::with_capacity(2 * pairs.len());
+        let mut d_rewards = Vec::with_capacity(2 * pairs.len());
+        let mut stats = PairwiseStats::default();
+        let n = pairs.len() as f64;
+        for pair in pairs {
+            let chosen = model.features(&pair.prompt, &pair.chosen)?;
+            let rejected = model.features(&pair.prompt, &pair.rejected)?;
+            let r_chosen = model.head.forward(&chosen)?[0];
+            let r_rejected = model.head.forward(&rejected)?[0];
+            let (loss, d_chosen, d_rejected) = pairwise_loss(r_chosen, r_rejected);
+            accumulate(&mut stats, loss, r_chosen - r_rejected);
+            features.push(chosen);
+            features.push(rejected);
+            d_rewards.push(d_chosen / n);
+            d_rewards.push(d_rejected / n);
+        }
+        let features = Matrix::vstack(&features.iter().collect::<Vec<_>>())?;
+        let grads = model.head.backward(&features, &d_rewards)?;
+        self.optimizer.step(&mut model.head, &grads)?;
+        Ok(averaged(stats, n))
+    }
+
+    /// Loss and accuracy of `model` on `pairs` without updating it.
+    pub fn evaluate(
+        &self,
+        model: &TransformerRewardModel,
+        pairs: &[PreferencePair],
+    ) -> Result<PairwiseStats> {
+        let mut stats = PairwiseStats::default();
+        let n = pairs.len() as f64;
+        for pair in pairs {
+            let margin = model.reward(&pair.prompt, &pair.chosen)?
+                - model.reward(&pair.prompt, &pair.rejected)?;
+            accumulate(&mut stats, pairwise_loss(margin, 0.0).0, margin);
+        }
+        Ok(averaged(stats, n))
+    }
+}
+
+fn accumulate(stats: &mut PairwiseStats, loss: f64, margin: f64) {
+    stats.loss += loss;
+    stats.margin += margin;
+    if margin > 0.0 {
+        stats.accuracy += 1.0;
+    }
+}
+
+fn averaged(stats: PairwiseStats, n: f64) -> PairwiseStats {
+    if n == 0.0 {
+        return stats;
+    }
+    PairwiseStats {
+        loss: stats.loss / n,
+        accuracy: stats.accuracy / n,
+        margin: stats.margin / n,
+    }
+}
@@ -0,0 +1,504 @@
+//! A small HTTP/1.1 inference service over an [`InferenceEngine`].
+//!
+//! [`InferenceServer`] answers JSON requests on a TCP port, one thread per
+//! connection, and hands the model work to the engine's worker pool, so
+//! concurrent requests and the inputs of one batched request are served in
+//! parallel. Endpoints:
+//!
+//! - `GET /health`: `{"status": "ok", "workers": n}`
+//! - `GET /memory`: what the loaded model occupies, `{"parameters": n,
+//!   "parameter_bytes": b, "kv_cache_bytes_per_token": k,
+//!   "cross_attention_bytes_per_source_token": c}`
+//! - `POST /encode` with `{"tokens": [..]}`: `{"encoding": [[..], ..]}`,
+//!   one row per token
+//! - `POST /generate` with `{"tokens": [..]}` and optionally
+//!   `max_new_tokens`, `do_sample`, `temperature`, `top_k` and `top_p`:
+//!   `{"tokens": [..], "new_tokens": n, "finish_reason": "stop",
+//!   "log_prob": x}`
+//!
+//! Both POST endpoints also accept `{"inputs": [[..], ..]}` and then answer
+//! with `{"encodings": [..]}` or `{"outputs": [..]}` in input order; a
+//! batch is queued whole or, if the engine's queue lacks room, not at all.
+//! Sources longer than the model's position table are rejected by
+//! `/encode` and left-truncated by `/generate`, like
+//! [`Transformer::generate_output`] does.
+//! Failures are reported as `{"error": "..."}`: 400 for malformed requests
+//! and inputs the model cannot take, 503 while the engine's queue is full,
+//! the engine is shut down or [`ServerConfig::max_connections`] are open,
+//! and 500 for anything that goes wrong inside the model.
+//!
+//! [`serve`](InferenceServer::serve) runs until the server's
+//! [`CancellationToken`] is cancelled; generations still running then stop
+//! early and return what they have.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::cancellation::CancellationToken;
+use crate::engine::{InferenceEngine, JobHandle};
+use crate::generation::{GenerationConfig, GenerationOutput, SamplingConfig};
+use crate::models::Transformer;
+use crate::tensor::Matrix;
+use crate::utils::json::Json;
+use crate::{Parameters, Result, TransformerError};
+
+/// Limits of [`InferenceServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerConfig {
+    /// Largest request body accepted.
+    pub max_body_bytes: usize,
+    /// Longest request line or header line accepted, in bytes.
+    pub max_line_bytes: usize,
+    /// Most headers accepted in one request.
+    pub max_headers: usize,
+    /// Connections served at once; further ones are answered with 503.
+    pub max_connections: usize,
+    /// How long a connection may take to send its request.
+    pub read_timeout: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1 << 20,
+            max_line_bytes: 8 << 10,
+            max_headers: 64,
+            max_connections: 256,
+            read_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// An HTTP front end to an [`InferenceEngine`].
+#[derive(Debug)]
+pub struct InferenceServer {
+    listener: TcpListener,
+    engine: Arc<InferenceEngine>,
+    config: ServerConfig,
+    cancellation: CancellationToken,
+}
+
+impl InferenceServer {
+    /// Listens on `addr` (port 0 picks a free port).
+    pub fn bind(addr: impl ToSocketAddrs, engine: InferenceEngine) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            engine: Arc::new(engine),
+            config: ServerConfig::default(),
+            cancellation: CancellationToken::new(),
+        })
+    }
+
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// The token that stops [`serve`](Self::serve) when cancelled.
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Accepts connections until the [`cancellation`](Self::cancellation)
+    /// token is cancelled, then waits for open connections to finish.
+    pub fn serve(self) -> Result<()> {
+        let mut connections = Vec::new();
+        while !self.cancellation.is_cancelled() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    connections.retain(|c: &thread::JoinHandle<()>| !c.is_finished());
+                    if connections.len() >= self.config.max_connections {
+                        reject_busy(stream);
+                        continue;
+                    }
+                    let engine = Arc::clone(&self.engine);
+                    let cancellation = self.cancellation.clone();
+                    let config = self.config;
+                    connections.push(thread::spawn(move || {
+                        // A connection that breaks off only affects itself.
+                        let _ = handle_connection(stream, &engine, &config, &cancellation);
+                    }));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        for connection in connections {
+            let _ = connection.join();
+        }
+        Ok(())
+    }
+}
+
+/// Answers 503 without a thread of its own. Whatever the client already
+/// sent is discarded first, since closing with unread input resets the
+/// connection and can lose the response.
+fn reject_busy(mut stream: TcpStream) {
+    let mut discard = [0; 4096];
+    if stream.set_nonblocking(true).is_ok() {
+        while matches!(stream.read(&mut discard), Ok(n) if n > 0) {}
+    }
+    let _ = stream.set_nonblocking(false);
+    let _ = write_response(stream, &Response::error(503, "too many open connections"));
+}
+
+/// A response status and JSON body.
+struct Response {
+    status: u16,
+    body: Json,
+}
+
+impl Response {
+    fn ok(body: Json) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: Json::object([("error", Json::from(message.into()))]),
+        }
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    engine: &InferenceEngine,
+    config: &ServerConfig,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(config.read_timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader, config) {
+        Ok((method, path, body)) => route(engine, cancellation, &method, &path, &body),
+        Err(response) => response,
+    };
+    write_response(stream, &response)
+}
+
+/// Method, path and body of one request, or the response rejecting it.
+fn read_request(
+    reader: &mut impl BufRead,
+    config: &ServerConfig,
+) -> std::result::Result<(String, String, String), Response> {
+    let bad_request = |message: &str| Response::error(400, message);
+    let mut line = String::new();
+    let complete = read_line(reader, &mut line, config.max_line_bytes)
+        .map_err(|_| bad_request("could not read the request line"))?;
+    if !complete {
+        return Err(Response::error(414, "request line too long"));
+    }
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(bad_request("malformed request line")),
+    };
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        line.clear();
+        let complete = read_line(reader, &mut line, config.max_line_bytes)
+            .map_err(|_| bad_request("could not read the headers"))?;
+        if !complete {
+            return Err(Response::error(431, "header line too long"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > config.max_headers {
+            return Err(Response::error(431, "too many headers"));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| bad_request("invalid Content-Length"))?;
+            }
+        }
+    }
+    if content_length > config.max_body_bytes {
+        return Err(Response::error(413, "request body too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad_request("request body ended early"))?;
+    let body = String::from_utf8(body).map_err(|_| bad_request("body is not UTF-8"))?;
+    Ok((method, path, body))
+}
+
+/// Appends one line of at most `limit` bytes to `line`; `false` if the line
+/// is longer.
+fn read_line(reader: &mut impl BufRead, line: &mut String, limit: usize) -> io::Result<bool> {
+    reader.take(limit as u64 + 1).read_line(line)?;
+    Ok(line.len() <= limit)
+}
+
+fn route(
+    engine: &InferenceEngine,
+    cancellation: &CancellationToken,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> Response {
+    let path = path.split('?').next().unwrap_or(path);
+    let result = match (method, path) {
+        ("GET", "/health") => Ok(Json::object([
+            ("status", Json::from("ok")),
+            ("workers", Json::from(engine.num_workers())),
+        ])),
+        ("GET", "/memory") => Ok(memory(engine.model())),
+        ("POST", "/encode") => parse_body(body)
+            .map_err(bad_request)
+            .and_then(|request| encode(engine, &request)),
+        ("POST", "/generate") => parse_body(body)
+            .map_err(bad_request)
+            .and_then(|request| generate(engine, cancellation, &request)),
+        (_, "/health" | "/memory" | "/encode" | "/generate") => {
+            return Response::error(405, format!("{} is not allowed on {}", method, path))
+        }
+        _ => return Response::error(404, format!("no endpoint {}", path)),
+    };
+    match result {
+        Ok(body) => Response::ok(body),
+        Err(response) => response,
+    }
+}
+
+/// A 400 response for a request that is malformed or that the model cannot
+/// take.
+fn bad_request(e: TransformerError) -> Response {
+    Response::error(400, e.to_string())
+}
+
+/// The response for a well-formed request that failed in the engine.
+fn engine_failure(e: TransformerError) -> Response {
+    let status = match e {
+        TransformerError::VocabOverflow { .. } => 400,
+        TransformerError::Unavailable(_) => 503,
+        _ => 500,
+    };
+    Response::error(status, e.to_string())
+}
+
+fn parse_body(body: &str) -> Result<Json> {
+    let request = Json::parse(body)?;
+    if request.as_object().is_none() {
+        return Err("request body must be a JSON object".into());
+    }
+    Ok(request)
+}
+
+/// The token sequences of a request: `inputs` for a batch, else `tokens`.
+/// The flag tells whether the request was a batch. Sequences the model
+/// cannot encode are rejected here rather than failing in a worker; with
+/// `truncated`, the caller shortens over-long sequences itself.
+fn inputs(model: &Transformer, request: &Json, truncated: bool) -> Result<(Vec<Vec<usize>>, bool)> {
+    let (inputs, batch) = match request.get("inputs") {
+        Some(inputs) => {
+            let inputs = inputs
+                .as_array()
+                .ok_or("inputs must be an array of token arrays")?;
+            (inputs.iter().map(token_ids).collect::<Result<_>>()?, true)
+        }
+        None => {
+            let tokens = request
+                .get("tokens")
+                .ok_or("request needs tokens or inputs")?;
+            (vec![token_ids(tokens)?], false)
+        }
+    };
+    for src in &inputs {
+        check_input(model, src, truncated)?;
+    }
+    Ok((inputs, batch))
+}
+
+fn check_input(model: &Transformer, src: &[usize], truncated: bool) -> Result<()> {
+    let vocab_size = model.config.vocab_size;
+    if let Some(&token) = src.iter().find(|&&t| t >= vocab_size) {
+        return Err(TransformerError::VocabOverflow { token, vocab_size });
+    }
+    let positional = &model.encoder.positional;
+    if !truncated && !positional.is_none() && src.len() > positional.max_seq_len() {
+        return Err(TransformerError::shape(format!(
+            "input of {} tokens exceeds max_seq_len {}",
+            src.len(),
+            positional.max_seq_len()
+        )));
+    }
+    Ok(())
+}
+
+fn token_ids(tokens: &Json) -> Result<Vec<usize>> {
+    tokens
+        .as_array()
+        .ok_or("tokens must be an array of token ids")?
+        .iter()
+        .map(|t| {
+            t.as_usize()
+                .ok_or_else(|| "token ids must be non-negative integers".into())
+        })
+        .collect()
+}
+
+/// Waits for every job, in submission order.
+fn wait_all<T>(handles: Vec<JobHandle<T>>) -> Result<Vec<T>> {
+    handles.into_iter().map(JobHandle::wait).collect()
+}
+
+fn encode(engine: &InferenceEngine, request: &Json) -> std::result::Result<Json, Response> {
+    let (inputs, batch) = inputs(engine.model(), request, false).map_err(bad_request)?;
+    let handles = engine
+        .try_submit_all(
+            inputs
+                .into_iter()
+                .map(|src| move |model: &Transformer| model.encode(&src)),
+        )
+        .map_err(engine_failure)?;
+    let mut encodings: Vec<Json> = wait_all(handles)
+        .map_err(engine_failure)?
+        .iter()
+        .map(matrix_json)
+        .collect();
+    Ok(if batch {
+        Json::object([("encodings", Json::Array(encodings))])
+    } else {
+        Json::object([("encoding", encodings.remove(0))])
+    })
+}
+
+fn generate(
+    engine: &InferenceEngine,
+    cancellation: &CancellationToken,
+    request: &Json,
+) -> std::result::Result<Json, Response> {
+    let (inputs, batch) = inputs(engine.model(), request, true).map_err(bad_request)?;
+    let generation = generation_config(request)
+        .map_err(bad_request)?
+        .with_cancellation(cancellation.clone());
+    let handles = engine
+        .try_submit_all(inputs.into_iter().map(|src| {
+            let generation = generation.clone();
+            move |model: &Transformer| model.generate_output(&src, &generation)
+        }))
+        .map_err(engine_failure)?;
+    let mut outputs: Vec<Json> = wait_all(handles)
+        .map_err(engine_failure)?
+        .iter()
+        .map(output_json)
+        .collect();
+    Ok(if batch {
+        Json::object([("outputs", Json::Array(outputs))])
+    } else {
+        outputs.remove(0)
+    })
+}
+
+/// Generation options given in the request, over [`GenerationConfig`]'s
+/// defaults.
+fn generation_config(request: &Json) -> Result<GenerationConfig> {
+    let mut generation = GenerationConfig::new();
+    let mut sampling = SamplingConfig::new();
+    if let Some(value) = request.get("max_new_tokens") {
+        generation.max_new_tokens = value
+            .as_usize()
+            .ok_or("max_new_tokens must be a non-negative integer")?;
+    }
+    if let Some(value) = request.get("do_sample") {
+        generation.do_sample = value.as_bool().ok_or("do_sample must be a boolean")?;
+    }
+    if let Some(value) = request.get("temperature") {
+        sampling.temperature = value.as_f64().ok_or("temperature must be a number")?;
+    }
+    if let Some(value) = request.get("top_k") {
+        sampling.top_k = Some(value.as_usize().ok_or("top_k must be a positive integer")?);
+    }
+    if let Some(value) = request.get("top_p") {
+        sampling.top_p = Some(value.as_f64().ok_or("top_p must be a number")?);
+    }
+    sampling.validate()?;
+    generation.sampling = sampling;
+    Ok(generation)
+}
+
+/// The size of the model's weights and of the key/value caches decoding
+/// adds per target token and per source token, in bytes.
+fn memory(model: &Transformer) -> Json {
+    let parameters = model.num_parameters();
+    let value_bytes = mem::size_of::<f64>();
+    // Every decoder layer caches one key and one value row per position.
+    let kv_row_bytes = 2 * model.decoder.layers.len() * model.config.d_model * value_bytes;
+    Json::object([
+        ("parameters", Json::from(parameters)),
+        ("parameter_bytes", Json::from(parameters * value_bytes)),
+        ("kv_cache_bytes_per_token", Json::from(kv_row_bytes)),
+        (
+            "cross_attention_bytes_per_source_token",
+            Json::from(kv_row_bytes),
+        ),
+    ])
+}
+
+fn matrix_json(matrix: &Matrix) -> Json {
+    Json::Array(
+        matrix
+            .row_iter()
+            .map(|row| Json::from(row.to_vec()))
+            .collect(),
+    )
+}
+
+fn output_json(output: &GenerationOutput) -> Json {
+    Json::object([
+        ("tokens", Json::from(output.tokens.clone())),
+        ("new_tokens", Json::from(output.new_tokens)),
+        (
+            "finish_reason",
+            Json::from(output.finish_reason.to_string()),
+        ),
+        ("log_prob", Json::from(output.log_prob)),
+    ])
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    let body = response.body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
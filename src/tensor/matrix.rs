@@ -0,0 +1,457 @@
+//! Dense row-major matrix.
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use super::Float;
+use crate::utils::Rng;
+use crate::{Result, TransformerError};
+
+/// A dense, row-major 2-D matrix.
+///
+/// Arithmetic is provided for any [`Float`] element type (`f64` and `f32`),
+/// and the random and identity constructors for `Matrix<f64>`; other element
+/// types (for example `bool` attention masks) get the structural operations
+/// only. [`cast`](Self::cast) converts between precisions.
+#[derive(Clone, PartialEq)]
+pub struct Matrix<T = f64> {
+    rows: usize,
+    cols: usize,
+    data: Vec<T>,
+}
+
+impl<T> Matrix<T> {
+    /// Creates a matrix from row-major data.
+    pub fn from_vec(rows: usize, cols: usize, data: Vec<T>) -> Result<Self> {
+        if data.len() != rows * cols {
+            return Err(TransformerError::shape(format!(
+                "cannot build a {}x{} matrix from {} elements",
+                rows,
+                cols,
+                data.len()
+            )));
+        }
+        Ok(Self { rows, cols, data })
+    }
+
+    /// Creates a matrix by evaluating `f(row, col)` for every element.
+    pub fn from_fn(rows: usize, cols: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let mut data = Vec::with_capacity(rows * cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                data.push(f(i, j));
+            }
+        }
+        Self { rows, cols, data }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// `(rows, cols)`.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    /// Total number of elements.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Row-major view of the elements.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Returns row `i` as a slice.
+    pub fn row(&self, i: usize) -> &[T] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    pub fn row_mut(&mut self, i: usize) -> &mut [T] {
+        &mut self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    /// Iterates over the rows as slices.
+    pub fn row_iter(&self) -> impl Iterator<Item = &[T]> {
+        let cols = self.cols;
+        (0..self.rows).map(move |i| &self.data[i * cols..(i + 1) * cols])
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Option<&T> {
+        if i < self.rows && j < self.cols {
+            self.data.get(i * self.cols + j)
+        } else {
+            None
+        }
+    }
+
+    /// Applies `f` element-wise, producing a new matrix.
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Matrix<U> {
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.iter().map(f).collect(),
+        }
+    }
+
+    /// Checks that `self` has exactly `shape`, naming `what` in the error.
+    pub fn ensure_shape(&self, shape: (usize, usize), what: &str) -> Result<()> {
+        if self.shape() != shape {
+            return Err(TransformerError::shape(format!(
+                "{}: expected shape {:?}, got {:?}",
+                what,
+                shape,
+                self.shape()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Clone> Matrix<T> {
+    /// Creates a matrix with every element set to `value`.
+    pub fn from_element(rows: usize, cols: usize, value: T) -> Self {
+        Self {
+            rows,
+            cols,
+            data: vec![value; rows * cols],
+        }
+    }
+
+    /// Builds a matrix whose rows are the given slices.
+    pub fn from_rows(rows: &[Vec<T>]) -> Result<Self> {
+        let cols = rows.first().map_or(0, |r| r.len());
+        if rows.iter().any(|r| r.len() != cols) {
+            return Err(TransformerError::shape(
+                "all rows must have the same length",
+            ));
+        }
+        Ok(Self {
+            rows: rows.len(),
+            cols,
+            data: rows.iter().flat_map(|r| r.iter().cloned()).collect(),
+        })
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.cols, self.rows, |i, j| self[(j, i)].clone())
+    }
+
+    /// Copies the rows at `indices` (in order) into a new matrix.
+    pub fn select_rows(&self, indices: &[usize]) -> Result<Self> {
+        let mut data = Vec::with_capacity(indices.len() * self.cols);
+        for &i in indices {
+            if i >= self.rows {
+                return Err(TransformerError::shape(format!(
+                    "row {} out of range for {} rows",
+                    i, self.rows
+                )));
+            }
+            data.extend_from_slice(self.row(i));
+        }
+        Ok(Self {
+            rows: indices.len(),
+            cols: self.cols,
+            data,
+        })
+    }
+
+    /// Copies rows `start..start + count`.
+    pub fn rows_range(&self, start: usize, count: usize) -> Result<Self> {
+        if start + count > self.rows {
+            return Err(TransformerError::shape(format!(
+                "rows {}..{} out of range for {} rows",
+                start,
+                start + count,
+                self.rows
+            )));
+        }
+        Ok(Self {
+            rows: count,
+            cols: self.cols,
+            data: self.data[start * self.cols..(start + count) * self.cols].to_vec(),
+        })
+    }
+
+    /// Copies columns `start..start + count`.
+    pub fn columns(&self, start: usize, count: usize) -> Result<Self> {
+        if start + count > self.cols {
+            return Err(TransformerError::shape(format!(
+                "columns {}..{} out of range for {} columns",
+                start,
+                start + count,
+                self.cols
+            )));
+        }
+        Ok(Self::from_fn(self.rows, count, |i, j| {
+            self[(i, start + j)].clone()
+        }))
+    }
+
+    /// Stacks matrices with equal column counts on top of each other.
+    pub fn vstack(parts: &[&Self]) -> Result<Self> {
+        let cols = parts.first().map_or(0, |m| m.cols);
+        if parts.iter().any(|m| m.cols != cols) {
+            return Err(TransformerError::shape(
+                "vstack requires equal column counts",
+            ));
+        }
+        let mut data = Vec::with_capacity(parts.iter().map(|m| m.len()).sum());
+        for m in parts {
+            data.extend_from_slice(&m.data);
+        }
+        Ok(Self {
+            rows: parts.iter().map(|m| m.rows).sum(),
+            cols,
+            data,
+        })
+    }
+
+    /// Places matrices with equal row counts side by side.
+    pub fn hstack(parts: &[&Self]) -> Result<Self> {
+        let rows = parts.first().map_or(0, |m| m.rows);
+        if parts.iter().any(|m| m.rows != rows) {
+            return Err(TransformerError::shape("hstack requires equal row counts"));
+        }
+        let cols = parts.iter().map(|m| m.cols).sum();
+        let mut data = Vec::with_capacity(rows * cols);
+        for i in 0..rows {
+            for m in parts {
+                data.extend_from_slice(m.row(i));
+            }
+        }
+        Ok(Self { rows, cols, data })
+    }
+}
+
+impl Matrix<f64> {
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self::from_element(rows, cols, 0.0)
+    }
+
+    pub fn ones(rows: usize, cols: usize) -> Self {
+        Self::from_element(rows, cols, 1.0)
+    }
+
+    pub fn identity(n: usize) -> Self {
+        Self::from_fn(n, n, |i, j| if i == j { 1.0 } else { 0.0 })
+    }
+
+    /// Samples every element from `N(0, std_dev²)`.
+    pub fn random_normal(rows: usize, cols: usize, std_dev: f64, rng: &mut Rng) -> Self {
+        Self::from_fn(rows, cols, |_, _| rng.normal(0.0, std_dev))
+    }
+
+    /// Xavier/Glorot normal initialization for a `fan_in × fan_out` weight.
+    pub fn xavier(fan_in: usize, fan_out: usize, rng: &mut Rng) -> Self {
+        let std_dev = (2.0 / (fan_in + fan_out) as f64).sqrt();
+        Self::random_normal(fan_in, fan_out, std_dev, rng)
+    }
+
+    /// A `1 × n` row vector.
+    pub fn row_vector(values: Vec<f64>) -> Self {
+        Self {
+            rows: 1,
+            cols: values.len(),
+            data: values,
+        }
+    }
+}
+
+impl<T: Float> Matrix<T> {
+    /// Converts every element to another precision.
+    pub fn cast<U: Float>(&self) -> Matrix<U> {
+        self.map(|&x| U::from_f64(x.to_f64()))
+    }
+
+    /// Matrix product `self · other`.
+    pub fn matmul(&self, other: &Self) -> Result<Self> {
+        if self.cols != other.rows {
+            return Err(TransformerError::shape(format!(
+                "matmul shape mismatch: {:?} x {:?}",
+                self.shape(),
+                other.shape()
+            )));
+        }
+        let mut out = Self::from_element(self.rows, other.cols, T::ZERO);
+        // i-k-j ordering keeps the inner loop contiguous in both operands.
+        for i in 0..self.rows {
+            let out_row = &mut out.data[i * other.cols..(i + 1) * other.cols];
+            for k in 0..self.cols {
+                let a = self.data[i * self.cols + k];
+                if a == T::ZERO {
+                    continue;
+                }
+                let b_row = &other.data[k * other.cols..(k + 1) * other.cols];
+                for (o, &b) in out_row.iter_mut().zip(b_row) {
+                    *o += a * b;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Element-wise sum.
+    pub fn add(&self, other: &Self) -> Result<Self> {
+        self.zip_with(other, "add", |a, b| a + b)
+    }
+
+    /// Element-wise difference.
+    pub fn sub(&self, other: &Self) -> Result<Self> {
+        self.zip_with(other, "sub", |a, b| a - b)
+    }
+
+    /// Element-wise (Hadamard) product.
+    pub fn hadamard(&self, other: &Self) -> Result<Self> {
+        self.zip_with(other, "hadamard", |a, b| a * b)
+    }
+
+    /// Adds `other` into `self` in place.
+    pub fn add_assign(&mut self, other: &Self) -> Result<()> {
+        self.ensure_shape(other.shape(), "add_assign")?;
+        for (a, &b) in self.data.iter_mut().zip(&other.data) {
+            *a += b;
+        }
+        Ok(())
+    }
+
+    /// Multiplies every element by `factor`.
+    pub fn scale(&self, factor: T) -> Self {
+        self.map(|&x| x * factor)
+    }
+
+    /// Adds a `1 × cols` row vector to every row.
+    pub fn add_row_vector(&self, bias: &Self) -> Result<Self> {
+        if bias.rows != 1 || bias.cols != self.cols {
+            return Err(TransformerError::shape(format!(
+                "row-vector broadcast: expected 1x{}, got {:?}",
+                self.cols,
+                bias.shape()
+            )));
+        }
+        let mut out = self.clone();
+        for row in out.data.chunks_mut(self.cols.max(1)) {
+            for (x, &b) in row.iter_mut().zip(&bias.data) {
+                *x += b;
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn sum(&self) -> T {
+        self.data.iter().copied().sum()
+    }
+
+    pub fn mean(&self) -> T {
+        if self.data.is_empty() {
+            T::ZERO
+        } else {
+            self.sum() / T::from_f64(self.data.len() as f64)
+        }
+    }
+
+    /// Frobenius (L2) norm.
+    pub fn norm(&self) -> T {
+        self.data.iter().map(|&x| x * x).sum::<T>().sqrt()
+    }
+
+    /// Sum over rows, as a `1 × cols` row vector.
+    pub fn column_sums(&self) -> Self {
+        let mut out = vec![T::ZERO; self.cols];
+        for row in self.row_iter() {
+            for (o, &x) in out.iter_mut().zip(row) {
+                *o += x;
+            }
+        }
+        Self {
+            rows: 1,
+            cols: self.cols,
+            data: out,
+        }
+    }
+
+    /// Mean over rows, as a `1 × cols` row vector.
+    pub fn column_means(&self) -> Self {
+        let n = T::from_f64(self.rows.max(1) as f64);
+        self.column_sums().scale(T::ONE / n)
+    }
+
+    fn zip_with(&self, other: &Self, op: &str, f: impl Fn(T, T) -> T) -> Result<Self> {
+        if self.shape() != other.shape() {
+            return Err(TransformerError::shape(format!(
+                "{} shape mismatch: {:?} vs {:?}",
+                op,
+                self.shape(),
+                other.shape()
+            )));
+        }
+        Ok(Self {
+            rows: self.rows,
+            cols: self.cols,
+            data: self
+                .data
+                .iter()
+                .zip(&other.data)
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+        })
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        assert!(
+            i < self.rows && j < self.cols,
+            "index ({}, {}) out of bounds for {}x{} matrix",
+            i,
+            j,
+            self.rows,
+            self.cols
+        );
+        &self.data[i * self.cols + j]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        assert!(
+            i < self.rows && j < self.cols,
+            "index ({}, {}) out of bounds for {}x{} matrix",
+            i,
+            j,
+            self.rows,
+            self.cols
+        );
+        &mut self.data[i * self.cols + j]
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Matrix<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Matrix {}x{} [", self.rows, self.cols)?;
+        for row in self.row_iter() {
+            writeln!(f, "  {:?}", row)?;
+        }
+        write!(f, "]")
+    }
+}
@@ -0,0 +1,16 @@
+//! Tensor types backing every layer in the crate.
+//!
+//! [`Matrix`] arithmetic is generic over [`Float`], so weights and
+//! activations can be held as `f32` in half the memory of `f64`. The
+//! embedding, normalization and feed-forward layers run their forward pass
+//! in either precision; each has a `cast` to convert its weights. Attention,
+//! the encoder and decoder stacks, every backward pass and the optimizers
+//! still compute in `f64`; convert at the boundary with [`Matrix::cast`].
+
+mod float;
+mod matrix;
+mod tensor3;
+
+pub use float::Float;
+pub use matrix::Matrix;
+pub use tensor3::Tensor3;
@@ -0,0 +1,288 @@
+//! Dense batch of row-major matrices.
+
+use std::ops::{Index, IndexMut};
+
+use super::matrix::Matrix;
+use crate::utils::tensor_ops::softmax;
+use crate::{Result, TransformerError};
+
+/// A `batch × rows × cols` tensor stored contiguously, one row-major matrix
+/// after another.
+///
+/// Binary operations broadcast over the batch dimension: an operand with a
+/// batch of one is reused for every batch entry of the other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor3 {
+    batch: usize,
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Tensor3 {
+    pub fn zeros(batch: usize, rows: usize, cols: usize) -> Self {
+        Self {
+            batch,
+            rows,
+            cols,
+            data: vec![0.0; batch * rows * cols],
+        }
+    }
+
+    /// Creates a tensor from contiguous data, batch-major.
+    pub fn from_vec(batch: usize, rows: usize, cols: usize, data: Vec<f64>) -> Result<Self> {
+        if data.len() != batch * rows * cols {
+            return Err(TransformerError::shape(format!(
+                "cannot build a {}x{}x{} tensor from {} elements",
+                batch,
+                rows,
+                cols,
+                data.len()
+            )));
+        }
+        Ok(Self {
+            batch,
+            rows,
+            cols,
+            data,
+        })
+    }
+
+    /// Stacks matrices of identical shape along a new batch dimension.
+    pub fn from_matrices(matrices: &[Matrix]) -> Result<Self> {
+        let (rows, cols) = matrices.first().map_or((0, 0), |m| m.shape());
+        let mut data = Vec::with_capacity(matrices.len() * rows * cols);
+        for (b, m) in matrices.iter().enumerate() {
+            m.ensure_shape((rows, cols), &format!("batch entry {}", b))?;
+            data.extend_from_slice(m.as_slice());
+        }
+        Ok(Self {
+            batch: matrices.len(),
+            rows,
+            cols,
+            data,
+        })
+    }
+
+    /// `(batch, rows, cols)`.
+    pub fn shape(&self) -> (usize, usize, usize) {
+        (self.batch, self.rows, self.cols)
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn as_slice(&self) -> &[f64] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [f64] {
+        &mut self.data
+    }
+
+    /// Row-major elements of batch entry `b`.
+    pub fn batch_slice(&self, b: usize) -> &[f64] {
+        let size = self.rows * self.cols;
+        &self.data[b * size..(b + 1) * size]
+    }
+
+    pub fn batch_slice_mut(&mut self, b: usize) -> &mut [f64] {
+        let size = self.rows * self.cols;
+        &mut self.data[b * size..(b + 1) * size]
+    }
+
+    /// Copies batch entry `b` into a matrix.
+    pub fn matrix(&self, b: usize) -> Matrix {
+        Matrix::from_vec(self.rows, self.cols, self.batch_slice(b).to_vec())
+            .expect("batch slice has rows × cols elements")
+    }
+
+    /// Splits the tensor into one matrix per batch entry.
+    pub fn to_matrices(&self) -> Vec<Matrix> {
+        (0..self.batch).map(|b| self.matrix(b)).collect()
+    }
+
+    /// Applies `f` element-wise.
+    pub fn map(&self, f: impl FnMut(&f64) -> f64) -> Self {
+        Self {
+            data: self.data.iter().map(f).collect(),
+            ..*self
+        }
+    }
+
+    /// Multiplies every element by `factor`.
+    pub fn scale(&self, factor: f64) -> Self {
+        self.map(|x| x * factor)
+    }
+
+    /// Element-wise sum, broadcasting over the batch.
+    pub fn add(&self, other: &Self) -> Result<Self> {
+        if (self.rows, self.cols) != (other.rows, other.cols) {
+            return Err(TransformerError::shape(format!(
+                "add shape mismatch: {:?} vs {:?}",
+                self.shape(),
+                other.shape()
+            )));
+        }
+        let batch = broadcast_batch(self.batch, other.batch, "add")?;
+        let mut out = Self::zeros(batch, self.rows, self.cols);
+        for b in 0..batch {
+            let (x, y) = (self.broadcast_slice(b), other.broadcast_slice(b));
+            for ((o, a), c) in out.batch_slice_mut(b).iter_mut().zip(x).zip(y) {
+                *o = a + c;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Batched matrix product, broadcasting over the batch. A plain weight
+    /// matrix can be applied to every entry with [`matmul_matrix`](Self::matmul_matrix).
+    pub fn matmul(&self, other: &Self) -> Result<Self> {
+        if self.cols != other.rows {
+            return Err(TransformerError::shape(format!(
+                "batched matmul shape mismatch: {:?} x {:?}",
+                self.shape(),
+                other.shape()
+            )));
+        }
+        let batch = broadcast_batch(self.batch, other.batch, "matmul")?;
+        let (n, k, m) = (self.rows, self.cols, other.cols);
+        let mut out = Self::zeros(batch, n, m);
+        for b in 0..batch {
+            let (x, y) = (self.broadcast_slice(b), other.broadcast_slice(b));
+            let out_b = out.batch_slice_mut(b);
+            // Same i-k-j ordering as `Matrix::matmul`.
+            for i in 0..n {
+                let out_row = &mut out_b[i * m..(i + 1) * m];
+                for kk in 0..k {
+                    let a = x[i * k + kk];
+                    if a == 0.0 {
+                        continue;
+                    }
+                    for (o, &w) in out_row.iter_mut().zip(&y[kk * m..(kk + 1) * m]) {
+                        *o += a * w;
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Multiplies every batch entry by `weight`.
+    pub fn matmul_matrix(&self, weight: &Matrix) -> Result<Self> {
+        self.matmul(&Self::from(weight.clone()))
+    }
+
+    /// Swaps the row and column dimensions of every batch entry.
+    pub fn transpose(&self) -> Self {
+        let mut out = Self::zeros(self.batch, self.cols, self.rows);
+        for b in 0..self.batch {
+            let src = self.batch_slice(b);
+            let dst = out.batch_slice_mut(b);
+            for i in 0..self.rows {
+                for j in 0..self.cols {
+                    dst[j * self.rows + i] = src[i * self.cols + j];
+                }
+            }
+        }
+        out
+    }
+
+    /// Softmax over the last dimension.
+    pub fn softmax(&self) -> Self {
+        let mut out = self.clone();
+        for row in out.data.chunks_mut(self.cols.max(1)) {
+            let probs = softmax(row);
+            row.copy_from_slice(&probs);
+        }
+        out
+    }
+
+    /// Layer normalization over the last dimension with `1 × cols` scale and
+    /// shift vectors.
+    pub fn layer_norm(&self, gamma: &Matrix, beta: &Matrix, eps: f64) -> Result<Self> {
+        gamma.ensure_shape((1, self.cols), "layer norm gamma")?;
+        beta.ensure_shape((1, self.cols), "layer norm beta")?;
+        let n = self.cols as f64;
+        let mut out = self.clone();
+        for row in out.data.chunks_mut(self.cols.max(1)) {
+            let mean = row.iter().sum::<f64>() / n;
+            let var = row.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / n;
+            let inv_std = 1.0 / (var + eps).sqrt();
+            for ((v, g), b) in row.iter_mut().zip(gamma.as_slice()).zip(beta.as_slice()) {
+                *v = (*v - mean) * inv_std * g + b;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Batch entry `b`, reusing entry 0 when the batch is broadcast.
+    fn broadcast_slice(&self, b: usize) -> &[f64] {
+        self.batch_slice(if self.batch == 1 { 0 } else { b })
+    }
+}
+
+impl From<Matrix> for Tensor3 {
+    /// A batch of one.
+    fn from(m: Matrix) -> Self {
+        let (rows, cols) = m.shape();
+        Self {
+            batch: 1,
+            rows,
+            cols,
+            data: m.into_vec(),
+        }
+    }
+}
+
+impl Index<(usize, usize, usize)> for Tensor3 {
+    type Output = f64;
+
+    fn index(&self, (b, i, j): (usize, usize, usize)) -> &f64 {
+        assert!(
+            b < self.batch && i < self.rows && j < self.cols,
+            "index ({}, {}, {}) out of bounds for {:?} tensor",
+            b,
+            i,
+            j,
+            self.shape()
+        );
+        &self.data[(b * self.rows + i) * self.cols + j]
+    }
+}
+
+impl IndexMut<(usize, usize, usize)> for Tensor3 {
+    fn index_mut(&mut self, (b, i, j): (usize, usize, usize)) -> &mut f64 {
+        assert!(
+            b < self.batch && i < self.rows && j < self.cols,
+            "index ({}, {}, {}) out of bounds for {:?} tensor",
+            b,
+            i,
+            j,
+            self.shape()
+        );
+        &mut self.data[(b * self.rows + i) * self.cols + j]
+    }
+}
+
+/// Batch size of a broadcast binary operation.
+fn broadcast_batch(a: usize, b: usize, op: &str) -> Result<usize> {
+    match (a, b) {
+        _ if a == b => Ok(a),
+        (1, _) => Ok(b),
+        (_, 1) => Ok(a),
+        _ => Err(TransformerError::shape(format!(
+            "{}: cannot broadcast batch sizes {} and {}",
+            op, a, b
+        ))),
+    }
+}
@@ -0,0 +1,475 @@
+//! Numerical parity harness.
+//!
+//! Validates the crate's layers against a reference implementation (normally
+//! PyTorch) by loading fixed weights and recorded activations from NumPy
+//! fixtures and comparing every intermediate output within a tolerance.
+//!
+//! Fixtures use the crate's parameter names (see [`Parameters`]) for weights,
+//! with projection matrices stored in `in × out` layout, i.e. the transpose of
+//! a PyTorch `nn.Linear` weight. Activations use these stage names:
+//!
+//! - `input_ids`, `decoder_input_ids`: token ids (1-D)
+//! - `pad_token_id`: optional scalar; enables padding masks
+//! - `encoder.embeddings`, `encoder.layers.{i}.output`, `encoder.output`
+//! - `decoder.embeddings`, `decoder.layers.{i}.output`, `logits`
+//!
+//! `scripts/export_parity_fixtures.py` produces compatible fixtures for the
+//! encoder from `torch.nn.TransformerEncoderLayer`.
+
+pub mod npy;
+pub mod npz;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::models::{Encoder, Transformer};
+use crate::params::Parameters;
+use crate::progress::{NoProgress, Progress, ProgressHandler, Stage};
+use crate::tensor::Matrix;
+use crate::utils::mask::{
+    combine_masks, create_causal_mask, create_cross_attention_mask, create_padding_mask, Mask,
+};
+use crate::Result;
+
+/// Named tensors loaded from `.npz` archives or directories of `.npy` files.
+#[derive(Debug, Clone, Default)]
+pub struct Fixtures {
+    tensors: BTreeMap<String, Matrix>,
+}
+
+impl Fixtures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads an `.npz` archive, or every `.npy` file in a directory (named by
+    /// file stem).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let arrays = if path.is_dir() {
+            let mut arrays = BTreeMap::new();
+            for entry in std::fs::read_dir(path)? {
+                let file = entry?.path();
+                if file.extension().is_some_and(|e| e == "npy") {
+                    let stem = file
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .ok_or_else(|| format!("non UTF-8 fixture name {:?}", file))?
+                        .to_string();
+                    arrays.insert(stem, npy::read_npy(&file)?);
+                }
+            }
+            arrays
+        } else {
+            npz::read_npz(path)?
+        };
+
+        let mut fixtures = Self::new();
+        for (name, array) in arrays {
+            let matrix = array
+                .into_matrix()
+                .map_err(|e| format!("fixture '{}': {}", name, e))?;
+            fixtures.insert(name, matrix);
+        }
+        Ok(fixtures)
+    }
+
+    /// Writes every tensor to an uncompressed `.npz` archive.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        npz::write_npz(path, self.tensors.iter().map(|(k, v)| (k.as_str(), v)))
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, tensor: Matrix) {
+        self.tensors.insert(name.into(), tensor);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tensors.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Matrix> {
+        self.tensors
+            .get(name)
+            .ok_or_else(|| format!("fixture '{}' not found", name).into())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tensors.keys().map(String::as_str)
+    }
+
+    /// Reads a fixture of non-negative integers as token ids.
+    pub fn tokens(&self, name: &str) -> Result<Vec<usize>> {
+        self.get(name)?
+            .as_slice()
+            .iter()
+            .map(|&v| {
+                if v >= 0.0 && v.fract() == 0.0 {
+                    Ok(v as usize)
+                } else {
+                    Err(format!("fixture '{}' contains non-token value {}", name, v).into())
+                }
+            })
+            .collect()
+    }
+
+    /// Records every parameter of `model` under its name.
+    pub fn from_parameters<P: Parameters + ?Sized>(model: &P, prefix: &str) -> Self {
+        let mut fixtures = Self::new();
+        model.visit_parameters(prefix, &mut |name, m| fixtures.insert(name, m.clone()));
+        fixtures
+    }
+}
+
+/// Copies fixture weights into every parameter of `model`.
+///
+/// Fails, listing the offending names, if any parameter is missing or has the
+/// wrong shape. Returns the number of parameters loaded.
+pub fn load_parameters<P: Parameters + ?Sized>(
+    model: &mut P,
+    prefix: &str,
+    fixtures: &Fixtures,
+) -> Result<usize> {
+    load_parameters_with_progress(model, prefix, fixtures, &NoProgress)
+}
+
+/// [`load_parameters`], reporting [`Stage::LoadingWeights`] after every
+/// parameter.
+pub fn load_parameters_with_progress<P: Parameters + ?Sized>(
+    model: &mut P,
+    prefix: &str,
+    fixtures: &Fixtures,
+    progress: &dyn ProgressHandler,
+) -> Result<usize> {
+    let mut total = 0;
+    model.visit_parameters(prefix, &mut |_, _| total += 1);
+    let mut visited = 0;
+    let mut loaded = 0;
+    let mut problems = Vec::new();
+    model.visit_parameters_mut(prefix, &mut |name, param| {
+        match fixtures.get(name) {
+            Ok(value) if value.shape() == param.shape() => {
+                *param = value.clone();
+                loaded += 1;
+            }
+            Ok(value) => problems.push(format!(
+                "{}: expected shape {:?}, fixture has {:?}",
+                name,
+                param.shape(),
+                value.shape()
+            )),
+            Err(_) => problems.push(format!("{}: missing", name)),
+        }
+        visited += 1;
+        progress.on_progress(&Progress::new(Stage::LoadingWeights, visited, total));
+    });
+    if !problems.is_empty() {
+        return Err(format!("cannot load parameters:\n  {}", problems.join("\n  ")).into());
+    }
+    Ok(loaded)
+}
+
+/// Absolute and relative tolerance, with `numpy.allclose` semantics:
+/// `|actual - expected| <= atol + rtol * |expected|`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub atol: f64,
+    pub rtol: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            atol: 1e-5,
+            rtol: 1e-4,
+        }
+    }
+}
+
+/// A single element that fell outside tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    pub row: usize,
+    pub col: usize,
+    pub actual: f64,
+    pub expected: f64,
+}
+
+/// Result of comparing one tensor against its reference.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub name: String,
+    pub actual_shape: (usize, usize),
+    pub expected_shape: (usize, usize),
+    pub tolerance: Tolerance,
+    pub max_abs_diff: f64,
+    pub mean_abs_diff: f64,
+    /// Number of elements outside tolerance.
+    pub num_mismatches: usize,
+    /// The worst offenders, largest difference first.
+    pub worst: Vec<Mismatch>,
+}
+
+impl Comparison {
+    pub fn passed(&self) -> bool {
+        self.actual_shape == self.expected_shape && self.num_mismatches == 0
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.actual_shape != self.expected_shape {
+            return write!(
+                f,
+                "FAIL {}: shape {:?} != expected {:?}",
+                self.name, self.actual_shape, self.expected_shape
+            );
+        }
+        let total = self.actual_shape.0 * self.actual_shape.1;
+        write!(
+            f,
+            "{} {}: max |diff| {:.3e}, mean |diff| {:.3e}",
+            if self.passed() { "ok  " } else { "FAIL" },
+            self.name,
+            self.max_abs_diff,
+            self.mean_abs_diff
+        )?;
+        if !self.passed() {
+            write!(
+                f,
+                ", {}/{} elements outside atol={:e} rtol={:e}",
+                self.num_mismatches, total, self.tolerance.atol, self.tolerance.rtol
+            )?;
+            for m in &self.worst {
+                write!(
+                    f,
+                    "\n       at ({}, {}): actual {:.8} expected {:.8} (diff {:+.3e})",
+                    m.row,
+                    m.col,
+                    m.actual,
+                    m.expected,
+                    m.actual - m.expected
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares `actual` against `expected` element-wise.
+pub fn compare(name: &str, actual: &Matrix, expected: &Matrix, tolerance: Tolerance) -> Comparison {
+    const WORST_SHOWN: usize = 5;
+    let mut comparison = Comparison {
+        name: name.to_string(),
+        actual_shape: actual.shape(),
+        expected_shape: expected.shape(),
+        tolerance,
+        max_abs_diff: f64::NAN,
+        mean_abs_diff: f64::NAN,
+        num_mismatches: 0,
+        worst: Vec::new(),
+    };
+    if actual.shape() != expected.shape() {
+        return comparison;
+    }
+
+    let cols = actual.cols().max(1);
+    let mut max_diff = 0.0f64;
+    let mut sum_diff = 0.0;
+    let mut mismatches = Vec::new();
+    for (idx, (&a, &e)) in actual
+        .as_slice()
+        .iter()
+        .zip(expected.as_slice())
+        .enumerate()
+    {
+        let diff = (a - e).abs();
+        // NaN never compares within tolerance, so it is always reported.
+        let within = diff <= tolerance.atol + tolerance.rtol * e.abs();
+        if diff.is_nan() {
+            max_diff = f64::INFINITY;
+        } else {
+            max_diff = max_diff.max(diff);
+            sum_diff += diff;
+        }
+        if !within {
+            mismatches.push(Mismatch {
+                row: idx / cols,
+                col: idx % cols,
+                actual: a,
+                expected: e,
+            });
+        }
+    }
+    mismatches.sort_by(|x, y| {
+        let dx = (x.actual - x.expected).abs();
+        let dy = (y.actual - y.expected).abs();
+        dy.partial_cmp(&dx).unwrap_or(std::cmp::Ordering::Less)
+    });
+
+    comparison.max_abs_diff = max_diff;
+    comparison.mean_abs_diff = sum_diff / actual.len().max(1) as f64;
+    comparison.num_mismatches = mismatches.len();
+    mismatches.truncate(WORST_SHOWN);
+    comparison.worst = mismatches;
+    comparison
+}
+
+/// Panics with a diff report if `actual` does not match `expected`.
+#[track_caller]
+pub fn assert_close(name: &str, actual: &Matrix, expected: &Matrix, tolerance: Tolerance) {
+    let comparison = compare(name, actual, expected, tolerance);
+    assert!(comparison.passed(), "{}", comparison);
+}
+
+/// Outcome of a layer-by-layer parity run.
+#[derive(Debug, Clone, Default)]
+pub struct ParityReport {
+    pub comparisons: Vec<Comparison>,
+}
+
+impl ParityReport {
+    pub fn passed(&self) -> bool {
+        self.comparisons.iter().all(Comparison::passed)
+    }
+
+    /// The earliest failing stage, which is where numerical drift begins.
+    pub fn first_failure(&self) -> Option<&Comparison> {
+        self.comparisons.iter().find(|c| !c.passed())
+    }
+
+    /// Panics with the full report if any stage failed.
+    #[track_caller]
+    pub fn assert_passed(&self) {
+        assert!(self.passed(), "parity check failed:\n{}", self);
+    }
+}
+
+impl fmt::Display for ParityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for comparison in &self.comparisons {
+            writeln!(f, "{}", comparison)?;
+        }
+        if let Some(first) = self.first_failure() {
+            write!(f, "first divergence: {}", first.name)?;
+        } else {
+            write!(f, "all {} stages match", self.comparisons.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Accumulates comparisons of named stages against fixtures.
+#[derive(Debug)]
+pub struct ParityChecker<'a> {
+    fixtures: &'a Fixtures,
+    tolerance: Tolerance,
+    report: ParityReport,
+}
+
+impl<'a> ParityChecker<'a> {
+    pub fn new(fixtures: &'a Fixtures, tolerance: Tolerance) -> Self {
+        Self {
+            fixtures,
+            tolerance,
+            report: ParityReport::default(),
+        }
+    }
+
+    /// Compares `actual` against fixture `name`, which must exist.
+    pub fn check(&mut self, name: &str, actual: &Matrix) -> Result<()> {
+        let expected = self.fixtures.get(name)?;
+        self.report
+            .comparisons
+            .push(compare(name, actual, expected, self.tolerance));
+        Ok(())
+    }
+
+    /// Compares against fixture `name` only if it was recorded.
+    pub fn check_if_present(&mut self, name: &str, actual: &Matrix) -> Result<()> {
+        if self.fixtures.contains(name) {
+            self.check(name, actual)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> ParityReport {
+        self.report
+    }
+}
+
+/// Runs `encoder` on the `input_ids` fixture and compares the embeddings,
+/// every layer output and the final output against the reference.
+///
+/// Dropout should be disabled (evaluation mode) for a meaningful comparison.
+pub fn encoder_parity(
+    encoder: &Encoder,
+    fixtures: &Fixtures,
+    tolerance: Tolerance,
+) -> Result<ParityReport> {
+    let tokens = fixtures.tokens("input_ids")?;
+    let mask = padding_mask(fixtures, &tokens)?;
+    let mut checker = ParityChecker::new(fixtures, tolerance);
+    encoder_stages(encoder, &tokens, mask.as_ref(), &mut checker)?;
+    Ok(checker.finish())
+}
+
+/// Like [`encoder_parity`], then also runs the decoder on `decoder_input_ids`
+/// and compares its embeddings, layer outputs and `logits`.
+pub fn transformer_parity(
+    model: &Transformer,
+    fixtures: &Fixtures,
+    tolerance: Tolerance,
+) -> Result<ParityReport> {
+    let src = fixtures.tokens("input_ids")?;
+    let tgt = fixtures.tokens("decoder_input_ids")?;
+    let src_mask = padding_mask(fixtures, &src)?;
+    let causal = create_causal_mask(tgt.len());
+    let tgt_mask = match padding_mask(fixtures, &tgt)? {
+        Some(pad) => combine_masks(&causal, &pad)?,
+        None => causal,
+    };
+
+    let memory_mask =
+        pad_token(fixtures)?.map(|pad| create_cross_attention_mask(tgt.len(), &src, pad));
+
+    let mut checker = ParityChecker::new(fixtures, tolerance);
+    let memory = encoder_stages(&model.encoder, &src, src_mask.as_ref(), &mut checker)?;
+
+    let mut x = model.decoder.embed(&tgt)?;
+    checker.check_if_present("decoder.embeddings", &x)?;
+    for (i, layer) in model.decoder.layers.iter().enumerate() {
+        x = layer.forward(&x, &memory, Some(&tgt_mask), memory_mask.as_ref())?;
+        checker.check_if_present(&format!("decoder.layers.{}.output", i), &x)?;
+    }
+    checker.check("logits", &model.decoder.output_logits(&x)?)?;
+    Ok(checker.finish())
+}
+
+fn encoder_stages(
+    encoder: &Encoder,
+    tokens: &[usize],
+    mask: Option<&Mask>,
+    checker: &mut ParityChecker<'_>,
+) -> Result<Matrix> {
+    let mut x = encoder.embed(tokens)?;
+    checker.check_if_present("encoder.embeddings", &x)?;
+    for (i, layer) in encoder.layers.iter().enumerate() {
+        x = layer.forward(&x, mask)?;
+        checker.check_if_present(&format!("encoder.layers.{}.output", i), &x)?;
+    }
+    checker.check("encoder.output", &x)?;
+    Ok(x)
+}
+
+fn padding_mask(fixtures: &Fixtures, tokens: &[usize]) -> Result<Option<Mask>> {
+    Ok(pad_token(fixtures)?.map(|pad| create_padding_mask(tokens, pad)))
+}
+
+fn pad_token(fixtures: &Fixtures) -> Result<Option<usize>> {
+    if !fixtures.contains("pad_token_id") {
+        return Ok(None);
+    }
+    let pad = fixtures.tokens("pad_token_id")?;
+    Ok(Some(*pad.first().ok_or("fixture 'pad_token_id' is empty")?))
+}
@@ -0,0 +1,187 @@
+//! Reader and writer for NumPy `.npy` arrays.
+//!
+//! Only what parity fixtures need is supported: little-endian numeric and
+//! boolean dtypes with up to two dimensions. Everything is converted to `f64`.
+
+use std::fs;
+use std::path::Path;
+
+use crate::tensor::Matrix;
+use crate::Result;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// A decoded `.npy` array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    /// Elements in C (row-major) order.
+    pub data: Vec<f64>,
+}
+
+impl NpyArray {
+    /// Converts to a matrix: scalars become `1 × 1`, vectors `1 × n`.
+    pub fn into_matrix(self) -> Result<Matrix> {
+        match self.shape.as_slice() {
+            [] => Matrix::from_vec(1, 1, self.data),
+            [n] => Matrix::from_vec(1, *n, self.data),
+            [rows, cols] => Matrix::from_vec(*rows, *cols, self.data),
+            shape => Err(format!("arrays with shape {:?} are not supported", shape).into()),
+        }
+    }
+}
+
+/// Reads a `.npy` file.
+pub fn read_npy(path: impl AsRef<Path>) -> Result<NpyArray> {
+    parse_npy(&fs::read(path)?)
+}
+
+/// Decodes the contents of a `.npy` file.
+pub fn parse_npy(bytes: &[u8]) -> Result<NpyArray> {
+    if bytes.len() < 10 || &bytes[..6] != MAGIC {
+        return Err("not a .npy file (bad magic)".into());
+    }
+    let major = bytes[6];
+    let (header_len, header_start) = match major {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 => {
+            if bytes.len() < 12 {
+                return Err("truncated .npy header".into());
+            }
+            (
+                u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+                12,
+            )
+        }
+        v => return Err(format!("unsupported .npy version {}", v).into()),
+    };
+    let header_end = header_start + header_len;
+    if bytes.len() < header_end {
+        return Err("truncated .npy header".into());
+    }
+    let header = std::str::from_utf8(&bytes[header_start..header_end])?;
+
+    let descr = header_value(header, "descr")
+        .and_then(|v| v.strip_prefix('\'').and_then(|v| v.split('\'').next()))
+        .ok_or("missing 'descr' in .npy header")?;
+    let fortran_order = header_value(header, "fortran_order")
+        .map(|v| v.starts_with("True"))
+        .unwrap_or(false);
+    let shape =
+        parse_shape(header_value(header, "shape").ok_or("missing 'shape' in .npy header")?)?;
+
+    let count: usize = shape.iter().product();
+    let payload = &bytes[header_end..];
+    let data = decode_elements(descr, payload, count)?;
+    let data = if fortran_order && shape.len() == 2 {
+        let (rows, cols) = (shape[0], shape[1]);
+        (0..rows * cols)
+            .map(|idx| data[(idx % cols) * rows + idx / cols])
+            .collect()
+    } else {
+        data
+    };
+    Ok(NpyArray { shape, data })
+}
+
+/// Encodes a matrix as a 2-D `<f8` `.npy` file.
+pub fn encode_npy(matrix: &Matrix) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+        matrix.rows(),
+        matrix.cols()
+    );
+    // Pad so the payload starts on a 64-byte boundary, ending with a newline.
+    while !(MAGIC.len() + 4 + header.len() + 1).is_multiple_of(64) {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(MAGIC.len() + 4 + header.len() + matrix.len() * 8);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    for v in matrix.as_slice() {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out
+}
+
+/// Writes a matrix to a `.npy` file.
+pub fn write_npy(path: impl AsRef<Path>, matrix: &Matrix) -> Result<()> {
+    fs::write(path, encode_npy(matrix))?;
+    Ok(())
+}
+
+/// Returns the text following `'key':` in a header dict.
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("'{}':", key);
+    let start = header.find(&pattern)? + pattern.len();
+    Some(header[start..].trim_start())
+}
+
+fn parse_shape(text: &str) -> Result<Vec<usize>> {
+    let inner = text
+        .strip_prefix('(')
+        .and_then(|t| t.split(')').next())
+        .ok_or("malformed shape in .npy header")?;
+    inner
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>().map_err(|e| e.into()))
+        .collect()
+}
+
+fn decode_elements(descr: &str, payload: &[u8], count: usize) -> Result<Vec<f64>> {
+    let (endian, kind) = descr.split_at(1);
+    if endian == ">" {
+        return Err(format!("big-endian dtype '{}' is not supported", descr).into());
+    }
+    let width = match kind {
+        "f8" | "i8" | "u8" => 8,
+        "f4" | "i4" | "u4" => 4,
+        "i2" | "u2" => 2,
+        "b1" | "u1" | "i1" => 1,
+        _ => return Err(format!("unsupported dtype '{}'", descr).into()),
+    };
+    if payload.len() < count * width {
+        return Err(format!(
+            "payload holds {} bytes, expected {}",
+            payload.len(),
+            count * width
+        )
+        .into());
+    }
+    let chunks = payload[..count * width].chunks_exact(width);
+    let data = match kind {
+        "f8" => chunks
+            .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+            .collect(),
+        "f4" => chunks
+            .map(|c| f32::from_le_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        "i8" => chunks
+            .map(|c| i64::from_le_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        "u8" => chunks
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        "i4" => chunks
+            .map(|c| i32::from_le_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        "u4" => chunks
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        "i2" => chunks
+            .map(|c| i16::from_le_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        "u2" => chunks
+            .map(|c| u16::from_le_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        "i1" => chunks.map(|c| c[0] as i8 as f64).collect(),
+        _ => chunks.map(|c| c[0] as f64).collect(),
+    };
+    Ok(data)
+}
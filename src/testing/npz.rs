@@ -0,0 +1,187 @@
+//! Reader and writer for NumPy `.npz` archives.
+//!
+//! An `.npz` file is a ZIP archive of `.npy` members. Archives written by
+//! `np.savez` use stored (uncompressed) entries, which is what this module
+//! supports; `np.savez_compressed` output is rejected with a clear error.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::npy::{encode_npy, parse_npy, NpyArray};
+use crate::tensor::Matrix;
+use crate::utils::checksum::crc32;
+use crate::Result;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+
+/// Reads every array in an `.npz` file, keyed by member name without the
+/// `.npy` suffix.
+pub fn read_npz(path: impl AsRef<Path>) -> Result<BTreeMap<String, NpyArray>> {
+    parse_npz(&fs::read(path)?)
+}
+
+/// Decodes the contents of an `.npz` archive.
+pub fn parse_npz(bytes: &[u8]) -> Result<BTreeMap<String, NpyArray>> {
+    let eocd = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&i| read_u32(bytes, i) == Some(END_OF_CENTRAL_DIR_SIG))
+        .ok_or("not a ZIP archive (no end of central directory)")?;
+    let entries = read_u16(bytes, eocd + 10).ok_or("truncated ZIP trailer")? as usize;
+    let mut offset = read_u32(bytes, eocd + 16).ok_or("truncated ZIP trailer")? as usize;
+
+    let mut arrays = BTreeMap::new();
+    for _ in 0..entries {
+        if read_u32(bytes, offset) != Some(CENTRAL_HEADER_SIG) {
+            return Err("corrupt ZIP central directory".into());
+        }
+        let field = |at: usize| read_u16(bytes, offset + at).ok_or("truncated ZIP entry");
+        let method = field(10)?;
+        let name_len = field(28)? as usize;
+        let extra_len = field(30)? as usize;
+        let comment_len = field(32)? as usize;
+        let mut size = read_u32(bytes, offset + 20).ok_or("truncated ZIP entry")? as u64;
+        let mut local_offset = read_u32(bytes, offset + 42).ok_or("truncated ZIP entry")? as u64;
+
+        let name_start = offset + 46;
+        let name = std::str::from_utf8(
+            bytes
+                .get(name_start..name_start + name_len)
+                .ok_or("truncated ZIP entry name")?,
+        )?
+        .to_string();
+        let extra = bytes
+            .get(name_start + name_len..name_start + name_len + extra_len)
+            .ok_or("truncated ZIP extra field")?;
+        apply_zip64_extra(extra, &mut size, &mut local_offset);
+
+        if method != 0 {
+            return Err(format!(
+                "member '{}' is compressed (method {}); write fixtures with np.savez",
+                name, method
+            )
+            .into());
+        }
+
+        let local = local_offset as usize;
+        if read_u32(bytes, local) != Some(LOCAL_HEADER_SIG) {
+            return Err(format!("corrupt local header for '{}'", name).into());
+        }
+        let local_name_len = read_u16(bytes, local + 26).ok_or("truncated local header")? as usize;
+        let local_extra_len = read_u16(bytes, local + 28).ok_or("truncated local header")? as usize;
+        let data_start = local + 30 + local_name_len + local_extra_len;
+        let data = bytes
+            .get(data_start..data_start + size as usize)
+            .ok_or_else(|| format!("truncated data for '{}'", name))?;
+
+        let key = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+        arrays.insert(key, parse_npy(data)?);
+        offset = name_start + name_len + extra_len + comment_len;
+    }
+    Ok(arrays)
+}
+
+/// Encodes named matrices as an uncompressed `.npz` archive.
+pub fn encode_npz<'a>(arrays: impl IntoIterator<Item = (&'a str, &'a Matrix)>) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    let mut count = 0u16;
+
+    for (name, matrix) in arrays {
+        let file_name = format!("{}.npy", name);
+        let data = encode_npy(matrix);
+        let crc = crc32(&data);
+        let local_offset = out.len() as u32;
+
+        out.extend_from_slice(&LOCAL_HEADER_SIG.to_le_bytes());
+        push_entry_fields(&mut out, crc, data.len() as u32, file_name.len() as u16);
+        out.extend_from_slice(file_name.as_bytes());
+        out.extend_from_slice(&data);
+
+        central.extend_from_slice(&CENTRAL_HEADER_SIG.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        push_entry_fields(&mut central, crc, data.len() as u32, file_name.len() as u16);
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&local_offset.to_le_bytes());
+        central.extend_from_slice(file_name.as_bytes());
+        count += 1;
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&END_OF_CENTRAL_DIR_SIG.to_le_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0]); // disk numbers
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    out
+}
+
+/// Writes named matrices to an uncompressed `.npz` file.
+pub fn write_npz<'a>(
+    path: impl AsRef<Path>,
+    arrays: impl IntoIterator<Item = (&'a str, &'a Matrix)>,
+) -> Result<()> {
+    fs::write(path, encode_npz(arrays))?;
+    Ok(())
+}
+
+/// Fields shared by local and central headers, from "version needed" through
+/// "extra field length".
+fn push_entry_fields(out: &mut Vec<u8>, crc: u32, size: u32, name_len: u16) {
+    out.extend_from_slice(&20u16.to_le_bytes()); // version needed
+    out.extend_from_slice(&0u16.to_le_bytes()); // flags
+    out.extend_from_slice(&0u16.to_le_bytes()); // method: stored
+    out.extend_from_slice(&0u16.to_le_bytes()); // mod time
+    out.extend_from_slice(&0x21u16.to_le_bytes()); // mod date (1980-01-01)
+    out.extend_from_slice(&crc.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes()); // compressed size
+    out.extend_from_slice(&size.to_le_bytes()); // uncompressed size
+    out.extend_from_slice(&name_len.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // extra length
+}
+
+/// Replaces saturated 32-bit fields with their ZIP64 values. NumPy forces
+/// ZIP64 entries, although sizes only overflow for very large arrays.
+fn apply_zip64_extra(extra: &[u8], size: &mut u64, offset: &mut u64) {
+    let mut pos = 0;
+    while pos + 4 <= extra.len() {
+        let id = u16::from_le_bytes([extra[pos], extra[pos + 1]]);
+        let len = u16::from_le_bytes([extra[pos + 2], extra[pos + 3]]) as usize;
+        let body = &extra[pos + 4..(pos + 4 + len).min(extra.len())];
+        if id == 0x0001 {
+            let mut fields = body
+                .chunks_exact(8)
+                .map(|c| u64::from_le_bytes(c.try_into().unwrap()));
+            // Order: uncompressed size, compressed size, local header offset;
+            // each is present only if its 32-bit field is saturated.
+            if *size == 0xFFFF_FFFF {
+                if let Some(uncompressed) = fields.next() {
+                    *size = uncompressed;
+                }
+                fields.next();
+            }
+            if *offset == 0xFFFF_FFFF {
+                if let Some(local) = fields.next() {
+                    *offset = local;
+                }
+            }
+        }
+        pos += 4 + len;
+    }
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
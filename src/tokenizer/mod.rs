@@ -0,0 +1,45 @@
+//! Conversion between text and token ids.
+//!
+//! Models only see token ids; a [`Tokenizer`] supplies the mapping when
+//! generation needs to reason about text, for example to ban phrases.
+//! [`VocabTokenizer`] is a small greedy subword tokenizer over a fixed
+//! vocabulary; [`HfTokenizer`](crate::huggingface::HfTokenizer) reads the
+//! `tokenizer.json` of pretrained checkpoints. [`encode_corpus`] tokenizes
+//! many documents with progress reports.
+
+mod vocab;
+
+pub use vocab::VocabTokenizer;
+
+use crate::progress::{Progress, ProgressHandler, Stage};
+use crate::Result;
+
+/// Maps text to token ids and back.
+pub trait Tokenizer: Send + Sync {
+    /// Number of ids the tokenizer can produce.
+    fn vocab_size(&self) -> usize;
+
+    /// Splits `text` into token ids.
+    fn encode(&self, text: &str) -> Result<Vec<usize>>;
+
+    /// Joins `ids` back into text. Special tokens decode to nothing.
+    fn decode(&self, ids: &[usize]) -> Result<String>;
+}
+
+/// Encodes every document of `corpus`, reporting [`Stage::Tokenizing`] after
+/// each one.
+pub fn encode_corpus<S: AsRef<str>>(
+    tokenizer: &dyn Tokenizer,
+    corpus: &[S],
+    progress: &dyn ProgressHandler,
+) -> Result<Vec<Vec<usize>>> {
+    corpus
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let ids = tokenizer.encode(text.as_ref())?;
+            progress.on_progress(&Progress::new(Stage::Tokenizing, i + 1, corpus.len()));
+            Ok(ids)
+        })
+        .collect()
+}
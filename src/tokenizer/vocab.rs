@@ -0,0 +1,112 @@
+//! Greedy longest-match tokenizer over a fixed vocabulary.
+
+use std::collections::{HashMap, HashSet};
+
+use super::Tokenizer;
+use crate::{Result, TransformerError};
+
+/// Splits text by repeatedly taking the longest vocabulary entry that
+/// prefixes the remaining input. Decoding concatenates token strings, so
+/// `decode(encode(text)) == text` whenever no unknown token was produced.
+#[derive(Debug, Clone)]
+pub struct VocabTokenizer {
+    tokens: Vec<String>,
+    index: HashMap<String, usize>,
+    /// Longest entry in bytes, bounding the prefix search.
+    max_token_len: usize,
+    unk_token: Option<usize>,
+    special: HashSet<usize>,
+}
+
+impl VocabTokenizer {
+    /// Builds a tokenizer where token `i` is `tokens[i]`. Duplicate entries
+    /// are rejected; empty strings are allowed for special tokens but are
+    /// never produced by [`encode`](Tokenizer::encode).
+    pub fn new(tokens: Vec<String>) -> Result<Self> {
+        let mut index = HashMap::with_capacity(tokens.len());
+        for (id, token) in tokens.iter().enumerate() {
+            if token.is_empty() {
+                continue;
+            }
+            if index.insert(token.clone(), id).is_some() {
+                return Err(format!("duplicate vocabulary entry {:?}", token).into());
+            }
+        }
+        let max_token_len = tokens.iter().map(String::len).max().unwrap_or(0);
+        Ok(Self {
+            tokens,
+            index,
+            max_token_len,
+            unk_token: None,
+            special: HashSet::new(),
+        })
+    }
+
+    /// Emits `id` for characters no entry covers instead of failing.
+    pub fn with_unk_token(mut self, id: usize) -> Self {
+        self.unk_token = Some(id);
+        self.special.insert(id);
+        self
+    }
+
+    /// Marks ids (padding, start and end tokens, ...) that decode to nothing.
+    pub fn with_special_tokens(mut self, ids: impl IntoIterator<Item = usize>) -> Self {
+        self.special.extend(ids);
+        self
+    }
+
+    /// The string of a single token, or `None` if `id` is out of range.
+    pub fn token(&self, id: usize) -> Option<&str> {
+        self.tokens.get(id).map(String::as_str)
+    }
+
+    pub fn token_id(&self, token: &str) -> Option<usize> {
+        self.index.get(token).copied()
+    }
+}
+
+impl Tokenizer for VocabTokenizer {
+    fn vocab_size(&self) -> usize {
+        self.tokens.len()
+    }
+
+    fn encode(&self, text: &str) -> Result<Vec<usize>> {
+        let mut ids = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let longest = (1..=self.max_token_len.min(rest.len()))
+                .rev()
+                .filter(|&n| rest.is_char_boundary(n))
+                .find_map(|n| self.index.get(&rest[..n]).map(|&id| (id, n)));
+            match (longest, self.unk_token) {
+                (Some((id, n)), _) => {
+                    ids.push(id);
+                    rest = &rest[n..];
+                }
+                (None, Some(unk)) => {
+                    ids.push(unk);
+                    rest = &rest[c.len_utf8()..];
+                }
+                (None, None) => {
+                    return Err(format!("no vocabulary entry covers {:?}", c).into());
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn decode(&self, ids: &[usize]) -> Result<String> {
+        let mut text = String::new();
+        for &id in ids {
+            if self.special.contains(&id) {
+                continue;
+            }
+            let token = self.token(id).ok_or(TransformerError::VocabOverflow {
+                token: id,
+                vocab_size: self.tokens.len(),
+            })?;
+            text.push_str(token);
+        }
+        Ok(text)
+    }
+}
@@ -0,0 +1,260 @@
+//! Contrastive sentence-embedding training (InfoNCE, SimCSE).
+//!
+//! [`info_nce`] scores every anchor against every positive in a batch, so
+//! each example's positive is contrasted with the other examples' positives
+//! as in-batch negatives. [`ContrastiveTrainer`] applies it to mean-pooled
+//! encoder outputs: unsupervised SimCSE (Gao et al., 2021) encodes each
+//! sentence twice with dropout active and treats the two views as a
+//! positive pair; the supervised variant uses given `(anchor, positive)`
+//! pairs.
+//!
+//! Training fits a linear projection on top of the encoder. The loss
+//! gradient with respect to the embeddings is also returned so the encoder
+//! itself can be fine-tuned by backpropagating it.
+
+use crate::optim::{Adam, AdamConfig, Optimizer};
+use crate::params::{join_name, Gradients, Parameters};
+use crate::tensor::Matrix;
+use crate::utils::similarity::{dot, norm};
+use crate::utils::tensor_ops::softmax;
+use crate::{Result, Transformer};
+
+/// InfoNCE loss of a batch and its gradients.
+#[derive(Debug, Clone)]
+pub struct InfoNceLoss {
+    pub loss: f64,
+    /// `∂loss/∂anchors`, same shape as the anchors.
+    pub d_anchors: Matrix,
+    /// `∂loss/∂positives`, same shape as the positives.
+    pub d_positives: Matrix,
+    /// Fraction of anchors whose own positive is the most similar.
+    pub accuracy: f64,
+}
+
+/// InfoNCE over cosine similarities of `anchors` and `positives` (both
+/// `batch × dim`, row `i` of each forming a positive pair) at `temperature`.
+/// With `symmetric` the loss is averaged with the positive-to-anchor
+/// direction.
+pub fn info_nce(
+    anchors: &Matrix,
+    positives: &Matrix,
+    temperature: f64,
+    symmetric: bool,
+) -> Result<InfoNceLoss> {
+    positives.ensure_shape(anchors.shape(), "info_nce positives")?;
+    let n = anchors.rows();
+    if n == 0 {
+        return Err("info_nce needs at least one pair".into());
+    }
+    let (a, a_norms) = normalize_rows(anchors);
+    let (p, p_norms) = normalize_rows(positives);
+    let logits = a.matmul(&p.transpose())?.scale(1.0 / temperature);
+
+    // d_logits = (softmax − identity) / n per direction.
+    let mut d_logits = Matrix::zeros(n, n);
+    let mut loss = 0.0;
+    let mut correct = 0;
+    let weight = if symmetric { 0.5 } else { 1.0 };
+    for i in 0..n {
+        let row = logits.row(i);
+        let probs = softmax(row);
+        loss -= weight * probs[i].ln();
+        if (0..n).all(|j| row[j] <= row[i]) {
+            correct += 1;
+        }
+        for (j, p) in probs.iter().enumerate() {
+            let target = if i == j { 1.0 } else { 0.0 };
+            d_logits[(i, j)] += weight * (p - target) / n as f64;
+        }
+    }
+    if symmetric {
+        let columns = logits.transpose();
+        for j in 0..n {
+            let probs = softmax(columns.row(j));
+            loss -= weight * probs[j].ln();
+            for (i, p) in probs.iter().enumerate() {
+                let target = if i == j { 1.0 } else { 0.0 };
+                d_logits[(i, j)] += weight * (p - target) / n as f64;
+            }
+        }
+    }
+
+    let d_a = d_logits.matmul(&p)?.scale(1.0 / temperature);
+    let d_p = d_logits.transpose().matmul(&a)?.scale(1.0 / temperature);
+    Ok(InfoNceLoss {
+        loss: loss / n as f64,
+        d_anchors: normalize_backward(&a, &a_norms, &d_a),
+        d_positives: normalize_backward(&p, &p_norms, &d_p),
+        accuracy: correct as f64 / n as f64,
+    })
+}
+
+/// Rows scaled to unit length, with the original norms.
+fn normalize_rows(x: &Matrix) -> (Matrix, Vec<f64>) {
+    let mut out = x.clone();
+    let mut norms = Vec::with_capacity(x.rows());
+    for i in 0..x.rows() {
+        let norm = norm(x.row(i)).max(1e-12);
+        for v in out.row_mut(i) {
+            *v /= norm;
+        }
+        norms.push(norm);
+    }
+    (out, norms)
+}
+
+/// Gradient through `x̂ = x/‖x‖`: `(g − x̂(x̂·g)) / ‖x‖` per row.
+fn normalize_backward(unit: &Matrix, norms: &[f64], grad: &Matrix) -> Matrix {
+    let mut out = grad.clone();
+    for (i, norm) in norms.iter().enumerate() {
+        let dot = dot(unit.row(i), grad.row(i));
+        for (o, u) in out.row_mut(i).iter_mut().zip(unit.row(i)) {
+            *o = (*o - u * dot) / norm;
+        }
+    }
+    out
+}
+
+/// Mean of the encoder outputs over non-padding positions (`1 × d_model`).
+pub fn mean_pooled_encoding(model: &Transformer, tokens: &[usize]) -> Result<Matrix> {
+    model.mean_pooled_encoding(tokens)
+}
+
+/// Settings of [`ContrastiveTrainer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContrastiveConfig {
+    /// Softmax temperature; SimCSE uses 0.05.
+    pub temperature: f64,
+    pub symmetric: bool,
+    /// Learning rate of the projection.
+    pub lr: f64,
+}
+
+impl Default for ContrastiveConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 0.05,
+            symmetric: false,
+            lr: 1e-3,
+        }
+    }
+}
+
+/// Loss statistics of one contrastive batch.
+#[derive(Debug, Clone)]
+pub struct ContrastiveStats {
+    pub loss: f64,
+    pub accuracy: f64,
+    /// `∂loss/∂embeddings` of the anchor side, `batch × d_model`, for
+    /// backpropagating into the encoder.
+    pub d_anchor_embeddings: Matrix,
+    pub d_positive_embeddings: Matrix,
+}
+
+/// Trains a `d_model × d_model` projection of pooled encoder outputs into a
+/// sentence-embedding space.
+#[derive(Debug, Clone)]
+pub struct ContrastiveTrainer {
+    pub config: ContrastiveConfig,
+    pub projection: Projection,
+    pub optimizer: Adam,
+}
+
+/// Linear map applied to pooled encodings, initialized to the identity.
+#[derive(Debug, Clone)]
+pub struct Projection {
+    pub weight: Matrix,
+}
+
+impl Parameters for Projection {
+    fn visit_parameters(&self, prefix: &str, visitor: &mut dyn FnMut(&str, &Matrix)) {
+        visitor(&join_name(prefix, "weight"), &self.weight);
+    }
+
+    fn visit_parameters_mut(&mut self, prefix: &str, visitor: &mut dyn FnMut(&str, &mut Matrix)) {
+        visitor(&join_name(prefix, "weight"), &mut self.weight);
+    }
+}
+
+impl ContrastiveTrainer {
+    pub fn new(d_model: usize, config: ContrastiveConfig) -> Self {
+        Self {
+            projection: Projection {
+                weight: Matrix::identity(d_model),
+            },
+            optimizer: Adam::new(AdamConfig {
+                lr: config.lr,
+                ..AdamConfig::default()
+            }),
+            config,
+        }
+    }
+
+    /// Unit-length sentence embedding of `tokens` (`1 × d_model`).
+    pub fn embed(&self, model: &Transformer, tokens: &[usize]) -> Result<Matrix> {
+        let projected = mean_pooled_encoding(model, tokens)?.matmul(&self.projection.weight)?;
+        Ok(normalize_rows(&projected).0)
+    }
+
+    /// Unsupervised SimCSE step: every sentence is encoded twice with
+    /// dropout on, and the two views form the positive pair. `model` is
+    /// left in evaluation mode.
+    pub fn train_unsupervised(
+        &mut self,
+        model: &mut Transformer,
+        sentences: &[Vec<usize>],
+    ) -> Result<ContrastiveStats> {
+        model.set_training(true);
+        let pooled = |model: &Transformer| -> Result<Vec<Matrix>> {
+            sentences
+                .iter()
+                .map(|s| mean_pooled_encoding(model, s))
+                .collect()
+        };
+        let views = pooled(model).and_then(|a| Ok((a, pooled(model)?)));
+        model.set_training(false);
+        let (anchors, positives) = views?;
+        self.train_on(&anchors, &positives)
+    }
+
+    /// Supervised step on `(anchor, positive)` sentence pairs.
+    pub fn train_pairs(
+        &mut self,
+        model: &Transformer,
+        pairs: &[(Vec<usize>, Vec<usize>)],
+    ) -> Result<ContrastiveStats> {
+        let mut anchors = Vec::with_capacity(pairs.len());
+        let mut positives = Vec::with_capacity(pairs.len());
+        for (anchor, positive) in pairs {
+            anchors.push(mean_pooled_encoding(model, anchor)?);
+            positives.push(mean_pooled_encoding(model, positive)?);
+        }
+        self.train_on(&anchors, &positives)
+    }
+
+    fn train_on(&mut self, anchors: &[Matrix], positives: &[Matrix]) -> Result<ContrastiveStats> {
+        let anchors = Matrix::vstack(&anchors.iter().collect::<Vec<_>>())?;
+        let positives = Matrix::vstack(&positives.iter().collect::<Vec<_>>())?;
+        let w = &self.projection.weight;
+        let loss = info_nce(
+            &anchors.matmul(w)?,
+            &positives.matmul(w)?,
+            self.config.temperature,
+            self.config.symmetric,
+        )?;
+        let d_weight = anchors
+            .transpose()
+            .matmul(&loss.d_anchors)?
+            .add(&positives.transpose().matmul(&loss.d_positives)?)?;
+        let stats = ContrastiveStats {
+            loss: loss.loss,
+            accuracy: loss.accuracy,
+            d_anchor_embeddings: loss.d_anchors.matmul(&w.transpose())?,
+            d_positive_embeddings: loss.d_positives.matmul(&w.transpose())?,
+        };
+        let mut grads = Gradients::new();
+        grads.insert("weight", d_weight);
+        self.optimizer.step(&mut self.projection, &grads)?;
+        Ok(stats)
+    }
+}
@@ -0,0 +1,156 @@
+//! Curriculum learning: start on short or easy examples and widen the
+//! training distribution as training progresses.
+//!
+//! A [`Competence`] schedule maps the training step to the fraction of the
+//! curriculum unlocked so far (Platanios et al., 2019). [`LengthCurriculum`]
+//! turns it into a growing maximum sequence length; [`DifficultyCurriculum`]
+//! into sampling from the easiest fraction of a dataset, which
+//! [`TrainerConfig::with_curriculum`](super::TrainerConfig::with_curriculum)
+//! uses to pick a [`Trainer`](super::Trainer)'s batches.
+
+use crate::data::Dataset;
+use crate::utils::rng::Rng;
+use crate::{Result, TransformerError};
+
+/// Shape of the competence curve between `initial` and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Grows linearly with the step.
+    Linear,
+    /// `c(t) = (t·(1 − c₀ᵖ)/T + c₀ᵖ)^(1/p)`; `p = 2` is the square-root
+    /// schedule, which unlocks new material quickly at first.
+    Root { power: f64 },
+    /// Grows in `stages` equal jumps.
+    Step { stages: usize },
+}
+
+/// Fraction of the curriculum available at a given step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Competence {
+    /// Competence at step 0, in `(0, 1]`.
+    pub initial: f64,
+    /// Step at which the full curriculum is reached.
+    pub total_steps: usize,
+    pub pacing: Pacing,
+}
+
+impl Competence {
+    pub fn new(initial: f64, total_steps: usize, pacing: Pacing) -> Result<Self> {
+        if !(initial > 0.0 && initial <= 1.0) {
+            return Err(TransformerError::config(format!(
+                "initial competence must be in (0, 1], got {}",
+                initial
+            )));
+        }
+        Ok(Self {
+            initial,
+            total_steps,
+            pacing,
+        })
+    }
+
+    /// Competence in `[initial, 1]` at `step`.
+    pub fn at(&self, step: usize) -> f64 {
+        if self.total_steps == 0 || step >= self.total_steps {
+            return 1.0;
+        }
+        let progress = step as f64 / self.total_steps as f64;
+        let c0 = self.initial;
+        let c = match self.pacing {
+            Pacing::Linear => c0 + (1.0 - c0) * progress,
+            Pacing::Root { power } => {
+                let p = power.max(1e-6);
+                (progress * (1.0 - c0.powf(p)) + c0.powf(p)).powf(1.0 / p)
+            }
+            Pacing::Step { stages } => {
+                let stages = stages.max(1) as f64;
+                c0 + (1.0 - c0) * (progress * stages).floor() / stages
+            }
+        };
+        c.clamp(c0, 1.0)
+    }
+}
+
+/// Maximum sequence length growing from `min_len` to `max_len`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthCurriculum {
+    pub min_len: usize,
+    pub max_len: usize,
+    pub pacing: Pacing,
+    pub total_steps: usize,
+}
+
+impl LengthCurriculum {
+    /// Maximum sequence length at `step`.
+    pub fn max_len_at(&self, step: usize) -> usize {
+        if self.max_len <= self.min_len {
+            return self.max_len;
+        }
+        let competence = Competence {
+            initial: (self.min_len.max(1) as f64 / self.max_len as f64).min(1.0),
+            total_steps: self.total_steps,
+            pacing: self.pacing,
+        };
+        ((competence.at(step) * self.max_len as f64).round() as usize)
+            .clamp(self.min_len, self.max_len)
+    }
+
+    /// `tokens` truncated to the length allowed at `step`.
+    pub fn truncate<'a, T>(&self, tokens: &'a [T], step: usize) -> &'a [T] {
+        &tokens[..tokens.len().min(self.max_len_at(step))]
+    }
+}
+
+/// Samples from the easiest `c(t)` fraction of a dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifficultyCurriculum {
+    /// Example indices from easiest to hardest.
+    order: Vec<usize>,
+    pub competence: Competence,
+}
+
+impl DifficultyCurriculum {
+    /// Orders `dataset` by `difficulty` (lower is easier; ties keep dataset
+    /// order).
+    pub fn new<D, F>(dataset: &D, difficulty: F, competence: Competence) -> Self
+    where
+        D: Dataset,
+        F: Fn(&D::Item) -> f64,
+    {
+        let mut scored: Vec<(f64, usize)> = (0..dataset.len())
+            .filter_map(|i| dataset.get(i).map(|item| (difficulty(&item), i)))
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            order: scored.into_iter().map(|(_, i)| i).collect(),
+            competence,
+        }
+    }
+
+    /// Orders sequence examples by length, the usual difficulty proxy.
+    pub fn by_length<T, D>(dataset: &D, competence: Competence) -> Self
+    where
+        D: Dataset<Item = Vec<T>>,
+    {
+        Self::new(dataset, |item| item.len() as f64, competence)
+    }
+
+    /// Indices unlocked at `step`, easiest first; never empty unless the
+    /// dataset is.
+    pub fn available(&self, step: usize) -> &[usize] {
+        let n = self.order.len();
+        let count = ((self.competence.at(step) * n as f64).ceil() as usize).clamp(n.min(1), n);
+        &self.order[..count]
+    }
+
+    /// Draws `batch_size` indices uniformly from [`available`](Self::available).
+    pub fn sample_batch(&self, step: usize, batch_size: usize, rng: &mut Rng) -> Vec<usize> {
+        let pool = self.available(step);
+        if pool.is_empty() {
+            return Vec::new();
+        }
+        (0..batch_size)
+            .map(|_| pool[rng.below(pool.len())])
+            .collect()
+    }
+}
@@ -0,0 +1,168 @@
+//! Per-layer gradient and update statistics.
+//!
+//! Vanishing or exploding gradients show up as gradient norms that shrink or
+//! grow from layer to layer, and as update-to-weight ratios far from the
+//! usual ~1e-3. [`GradientFlowTracker`] accumulates both for one optimizer
+//! step, grouping parameters by layer (see [`layer_group`]).
+
+use std::collections::BTreeMap;
+
+use crate::tensor::Matrix;
+use crate::Result;
+
+/// Statistics of one parameter group for a single step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerGradientStats {
+    /// Group name, e.g. `encoder.layers.0`.
+    pub layer: String,
+    /// L2 norm of all gradients in the group.
+    pub grad_norm: f64,
+    /// L2 norm of the weights before the update.
+    pub weight_norm: f64,
+    /// L2 norm of the applied update, if one was recorded.
+    pub update_norm: Option<f64>,
+    pub num_parameters: usize,
+}
+
+impl LayerGradientStats {
+    /// `‖Δw‖ / ‖w‖`, or `None` if no update was recorded or the weights are zero.
+    pub fn update_ratio(&self) -> Option<f64> {
+        match self.update_norm {
+            Some(update) if self.weight_norm > 0.0 => Some(update / self.weight_norm),
+            _ => None,
+        }
+    }
+}
+
+/// Gradient-flow statistics of one step, ordered by first appearance.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GradientFlow {
+    pub layers: Vec<LayerGradientStats>,
+}
+
+impl GradientFlow {
+    /// Global gradient L2 norm across all groups.
+    pub fn total_grad_norm(&self) -> f64 {
+        self.layers
+            .iter()
+            .map(|l| l.grad_norm * l.grad_norm)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Flattens the statistics into `(name, value)` pairs for logging:
+    /// `grad_norm/{layer}`, `update_ratio/{layer}` and `grad_norm/total`.
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = Vec::with_capacity(2 * self.layers.len() + 1);
+        for layer in &self.layers {
+            metrics.push((format!("grad_norm/{}", layer.layer), layer.grad_norm));
+            if let Some(ratio) = layer.update_ratio() {
+                metrics.push((format!("update_ratio/{}", layer.layer), ratio));
+            }
+        }
+        metrics.push(("grad_norm/total".to_string(), self.total_grad_norm()));
+        metrics
+    }
+
+    /// Groups whose gradient norm is below `min_norm` or above `max_norm`
+    /// (or not finite).
+    pub fn anomalies(&self, min_norm: f64, max_norm: f64) -> Vec<&LayerGradientStats> {
+        self.layers
+            .iter()
+            .filter(|l| {
+                !l.grad_norm.is_finite() || l.grad_norm < min_norm || l.grad_norm > max_norm
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Accumulator {
+    order: usize,
+    grad_sq: f64,
+    weight_sq: f64,
+    update_sq: Option<f64>,
+    num_parameters: usize,
+}
+
+/// Accumulates gradient and update norms for a single step.
+#[derive(Debug, Clone, Default)]
+pub struct GradientFlowTracker {
+    groups: BTreeMap<String, Accumulator>,
+}
+
+impl GradientFlowTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the gradient `grad` of the weight `name` with current value `weight`.
+    pub fn record_gradient(&mut self, name: &str, weight: &Matrix, grad: &Matrix) -> Result<()> {
+        grad.ensure_shape(weight.shape(), name)?;
+        let acc = self.group(name);
+        acc.grad_sq += squared_norm(grad);
+        acc.weight_sq += squared_norm(weight);
+        acc.num_parameters += weight.len();
+        Ok(())
+    }
+
+    /// Records the update applied to `name`, given its values before and after the step.
+    pub fn record_update(&mut self, name: &str, before: &Matrix, after: &Matrix) -> Result<()> {
+        let update = after.sub(before)?;
+        let acc = self.group(name);
+        *acc.update_sq.get_or_insert(0.0) += squared_norm(&update);
+        Ok(())
+    }
+
+    /// Returns the statistics gathered so far and resets the tracker.
+    pub fn finish(&mut self) -> GradientFlow {
+        let mut groups: Vec<_> = std::mem::take(&mut self.groups).into_iter().collect();
+        groups.sort_by_key(|(_, acc)| acc.order);
+        GradientFlow {
+            layers: groups
+                .into_iter()
+                .map(|(layer, acc)| LayerGradientStats {
+                    layer,
+                    grad_norm: acc.grad_sq.sqrt(),
+                    weight_norm: acc.weight_sq.sqrt(),
+                    update_norm: acc.update_sq.map(f64::sqrt),
+                    num_parameters: acc.num_parameters,
+                })
+                .collect(),
+        }
+    }
+
+    fn group(&mut self, name: &str) -> &mut Accumulator {
+        let order = self.groups.len();
+        self.groups
+            .entry(layer_group(name).to_string())
+            .or_insert_with(|| Accumulator {
+                order,
+                ..Accumulator::default()
+            })
+    }
+}
+
+/// The layer a parameter belongs to: the name up to and including
+/// `layers.{i}`, or the full name for weights outside the layer stacks
+/// (embeddings, output projections).
+pub fn layer_group(name: &str) -> &str {
+    let mut offset = 0;
+    let mut segments = name.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        offset += segment.len();
+        if segment == "layers" {
+            if let Some(index) = segments.peek() {
+                if index.parse::<usize>().is_ok() {
+                    return &name[..offset + 1 + index.len()];
+                }
+            }
+        }
+        offset += 1;
+    }
+    name
+}
+
+fn squared_norm(m: &Matrix) -> f64 {
+    m.as_slice().iter().map(|v| v * v).sum()
+}
@@ -0,0 +1,185 @@
+//! Token-level cross-entropy for sequence-to-sequence training.
+//!
+//! [`cross_entropy`] scores decoder logits (`tgt_len × vocab_size`) against
+//! the target ids one position per row. Positions whose target equals the
+//! ignored id (usually `pad_token_id`) contribute neither loss nor
+//! gradient, and the loss is averaged over the remaining tokens. With label
+//! smoothing (Szegedy et al., 2016) the target distribution puts
+//! `1 − ε` on the reference token and spreads `ε` evenly over the whole
+//! vocabulary.
+//!
+//! The returned `d_logits` is what [`Transformer::backward`] expects.
+//! [`masked_cross_entropy`] scores only the positions selected by a mask,
+//! as token tagging and masked language modeling need.
+//!
+//! [`Transformer::backward`]: crate::models::Transformer::backward
+
+use crate::config::TransformerConfig;
+use crate::tensor::Matrix;
+use crate::utils::tensor_ops::softmax;
+use crate::{Result, TransformerError};
+
+/// Settings of [`cross_entropy`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrossEntropyConfig {
+    /// Target id skipped by the loss.
+    pub ignore_index: Option<usize>,
+    /// Probability mass `ε` moved from the reference token to a uniform
+    /// distribution, in `[0, 1)`.
+    pub label_smoothing: f64,
+}
+
+impl Default for CrossEntropyConfig {
+    fn default() -> Self {
+        Self {
+            ignore_index: None,
+            label_smoothing: 0.0,
+        }
+    }
+}
+
+impl CrossEntropyConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ignores the model's `pad_token_id`.
+    pub fn for_model(config: &TransformerConfig) -> Self {
+        Self::default().with_ignore_index(config.pad_token_id)
+    }
+
+    pub fn with_ignore_index(mut self, id: usize) -> Self {
+        self.ignore_index = Some(id);
+        self
+    }
+
+    pub fn with_label_smoothing(mut self, epsilon: f64) -> Self {
+        self.label_smoothing = epsilon;
+        self
+    }
+}
+
+/// Cross-entropy of a batch of positions and its gradient.
+#[derive(Debug, Clone)]
+pub struct CrossEntropyLoss {
+    /// Mean loss per counted token; `0` when every target is ignored.
+    pub loss: f64,
+    /// `∂loss/∂logits`, same shape as the logits; ignored rows are zero.
+    pub d_logits: Matrix,
+    /// Positions that contributed to the loss.
+    pub tokens: usize,
+    /// Fraction of counted positions whose reference token has the highest
+    /// logit.
+    pub accuracy: f64,
+}
+
+/// Mean token cross-entropy of `logits` (one row per position) against
+/// `targets`; see the [module docs](self).
+pub fn cross_entropy(
+    logits: &Matrix,
+    targets: &[usize],
+    config: &CrossEntropyConfig,
+) -> Result<CrossEntropyLoss> {
+    if logits.rows() != targets.len() {
+        return Err(TransformerError::shape(format!(
+            "cross_entropy got {} logit rows for {} targets",
+            logits.rows(),
+            targets.len()
+        )));
+    }
+    let epsilon = config.label_smoothing;
+    if !(0.0..1.0).contains(&epsilon) {
+        return Err(TransformerError::config(format!(
+            "label smoothing {} must be in [0, 1)",
+            epsilon
+        )));
+    }
+    let vocab_size = logits.cols();
+    let counted: Vec<usize> = (0..targets.len())
+        .filter(|&i| Some(targets[i]) != config.ignore_index)
+        .collect();
+    if let Some(&i) = counted.iter().find(|&&i| targets[i] >= vocab_size) {
+        return Err(TransformerError::VocabOverflow {
+            token: targets[i],
+            vocab_size,
+        });
+    }
+
+    let mut d_logits = Matrix::zeros(logits.rows(), vocab_size);
+    if counted.is_empty() {
+        return Ok(CrossEntropyLoss {
+            loss: 0.0,
+            d_logits,
+            tokens: 0,
+            accuracy: 0.0,
+        });
+    }
+    let n = counted.len() as f64;
+    let smooth = epsilon / vocab_size as f64;
+    let mut loss = 0.0;
+    let mut correct = 0;
+    for &i in &counted {
+        let row = logits.row(i);
+        let target = targets[i];
+        let probs = softmax(row);
+        let max = row.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let log_sum = max + row.iter().map(|&v| (v - max).exp()).sum::<f64>().ln();
+        // q = (1 − ε)·onehot + ε/V, loss = −Σ q log p, ∂/∂logits = p − q.
+        for (j, &p) in probs.iter().enumerate() {
+            let q = if j == target {
+                1.0 - epsilon + smooth
+            } else {
+                smooth
+            };
+            if q > 0.0 {
+                loss -= q * (row[j] - log_sum);
+            }
+            d_logits[(i, j)] = (p - q) / n;
+        }
+        if row.iter().all(|&v| v <= row[target]) {
+            correct += 1;
+        }
+    }
+    Ok(CrossEntropyLoss {
+        loss: loss / n,
+        d_logits,
+        tokens: counted.len(),
+        accuracy: correct as f64 / n,
+    })
+}
+
+/// [`cross_entropy`] over the positions where `mask` is `true`; the other
+/// rows contribute neither loss nor gradient, as if their targets were
+/// ignored.
+pub fn masked_cross_entropy(
+    logits: &Matrix,
+    targets: &[usize],
+    mask: &[bool],
+    config: &CrossEntropyConfig,
+) -> Result<CrossEntropyLoss> {
+    if mask.len() != logits.rows() {
+        return Err(TransformerError::shape(format!(
+            "masked_cross_entropy got a mask of {} for {} logit rows",
+            mask.len(),
+            logits.rows()
+        )));
+    }
+    if targets.len() != logits.rows() {
+        return Err(TransformerError::shape(format!(
+            "cross_entropy got {} logit rows for {} targets",
+            logits.rows(),
+            targets.len()
+        )));
+    }
+    let kept: Vec<usize> = (0..mask.len()).filter(|&i| mask[i]).collect();
+    let kept_targets: Vec<usize> = kept.iter().map(|&i| targets[i]).collect();
+    let mut scored = cross_entropy(&logits.select_rows(&kept)?, &kept_targets, config)?;
+    let mut d_logits = Matrix::zeros(logits.rows(), logits.cols());
+    for (row, &i) in kept.iter().enumerate() {
+        d_logits
+            .row_mut(i)
+            .copy_from_slice(scored.d_logits.row(row));
+    }
+    scored.d_logits = d_logits;
+    Ok(scored)
+}
@@ -0,0 +1,24 @@
+//! Training utilities.
+//!
+//! The pieces here are independent of any particular training loop: they
+//! work on named weights, gradients and updates as exposed through
+//! [`Parameters`](crate::Parameters), so the same code serves hand-written
+//! loops and the crate's own [`Trainer`].
+
+pub mod contrastive;
+pub mod curriculum;
+pub mod gradient_flow;
+pub mod loss;
+pub mod trainer;
+
+pub use contrastive::{
+    info_nce, mean_pooled_encoding, ContrastiveConfig, ContrastiveStats, ContrastiveTrainer,
+    InfoNceLoss, Projection,
+};
+pub use curriculum::{Competence, DifficultyCurriculum, LengthCurriculum, Pacing};
+pub use gradient_flow::{layer_group, GradientFlow, GradientFlowTracker, LayerGradientStats};
+pub use loss::{cross_entropy, masked_cross_entropy, CrossEntropyConfig, CrossEntropyLoss};
+pub use trainer::{
+    masked_lm_loss, seq2seq_loss, sequence_classification_loss, token_classification_loss,
+    StepStats, Trainer, TrainerCallback, TrainerConfig,
+};
@@ -0,0 +1,519 @@
+//! A single-process training loop.
+//!
+//! [`Trainer`] owns a model and an optimizer and runs epochs over a
+//! [`Dataset`]: it shuffles the examples, cuts them into batches, asks a
+//! step function for the loss and gradients of each batch, optionally clips
+//! the gradients and applies the optimizer at the rate of an optional
+//! [`LrScheduler`]. [`TrainerCallback`]s observe
+//! every step and epoch, and weights are written to `.npz` checkpoints at a
+//! fixed step interval. With [`TrainerConfig::track_gradient_flow`] every
+//! step also reports per-layer gradient norms and update-to-weight ratios
+//! (see [`GradientFlow`]). With a [`TrainerConfig::curriculum`] every batch
+//! is drawn from the examples the curriculum has unlocked at that step
+//! instead of from a pass over the whole dataset.
+//!
+//! The step function decides what is being trained. [`seq2seq_loss`] is the
+//! usual one for a [`Transformer`] on `(source, target)` pairs: teacher
+//! forcing with token cross-entropy. [`sequence_classification_loss`]
+//! fine-tunes a [`SequenceClassifier`] on `(tokens, label)` pairs and
+//! [`token_classification_loss`] a [`TokenClassifier`] on `(tokens, tags)`
+//! pairs. [`masked_lm_loss`] pretrains a [`MaskedLanguageModel`] on
+//! sequences corrupted by an [`MlmCollator`](crate::data::MlmCollator).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::curriculum::DifficultyCurriculum;
+use super::gradient_flow::{GradientFlow, GradientFlowTracker};
+use super::loss::{cross_entropy, masked_cross_entropy, CrossEntropyConfig};
+use crate::cancellation::CancellationToken;
+use crate::data::{Dataset, MaskedTokens};
+use crate::distributed::EpochStats;
+use crate::models::{MaskedLanguageModel, SequenceClassifier, TokenClassifier};
+use crate::optim::{LrScheduler, Optimizer};
+use crate::params::{Gradients, Parameters};
+use crate::progress::{NoProgress, Progress, ProgressHandler, Stage};
+use crate::tensor::Matrix;
+use crate::testing::Fixtures;
+use crate::utils::rng::{mix64, Rng};
+use crate::{Result, Transformer};
+
+/// Settings of [`Trainer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrainerConfig {
+    /// Epochs run by [`Trainer::fit`].
+    pub epochs: u64,
+    pub batch_size: usize,
+    /// Visit the examples in a fresh random order every epoch.
+    pub shuffle: bool,
+    /// Seed of the per-epoch permutation and of curriculum sampling.
+    pub seed: u64,
+    /// Clip the global gradient norm of every batch to this value.
+    pub max_grad_norm: Option<f64>,
+    /// Write a checkpoint every this many optimizer steps.
+    pub checkpoint_every: Option<usize>,
+    /// Directory receiving `step-{n}.npz` checkpoints.
+    pub checkpoint_dir: PathBuf,
+    /// Record [`StepStats::gradient_flow`] every step. Costs a copy of the
+    /// weights per step.
+    pub track_gradient_flow: bool,
+    /// Draws every batch uniformly from the examples of the training
+    /// dataset unlocked at the current step, easiest first. An epoch still
+    /// takes as many steps as a pass over the dataset would.
+    pub curriculum: Option<DifficultyCurriculum>,
+}
+
+impl Default for TrainerConfig {
+    fn default() -> Self {
+        Self {
+            epochs: 1,
+            batch_size: 32,
+            shuffle: true,
+            seed: 0,
+            max_grad_norm: None,
+            checkpoint_every: None,
+            checkpoint_dir: PathBuf::from("checkpoints"),
+            track_gradient_flow: false,
+            curriculum: None,
+        }
+    }
+}
+
+impl TrainerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_epochs(mut self, epochs: u64) -> Self {
+        self.epochs = epochs;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_shuffle(mut self, shuffle: bool, seed: u64) -> Self {
+        self.shuffle = shuffle;
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_max_grad_norm(mut self, max_norm: f64) -> Self {
+        self.max_grad_norm = Some(max_norm);
+        self
+    }
+
+    /// Checkpoints into `dir` every `every` steps.
+    pub fn with_checkpoints(mut self, dir: impl Into<PathBuf>, every: usize) -> Self {
+        self.checkpoint_dir = dir.into();
+        self.checkpoint_every = Some(every);
+        self
+    }
+
+    pub fn with_gradient_flow(mut self, track: bool) -> Self {
+        self.track_gradient_flow = track;
+        self
+    }
+
+    /// Selects batches with `curriculum`, which must order the dataset
+    /// being trained on.
+    pub fn with_curriculum(mut self, curriculum: DifficultyCurriculum) -> Self {
+        self.curriculum = Some(curriculum);
+        self
+    }
+}
+
+/// What happened in one optimizer step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepStats {
+    pub epoch: u64,
+    /// Optimizer steps completed so far, across epochs.
+    pub step: usize,
+    pub loss: f64,
+    /// Global gradient norm before clipping.
+    pub grad_norm: f64,
+    pub learning_rate: f64,
+    /// Per-layer gradient norms (before clipping) and update norms, when
+    /// [`TrainerConfig::track_gradient_flow`] is set.
+    pub gradient_flow: Option<GradientFlow>,
+}
+
+/// Observes a [`Trainer`]. Every method defaults to doing nothing.
+pub trait TrainerCallback {
+    fn on_step(&mut self, _stats: &StepStats) {}
+
+    fn on_epoch_end(&mut self, _stats: &EpochStats) {}
+
+    fn on_checkpoint(&mut self, _step: usize, _path: &Path) {}
+}
+
+/// Runs epochs of a model and optimizer over a dataset.
+pub struct Trainer<M, O> {
+    pub model: M,
+    pub optimizer: O,
+    pub config: TrainerConfig,
+    /// Stops training after the current step when cancelled.
+    pub cancellation: Option<CancellationToken>,
+    /// Sets the learning rate before every step.
+    pub scheduler: Option<Box<dyn LrScheduler>>,
+    callbacks: Vec<Box<dyn TrainerCallback>>,
+    step: usize,
+}
+
+impl<M: Parameters, O: Optimizer> Trainer<M, O> {
+    pub fn new(model: M, optimizer: O, config: TrainerConfig) -> Self {
+        Self {
+            model,
+            optimizer,
+            config,
+            cancellation: None,
+            scheduler: None,
+            callbacks: Vec::new(),
+            step: 0,
+        }
+    }
+
+    pub fn with_callback(mut self, callback: impl TrainerCallback + 'static) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn with_scheduler(mut self, scheduler: impl LrScheduler + 'static) -> Self {
+        self.scheduler = Some(Box::new(scheduler));
+        self
+    }
+
+    /// Optimizer steps taken so far.
+    pub fn steps(&self) -> usize {
+        self.step
+    }
+
+    pub fn into_model(self) -> M {
+        self.model
+    }
+
+    /// Runs [`TrainerConfig::epochs`] epochs. `compute` returns the mean
+    /// loss and the gradients of a batch. Stops early, returning the epochs
+    /// run so far, when the cancellation token fires.
+    pub fn fit<D, F>(&mut self, dataset: &D, compute: F) -> Result<Vec<EpochStats>>
+    where
+        D: Dataset,
+        F: FnMut(&M, &[D::Item]) -> Result<(f64, Gradients)>,
+    {
+        self.fit_with_progress(dataset, compute, &NoProgress)
+    }
+
+    /// [`fit`](Self::fit), reporting [`Stage::Training`] after every step.
+    pub fn fit_with_progress<D, F>(
+        &mut self,
+        dataset: &D,
+        mut compute: F,
+        progress: &dyn ProgressHandler,
+    ) -> Result<Vec<EpochStats>>
+    where
+        D: Dataset,
+        F: FnMut(&M, &[D::Item]) -> Result<(f64, Gradients)>,
+    {
+        let mut history = Vec::new();
+        for epoch in 0..self.config.epochs {
+            let stats = self.train_epoch_with_progress(dataset, epoch, &mut compute, progress)?;
+            history.push(stats);
+            if stats.cancelled {
+                break;
+            }
+        }
+        Ok(history)
+    }
+
+    /// Trains one pass over `dataset`.
+    pub fn train_epoch<D, F>(&mut self, dataset: &D, epoch: u64, compute: F) -> Result<EpochStats>
+    where
+        D: Dataset,
+        F: FnMut(&M, &[D::Item]) -> Result<(f64, Gradients)>,
+    {
+        self.train_epoch_with_progress(dataset, epoch, compute, &NoProgress)
+    }
+
+    /// [`train_epoch`](Self::train_epoch), reporting [`Stage::Training`]
+    /// after every step.
+    pub fn train_epoch_with_progress<D, F>(
+        &mut self,
+        dataset: &D,
+        epoch: u64,
+        mut compute: F,
+        progress: &dyn ProgressHandler,
+    ) -> Result<EpochStats>
+    where
+        D: Dataset,
+        F: FnMut(&M, &[D::Item]) -> Result<(f64, Gradients)>,
+    {
+        let batch_size = self.config.batch_size.max(1);
+        let mut rng = Rng::seed_from_u64(mix64(self.config.seed ^ mix64(epoch)));
+        let mut order: Vec<usize> = (0..dataset.len()).collect();
+        if self.config.shuffle && self.config.curriculum.is_none() {
+            rng.shuffle(&mut order);
+        }
+        let total_steps = order.len().div_ceil(batch_size);
+        let mut total_loss = 0.0;
+        let mut steps = 0;
+        let mut cancelled = false;
+        for chunk in order.chunks(batch_size) {
+            let indices = match &self.config.curriculum {
+                Some(curriculum) => curriculum.sample_batch(self.step, batch_size, &mut rng),
+                None => chunk.to_vec(),
+            };
+            let batch: Vec<D::Item> = indices.iter().filter_map(|&i| dataset.get(i)).collect();
+            let (loss, mut grads) = compute(&self.model, &batch)?;
+            let mut tracker = self
+                .config
+                .track_gradient_flow
+                .then(|| self.record_gradients(&grads))
+                .transpose()?;
+            let grad_norm = match self.config.max_grad_norm {
+                Some(max_norm) => grads.clip_global_norm(max_norm),
+                None => grads.global_norm(),
+            };
+            if let Some(scheduler) = &self.scheduler {
+                scheduler.apply(&mut self.optimizer, self.step + 1);
+            }
+            self.optimizer.step(&mut self.model, &grads)?;
+            let gradient_flow = match &mut tracker {
+                Some((tracker, before)) => Some(self.record_updates(tracker, before)?),
+                None => None,
+            };
+            self.step += 1;
+            steps += 1;
+            total_loss += loss;
+
+            let stats = StepStats {
+                epoch,
+                step: self.step,
+                loss,
+                grad_norm,
+                learning_rate: self.optimizer.learning_rate(),
+                gradient_flow,
+            };
+            for callback in &mut self.callbacks {
+                callback.on_step(&stats);
+            }
+            if self
+                .config
+                .checkpoint_every
+                .is_some_and(|every| every > 0 && self.step.is_multiple_of(every))
+            {
+                self.save_checkpoint()?;
+            }
+            progress.on_progress(&Progress::new(
+                Stage::Training { epoch },
+                steps,
+                total_steps,
+            ));
+            if self
+                .cancellation
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled)
+            {
+                cancelled = true;
+                break;
+            }
+        }
+        let stats = EpochStats {
+            epoch,
+            steps,
+            mean_loss: if steps > 0 {
+                total_loss / steps as f64
+            } else {
+                0.0
+            },
+            cancelled,
+        };
+        for callback in &mut self.callbacks {
+            callback.on_epoch_end(&stats);
+        }
+        Ok(stats)
+    }
+
+    /// Starts the gradient-flow statistics of a step from its gradients,
+    /// returning them with a copy of the weights before the update.
+    fn record_gradients(
+        &self,
+        grads: &Gradients,
+    ) -> Result<(GradientFlowTracker, HashMap<String, Matrix>)> {
+        let mut tracker = GradientFlowTracker::new();
+        let mut before = HashMap::new();
+        let mut result = Ok(());
+        self.model.visit_parameters("", &mut |name, weight| {
+            if let Some(grad) = grads.get(name) {
+                if result.is_ok() {
+                    result = tracker.record_gradient(name, weight, grad);
+                }
+                before.insert(name.to_string(), weight.clone());
+            }
+        });
+        result.map(|()| (tracker, before))
+    }
+
+    /// Adds the applied updates to `tracker` and returns the statistics.
+    fn record_updates(
+        &self,
+        tracker: &mut GradientFlowTracker,
+        before: &HashMap<String, Matrix>,
+    ) -> Result<GradientFlow> {
+        let mut result = Ok(());
+        self.model.visit_parameters("", &mut |name, after| {
+            if let (Some(before), true) = (before.get(name), result.is_ok()) {
+                result = tracker.record_update(name, before, after);
+            }
+        });
+        result.map(|()| tracker.finish())
+    }
+
+    /// Writes every weight to `{checkpoint_dir}/step-{n}.npz` and returns
+    /// the path. Checkpoints load back with
+    /// [`load_parameters`](crate::testing::load_parameters).
+    pub fn save_checkpoint(&mut self) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.config.checkpoint_dir)?;
+        let path = self
+            .config
+            .checkpoint_dir
+            .join(format!("step-{:08}.npz", self.step));
+        Fixtures::from_parameters(&self.model, "").save(&path)?;
+        for callback in &mut self.callbacks {
+            callback.on_checkpoint(self.step, &path);
+        }
+        Ok(path)
+    }
+}
+
+impl<M, O> fmt::Debug for Trainer<M, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trainer")
+            .field("config", &self.config)
+            .field("step", &self.step)
+            .field("callbacks", &self.callbacks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Teacher-forced sequence-to-sequence step for [`Trainer`]: the decoder
+/// reads `[bos] + target[..n − 1]` and is scored on `target`, which should
+/// end in the end token. Returns the mean loss over the batch's examples
+/// and the matching mean gradients.
+pub fn seq2seq_loss(
+    model: &Transformer,
+    batch: &[(Vec<usize>, Vec<usize>)],
+    loss: &CrossEntropyConfig,
+) -> Result<(f64, Gradients)> {
+    let mut grads = Gradients::new();
+    let mut total = 0.0;
+    let mut examples = 0;
+    for (src, tgt) in batch {
+        if tgt.is_empty() {
+            continue;
+        }
+        let mut decoder_input = Vec::with_capacity(tgt.len());
+        decoder_input.push(model.config.bos_token_id);
+        decoder_input.extend_from_slice(&tgt[..tgt.len() - 1]);
+        let (logits, cache) = model.forward_with_cache(src, &decoder_input)?;
+        let scored = cross_entropy(&logits, tgt, loss)?;
+        model.accumulate_gradients(&cache, &scored.d_logits, &mut grads)?;
+        total += scored.loss;
+        examples += 1;
+    }
+    if examples == 0 {
+        return Ok((0.0, grads));
+    }
+    grads.scale(1.0 / examples as f64);
+    Ok((total / examples as f64, grads))
+}
+
+/// Fine-tuning step for [`Trainer`] over `(tokens, label)` examples:
+/// cross-entropy of the classifier's logits against the label. Returns the
+/// mean loss over the batch and the matching mean gradients of the head and
+/// the encoder.
+pub fn sequence_classification_loss(
+    model: &SequenceClassifier,
+    batch: &[(Vec<usize>, usize)],
+    loss: &CrossEntropyConfig,
+) -> Result<(f64, Gradients)> {
+    let mut grads = Gradients::new();
+    let mut total = 0.0;
+    for (tokens, label) in batch {
+        let (logits, cache) = model.forward_with_cache(tokens)?;
+        let scored = cross_entropy(&logits, &[*label], loss)?;
+        model.accumulate_gradients(&cache, &scored.d_logits, &mut grads)?;
+        total += scored.loss;
+    }
+    if batch.is_empty() {
+        return Ok((0.0, grads));
+    }
+    grads.scale(1.0 / batch.len() as f64);
+    Ok((total / batch.len() as f64, grads))
+}
+
+/// Fine-tuning step for [`Trainer`] over `(tokens, tags)` examples with one
+/// tag per token. Padding tokens, and tags equal to the loss's
+/// `ignore_index` (for example sub-word continuations), are left out of the
+/// cross-entropy. Returns the mean loss over the batch's examples and the
+/// matching mean gradients.
+pub fn token_classification_loss(
+    model: &TokenClassifier,
+    batch: &[(Vec<usize>, Vec<usize>)],
+    loss: &CrossEntropyConfig,
+) -> Result<(f64, Gradients)> {
+    let mut grads = Gradients::new();
+    let mut total = 0.0;
+    let mut examples = 0;
+    for (tokens, tags) in batch {
+        if tokens.is_empty() {
+            continue;
+        }
+        let (logits, cache) = model.forward_with_cache(tokens)?;
+        let scored = masked_cross_entropy(&logits, tags, &model.loss_mask(tokens), loss)?;
+        model.accumulate_gradients(&cache, &scored.d_logits, &mut grads)?;
+        total += scored.loss;
+        examples += 1;
+    }
+    if examples == 0 {
+        return Ok((0.0, grads));
+    }
+    grads.scale(1.0 / examples as f64);
+    Ok((total / examples as f64, grads))
+}
+
+/// Masked language modeling step for [`Trainer`]: cross-entropy of the
+/// model's predictions against the original tokens at the masked positions
+/// only. Examples without masked positions are skipped. Returns the mean
+/// loss over the remaining examples and the matching mean gradients.
+pub fn masked_lm_loss(
+    model: &MaskedLanguageModel,
+    batch: &[MaskedTokens],
+    loss: &CrossEntropyConfig,
+) -> Result<(f64, Gradients)> {
+    let mut grads = Gradients::new();
+    let mut total = 0.0;
+    let mut examples = 0;
+    for example in batch {
+        if example.num_masked() == 0 {
+            continue;
+        }
+        let (logits, cache) = model.forward_with_cache(&example.input)?;
+        let scored = masked_cross_entropy(&logits, &example.labels, &example.masked, loss)?;
+        model.accumulate_gradients(&cache, &scored.d_logits, &mut grads)?;
+        total += scored.loss;
+        examples += 1;
+    }
+    if examples == 0 {
+        return Ok((0.0, grads));
+    }
+    grads.scale(1.0 / examples as f64);
+    Ok((total / examples as f64, grads))
+}
@@ -0,0 +1,434 @@
+//! Streaming gzip (RFC 1952) decompression.
+//!
+//! [`GzipReader`] inflates deflate (RFC 1951) data as it is read, keeping only
+//! the 32 KiB back-reference window, the undelivered output and one block's
+//! Huffman tables in memory. Concatenated gzip members decode as one stream,
+//! as `gzip -d` does.
+
+use std::io::{self, BufRead, Read};
+
+use super::checksum::crc32_update;
+
+/// Largest distance a deflate back-reference may reach.
+const WINDOW: usize = 32 * 1024;
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code-length code lengths are stored in a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("gzip: {}", message))
+}
+
+/// A canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    /// Number of codes of each bit length.
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    /// The fixed literal/length and distance codes of block type 1.
+    fn fixed() -> (Self, Self) {
+        let mut lengths = [0u8; 288];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        let literals = Self::new(&lengths).expect("fixed literal code is complete");
+        let distances = Self::new(&[5; 30]).expect("fixed distance code is complete");
+        (literals, distances)
+    }
+}
+
+/// Least-significant-bit-first reader over a byte stream.
+struct BitReader<R> {
+    inner: R,
+    buffer: u32,
+    count: u32,
+}
+
+impl<R: BufRead> BitReader<R> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self.inner.fill_buf()?.first().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "gzip: truncated stream")
+        })?;
+        self.inner.consume(1);
+        Ok(byte)
+    }
+
+    /// Reads `n <= 16` bits.
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            self.buffer |= (self.byte()? as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drops the bits left in the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn u16_le(&mut self) -> io::Result<u16> {
+        Ok(self.bits(16)? as u16)
+    }
+
+    fn u32_le(&mut self) -> io::Result<u32> {
+        Ok(self.bits(16)? | (self.bits(16)? << 16))
+    }
+
+    fn at_eof(&mut self) -> io::Result<bool> {
+        Ok(self.count == 0 && self.inner.fill_buf()?.is_empty())
+    }
+
+    fn decode(&mut self, code: &Huffman) -> io::Result<u16> {
+        let (mut value, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &code.counts[1..] {
+            value |= self.bits(1)? as i32;
+            let count = count as i32;
+            if value - count < first {
+                return Ok(code.symbols[(index + value - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            value <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+enum State {
+    Header,
+    BlockStart,
+    Stored(usize),
+    Compressed(Box<(Huffman, Huffman)>),
+    Trailer,
+    Done,
+}
+
+/// Decompresses a gzip stream as it is read.
+///
+/// Every member's CRC-32 and length are checked against its trailer; a
+/// mismatch or a malformed stream surfaces as an
+/// [`InvalidData`](io::ErrorKind::InvalidData) error.
+pub struct GzipReader<R> {
+    input: BitReader<R>,
+    state: State,
+    last_block: bool,
+    /// Recent output: the back-reference window followed by bytes not yet
+    /// handed to the caller.
+    history: Vec<u8>,
+    read_pos: usize,
+    crc_pos: usize,
+    crc: u32,
+    size: u32,
+}
+
+impl<R: BufRead> GzipReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            input: BitReader {
+                inner,
+                buffer: 0,
+                count: 0,
+            },
+            state: State::Header,
+            last_block: false,
+            history: Vec::new(),
+            read_pos: 0,
+            crc_pos: 0,
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    /// Decodes until a window's worth of output is pending or the stream
+    /// ends.
+    fn fill(&mut self) -> io::Result<()> {
+        if self.read_pos > WINDOW {
+            let drop = self.read_pos - WINDOW;
+            self.history.drain(..drop);
+            self.read_pos -= drop;
+            self.crc_pos -= drop;
+        }
+
+        while self.history.len() - self.read_pos < WINDOW {
+            match std::mem::replace(&mut self.state, State::Done) {
+                State::Header => {
+                    self.read_header()?;
+                    self.crc = 0;
+                    self.size = 0;
+                    self.state = State::BlockStart;
+                }
+                State::BlockStart => self.state = self.read_block_header()?,
+                State::Stored(remaining) => {
+                    let available = remaining.min(WINDOW);
+                    for _ in 0..available {
+                        let byte = self.input.byte()?;
+                        self.history.push(byte);
+                    }
+                    self.state = if remaining > available {
+                        State::Stored(remaining - available)
+                    } else {
+                        self.end_of_block()
+                    };
+                }
+                State::Compressed(codes) => {
+                    self.state = if self.inflate(&codes.0, &codes.1)? {
+                        self.end_of_block()
+                    } else {
+                        State::Compressed(codes)
+                    };
+                }
+                State::Trailer => {
+                    self.checksum();
+                    self.input.align();
+                    let crc = self.input.u32_le()?;
+                    let size = self.input.u32_le()?;
+                    if crc != self.crc {
+                        return Err(invalid("CRC-32 mismatch"));
+                    }
+                    if size != self.size {
+                        return Err(invalid("length mismatch"));
+                    }
+                    self.state = if self.input.at_eof()? {
+                        State::Done
+                    } else {
+                        State::Header
+                    };
+                }
+                State::Done => break,
+            }
+        }
+        self.checksum();
+        Ok(())
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        const FHCRC: u8 = 0x02;
+        const FEXTRA: u8 = 0x04;
+        const FNAME: u8 = 0x08;
+        const FCOMMENT: u8 = 0x10;
+
+        let input = &mut self.input;
+        if input.byte()? != 0x1f || input.byte()? != 0x8b {
+            return Err(invalid("not a gzip stream"));
+        }
+        if input.byte()? != 8 {
+            return Err(invalid("unsupported compression method"));
+        }
+        let flags = input.byte()?;
+        // Modification time, extra flags and operating system.
+        for _ in 0..6 {
+            input.byte()?;
+        }
+        if flags & FEXTRA != 0 {
+            for _ in 0..input.u16_le()? {
+                input.byte()?;
+            }
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                while input.byte()? != 0 {}
+            }
+        }
+        if flags & FHCRC != 0 {
+            input.u16_le()?;
+        }
+        Ok(())
+    }
+
+    fn read_block_header(&mut self) -> io::Result<State> {
+        self.last_block = self.input.bits(1)? == 1;
+        match self.input.bits(2)? {
+            0 => {
+                self.input.align();
+                let len = self.input.u16_le()?;
+                if self.input.u16_le()? != !len {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                Ok(State::Stored(len as usize))
+            }
+            1 => Ok(State::Compressed(Box::new(Huffman::fixed()))),
+            2 => Ok(State::Compressed(Box::new(self.read_dynamic_codes()?))),
+            _ => Err(invalid("reserved block type")),
+        }
+    }
+
+    fn read_dynamic_codes(&mut self) -> io::Result<(Huffman, Huffman)> {
+        let literal_count = self.input.bits(5)? as usize + 257;
+        let distance_count = self.input.bits(5)? as usize + 1;
+        let code_length_count = self.input.bits(4)? as usize + 4;
+        if literal_count > 286 || distance_count > 30 {
+            return Err(invalid("too many codes"));
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_lengths[symbol] = self.input.bits(3)? as u8;
+        }
+        let code_length_code = Huffman::new(&code_lengths)?;
+
+        let total = literal_count + distance_count;
+        let mut lengths = Vec::with_capacity(total);
+        while lengths.len() < total {
+            let symbol = self.input.decode(&code_length_code)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let previous = *lengths
+                        .last()
+                        .ok_or_else(|| invalid("repeat with no previous length"))?;
+                    (previous, 3 + self.input.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.input.bits(3)? as usize),
+                _ => (0, 11 + self.input.bits(7)? as usize),
+            };
+            if lengths.len() + repeat > total {
+                return Err(invalid("code lengths overrun"));
+            }
+            lengths.resize(lengths.len() + repeat, value);
+        }
+        if lengths[256] == 0 {
+            return Err(invalid("missing end-of-block code"));
+        }
+
+        let literals = Huffman::new(&lengths[..literal_count])?;
+        let distances = Huffman::new(&lengths[literal_count..])?;
+        Ok((literals, distances))
+    }
+
+    /// Decodes symbols of a compressed block until it ends (returning
+    /// `true`) or a window's worth of output is pending.
+    fn inflate(&mut self, literals: &Huffman, distances: &Huffman) -> io::Result<bool> {
+        while self.history.len() - self.read_pos < WINDOW {
+            let symbol = self.input.decode(literals)? as usize;
+            if symbol < 256 {
+                self.history.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Ok(true);
+            }
+
+            let index = symbol - 257;
+            if index >= LENGTH_BASE.len() {
+                return Err(invalid("invalid length symbol"));
+            }
+            let length =
+                LENGTH_BASE[index] as usize + self.input.bits(LENGTH_EXTRA[index] as u32)? as usize;
+            let index = self.input.decode(distances)? as usize;
+            if index >= DIST_BASE.len() {
+                return Err(invalid("invalid distance symbol"));
+            }
+            let distance =
+                DIST_BASE[index] as usize + self.input.bits(DIST_EXTRA[index] as u32)? as usize;
+            if distance > self.history.len() {
+                return Err(invalid("distance reaches before the start of the output"));
+            }
+            // Byte by byte, since the source may overlap the bytes being written.
+            let start = self.history.len() - distance;
+            for i in 0..length {
+                let byte = self.history[start + i];
+                self.history.push(byte);
+            }
+        }
+        Ok(false)
+    }
+
+    fn end_of_block(&self) -> State {
+        if self.last_block {
+            State::Trailer
+        } else {
+            State::BlockStart
+        }
+    }
+
+    fn checksum(&mut self) {
+        let fresh = &self.history[self.crc_pos..];
+        self.crc = crc32_update(self.crc, fresh);
+        self.size = self.size.wrapping_add(fresh.len() as u32);
+        self.crc_pos = self.history.len();
+    }
+}
+
+impl<R: BufRead> Read for GzipReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for GzipReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.read_pos == self.history.len() {
+            self.fill()?;
+        }
+        Ok(&self.history[self.read_pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.read_pos = (self.read_pos + amt).min(self.history.len());
+    }
+}
+
+/// Returns `true` if `bytes` start with the gzip magic number.
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1f, 0x8b])
+}
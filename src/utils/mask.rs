@@ -0,0 +1,204 @@
+//! Attention mask builders.
+//!
+//! A mask has one row per query and one column per key. `true` means the
+//! query may attend to that key; `false` entries are excluded from the
+//! softmax.
+//!
+//! The `create_*` functions build [`Mask`] matrices directly.
+//! [`AttentionMask`] describes a mask independently of sequence length and
+//! materializes it on demand, reusing cached causal matrices and skipping
+//! masks that would hide nothing.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::tensor::Matrix;
+use crate::{Result, TransformerError};
+
+/// Boolean attention mask (`true` = attend).
+pub type Mask = Matrix<bool>;
+
+/// Lower-triangular mask preventing positions from attending to the future.
+pub fn create_causal_mask(seq_len: usize) -> Mask {
+    Matrix::from_fn(seq_len, seq_len, |i, j| j <= i)
+}
+
+/// Square self-attention mask hiding keys equal to `pad_token`.
+pub fn create_padding_mask(seq: &[usize], pad_token: usize) -> Mask {
+    Matrix::from_fn(seq.len(), seq.len(), |_, j| seq[j] != pad_token)
+}
+
+/// `query_len × key_seq.len()` mask hiding keys equal to `pad_token`, for
+/// attention whose queries and keys come from different sequences (such as
+/// decoder queries over encoder memory).
+pub fn create_cross_attention_mask(query_len: usize, key_seq: &[usize], pad_token: usize) -> Mask {
+    Matrix::from_fn(query_len, key_seq.len(), |_, j| key_seq[j] != pad_token)
+}
+
+/// Banded mask letting each position attend to keys at most `window`
+/// positions away on either side. Combine with [`create_causal_mask`] for a
+/// causal sliding window.
+pub fn create_local_mask(seq_len: usize, window: usize) -> Mask {
+    Matrix::from_fn(seq_len, seq_len, |i, j| i.abs_diff(j) <= window)
+}
+
+/// Mask splitting the sequence into consecutive chunks of `chunk` positions
+/// (the last may be shorter) that only attend within themselves. A `chunk`
+/// of zero is treated as one.
+pub fn create_chunked_mask(seq_len: usize, chunk: usize) -> Mask {
+    let chunk = chunk.max(1);
+    Matrix::from_fn(seq_len, seq_len, |i, j| i / chunk == j / chunk)
+}
+
+/// Block-diagonal mask for several sequences packed into one: position `i`
+/// may only attend to positions of its own segment. Segments are laid out in
+/// order with the given lengths. Combine with [`create_causal_mask`] for
+/// packed decoder inputs.
+pub fn create_block_diagonal_mask(segment_lengths: &[usize]) -> Mask {
+    let segments: Vec<usize> = segment_lengths
+        .iter()
+        .enumerate()
+        .flat_map(|(segment, &len)| std::iter::repeat_n(segment, len))
+        .collect();
+    Matrix::from_fn(segments.len(), segments.len(), |i, j| {
+        segments[i] == segments[j]
+    })
+}
+
+/// [`Matrix::ensure_shape`] for masks, failing with
+/// [`TransformerError::MaskError`].
+pub(crate) fn ensure_mask_shape(mask: &Mask, shape: (usize, usize), what: &str) -> Result<()> {
+    mask.ensure_shape(shape, what)
+        .map_err(|e| TransformerError::mask(e.to_string()))
+}
+
+/// Element-wise AND of two masks of equal shape.
+pub fn combine_masks(a: &Mask, b: &Mask) -> Result<Mask> {
+    if a.shape() != b.shape() {
+        return Err(TransformerError::mask(format!(
+            "cannot combine masks of shape {:?} and {:?}",
+            a.shape(),
+            b.shape()
+        )));
+    }
+    Ok(Matrix::from_fn(a.rows(), a.cols(), |i, j| {
+        a[(i, j)] && b[(i, j)]
+    }))
+}
+
+/// A mask described by its structure rather than by a matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttentionMask {
+    /// Each query sees keys at or before its own position.
+    Causal,
+    /// Keys whose flag is `false` are hidden from every query.
+    Padding(Vec<bool>),
+    /// Intersection of several masks.
+    Combined(Vec<AttentionMask>),
+    /// An explicit `queries × keys` matrix.
+    Custom(Arc<Mask>),
+}
+
+impl AttentionMask {
+    /// Hides keys equal to `pad_token`.
+    pub fn padding(seq: &[usize], pad_token: usize) -> Self {
+        AttentionMask::Padding(seq.iter().map(|&t| t != pad_token).collect())
+    }
+
+    /// Intersection of `self` and `other`.
+    pub fn and(self, other: AttentionMask) -> Self {
+        let mut parts = match self {
+            AttentionMask::Combined(parts) => parts,
+            mask => vec![mask],
+        };
+        match other {
+            AttentionMask::Combined(more) => parts.extend(more),
+            mask => parts.push(mask),
+        }
+        AttentionMask::Combined(parts)
+    }
+
+    /// Whether `query` may attend to `key`. Out-of-range padding or custom
+    /// entries are treated as hidden.
+    pub fn allows(&self, query: usize, key: usize) -> bool {
+        match self {
+            AttentionMask::Causal => key <= query,
+            AttentionMask::Padding(keep) => keep.get(key).copied().unwrap_or(false),
+            AttentionMask::Combined(parts) => parts.iter().all(|m| m.allows(query, key)),
+            AttentionMask::Custom(mask) => {
+                query < mask.rows() && key < mask.cols() && mask[(query, key)]
+            }
+        }
+    }
+
+    /// The `queries × keys` matrix, or `None` if nothing would be hidden.
+    /// Causal matrices are cached per shape and shared between calls.
+    pub fn materialize(&self, queries: usize, keys: usize) -> Result<Option<Arc<Mask>>> {
+        match self {
+            // The first query hides every later key, so a causal mask only
+            // has an effect once there are two keys.
+            AttentionMask::Causal if queries == 0 || keys < 2 => Ok(None),
+            AttentionMask::Causal => Ok(Some(cached_causal(queries, keys))),
+            AttentionMask::Padding(keep) => {
+                if keep.len() != keys {
+                    return Err(TransformerError::mask(format!(
+                        "padding mask covers {} keys, attention has {}",
+                        keep.len(),
+                        keys
+                    )));
+                }
+                if keep.iter().all(|&k| k) {
+                    return Ok(None);
+                }
+                Ok(Some(Arc::new(Matrix::from_fn(queries, keys, |_, j| {
+                    keep[j]
+                }))))
+            }
+            AttentionMask::Combined(parts) => {
+                let mut result: Option<Arc<Mask>> = None;
+                for part in parts {
+                    if let Some(mask) = part.materialize(queries, keys)? {
+                        result = Some(match result {
+                            Some(acc) => Arc::new(combine_masks(&acc, &mask)?),
+                            None => mask,
+                        });
+                    }
+                }
+                Ok(result)
+            }
+            AttentionMask::Custom(mask) => {
+                ensure_mask_shape(mask, (queries, keys), "custom attention mask")?;
+                Ok(Some(Arc::clone(mask)))
+            }
+        }
+    }
+}
+
+impl From<Mask> for AttentionMask {
+    fn from(mask: Mask) -> Self {
+        AttentionMask::Custom(Arc::new(mask))
+    }
+}
+
+/// Causal masks keyed by `(queries, keys)`.
+type MaskCache = Mutex<HashMap<(usize, usize), Arc<Mask>>>;
+
+/// Returns the shared causal mask of the given shape.
+fn cached_causal(queries: usize, keys: usize) -> Arc<Mask> {
+    // Bounded so that unusual length mixes cannot grow the cache forever.
+    const MAX_ENTRIES: usize = 256;
+    static CACHE: OnceLock<MaskCache> = OnceLock::new();
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(mask) = cache.get(&(queries, keys)) {
+        return Arc::clone(mask);
+    }
+    if cache.len() >= MAX_ENTRIES {
+        cache.clear();
+    }
+    let mask = Arc::new(Matrix::from_fn(queries, keys, |i, j| j <= i));
+    cache.insert((queries, keys), Arc::clone(&mask));
+    mask
+}
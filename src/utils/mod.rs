@@ -0,0 +1,17 @@
+//! Shared utilities used across the crate.
+
+pub mod checksum;
+pub mod gzip;
+pub mod json;
+pub mod mask;
+pub mod parallel;
+pub mod png;
+pub mod rng;
+pub mod similarity;
+pub mod tensor_ops;
+
+pub use mask::{
+    combine_masks, create_block_diagonal_mask, create_causal_mask, create_chunked_mask,
+    create_cross_attention_mask, create_local_mask, create_padding_mask, AttentionMask, Mask,
+};
+pub use rng::{Rng, SharedRng};
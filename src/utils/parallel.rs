@@ -0,0 +1,78 @@
+//! Splitting independent work over scoped threads.
+//!
+//! With the `parallel` cargo feature, [`map`] and [`for_each_row_mut`]
+//! spread their work over [`std::thread::available_parallelism`] scoped
+//! threads once there is enough of it to pay for starting them; without the
+//! feature they run on the calling thread. Every item is computed by the
+//! same code either way, so results are identical.
+
+/// Scalar operations below which work stays on the calling thread.
+#[cfg(feature = "parallel")]
+const MIN_PARALLEL_WORK: usize = 1 << 15;
+
+/// Threads worth using for `items` items totalling `work` scalar operations.
+#[cfg(feature = "parallel")]
+fn threads_for(items: usize, work: usize) -> usize {
+    if work < MIN_PARALLEL_WORK {
+        return 1;
+    }
+    std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(items)
+        .max(1)
+}
+
+#[cfg(not(feature = "parallel"))]
+fn threads_for(_items: usize, _work: usize) -> usize {
+    1
+}
+
+/// `items.iter().map(f).collect()`, possibly in parallel. `work` estimates
+/// the scalar operations of the whole job.
+pub fn map<T: Sync, R: Send>(items: &[T], work: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = threads_for(items.len(), work);
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let chunk = items.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk)
+            .map(|part| {
+                let f = &f;
+                scope.spawn(move || part.iter().map(f).collect::<Vec<R>>())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    })
+}
+
+/// Calls `f(i, row)` for every `cols`-wide row of the row-major `data`,
+/// possibly in parallel.
+pub fn for_each_row_mut<T: Send>(data: &mut [T], cols: usize, f: impl Fn(usize, &mut [T]) + Sync) {
+    if cols == 0 {
+        return;
+    }
+    let rows = data.len() / cols;
+    let threads = threads_for(rows, data.len());
+    if threads <= 1 {
+        for (i, row) in data.chunks_mut(cols).enumerate() {
+            f(i, row);
+        }
+        return;
+    }
+    let rows_per_thread = rows.div_ceil(threads);
+    std::thread::scope(|scope| {
+        for (t, block) in data.chunks_mut(rows_per_thread * cols).enumerate() {
+            let f = &f;
+            scope.spawn(move || {
+                for (i, row) in block.chunks_mut(cols).enumerate() {
+                    f(t * rows_per_thread + i, row);
+                }
+            });
+        }
+    });
+}
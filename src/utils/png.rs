@@ -0,0 +1,72 @@
+//! Minimal PNG encoder for 8-bit RGB images.
+//!
+//! Image data is stored in uncompressed deflate blocks, which keeps the
+//! encoder tiny; files are larger than necessary but valid everywhere.
+
+use super::checksum::{adler32, crc32};
+use crate::Result;
+
+/// Encodes `pixels` (row-major RGB triples) as a PNG file.
+pub fn encode_rgb(width: usize, height: usize, pixels: &[[u8; 3]]) -> Result<Vec<u8>> {
+    if pixels.len() != width * height {
+        return Err(format!(
+            "expected {} pixels for a {}x{} image, got {}",
+            width * height,
+            width,
+            height,
+            pixels.len()
+        )
+        .into());
+    }
+    if width == 0 || height == 0 || width > u32::MAX as usize || height > u32::MAX as usize {
+        return Err(format!("invalid PNG dimensions {}x{}", width, height).into());
+    }
+
+    // Each scanline starts with filter type 0 (none).
+    let mut raw = Vec::with_capacity(height * (1 + 3 * width));
+    for row in pixels.chunks(width) {
+        raw.push(0);
+        for px in row {
+            raw.extend_from_slice(px);
+        }
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit, RGB, deflate, no filter, no interlace
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of stored (uncompressed) deflate blocks.
+pub(crate) fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65_535;
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(if blocks.peek().is_none() { 1 } else { 0 });
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
@@ -0,0 +1,210 @@
+//! Small, seedable pseudo-random number generator.
+//!
+//! The crate has no external dependencies, so this module provides the
+//! randomness needed for shuffling, initialization and sampling. The generator
+//! is xoshiro256** seeded through SplitMix64, which is fast, has a 2^256 period
+//! and produces identical streams on every platform for the same seed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Seedable xoshiro256** generator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// Creates a generator whose stream is fully determined by `seed`.
+    pub fn seed_from_u64(seed: u64) -> Self {
+        let mut sm = seed;
+        let mut state = [0u64; 4];
+        for slot in state.iter_mut() {
+            *slot = splitmix64(&mut sm);
+        }
+        Self { state }
+    }
+
+    /// Creates a generator seeded from the system clock and a process-wide
+    /// counter, so that two calls never share a stream.
+    ///
+    /// `wasm32-unknown-unknown` has no clock to read, so there the streams
+    /// depend on the counter alone and repeat from one page load to the
+    /// next; seed explicitly when that matters.
+    pub fn from_entropy() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self::seed_from_u64(clock_nanos() ^ count.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    /// Returns a uniform sample in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Returns a uniform integer in `[0, n)`. `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "Rng::below called with n = 0");
+        // Lemire's multiply-shift reduction; the bias is negligible for the
+        // sizes used in this crate.
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Returns a uniform sample in `[low, high)`.
+    pub fn uniform(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+
+    /// Returns a sample from a normal distribution (Box-Muller transform).
+    pub fn normal(&mut self, mean: f64, std_dev: f64) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        mean + std_dev * z
+    }
+
+    /// Returns `true` with probability `p`.
+    pub fn bernoulli(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// Draws an index from the categorical distribution `probs`, which
+    /// should sum to one. Rounding leftovers fall to the last index.
+    pub fn categorical(&mut self, probs: &[f64]) -> usize {
+        let mut threshold = self.next_f64();
+        for (index, &p) in probs.iter().enumerate() {
+            if threshold < p {
+                return index;
+            }
+            threshold -= p;
+        }
+        probs.len().saturating_sub(1)
+    }
+
+    /// Shuffles `items` in place (Fisher-Yates).
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+/// A generator shared between the stochastic parts of a model.
+///
+/// Clones refer to the same stream, so every dropout layer of a model and
+/// its sampling draw from one sequence that a single seed fully determines.
+/// The handle is `Send + Sync`; draws are serialized by a mutex.
+#[derive(Debug, Clone)]
+pub struct SharedRng {
+    inner: Arc<Mutex<Rng>>,
+}
+
+impl SharedRng {
+    pub fn new(rng: Rng) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    pub fn seed_from_u64(seed: u64) -> Self {
+        Self::new(Rng::seed_from_u64(seed))
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(Rng::from_entropy())
+    }
+
+    /// Runs `f` with exclusive access to the generator.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Rng) -> R) -> R {
+        let mut rng = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut rng)
+    }
+
+    /// Restarts the stream from `seed`, for every clone of this handle.
+    pub fn reseed(&self, seed: u64) {
+        self.with(|rng| *rng = Rng::seed_from_u64(seed));
+    }
+
+    /// An independent generator seeded from this stream.
+    pub fn fork(&self) -> Rng {
+        Rng::seed_from_u64(self.with(|rng| rng.next_u64()))
+    }
+
+    /// Whether `self` and `other` draw from the same stream.
+    pub fn same_stream(&self, other: &SharedRng) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    mix64(*state)
+}
+
+/// SplitMix64 finalizer: a bijective mix that spreads every input bit over
+/// the whole output word.
+pub fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Stable 64-bit FNV-1a hash.
+///
+/// Unlike `std::collections::hash_map::DefaultHasher`, the output is
+/// guaranteed not to change between Rust releases, which makes it suitable for
+/// reproducible bucketing.
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Nanoseconds since the Unix epoch, the clock part of
+/// [`Rng::from_entropy`].
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn clock_nanos() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn clock_nanos() -> u64 {
+    0
+}
+
+/// Returns a freshly entropy-seeded generator for ad-hoc use.
+pub fn thread_rng() -> Rng {
+    Rng::from_entropy()
+}
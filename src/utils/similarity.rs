@@ -0,0 +1,124 @@
+//! Vector similarity and nearest-neighbour search.
+//!
+//! Vectors are slices and candidate sets are matrices with one candidate
+//! per row. Every [`Metric`] scores so that larger means more similar, which
+//! lets retrieval code rank with a single top-k regardless of the metric.
+
+use crate::tensor::Matrix;
+use crate::utils::tensor_ops::top_k;
+use crate::{Result, TransformerError};
+
+/// Dot product of two equal-length vectors.
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Euclidean (L2) length.
+pub fn norm(v: &[f64]) -> f64 {
+    dot(v, v).sqrt()
+}
+
+/// Cosine of the angle between `a` and `b`; zero if either is a zero vector.
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot(a, b) / denominator
+    }
+}
+
+pub fn squared_euclidean_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+pub fn euclidean_distance(a: &[f64], b: &[f64]) -> f64 {
+    squared_euclidean_distance(a, b).sqrt()
+}
+
+/// `v` scaled to unit length; zero vectors are returned unchanged.
+pub fn normalize(v: &[f64]) -> Vec<f64> {
+    let n = norm(v);
+    if n == 0.0 {
+        v.to_vec()
+    } else {
+        v.iter().map(|x| x / n).collect()
+    }
+}
+
+/// Every row of `x` scaled to unit length.
+pub fn normalize_rows(x: &Matrix) -> Matrix {
+    let mut out = x.clone();
+    for i in 0..x.rows() {
+        let row = normalize(x.row(i));
+        out.row_mut(i).copy_from_slice(&row);
+    }
+    out
+}
+
+/// How two vectors are compared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    #[default]
+    Cosine,
+    Dot,
+    /// Scored as the negated distance.
+    Euclidean,
+}
+
+impl Metric {
+    /// Similarity of `a` and `b`; larger is more similar.
+    pub fn score(&self, a: &[f64], b: &[f64]) -> f64 {
+        match self {
+            Metric::Cosine => cosine_similarity(a, b),
+            Metric::Dot => dot(a, b),
+            Metric::Euclidean => -euclidean_distance(a, b),
+        }
+    }
+}
+
+/// Scores `query` against every row of `candidates`.
+pub fn similarities(query: &[f64], candidates: &Matrix, metric: Metric) -> Result<Vec<f64>> {
+    if query.len() != candidates.cols() {
+        return Err(TransformerError::shape(format!(
+            "query has {} dimensions, candidates have {}",
+            query.len(),
+            candidates.cols()
+        )));
+    }
+    Ok(candidates
+        .row_iter()
+        .map(|candidate| metric.score(query, candidate))
+        .collect())
+}
+
+/// `queries.rows() × candidates.rows()` matrix of pairwise scores.
+pub fn similarity_matrix(queries: &Matrix, candidates: &Matrix, metric: Metric) -> Result<Matrix> {
+    if queries.cols() != candidates.cols() {
+        return Err(TransformerError::shape(format!(
+            "queries have {} dimensions, candidates have {}",
+            queries.cols(),
+            candidates.cols()
+        )));
+    }
+    match metric {
+        Metric::Dot => queries.matmul(&candidates.transpose()),
+        Metric::Cosine => normalize_rows(queries).matmul(&normalize_rows(candidates).transpose()),
+        Metric::Euclidean => Ok(Matrix::from_fn(
+            queries.rows(),
+            candidates.rows(),
+            |i, j| -euclidean_distance(queries.row(i), candidates.row(j)),
+        )),
+    }
+}
+
+/// The `k` rows of `candidates` most similar to `query`, as
+/// `(row, score)` pairs, best first.
+pub fn top_k_neighbors(
+    query: &[f64],
+    candidates: &Matrix,
+    k: usize,
+    metric: Metric,
+) -> Result<Vec<(usize, f64)>> {
+    Ok(top_k(&similarities(query, candidates, metric)?, k))
+}
@@ -0,0 +1,170 @@
+//! Numeric helpers shared by layers, attention, decoding and losses.
+
+use crate::tensor::Matrix;
+use crate::utils::mask::{ensure_mask_shape, Mask};
+use crate::utils::parallel;
+use crate::{Result, TransformerError};
+
+/// Numerically stable softmax of a slice.
+pub fn softmax(values: &[f64]) -> Vec<f64> {
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<f64> = values.iter().map(|&v| (v - max).exp()).collect();
+    let sum: f64 = exps.iter().sum();
+    exps.into_iter().map(|e| e / sum).collect()
+}
+
+/// Applies [`softmax`] independently to every row.
+pub fn softmax_rows(x: &Matrix) -> Matrix {
+    let mut out = x.clone();
+    for i in 0..x.rows() {
+        let row = softmax(x.row(i));
+        out.row_mut(i).copy_from_slice(&row);
+    }
+    out
+}
+
+/// One row per id, with a one in the id's column (`ids.len() × vocab_size`).
+pub fn one_hot(ids: &[usize], vocab_size: usize) -> Result<Matrix> {
+    if let Some(&id) = ids.iter().find(|&&id| id >= vocab_size) {
+        return Err(TransformerError::VocabOverflow {
+            token: id,
+            vocab_size,
+        });
+    }
+    Ok(Matrix::from_fn(ids.len(), vocab_size, |i, j| {
+        if ids[i] == j {
+            1.0
+        } else {
+            0.0
+        }
+    }))
+}
+
+/// What [`masked_softmax`] returns for a row in which every entry is masked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FullyMaskedRow {
+    /// All zeros: the query attends to nothing and its output is zero.
+    #[default]
+    Zeros,
+    /// Equal weight on every position, as if the row were unmasked and
+    /// constant.
+    Uniform,
+}
+
+/// Softmax over the entries of `values` whose `keep` flag is set; the rest
+/// get probability zero. Entries equal to `-inf` count as masked too. A row
+/// with nothing left is filled according to `fully_masked` instead of
+/// producing NaNs.
+pub fn masked_softmax(values: &[f64], keep: &[bool], fully_masked: FullyMaskedRow) -> Vec<f64> {
+    let kept = |j: usize| keep.get(j).copied().unwrap_or(true) && values[j] > f64::NEG_INFINITY;
+    let max = (0..values.len())
+        .filter(|&j| kept(j))
+        .map(|j| values[j])
+        .fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return match fully_masked {
+            FullyMaskedRow::Zeros => vec![0.0; values.len()],
+            FullyMaskedRow::Uniform => vec![1.0 / values.len() as f64; values.len()],
+        };
+    }
+    let exps: Vec<f64> = (0..values.len())
+        .map(|j| {
+            if kept(j) {
+                (values[j] - max).exp()
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let sum: f64 = exps.iter().sum();
+    exps.into_iter().map(|e| e / sum).collect()
+}
+
+/// Applies [`masked_softmax`] to every row of `scores`; `mask`, if given,
+/// must have the same shape.
+pub fn masked_softmax_rows(
+    scores: &Matrix,
+    mask: Option<&Mask>,
+    fully_masked: FullyMaskedRow,
+) -> Result<Matrix> {
+    if let Some(mask) = mask {
+        ensure_mask_shape(mask, scores.shape(), "softmax mask")?;
+    }
+    let mut out = scores.clone();
+    let cols = out.cols();
+    parallel::for_each_row_mut(out.as_mut_slice(), cols, |i, row| {
+        let keep = mask.map_or(&[][..], |m| m.row(i));
+        let probs = masked_softmax(row, keep, fully_masked);
+        row.copy_from_slice(&probs);
+    });
+    Ok(out)
+}
+
+/// `ln Σ exp(v)`, computed without overflow. Returns `-inf` for an empty
+/// slice or one holding only `-inf`.
+pub fn log_sum_exp(values: &[f64]) -> f64 {
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + values.iter().map(|&v| (v - max).exp()).sum::<f64>().ln()
+}
+
+/// Numerically stable `ln softmax(values)`. Unlike `softmax(..).ln()`, very
+/// unlikely entries keep a finite log-probability instead of `-inf`.
+pub fn log_softmax(values: &[f64]) -> Vec<f64> {
+    let lse = log_sum_exp(values);
+    values.iter().map(|&v| v - lse).collect()
+}
+
+/// Applies [`log_softmax`] independently to every row.
+pub fn log_softmax_rows(x: &Matrix) -> Matrix {
+    let mut out = x.clone();
+    for i in 0..x.rows() {
+        let row = log_softmax(x.row(i));
+        out.row_mut(i).copy_from_slice(&row);
+    }
+    out
+}
+
+/// Index of the largest value; the first one wins ties and NaNs are
+/// skipped. `None` if there is no non-NaN value.
+pub fn argmax_row(values: &[f64]) -> Option<usize> {
+    let mut best: Option<(usize, f64)> = None;
+    for (i, &v) in values.iter().enumerate() {
+        if !v.is_nan() && best.is_none_or(|(_, b)| v > b) {
+            best = Some((i, v));
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+/// [`argmax_row`] of every row of `x`, with `0` for rows that are all NaN.
+pub fn argmax_rows(x: &Matrix) -> Vec<usize> {
+    x.row_iter()
+        .map(|row| argmax_row(row).unwrap_or(0))
+        .collect()
+}
+
+/// The `k` largest values as `(index, value)` pairs, largest first, ties
+/// broken by lower index. NaNs are never selected. Uses a partial selection,
+/// so it is linear in `values.len()` plus `k log k` for the final sort.
+pub fn top_k(values: &[f64], k: usize) -> Vec<(usize, f64)> {
+    let mut candidates: Vec<(usize, f64)> = values
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, v)| !v.is_nan())
+        .collect();
+    let order = |a: &(usize, f64), b: &(usize, f64)| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0));
+    let k = k.min(candidates.len());
+    if k == 0 {
+        return Vec::new();
+    }
+    if k < candidates.len() {
+        candidates.select_nth_unstable_by(k - 1, order);
+        candidates.truncate(k);
+    }
+    candidates.sort_unstable_by(order);
+    candidates
+}
@@ -0,0 +1,226 @@
+//! Attention map export for inspection, papers and teaching.
+//!
+//! Maps collected with the `forward_with_attentions` methods can be written
+//! as a single JSON document (weights plus token labels) or rendered as one
+//! PNG heatmap per layer and head.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::models::{Transformer, TransformerAttentions};
+use crate::tensor::Matrix;
+use crate::utils::json::Json;
+use crate::utils::png;
+use crate::Result;
+
+/// Which attention block a map was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttentionKind {
+    EncoderSelf,
+    DecoderSelf,
+    Cross,
+}
+
+impl AttentionKind {
+    /// Short identifier used in JSON and file names.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttentionKind::EncoderSelf => "encoder_self",
+            AttentionKind::DecoderSelf => "decoder_self",
+            AttentionKind::Cross => "cross",
+        }
+    }
+}
+
+/// Attention probabilities of a single head.
+#[derive(Debug, Clone)]
+pub struct AttentionMap {
+    pub kind: AttentionKind,
+    pub layer: usize,
+    pub head: usize,
+    /// `queries × keys` probabilities.
+    pub weights: Matrix,
+}
+
+/// Flattens model attentions into one map per block, layer and head.
+pub fn collect_attention_maps(attentions: &TransformerAttentions) -> Vec<AttentionMap> {
+    let mut maps = encoder_attention_maps(&attentions.encoder);
+    for (layer, decoder) in attentions.decoder.iter().enumerate() {
+        for (kind, heads) in [
+            (AttentionKind::DecoderSelf, &decoder.self_attention),
+            (AttentionKind::Cross, &decoder.cross_attention),
+        ] {
+            for (head, weights) in heads.iter().enumerate() {
+                maps.push(AttentionMap {
+                    kind,
+                    layer,
+                    head,
+                    weights: weights.clone(),
+                });
+            }
+        }
+    }
+    maps
+}
+
+/// Flattens encoder attentions (`[layer][head]`) into maps.
+pub fn encoder_attention_maps(attentions: &[Vec<Matrix>]) -> Vec<AttentionMap> {
+    let mut maps = Vec::new();
+    for (layer, heads) in attentions.iter().enumerate() {
+        for (head, weights) in heads.iter().enumerate() {
+            maps.push(AttentionMap {
+                kind: AttentionKind::EncoderSelf,
+                layer,
+                head,
+                weights: weights.clone(),
+            });
+        }
+    }
+    maps
+}
+
+/// Token labels for the axes of exported maps.
+#[derive(Debug, Clone, Default)]
+pub struct TokenLabels {
+    pub source: Vec<String>,
+    pub target: Vec<String>,
+}
+
+impl TokenLabels {
+    /// Labels each position with its token id.
+    pub fn from_ids(source: &[usize], target: &[usize]) -> Self {
+        let label = |ids: &[usize]| ids.iter().map(|t| t.to_string()).collect();
+        Self {
+            source: label(source),
+            target: label(target),
+        }
+    }
+
+    fn axes(&self, kind: AttentionKind) -> (&[String], &[String]) {
+        match kind {
+            AttentionKind::EncoderSelf => (&self.source, &self.source),
+            AttentionKind::DecoderSelf => (&self.target, &self.target),
+            AttentionKind::Cross => (&self.target, &self.source),
+        }
+    }
+}
+
+/// Serializes maps as a JSON document of the form
+/// `{"maps": [{"kind", "layer", "head", "queries", "keys", "weights"}]}`.
+pub fn attention_maps_to_json(maps: &[AttentionMap], labels: &TokenLabels) -> Json {
+    let entries = maps
+        .iter()
+        .map(|map| {
+            let (queries, keys) = labels.axes(map.kind);
+            let weights = map
+                .weights
+                .row_iter()
+                .map(|row| Json::from(row.to_vec()))
+                .collect::<Vec<_>>();
+            Json::object([
+                ("kind", Json::from(map.kind.as_str())),
+                ("layer", Json::from(map.layer)),
+                ("head", Json::from(map.head)),
+                ("queries", Json::from(queries.to_vec())),
+                ("keys", Json::from(keys.to_vec())),
+                ("weights", Json::Array(weights)),
+            ])
+        })
+        .collect();
+    Json::object([("maps", Json::Array(entries))])
+}
+
+/// Writes [`attention_maps_to_json`] output to `path`.
+pub fn write_attention_json(
+    path: impl AsRef<Path>,
+    maps: &[AttentionMap],
+    labels: &TokenLabels,
+) -> Result<()> {
+    fs::write(
+        path,
+        attention_maps_to_json(maps, labels).to_pretty_string(),
+    )?;
+    Ok(())
+}
+
+/// Renders a probability matrix as a PNG heatmap, drawing each entry as a
+/// `cell_size × cell_size` square. Values are clamped to `[0, 1]`.
+pub fn render_heatmap(weights: &Matrix, cell_size: usize) -> Result<Vec<u8>> {
+    let cell_size = cell_size.max(1);
+    let (width, height) = (weights.cols() * cell_size, weights.rows() * cell_size);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.push(colormap(weights[(y / cell_size, x / cell_size)]));
+        }
+    }
+    png::encode_rgb(width, height, &pixels)
+}
+
+/// Writes one heatmap per map into `dir` as `{kind}_layer{L}_head{H}.png`
+/// and returns the paths written.
+pub fn write_heatmaps(
+    dir: impl AsRef<Path>,
+    maps: &[AttentionMap],
+    cell_size: usize,
+) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    let mut paths = Vec::with_capacity(maps.len());
+    for map in maps {
+        let path = dir.join(format!(
+            "{}_layer{}_head{}.png",
+            map.kind.as_str(),
+            map.layer,
+            map.head
+        ));
+        fs::write(&path, render_heatmap(&map.weights, cell_size)?)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Runs `model` on `src`/`tgt` and writes `attention.json` plus a heatmap
+/// for every layer and head into `dir`. Returns the collected maps.
+pub fn export_attention(
+    model: &Transformer,
+    src: &[usize],
+    tgt: &[usize],
+    dir: impl AsRef<Path>,
+    cell_size: usize,
+) -> Result<Vec<AttentionMap>> {
+    let dir = dir.as_ref();
+    let (_, attentions) = model.forward_with_attentions(src, tgt)?;
+    let maps = collect_attention_maps(&attentions);
+    write_heatmaps(dir, &maps, cell_size)?;
+    write_attention_json(
+        dir.join("attention.json"),
+        &maps,
+        &TokenLabels::from_ids(src, tgt),
+    )?;
+    Ok(maps)
+}
+
+/// Perceptually ordered dark-to-bright color ramp (approximates "inferno").
+fn colormap(value: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.0, 0.0, 4.0],
+        [87.0, 16.0, 110.0],
+        [188.0, 55.0, 84.0],
+        [249.0, 142.0, 9.0],
+        [252.0, 255.0, 164.0],
+    ];
+    let v = if value.is_finite() {
+        value.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let scaled = v * (STOPS.len() - 1) as f64;
+    let i = (scaled.floor() as usize).min(STOPS.len() - 2);
+    let t = scaled - i as f64;
+    let mut rgb = [0u8; 3];
+    for (c, out) in rgb.iter_mut().enumerate() {
+        *out = (STOPS[i][c] + t * (STOPS[i + 1][c] - STOPS[i][c])).round() as u8;
+    }
+    rgb
+}
@@ -0,0 +1,118 @@
+//! [`Transformer`] inference for the browser through `wasm-bindgen`.
+//!
+//! [`WasmTransformer`] is exported as a JavaScript class. Its methods only
+//! take and return types that cross the JS boundary directly: byte slices
+//! for checkpoints, `u32` token ids, `f32` arrays for encodings, and strings
+//! for configurations and errors. Build the module with the `parallel`
+//! feature off, since the inference path then needs neither threads nor a
+//! clock:
+//!
+//! ```text
+//! cargo check --target wasm32-unknown-unknown --features wasm
+//! wasm-pack build --target web -- --features wasm
+//! ```
+//!
+//! Seed the model with [`setSeed`](WasmTransformer::set_seed). Without a
+//! clock, entropy-seeded generators repeat between page loads.
+
+use crate::config::TransformerConfig;
+use crate::models::Transformer;
+use crate::utils::json::Json;
+use wasm_bindgen::prelude::*;
+
+/// Errors surface to JavaScript as their message.
+pub type WasmResult<T> = std::result::Result<T, String>;
+
+/// An inference-mode [`Transformer`] behind a flat API.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct WasmTransformer {
+    model: Transformer,
+}
+
+#[wasm_bindgen]
+impl WasmTransformer {
+    /// Loads a checkpoint written by [`Transformer::to_bytes`], e.g. fetched
+    /// into an `ArrayBuffer`.
+    #[wasm_bindgen(js_name = fromCheckpoint)]
+    pub fn from_checkpoint(bytes: &[u8]) -> WasmResult<WasmTransformer> {
+        let model = Transformer::from_bytes(bytes).map_err(|e| e.to_string())?;
+        Ok(Self::from_model(model))
+    }
+
+    /// Builds a freshly initialized model from a configuration in the JSON
+    /// form of [`TransformerConfig::to_json`].
+    #[wasm_bindgen(js_name = fromConfigJson)]
+    pub fn from_config_json(config: &str, seed: u64) -> WasmResult<WasmTransformer> {
+        let json = Json::parse(config).map_err(|e| e.to_string())?;
+        let config = TransformerConfig::from_json(&json).map_err(|e| e.to_string())?;
+        let model = Transformer::with_seed(config, seed).map_err(|e| e.to_string())?;
+        Ok(Self::from_model(model))
+    }
+
+    /// The configuration as JSON.
+    #[wasm_bindgen(js_name = configJson)]
+    pub fn config_json(&self) -> String {
+        self.model.config.to_json().to_string()
+    }
+
+    #[wasm_bindgen(js_name = dModel)]
+    pub fn d_model(&self) -> u32 {
+        self.model.config.d_model as u32
+    }
+
+    #[wasm_bindgen(js_name = vocabSize)]
+    pub fn vocab_size(&self) -> u32 {
+        self.model.config.vocab_size as u32
+    }
+
+    /// Restarts the sampling stream from `seed`.
+    #[wasm_bindgen(js_name = setSeed)]
+    pub fn set_seed(&mut self, seed: u64) {
+        self.model.set_seed(seed);
+    }
+
+    /// Encoder states of `src`, row-major `src.len() × d_model`.
+    pub fn encode(&self, src: &[u32]) -> WasmResult<Vec<f32>> {
+        let memory = self.model.encode(&to_ids(src)).map_err(|e| e.to_string())?;
+        Ok(memory.as_slice().iter().map(|&v| v as f32).collect())
+    }
+
+    /// Samples up to `max_length` tokens, start token included.
+    pub fn generate(&self, src: &[u32], max_length: u32) -> WasmResult<Vec<u32>> {
+        self.model
+            .generate(&to_ids(src), max_length as usize)
+            .map(|ids| from_ids(&ids))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Like [`generate`](Self::generate), always taking the most likely
+    /// token.
+    #[wasm_bindgen(js_name = generateGreedy)]
+    pub fn generate_greedy(&self, src: &[u32], max_length: u32) -> WasmResult<Vec<u32>> {
+        self.model
+            .generate_greedy(&to_ids(src), max_length as usize)
+            .map(|ids| from_ids(&ids))
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl WasmTransformer {
+    /// Wraps `model`, switching dropout off.
+    pub fn from_model(mut model: Transformer) -> WasmTransformer {
+        model.set_training(false);
+        Self { model }
+    }
+
+    pub fn model(&self) -> &Transformer {
+        &self.model
+    }
+}
+
+fn to_ids(ids: &[u32]) -> Vec<usize> {
+    ids.iter().map(|&id| id as usize).collect()
+}
+
+fn from_ids(ids: &[usize]) -> Vec<u32> {
+    ids.iter().map(|&id| id as u32).collect()
+}
@@ -0,0 +1,153 @@
+use rust_transformer::attention::{
+    alibi_slopes, KvCache, MultiHeadAttention, ScaledDotProductAttention,
+};
+use rust_transformer::utils::mask::{combine_masks, create_causal_mask, create_local_mask, Mask};
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::Matrix;
+
+fn assert_matrix_close(actual: &Matrix, expected: &Matrix) {
+    assert_eq!(actual.shape(), expected.shape());
+    for (a, e) in actual.as_slice().iter().zip(expected.as_slice()) {
+        assert!((a - e).abs() < 1e-12, "{} vs {}", a, e);
+    }
+}
+
+/// The local mask for `n` queries taken to be the last of `m` positions.
+fn trailing_local_mask(n: usize, m: usize, window: usize) -> Mask {
+    Matrix::from_fn(n, m, |i, j| (i + m - n).abs_diff(j) <= window)
+}
+
+#[test]
+fn banded_attention_equals_full_attention_under_a_local_mask() {
+    let mut rng = Rng::seed_from_u64(11);
+    let (q, k, v) = (
+        Matrix::random_normal(7, 4, 1.0, &mut rng),
+        Matrix::random_normal(7, 4, 1.0, &mut rng),
+        Matrix::random_normal(7, 4, 1.0, &mut rng),
+    );
+    let causal = create_causal_mask(7);
+    for window in [0, 1, 3, 10] {
+        let banded = ScaledDotProductAttention::new(4).with_window(window);
+        let full = ScaledDotProductAttention::new(4);
+        let local = create_local_mask(7, window);
+        let causal_local = combine_masks(&local, &causal).unwrap();
+        for (mask, explicit) in [(None, &local), (Some(&causal), &causal_local)] {
+            let (out, weights) = banded.forward_with_weights(&q, &k, &v, mask).unwrap();
+            let (expected, expected_weights) = full
+                .forward_with_weights(&q, &k, &v, Some(explicit))
+                .unwrap();
+            assert_matrix_close(&out, &expected);
+            assert_matrix_close(&weights, &expected_weights);
+            assert_matrix_close(&banded.forward(&q, &k, &v, mask).unwrap(), &expected);
+        }
+    }
+}
+
+#[test]
+fn banded_attention_places_fewer_queries_at_the_last_positions() {
+    let mut rng = Rng::seed_from_u64(12);
+    let q = Matrix::random_normal(2, 4, 1.0, &mut rng);
+    let k = Matrix::random_normal(6, 4, 1.0, &mut rng);
+    let v = Matrix::random_normal(6, 4, 1.0, &mut rng);
+    for window in [0, 2, 5] {
+        let out = ScaledDotProductAttention::new(4)
+            .with_window(window)
+            .forward(&q, &k, &v, None)
+            .unwrap();
+        let expected = ScaledDotProductAttention::new(4)
+            .forward(&q, &k, &v, Some(&trailing_local_mask(2, 6, window)))
+            .unwrap();
+        assert_matrix_close(&out, &expected);
+    }
+}
+
+#[test]
+fn windowed_multi_head_attention_matches_a_local_causal_mask() {
+    let mut rng = Rng::seed_from_u64(13);
+    let full = MultiHeadAttention::new_with_rng(8, 2, &mut rng).unwrap();
+    let x = Matrix::random_normal(9, 8, 1.0, &mut rng);
+    let causal = create_causal_mask(9);
+    for window in [1, 3] {
+        let windowed = full.clone().with_window(window);
+        let explicit = combine_masks(&create_local_mask(9, window), &causal).unwrap();
+        let expected = full.forward(&x, &x, &x, Some(&explicit)).unwrap();
+        assert_matrix_close(
+            &windowed.forward(&x, &x, &x, Some(&causal)).unwrap(),
+            &expected,
+        );
+
+        // Decoding one position at a time through the key/value cache sees
+        // the same keys as the masked full pass.
+        let mut cache = KvCache::new(8);
+        for i in 0..x.rows() {
+            let row = x.rows_range(i, 1).unwrap();
+            windowed.extend_cache(&mut cache, &row, &row).unwrap();
+            let out = windowed.forward_cached(&row, &cache, None).unwrap();
+            assert_matrix_close(&out, &expected.rows_range(i, 1).unwrap());
+        }
+    }
+}
+
+#[test]
+fn alibi_slopes_follow_the_paper() {
+    // Powers of two: the geometric sequence 2^(-8h/n), h = 1..=n.
+    let two_pow = |e: f64| 2f64.powf(e);
+    assert_eq!(alibi_slopes(1), vec![two_pow(-8.0)]);
+    assert_eq!(alibi_slopes(2), vec![two_pow(-4.0), two_pow(-8.0)]);
+    assert_eq!(alibi_slopes(4), vec![0.25, 0.0625, 0.015625, 0.00390625]);
+    let eight: Vec<f64> = (1..=8).map(|h| two_pow(-(h as f64))).collect();
+    assert_eq!(alibi_slopes(8), eight);
+
+    // Otherwise the slopes of the power of two below, then every other
+    // slope of the power of two above: for 6 heads 4 + the 1st and 3rd of 8.
+    assert_eq!(
+        alibi_slopes(6),
+        vec![0.25, 0.0625, 0.015625, 0.00390625, 0.5, 0.125]
+    );
+    let mut twelve = eight.clone();
+    twelve.extend([-0.5, -1.5, -2.5, -3.5].map(two_pow));
+    let actual = alibi_slopes(12);
+    assert_eq!(actual.len(), 12);
+    for (a, e) in actual.iter().zip(&twelve) {
+        assert!((a - e).abs() < 1e-15, "{} vs {}", a, e);
+    }
+    assert!(alibi_slopes(0).is_empty());
+}
+
+/// Softmax of `scores[i][j] − slope·|i − j|` along each row.
+fn biased_softmax(scores: &[[f64; 3]; 3], slope: f64) -> Matrix {
+    Matrix::from_fn(3, 3, |i, j| {
+        let biased = |j: usize| (scores[i][j] - slope * i.abs_diff(j) as f64).exp();
+        biased(j) / (0..3).map(biased).sum::<f64>()
+    })
+}
+
+#[test]
+fn alibi_bias_is_added_to_the_scores_before_softmax() {
+    // One-dimensional keys and queries, so the scores are q_i · k_j.
+    let q = Matrix::from_fn(3, 1, |i, _| [0.5, -1.0, 2.0][i]);
+    let k = Matrix::from_fn(3, 1, |j, _| [1.0, 0.25, -0.5][j]);
+    let v = Matrix::identity(3);
+    let scores = [0, 1, 2].map(|i| [0, 1, 2].map(|j| q[(i, 0)] * k[(j, 0)]));
+    let (out, weights) = ScaledDotProductAttention::new(1)
+        .with_alibi_slope(0.75)
+        .forward_with_weights(&q, &k, &v, None)
+        .unwrap();
+    let expected = biased_softmax(&scores, 0.75);
+    assert_matrix_close(&weights, &expected);
+    assert_matrix_close(&out, &expected);
+
+    // A two-head toy whose queries are all zero: each head's weights are
+    // the softmax of its distance penalty alone.
+    let mut rng = Rng::seed_from_u64(14);
+    let mut attention = MultiHeadAttention::new_with_rng(4, 2, &mut rng)
+        .unwrap()
+        .with_alibi();
+    attention.w_q = Matrix::zeros(4, 4);
+    let x = Matrix::random_normal(3, 4, 1.0, &mut rng);
+    let (_, heads) = attention.forward_with_attentions(&x, &x, &x, None).unwrap();
+    assert_eq!(heads.len(), 2);
+    for (head, slope) in heads.iter().zip(alibi_slopes(2)) {
+        assert_matrix_close(head, &biased_softmax(&[[0.0; 3]; 3], slope));
+    }
+}
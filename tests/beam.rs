@@ -0,0 +1,168 @@
+mod common;
+
+use rust_transformer::generation::{mean_cross_attention, BeamScoring, FinishReason};
+use rust_transformer::utils::tensor_ops::log_softmax;
+use rust_transformer::{Transformer, TransformerConfig};
+
+fn model(config: TransformerConfig, seed: u64) -> Transformer {
+    let mut model = Transformer::with_seed(config, seed).unwrap();
+    model.set_training(false);
+    model
+}
+
+/// Summed log-probability of everything after the start token of `tgt`,
+/// recomputed from a full forward pass.
+fn sequence_log_prob(model: &Transformer, src: &[usize], tgt: &[usize]) -> f64 {
+    let logits = model.forward(src, &tgt[..tgt.len() - 1]).unwrap();
+    (1..tgt.len())
+        .map(|i| log_softmax(logits.row(i - 1))[tgt[i]])
+        .sum()
+}
+
+const SOURCES: [&[usize]; 3] = [&[4, 5, 6], &[9, 3, 12, 7, 8], &[15]];
+
+#[test]
+fn width_one_is_greedy_decoding() {
+    for seed in 0..4 {
+        let model = model(common::tiny_config(), seed);
+        for src in SOURCES {
+            let beams = model.generate_beam(src, 1, 0.0, 10).unwrap();
+            assert_eq!(beams.len(), 1);
+            assert_eq!(
+                beams[0].tokens,
+                model.generate_greedy(src, 10).unwrap(),
+                "seed {} source {:?}",
+                seed,
+                src
+            );
+        }
+    }
+}
+
+#[test]
+fn wider_beams_score_at_least_as_well_as_greedy() {
+    for seed in 0..4 {
+        let model = model(common::tiny_config(), seed);
+        for src in SOURCES {
+            let greedy = model.generate_greedy(src, 8).unwrap();
+            let greedy_log_prob = sequence_log_prob(&model, src, &greedy);
+            let best = &model.generate_beam(src, 4, 0.0, 8).unwrap()[0];
+            assert!(
+                (best.log_prob - sequence_log_prob(&model, src, &best.tokens)).abs() < 1e-9,
+                "reported log-probability disagrees with a forward pass"
+            );
+            assert!(
+                best.score >= greedy_log_prob - 1e-12,
+                "seed {} source {:?}: beam {} < greedy {}",
+                seed,
+                src,
+                best.score,
+                greedy_log_prob
+            );
+        }
+    }
+}
+
+#[test]
+fn cached_beams_match_full_recompute() {
+    for seed in 0..3 {
+        let cached = model(common::tiny_config(), seed);
+        // Any decoder hook makes every beam decode its whole prefix.
+        let mut recomputed = cached.clone();
+        recomputed
+            .decoder
+            .hooks
+            .register_forward("*", |_: &str, _: &_, _: &mut _| {});
+        for src in SOURCES {
+            let a = cached.generate_beam(src, 3, 0.6, 9).unwrap();
+            let b = recomputed.generate_beam(src, 3, 0.6, 9).unwrap();
+            assert_eq!(a.len(), b.len());
+            for (a, b) in a.iter().zip(&b) {
+                assert_eq!(a.tokens, b.tokens);
+                assert_eq!(a.finish_reason, b.finish_reason);
+                assert!((a.log_prob - b.log_prob).abs() < 1e-10);
+                assert!((a.score - b.score).abs() < 1e-10);
+            }
+        }
+    }
+}
+
+#[test]
+fn end_token_finalizes_a_hypothesis() {
+    let config = TransformerConfig {
+        output_bias: true,
+        ..common::tiny_config()
+    };
+    let mut model = model(config, 3);
+    let eos = model.config.eos_token_id;
+    // Make the end token by far the most likely continuation everywhere.
+    model.decoder.output_bias.as_mut().unwrap()[(0, eos)] = 20.0;
+
+    let beams = model.generate_beam(&[4, 5, 6], 3, 0.0, 8).unwrap();
+    assert_eq!(beams.len(), 3);
+    assert_eq!(beams[0].tokens, vec![model.config.bos_token_id, eos]);
+    for beam in &beams {
+        assert_eq!(beam.finish_reason, FinishReason::Stop);
+        assert_eq!(beam.tokens.last(), Some(&eos));
+        assert!(!beam.tokens[..beam.tokens.len() - 1].contains(&eos));
+    }
+
+    // Without the bias, whatever finishes early still ends at its end token.
+    let model = self::model(common::tiny_config(), 3);
+    for beam in model.generate_beam(&[4, 5, 6], 4, 0.0, 12).unwrap() {
+        let stopped = beam.finish_reason == FinishReason::Stop;
+        assert_eq!(stopped, beam.tokens.last() == Some(&eos));
+        assert!(!beam.tokens[..beam.tokens.len() - 1].contains(&eos));
+    }
+}
+
+#[test]
+fn coverage_penalty_prefers_hypotheses_that_cover_the_source() {
+    let model = model(common::tiny_config(), 8);
+    let src = [4, 5, 6, 7, 8, 9];
+    let eos = model.config.eos_token_id;
+
+    // Ranked by log-probability alone, stopping at once wins; its single
+    // decoder step cannot spread a total attention of one over six source
+    // positions.
+    let plain = model
+        .generate_beam_with_scoring(&src, 4, &BeamScoring::new(), 6)
+        .unwrap();
+    assert_eq!(plain[0].tokens, vec![model.config.bos_token_id, eos]);
+
+    let scoring = BeamScoring::new().with_coverage_penalty(2.0);
+    let covered = model
+        .generate_beam_with_scoring(&src, 4, &scoring, 6)
+        .unwrap();
+    assert_ne!(covered[0].tokens, plain[0].tokens);
+    assert!(covered[0].tokens.len() > plain[0].tokens.len());
+
+    // Every score is the length-normalized log-probability plus the
+    // coverage of the attention a full forward pass produces.
+    for beam in &covered {
+        let tgt = &beam.tokens[..beam.tokens.len() - 1];
+        let (_, attentions) = model.forward_with_attentions(&src, tgt).unwrap();
+        let attention = mean_cross_attention(&attentions.decoder).unwrap();
+        assert_eq!(attention.rows(), beam.tokens.len() - 1);
+        let expected = scoring.score(beam.log_prob, beam.tokens.len() - 1, Some(&attention));
+        assert!(
+            (beam.score - expected).abs() < 1e-9,
+            "{} vs {}",
+            beam.score,
+            expected
+        );
+    }
+
+    let mut recomputed = model.clone();
+    recomputed
+        .decoder
+        .hooks
+        .register_forward("*", |_: &str, _: &_, _: &mut _| {});
+    let full = recomputed
+        .generate_beam_with_scoring(&src, 4, &scoring, 6)
+        .unwrap();
+    for (a, b) in covered.iter().zip(&full) {
+        assert_eq!(a.tokens, b.tokens);
+        assert!((a.score - b.score).abs() < 1e-10);
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::{assert_gradient_close, finite_difference, tiny_config};
+use rust_transformer::models::{Pooling, SequenceClassifier, TokenClassifier};
+use rust_transformer::optim::{Adam, AdamConfig};
+use rust_transformer::training::{
+    cross_entropy, masked_cross_entropy, sequence_classification_loss, token_classification_loss,
+    CrossEntropyConfig, Trainer, TrainerConfig,
+};
+
+fn loss_of(model: &SequenceClassifier, tokens: &[usize], label: usize) -> f64 {
+    let logits = model.logits(tokens).unwrap();
+    cross_entropy(&logits, &[label], &CrossEntropyConfig::default())
+        .unwrap()
+        .loss
+}
+
+#[test]
+fn sequence_classifier_gradients_match_finite_differences() {
+    for pooling in [
+        Pooling::Cls,
+        Pooling::Mean,
+        Pooling::Max,
+        Pooling::LastToken,
+    ] {
+        let mut model = SequenceClassifier::with_seed(tiny_config(), 3, 5).unwrap();
+        model.backbone.pooling = pooling;
+        model.set_training(false);
+        let tokens = [4, 7, 2, 9, 0];
+
+        let (logits, cache) = model.forward_with_cache(&tokens).unwrap();
+        let scored = cross_entropy(&logits, &[1], &CrossEntropyConfig::default()).unwrap();
+        let grads = model.backward(&cache, &scored.d_logits).unwrap();
+
+        for name in ["classifier.weight", "encoder.layers.0.feed_forward.w1"] {
+            let numeric = finite_difference(&model, name, (1, 1), |m| loss_of(m, &tokens, 1));
+            assert_gradient_close(
+                &format!("{:?} {}", pooling, name),
+                grads.get(name).unwrap()[(1, 1)],
+                numeric,
+            );
+        }
+    }
+}
+
+#[test]
+fn fine_tuning_separates_two_classes() {
+    let mut model = SequenceClassifier::with_seed(tiny_config(), 2, 1).unwrap();
+    model.set_training(false);
+    let data: Vec<(Vec<usize>, usize)> = vec![
+        (vec![2, 3, 4], 0),
+        (vec![3, 2, 4, 2], 0),
+        (vec![8, 9, 10], 1),
+        (vec![9, 10, 8, 11], 1),
+    ];
+    let optimizer = Adam::new(AdamConfig {
+        lr: 1e-2,
+        ..AdamConfig::default()
+    });
+    let mut trainer = Trainer::new(
+        model,
+        optimizer,
+        TrainerConfig::new().with_epochs(40).with_batch_size(4),
+    );
+    let loss = CrossEntropyConfig::default();
+    let history = trainer
+        .fit(&data, |m, batch| {
+            sequence_classification_loss(m, batch, &loss)
+        })
+        .unwrap();
+    assert!(history.last().unwrap().mean_loss < history[0].mean_loss);
+
+    let model = trainer.into_model();
+    for (tokens, label) in &data {
+        assert_eq!(model.predict(tokens).unwrap(), *label);
+    }
+}
+
+#[test]
+fn token_classifier_loss_skips_padding_and_matches_finite_differences() {
+    let mut model = TokenClassifier::with_seed(tiny_config(), 4, 9).unwrap();
+    model.set_training(false);
+    let tokens = vec![5, 3, 8, 0, 0];
+    let tags = vec![1, 0, 3, 2, 2];
+    let loss = CrossEntropyConfig::default();
+
+    let (logits, _) = model.forward_with_cache(&tokens).unwrap();
+    let scored = masked_cross_entropy(&logits, &tags, &model.loss_mask(&tokens), &loss).unwrap();
+    assert_eq!(scored.tokens, 3);
+    assert!(scored.d_logits.row(3).iter().all(|&g| g == 0.0));
+
+    let batch = [(tokens, tags)];
+    let (_, grads) = token_classification_loss(&model, &batch, &loss).unwrap();
+    let name = "encoder.layers.0.self_attention.w_q";
+    let numeric = finite_difference(&model, name, (2, 3), |m| {
+        token_classification_loss(m, &batch, &loss).unwrap().0
+    });
+    assert_gradient_close(name, grads.get(name).unwrap()[(2, 3)], numeric);
+}
@@ -0,0 +1,57 @@
+//! Fixtures shared by the integration tests.
+
+// Each test crate uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use rust_transformer::{Parameters, TransformerConfig};
+
+/// A one-layer, eight-wide model configuration without dropout, small
+/// enough for finite-difference checks.
+pub fn tiny_config() -> TransformerConfig {
+    TransformerConfig {
+        vocab_size: 16,
+        d_model: 8,
+        num_heads: 2,
+        num_encoder_layers: 1,
+        num_decoder_layers: 1,
+        d_ff: 16,
+        max_seq_len: 16,
+        dropout: 0.0,
+        ..TransformerConfig::default()
+    }
+}
+
+/// Central-difference estimate of `∂loss/∂w[index]` for the weight `name`
+/// of `model`.
+pub fn finite_difference<M, F>(model: &M, name: &str, index: (usize, usize), loss: F) -> f64
+where
+    M: Parameters + Clone,
+    F: Fn(&M) -> f64,
+{
+    let eps = 1e-6;
+    let shifted = |delta: f64| {
+        let mut m = model.clone();
+        let mut found = false;
+        m.visit_parameters_mut("", &mut |n, w| {
+            if n == name {
+                w[index] += delta;
+                found = true;
+            }
+        });
+        assert!(found, "no parameter named {}", name);
+        loss(&m)
+    };
+    (shifted(eps) - shifted(-eps)) / (2.0 * eps)
+}
+
+/// Asserts that an analytic gradient matches its finite-difference estimate.
+pub fn assert_gradient_close(what: &str, analytic: f64, numeric: f64) {
+    let scale = analytic.abs().max(numeric.abs()).max(1.0);
+    assert!(
+        (analytic - numeric).abs() / scale < 1e-6,
+        "{}: analytic {} vs numeric {}",
+        what,
+        analytic,
+        numeric
+    );
+}
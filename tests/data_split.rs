@@ -0,0 +1,83 @@
+use rust_transformer::data::{Dataset, SplitRatios, Subset};
+
+fn sorted(subsets: &[&Subset<'_, Vec<usize>>]) -> Vec<usize> {
+    let mut all: Vec<usize> = subsets.iter().flat_map(|s| s.indices().to_vec()).collect();
+    all.sort_unstable();
+    all
+}
+
+#[test]
+fn split_is_seeded_and_partitions_the_dataset() {
+    let data: Vec<usize> = (0..53).collect();
+    let ratios = SplitRatios::default();
+
+    let a = data.split(ratios, 7).unwrap();
+    let b = data.split(ratios, 7).unwrap();
+    let c = data.split(ratios, 8).unwrap();
+    assert_eq!(a.train.indices(), b.train.indices());
+    assert_eq!(a.validation.indices(), b.validation.indices());
+    assert_ne!(a.train.indices(), c.train.indices());
+
+    // Disjoint and complete: every index appears exactly once.
+    assert_eq!(
+        sorted(&[&a.train, &a.validation, &a.test]),
+        (0..53).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn split_sizes_round_down_and_test_takes_the_rest() {
+    let data: Vec<usize> = (0..53).collect();
+    let split = data.split(SplitRatios::default(), 1).unwrap();
+    // 53 · 0.8 = 42.4 and 53 · 0.1 = 5.3.
+    assert_eq!(split.train.len(), 42);
+    assert_eq!(split.validation.len(), 5);
+    assert_eq!(split.test.len(), 6);
+
+    let split = data
+        .split(SplitRatios::new(1.0, 0.0, 0.0).unwrap(), 1)
+        .unwrap();
+    assert_eq!(split.train.len(), 53);
+    assert!(split.validation.is_empty() && split.test.is_empty());
+}
+
+#[test]
+fn split_by_key_is_stable_under_growth() {
+    let small: Vec<usize> = (0..40).collect();
+    let large: Vec<usize> = (0..80).collect();
+    let key = |x: &usize| x.to_le_bytes();
+    let ratios = SplitRatios::new(0.5, 0.25, 0.25).unwrap();
+
+    let a = small.split_by_key(ratios, 3, key).unwrap();
+    let b = large.split_by_key(ratios, 3, key).unwrap();
+    assert_eq!(
+        sorted(&[&a.train, &a.validation, &a.test]),
+        (0..40).collect::<Vec<_>>()
+    );
+    let head: Vec<usize> = b
+        .train
+        .indices()
+        .iter()
+        .copied()
+        .filter(|&i| i < 40)
+        .collect();
+    assert_eq!(a.train.indices(), head.as_slice());
+}
+
+#[test]
+fn split_rejects_invalid_ratios() {
+    let data: Vec<usize> = (0..10).collect();
+    let ratios = SplitRatios {
+        train: 0.9,
+        validation: 0.2,
+        test: 0.0,
+    };
+    assert!(data.split(ratios, 0).is_err());
+    assert!(data.split_by_key(ratios, 0, |x| x.to_le_bytes()).is_err());
+    let negative = SplitRatios {
+        train: 1.2,
+        validation: -0.2,
+        test: 0.0,
+    };
+    assert!(data.split(negative, 0).is_err());
+}
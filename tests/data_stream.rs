@@ -0,0 +1,74 @@
+use std::io::{Cursor, Read};
+
+use rust_transformer::data::{CorpusReader, DocumentSplit, StreamLoader};
+use rust_transformer::utils::gzip::GzipReader;
+
+const TEXT: &str =
+    "the cat sat on the mat\n\nthe dog ran after the cat\nand the cat ran up the tree\n\n";
+
+/// `TEXT` repeated three times, compressed by `gzip -9`.
+const GZIPPED: [u8; 80] = [
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x2b, 0xc9, 0x48, 0x55, 0x48, 0x4e,
+    0x2c, 0x51, 0x28, 0x06, 0xe2, 0xfc, 0x3c, 0x85, 0x12, 0x20, 0x37, 0x37, 0xb1, 0x84, 0x8b, 0x0b,
+    0xc4, 0x48, 0xc9, 0x4f, 0x57, 0x28, 0x4a, 0xcc, 0x53, 0x48, 0x4c, 0x2b, 0x49, 0x2d, 0x02, 0x4b,
+    0x01, 0x55, 0x72, 0x25, 0xe6, 0xa5, 0xc0, 0xd8, 0x60, 0xd9, 0xd2, 0x02, 0x30, 0xb7, 0xa4, 0x28,
+    0x35, 0x15, 0xa2, 0x6d, 0xf0, 0x1a, 0x07, 0x00, 0x9d, 0x30, 0xa8, 0x63, 0xed, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn gzip_reader_inflates_and_checks_the_trailer() {
+    let mut text = String::new();
+    GzipReader::new(Cursor::new(GZIPPED.to_vec()))
+        .read_to_string(&mut text)
+        .unwrap();
+    assert_eq!(text, TEXT.repeat(3));
+
+    let mut corrupt = GZIPPED.to_vec();
+    corrupt[72] ^= 1;
+    let mut sink = Vec::new();
+    assert!(GzipReader::new(Cursor::new(corrupt))
+        .read_to_end(&mut sink)
+        .is_err());
+}
+
+#[test]
+fn corpus_reader_splits_lines_or_paragraphs() {
+    let lines: Vec<String> = CorpusReader::new(Cursor::new(TEXT))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(lines.len(), 3);
+
+    let paragraphs: Vec<String> = CorpusReader::new(GzipReader::new(Cursor::new(GZIPPED.to_vec())))
+        .with_split(DocumentSplit::BlankLine)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(paragraphs.len(), 6);
+    assert_eq!(
+        paragraphs[1],
+        "the dog ran after the cat\nand the cat ran up the tree"
+    );
+}
+
+#[test]
+fn stream_loader_pads_every_sequence_exactly_once() {
+    let sequences: Vec<Vec<usize>> = (1..=10).map(|n| vec![n; n]).collect();
+    let batches: Vec<_> = StreamLoader::new(sequences.into_iter().map(Ok), 4, 0)
+        .with_shuffle_buffer(5, 7)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+        [4, 4, 2]
+    );
+
+    let mut seen: Vec<usize> = Vec::new();
+    for batch in &batches {
+        for (row, &len) in batch.tokens.iter().zip(&batch.lengths) {
+            assert_eq!(row.len(), batch.max_len());
+            assert!(row[len..].iter().all(|&t| t == 0));
+            seen.push(row[0]);
+        }
+    }
+    seen.sort_unstable();
+    assert_eq!(seen, (1..=10).collect::<Vec<_>>());
+}
@@ -0,0 +1,66 @@
+use rust_transformer::models::{EmbedConfig, Pooling};
+use rust_transformer::utils::similarity::norm;
+use rust_transformer::{Transformer, TransformerConfig};
+
+fn model() -> Transformer {
+    let config = TransformerConfig {
+        vocab_size: 20,
+        d_model: 8,
+        num_heads: 2,
+        d_ff: 16,
+        max_seq_len: 16,
+        ..TransformerConfig::default()
+    };
+    let mut model = Transformer::with_seed(config, 3).unwrap();
+    model.set_training(false);
+    model
+}
+
+#[test]
+fn embed_pools_non_padding_positions() {
+    let model = model();
+    let tokens = [5, 9, 11, 0, 0];
+    let hidden = model.encode(&tokens).unwrap();
+
+    assert_eq!(
+        model.embed(&tokens).unwrap(),
+        model.mean_pooled_encoding(&tokens).unwrap().into_vec()
+    );
+    let cls = EmbedConfig::new().with_pooling(Pooling::Cls);
+    assert_eq!(
+        model.embed_with_config(&tokens, &cls).unwrap(),
+        hidden.row(0)
+    );
+    let last = EmbedConfig::new().with_pooling(Pooling::LastToken);
+    assert_eq!(
+        model.embed_with_config(&tokens, &last).unwrap(),
+        hidden.row(2)
+    );
+
+    let max = EmbedConfig::new().with_pooling(Pooling::Max);
+    let pooled = model.embed_with_config(&tokens, &max).unwrap();
+    for (j, &value) in pooled.iter().enumerate() {
+        let expected = (0..3)
+            .map(|i| hidden[(i, j)])
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(value, expected);
+    }
+}
+
+#[test]
+fn normalized_embeddings_have_unit_length() {
+    let model = model();
+    for pooling in [
+        Pooling::Cls,
+        Pooling::Mean,
+        Pooling::Max,
+        Pooling::LastToken,
+    ] {
+        let config = EmbedConfig::new()
+            .with_pooling(pooling)
+            .with_normalize(true);
+        let embedding = model.embed_with_config(&[4, 7, 13], &config).unwrap();
+        assert_eq!(embedding.len(), 8);
+        assert!((norm(&embedding) - 1.0).abs() < 1e-12);
+    }
+}
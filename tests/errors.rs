@@ -0,0 +1,188 @@
+mod common;
+
+use rust_transformer::data::SplitRatios;
+use rust_transformer::generation::{rerank, GenerationConfig, RerankBy, Truncation};
+use rust_transformer::models::ChunkingConfig;
+use rust_transformer::training::{cross_entropy, masked_cross_entropy, CrossEntropyConfig};
+use rust_transformer::utils::tensor_ops::one_hot;
+use rust_transformer::{Matrix, Transformer, TransformerError};
+
+fn model() -> Transformer {
+    let mut model = Transformer::with_seed(common::tiny_config(), 0).unwrap();
+    model.set_training(false);
+    model
+}
+
+#[test]
+fn loss_reports_shapes_targets_and_smoothing() {
+    let logits = Matrix::zeros(3, 5);
+    let config = CrossEntropyConfig::new();
+
+    let err = cross_entropy(&logits, &[0, 1], &config).unwrap_err();
+    assert!(
+        matches!(err, TransformerError::ShapeMismatch(_)),
+        "{:?}",
+        err
+    );
+    let err = masked_cross_entropy(&logits, &[0, 1, 2], &[true], &config).unwrap_err();
+    assert!(
+        matches!(err, TransformerError::ShapeMismatch(_)),
+        "{:?}",
+        err
+    );
+
+    let err = cross_entropy(&logits, &[0, 7, 1], &config).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            TransformerError::VocabOverflow {
+                token: 7,
+                vocab_size: 5
+            }
+        ),
+        "{:?}",
+        err
+    );
+    // Ignored positions may hold any id.
+    let ignoring = CrossEntropyConfig::new().with_ignore_index(7);
+    assert!(cross_entropy(&logits, &[0, 7, 1], &ignoring).is_ok());
+
+    let smoothing = CrossEntropyConfig::new().with_label_smoothing(1.0);
+    let err = cross_entropy(&logits, &[0, 1, 2], &smoothing).unwrap_err();
+    assert!(
+        matches!(err, TransformerError::InvalidConfig(_)),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn one_hot_and_rerank_report_vocab_overflow() {
+    let err = one_hot(&[1, 4], 4).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            TransformerError::VocabOverflow {
+                token: 4,
+                vocab_size: 4
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let model = model();
+    let candidates = vec![vec![1, 5, 2], vec![1, 16, 2]];
+    let err = rerank(&model, &[4, 5], &candidates, RerankBy::LogProb).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            TransformerError::VocabOverflow {
+                token: 16,
+                vocab_size: 16
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn over_long_sequences_are_shape_mismatches() {
+    let model = model();
+    let long: Vec<usize> = (0..17).map(|i| 3 + i % 10).collect();
+    let err = model.encode(&long).unwrap_err();
+    assert!(
+        matches!(err, TransformerError::ShapeMismatch(_)),
+        "{:?}",
+        err
+    );
+
+    let refuse = GenerationConfig::new().with_truncation(Truncation::Error);
+    let err = refuse.truncate(&long, 16).unwrap_err();
+    assert!(
+        matches!(err, TransformerError::ShapeMismatch(_)),
+        "{:?}",
+        err
+    );
+    assert_eq!(
+        GenerationConfig::new().truncate(&long, 16).unwrap().len(),
+        16
+    );
+}
+
+#[test]
+fn decoder_prefixes_are_validated() {
+    let empty = GenerationConfig::new().with_decoder_prefix(Vec::new());
+    let err = empty.prefix(1, 16, 16).unwrap_err();
+    assert!(
+        matches!(err, TransformerError::InvalidConfig(_)),
+        "{:?}",
+        err
+    );
+
+    let long = GenerationConfig::new().with_decoder_prefix(vec![1; 17]);
+    let err = long.prefix(1, 16, 16).unwrap_err();
+    assert!(
+        matches!(err, TransformerError::ShapeMismatch(_)),
+        "{:?}",
+        err
+    );
+
+    let unknown = GenerationConfig::new().with_decoder_prefix(vec![1, 20]);
+    let err = unknown.prefix(1, 16, 16).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            TransformerError::VocabOverflow {
+                token: 20,
+                vocab_size: 16
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn kv_cache_ahead_of_the_target_is_a_shape_mismatch() {
+    let model = model();
+    let src = [4, 5, 6];
+    let memory = model.encode(&src).unwrap();
+    let mut cache = model.decoder.start_kv_cache(&memory).unwrap();
+    model
+        .decode_incremental(&[1, 7, 8], &src, &mut cache)
+        .unwrap();
+    let err = model
+        .decode_incremental(&[1, 7], &src, &mut cache)
+        .unwrap_err();
+    assert!(
+        matches!(err, TransformerError::ShapeMismatch(_)),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn invalid_settings_are_config_errors() {
+    for err in [
+        SplitRatios::new(0.5, -0.1, 0.6).unwrap_err(),
+        SplitRatios::new(0.5, 0.2, 0.2).unwrap_err(),
+        ChunkingConfig::new()
+            .with_window(0)
+            .windows(10, 16)
+            .unwrap_err(),
+        ChunkingConfig::new()
+            .with_window(4)
+            .with_overlap(4)
+            .windows(10, 16)
+            .unwrap_err(),
+        model().generate_beam(&[4, 5], 0, 0.0, 8).unwrap_err(),
+    ] {
+        assert!(
+            matches!(err, TransformerError::InvalidConfig(_)),
+            "{:?}",
+            err
+        );
+    }
+}
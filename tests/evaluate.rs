@@ -0,0 +1,101 @@
+use rust_transformer::evaluate::{
+    evaluate_generation, rouge_l, rouge_n, sentence_bleu, BleuConfig, Smoothing,
+};
+
+fn words(text: &str) -> Vec<&str> {
+    text.split_whitespace().collect()
+}
+
+fn bleu(candidate: &str, reference: &str, max_order: usize) -> f64 {
+    let config = BleuConfig {
+        max_order,
+        smoothing: Smoothing::None,
+    };
+    sentence_bleu(&words(candidate), &words(reference), config)
+        .unwrap()
+        .score
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-12,
+        "{} vs {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn bleu_of_a_perfect_match_is_one() {
+    let sentence = "the quick brown fox jumps over the lazy dog";
+    assert_close(bleu(sentence, sentence, 4), 1.0);
+}
+
+#[test]
+fn bleu_matches_hand_computed_values() {
+    // Papineni et al.: the modified unigram precision clips "the" at its
+    // two occurrences in the reference.
+    assert_close(
+        bleu("the the the the the the the", "the cat is on the mat", 1),
+        2.0 / 7.0,
+    );
+
+    // p1 = 5/6, p2 = 3/5 (the cat, on the, the mat), no brevity penalty.
+    let reference = "the cat is on the mat";
+    assert_close(
+        bleu("the cat sat on the mat", reference, 2),
+        (5.0f64 / 6.0 * 3.0 / 5.0).sqrt(),
+    );
+    // No 4-gram matches, so unsmoothed BLEU-4 is zero.
+    assert_eq!(bleu("the cat sat on the mat", reference, 4), 0.0);
+
+    // Perfect precisions, brevity penalty exp(1 − 6/2).
+    assert_close(bleu("the cat", reference, 2), (-2.0f64).exp());
+}
+
+#[test]
+fn rouge_matches_hand_computed_values() {
+    let reference = words("the cat is on the mat");
+    let candidate = words("the cat sat on the mat");
+    assert_eq!(rouge_n(&reference, &reference, 2).f1, 1.0);
+
+    let rouge1 = rouge_n(&candidate, &reference, 1);
+    assert_close(rouge1.precision, 5.0 / 6.0);
+    assert_close(rouge1.recall, 5.0 / 6.0);
+    assert_close(rouge_n(&candidate, &reference, 2).f1, 3.0 / 5.0);
+
+    // Lin (2004): the LCS "police the gunman" covers three of four words.
+    let lcs = rouge_l(
+        &words("police kill the gunman"),
+        &words("police killed the gunman"),
+    );
+    assert_close(lcs.precision, 0.75);
+    assert_close(lcs.recall, 0.75);
+    let shorter = rouge_l(
+        &words("police the gunman"),
+        &words("police killed the gunman"),
+    );
+    assert_close(shorter.precision, 1.0);
+    assert_close(shorter.f1, 2.0 * 0.75 / 1.75);
+}
+
+#[test]
+fn evaluate_generation_scores_hypotheses_as_returned() {
+    let data: Vec<(Vec<usize>, Vec<usize>)> = vec![
+        (vec![4, 5, 6, 7], vec![4, 5, 6, 7]),
+        (vec![8, 9, 10, 11, 12], vec![8, 9, 10, 11, 12]),
+    ];
+    let copied = evaluate_generation(&data, |src| Ok(src.to_vec()), BleuConfig::default()).unwrap();
+    assert_close(copied.bleu.score, 1.0);
+    assert_close(copied.rouge_l.f1, 1.0);
+
+    // Start and end tokens left in the output are scored as extra tokens.
+    let wrapped = evaluate_generation(
+        &data,
+        |src| Ok([&[1], src, &[2]].concat()),
+        BleuConfig::default(),
+    )
+    .unwrap();
+    assert!(wrapped.bleu.score < 1.0);
+    assert_eq!(wrapped.hypotheses[0], vec![1, 4, 5, 6, 7, 2]);
+}
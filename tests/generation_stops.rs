@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use rust_transformer::cancellation::CancellationToken;
+use rust_transformer::generation::{BadWords, FinishReason, GenerationConfig, GenerationOutput};
+use rust_transformer::tokenizer::VocabTokenizer;
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::{Transformer, TransformerConfig};
+
+const EOS: usize = 2;
+const SRC: [usize; 4] = [4, 7, 5, 9];
+
+fn model() -> Transformer {
+    let config = TransformerConfig {
+        vocab_size: 12,
+        d_model: 8,
+        num_heads: 2,
+        d_ff: 16,
+        max_seq_len: 16,
+        ..TransformerConfig::default()
+    };
+    let mut model = Transformer::with_seed(config, 17).unwrap();
+    model.set_training(false);
+    model
+}
+
+fn tokenizer() -> VocabTokenizer {
+    let tokens = [
+        "<pad>", "<s>", "</s>", "<unk>", "a", "b", "c", "d", "e", "f", "g", "h",
+    ];
+    VocabTokenizer::new(tokens.iter().map(|t| t.to_string()).collect()).unwrap()
+}
+
+fn bad_words(phrases: &[&str]) -> BadWords {
+    BadWords::new(&tokenizer(), phrases).unwrap()
+}
+
+fn greedy(model: &Transformer, generation: GenerationConfig) -> GenerationOutput {
+    model
+        .generate_output(&SRC, &generation.with_sampling(false))
+        .unwrap()
+}
+
+#[test]
+fn cancellation_and_deadline_stop_before_the_first_step() {
+    let model = model();
+    let token = CancellationToken::new();
+    token.cancel();
+    let output = greedy(&model, GenerationConfig::new().with_cancellation(token));
+    assert_eq!(output.finish_reason, FinishReason::Cancelled);
+    assert_eq!(output.tokens, vec![model.config.bos_token_id]);
+
+    let output = greedy(
+        &model,
+        GenerationConfig::new().with_time_limit(Duration::ZERO),
+    );
+    assert_eq!(output.finish_reason, FinishReason::Deadline);
+    assert_eq!(output.new_tokens, 0);
+}
+
+#[test]
+fn end_token_stops_generation_and_is_kept() {
+    let model = model();
+    // Ban the text of every token but the end token.
+    let all_but_eos = bad_words(&[
+        "<pad>", "<s>", "<unk>", "a", "b", "c", "d", "e", "f", "g", "h",
+    ]);
+    let output = greedy(
+        &model,
+        GenerationConfig::new().with_bad_words(all_but_eos.clone()),
+    );
+    assert_eq!(output.finish_reason, FinishReason::Stop);
+    assert_eq!(output.tokens, vec![model.config.bos_token_id, EOS]);
+
+    let mut rng = Rng::seed_from_u64(3);
+    let sampled = model
+        .generate_output_rng(
+            &SRC,
+            &GenerationConfig::new().with_bad_words(all_but_eos),
+            &mut rng,
+        )
+        .unwrap();
+    assert_eq!(sampled.tokens, vec![model.config.bos_token_id, EOS]);
+}
+
+#[test]
+fn bad_words_never_appear_in_sampled_output() {
+    let model = model();
+    let generation = GenerationConfig::new()
+        .with_max_new_tokens(12)
+        .with_bad_words(bad_words(&["</s>", "a", "c"]));
+    for seed in 0..8 {
+        let mut rng = Rng::seed_from_u64(seed);
+        let output = model
+            .generate_output_rng(&SRC, &generation, &mut rng)
+            .unwrap();
+        assert_eq!(output.finish_reason, FinishReason::Length);
+        assert_eq!(output.new_tokens, 12);
+        assert!(output.tokens[1..]
+            .iter()
+            .all(|&t| t != EOS && t != 4 && t != 6));
+
+        let mut again = Rng::seed_from_u64(seed);
+        let repeated = model
+            .generate_output_rng(&SRC, &generation, &mut again)
+            .unwrap();
+        assert_eq!(repeated.tokens, output.tokens);
+    }
+}
+
+#[test]
+fn logprob_budget_stops_after_the_crossing_token() {
+    let model = model();
+    let no_eos = GenerationConfig::new().with_bad_words(bad_words(&["</s>"]));
+    let cumulative: Vec<f64> = (1..=4)
+        .map(|n| greedy(&model, no_eos.clone().with_max_new_tokens(n)).log_prob)
+        .collect();
+    assert!(cumulative.windows(2).all(|w| w[1] < w[0]));
+
+    // Allow a drop between the second and third token's totals.
+    let drop = -(cumulative[1] + cumulative[2]) / 2.0;
+    let output = greedy(
+        &model,
+        no_eos
+            .clone()
+            .with_max_new_tokens(8)
+            .with_max_logprob_drop(drop),
+    );
+    assert_eq!(output.finish_reason, FinishReason::LogProbBudget);
+    assert_eq!(output.new_tokens, 3);
+    assert!((output.log_prob - cumulative[2]).abs() < 1e-12);
+
+    let output = greedy(
+        &model,
+        no_eos.with_max_new_tokens(2).with_max_logprob_drop(drop),
+    );
+    assert_eq!(output.finish_reason, FinishReason::Length);
+}
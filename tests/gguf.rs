@@ -0,0 +1,195 @@
+use rust_transformer::gguf::{export_gguf, load_gguf, to_gguf, GgufFile, GgufTensor, GgufValue};
+use rust_transformer::models::ModelMetadata;
+use rust_transformer::quantization::GgmlType;
+use rust_transformer::{Matrix, Parameters, Transformer, TransformerConfig};
+
+#[test]
+fn file_round_trips_metadata_and_every_tensor_type() {
+    let mut file = GgufFile::new();
+    file.set("general.alignment", GgufValue::U32(64));
+    let values = [
+        GgufValue::U8(200),
+        GgufValue::I8(-100),
+        GgufValue::U16(60_000),
+        GgufValue::I16(-30_000),
+        GgufValue::U32(4_000_000_000),
+        GgufValue::I32(-2_000_000_000),
+        GgufValue::F32(0.5),
+        GgufValue::Bool(true),
+        GgufValue::String("héllo".to_string()),
+        GgufValue::Array(vec![GgufValue::U32(1), GgufValue::U32(2)]),
+        GgufValue::Array(vec![GgufValue::from("a"), GgufValue::from("bc")]),
+        GgufValue::U64(1 << 40),
+        GgufValue::I64(-(1 << 40)),
+        GgufValue::F64(-0.1),
+    ];
+    for (i, value) in values.iter().enumerate() {
+        file.set(format!("test.value{}", i), value.clone());
+    }
+
+    // Values each type represents exactly: Q8_0 blocks with max |x| = 127
+    // and Q4_0 blocks with max |x| = -8 have a scale of one.
+    let f32_values = Matrix::from_fn(2, 3, |i, j| (i * 3 + j) as f64 * 0.1 - 0.2);
+    let f16_values = Matrix::row_vector(vec![1.0, -0.5, 0.25, 2048.0]);
+    let q8_values = Matrix::from_fn(2, 32, |i, j| {
+        if j == 0 {
+            127.0
+        } else {
+            (i as f64 * 32.0 + j as f64) - 40.0
+        }
+    });
+    let q4_values = Matrix::from_fn(1, 64, |_, j| (j % 16) as f64 - 8.0);
+    let tensors = [
+        ("f32", &f32_values, GgmlType::F32),
+        ("f16", &f16_values, GgmlType::F16),
+        ("q8_0", &q8_values, GgmlType::Q8_0),
+        ("q4_0", &q4_values, GgmlType::Q4_0),
+    ];
+    for (name, matrix, ty) in tensors {
+        file.tensors
+            .push(GgufTensor::from_matrix(name, matrix, ty).unwrap());
+    }
+
+    let bytes = file.to_bytes().unwrap();
+    let parsed = GgufFile::parse(&bytes).unwrap();
+    assert_eq!(parsed, file);
+    assert_eq!(parsed.alignment(), 64);
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(parsed.get(&format!("test.value{}", i)), Some(value));
+    }
+
+    assert_eq!(parsed.tensor("f32").unwrap().dims, [3, 2]);
+    assert_eq!(parsed.tensor("f16").unwrap().dims, [4]);
+    // 2 + 32 bytes per Q8_0 block, 2 + 16 per Q4_0 block.
+    assert_eq!(parsed.tensor("q8_0").unwrap().data.len(), 2 * 34);
+    assert_eq!(parsed.tensor("q4_0").unwrap().data.len(), 2 * 18);
+    for (name, matrix, _) in tensors {
+        let decoded = parsed.tensor(name).unwrap().to_matrix().unwrap();
+        let expected = matrix.map(|&v| v as f32 as f64);
+        assert_eq!(decoded, expected, "{}", name);
+    }
+}
+
+#[test]
+fn parse_rejects_bad_files() {
+    let mut file = GgufFile::new();
+    let matrix = Matrix::from_fn(2, 32, |i, j| (i + j) as f64);
+    file.tensors
+        .push(GgufTensor::from_matrix("w", &matrix, GgmlType::Q8_0).unwrap());
+    let bytes = file.to_bytes().unwrap();
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert!(GgufFile::parse(&bad_magic).is_err());
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 9;
+    assert!(GgufFile::parse(&bad_version).is_err());
+    // The data ends with up to 31 bytes of alignment padding.
+    let err = GgufFile::parse(&bytes[..bytes.len() - 32]).unwrap_err();
+    assert!(
+        err.to_string().contains("truncated data for tensor 'w'"),
+        "{}",
+        err
+    );
+}
+
+fn model() -> Transformer {
+    // Rows of 32 fill whole quantization blocks.
+    let config = TransformerConfig {
+        vocab_size: 16,
+        d_model: 32,
+        num_heads: 2,
+        num_encoder_layers: 1,
+        num_decoder_layers: 1,
+        d_ff: 64,
+        max_seq_len: 16,
+        attention_bias: true,
+        ..TransformerConfig::default()
+    };
+    let mut metadata = ModelMetadata {
+        license: Some("MIT".to_string()),
+        git_commit: Some("abc123".to_string()),
+        ..ModelMetadata::default()
+    };
+    metadata.eval_scores.insert("bleu".to_string(), 0.5);
+    metadata
+        .extra
+        .insert("dataset".to_string(), "toy".to_string());
+    let mut model = Transformer::with_seed(config, 11)
+        .unwrap()
+        .with_metadata(metadata);
+    model.set_training(false);
+    model
+}
+
+#[test]
+fn export_and_load_keep_config_metadata_and_weights() {
+    let model = model();
+    // Largest error relative to a tensor's largest magnitude.
+    let cases = [
+        (GgmlType::F32, 1e-7),
+        (GgmlType::F16, 1e-3),
+        (GgmlType::Q8_0, 1.0 / 127.0),
+        (GgmlType::Q4_0, 1.0 / 7.0),
+    ];
+    for (ty, tolerance) in cases {
+        let file = to_gguf(&model, "toy", ty).unwrap();
+        let mut quantized = 0;
+        model.visit_parameters("", &mut |name, param| {
+            let expected = if param.rows() > 1 && param.cols() % ty.block_len() == 0 {
+                quantized += 1;
+                ty
+            } else {
+                GgmlType::F32
+            };
+            assert_eq!(file.tensor(name).unwrap().ggml_type, expected, "{}", name);
+        });
+        assert!(quantized > 0);
+
+        let path = std::env::temp_dir().join(format!(
+            "gguf-round-trip-{}-{}.gguf",
+            ty.name(),
+            std::process::id()
+        ));
+        export_gguf(&model, &path, ty).unwrap();
+        let mut loaded = load_gguf(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        loaded.set_training(false);
+
+        assert_eq!(loaded.config(), model.config());
+        assert_eq!(loaded.metadata(), model.metadata());
+        let mut restored = Vec::new();
+        loaded.visit_parameters("", &mut |name, m| {
+            restored.push((name.to_string(), m.clone()))
+        });
+        let mut count = 0;
+        model.visit_parameters("", &mut |name, original| {
+            let (restored_name, restored) = &restored[count];
+            count += 1;
+            assert_eq!(restored_name, name);
+            let scale = original
+                .as_slice()
+                .iter()
+                .fold(0.0f64, |m, v| m.max(v.abs()));
+            for (a, b) in original.as_slice().iter().zip(restored.as_slice()) {
+                assert!(
+                    (a - b).abs() <= tolerance * scale,
+                    "{} {}: {} vs {}",
+                    ty.name(),
+                    name,
+                    a,
+                    b
+                );
+            }
+        });
+        assert_eq!(count, restored.len());
+
+        if ty == GgmlType::F32 {
+            let (src, tgt) = ([1, 5, 9, 2], [3, 7]);
+            let expected = model.forward(&src, &tgt).unwrap();
+            let actual = loaded.forward(&src, &tgt).unwrap();
+            for (a, b) in expected.as_slice().iter().zip(actual.as_slice()) {
+                assert!((a - b).abs() < 1e-4, "{} vs {}", a, b);
+            }
+        }
+    }
+}
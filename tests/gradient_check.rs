@@ -0,0 +1,107 @@
+mod common;
+
+use common::{assert_gradient_close, finite_difference, tiny_config};
+use rust_transformer::layers::LayerNorm;
+use rust_transformer::training::{cross_entropy, CrossEntropyConfig};
+use rust_transformer::{
+    Matrix, NormType, Parameters, PositionalScheme, Transformer, TransformerConfig,
+};
+
+const SRC: [usize; 5] = [4, 9, 6, 13, 0];
+const TGT_IN: [usize; 4] = [1, 7, 5, 11];
+const TGT_OUT: [usize; 4] = [7, 5, 11, 2];
+
+fn loss(model: &Transformer) -> f64 {
+    let logits = model.forward(&SRC, &TGT_IN).unwrap();
+    cross_entropy(&logits, &TGT_OUT, &CrossEntropyConfig::default())
+        .unwrap()
+        .loss
+}
+
+/// Compares the analytic gradient of every weight with a finite difference
+/// at its largest entry and at its first entry.
+fn check(label: &str, mut model: Transformer) {
+    model.set_training(false);
+    let (logits, cache) = model.forward_with_cache(&SRC, &TGT_IN).unwrap();
+    let scored = cross_entropy(&logits, &TGT_OUT, &CrossEntropyConfig::default()).unwrap();
+    let grads = model.backward(&cache, &scored.d_logits).unwrap();
+
+    let mut names = Vec::new();
+    model.visit_parameters("", &mut |name, _| names.push(name.to_string()));
+    for name in names {
+        let grad: &Matrix = grads
+            .get(&name)
+            .unwrap_or_else(|| panic!("{}: no gradient for {}", label, name));
+        let largest = (0..grad.len())
+            .max_by(|&a, &b| {
+                grad.as_slice()[a]
+                    .abs()
+                    .total_cmp(&grad.as_slice()[b].abs())
+            })
+            .unwrap();
+        for index in [(0, 0), (largest / grad.cols(), largest % grad.cols())] {
+            let numeric = finite_difference(&model, &name, index, loss);
+            assert_gradient_close(
+                &format!("{} {} {:?}", label, name, index),
+                grad[index],
+                numeric,
+            );
+        }
+    }
+}
+
+#[test]
+fn gradients_match_finite_differences_across_configurations() {
+    let variants: [(&str, TransformerConfig); 6] = [
+        ("sinusoidal", tiny_config()),
+        (
+            "alibi",
+            TransformerConfig {
+                positional: PositionalScheme::Alibi,
+                ..tiny_config()
+            },
+        ),
+        (
+            "sliding window",
+            TransformerConfig {
+                attention_window: Some(1),
+                ..tiny_config()
+            },
+        ),
+        (
+            "shared embeddings",
+            TransformerConfig {
+                share_embeddings: true,
+                ..tiny_config()
+            },
+        ),
+        (
+            "rms norm",
+            TransformerConfig {
+                norm: NormType::RMSNorm,
+                ..tiny_config()
+            },
+        ),
+        (
+            "projection biases",
+            TransformerConfig {
+                attention_bias: true,
+                output_bias: true,
+                ..tiny_config()
+            },
+        ),
+    ];
+    for (i, (label, config)) in variants.into_iter().enumerate() {
+        check(
+            label,
+            Transformer::with_seed(config, 20 + i as u64).unwrap(),
+        );
+    }
+}
+
+#[test]
+fn gradients_flow_through_the_embedding_norm() {
+    let mut model = Transformer::with_seed(tiny_config(), 31).unwrap();
+    model.encoder.embedding_norm = Some(LayerNorm::new(8, 1e-5));
+    check("embedding norm", model);
+}
@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use rust_transformer::huggingface::{
+    parse_safetensors, to_safetensors, write_safetensors, HfCheckpoint,
+};
+use rust_transformer::layers::{Embedding, LayerNorm};
+use rust_transformer::models::Encoder;
+use rust_transformer::testing::{load_parameters, Fixtures};
+use rust_transformer::utils::json::Json;
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::{Matrix, Parameters, PositionalScheme, Transformer};
+
+const VOCAB: usize = 12;
+const D_MODEL: usize = 8;
+const D_FF: usize = 16;
+const POSITIONS: usize = 10;
+
+struct Tensors {
+    map: BTreeMap<String, Matrix>,
+    rng: Rng,
+}
+
+impl Tensors {
+    fn new(seed: u64) -> Self {
+        Self {
+            map: BTreeMap::new(),
+            rng: Rng::seed_from_u64(seed),
+        }
+    }
+
+    fn add(&mut self, name: impl Into<String>, rows: usize, cols: usize) {
+        let m = Matrix::random_normal(rows, cols, 0.3, &mut self.rng);
+        self.map.insert(name.into(), m);
+    }
+
+    /// An `nn.Linear` from `d_in` to `d_out`.
+    fn linear(&mut self, name: &str, d_in: usize, d_out: usize) {
+        self.add(format!("{}.weight", name), d_out, d_in);
+        self.add(format!("{}.bias", name), 1, d_out);
+    }
+
+    fn norm(&mut self, name: &str) {
+        self.add(format!("{}.weight", name), 1, D_MODEL);
+        self.add(format!("{}.bias", name), 1, D_MODEL);
+    }
+
+    fn attention(&mut self, name: &str) {
+        for proj in ["q_proj", "k_proj", "v_proj", "out_proj"] {
+            self.linear(&format!("{}.{}", name, proj), D_MODEL, D_MODEL);
+        }
+    }
+}
+
+fn marian_checkpoint() -> HfCheckpoint {
+    let mut t = Tensors::new(1);
+    t.add("model.shared.weight", VOCAB, D_MODEL);
+    t.add("final_logits_bias", 1, VOCAB);
+    for stack in ["encoder", "decoder"] {
+        t.add(
+            format!("model.{}.embed_positions.weight", stack),
+            POSITIONS,
+            D_MODEL,
+        );
+        let layer = format!("model.{}.layers.0", stack);
+        t.attention(&format!("{}.self_attn", layer));
+        t.norm(&format!("{}.self_attn_layer_norm", layer));
+        if stack == "decoder" {
+            t.attention(&format!("{}.encoder_attn", layer));
+            t.norm(&format!("{}.encoder_attn_layer_norm", layer));
+        }
+        t.linear(&format!("{}.fc1", layer), D_MODEL, D_FF);
+        t.linear(&format!("{}.fc2", layer), D_FF, D_MODEL);
+        t.norm(&format!("{}.final_layer_norm", layer));
+    }
+    let config = Json::object([
+        ("model_type", Json::from("marian")),
+        ("vocab_size", Json::from(VOCAB)),
+        ("d_model", Json::from(D_MODEL)),
+        ("encoder_attention_heads", Json::from(2)),
+        ("encoder_ffn_dim", Json::from(D_FF)),
+        ("encoder_layers", Json::from(1)),
+        ("decoder_layers", Json::from(1)),
+        ("max_position_embeddings", Json::from(POSITIONS)),
+        ("pad_token_id", Json::from(VOCAB - 1)),
+        ("eos_token_id", Json::from(0)),
+        ("scale_embedding", Json::from(false)),
+    ]);
+    HfCheckpoint {
+        config,
+        tensors: t.map,
+    }
+}
+
+fn bert_checkpoint() -> HfCheckpoint {
+    let mut t = Tensors::new(2);
+    t.add("bert.embeddings.word_embeddings.weight", VOCAB, D_MODEL);
+    t.add(
+        "bert.embeddings.position_embeddings.weight",
+        POSITIONS,
+        D_MODEL,
+    );
+    t.add("bert.embeddings.token_type_embeddings.weight", 2, D_MODEL);
+    t.norm("bert.embeddings.LayerNorm");
+    let layer = "bert.encoder.layer.0";
+    for linear in ["query", "key", "value"] {
+        t.linear(
+            &format!("{}.attention.self.{}", layer, linear),
+            D_MODEL,
+            D_MODEL,
+        );
+    }
+    t.linear(
+        &format!("{}.attention.output.dense", layer),
+        D_MODEL,
+        D_MODEL,
+    );
+    t.norm(&format!("{}.attention.output.LayerNorm", layer));
+    t.linear(&format!("{}.intermediate.dense", layer), D_MODEL, D_FF);
+    t.linear(&format!("{}.output.dense", layer), D_FF, D_MODEL);
+    t.norm(&format!("{}.output.LayerNorm", layer));
+    let config = Json::object([
+        ("model_type", Json::from("bert")),
+        ("vocab_size", Json::from(VOCAB)),
+        ("hidden_size", Json::from(D_MODEL)),
+        ("num_attention_heads", Json::from(2)),
+        ("num_hidden_layers", Json::from(1)),
+        ("intermediate_size", Json::from(D_FF)),
+        ("max_position_embeddings", Json::from(POSITIONS)),
+    ]);
+    HfCheckpoint {
+        config,
+        tensors: t.map,
+    }
+}
+
+#[test]
+fn imported_marian_survives_save_and_load() {
+    let model = marian_checkpoint().marian_transformer().unwrap();
+    assert_eq!(model.config.positional, PositionalScheme::Learned);
+    assert!(!model.config.scale_embeddings);
+    assert_eq!(model.encoder.embedding.scale, 1.0);
+
+    let mut loaded = Transformer::from_bytes(&model.to_bytes()).unwrap();
+    loaded.set_training(false);
+    assert_eq!(loaded.config, model.config);
+    assert_eq!(loaded.decoder.embedding.scale, 1.0);
+    assert_eq!(
+        loaded.encoder.positional.table(),
+        model.encoder.positional.table()
+    );
+
+    let (src, tgt) = ([3, 7, 1, 4], [11, 5, 9]);
+    assert_eq!(
+        loaded.forward(&src, &tgt).unwrap(),
+        model.forward(&src, &tgt).unwrap()
+    );
+}
+
+#[test]
+fn safetensors_round_trip_through_a_checkpoint_directory() {
+    let checkpoint = marian_checkpoint();
+    let bytes = to_safetensors(&checkpoint.tensors);
+    let header_len = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+    assert_eq!(header_len % 8, 0);
+    assert_eq!(parse_safetensors(&bytes).unwrap(), checkpoint.tensors);
+
+    let dir = std::env::temp_dir().join(format!("safetensors-round-trip-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.json"), checkpoint.config.to_string()).unwrap();
+    write_safetensors(dir.join("model.safetensors"), &checkpoint.tensors).unwrap();
+    let loaded = HfCheckpoint::load(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.tensors, checkpoint.tensors);
+
+    let (src, tgt) = ([3, 7, 1, 4], [11, 5, 9]);
+    let mut expected = checkpoint.marian_transformer().unwrap();
+    let mut actual = loaded.marian_transformer().unwrap();
+    expected.set_training(false);
+    actual.set_training(false);
+    assert_eq!(
+        actual.forward(&src, &tgt).unwrap(),
+        expected.forward(&src, &tgt).unwrap()
+    );
+}
+
+#[test]
+fn learned_position_tables_are_trainable() {
+    let model = marian_checkpoint().marian_transformer().unwrap();
+    let mut names = Vec::new();
+    model.visit_parameters("", &mut |name, _| names.push(name.to_string()));
+    assert!(names.contains(&"encoder.positional".to_string()));
+    assert!(names.contains(&"decoder.positional".to_string()));
+
+    let (logits, cache) = model.forward_with_cache(&[3, 7, 1], &[11, 5]).unwrap();
+    let grads = model
+        .backward(
+            &cache,
+            &Matrix::from_fn(2, VOCAB, |i, j| (i + j) as f64 * 0.1),
+        )
+        .unwrap();
+    assert_eq!(logits.rows(), 2);
+    for stack in ["encoder", "decoder"] {
+        let grad = grads.get(&format!("{}.positional", stack)).unwrap();
+        assert_eq!(grad.shape(), (POSITIONS, D_MODEL));
+        // Only the positions the pass used get a gradient.
+        assert!(grad.row(0).iter().any(|&g| g != 0.0));
+        assert!(grad.row(POSITIONS - 1).iter().all(|&g| g == 0.0));
+    }
+}
+
+#[test]
+fn imported_bert_is_rebuilt_from_its_config_and_parameters() {
+    let (config, encoder) = bert_checkpoint().bert_encoder().unwrap();
+    assert_eq!(config.positional, PositionalScheme::Learned);
+    assert!(!config.scale_embeddings);
+
+    let mut rebuilt = Encoder::new(&config).unwrap();
+    rebuilt.set_training(false);
+    rebuilt.embedding_norm = Some(LayerNorm::new(D_MODEL, config.layer_norm_eps));
+    rebuilt.token_type_embedding = Some(Embedding::from_weight(Matrix::zeros(2, D_MODEL)));
+    load_parameters(&mut rebuilt, "", &Fixtures::from_parameters(&encoder, "")).unwrap();
+
+    let tokens = [2, 9, 4, 6, 1];
+    assert_eq!(
+        rebuilt.forward(&tokens, None).unwrap(),
+        encoder.forward(&tokens, None).unwrap()
+    );
+}
@@ -0,0 +1,93 @@
+use rust_transformer::{NormType, PositionalScheme, Transformer, TransformerConfig};
+
+fn config() -> TransformerConfig {
+    TransformerConfig {
+        vocab_size: 14,
+        d_model: 8,
+        num_heads: 2,
+        num_encoder_layers: 2,
+        num_decoder_layers: 2,
+        d_ff: 16,
+        max_seq_len: 16,
+        dropout: 0.0,
+        ..TransformerConfig::default()
+    }
+}
+
+/// Decodes `tgt` through the key/value cache `step` positions at a time and
+/// checks every row against a full recompute.
+fn assert_incremental_matches_full(model: &Transformer, src: &[usize], tgt: &[usize], step: usize) {
+    let memory = model.encode(src).unwrap();
+    let full = model.decode(tgt, src, &memory).unwrap();
+    let mut cache = model.decoder.start_kv_cache(&memory).unwrap();
+
+    let mut end = 0;
+    while end < tgt.len() {
+        let start = end;
+        end = (end + step).min(tgt.len());
+        let rows = model
+            .decode_incremental(&tgt[..end], src, &mut cache)
+            .unwrap();
+        assert_eq!(rows.rows(), end - start);
+        assert_eq!(cache.len(), end);
+        for i in start..end {
+            for (a, b) in rows.row(i - start).iter().zip(full.row(i)) {
+                assert!((a - b).abs() < 1e-10, "position {}: {} vs {}", i, a, b);
+            }
+        }
+    }
+}
+
+#[test]
+fn incremental_decoding_matches_full_recompute() {
+    let variants = [
+        config(),
+        TransformerConfig {
+            positional: PositionalScheme::Alibi,
+            ..config()
+        },
+        TransformerConfig {
+            attention_window: Some(2),
+            ..config()
+        },
+        TransformerConfig {
+            norm: NormType::RMSNorm,
+            attention_bias: true,
+            output_bias: true,
+            share_embeddings: true,
+            ..config()
+        },
+    ];
+    // A padded source and a target that repeats the padding id after the
+    // start token, which the causal mask must still hide.
+    let src = [5, 9, 4, 11, 0, 0];
+    let tgt = [1, 6, 8, 0, 12, 3, 7];
+    for (i, config) in variants.into_iter().enumerate() {
+        let mut model = Transformer::with_seed(config, 40 + i as u64).unwrap();
+        model.set_training(false);
+        for step in [1, 3] {
+            assert_incremental_matches_full(&model, &src, &tgt, step);
+        }
+    }
+}
+
+#[test]
+fn generation_through_the_cache_matches_recomputed_greedy_decoding() {
+    let mut model = Transformer::with_seed(config(), 8).unwrap();
+    model.set_training(false);
+    let src = [4, 10, 6, 13];
+    let generated = model.generate_greedy(&src, 10).unwrap();
+
+    // Recompute greedy decoding from scratch at every step.
+    let memory = model.encode(&src).unwrap();
+    let mut tokens = vec![model.config.bos_token_id];
+    while tokens.len() < generated.len() {
+        let logits = model.decode(&tokens, &src, &memory).unwrap();
+        let last = logits.row(logits.rows() - 1);
+        let next = (0..last.len())
+            .max_by(|&a, &b| last[a].total_cmp(&last[b]))
+            .unwrap();
+        tokens.push(next);
+    }
+    assert_eq!(tokens, generated);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{assert_gradient_close, finite_difference, tiny_config};
+use rust_transformer::data::MlmCollator;
+use rust_transformer::models::MaskedLanguageModel;
+use rust_transformer::training::{masked_lm_loss, CrossEntropyConfig};
+
+const MASK: usize = 3;
+
+#[test]
+fn collator_follows_the_80_10_10_scheme() {
+    let config = tiny_config();
+    let mut collator = MlmCollator::for_model(&config, MASK, 4).with_mask_probability(0.5);
+    let tokens: Vec<usize> = (0..20_000).map(|i| [1, 4, 5, 6, 7, 0][i % 6]).collect();
+    let masked = collator.mask(&tokens);
+
+    let (mut as_mask, mut kept, mut total) = (0, 0, 0);
+    assert_eq!(masked.labels, tokens);
+    for (i, &token) in tokens.iter().enumerate() {
+        if !masked.masked[i] {
+            assert_eq!(masked.input[i], token);
+            continue;
+        }
+        assert!(![0, 1, 2, MASK].contains(&token));
+        total += 1;
+        if masked.input[i] == MASK {
+            as_mask += 1;
+        } else if masked.input[i] == token {
+            kept += 1;
+        }
+    }
+    let selectable = tokens.iter().filter(|&&t| t >= 4).count() as f64;
+    assert!((total as f64 / selectable - 0.5).abs() < 0.02);
+    assert!((as_mask as f64 / total as f64 - 0.8).abs() < 0.02);
+    // Kept tokens plus random replacements that happened to hit the original.
+    assert!((kept as f64 / total as f64 - 0.1 - 0.1 / 16.0).abs() < 0.02);
+}
+
+#[test]
+fn masked_lm_loss_counts_only_masked_positions() {
+    let mut model = MaskedLanguageModel::with_seed(tiny_config(), 2).unwrap();
+    model.set_training(false);
+    let mut collator =
+        MlmCollator::for_model(&model.backbone.config, MASK, 2).with_mask_probability(0.4);
+    let batch = collator.mask_batch(&[vec![4, 9, 12, 5, 7, 11, 0], vec![6, 8, 10, 13]]);
+    assert!(batch.iter().all(|example| example.num_masked() > 0));
+    let loss = CrossEntropyConfig::default();
+    let (value, grads) = masked_lm_loss(&model, &batch, &loss).unwrap();
+    assert!(value > 0.0);
+
+    // Labels at unmasked positions do not matter.
+    let mut relabeled = batch.clone();
+    for example in &mut relabeled {
+        for i in 0..example.labels.len() {
+            if !example.masked[i] {
+                example.labels[i] = 15;
+            }
+        }
+    }
+    assert_eq!(masked_lm_loss(&model, &relabeled, &loss).unwrap().0, value);
+
+    for (name, at) in [("encoder.embedding", (9, 2)), ("mlm_head.bias", (0, 5))] {
+        let numeric = finite_difference(&model, name, at, |m| {
+            masked_lm_loss(m, &batch, &loss).unwrap().0
+        });
+        assert_gradient_close(name, grads.get(name).unwrap()[at], numeric);
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{assert_gradient_close, finite_difference};
+use rust_transformer::layers::RMSNorm;
+use rust_transformer::params::Gradients;
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::Matrix;
+
+fn rows(values: &[[f64; 4]]) -> Matrix {
+    Matrix::from_fn(values.len(), 4, |i, j| values[i][j])
+}
+
+#[test]
+fn rms_norm_matches_hand_computed_values() {
+    let mut norm = RMSNorm::new(4, 0.0);
+    norm.gamma = rows(&[[1.0, 2.0, 3.0, 0.5]]);
+    let x = rows(&[
+        // Mean square 25/4, so every entry is divided by 2.5.
+        [3.0, 4.0, 0.0, 0.0],
+        [1.0, -1.0, 1.0, -1.0],
+        // Unlike LayerNorm, a constant row is not centered to zero.
+        [2.0, 2.0, 2.0, 2.0],
+    ]);
+    let expected = rows(&[
+        [1.2, 3.2, 0.0, 0.0],
+        [1.0, -2.0, 3.0, -0.5],
+        [1.0, 2.0, 3.0, 0.5],
+    ]);
+    let out = norm.forward(&x).unwrap();
+    for (a, e) in out.as_slice().iter().zip(expected.as_slice()) {
+        assert!((a - e).abs() < 1e-12, "{} vs {}", a, e);
+    }
+
+    // `eps` keeps an all-zero row finite.
+    let zeros = RMSNorm::new(4, 1e-5).forward(&Matrix::zeros(1, 4)).unwrap();
+    assert_eq!(zeros.as_slice(), &[0.0; 4]);
+}
+
+#[test]
+fn rms_norm_gradients_match_finite_differences() {
+    let mut rng = Rng::seed_from_u64(21);
+    let mut norm = RMSNorm::new(4, 1e-5);
+    norm.gamma = Matrix::random_normal(1, 4, 1.0, &mut rng);
+    let x = Matrix::random_normal(3, 4, 1.0, &mut rng);
+    // The loss Σ w ⊙ y has upstream gradient w.
+    let w = Matrix::random_normal(3, 4, 1.0, &mut rng);
+    let loss = |norm: &RMSNorm, x: &Matrix| -> f64 {
+        let y = norm.forward(x).unwrap();
+        y.as_slice()
+            .iter()
+            .zip(w.as_slice())
+            .map(|(y, w)| y * w)
+            .sum()
+    };
+
+    let (_, cache) = norm.forward_with_cache(&x).unwrap();
+    let mut grads = Gradients::new();
+    let dx = norm.backward(&cache, &w, "norm", &mut grads).unwrap();
+
+    let d_gamma = grads.get("norm.gamma").unwrap();
+    for j in 0..4 {
+        let numeric = finite_difference(&norm, "gamma", (0, j), |n| loss(n, &x));
+        assert_gradient_close(&format!("gamma[{}]", j), d_gamma[(0, j)], numeric);
+    }
+
+    let eps = 1e-6;
+    for i in 0..3 {
+        for j in 0..4 {
+            let shifted = |delta: f64| {
+                let mut x = x.clone();
+                x[(i, j)] += delta;
+                loss(&norm, &x)
+            };
+            let numeric = (shifted(eps) - shifted(-eps)) / (2.0 * eps);
+            assert_gradient_close(&format!("x[{}, {}]", i, j), dx[(i, j)], numeric);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use rust_transformer::layers::{ActivationType, Dropout, Embedding, FeedForward};
+use rust_transformer::onnx::{OnnxModel, OnnxTensor};
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::{Matrix, Parameters};
+
+// Just enough of a protobuf writer to emit the `ModelProto` fields the
+// importer reads.
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn field_varint(out: &mut Vec<u8>, number: u64, value: u64) {
+    varint(out, number << 3);
+    varint(out, value);
+}
+
+fn field_bytes(out: &mut Vec<u8>, number: u64, bytes: &[u8]) {
+    varint(out, (number << 3) | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// A `TensorProto` of doubles in `raw_data`.
+fn tensor(name: &str, shape: &[usize], data: &[f64]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for &dim in shape {
+        field_varint(&mut out, 1, dim as u64);
+    }
+    field_varint(&mut out, 2, 11);
+    field_bytes(&mut out, 8, name.as_bytes());
+    let raw: Vec<u8> = data.iter().flat_map(|v| v.to_le_bytes()).collect();
+    field_bytes(&mut out, 9, &raw);
+    out
+}
+
+fn node(op_type: &str, inputs: &[&str], output: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    for input in inputs {
+        field_bytes(&mut out, 1, input.as_bytes());
+    }
+    field_bytes(&mut out, 2, output.as_bytes());
+    field_bytes(&mut out, 3, output.as_bytes());
+    field_bytes(&mut out, 4, op_type.as_bytes());
+    out
+}
+
+fn value_info(name: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    field_bytes(&mut out, 1, name.as_bytes());
+    out
+}
+
+/// An embedding lookup followed by a ReLU feed-forward block, with every
+/// weight stored as an initializer named as by [`Parameters`].
+fn export(embedding: &Embedding, ffn: &FeedForward) -> Vec<u8> {
+    let mut graph = Vec::new();
+    let nodes = [
+        node("Gather", &["embedding.weight", "input_ids"], "embedded"),
+        node("MatMul", &["embedded", "ffn.w1"], "ffn.h1"),
+        node("Add", &["ffn.h1", "ffn.b1"], "ffn.pre"),
+        node("Relu", &["ffn.pre"], "ffn.act"),
+        node("MatMul", &["ffn.act", "ffn.w2"], "ffn.h2"),
+        node("Add", &["ffn.h2", "ffn.b2"], "hidden"),
+    ];
+    for n in &nodes {
+        field_bytes(&mut graph, 1, n);
+    }
+    let mut add_initializer = |name: &str, m: &Matrix| {
+        field_bytes(
+            &mut graph,
+            5,
+            &tensor(name, &[m.rows(), m.cols()], m.as_slice()),
+        );
+    };
+    embedding.visit_parameters("embedding", &mut add_initializer);
+    ffn.visit_parameters("ffn", &mut add_initializer);
+    field_bytes(&mut graph, 11, &value_info("input_ids"));
+    // Older exporters also list initializers as graph inputs.
+    field_bytes(&mut graph, 11, &value_info("ffn.w1"));
+    field_bytes(&mut graph, 12, &value_info("hidden"));
+
+    let mut model = Vec::new();
+    field_varint(&mut model, 1, 8);
+    field_bytes(&mut model, 7, &graph);
+    model
+}
+
+fn layers() -> (Embedding, FeedForward) {
+    let mut rng = Rng::seed_from_u64(7);
+    let embedding = Embedding::new(10, 4, &mut rng);
+    let ffn = FeedForward::new_with_rng(4, 6, ActivationType::ReLU, Dropout::new(0.0), &mut rng);
+    (embedding, ffn)
+}
+
+#[test]
+fn exported_graph_lists_inputs_outputs_and_every_parameter() {
+    let (embedding, ffn) = layers();
+    let model = OnnxModel::parse(&export(&embedding, &ffn)).unwrap();
+
+    assert_eq!(model.inputs(), vec!["input_ids"]);
+    assert_eq!(model.outputs(), vec!["hidden"]);
+
+    let mut expected = Vec::new();
+    let mut collect = |name: &str, m: &Matrix| expected.push((name.to_string(), m.clone()));
+    embedding.visit_parameters("embedding", &mut collect);
+    ffn.visit_parameters("ffn", &mut collect);
+
+    let initializers = model.initializers();
+    assert_eq!(initializers.len(), expected.len());
+    for (name, matrix) in &expected {
+        let tensor = initializers
+            .get(name)
+            .unwrap_or_else(|| panic!("missing initializer '{}'", name));
+        assert_eq!(tensor.shape, vec![matrix.rows(), matrix.cols()], "{}", name);
+        assert_eq!(tensor.data, matrix.as_slice(), "{}", name);
+    }
+}
+
+#[test]
+fn exported_graph_runs_like_the_layers() {
+    let (embedding, ffn) = layers();
+    let model = OnnxModel::parse(&export(&embedding, &ffn)).unwrap();
+    let tokens = [3, 0, 9, 3];
+
+    let expected = ffn.forward(&embedding.forward(&tokens).unwrap()).unwrap();
+    let actual = model.encode(&tokens).unwrap();
+    assert_eq!(actual.shape(), expected.shape());
+    for (a, e) in actual.as_slice().iter().zip(expected.as_slice()) {
+        assert!((a - e).abs() < 1e-12, "{} vs {}", a, e);
+    }
+
+    let outputs = model
+        .run(&[("input_ids", OnnxTensor::from_tokens(&tokens))])
+        .unwrap();
+    assert_eq!(outputs["hidden"].shape, vec![1, tokens.len(), 4]);
+}
+
+#[test]
+fn unsupported_operators_are_reported_at_load() {
+    let mut graph = Vec::new();
+    field_bytes(&mut graph, 1, &node("Conv", &["x", "w"], "y"));
+    field_bytes(&mut graph, 1, &node("LSTM", &["y"], "z"));
+    let mut model = Vec::new();
+    field_bytes(&mut model, 7, &graph);
+
+    let err = OnnxModel::parse(&model).unwrap_err().to_string();
+    assert!(err.contains("Conv, LSTM"), "{}", err);
+}
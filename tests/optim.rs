@@ -0,0 +1,275 @@
+mod common;
+
+use rust_transformer::layers::LayerNorm;
+use rust_transformer::models::EncoderOnlyTransformer;
+use rust_transformer::optim::quantized::QuantizedBuffer;
+use rust_transformer::optim::{
+    Adafactor, AdafactorConfig, Adam, AdamConfig, Lamb, LambConfig, Optimizer, ParamGroups,
+    UpdateSettings,
+};
+use rust_transformer::tensor::Matrix;
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::{Parameters, PositionalScheme, TransformerConfig};
+
+fn row(values: &[f64]) -> Matrix {
+    Matrix::row_vector(values.to_vec())
+}
+
+fn assert_close(actual: &Matrix, expected: &[f64]) {
+    for (a, e) in actual.as_slice().iter().zip(expected) {
+        assert!(
+            (a - e).abs() < 1e-12,
+            "{:?} vs {:?}",
+            actual.as_slice(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn adamw_first_step_matches_hand_computed_values() {
+    let mut adam = Adam::new(AdamConfig {
+        lr: 0.1,
+        ..AdamConfig::adamw()
+    });
+    let mut w = row(&[1.0, -2.0]);
+    let settings = UpdateSettings {
+        lr: 0.1,
+        weight_decay: 0.01,
+    };
+    adam.begin_step();
+    adam.update("w", &mut w, &row(&[0.5, 0.1]), settings)
+        .unwrap();
+    // Decay first: w ← w − 0.1·0.01·w. After bias correction m̂ = g and
+    // v̂ = g², so the Adam step is 0.1 · g / (|g| + 1e-8).
+    assert_close(
+        &w,
+        &[
+            0.999 - 0.1 * 0.5 / (0.5 + 1e-8),
+            -1.998 - 0.1 * 0.1 / (0.1 + 1e-8),
+        ],
+    );
+}
+
+#[test]
+fn adam_with_l2_adds_the_decay_to_the_gradient() {
+    let mut adam = Adam::new(AdamConfig {
+        lr: 0.1,
+        weight_decay: 0.5,
+        ..AdamConfig::default()
+    });
+    let mut w = row(&[1.0]);
+    let settings = adam.settings_for("w");
+    adam.begin_step();
+    adam.update("w", &mut w, &row(&[-0.25]), settings).unwrap();
+    // g + 0.5·w = 0.25.
+    let w1 = 1.0 - 0.1 * 0.25 / (0.25 + 1e-8);
+    assert_close(&w, &[w1]);
+
+    adam.begin_step();
+    adam.update("w", &mut w, &row(&[-0.25]), settings).unwrap();
+    let g = -0.25 + 0.5 * w1;
+    let m = 0.9 * 0.025 + 0.1 * g;
+    let v = 0.999 * 0.25 * 0.25 * 0.001 + 0.001 * g * g;
+    let step = 0.1 * (m / (1.0 - 0.81)) / ((v / (1.0 - 0.999 * 0.999)).sqrt() + 1e-8);
+    assert_close(&w, &[w1 - step]);
+}
+
+#[test]
+fn signed_8bit_code_is_symmetric() {
+    let mut buffer = QuantizedBuffer::zeros(3, 3, true);
+    assert_eq!(buffer.dequantize(), vec![0.0; 3]);
+
+    buffer.quantize_from(&[-2.0, 0.0, 0.62]);
+    let decoded = buffer.dequantize();
+    assert_eq!(decoded[0], -2.0);
+    assert_eq!(decoded[1], 0.0);
+
+    let mut flipped = QuantizedBuffer::zeros(3, 3, true);
+    flipped.quantize_from(&[2.0, 0.0, -0.62]);
+    let negated: Vec<f64> = flipped.dequantize().iter().map(|v| -v).collect();
+    assert_eq!(decoded, negated);
+}
+
+fn lamb_step(config: LambConfig, w: &mut Matrix, g: &[f64]) -> f64 {
+    let mut lamb = Lamb::new(config);
+    let settings = lamb.settings_for("w");
+    lamb.begin_step();
+    lamb.update("w", w, &row(g), settings).unwrap();
+    lamb.trust_ratio("w").unwrap()
+}
+
+#[test]
+fn lamb_first_step_scales_the_direction_by_the_trust_ratio() {
+    let config = LambConfig {
+        lr: 0.1,
+        eps: 0.0,
+        weight_decay: 0.1,
+        max_trust_ratio: None,
+        ..LambConfig::default()
+    };
+    let mut w = row(&[3.0, 4.0]);
+    let trust = lamb_step(config, &mut w, &[2.0, -0.5]);
+    // The bias-corrected Adam direction is sign(g) = [1, −1]; decay adds
+    // 0.1·w, giving [1.3, −0.6]. ‖w‖ = 5, so the trust ratio is 5 / √2.05.
+    let expected = 5.0 / 2.05f64.sqrt();
+    assert!((trust - expected).abs() < 1e-12);
+    assert_close(
+        &w,
+        &[3.0 - 0.1 * expected * 1.3, 4.0 + 0.1 * expected * 0.6],
+    );
+
+    let mut w = row(&[3.0, 4.0]);
+    let clipped = lamb_step(
+        LambConfig {
+            max_trust_ratio: Some(2.0),
+            ..config
+        },
+        &mut w,
+        &[2.0, -0.5],
+    );
+    assert_eq!(clipped, 2.0);
+    assert_close(&w, &[3.0 - 0.2 * 1.3, 4.0 + 0.2 * 0.6]);
+}
+
+#[test]
+fn lamb_trust_ratio_is_one_when_a_norm_vanishes() {
+    let config = LambConfig {
+        lr: 0.1,
+        eps: 0.0,
+        weight_decay: 0.0,
+        max_trust_ratio: None,
+        ..LambConfig::default()
+    };
+
+    // Zero weights: a plain Adam step.
+    let mut w = row(&[0.0, 0.0]);
+    assert_eq!(lamb_step(config, &mut w, &[0.5, -3.0]), 1.0);
+    assert_close(&w, &[-0.1, 0.1]);
+
+    // Zero update: the weights stay put.
+    let mut w = row(&[1.0, -1.0]);
+    let config = LambConfig {
+        eps: 1e-6,
+        ..config
+    };
+    assert_eq!(lamb_step(config, &mut w, &[0.0, 0.0]), 1.0);
+    assert_close(&w, &[1.0, -1.0]);
+}
+
+fn plain_adafactor(clip_threshold: f64) -> Adafactor {
+    Adafactor::new(AdafactorConfig {
+        lr: 0.1,
+        relative_step: false,
+        scale_parameter: false,
+        eps1: 0.0,
+        clip_threshold,
+        ..AdafactorConfig::default()
+    })
+}
+
+fn adafactor_step(opt: &mut Adafactor, w: &mut Matrix, g: &Matrix) {
+    let settings = opt.settings_for("w");
+    opt.begin_step();
+    opt.update("w", w, g, settings).unwrap();
+}
+
+#[test]
+fn adafactor_factors_the_second_moment_of_matrices() {
+    let mut opt = plain_adafactor(100.0);
+    let mut w = Matrix::zeros(2, 3);
+    let g1 = Matrix::from_rows(&[vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]).unwrap();
+    adafactor_step(&mut opt, &mut w, &g1);
+    assert_eq!(opt.state_len(), 5);
+
+    // β₂ is 0 on the first step, so the accumulators are the row and column
+    // means of g²: R = [14/3, 77/3], C = [17/2, 29/2, 45/2], and
+    // v̂ᵢⱼ = RᵢCⱼ / mean(R) with mean(R) = 91/6.
+    let r: [f64; 2] = [14.0 / 3.0, 77.0 / 3.0];
+    let c: [f64; 3] = [8.5, 14.5, 22.5];
+    let r_mean = 91.0 / 6.0;
+    let mut expected = [0.0; 6];
+    for (k, (e, g)) in expected.iter_mut().zip(g1.as_slice()).enumerate() {
+        let v = r[k / 3] * c[k % 3] / r_mean;
+        *e = -0.1 * g / v.sqrt();
+    }
+    assert_close(&w, &expected);
+
+    // Second step: β₂ = 1 − 2^−0.8 blends in the new means.
+    let g2 = Matrix::from_rows(&[vec![1.0, 0.0, 0.0], vec![0.0, 0.0, 1.0]]).unwrap();
+    adafactor_step(&mut opt, &mut w, &g2);
+    let beta2 = 1.0 - 2f64.powf(-0.8);
+    let r = [
+        beta2 * r[0] + (1.0 - beta2) / 3.0,
+        beta2 * r[1] + (1.0 - beta2) / 3.0,
+    ];
+    let c = [
+        beta2 * c[0] + (1.0 - beta2) * 0.5,
+        beta2 * c[1],
+        beta2 * c[2] + (1.0 - beta2) * 0.5,
+    ];
+    let r_mean = (r[0] + r[1]) / 2.0;
+    expected[0] -= 0.1 / (r[0] * c[0] / r_mean).sqrt();
+    expected[5] -= 0.1 / (r[1] * c[2] / r_mean).sqrt();
+    assert_close(&w, &expected);
+}
+
+#[test]
+fn adafactor_keeps_a_full_accumulator_for_vectors() {
+    let mut opt = plain_adafactor(0.2);
+    let mut w = row(&[1.0, 1.0]);
+    adafactor_step(&mut opt, &mut w, &row(&[3.0, -4.0]));
+    assert_eq!(opt.state_len(), 2);
+    // v = g², so u = sign(g) with RMS 1; clipping scales it to RMS 0.2.
+    assert_close(&w, &[0.98, 1.02]);
+
+    adafactor_step(&mut opt, &mut w, &row(&[1.0, 1.0]));
+    let beta2 = 1.0 - 2f64.powf(-0.8);
+    let u = [
+        1.0 / (beta2 * 9.0 + 1.0 - beta2).sqrt(),
+        1.0 / (beta2 * 16.0 + 1.0 - beta2).sqrt(),
+    ];
+    let clip = ((u[0] * u[0] + u[1] * u[1]) / 2.0).sqrt() / 0.2;
+    assert!(clip > 1.0);
+    assert_close(&w, &[0.98 - 0.1 * u[0] / clip, 1.02 - 0.1 * u[1] / clip]);
+}
+
+#[test]
+fn layer_wise_decay_scales_every_embedding_weight_with_the_embedding() {
+    let config = TransformerConfig {
+        num_encoder_layers: 2,
+        positional: PositionalScheme::Learned,
+        ..common::tiny_config()
+    };
+    let mut model =
+        EncoderOnlyTransformer::new_with_rng(config.clone(), &mut Rng::seed_from_u64(1))
+            .unwrap()
+            .with_token_types(2);
+    model.encoder.embedding_norm = Some(LayerNorm::new(config.d_model, config.layer_norm_eps));
+
+    let decay = 0.5;
+    let groups = ParamGroups::layer_wise_decay("encoder", 2, decay);
+    let mut names = Vec::new();
+    model.visit_parameters("", &mut |name: &str, _: &Matrix| {
+        names.push(name.to_string())
+    });
+    for name in &names {
+        let expected = if name.starts_with("encoder.layers.1.") {
+            1.0
+        } else if name.starts_with("encoder.layers.0.") {
+            decay
+        } else {
+            decay * decay
+        };
+        let scale = groups.find(name).map(|g| g.lr_scale);
+        assert_eq!(scale, Some(expected), "{}", name);
+    }
+    for name in [
+        "encoder.embedding",
+        "encoder.positional",
+        "encoder.token_type_embedding",
+        "encoder.embedding_norm.gamma",
+    ] {
+        assert!(names.iter().any(|n| n == name), "no weight {}", name);
+    }
+}
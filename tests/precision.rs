@@ -0,0 +1,43 @@
+use rust_transformer::layers::{ActivationType, Dropout, Embedding, FeedForward, LayerNorm, Norm};
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::{Matrix, NormType};
+
+fn assert_close(what: &str, single: &Matrix<f32>, double: &Matrix<f64>) {
+    assert_eq!(single.shape(), double.shape(), "{}", what);
+    for (i, (&s, &d)) in single.as_slice().iter().zip(double.as_slice()).enumerate() {
+        let err = (s as f64 - d).abs() / d.abs().max(1.0);
+        assert!(err < 1e-5, "{}[{}]: f32 {} vs f64 {}", what, i, s, d);
+    }
+}
+
+#[test]
+fn f32_layers_match_f64_forward() {
+    let mut rng = Rng::seed_from_u64(3);
+    let embedding = Embedding::new(10, 8, &mut rng).with_scale(8f64.sqrt());
+    let mut ff =
+        FeedForward::new_with_rng(8, 16, ActivationType::GELU, Dropout::new(0.1), &mut rng);
+    ff.b1 = Matrix::random_normal(1, 16, 0.5, &mut rng);
+    let mut layer_norm = LayerNorm::new(8, 1e-5);
+    layer_norm.gamma = Matrix::random_normal(1, 8, 1.0, &mut rng);
+    layer_norm.beta = Matrix::random_normal(1, 8, 1.0, &mut rng);
+    let rms_norm = Norm::new(NormType::RMSNorm, 8, 1e-6);
+    let tokens = [4, 0, 9, 4, 2];
+
+    let x = embedding.forward(&tokens).unwrap();
+    let h = x.add(&ff.forward(&x).unwrap()).unwrap();
+    let h = layer_norm.forward(&h).unwrap();
+    let out = rms_norm.forward(&h).unwrap();
+
+    let embedding32 = embedding.cast::<f32>();
+    let x32 = embedding32.forward(&tokens).unwrap();
+    assert_close("embedding", &x32, &x);
+    let h32 = x32.add(&ff.cast::<f32>().forward(&x32).unwrap()).unwrap();
+    let h32 = layer_norm.cast::<f32>().forward(&h32).unwrap();
+    assert_close("layer norm", &h32, &h);
+    let out32 = rms_norm.cast::<f32>().forward(&h32).unwrap();
+    assert_close("rms norm", &out32, &out);
+
+    // Tied output projection.
+    let logits = embedding.attend(&out).unwrap();
+    assert_close("logits", &embedding32.attend(&out32).unwrap(), &logits);
+}
@@ -0,0 +1,108 @@
+use rust_transformer::models::ModelMetadata;
+use rust_transformer::utils::checksum::crc32;
+use rust_transformer::{NormType, Parameters, PositionalScheme, Transformer, TransformerConfig};
+
+fn model() -> Transformer {
+    let config = TransformerConfig {
+        vocab_size: 20,
+        d_model: 8,
+        num_heads: 2,
+        d_ff: 16,
+        max_seq_len: 12,
+        positional: PositionalScheme::Alibi,
+        norm: NormType::RMSNorm,
+        attention_bias: true,
+        share_embeddings: true,
+        ..TransformerConfig::default()
+    };
+    let mut metadata = ModelMetadata {
+        license: Some("MIT".to_string()),
+        ..ModelMetadata::default()
+    };
+    metadata.eval_scores.insert("bleu".to_string(), 0.25);
+    let mut model = Transformer::with_seed(config, 3)
+        .unwrap()
+        .with_metadata(metadata);
+    model.set_training(false);
+    model
+}
+
+/// Replaces the trailing checksum so only the edited content is wrong.
+fn reseal(bytes: &mut Vec<u8>) {
+    bytes.truncate(bytes.len() - 4);
+    let crc = crc32(bytes);
+    bytes.extend_from_slice(&crc.to_le_bytes());
+}
+
+fn error(bytes: &[u8]) -> String {
+    Transformer::from_bytes(bytes).unwrap_err().to_string()
+}
+
+#[test]
+fn save_and_load_round_trip_exactly() {
+    let model = model();
+    let path = std::env::temp_dir().join(format!("rtwt-round-trip-{}.bin", std::process::id()));
+    model.save(&path).unwrap();
+    let loaded = Transformer::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.config, model.config);
+    assert_eq!(loaded.metadata, model.metadata);
+    let mut original = Vec::new();
+    model.visit_parameters("", &mut |name, m| {
+        original.push((name.to_string(), m.clone()))
+    });
+    let mut restored = Vec::new();
+    loaded.visit_parameters("", &mut |name, m| {
+        restored.push((name.to_string(), m.clone()))
+    });
+    assert_eq!(restored, original);
+
+    let (src, tgt) = ([4, 9, 13, 7], [1, 5, 8]);
+    assert_eq!(
+        loaded.forward(&src, &tgt).unwrap(),
+        model.forward(&src, &tgt).unwrap()
+    );
+    assert_eq!(loaded.to_bytes(), model.to_bytes());
+}
+
+#[test]
+fn bad_magic_and_unknown_versions_are_rejected() {
+    let bytes = model().to_bytes();
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'X';
+    assert!(error(&wrong_magic).contains("bad magic"));
+    assert!(error(b"RTW").contains("bad magic"));
+
+    for version in [0u32, 2] {
+        let mut other = bytes.clone();
+        other[4..8].copy_from_slice(&version.to_le_bytes());
+        reseal(&mut other);
+        assert!(error(&other).contains("unsupported model format version"));
+    }
+}
+
+#[test]
+fn corruption_and_truncation_are_detected() {
+    let bytes = model().to_bytes();
+
+    let mut flipped = bytes.clone();
+    let middle = bytes.len() / 2;
+    flipped[middle] ^= 0x01;
+    assert!(error(&flipped).contains("checksum mismatch"));
+
+    let truncated = &bytes[..bytes.len() - 9];
+    assert!(error(truncated).contains("checksum mismatch"));
+
+    // A truncated body with a valid checksum still fails to parse.
+    let mut short = bytes[..bytes.len() - 12].to_vec();
+    short.extend_from_slice(&[0; 4]);
+    reseal(&mut short);
+    assert!(error(&short).contains("past the end"));
+
+    let mut trailing = bytes[..bytes.len() - 4].to_vec();
+    trailing.extend_from_slice(&[0; 4 + 4]);
+    reseal(&mut trailing);
+    assert!(error(&trailing).contains("trailing bytes"));
+}
@@ -0,0 +1,346 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rust_transformer::cancellation::CancellationToken;
+use rust_transformer::engine::{EngineConfig, InferenceEngine};
+use rust_transformer::server::{InferenceServer, ServerConfig};
+use rust_transformer::utils::json::Json;
+use rust_transformer::{Parameters, Transformer, TransformerError};
+
+struct Running {
+    addr: SocketAddr,
+    cancellation: CancellationToken,
+    serving: Option<JoinHandle<()>>,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+        if let Some(serving) = self.serving.take() {
+            let _ = serving.join();
+        }
+    }
+}
+
+fn engine(queue_capacity: usize) -> InferenceEngine {
+    let model = Transformer::with_seed(common::tiny_config(), 5).unwrap();
+    let config = EngineConfig {
+        num_workers: 1,
+        queue_capacity,
+    };
+    InferenceEngine::new(model, config).unwrap()
+}
+
+fn start(config: ServerConfig) -> Running {
+    start_with(engine(8), config)
+}
+
+fn start_with(engine: InferenceEngine, config: ServerConfig) -> Running {
+    let server = InferenceServer::bind("127.0.0.1:0", engine)
+        .unwrap()
+        .with_config(config);
+    let addr = server.local_addr().unwrap();
+    let cancellation = server.cancellation();
+    let serving = thread::spawn(move || server.serve().unwrap());
+    Running {
+        addr,
+        cancellation,
+        serving: Some(serving),
+    }
+}
+
+/// Sends `raw` and returns the status and JSON body of the response.
+fn send(addr: SocketAddr, raw: &[u8]) -> (u16, Json) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(raw).unwrap();
+    read_response(stream)
+}
+
+fn read_response(mut stream: TcpStream) -> (u16, Json) {
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    (status, Json::parse(body).unwrap())
+}
+
+fn post(addr: SocketAddr, path: &str, body: &str) -> (u16, Json) {
+    let raw = format!(
+        "POST {} HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        body.len(),
+        body
+    );
+    send(addr, raw.as_bytes())
+}
+
+fn error_of(body: &Json) -> &str {
+    body.get("error").and_then(Json::as_str).unwrap()
+}
+
+#[test]
+fn health_reports_workers() {
+    let server = start(ServerConfig::default());
+    let (status, body) = send(server.addr, b"GET /health HTTP/1.1\r\n\r\n");
+    assert_eq!(status, 200);
+    assert_eq!(body.get("status").and_then(Json::as_str), Some("ok"));
+    assert_eq!(body.get("workers").and_then(Json::as_usize), Some(1));
+}
+
+#[test]
+fn encode_answers_one_row_per_token() {
+    let server = start(ServerConfig::default());
+    let (status, body) = post(server.addr, "/encode", r#"{"tokens": [3, 4, 5]}"#);
+    assert_eq!(status, 200);
+    let rows = body.get("encoding").and_then(Json::as_array).unwrap();
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|row| row.as_array().unwrap().len() == 8));
+
+    let (status, body) = post(server.addr, "/encode", r#"{"inputs": [[3], [4, 5]]}"#);
+    assert_eq!(status, 200);
+    let encodings = body.get("encodings").and_then(Json::as_array).unwrap();
+    let lengths: Vec<usize> = encodings
+        .iter()
+        .map(|e| e.as_array().unwrap().len())
+        .collect();
+    assert_eq!(lengths, [1, 2]);
+}
+
+#[test]
+fn generate_matches_the_model() {
+    let server = start(ServerConfig::default());
+    let (status, body) = post(
+        server.addr,
+        "/generate",
+        r#"{"tokens": [3, 4, 5], "max_new_tokens": 4}"#,
+    );
+    assert_eq!(status, 200);
+    let model = Transformer::with_seed(common::tiny_config(), 5).unwrap();
+    let generation = rust_transformer::generation::GenerationConfig::new().with_max_new_tokens(4);
+    let expected = model.generate_output(&[3, 4, 5], &generation).unwrap();
+    let tokens: Vec<usize> = body
+        .get("tokens")
+        .and_then(Json::as_array)
+        .unwrap()
+        .iter()
+        .map(|t| t.as_usize().unwrap())
+        .collect();
+    assert_eq!(tokens, expected.tokens);
+    assert_eq!(
+        body.get("new_tokens").and_then(Json::as_usize),
+        Some(expected.new_tokens)
+    );
+
+    let (status, body) = post(server.addr, "/generate", r#"{"inputs": [[3], [4]]}"#);
+    assert_eq!(status, 200);
+    assert_eq!(
+        body.get("outputs").and_then(Json::as_array).unwrap().len(),
+        2
+    );
+}
+
+#[test]
+fn generate_truncates_long_sources() {
+    let server = start(ServerConfig::default());
+    let src: Vec<usize> = (0..20).map(|i| 3 + i % 12).collect();
+    let body = format!(r#"{{"tokens": {:?}, "max_new_tokens": 3}}"#, src);
+    let (status, body) = post(server.addr, "/generate", &body);
+    assert_eq!(status, 200, "{:?}", body);
+
+    let model = Transformer::with_seed(common::tiny_config(), 5).unwrap();
+    let generation = rust_transformer::generation::GenerationConfig::new().with_max_new_tokens(3);
+    let expected = model.generate_output(&src[4..], &generation).unwrap();
+    let tokens: Vec<usize> = body
+        .get("tokens")
+        .and_then(Json::as_array)
+        .unwrap()
+        .iter()
+        .map(|t| t.as_usize().unwrap())
+        .collect();
+    assert_eq!(tokens, expected.tokens);
+}
+
+#[test]
+fn memory_reports_weights_and_cache_sizes() {
+    let server = start(ServerConfig::default());
+    let (status, body) = send(server.addr, b"GET /memory HTTP/1.1\r\n\r\n");
+    assert_eq!(status, 200);
+    let model = Transformer::with_seed(common::tiny_config(), 5).unwrap();
+    let field = |name: &str| body.get(name).and_then(Json::as_usize).unwrap();
+    assert_eq!(field("parameters"), model.num_parameters());
+    assert_eq!(field("parameter_bytes"), model.num_parameters() * 8);
+    // One decoder layer caching a key and a value row of d_model = 8.
+    assert_eq!(field("kv_cache_bytes_per_token"), 2 * 8 * 8);
+    assert_eq!(field("cross_attention_bytes_per_source_token"), 2 * 8 * 8);
+    assert_eq!(send(server.addr, b"POST /memory HTTP/1.1\r\n\r\n").0, 405);
+}
+
+#[test]
+fn batches_larger_than_the_queue_get_503() {
+    let server = start_with(engine(2), ServerConfig::default());
+    let (status, body) = post(server.addr, "/encode", r#"{"inputs": [[3], [4], [5]]}"#);
+    assert_eq!(status, 503);
+    assert!(error_of(&body).contains("no room for 3 jobs"), "{:?}", body);
+    let (status, _) = post(server.addr, "/encode", r#"{"inputs": [[3], [4]]}"#);
+    assert_eq!(status, 200);
+}
+
+#[test]
+fn client_errors_are_400() {
+    let server = start(ServerConfig::default());
+    for (path, body, message) in [
+        ("/encode", "{not json", ""),
+        ("/encode", "[1, 2]", "JSON object"),
+        ("/encode", r#"{"src": [1]}"#, "tokens or inputs"),
+        ("/encode", r#"{"tokens": [1, -2]}"#, "non-negative"),
+        ("/encode", r#"{"tokens": [1, 16]}"#, "out of range"),
+        (
+            "/encode",
+            r#"{"tokens": [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]}"#,
+            "max_seq_len",
+        ),
+        (
+            "/generate",
+            r#"{"tokens": [1], "temperature": "hot"}"#,
+            "temperature",
+        ),
+    ] {
+        let (status, response) = post(server.addr, path, body);
+        assert_eq!(status, 400, "{}", body);
+        assert!(error_of(&response).contains(message), "{:?}", response);
+    }
+}
+
+#[test]
+fn unknown_routes_and_methods_are_rejected() {
+    let server = start(ServerConfig::default());
+    assert_eq!(send(server.addr, b"GET /nope HTTP/1.1\r\n\r\n").0, 404);
+    assert_eq!(send(server.addr, b"GET /encode HTTP/1.1\r\n\r\n").0, 405);
+    assert_eq!(send(server.addr, b"garbage\r\n\r\n").0, 400);
+}
+
+#[test]
+fn oversized_requests_are_rejected() {
+    let server = start(ServerConfig {
+        max_body_bytes: 16,
+        max_line_bytes: 64,
+        max_headers: 2,
+        ..ServerConfig::default()
+    });
+    let (status, _) = post(server.addr, "/encode", r#"{"tokens": [1, 2, 3, 4]}"#);
+    assert_eq!(status, 413);
+
+    let long_path = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(100));
+    assert_eq!(send(server.addr, long_path.as_bytes()).0, 414);
+
+    let long_header = format!("GET /health HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(100));
+    assert_eq!(send(server.addr, long_header.as_bytes()).0, 431);
+
+    let (status, body) = send(
+        server.addr,
+        b"GET /health HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n",
+    );
+    assert_eq!(status, 431);
+    assert_eq!(error_of(&body), "too many headers");
+}
+
+#[test]
+fn connections_beyond_the_limit_get_503() {
+    let server = start(ServerConfig {
+        max_connections: 1,
+        ..ServerConfig::default()
+    });
+    // Holds the only slot without sending a request.
+    let idle = TcpStream::connect(server.addr).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let (status, body) = read_response(TcpStream::connect(server.addr).unwrap());
+    assert_eq!(status, 503);
+    assert_eq!(error_of(&body), "too many open connections");
+
+    drop(idle);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(send(server.addr, b"GET /health HTTP/1.1\r\n\r\n").0, 200);
+}
+
+#[test]
+fn try_submit_fails_when_the_queue_is_full() {
+    let model = Transformer::with_seed(common::tiny_config(), 5).unwrap();
+    let engine = InferenceEngine::new(
+        model,
+        EngineConfig {
+            num_workers: 1,
+            queue_capacity: 1,
+        },
+    )
+    .unwrap();
+    let (started, wait_started) = mpsc::channel();
+    let (release, wait_release) = mpsc::channel::<()>();
+    let busy = engine
+        .submit(move |_| {
+            started.send(()).unwrap();
+            wait_release.recv().unwrap();
+            Ok(())
+        })
+        .unwrap();
+    wait_started.recv().unwrap();
+    let queued = engine.try_submit(|_| Ok(())).unwrap();
+
+    match engine.try_submit(|_| Ok(())) {
+        Err(TransformerError::Unavailable(message)) => {
+            assert_eq!(message, "inference queue is full")
+        }
+        other => panic!("expected Unavailable, got {:?}", other.map(|_| ())),
+    }
+    release.send(()).unwrap();
+    busy.wait().unwrap();
+    queued.wait().unwrap();
+}
+
+#[test]
+fn try_submit_all_queues_nothing_without_room_for_every_job() {
+    let engine = engine(2);
+    let (started, wait_started) = mpsc::channel();
+    let (release, wait_release) = mpsc::channel::<()>();
+    let busy = engine
+        .submit(move |_| {
+            started.send(()).unwrap();
+            wait_release.recv().unwrap();
+            Ok(())
+        })
+        .unwrap();
+    wait_started.recv().unwrap();
+    let queued = engine.try_submit(|_| Ok(())).unwrap();
+
+    let ran = Arc::new(AtomicUsize::new(0));
+    let batch = |n: usize| {
+        let ran = Arc::clone(&ran);
+        (0..n).map(move |_| {
+            let ran = Arc::clone(&ran);
+            move |_: &Transformer| {
+                ran.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        })
+    };
+    match engine.try_submit_all(batch(2)) {
+        Err(TransformerError::Unavailable(message)) => {
+            assert_eq!(message, "inference queue has no room for 2 jobs")
+        }
+        other => panic!("expected Unavailable, got {:?}", other.map(|_| ())),
+    }
+    let handles = engine.try_submit_all(batch(1)).unwrap();
+
+    release.send(()).unwrap();
+    busy.wait().unwrap();
+    queued.wait().unwrap();
+    for handle in handles {
+        handle.wait().unwrap();
+    }
+    assert_eq!(ran.load(Ordering::SeqCst), 1);
+}
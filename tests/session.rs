@@ -0,0 +1,51 @@
+use rust_transformer::config::PositionalScheme;
+use rust_transformer::generation::{InferenceSession, SamplingConfig};
+use rust_transformer::utils::rng::Rng;
+use rust_transformer::{Transformer, TransformerConfig};
+
+fn model(positional: PositionalScheme) -> Transformer {
+    let config = TransformerConfig {
+        vocab_size: 12,
+        d_model: 8,
+        num_heads: 2,
+        d_ff: 16,
+        max_seq_len: 4,
+        positional,
+        ..TransformerConfig::default()
+    };
+    let mut model = Transformer::with_seed(config, 5).unwrap();
+    model.set_training(false);
+    model
+}
+
+#[test]
+fn supplied_rng_leaves_the_model_stream_untouched() {
+    let model = model(PositionalScheme::Sinusoidal);
+    let before = model.rng.with(|rng| rng.clone()).next_u64();
+
+    let sampling = SamplingConfig::default();
+    let mut session = InferenceSession::new(&model, &[3, 4])
+        .unwrap()
+        .with_rng(Rng::seed_from_u64(1));
+    session.sample(&sampling).unwrap();
+    assert_eq!(model.rng.with(|rng| rng.next_u64()), before);
+}
+
+#[test]
+fn sequence_limit_follows_the_position_table() {
+    let sinusoidal = model(PositionalScheme::Sinusoidal);
+    let mut session = sinusoidal.start_session(&[3, 4]).unwrap();
+    for token in [5, 6, 7] {
+        session.push(token).unwrap();
+    }
+    assert!(session.is_finished());
+    assert!(session.push(8).is_err());
+
+    let alibi = model(PositionalScheme::Alibi);
+    let mut session = alibi.start_session(&[3, 4]).unwrap();
+    for token in [5, 6, 7, 8, 9] {
+        session.push(token).unwrap();
+    }
+    assert!(!session.is_finished());
+    assert_eq!(session.next_logits().unwrap().len(), 12);
+}
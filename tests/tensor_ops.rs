@@ -0,0 +1,133 @@
+use rust_transformer::utils::tensor_ops::{
+    argmax_row, argmax_rows, log_softmax, log_softmax_rows, log_sum_exp, masked_softmax,
+    masked_softmax_rows, softmax, top_k, FullyMaskedRow,
+};
+use rust_transformer::Matrix;
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+}
+
+#[test]
+fn log_softmax_matches_log_of_softmax() {
+    let values = [0.5, -1.25, 3.0, 0.0];
+    for (l, p) in log_softmax(&values).iter().zip(softmax(&values)) {
+        assert_close(*l, p.ln());
+    }
+}
+
+#[test]
+fn log_softmax_is_stable_for_large_logits() {
+    let values = [1000.0, 0.0, -1000.0];
+    let log_probs = log_softmax(&values);
+    assert_close(log_probs[0], 0.0);
+    assert_close(log_probs[1], -1000.0);
+    assert_close(log_probs[2], -2000.0);
+    assert!(log_probs.iter().all(|l| l.is_finite()));
+}
+
+#[test]
+fn log_softmax_is_shift_invariant() {
+    let values = [0.1, 0.2, 0.7];
+    let shifted: Vec<f64> = values.iter().map(|v| v + 500.0).collect();
+    for (a, b) in log_softmax(&values).iter().zip(log_softmax(&shifted)) {
+        assert_close(*a, b);
+    }
+}
+
+#[test]
+fn log_sum_exp_edge_cases() {
+    assert_eq!(log_sum_exp(&[]), f64::NEG_INFINITY);
+    assert_eq!(log_sum_exp(&[f64::NEG_INFINITY; 3]), f64::NEG_INFINITY);
+    assert_close(log_sum_exp(&[f64::NEG_INFINITY, 2.0]), 2.0);
+    assert_close(log_sum_exp(&[0.0, 0.0]), 2f64.ln());
+}
+
+#[test]
+fn log_softmax_rows_normalizes_each_row() {
+    let x = Matrix::from_rows(&[vec![1.0, 2.0, 3.0], vec![-5.0, 0.0, 5.0]]).unwrap();
+    let out = log_softmax_rows(&x);
+    for row in out.row_iter() {
+        assert_close(row.iter().map(|l| l.exp()).sum::<f64>(), 1.0);
+    }
+}
+
+#[test]
+fn argmax_prefers_first_maximum_and_skips_nan() {
+    assert_eq!(argmax_row(&[1.0, 3.0, 3.0, 2.0]), Some(1));
+    assert_eq!(argmax_row(&[f64::NAN, -1.0, -2.0]), Some(1));
+    assert_eq!(argmax_row(&[f64::NAN]), None);
+    assert_eq!(argmax_row(&[]), None);
+    assert_eq!(argmax_row(&[f64::NEG_INFINITY, f64::NEG_INFINITY]), Some(0));
+}
+
+#[test]
+fn argmax_rows_covers_every_row() {
+    let x = Matrix::from_rows(&[vec![0.0, 1.0], vec![2.0, 1.0], vec![f64::NAN, f64::NAN]]).unwrap();
+    assert_eq!(argmax_rows(&x), vec![1, 0, 0]);
+}
+
+#[test]
+fn top_k_returns_largest_in_order() {
+    let values = [0.1, 0.9, 0.3, 0.9, -1.0, 0.5];
+    assert_eq!(top_k(&values, 3), vec![(1, 0.9), (3, 0.9), (5, 0.5)]);
+    assert_eq!(top_k(&values, 0), vec![]);
+    assert_eq!(top_k(&values, 100).len(), values.len());
+    assert_eq!(top_k(&values, 100).last(), Some(&(4, -1.0)));
+}
+
+#[test]
+fn top_k_ignores_nan() {
+    assert_eq!(
+        top_k(&[f64::NAN, 1.0, f64::NAN, 2.0], 3),
+        vec![(3, 2.0), (1, 1.0)]
+    );
+}
+
+#[test]
+fn top_k_agrees_with_full_sort() {
+    let values: Vec<f64> = (0..200).map(|i| ((i * 7919) % 211) as f64 / 13.0).collect();
+    let mut sorted: Vec<(usize, f64)> = values.iter().copied().enumerate().collect();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    for k in [1, 5, 17, 199, 200] {
+        assert_eq!(top_k(&values, k), sorted[..k].to_vec());
+    }
+}
+
+#[test]
+fn masked_softmax_excludes_masked_entries() {
+    let probs = masked_softmax(
+        &[1.0, 5.0, 1.0],
+        &[true, false, true],
+        FullyMaskedRow::Zeros,
+    );
+    assert_eq!(probs, vec![0.5, 0.0, 0.5]);
+}
+
+#[test]
+fn masked_softmax_fills_fully_masked_rows() {
+    let values = [1.0, 2.0];
+    assert_eq!(
+        masked_softmax(&values, &[false, false], FullyMaskedRow::Zeros),
+        vec![0.0, 0.0]
+    );
+    assert_eq!(
+        masked_softmax(&values, &[false, false], FullyMaskedRow::Uniform),
+        vec![0.5, 0.5]
+    );
+    let neg_inf = [f64::NEG_INFINITY; 2];
+    assert_eq!(
+        masked_softmax(&neg_inf, &[], FullyMaskedRow::Zeros),
+        vec![0.0, 0.0]
+    );
+}
+
+#[test]
+fn masked_softmax_rows_checks_mask_shape() {
+    let scores = Matrix::from_rows(&[vec![0.0, 0.0]]).unwrap();
+    let mask = Matrix::from_fn(1, 3, |_, _| true);
+    assert!(masked_softmax_rows(&scores, Some(&mask), FullyMaskedRow::Zeros).is_err());
+    let mask = Matrix::from_fn(1, 2, |_, j| j == 1);
+    let probs = masked_softmax_rows(&scores, Some(&mask), FullyMaskedRow::Zeros).unwrap();
+    assert_eq!(probs.row(0), &[0.0, 1.0]);
+}
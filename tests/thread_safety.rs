@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::thread;
+
+use rust_transformer::engine::InferenceEngine;
+use rust_transformer::generation::InferenceSession;
+use rust_transformer::models::{Decoder, Encoder, EncoderOnlyTransformer};
+use rust_transformer::{Transformer, TransformerConfig};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn small_model() -> Transformer {
+    let config = TransformerConfig {
+        vocab_size: 40,
+        d_model: 16,
+        num_heads: 2,
+        d_ff: 32,
+        max_seq_len: 32,
+        ..TransformerConfig::default()
+    };
+    let mut model = Transformer::with_seed(config, 11).unwrap();
+    model.set_training(false);
+    model
+}
+
+#[test]
+fn models_are_send_and_sync() {
+    assert_send_sync::<Transformer>();
+    assert_send_sync::<EncoderOnlyTransformer>();
+    assert_send_sync::<Encoder>();
+    assert_send_sync::<Decoder>();
+    assert_send_sync::<InferenceSession<'static>>();
+    assert_send_sync::<InferenceEngine>();
+}
+
+#[test]
+fn shared_model_serves_concurrent_forwards() {
+    let model = Arc::new(small_model());
+    let inputs: Vec<Vec<usize>> = (0..8).map(|i| vec![3 + i, 7, 9 + i, 4]).collect();
+    let expected: Vec<Vec<f64>> = inputs
+        .iter()
+        .map(|src| model.forward(src, &[1, 5, 6]).unwrap().as_slice().to_vec())
+        .collect();
+
+    let handles: Vec<_> = inputs
+        .into_iter()
+        .map(|src| {
+            let model = Arc::clone(&model);
+            thread::spawn(move || model.forward(&src, &[1, 5, 6]).unwrap().as_slice().to_vec())
+        })
+        .collect();
+    for (handle, expected) in handles.into_iter().zip(expected) {
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+}
+
+#[test]
+fn sessions_decode_independently_on_one_model() {
+    let model = Arc::new(small_model());
+    let sources: Vec<Vec<usize>> = (0..4).map(|i| vec![5 + i, 6, 7]).collect();
+    let expected: Vec<Vec<usize>> = sources
+        .iter()
+        .map(|src| model.generate_greedy(src, 8).unwrap())
+        .collect();
+
+    let handles: Vec<_> = sources
+        .into_iter()
+        .map(|src| {
+            let model = Arc::clone(&model);
+            thread::spawn(move || {
+                let mut session = model.start_session(&src).unwrap();
+                while !session.is_finished() && session.tokens().len() < 8 {
+                    session.greedy().unwrap();
+                }
+                session.tokens().to_vec()
+            })
+        })
+        .collect();
+    for (handle, expected) in handles.into_iter().zip(expected) {
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+}
@@ -0,0 +1,136 @@
+use rust_transformer::huggingface::HfTokenizer;
+use rust_transformer::tokenizer::Tokenizer;
+use rust_transformer::utils::json::Json;
+
+fn load(json: &str) -> HfTokenizer {
+    HfTokenizer::from_json(&Json::parse(json).unwrap()).unwrap()
+}
+
+/// A BERT-style WordPiece tokenizer.
+const WORDPIECE: &str = r###"{
+  "added_tokens": [
+    {"id": 0, "content": "[PAD]", "special": true},
+    {"id": 1, "content": "[UNK]", "special": true},
+    {"id": 2, "content": "[CLS]", "special": true},
+    {"id": 3, "content": "[SEP]", "special": true}
+  ],
+  "normalizer": {"type": "BertNormalizer", "clean_text": true,
+    "handle_chinese_chars": true, "strip_accents": null, "lowercase": true},
+  "pre_tokenizer": {"type": "BertPreTokenizer"},
+  "post_processor": {"type": "TemplateProcessing",
+    "single": [{"SpecialToken": {"id": "[CLS]", "type_id": 0}},
+               {"Sequence": {"id": "A", "type_id": 0}},
+               {"SpecialToken": {"id": "[SEP]", "type_id": 0}}],
+    "pair": [{"SpecialToken": {"id": "[CLS]", "type_id": 0}},
+             {"Sequence": {"id": "A", "type_id": 0}},
+             {"SpecialToken": {"id": "[SEP]", "type_id": 0}},
+             {"Sequence": {"id": "B", "type_id": 1}},
+             {"SpecialToken": {"id": "[SEP]", "type_id": 1}}],
+    "special_tokens": {
+      "[CLS]": {"id": "[CLS]", "ids": [2], "tokens": ["[CLS]"]},
+      "[SEP]": {"id": "[SEP]", "ids": [3], "tokens": ["[SEP]"]}
+    }},
+  "decoder": {"type": "WordPiece", "prefix": "##", "cleanup": true},
+  "model": {"type": "WordPiece", "unk_token": "[UNK]",
+    "continuing_subword_prefix": "##", "max_input_chars_per_word": 100,
+    "vocab": {"[PAD]": 0, "[UNK]": 1, "[CLS]": 2, "[SEP]": 3, "the": 4,
+      "cat": 5, "sat": 6, "un": 7, "##aff": 8, "##able": 9, "!": 10, ",": 11,
+      "cafe": 12}}
+}"###;
+
+/// A GPT-2-style byte-level BPE tokenizer.
+const BPE: &str = r###"{
+  "added_tokens": [
+    {"id": 17, "content": "<|endoftext|>", "special": true}
+  ],
+  "normalizer": null,
+  "pre_tokenizer": {"type": "ByteLevel", "add_prefix_space": false,
+    "trim_offsets": true, "use_regex": true},
+  "post_processor": {"type": "ByteLevel", "add_prefix_space": true,
+    "trim_offsets": false, "use_regex": true},
+  "decoder": {"type": "ByteLevel", "add_prefix_space": true,
+    "trim_offsets": true, "use_regex": true},
+  "model": {"type": "BPE", "dropout": null, "unk_token": null,
+    "continuing_subword_prefix": "", "end_of_word_suffix": "",
+    "fuse_unk": false, "byte_fallback": false,
+    "vocab": {"h": 0, "e": 1, "l": 2, "o": 3, "w": 4, "r": 5, "d": 6,
+      "Ġ": 7, "he": 8, "ll": 9, "hell": 10, "hello": 11, "Ġw": 12, "or": 13,
+      "Ġwor": 14, "ld": 15, "Ġworld": 16, "<|endoftext|>": 17},
+    "merges": ["h e", "l l", "he ll", "hell o", "Ġ w", "o r", "Ġw or", "l d",
+      "Ġwor ld"]}
+}"###;
+
+/// A SentencePiece-style Unigram tokenizer.
+const UNIGRAM: &str = r###"{
+  "added_tokens": [
+    {"id": 1, "content": "</s>", "special": true}
+  ],
+  "normalizer": null,
+  "pre_tokenizer": {"type": "Metaspace", "replacement": "▁",
+    "prepend_scheme": "always", "split": true},
+  "post_processor": {"type": "TemplateProcessing",
+    "single": [{"Sequence": {"id": "A", "type_id": 0}},
+               {"SpecialToken": {"id": "</s>", "type_id": 0}}],
+    "pair": [{"Sequence": {"id": "A", "type_id": 0}},
+             {"SpecialToken": {"id": "</s>", "type_id": 0}},
+             {"Sequence": {"id": "B", "type_id": 0}},
+             {"SpecialToken": {"id": "</s>", "type_id": 0}}],
+    "special_tokens": {"</s>": {"id": "</s>", "ids": [1], "tokens": ["</s>"]}}},
+  "decoder": {"type": "Metaspace", "replacement": "▁",
+    "prepend_scheme": "always", "split": true},
+  "model": {"type": "Unigram", "unk_id": 0, "byte_fallback": false,
+    "vocab": [["<unk>", 0.0], ["</s>", 0.0], ["▁", -2.0], ["▁the", -2.5],
+      ["▁cat", -3.0], ["▁c", -4.0], ["at", -3.5], ["s", -3.0],
+      ["▁cats", -8.0], ["t", -4.0], ["h", -4.5], ["e", -4.0], ["a", -4.0],
+      ["c", -4.5], ["<0x64>", 0.0], ["<0x6F>", 0.0], ["<0x67>", 0.0]]}
+}"###;
+
+#[test]
+fn wordpiece_fixture() {
+    let tokenizer = load(WORDPIECE);
+    let ids = tokenizer.encode("The cat sat, unaffable!").unwrap();
+    assert_eq!(ids, [2, 4, 5, 6, 11, 7, 8, 9, 10, 3]);
+    assert_eq!(tokenizer.decode(&ids).unwrap(), "the cat sat, unaffable!");
+    // Accents are stripped and unknown words map to [UNK].
+    assert_eq!(tokenizer.encode("Café dog").unwrap(), [2, 12, 1, 3]);
+    assert_eq!(
+        tokenizer.encode_pair("the cat", "sat").unwrap(),
+        (vec![2, 4, 5, 3, 6, 3], vec![0, 0, 0, 0, 1, 1])
+    );
+}
+
+#[test]
+fn byte_level_bpe_fixture() {
+    let tokenizer = load(BPE);
+    let ids = tokenizer.encode("hello world").unwrap();
+    assert_eq!(ids, [11, 16]);
+    assert_eq!(tokenizer.decode(&ids).unwrap(), "hello world");
+    // Merges apply by rank, not by length.
+    assert_eq!(tokenizer.encode("hold").unwrap(), [0, 3, 15]);
+    assert_eq!(tokenizer.encode("hello<|endoftext|>").unwrap(), [11, 17]);
+    assert_eq!(tokenizer.decode(&[11, 17]).unwrap(), "hello");
+}
+
+#[test]
+fn unigram_fixture() {
+    let tokenizer = load(UNIGRAM);
+    // "▁cat" + "s" (-6.0) beats "▁cats" (-8.0).
+    let ids = tokenizer.encode("the cats").unwrap();
+    assert_eq!(ids, [3, 4, 7, 1]);
+    assert_eq!(tokenizer.decode(&ids).unwrap(), "the cats");
+    // Uncovered characters fuse into one <unk>.
+    assert_eq!(tokenizer.encode("the dog").unwrap(), [3, 2, 0, 1]);
+
+    let fallback = load(&UNIGRAM.replace(r#""byte_fallback": false"#, r#""byte_fallback": true"#));
+    assert_eq!(fallback.encode("the dog").unwrap(), [3, 2, 14, 15, 16, 1]);
+}
+
+#[test]
+fn unsupported_components_are_rejected() {
+    let json = UNIGRAM.replace(
+        r#""normalizer": null"#,
+        r#""normalizer": {"type": "Precompiled"}"#,
+    );
+    let err = HfTokenizer::from_json(&Json::parse(&json).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("Precompiled"), "{}", err);
+}
@@ -0,0 +1,213 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use rust_transformer::optim::{Sgd, SgdConfig};
+use rust_transformer::params::Gradients;
+use rust_transformer::training::{
+    layer_group, seq2seq_loss, Competence, CrossEntropyConfig, DifficultyCurriculum, Pacing,
+    StepStats, Trainer, TrainerCallback, TrainerConfig,
+};
+use rust_transformer::{Parameters, Transformer};
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<StepStats>>>);
+
+impl TrainerCallback for Recorder {
+    fn on_step(&mut self, stats: &StepStats) {
+        self.0.lock().unwrap().push(stats.clone());
+    }
+}
+
+fn pairs() -> Vec<(Vec<usize>, Vec<usize>)> {
+    vec![
+        (vec![4, 5, 6], vec![6, 5, 2]),
+        (vec![7, 8], vec![8, 7, 2]),
+        (vec![9, 10, 11], vec![11, 2]),
+    ]
+}
+
+fn squared_norm(m: &rust_transformer::Matrix) -> f64 {
+    m.as_slice().iter().map(|v| v * v).sum()
+}
+
+#[test]
+fn gradient_flow_matches_the_step_gradients() {
+    let mut model = Transformer::with_seed(common::tiny_config(), 2).unwrap();
+    model.set_training(false);
+    let initial = model.clone();
+    let lr = 0.1;
+    let recorder = Recorder::default();
+    let config = TrainerConfig::new()
+        .with_batch_size(3)
+        .with_shuffle(false, 0)
+        .with_gradient_flow(true);
+    let mut trainer = Trainer::new(
+        model,
+        Sgd::new(SgdConfig {
+            lr,
+            ..SgdConfig::default()
+        }),
+        config,
+    )
+    .with_callback(recorder.clone());
+
+    let loss = CrossEntropyConfig::default();
+    let mut step_grads: Vec<Gradients> = Vec::new();
+    trainer
+        .train_epoch(&pairs(), 0, |m, batch| {
+            let (value, grads) = seq2seq_loss(m, batch, &loss)?;
+            step_grads.push(grads.clone());
+            Ok((value, grads))
+        })
+        .unwrap();
+
+    let steps = recorder.0.lock().unwrap();
+    assert_eq!(steps.len(), 1);
+    let flow = steps[0].gradient_flow.as_ref().unwrap();
+    let grads = &step_grads[0];
+
+    // Expected per-layer sums of squares, in the order parameters are
+    // visited.
+    let mut order = Vec::new();
+    let mut expected: BTreeMap<String, (f64, f64, usize)> = BTreeMap::new();
+    initial.visit_parameters("", &mut |name, weight| {
+        let Some(grad) = grads.get(name) else { return };
+        let group = layer_group(name).to_string();
+        if !expected.contains_key(&group) {
+            order.push(group.clone());
+        }
+        let entry = expected.entry(group).or_default();
+        entry.0 += squared_norm(grad);
+        entry.1 += squared_norm(weight);
+        entry.2 += weight.len();
+    });
+    let names: Vec<&str> = flow.layers.iter().map(|l| l.layer.as_str()).collect();
+    assert_eq!(names, order);
+    assert!(names.contains(&"encoder.layers.0"));
+    assert!(names.contains(&"decoder.layers.0"));
+
+    for layer in &flow.layers {
+        let (grad_sq, weight_sq, count) = expected[&layer.layer];
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-12 * b.abs().max(1.0);
+        assert!(close(layer.grad_norm, grad_sq.sqrt()), "{}", layer.layer);
+        assert!(
+            close(layer.weight_norm, weight_sq.sqrt()),
+            "{}",
+            layer.layer
+        );
+        assert_eq!(layer.num_parameters, count);
+        // Plain SGD moves every weight by lr times its gradient.
+        let update = layer.update_norm.unwrap();
+        assert!(close(update, lr * layer.grad_norm), "{}", layer.layer);
+    }
+    assert!((flow.total_grad_norm() - steps[0].grad_norm).abs() < 1e-12);
+    assert!((flow.total_grad_norm() - grads.global_norm()).abs() < 1e-12);
+}
+
+#[test]
+fn gradient_flow_is_off_by_default() {
+    let model = Transformer::with_seed(common::tiny_config(), 2).unwrap();
+    let recorder = Recorder::default();
+    let mut trainer = Trainer::new(
+        model,
+        Sgd::new(SgdConfig::default()),
+        TrainerConfig::new().with_batch_size(2),
+    )
+    .with_callback(recorder.clone());
+    let loss = CrossEntropyConfig::default();
+    trainer
+        .train_epoch(&pairs(), 0, |m, batch| seq2seq_loss(m, batch, &loss))
+        .unwrap();
+    let steps = recorder.0.lock().unwrap();
+    assert_eq!(steps.len(), 2);
+    assert!(steps.iter().all(|s| s.gradient_flow.is_none()));
+}
+
+#[test]
+fn padded_targets_score_like_unpadded_ones() {
+    let mut model = Transformer::with_seed(common::tiny_config(), 4).unwrap();
+    model.set_training(false);
+    let pad = model.config.pad_token_id;
+    let loss = CrossEntropyConfig::for_model(&model.config);
+    let padded: Vec<_> = pairs()
+        .into_iter()
+        .map(|(src, mut tgt)| {
+            tgt.resize(6, pad);
+            (src, tgt)
+        })
+        .collect();
+
+    let (expected, expected_grads) = seq2seq_loss(&model, &pairs(), &loss).unwrap();
+    let (actual, actual_grads) = seq2seq_loss(&model, &padded, &loss).unwrap();
+    assert!(
+        (actual - expected).abs() < 1e-12,
+        "{} vs {}",
+        actual,
+        expected
+    );
+    for (name, grad) in expected_grads.iter() {
+        let other = actual_grads.get(name).unwrap();
+        for (a, b) in grad.as_slice().iter().zip(other.as_slice()) {
+            assert!((a - b).abs() < 1e-12, "{}: {} vs {}", name, a, b);
+        }
+    }
+
+    // Without an ignore index the padding is scored as targets.
+    let (counted, _) = seq2seq_loss(&model, &padded, &CrossEntropyConfig::new()).unwrap();
+    assert!((counted - expected).abs() > 1e-6);
+}
+
+#[test]
+fn curriculum_widens_the_batch_pool_with_the_schedule() {
+    // Source length is the difficulty: example i has i + 1 source tokens.
+    let examples: Vec<(Vec<usize>, Vec<usize>)> = (0..8)
+        .map(|i| ((0..=i).map(|t| 4 + t).collect(), vec![5, 2]))
+        .collect();
+    let competence = Competence::new(0.25, 8, Pacing::Linear).unwrap();
+    let curriculum = DifficultyCurriculum::new(&examples, |(src, _)| src.len() as f64, competence);
+    for (step, unlocked) in [(0, 2), (2, 4), (4, 5), (6, 7), (8, 8)] {
+        assert_eq!(curriculum.available(step).len(), unlocked, "step {}", step);
+    }
+
+    let mut model = Transformer::with_seed(common::tiny_config(), 1).unwrap();
+    model.set_training(false);
+    let config = TrainerConfig::new()
+        .with_batch_size(2)
+        .with_curriculum(curriculum.clone());
+    let optimizer = Sgd::new(SgdConfig {
+        lr: 0.01,
+        ..SgdConfig::default()
+    });
+    let mut trainer = Trainer::new(model, optimizer, config);
+    let loss = CrossEntropyConfig::for_model(&trainer.model.config);
+
+    let mut drawn: Vec<Vec<usize>> = Vec::new();
+    for epoch in 0..3 {
+        let stats = trainer
+            .train_epoch(&examples, epoch, |model, batch| {
+                drawn.push(batch.iter().map(|(src, _)| src.len()).collect());
+                seq2seq_loss(model, batch, &loss)
+            })
+            .unwrap();
+        assert_eq!(stats.steps, 4);
+    }
+
+    // Step `s` only sees the `available(s)` shortest sources.
+    for (step, lengths) in drawn.iter().enumerate() {
+        assert_eq!(lengths.len(), 2);
+        let threshold = curriculum.available(step).len();
+        assert!(
+            lengths.iter().all(|&len| len <= threshold),
+            "step {} drew {:?} past length {}",
+            step,
+            lengths,
+            threshold
+        );
+    }
+    let first_epoch_max = drawn[..4].iter().flatten().max().unwrap();
+    assert!(*first_epoch_max <= 5);
+    let last_epoch_max = drawn[8..].iter().flatten().max().unwrap();
+    assert!(*last_epoch_max > 5, "{:?}", drawn);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use rust_transformer::wasm::WasmTransformer;
+use rust_transformer::Transformer;
+
+#[test]
+fn exports_match_the_wrapped_model() {
+    let mut model = Transformer::with_seed(common::tiny_config(), 5).unwrap();
+    model.set_training(false);
+    let facade = WasmTransformer::from_checkpoint(&model.to_bytes()).unwrap();
+    assert_eq!(facade.d_model(), 8);
+    assert_eq!(facade.vocab_size(), 16);
+
+    let src = [4u32, 9, 2];
+    let encoded = facade.encode(&src).unwrap();
+    let expected = model.encode(&[4, 9, 2]).unwrap();
+    assert_eq!(encoded.len(), 3 * 8);
+    for (a, e) in encoded.iter().zip(expected.as_slice()) {
+        assert_eq!(*a, *e as f32);
+    }
+
+    let greedy = facade.generate_greedy(&src, 6).unwrap();
+    let expected: Vec<u32> = model
+        .generate_greedy(&[4, 9, 2], 6)
+        .unwrap()
+        .into_iter()
+        .map(|id| id as u32)
+        .collect();
+    assert_eq!(greedy, expected);
+}
+
+#[test]
+fn seeded_sampling_repeats() {
+    let config = common::tiny_config().to_json().to_string();
+    let mut facade = WasmTransformer::from_config_json(&config, 3).unwrap();
+    assert_eq!(facade.config_json(), config);
+
+    facade.set_seed(11);
+    let first = facade.generate(&[5, 6], 8).unwrap();
+    facade.set_seed(11);
+    assert_eq!(facade.generate(&[5, 6], 8).unwrap(), first);
+}
+
+#[test]
+fn errors_cross_as_messages() {
+    let facade =
+        WasmTransformer::from_config_json(&common::tiny_config().to_json().to_string(), 3).unwrap();
+    let err = facade.encode(&[99]).unwrap_err();
+    assert!(err.contains("99"), "{}", err);
+    assert!(WasmTransformer::from_checkpoint(b"not a model").is_err());
+    assert!(WasmTransformer::from_config_json("{", 0).is_err());
+}